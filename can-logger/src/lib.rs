@@ -0,0 +1,293 @@
+//! Logging of raw CAN frames to candump compatible log files, with lines like
+//! `(1700000000.123456) can0 123#DEADBEEF`, which can be replayed with `canplayer` or inspected
+//! with the other can-utils.
+
+use eoi_can_decoder::can_frame::CanFrame;
+use std::fmt::Write as _;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Log files are rotated when they grow past this size
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Buffered lines are written to disk at least this often, so a power cut loses little
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct CanLogger {
+    dir: PathBuf,
+    /// Indexed by the bus of the frames
+    interfaces: Vec<String>,
+    max_file_size: u64,
+    file: BufWriter<File>,
+    file_size: u64,
+    last_flush: Instant,
+}
+
+impl CanLogger {
+    /// Start a new log file in `dir`, the directory is created when needed.
+    /// `interface` is the name written in front of every frame, like `can0`
+    pub fn new(dir: impl AsRef<Path>, interface: &str) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+        let file = create_log_file(&dir)?;
+
+        Ok(Self {
+            dir,
+            interfaces: vec![interface.to_string()],
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            file,
+            file_size: 0,
+            last_flush: Instant::now(),
+        })
+    }
+
+    pub fn with_max_file_size(mut self, max_file_size: u64) -> Self {
+        self.max_file_size = max_file_size;
+        self
+    }
+
+    /// Names of all interfaces when more than one is logged, the bus of a frame selects its name.
+    /// Frames of other buses get the first name
+    pub fn with_interfaces(mut self, interfaces: &[String]) -> Self {
+        if !interfaces.is_empty() {
+            self.interfaces = interfaces.to_vec();
+        }
+        self
+    }
+
+    /// Log a frame received just now
+    pub fn log(&mut self, frame: &CanFrame) -> io::Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        self.log_at(timestamp, frame)
+    }
+
+    /// Log a frame with its timestamp since the unix epoch
+    pub fn log_at(&mut self, timestamp: Duration, frame: &CanFrame) -> io::Result<()> {
+        if self.file_size >= self.max_file_size {
+            self.rotate()?;
+        }
+
+        let interface = self
+            .interfaces
+            .get(frame.bus as usize)
+            .unwrap_or(&self.interfaces[0]);
+        let line = format_candump_line(timestamp, interface, frame);
+        self.file.write_all(line.as_bytes())?;
+        self.file_size += line.len() as u64;
+
+        if self.last_flush.elapsed() >= FLUSH_INTERVAL {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.last_flush = Instant::now();
+        self.file.flush()
+    }
+
+    /// Flush and wait until the log is on the disk, before the power goes away
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.flush()?;
+        self.file.get_ref().sync_all()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        self.file = create_log_file(&self.dir)?;
+        self.file_size = 0;
+        Ok(())
+    }
+}
+
+impl Drop for CanLogger {
+    fn drop(&mut self) {
+        let _ = self.file.flush();
+    }
+}
+
+/// Format a frame as candump log line, including the line ending
+pub fn format_candump_line(timestamp: Duration, interface: &str, frame: &CanFrame) -> String {
+    let mut line = format!(
+        "({}.{:06}) {} ",
+        timestamp.as_secs(),
+        timestamp.subsec_micros(),
+        interface
+    );
+    // Writing to a String can not fail
+    let _ = match frame.id {
+        embedded_can::Id::Standard(id) => write!(line, "{:03X}#", id.as_raw()),
+        embedded_can::Id::Extended(id) => write!(line, "{:08X}#", id.as_raw()),
+    };
+    for byte in &frame.data {
+        let _ = write!(line, "{:02X}", byte);
+    }
+    line.push('\n');
+    line
+}
+
+/// Parse a candump log line into its timestamp since the unix epoch, interface and frame.
+/// Returns `None` for lines that aren't classic data frames, like remote or CAN FD frames
+pub fn parse_candump_line(line: &str) -> Option<(Duration, &str, CanFrame)> {
+    let mut parts = line.split_whitespace();
+    let timestamp = parts.next()?.strip_prefix('(')?.strip_suffix(')')?;
+    let interface = parts.next()?;
+    let (id, data) = parts.next()?.split_once('#')?;
+
+    let (seconds, fraction) = timestamp.split_once('.')?;
+    // Pad or cut the fraction to microseconds
+    let micros: String = fraction
+        .chars()
+        .chain(std::iter::repeat('0'))
+        .take(6)
+        .collect();
+    let timestamp =
+        Duration::from_secs(seconds.parse().ok()?) + Duration::from_micros(micros.parse().ok()?);
+
+    // candump writes standard IDs with 3 and extended IDs with 8 digits
+    let raw_id = u32::from_str_radix(id, 16).ok()?;
+    let id = if id.len() > 3 {
+        embedded_can::Id::Extended(embedded_can::ExtendedId::new(raw_id)?)
+    } else {
+        embedded_can::Id::Standard(embedded_can::StandardId::new(raw_id as u16)?)
+    };
+
+    if data.len() % 2 != 0 || data.len() > 16 {
+        return None;
+    }
+    let mut bytes = heapless::Vec::<u8, 8>::new();
+    for index in (0..data.len()).step_by(2) {
+        bytes
+            .push(u8::from_str_radix(data.get(index..index + 2)?, 16).ok()?)
+            .ok()?;
+    }
+
+    Some((timestamp, interface, CanFrame::try_new(id, &bytes).ok()?))
+}
+
+/// Create a new file named like candump does, `candump-2025-06-21_133742.log`
+fn create_log_file(dir: &Path) -> io::Result<BufWriter<File>> {
+    let name = chrono::Local::now()
+        .format("candump-%Y-%m-%d_%H%M%S")
+        .to_string();
+    let mut path = dir.join(format!("{}.log", name));
+    // Rotating more than once a second needs a suffix to not overwrite the previous file
+    let mut index = 1;
+    while path.exists() {
+        path = dir.join(format!("{}-{}.log", name, index));
+        index += 1;
+    }
+
+    let file = OpenOptions::new()
+        .create_new(true)
+        .append(true)
+        .open(path)?;
+    Ok(BufWriter::new(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use embedded_can::{ExtendedId, Id, StandardId};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("can-logger-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn candump_line() {
+        let frame = CanFrame::from_array(
+            Id::Standard(StandardId::new(0x44).unwrap()),
+            [0x2A, 0x36, 0x6C, 0x2B, 0xBA],
+        );
+        let line = format_candump_line(Duration::new(1436509052, 249713000), "can0", &frame);
+        assert!(line == "(1436509052.249713) can0 044#2A366C2BBA\n");
+
+        let frame = CanFrame::from_array(Id::Extended(ExtendedId::new(0x0909).unwrap()), []);
+        let line = format_candump_line(Duration::new(1, 5000), "vcan0", &frame);
+        assert!(line == "(1.000005) vcan0 00000909#\n");
+    }
+
+    #[test]
+    fn parse_line() {
+        let (timestamp, interface, frame) =
+            parse_candump_line("(1436509052.249713) can0 044#2A366C2BBA").unwrap();
+        assert!(timestamp == Duration::new(1436509052, 249713000));
+        assert!(interface == "can0");
+        assert!(frame.id == Id::Standard(StandardId::new(0x44).unwrap()));
+        assert!(frame.data == [0x2A, 0x36, 0x6C, 0x2B, 0xBA]);
+
+        let (_, _, frame) = parse_candump_line("(1.5) vcan0 00000909#").unwrap();
+        assert!(frame.id == Id::Extended(ExtendedId::new(0x909).unwrap()));
+        assert!(frame.data.is_empty());
+
+        // Remote and CAN FD frames
+        assert!(parse_candump_line("(1.000000) can0 123#R").is_none());
+        assert!(parse_candump_line("(1.000000) can0 123##1AA").is_none());
+    }
+
+    #[test]
+    fn format_and_parse() {
+        let frame = CanFrame::from_array(
+            Id::Extended(ExtendedId::new(0x1337).unwrap()),
+            [1, 2, 3, 4, 5, 6, 7, 8],
+        );
+        let line = format_candump_line(Duration::new(42, 1000), "can0", &frame);
+        assert!(parse_candump_line(&line) == Some((Duration::new(42, 1000), "can0", frame)));
+    }
+
+    #[test]
+    fn rotates_log_files() {
+        let dir = temp_dir("rotate");
+        let frame = CanFrame::from_array(Id::Standard(StandardId::new(0x100).unwrap()), [0; 8]);
+
+        let mut logger = CanLogger::new(&dir, "can0")
+            .unwrap()
+            .with_max_file_size(100);
+        for _ in 0..5 {
+            logger.log(&frame).unwrap();
+        }
+        drop(logger);
+
+        // Every line is 46 bytes, files are rotated once they reached 100 bytes
+        let mut sizes: Vec<u64> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().metadata().unwrap().len())
+            .collect();
+        sizes.sort();
+        assert!(sizes == [92, 138]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn interface_of_the_bus() {
+        let dir = temp_dir("interfaces");
+        let frame = CanFrame::from_array(Id::Standard(StandardId::new(0x100).unwrap()), [1]);
+
+        let mut logger = CanLogger::new(&dir, "can0")
+            .unwrap()
+            .with_interfaces(&["can0".to_string(), "can1".to_string()]);
+        logger.log_at(Duration::new(1, 0), &frame).unwrap();
+        logger
+            .log_at(Duration::new(2, 0), &frame.clone().with_bus(1))
+            .unwrap();
+        logger
+            .log_at(Duration::new(3, 0), &frame.clone().with_bus(7))
+            .unwrap();
+        drop(logger);
+
+        let file = std::fs::read_dir(&dir).unwrap().next().unwrap().unwrap();
+        let log = std::fs::read_to_string(file.path()).unwrap();
+        assert!(log == "(1.000000) can0 100#01\n(2.000000) can1 100#01\n(3.000000) can0 100#01\n");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
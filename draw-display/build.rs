@@ -1,4 +1,227 @@
+//! Writes the build information shown on the display, and converts the assets to raw images
+//! embedded-graphics draws directly, so the display doesn't parse bitmaps at runtime and the
+//! source doesn't need hand-written arrays.
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// Bitmaps drawn as `ImageRaw<BinaryColor>`, light pixels are `BinaryColor::On`
+const IMAGES: &[(&str, &str)] = &[("EOI_LOGO", "assets/eoi-logo-mark--monochrome-black.bmp")];
+
+/// Sheet of glyphs side by side in a single row, dark pixels are the glyphs
+struct FontAsset {
+    name: &'static str,
+    path: &'static str,
+    /// Characters of the glyphs in the order of the sheet, the last one replaces unknown ones
+    glyphs: &'static str,
+    character_size: (u32, u32),
+    baseline: u32,
+}
+
+const FONTS: &[FontAsset] = &[
+    FontAsset {
+        name: "FONT_DIGITS_30X60",
+        path: "assets/fonts/digits-30x60.bmp",
+        glyphs: "0123456789.-+%: ",
+        character_size: (30, 60),
+        baseline: 45,
+    },
+    FontAsset {
+        name: "FONT_DIGITS_40X64",
+        path: "assets/fonts/digits-40x64.bmp",
+        glyphs: "0123456789.-% ",
+        character_size: (40, 64),
+        baseline: 59,
+    },
+];
+
 fn main() {
     println!("cargo:rerun-if-changed=../");
     built::write_built_file().expect("Failed to acquire build-time information");
+    write_assets().expect("Failed to convert the assets");
+}
+
+fn write_assets() -> Result<(), String> {
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").map_err(|error| error.to_string())?);
+    let mut assets = String::from(
+        "use embedded_graphics::{\n    \
+             geometry::Size,\n    \
+             image::ImageRaw,\n    \
+             mono_font::{mapping::StrGlyphMapping, DecorationDimensions, MonoFont},\n    \
+             pixelcolor::BinaryColor,\n\
+         };\n",
+    );
+
+    for (name, path) in IMAGES {
+        let bitmap = Bitmap::read(Path::new(path))?;
+        let raw = out_dir.join(format!("{}.raw", name));
+        std::fs::write(&raw, bitmap.to_raw(|luma| luma >= 128)).map_err(|e| e.to_string())?;
+        writeln!(
+            assets,
+            "\n/// `{}`\npub const {}: ImageRaw<'static, BinaryColor> = \
+             ImageRaw::new(include_bytes!({:?}), {});",
+            path, name, raw, bitmap.width
+        )
+        .unwrap();
+    }
+
+    for font in FONTS {
+        let bitmap = Bitmap::read(Path::new(font.path))?;
+        let (width, height) = font.character_size;
+        let glyphs = font.glyphs.chars().count() as u32;
+        if bitmap.width != glyphs * width || bitmap.height != height {
+            return Err(format!(
+                "{} is {}x{}, expected {} glyphs of {}x{} in a row",
+                font.path, bitmap.width, bitmap.height, glyphs, width, height
+            ));
+        }
+        let raw = out_dir.join(format!("{}.raw", font.name));
+        std::fs::write(&raw, bitmap.to_raw(|luma| luma < 128)).map_err(|e| e.to_string())?;
+        writeln!(
+            assets,
+            "\nconst {name}_GLYPHS: StrGlyphMapping<'static> = StrGlyphMapping::new({glyphs:?}, {replacement});\n\
+             /// `{path}`, glyphs `{glyphs}`\n\
+             pub const {name}: MonoFont<'static> = MonoFont {{\n    \
+                 image: ImageRaw::new(include_bytes!({raw:?}), {image_width}),\n    \
+                 glyph_mapping: &{name}_GLYPHS,\n    \
+                 character_size: Size::new({width}, {height}),\n    \
+                 character_spacing: 0,\n    \
+                 baseline: {baseline},\n    \
+                 underline: DecorationDimensions::new({underline}, {thickness}),\n    \
+                 strikethrough: DecorationDimensions::new({strikethrough}, {thickness}),\n\
+             }};",
+            name = font.name,
+            path = font.path,
+            glyphs = font.glyphs,
+            replacement = glyphs - 1,
+            raw = raw,
+            image_width = bitmap.width,
+            width = width,
+            height = height,
+            baseline = font.baseline,
+            underline = (font.baseline + height / 10).min(height - (height / 20).max(1)),
+            strikethrough = height / 2,
+            thickness = (height / 20).max(1),
+        )
+        .unwrap();
+    }
+
+    std::fs::write(out_dir.join("assets.rs"), assets).map_err(|error| error.to_string())
+}
+
+/// Uncompressed BMP with 1, 4, 8, 24 or 32 bits per pixel, as the luma of every pixel
+struct Bitmap {
+    width: u32,
+    height: u32,
+    /// Rows from the top
+    luma: Vec<u8>,
+}
+
+impl Bitmap {
+    fn read(path: &Path) -> Result<Self, String> {
+        let data = std::fs::read(path).map_err(|error| format!("{:?}: {}", path, error))?;
+        let error = |message: &str| format!("{:?}: {}", path, message);
+        let u16_at = |offset: usize| -> Result<u16, String> {
+            let bytes = data
+                .get(offset..offset + 2)
+                .ok_or_else(|| error("too short"))?;
+            Ok(u16::from_le_bytes(bytes.try_into().unwrap()))
+        };
+        let u32_at = |offset: usize| -> Result<u32, String> {
+            let bytes = data
+                .get(offset..offset + 4)
+                .ok_or_else(|| error("too short"))?;
+            Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+        };
+
+        if data.get(..2) != Some(b"BM") {
+            return Err(error("not a BMP"));
+        }
+        let pixels = u32_at(10)? as usize;
+        let header_size = u32_at(14)? as usize;
+        let width = u32_at(18)? as i32;
+        let height = u32_at(22)? as i32;
+        let bits = u16_at(28)?;
+        let compression = u32_at(30)?;
+        // Bit fields are only accepted for the usual BGRA order of 32 bits
+        if compression != 0 && !(compression == 3 && bits == 32) {
+            return Err(error("compressed BMPs aren't supported"));
+        }
+        let palette: Vec<u8> = match bits {
+            1 | 4 | 8 => {
+                let used = match u32_at(46)? {
+                    0 => 1 << bits,
+                    used => used,
+                };
+                (0..used as usize)
+                    .map(|index| {
+                        let entry = 14 + header_size + 4 * index;
+                        let bgr = data
+                            .get(entry..entry + 3)
+                            .ok_or_else(|| error("too short"))?;
+                        Ok(luma(bgr[2], bgr[1], bgr[0]))
+                    })
+                    .collect::<Result<_, String>>()?
+            }
+            24 | 32 => Vec::new(),
+            _ => return Err(error("unsupported bits per pixel")),
+        };
+
+        let (width, bottom_up) = (width.unsigned_abs(), height > 0);
+        let height = height.unsigned_abs();
+        let stride = (width as usize * usize::from(bits)).div_ceil(32) * 4;
+        let mut luma_of = Vec::with_capacity((width * height) as usize);
+        for y in 0..height as usize {
+            let row = if bottom_up {
+                height as usize - 1 - y
+            } else {
+                y
+            };
+            let start = pixels + row * stride;
+            let row = data
+                .get(start..start + stride)
+                .ok_or_else(|| error("too short"))?;
+            for x in 0..width as usize {
+                let value = match bits {
+                    24 | 32 => {
+                        let pixel = &row[x * usize::from(bits / 8)..];
+                        luma(pixel[2], pixel[1], pixel[0])
+                    }
+                    _ => {
+                        let bit = x * usize::from(bits);
+                        let index = (row[bit / 8] >> (8 - usize::from(bits) - bit % 8))
+                            & ((1u16 << bits) - 1) as u8;
+                        *palette
+                            .get(usize::from(index))
+                            .ok_or_else(|| error("color outside of the palette"))?
+                    }
+                };
+                luma_of.push(value);
+            }
+        }
+        Ok(Self {
+            width,
+            height,
+            luma: luma_of,
+        })
+    }
+
+    /// One bit per pixel, set for the pixels `on` is true for, rows padded to whole bytes like
+    /// `ImageRaw<BinaryColor>` expects
+    fn to_raw(&self, on: impl Fn(u8) -> bool) -> Vec<u8> {
+        let stride = (self.width as usize).div_ceil(8);
+        let mut raw = vec![0; stride * self.height as usize];
+        for (index, luma) in self.luma.iter().enumerate() {
+            let (x, y) = (index % self.width as usize, index / self.width as usize);
+            if on(*luma) {
+                raw[y * stride + x / 8] |= 0x80 >> (x % 8);
+            }
+        }
+        raw
+    }
+}
+
+/// Luma like embedded-graphics converts colors to `BinaryColor`
+fn luma(r: u8, g: u8, b: u8) -> u8 {
+    ((u16::from(r) * 77 + u16::from(g) * 150 + u16::from(b) * 29 + 128) / 256) as u8
 }
@@ -0,0 +1,200 @@
+//! Values computed from several others, like the net power of the battery from the voltage and
+//! three currents. How they handle inputs that are stale or not known is a policy: strictly
+//! unknown, left out, or taken at their last value. The latter two mark the result as partial,
+//! which is drawn with a small "≈" in front of it.
+
+use core::str::FromStr;
+
+use embedded_graphics::{
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::{Polyline, PrimitiveStyle},
+};
+
+use crate::DisplayValue;
+
+/// Width of the "≈" marker, plus the gap to the value
+pub const PARTIAL_MARKER_WIDTH: i32 = 10;
+
+/// How an aggregate handles inputs that are stale or not known
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AggregatePolicy {
+    /// Not known as soon as one input isn't
+    #[default]
+    Strict,
+    /// Terms with an input that isn't known are left out, the result is partial
+    Partial,
+    /// Stale inputs are taken at their last value, the result is partial. Terms with an input
+    /// that was never received are left out
+    LastKnown,
+}
+
+impl FromStr for AggregatePolicy {
+    type Err = &'static str;
+
+    fn from_str(policy: &str) -> Result<Self, Self::Err> {
+        match policy {
+            "strict" => Ok(AggregatePolicy::Strict),
+            "partial" => Ok(AggregatePolicy::Partial),
+            "last-known" => Ok(AggregatePolicy::LastKnown),
+            _ => Err("expected strict, partial or last-known"),
+        }
+    }
+}
+
+/// The result of an aggregate, `NaN` when it isn't known
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aggregated {
+    pub value: f32,
+    /// Computed from only some of the inputs, or from stale ones
+    pub partial: bool,
+}
+
+/// Sums terms of inputs by a policy
+#[derive(Debug)]
+pub struct Aggregate {
+    policy: AggregatePolicy,
+    sum: f32,
+    terms: u8,
+    partial: bool,
+}
+
+impl Aggregate {
+    pub fn new(policy: AggregatePolicy) -> Self {
+        Self {
+            policy,
+            sum: 0.0,
+            terms: 0,
+            partial: false,
+        }
+    }
+
+    /// An input of a term, `NaN` when the policy doesn't use it
+    pub fn input(&mut self, value: &DisplayValue<f32>) -> f32 {
+        match self.policy {
+            AggregatePolicy::Strict | AggregatePolicy::Partial => *value.get().unwrap_or(&f32::NAN),
+            AggregatePolicy::LastKnown => {
+                self.partial |= value.is_stale();
+                *value.last().unwrap_or(&f32::NAN)
+            }
+        }
+    }
+
+    /// Add a term, one which isn't known makes the sum unknown when strict and is left out
+    /// otherwise
+    pub fn add(&mut self, term: f32) {
+        if term.is_nan() && self.policy != AggregatePolicy::Strict {
+            self.partial = true;
+            return;
+        }
+        self.sum += term;
+        self.terms += 1;
+    }
+
+    pub fn finish(self) -> Aggregated {
+        if self.terms == 0 {
+            return Aggregated {
+                value: f32::NAN,
+                partial: false,
+            };
+        }
+        Aggregated {
+            value: self.sum,
+            partial: self.partial && !self.sum.is_nan(),
+        }
+    }
+}
+
+/// Draw the "≈" of a partial value with its top left corner at `top_left`, 8x7 pixels
+pub fn draw_partial_marker<D, C>(display: &mut D, top_left: Point) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor + From<BinaryColor>,
+{
+    let style = PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 1);
+    for y in [1, 5] {
+        let wave = [
+            Point::new(0, y + 1),
+            Point::new(2, y - 1),
+            Point::new(5, y + 1),
+            Point::new(7, y - 1),
+        ]
+        .map(|point| point + top_left);
+        Polyline::new(&wave).into_styled(style).draw(display)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::Duration;
+
+    fn sum(policy: AggregatePolicy, voltage: &DisplayValue<f32>, currents: &[f32]) -> Aggregated {
+        let mut aggregate = Aggregate::new(policy);
+        let voltage = aggregate.input(voltage);
+        for current in currents {
+            aggregate.add(voltage * current);
+        }
+        aggregate.finish()
+    }
+
+    #[test]
+    fn terms_not_known() {
+        let mut voltage = DisplayValue::default();
+        voltage.update(50.0);
+
+        let all = sum(AggregatePolicy::Strict, &voltage, &[2.0, -1.0]);
+        assert_eq!(
+            all,
+            Aggregated {
+                value: 50.0,
+                partial: false
+            }
+        );
+        assert!(sum(AggregatePolicy::Strict, &voltage, &[2.0, f32::NAN])
+            .value
+            .is_nan());
+
+        let partial = sum(AggregatePolicy::Partial, &voltage, &[2.0, f32::NAN]);
+        assert_eq!(
+            partial,
+            Aggregated {
+                value: 100.0,
+                partial: true
+            }
+        );
+
+        let none = sum(AggregatePolicy::Partial, &voltage, &[f32::NAN]);
+        assert!(none.value.is_nan());
+        assert!(!none.partial);
+    }
+
+    #[test]
+    fn stale_inputs() {
+        let mut voltage = DisplayValue::with_timeout(Duration::from_millis(0));
+        voltage.update(50.0);
+        assert!(voltage.is_stale());
+
+        assert!(sum(AggregatePolicy::Strict, &voltage, &[2.0])
+            .value
+            .is_nan());
+        assert!(sum(AggregatePolicy::Partial, &voltage, &[2.0])
+            .value
+            .is_nan());
+        assert_eq!(
+            sum(AggregatePolicy::LastKnown, &voltage, &[2.0]),
+            Aggregated {
+                value: 100.0,
+                partial: true
+            }
+        );
+    }
+
+    #[test]
+    fn policy_from_str() {
+        assert_eq!("last-known".parse(), Ok(AggregatePolicy::LastKnown));
+        assert!("lenient".parse::<AggregatePolicy>().is_err());
+    }
+}
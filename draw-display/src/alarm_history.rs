@@ -0,0 +1,298 @@
+//! History of the raised and cleared alarms, so faults which came and went during a run can be
+//! reviewed back at the dock. The data model compares the active alarms with the last known ones
+//! after every ingested message and records the differences, the oldest events are dropped when
+//! the history is full.
+
+use eoi_can_decoder::{GnssDateTime, VescFault, MAX_VESC_CONTROLLERS};
+use heapless::{Deque, Vec};
+
+use crate::time::{self, Instant};
+
+/// Events kept in memory, the application persists them if it has somewhere to
+pub const ALARM_HISTORY_LENGTH: usize = 32;
+/// Every alarm without a controller can be active together with a fault of every controller
+const MAX_ACTIVE_ALARMS: usize = 7 + MAX_VESC_CONTROLLERS;
+
+pub type ActiveAlarms = Vec<Alarm, MAX_ACTIVE_ALARMS>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alarm {
+    /// The state of charge fell below the reserve
+    Reserve,
+    /// The cell voltages drifted apart
+    CellImbalance,
+    /// The clock stamping the logs is off the GNSS time
+    ClockDivergence,
+    /// The MPPTs and the BMS disagree on the charge current
+    ChargeCheck,
+    /// The sources of the state of charge differ too much
+    SocDisagreement,
+    /// The BMS opened the discharge path, or its pre-charge timed out
+    BatteryCutoff,
+    /// The throttle reports an error
+    Throttle,
+    /// A motor controller stopped driving its motor
+    MotorFault { controller_id: u8, fault: VescFault },
+}
+
+impl Alarm {
+    /// Name used by the HTTP API and the persisted log
+    pub fn name(&self) -> &'static str {
+        match self {
+            Alarm::Reserve => "reserve",
+            Alarm::CellImbalance => "cell-imbalance",
+            Alarm::ClockDivergence => "clock-divergence",
+            Alarm::ChargeCheck => "charge-check",
+            Alarm::SocDisagreement => "soc-disagreement",
+            Alarm::BatteryCutoff => "battery-cutoff",
+            Alarm::Throttle => "throttle",
+            Alarm::MotorFault { .. } => "motor-fault",
+        }
+    }
+
+    /// Whether the alarm stops the boat, rather than warning of what may come
+    pub fn is_critical(&self) -> bool {
+        matches!(
+            self,
+            Alarm::BatteryCutoff | Alarm::Throttle | Alarm::MotorFault { .. }
+        )
+    }
+}
+
+/// Short description fitting a line of the alarm page, like `VESC 9: FET hot`
+impl core::fmt::Display for Alarm {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Alarm::Reserve => f.write_str("Reserve"),
+            Alarm::CellImbalance => f.write_str("Cell imbalance"),
+            Alarm::ClockDivergence => f.write_str("Clock off GNSS time"),
+            Alarm::ChargeCheck => f.write_str("Charge check diverging"),
+            Alarm::SocDisagreement => f.write_str("SoC sources disagree"),
+            Alarm::BatteryCutoff => f.write_str("BMS cut off"),
+            Alarm::Throttle => f.write_str("Throttle error"),
+            Alarm::MotorFault {
+                controller_id,
+                fault,
+            } => write!(f, "VESC {}: {}", controller_id, fault.label()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AlarmEvent {
+    pub alarm: Alarm,
+    /// Whether the alarm was raised or cleared
+    pub raised: bool,
+    /// When it was recorded, `None` for an event of an earlier run restored from a log
+    pub at: Option<Instant>,
+    /// UTC time of the GNSS receiver, `None` without a fix
+    pub time: Option<GnssDateTime>,
+}
+
+/// Alarm, controller and fault, whether it was raised, the GNSS time and the seconds since, like
+/// `{"age": 12.5, "alarm": "motor-fault", "controller_id": 1, "fault": "FET hot", "raised": true,
+/// "time": {"year": 2024, ...}}`. The age is `null` for an event of an earlier run
+#[cfg(feature = "serde")]
+impl serde::Serialize for AlarmEvent {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let (controller_id, fault) = match self.alarm {
+            Alarm::MotorFault {
+                controller_id,
+                fault,
+            } => (Some(controller_id), Some(fault.label())),
+            _ => (None, None),
+        };
+        let mut state = serializer.serialize_struct("AlarmEvent", 6)?;
+        let age = self
+            .at
+            .map(|at| time::since(at).as_millis() as f32 / 1000.0);
+        state.serialize_field("age", &age)?;
+        state.serialize_field("alarm", self.alarm.name())?;
+        state.serialize_field("controller_id", &controller_id)?;
+        state.serialize_field("fault", &fault)?;
+        state.serialize_field("raised", &self.raised)?;
+        state.serialize_field("time", &self.time)?;
+        state.end()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct AlarmHistory {
+    events: Deque<AlarmEvent, ALARM_HISTORY_LENGTH>,
+    active: ActiveAlarms,
+    /// Number of events recorded since the start, including the dropped ones
+    recorded: u32,
+}
+
+/// The active alarms and the events, newest first
+#[cfg(feature = "serde")]
+impl serde::Serialize for AlarmHistory {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        struct Active<'a>(&'a [Alarm]);
+        impl serde::Serialize for Active<'_> {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_seq(self.0.iter().map(Alarm::name))
+            }
+        }
+        struct Events<'a>(&'a AlarmHistory);
+        impl serde::Serialize for Events<'_> {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_seq(self.0.events().rev())
+            }
+        }
+
+        let mut state = serializer.serialize_struct("AlarmHistory", 2)?;
+        state.serialize_field("active", &Active(&self.active))?;
+        state.serialize_field("events", &Events(self))?;
+        state.end()
+    }
+}
+
+impl AlarmHistory {
+    /// Alarms active at the last update
+    pub fn active(&self) -> &[Alarm] {
+        &self.active
+    }
+
+    /// Kept events, oldest first
+    pub fn events(&self) -> impl DoubleEndedIterator<Item = &AlarmEvent> {
+        self.events.iter()
+    }
+
+    /// Number of events recorded since the start, for [`since`](Self::since)
+    pub fn recorded(&self) -> u32 {
+        self.recorded
+    }
+
+    /// The kept events recorded after the first `recorded` ones, for the application to persist
+    /// the new events
+    pub fn since(&self, recorded: u32) -> impl Iterator<Item = &AlarmEvent> {
+        let new = self.recorded.saturating_sub(recorded) as usize;
+        self.events
+            .iter()
+            .skip(self.events.len().saturating_sub(new))
+    }
+
+    /// Put the events of an earlier run, oldest first, in the history before anything is recorded.
+    /// They don't count as recorded, so they aren't persisted again, and don't make their alarms
+    /// active, the live data raises them again
+    pub fn restore(&mut self, events: impl IntoIterator<Item = AlarmEvent>) {
+        for event in events {
+            if self.events.is_full() {
+                self.events.pop_front();
+            }
+            _ = self.events.push_back(event);
+        }
+    }
+
+    /// Record the alarms which are no longer active as cleared and the new ones as raised
+    pub fn update(&mut self, active: ActiveAlarms, time: Option<GnssDateTime>) {
+        self.update_at(active, time, time::now());
+    }
+
+    fn update_at(&mut self, active: ActiveAlarms, time: Option<GnssDateTime>, at: Instant) {
+        let cleared = self.active.iter().filter(|alarm| !active.contains(alarm));
+        let raised = active.iter().filter(|alarm| !self.active.contains(alarm));
+        let changes: Vec<(Alarm, bool), { 2 * MAX_ACTIVE_ALARMS }> = cleared
+            .map(|alarm| (*alarm, false))
+            .chain(raised.map(|alarm| (*alarm, true)))
+            .collect();
+
+        for (alarm, raised) in changes {
+            if self.events.is_full() {
+                self.events.pop_front();
+            }
+            _ = self.events.push_back(AlarmEvent {
+                alarm,
+                raised,
+                at: Some(at),
+                time,
+            });
+            self.recorded += 1;
+        }
+        self.active = active;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::Duration;
+
+    fn alarms(alarms: &[Alarm]) -> ActiveAlarms {
+        alarms.iter().copied().collect()
+    }
+
+    #[test]
+    fn raised_and_cleared() {
+        let mut history = AlarmHistory::default();
+        let start = Instant::now();
+        history.update_at(alarms(&[]), None, start);
+        assert_eq!(history.recorded(), 0);
+
+        let fault = Alarm::MotorFault {
+            controller_id: 1,
+            fault: VescFault::OverTempFet,
+        };
+        history.update_at(alarms(&[Alarm::Reserve, fault]), None, start);
+        history.update_at(alarms(&[Alarm::Reserve, fault]), None, start);
+        assert_eq!(history.recorded(), 2);
+        assert_eq!(history.active(), [Alarm::Reserve, fault]);
+
+        // Another fault of the same controller clears the first one
+        let other = Alarm::MotorFault {
+            controller_id: 1,
+            fault: VescFault::OverTempMotor,
+        };
+        let later = start + Duration::from_secs(5);
+        history.update_at(alarms(&[Alarm::Reserve, other]), None, later);
+        let events: Vec<_, 4> = history
+            .since(2)
+            .map(|event| (event.alarm, event.raised, event.at))
+            .collect();
+        assert_eq!(
+            events,
+            [(fault, false, Some(later)), (other, true, Some(later))]
+        );
+        assert_eq!(history.since(history.recorded()).count(), 0);
+    }
+
+    #[test]
+    fn oldest_events_dropped() {
+        let mut history = AlarmHistory::default();
+        let start = Instant::now();
+        for _ in 0..ALARM_HISTORY_LENGTH {
+            history.update_at(alarms(&[Alarm::Throttle]), None, start);
+            history.update_at(alarms(&[]), None, start);
+        }
+        assert_eq!(history.recorded(), 2 * ALARM_HISTORY_LENGTH as u32);
+        assert_eq!(history.events().count(), ALARM_HISTORY_LENGTH);
+        // Only the kept ones of the new events
+        assert_eq!(history.since(0).count(), ALARM_HISTORY_LENGTH);
+        assert_eq!(history.since(history.recorded() - 3).count(), 3);
+        assert!(!history.events().next_back().unwrap().raised);
+    }
+
+    #[test]
+    fn restored_events_are_not_recorded() {
+        let mut history = AlarmHistory::default();
+        let earlier = AlarmEvent {
+            alarm: Alarm::Throttle,
+            raised: true,
+            at: None,
+            time: None,
+        };
+        history.restore([earlier; 2]);
+        assert_eq!(history.recorded(), 0);
+        assert_eq!(history.active(), []);
+
+        history.update_at(alarms(&[Alarm::Reserve]), None, Instant::now());
+        assert_eq!(history.events().count(), 3);
+        let new: Vec<_, 2> = history.since(0).map(|event| event.alarm).collect();
+        assert_eq!(new, [Alarm::Reserve]);
+    }
+}
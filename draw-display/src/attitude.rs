@@ -0,0 +1,147 @@
+//! Artificial horizon of the IMU for trimming the foils. The horizon tilts with the roll and moves
+//! down when the bow comes up, behind a fixed symbol of the boat, like the attitude indicator of
+//! an aircraft.
+
+use embedded_graphics::{
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::{Circle, Line, PrimitiveStyle},
+};
+use micromath::F32;
+
+/// How far the horizon moves per degree of pitch
+const PIXELS_PER_DEGREE: f32 = 8.0;
+/// Pitch of the marks above and below the horizon in degrees
+const LADDER: [f32; 4] = [-10.0, -5.0, 5.0, 10.0];
+const LADDER_HALF_LENGTH: f32 = 30.0;
+
+/// Draw the horizon for `pitch` and `roll` in degrees in a circle of `radius` around `center`.
+/// Without an attitude only the circle and the boat are drawn
+pub fn draw_attitude<D, C>(
+    display: &mut D,
+    attitude: Option<(f32, f32)>,
+    center: Point,
+    radius: u32,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor + From<BinaryColor>,
+{
+    Circle::with_center(center, 2 * radius + 1)
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 2))
+        .draw(display)?;
+
+    if let Some((pitch, roll)) = attitude {
+        if let Some((start, end)) = chord(center, radius as f32, pitch, roll, None) {
+            Line::new(start, end)
+                .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 3))
+                .draw(display)?;
+        }
+        for mark in LADDER {
+            let Some((start, end)) = chord(
+                center,
+                radius as f32,
+                pitch - mark,
+                roll,
+                Some(LADDER_HALF_LENGTH),
+            ) else {
+                continue;
+            };
+            Line::new(start, end)
+                .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 1))
+                .draw(display)?;
+        }
+    }
+
+    // The boat stays level in the middle, the horizon moves around it
+    for side in [-1, 1] {
+        Line::new(
+            center + Point::new(side * 20, 0),
+            center + Point::new(side * 60, 0),
+        )
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 3))
+        .draw(display)?;
+    }
+    Circle::with_center(center, 7)
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::Off.into()))
+        .draw(display)?;
+    Ok(())
+}
+
+/// Ends of the line `pitch` degrees below the middle of the circle, tilted by `roll`. The line
+/// reaches the circle, or `half_length` to both sides when that is shorter. `None` when the line
+/// is out of the circle
+fn chord(
+    center: Point,
+    radius: f32,
+    pitch: f32,
+    roll: f32,
+    half_length: Option<f32>,
+) -> Option<(Point, Point)> {
+    let distance = pitch * PIXELS_PER_DEGREE;
+    if distance.abs() >= radius {
+        return None;
+    }
+    let mut half = sqrt(radius * radius - distance * distance);
+    if let Some(half_length) = half_length {
+        half = half.min(half_length);
+    }
+
+    // Heeling to starboard lifts the right side of the horizon, the screen y grows downwards
+    let roll = F32(roll.to_radians());
+    let (cos, sin) = (roll.cos().0, roll.sin().0);
+    let middle = (
+        center.x as f32 + sin * distance,
+        center.y as f32 + cos * distance,
+    );
+    let point = |side: f32| {
+        Point::new(
+            F32(middle.0 + side * cos * half).round().0 as i32,
+            F32(middle.1 - side * sin * half).round().0 as i32,
+        )
+    };
+    Some((point(-1.0), point(1.0)))
+}
+
+/// The square root of micromath is off by a few percent, two Newton steps make the line end on
+/// the circle
+fn sqrt(value: f32) -> f32 {
+    let mut root = F32(value).sqrt().0;
+    for _ in 0..2 {
+        if root > 0.0 {
+            root = (root + value / root) / 2.0;
+        }
+    }
+    root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The sine of micromath is an approximation
+    fn near(point: Point, expected: Point) -> bool {
+        (point - expected).abs().x <= 1 && (point - expected).abs().y <= 1
+    }
+
+    #[test]
+    fn horizon_follows_the_attitude() {
+        let center = Point::new(200, 200);
+        // Level, the horizon goes through the middle
+        let (start, end) = chord(center, 100.0, 0.0, 0.0, None).unwrap();
+        assert!(near(start, Point::new(100, 200)));
+        assert!(near(end, Point::new(300, 200)));
+        // Bow up, the horizon moves down and gets shorter
+        let (start, end) = chord(center, 100.0, 5.0, 0.0, None).unwrap();
+        assert_eq!((start.y, end.y), (240, 240));
+        assert!(end.x - start.x < 200);
+        // Heeling to starboard, the right side comes up
+        let (start, end) = chord(center, 100.0, 0.0, 10.0, None).unwrap();
+        assert!(end.y < 200 && start.y > 200);
+        // Out of the circle
+        assert_eq!(chord(center, 100.0, 15.0, 0.0, None), None);
+        // Marks of the ladder are short
+        let (start, end) = chord(center, 100.0, 0.0, 0.0, Some(30.0)).unwrap();
+        assert!(near(end - start, Point::new(60, 0)));
+    }
+}
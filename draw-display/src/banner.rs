@@ -0,0 +1,154 @@
+//! Short messages of the chase car, like "PIT NOW", shown in a banner over the page until the
+//! pilot acknowledges them.
+
+use eoi_can_decoder::{DisplayMessage, DISPLAY_MESSAGE_LEN, DISPLAY_MESSAGE_PARTS};
+use heapless::String;
+
+use crate::time::Duration;
+use crate::DisplayValue;
+
+/// A message is shown this long, unless the chase car sends it again
+const BANNER_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Puts the parts of a [`DisplayMessage`] back together. Every part has its own CAN ID, so the
+/// parts can arrive in any order.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Banner {
+    #[cfg_attr(feature = "serde", serde(skip))]
+    parts: [Option<DisplayMessage>; DISPLAY_MESSAGE_PARTS],
+    text: DisplayValue<String<DISPLAY_MESSAGE_LEN>>,
+    /// Of the current text
+    message_id: Option<u8>,
+    acknowledged: bool,
+}
+
+impl Default for Banner {
+    fn default() -> Self {
+        Self {
+            parts: Default::default(),
+            text: DisplayValue::with_timeout(BANNER_TIMEOUT),
+            message_id: None,
+            acknowledged: false,
+        }
+    }
+}
+
+impl Banner {
+    pub fn push(&mut self, message: DisplayMessage) {
+        let Some(slot) = self.parts.get_mut(message.part as usize) else {
+            return;
+        };
+        *slot = Some(message);
+
+        // Complete once all parts up to the last one belong to the same message
+        let Some(message_id) = self.parts[0].as_ref().map(|first| first.message_id) else {
+            return;
+        };
+        let mut text = String::new();
+        for index in 0..DISPLAY_MESSAGE_PARTS {
+            let Some(part) = self.parts[index].as_ref() else {
+                return;
+            };
+            if part.message_id != message_id || text.push_str(&part.text).is_err() {
+                return;
+            }
+            if part.last {
+                self.parts[..=index].fill(None);
+                // The chase car sending a message again doesn't bring it back
+                let repeated =
+                    self.message_id == Some(message_id) && self.text.last() == Some(&text);
+                if !repeated {
+                    self.message_id = Some(message_id);
+                    self.acknowledged = false;
+                }
+                self.text.update(text);
+                return;
+            }
+        }
+    }
+
+    /// The message to show, `None` when it was cleared, acknowledged or timed out
+    pub fn text(&self) -> Option<&str> {
+        if self.acknowledged {
+            return None;
+        }
+        self.text
+            .get()
+            .map(|text| text.as_str())
+            .filter(|text| !text.is_empty())
+    }
+
+    /// Hide the shown message, returns its ID to send back to the chase car. `None` when no
+    /// message is shown
+    pub fn acknowledge(&mut self) -> Option<u8> {
+        self.text()?;
+        self.acknowledged = true;
+        self.message_id
+    }
+
+    /// Another display acknowledged the message `message_id`, hide it here too
+    pub fn acknowledged_elsewhere(&mut self, message_id: u8) {
+        if self.message_id == Some(message_id) {
+            self.acknowledged = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parts_in_any_order() {
+        let mut banner = Banner::default();
+        let mut parts = DisplayMessage::split(1, "SLOW 2 MIN");
+        parts.reverse();
+        for part in parts {
+            assert_eq!(banner.text(), None);
+            banner.push(part);
+        }
+        assert_eq!(banner.text(), Some("SLOW 2 MIN"));
+
+        // A part of another message doesn't complete the new one
+        let old = DisplayMessage::split(1, "SLOW 2 MIN");
+        let new = DisplayMessage::split(2, "PIT IN 3 LAPS");
+        banner.push(old[0].clone());
+        banner.push(new[1].clone());
+        assert_eq!(banner.text(), Some("SLOW 2 MIN"));
+        banner.push(new[0].clone());
+        assert_eq!(banner.text(), Some("PIT IN 3 LAPS"));
+
+        for part in DisplayMessage::split(3, "") {
+            banner.push(part);
+        }
+        assert_eq!(banner.text(), None);
+    }
+
+    #[test]
+    fn acknowledge() {
+        let mut banner = Banner::default();
+        assert_eq!(banner.acknowledge(), None);
+
+        let push = |banner: &mut Banner, message_id, text| {
+            for part in DisplayMessage::split(message_id, text) {
+                banner.push(part);
+            }
+        };
+        push(&mut banner, 4, "PIT NOW");
+        assert_eq!(banner.acknowledge(), Some(4));
+        assert_eq!(banner.text(), None);
+        assert_eq!(banner.acknowledge(), None);
+
+        // Sent again it stays acknowledged, a new message is shown
+        push(&mut banner, 4, "PIT NOW");
+        assert_eq!(banner.text(), None);
+        push(&mut banner, 5, "PIT NOW");
+        assert_eq!(banner.text(), Some("PIT NOW"));
+
+        banner.acknowledged_elsewhere(4);
+        assert_eq!(banner.text(), Some("PIT NOW"));
+        banner.acknowledged_elsewhere(5);
+        assert_eq!(banner.text(), None);
+    }
+}
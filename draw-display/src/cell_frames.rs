@@ -0,0 +1,137 @@
+//! The BMS sends the 14 cell voltages in four frames every cycle, the BMS of a 16 cell pack a
+//! fifth one with the last two cells. When a frame of a cycle is lost, its cells keep the voltages
+//! of the cycle before until they go stale, mixed silently with the new ones of the other frames.
+//! Tracking when every frame of the group was last received tells which cells are behind the
+//! others, and whether the pack has 16 cells.
+
+use crate::time::{self, Duration, Instant};
+
+/// Frames of the cell voltages, 0x103 to 0x106 and 0x10C of a 16 cell pack
+pub const CELL_VOLTAGE_FRAMES: usize = 5;
+/// Cells of the largest pack
+pub const MAX_CELLS: usize = 16;
+/// Cells of a pack without the frame of cells 15 and 16
+pub const DEFAULT_CELLS: usize = 14;
+/// Cells sent in every frame, the last two only have two
+const CELLS_PER_FRAME: usize = 4;
+/// The BMS sends the frames 10 times a second, a frame received longer than this before the
+/// newest one of the group missed a cycle
+const CYCLE_WINDOW: Duration = Duration::from_millis(150);
+
+#[derive(Debug, Default)]
+pub struct CellVoltageFrames {
+    received: [Option<Instant>; CELL_VOLTAGE_FRAMES],
+}
+
+impl CellVoltageFrames {
+    /// Called with the index of the frame, 0 for cells 1 to 4
+    pub fn update(&mut self, frame: usize) {
+        self.update_at(frame, time::now());
+    }
+
+    fn update_at(&mut self, frame: usize, at: Instant) {
+        if let Some(received) = self.received.get_mut(frame) {
+            *received = Some(at);
+        }
+    }
+
+    /// Whether `frame` is missing from the latest cycle, received more than a cycle before the
+    /// newest frame of the group or not at all
+    pub fn is_behind(&self, frame: usize) -> bool {
+        let Some(newest) = self.received.iter().flatten().max() else {
+            return false;
+        };
+        match self.received.get(frame).copied().flatten() {
+            Some(received) => newest.duration_since(received) > CYCLE_WINDOW,
+            None => true,
+        }
+    }
+
+    /// Whether the cell with the index `cell` still shows a voltage of an earlier cycle
+    pub fn is_cell_behind(&self, cell: usize) -> bool {
+        self.is_behind(frame_of_cell(cell))
+    }
+
+    /// Some, but not all frames of the latest cycle were received
+    pub fn is_partially_stale(&self) -> bool {
+        (0..frame_of_cell(self.cells() - 1) + 1).any(|frame| self.is_behind(frame))
+    }
+
+    /// Cells of the pack, 16 once the frame of cells 15 and 16 was received and 14 before
+    pub fn cells(&self) -> usize {
+        if self.received[CELL_VOLTAGE_FRAMES - 1].is_some() {
+            MAX_CELLS
+        } else {
+            DEFAULT_CELLS
+        }
+    }
+}
+
+/// Index of the frame with the cell with the index `cell`
+pub fn frame_of_cell(cell: usize) -> usize {
+    if cell < DEFAULT_CELLS {
+        cell / CELLS_PER_FRAME
+    } else {
+        CELL_VOLTAGE_FRAMES - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_missing_from_a_cycle() {
+        let mut frames = CellVoltageFrames::default();
+        assert!(!frames.is_partially_stale());
+
+        let start = Instant::now();
+        let cycle = |frames: &mut CellVoltageFrames, at: Instant, skip: Option<usize>| {
+            for frame in
+                (0..frame_of_cell(DEFAULT_CELLS - 1) + 1).filter(|frame| Some(*frame) != skip)
+            {
+                frames.update_at(frame, at + Duration::from_millis(10 * frame as u64));
+            }
+        };
+        cycle(&mut frames, start, None);
+        assert!(!frames.is_partially_stale());
+
+        // The frame of cells 5 to 8 is lost in the next two cycles
+        cycle(&mut frames, start + Duration::from_millis(100), Some(1));
+        assert!(!frames.is_partially_stale());
+        cycle(&mut frames, start + Duration::from_millis(200), Some(1));
+        cycle(&mut frames, start + Duration::from_millis(300), Some(1));
+        assert!(frames.is_partially_stale());
+        assert!(frames.is_cell_behind(4) && frames.is_cell_behind(7));
+        assert!(!frames.is_cell_behind(3) && !frames.is_cell_behind(13));
+
+        cycle(&mut frames, start + Duration::from_millis(400), None);
+        assert!(!frames.is_partially_stale());
+    }
+
+    #[test]
+    fn frame_never_received() {
+        let mut frames = CellVoltageFrames::default();
+        frames.update_at(0, Instant::now());
+        assert!(frames.is_cell_behind(12));
+        assert!(!frames.is_cell_behind(0));
+    }
+
+    #[test]
+    fn pack_with_16_cells() {
+        let mut frames = CellVoltageFrames::default();
+        let now = Instant::now();
+        for frame in 0..4 {
+            frames.update_at(frame, now);
+        }
+        assert_eq!(frames.cells(), 14);
+        assert!(!frames.is_partially_stale());
+
+        frames.update_at(4, now);
+        assert_eq!(frames.cells(), 16);
+        assert_eq!(frame_of_cell(13), 3);
+        assert_eq!(frame_of_cell(15), 4);
+        frames.update_at(4, now + Duration::from_millis(300));
+        assert!(frames.is_cell_behind(13) && !frames.is_cell_behind(14));
+    }
+}
@@ -0,0 +1,121 @@
+//! Which cells the temperature sensors of the battery sit at. The BMS only reports the
+//! temperatures of its inputs, a mapping from a config file of the application tells which cells
+//! every sensor measures, so the main page shows the temperature of every group of cells under
+//! their bars and a hot spot can be found in the pack. Without a mapping, like with the four
+//! sensors of the current BMS, only the minimum, maximum and average are shown.
+
+use core::ops::RangeInclusive;
+
+use crate::{DisplayData, BATTERY_TEMPERATURE_RANGE, MAX_BATTERY_TEMPERATURES};
+
+/// A temperature sensor and the cells it measures
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct SensorGroup {
+    /// Input of the BMS, counted from 1 like the temperatures of the protocol
+    pub sensor: u8,
+    /// Counted from 1 like the cell bars
+    pub first_cell: u8,
+    pub last_cell: u8,
+}
+
+/// Groups of cells by their temperature sensor, in any order
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct CellTemperatureMap {
+    pub groups: heapless::Vec<SensorGroup, MAX_BATTERY_TEMPERATURES>,
+}
+
+impl CellTemperatureMap {
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// The indexes of the cells of every group with the temperature of its sensor in °C, `None`
+    /// while it is stale or out of range. Groups of sensors or cells the pack doesn't have are
+    /// left out
+    pub fn temperatures<'a>(
+        &'a self,
+        data: &'a DisplayData,
+    ) -> impl Iterator<Item = (RangeInclusive<usize>, Option<i8>)> + 'a {
+        self.groups.iter().filter_map(|group| {
+            let sensor = data
+                .battery_temperatures
+                .get(usize::from(group.sensor).checked_sub(1)?)?;
+            let first = usize::from(group.first_cell).checked_sub(1)?;
+            let last = usize::from(group.last_cell).checked_sub(1)?;
+            if first > last || last >= data.battery_cell_voltages.len() {
+                return None;
+            }
+            let temperature = sensor
+                .get()
+                .copied()
+                .filter(|temperature| BATTERY_TEMPERATURE_RANGE.contains(temperature));
+            Some((first..=last, temperature))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_graphics::prelude::*;
+    use embedded_graphics::primitives::Rectangle;
+
+    fn group(sensor: u8, first_cell: u8, last_cell: u8) -> SensorGroup {
+        SensorGroup {
+            sensor,
+            first_cell,
+            last_cell,
+        }
+    }
+
+    #[test]
+    fn temperatures_of_the_groups() {
+        let mut data = DisplayData::default();
+        data.update_battery_temperatures(0, &[25, 26, 27, 28]);
+        data.update_battery_temperatures(4, &[31, -128, 29, 30]);
+        assert_eq!(data.battery_temperatures.len(), 8);
+
+        let map = CellTemperatureMap {
+            groups: [
+                group(1, 1, 2),
+                group(5, 9, 10),
+                group(6, 11, 12),
+                // The pack has 14 cells
+                group(8, 15, 16),
+                group(9, 1, 1),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        let temperatures: std::vec::Vec<_> = map.temperatures(&data).collect();
+        assert_eq!(
+            temperatures,
+            [(0..=1, Some(25)), (8..=9, Some(31)), (10..=11, None)]
+        );
+    }
+
+    #[test]
+    fn drawn_under_the_cell_bars() {
+        let mut data = DisplayData {
+            cell_temperature_map: CellTemperatureMap {
+                groups: [group(1, 1, 2), group(2, 13, 14)].into_iter().collect(),
+            },
+            ..Default::default()
+        };
+        data.update_battery_temperatures(0, &[25, -128, 27, 28]);
+        let mut target = crate::recording_target::RecordingTarget::new();
+        crate::draw_page(&mut target, &data, crate::Page::Main).unwrap();
+
+        // Sensor 2 is broken
+        let below_the_bars = Rectangle::new(Point::new(400, 462), Size::new(400, 8));
+        let texts: std::vec::Vec<_> = target
+            .texts_in(below_the_bars)
+            .map(|text| text.text.as_str())
+            .collect();
+        assert_eq!(texts, ["25C", "--"]);
+    }
+}
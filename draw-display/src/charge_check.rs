@@ -0,0 +1,110 @@
+//! Cross-check of the current the MPPTs put out against the charge current the BMS measures.
+//! They should match apart from the wiring, a persistent difference means losses in the wiring
+//! or a failed output stage of an MPPT.
+
+use crate::time::{self, Duration, Instant};
+use crate::DisplayValue;
+
+/// Difference in A that is always allowed, the sensors aren't more accurate at low currents
+const TOLERANCE_A: f32 = 2.0;
+/// Difference as a fraction of the larger current that is allowed at high currents
+const TOLERANCE_RELATIVE: f32 = 0.15;
+/// The currents have to differ this long, they are measured at different times
+const DIVERGING_AFTER: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ChargeCrossCheck {
+    /// Summed output current of the MPPTs in A at the last check
+    pub mppt_current: Option<f32>,
+    /// Charge current of the BMS in A at the last check
+    pub battery_current: Option<f32>,
+    /// Whether the currents differ for longer than [`DIVERGING_AFTER`]
+    pub diverging: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    differing_since: Option<Instant>,
+}
+
+impl ChargeCrossCheck {
+    /// Compare the currents, called when the BMS reports its charge current
+    pub fn update(&mut self, mppt_output_currents: &[DisplayValue<f32>], battery_current: f32) {
+        self.update_at(mppt_output_currents, battery_current, time::now())
+    }
+
+    fn update_at(
+        &mut self,
+        mppt_output_currents: &[DisplayValue<f32>],
+        battery_current: f32,
+        now: Instant,
+    ) {
+        let mut currents = mppt_output_currents
+            .iter()
+            .filter_map(|current| current.get());
+        // Without an MPPT there is nothing to compare
+        let mppt_current = currents.next().map(|first| first + currents.sum::<f32>());
+        self.mppt_current = mppt_current;
+        self.battery_current = Some(battery_current);
+
+        let differing = mppt_current.is_some_and(|mppt_current| {
+            let tolerance =
+                TOLERANCE_A.max(mppt_current.abs().max(battery_current.abs()) * TOLERANCE_RELATIVE);
+            (mppt_current - battery_current).abs() > tolerance
+        });
+        self.differing_since = match self.differing_since {
+            Some(since) if differing => Some(since),
+            _ if differing => Some(now),
+            _ => None,
+        };
+        self.diverging = self
+            .differing_since
+            .is_some_and(|since| now.duration_since(since) >= DIVERGING_AFTER);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn currents(values: [Option<f32>; 3]) -> [DisplayValue<f32>; 3] {
+        values.map(|value| {
+            let mut current = DisplayValue::default();
+            if let Some(value) = value {
+                current.update(value);
+            }
+            current
+        })
+    }
+
+    #[test]
+    fn persistent_difference() {
+        let mut check = ChargeCrossCheck::default();
+        let start = Instant::now();
+        let mppts = currents([Some(10.0), None, Some(12.0)]);
+
+        // Within the tolerance
+        check.update_at(&mppts, 20.5, start);
+        assert_eq!(check.mppt_current, Some(22.0));
+        assert!(!check.diverging);
+
+        // The battery gets much less than the MPPTs put out, a short difference is ignored
+        check.update_at(&mppts, 10.0, start + Duration::from_secs(1));
+        check.update_at(&mppts, 10.0, start + Duration::from_secs(30));
+        assert!(!check.diverging);
+        check.update_at(&mppts, 10.0, start + Duration::from_secs(31));
+        assert!(check.diverging);
+
+        check.update_at(&mppts, 21.0, start + Duration::from_secs(32));
+        assert!(!check.diverging);
+    }
+
+    #[test]
+    fn nothing_to_compare() {
+        let mut check = ChargeCrossCheck::default();
+        let start = Instant::now();
+        let mppts = currents([None, None, None]);
+        check.update_at(&mppts, 20.0, start);
+        check.update_at(&mppts, 20.0, start + Duration::from_secs(60));
+        assert_eq!(check.mppt_current, None);
+        assert!(!check.diverging);
+    }
+}
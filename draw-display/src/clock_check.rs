@@ -0,0 +1,91 @@
+//! Watchdog for the clock of the application drifting away from the GNSS time. The CAN logs and
+//! the MQTT data are stamped by the clock of the Pi, the defmt log by the synced clock of the
+//! firmware; when one of them is off, the logs can't be correlated with each other afterwards. The
+//! application compares its clock with the GNSS time now and then, a divergence above the
+//! threshold raises an alarm.
+
+/// Divergence in s above which the alarm starts, the GNSS time only has whole seconds and arrives
+/// with some latency
+pub const DEFAULT_CLOCK_DIVERGENCE: f32 = 5.0;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ClockCheck {
+    /// Divergence in s above which the alarm starts
+    threshold: f32,
+    /// The clock of the application minus the GNSS time in s, at the last comparison
+    divergence: Option<f32>,
+    active: bool,
+    /// The new state when it changed and the application didn't take it yet
+    #[cfg_attr(feature = "serde", serde(skip))]
+    changed: Option<bool>,
+}
+
+impl Default for ClockCheck {
+    fn default() -> Self {
+        Self::new(DEFAULT_CLOCK_DIVERGENCE)
+    }
+}
+
+impl ClockCheck {
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            threshold,
+            divergence: None,
+            active: false,
+            changed: None,
+        }
+    }
+
+    pub fn threshold(&self) -> f32 {
+        self.threshold
+    }
+
+    /// The clock of the application minus the GNSS time in s, `None` before the first comparison
+    pub fn divergence(&self) -> Option<f32> {
+        self.divergence
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Called with the clock of the application minus the GNSS time in s
+    pub fn update(&mut self, divergence: f32) {
+        self.divergence = Some(divergence);
+        let active = divergence.abs() > self.threshold;
+        if active != self.active {
+            self.active = active;
+            self.changed = Some(active);
+        }
+    }
+
+    /// Whether the alarm started or ended since the last call, for the application to log or
+    /// publish it
+    pub fn take_change(&mut self) -> Option<bool> {
+        self.changed.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diverging_clock() {
+        let mut check = ClockCheck::default();
+        check.update(1.2);
+        assert!(!check.is_active());
+        assert_eq!(check.take_change(), None);
+
+        // A clock behind the GNSS time is just as wrong
+        check.update(-3600.0);
+        assert!(check.is_active());
+        assert_eq!(check.take_change(), Some(true));
+        assert_eq!(check.take_change(), None);
+        assert_eq!(check.divergence(), Some(-3600.0));
+
+        check.update(0.4);
+        assert_eq!(check.take_change(), Some(false));
+    }
+}
@@ -0,0 +1,106 @@
+//! Countdown to the end of the race, set by the chase car. The end is a UTC time of day, so all
+//! displays count down to the same moment with the GNSS time and the firmware can restore it
+//! after a reset.
+
+use eoi_can_decoder::GnssDateTime;
+
+use crate::time::Duration;
+
+const SECONDS_PER_DAY: i64 = 24 * 3600;
+/// An end further ahead than this has passed, races are shorter
+const MAX_RACE_DURATION_S: i64 = SECONDS_PER_DAY / 2;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RaceCountdown {
+    /// End of the race as second of the UTC day
+    end_s: Option<u32>,
+}
+
+impl RaceCountdown {
+    pub fn end_s(&self) -> Option<u32> {
+        self.end_s
+    }
+
+    /// Count down to `end_s`, the second of the UTC day, `None` stops the countdown
+    pub fn set_end(&mut self, end_s: Option<u32>) {
+        self.end_s = end_s.map(|end_s| end_s % SECONDS_PER_DAY as u32);
+    }
+
+    /// Time left at the UTC time `now`, zero once the race ended
+    pub fn remaining(&self, now: &GnssDateTime) -> Option<Duration> {
+        let end_s = i64::from(self.end_s?);
+        let now_s =
+            i64::from(now.hours) * 3600 + i64::from(now.minutes) * 60 + i64::from(now.seconds);
+        // The race can end after midnight
+        let remaining_s = (end_s - now_s).rem_euclid(SECONDS_PER_DAY);
+        Some(if remaining_s > MAX_RACE_DURATION_S {
+            Duration::from_secs(0)
+        } else {
+            Duration::from_secs(remaining_s as u64)
+        })
+    }
+}
+
+/// State of charge in % that can be used per minute to arrive empty at the end of the race,
+/// `None` once it ended or when the state of charge isn't known
+pub fn energy_budget_per_minute(state_of_charge: f32, remaining: Duration) -> Option<f32> {
+    let minutes = remaining.as_secs() as f32 / 60.0;
+    if state_of_charge.is_nan() || minutes <= 0.0 {
+        return None;
+    }
+    Some(state_of_charge.max(0.0) / minutes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hours: u8, minutes: u8, seconds: u8) -> GnssDateTime {
+        GnssDateTime {
+            year: 2025,
+            month: 6,
+            day: 14,
+            hours,
+            minutes,
+            seconds,
+        }
+    }
+
+    #[test]
+    fn remaining_time() {
+        let mut countdown = RaceCountdown::default();
+        assert_eq!(countdown.remaining(&at(14, 0, 0)), None);
+
+        countdown.set_end(Some(14 * 3600 + 30 * 60));
+        assert_eq!(
+            countdown.remaining(&at(14, 0, 0)),
+            Some(Duration::from_secs(1800))
+        );
+        // Ended
+        assert_eq!(
+            countdown.remaining(&at(14, 31, 0)),
+            Some(Duration::from_secs(0))
+        );
+
+        // Over midnight
+        countdown.set_end(Some(1800));
+        assert_eq!(
+            countdown.remaining(&at(23, 50, 0)),
+            Some(Duration::from_secs(2400))
+        );
+    }
+
+    #[test]
+    fn budget() {
+        assert_eq!(
+            energy_budget_per_minute(30.0, Duration::from_secs(3600)),
+            Some(0.5)
+        );
+        assert_eq!(energy_budget_per_minute(30.0, Duration::from_secs(0)), None);
+        assert_eq!(
+            energy_budget_per_minute(f32::NAN, Duration::from_secs(60)),
+            None
+        );
+    }
+}
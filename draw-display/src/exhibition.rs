@@ -0,0 +1,208 @@
+//! The exhibition page for public demo events: the solar energy harvested today, and what it
+//! means next to a household and the grid. Shown by the exhibition profile, the energy comes from
+//! the trip counters.
+
+use embedded_graphics::{
+    mono_font::{ascii::FONT_10X20, MonoTextStyle, MonoTextStyleBuilder},
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::{Line, PrimitiveStyle},
+    text::{Alignment, Text},
+};
+use eoi_can_decoder::GnssDateTime;
+use heapless::String;
+
+use crate::{assets, DisplayData};
+
+/// Electricity an average household uses in a day in kWh, about 3300 kWh a year
+pub const HOUSEHOLD_KWH_PER_DAY: f32 = 9.0;
+/// CO2 the grid emits for a kWh in kg
+pub const GRID_CO2_KG_PER_KWH: f32 = 0.4;
+/// What a kWh from the grid costs in EUR
+pub const GRID_PRICE_EUR_PER_KWH: f32 = 0.3;
+
+/// Where the solar energy of the trip counters stood when the local day began
+#[derive(Debug, Default)]
+pub struct SolarToday {
+    date: Option<(u16, u8, u8)>,
+    start_wh: f32,
+}
+
+impl SolarToday {
+    /// Called with the local time, a new date starts a new day. Before the first date the day
+    /// started with the display
+    pub fn update(&mut self, local_time: &GnssDateTime, solar_energy_wh: f32) {
+        let date = (local_time.year, local_time.month, local_time.day);
+        if self.date.is_some_and(|today| today != date) {
+            self.start_wh = solar_energy_wh;
+        }
+        self.date = Some(date);
+    }
+
+    /// Solar energy harvested today in Wh, given the one of the trip counters
+    pub fn energy_wh(&self, solar_energy_wh: f32) -> f32 {
+        (solar_energy_wh - self.start_wh).max(0.0)
+    }
+}
+
+/// Hours the energy in Wh would supply an average household
+pub fn household_hours(energy_wh: f32) -> f32 {
+    energy_wh / 1000.0 / HOUSEHOLD_KWH_PER_DAY * 24.0
+}
+
+/// CO2 in kg the grid would have emitted for the energy in Wh
+pub fn co2_avoided_kg(energy_wh: f32) -> f32 {
+    energy_wh / 1000.0 * GRID_CO2_KG_PER_KWH
+}
+
+/// What the energy in Wh would cost from the grid in EUR
+pub fn grid_value_eur(energy_wh: f32) -> f32 {
+    energy_wh / 1000.0 * GRID_PRICE_EUR_PER_KWH
+}
+
+/// The solar energy of today in large figures, with its equivalents as sentences for visitors
+pub fn draw_exhibition<D, C>(display: &mut D, data: &DisplayData) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor + From<BinaryColor>,
+{
+    use core::fmt::Write;
+
+    display.clear(BinaryColor::On.into())?;
+    let mut string_helper: String<64> = String::new();
+
+    let font_normal: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .build();
+
+    let font_normal_header: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .underline()
+        .build();
+
+    let font_large: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&assets::FONT_DIGITS_40X64)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .build();
+    const ROW_SPACE: i32 = 40;
+
+    Text::with_alignment(
+        "Powered by the sun",
+        Point::new(400, 20),
+        font_normal_header,
+        Alignment::Center,
+    )
+    .draw(display)?;
+
+    Line::new(Point::new(0, 70), Point::new(800, 70))
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 1))
+        .draw(display)?;
+
+    Text::with_alignment(
+        "Solar energy harvested today in kWh",
+        Point::new(400, 120),
+        font_normal,
+        Alignment::Center,
+    )
+    .draw(display)?;
+
+    // The digit font has no letters, the unit is in the label
+    let energy_wh = data.solar_today.energy_wh(data.trip.solar_energy_wh);
+    string_helper.clear();
+    write!(&mut string_helper, "{:.2}", energy_wh / 1000.0).unwrap();
+    Text::with_alignment(
+        string_helper.as_str(),
+        Point::new(400, 230),
+        font_large,
+        Alignment::Center,
+    )
+    .draw(display)?;
+
+    let mut offset_y = 320;
+    for line in 0..3 {
+        string_helper.clear();
+        match line {
+            0 => write!(
+                &mut string_helper,
+                "Enough for an average household for {:.1} hours",
+                household_hours(energy_wh)
+            ),
+            1 => write!(
+                &mut string_helper,
+                "{:.2} kg of CO2 saved against the grid",
+                co2_avoided_kg(energy_wh)
+            ),
+            _ => write!(
+                &mut string_helper,
+                "Worth {:.2} EUR at {:.2} EUR/kWh",
+                grid_value_eur(energy_wh),
+                GRID_PRICE_EUR_PER_KWH
+            ),
+        }
+        .unwrap();
+        Text::with_alignment(
+            string_helper.as_str(),
+            Point::new(400, offset_y),
+            font_normal,
+            Alignment::Center,
+        )
+        .draw(display)?;
+        offset_y += ROW_SPACE;
+    }
+
+    if let Some(state_of_charge) = data.battery_state_of_charge.get() {
+        string_helper.clear();
+        write!(&mut string_helper, "Battery {:.0}%", state_of_charge).unwrap();
+        Text::with_alignment(
+            string_helper.as_str(),
+            Point::new(400, offset_y + ROW_SPACE),
+            font_normal,
+            Alignment::Center,
+        )
+        .draw(display)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local_time(day: u8) -> GnssDateTime {
+        GnssDateTime {
+            year: 2025,
+            month: 6,
+            day,
+            hours: 10,
+            minutes: 0,
+            seconds: 0,
+        }
+    }
+
+    #[test]
+    fn energy_of_today() {
+        let mut today = SolarToday::default();
+        assert_eq!(today.energy_wh(120.0), 120.0);
+
+        today.update(&local_time(14), 120.0);
+        assert_eq!(today.energy_wh(500.0), 500.0);
+
+        today.update(&local_time(15), 500.0);
+        assert_eq!(today.energy_wh(500.0), 0.0);
+        today.update(&local_time(15), 800.0);
+        assert_eq!(today.energy_wh(800.0), 300.0);
+    }
+
+    #[test]
+    fn equivalents() {
+        assert_eq!(household_hours(9000.0), 24.0);
+        assert_eq!(co2_avoided_kg(2500.0), 1.0);
+        assert_eq!(grid_value_eur(1000.0), 0.3);
+    }
+}
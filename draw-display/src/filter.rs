@@ -0,0 +1,251 @@
+//! Filters calming the values of noisy signals before they are shown, attached to a
+//! [`DisplayValue`](crate::DisplayValue) with
+//! [`DisplayValue::with_filter`](crate::DisplayValue::with_filter). A [`FilterChain`] runs a median
+//! against single spikes of a sensor, a slew limiter against jumps and an exponential moving
+//! average against jitter, each only when it was added. No allocation and only a few bytes per
+//! value, so the same filters run on the firmware, which keeps hundreds of values.
+
+use micromath::F32;
+
+use crate::time::Duration;
+
+/// Longest window of a [`Median`]
+pub const MAX_MEDIAN_WINDOW: usize = 5;
+
+/// Exponential moving average, weighted by the time since the last value rather than per value,
+/// so the value follows as quickly whether it's sent once or five times a second
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Ema {
+    time_constant_us: f32,
+    value: Option<f32>,
+}
+
+impl Ema {
+    pub fn new(time_constant: Duration) -> Self {
+        Self {
+            time_constant_us: time_constant.as_micros() as f32,
+            value: None,
+        }
+    }
+
+    pub fn apply(&mut self, value: f32, elapsed: Duration) -> f32 {
+        let filtered = match self.value {
+            Some(previous) if self.time_constant_us > 0.0 => {
+                let weight = 1.0
+                    - F32(-(elapsed.as_micros() as f32) / self.time_constant_us)
+                        .exp()
+                        .0;
+                previous + (value - previous) * weight
+            }
+            _ => value,
+        };
+        self.value = Some(filtered);
+        filtered
+    }
+
+    pub fn reset(&mut self) {
+        self.value = None;
+    }
+}
+
+/// Median of the last values, a single spike doesn't show at all. Until the window is full the
+/// median of the values so far
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Median {
+    values: [f32; MAX_MEDIAN_WINDOW],
+    window: u8,
+    len: u8,
+    /// Where the next value goes, the oldest once the window is full
+    next: u8,
+}
+
+impl Median {
+    /// Of the last `window` values, up to [`MAX_MEDIAN_WINDOW`]
+    pub fn new(window: usize) -> Self {
+        Self {
+            values: [0.0; MAX_MEDIAN_WINDOW],
+            window: window.clamp(1, MAX_MEDIAN_WINDOW) as u8,
+            len: 0,
+            next: 0,
+        }
+    }
+
+    pub fn apply(&mut self, value: f32) -> f32 {
+        self.values[usize::from(self.next)] = value;
+        self.next = (self.next + 1) % self.window;
+        self.len = (self.len + 1).min(self.window);
+
+        let mut sorted = self.values;
+        let sorted = &mut sorted[..usize::from(self.len)];
+        sorted.sort_unstable_by(f32::total_cmp);
+        let middle = sorted.len() / 2;
+        if sorted.len().is_multiple_of(2) {
+            (sorted[middle - 1] + sorted[middle]) / 2.0
+        } else {
+            sorted[middle]
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.next = 0;
+    }
+}
+
+/// Limits how quickly the value may change, in units per second, so a jump shows as a ramp
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SlewLimiter {
+    max_rate: f32,
+    value: Option<f32>,
+}
+
+impl SlewLimiter {
+    pub fn new(max_rate: f32) -> Self {
+        Self {
+            max_rate: max_rate.abs(),
+            value: None,
+        }
+    }
+
+    pub fn apply(&mut self, value: f32, elapsed: Duration) -> f32 {
+        let limited = match self.value {
+            Some(previous) => {
+                let max_step = self.max_rate * elapsed.as_micros() as f32 / 1_000_000.0;
+                previous + (value - previous).clamp(-max_step, max_step)
+            }
+            None => value,
+        };
+        self.value = Some(limited);
+        limited
+    }
+
+    pub fn reset(&mut self) {
+        self.value = None;
+    }
+}
+
+/// The filters of a signal, built like `FilterChain::new().median(3).ema(time_constant)`. A value
+/// runs through the median, the slew limiter and the average, in this order whatever the order
+/// they were added in
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FilterChain {
+    median: Option<Median>,
+    slew_limiter: Option<SlewLimiter>,
+    ema: Option<Ema>,
+}
+
+impl FilterChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the median of the last `window` values, see [`Median`]
+    pub fn median(self, window: usize) -> Self {
+        Self {
+            median: Some(Median::new(window)),
+            ..self
+        }
+    }
+
+    /// Add a limit of the change per second, see [`SlewLimiter`]
+    pub fn slew_limit(self, max_rate: f32) -> Self {
+        Self {
+            slew_limiter: Some(SlewLimiter::new(max_rate)),
+            ..self
+        }
+    }
+
+    /// Add an exponential moving average, see [`Ema`]
+    pub fn ema(self, time_constant: Duration) -> Self {
+        Self {
+            ema: Some(Ema::new(time_constant)),
+            ..self
+        }
+    }
+
+    /// Run `value`, received `elapsed` after the one before, through the filters. NaN isn't
+    /// filtered, it's passed on right away and the filters start over after it
+    pub fn apply(&mut self, mut value: f32, elapsed: Duration) -> f32 {
+        if value.is_nan() {
+            self.reset();
+            return value;
+        }
+        if let Some(median) = &mut self.median {
+            value = median.apply(value);
+        }
+        if let Some(slew_limiter) = &mut self.slew_limiter {
+            value = slew_limiter.apply(value, elapsed);
+        }
+        if let Some(ema) = &mut self.ema {
+            value = ema.apply(value, elapsed);
+        }
+        value
+    }
+
+    /// Forget the values so far, the next one is taken as it is
+    pub fn reset(&mut self) {
+        if let Some(median) = &mut self.median {
+            median.reset();
+        }
+        if let Some(slew_limiter) = &mut self.slew_limiter {
+            slew_limiter.reset();
+        }
+        if let Some(ema) = &mut self.ema {
+            ema.reset();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn ema_follows_the_time_constant() {
+        let mut ema = Ema::new(SECOND);
+        assert_eq!(ema.apply(0.0, SECOND), 0.0);
+        // After one time constant 63 % of the step
+        let value = ema.apply(100.0, SECOND);
+        assert!((value - 63.2).abs() < 0.5, "{value}");
+        ema.reset();
+        assert_eq!(ema.apply(100.0, SECOND), 100.0);
+    }
+
+    #[test]
+    fn median_drops_spikes() {
+        let mut median = Median::new(3);
+        assert_eq!(median.apply(20.0), 20.0);
+        assert_eq!(median.apply(22.0), 21.0);
+        assert_eq!(median.apply(250.0), 22.0);
+        assert_eq!(median.apply(21.0), 22.0);
+        assert_eq!(median.apply(20.0), 21.0);
+    }
+
+    #[test]
+    fn slew_limiter_ramps() {
+        let mut limiter = SlewLimiter::new(10.0);
+        assert_eq!(limiter.apply(0.0, SECOND), 0.0);
+        assert_eq!(limiter.apply(100.0, SECOND), 10.0);
+        assert_eq!(limiter.apply(100.0, Duration::from_millis(500)), 15.0);
+        assert_eq!(limiter.apply(-100.0, SECOND), 5.0);
+    }
+
+    #[test]
+    fn chain_runs_the_filters_in_order() {
+        let mut chain = FilterChain::new().slew_limit(5.0).median(3);
+        assert_eq!(chain.apply(10.0, SECOND), 10.0);
+        assert_eq!(chain.apply(10.0, SECOND), 10.0);
+        // The spike is taken out before the limiter sees it
+        assert_eq!(chain.apply(90.0, SECOND), 10.0);
+        assert_eq!(chain.apply(30.0, SECOND), 15.0);
+
+        assert!(chain.apply(f32::NAN, SECOND).is_nan());
+        assert_eq!(chain.apply(50.0, SECOND), 50.0);
+    }
+}
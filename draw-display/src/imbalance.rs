@@ -0,0 +1,123 @@
+//! Alarm for cells drifting apart. The difference between the highest and lowest cell voltage
+//! jumps with every noisy frame of the BMS, so the alarm only starts when the difference stays
+//! above the threshold for a while, and only ends once it stays clearly below it again.
+
+use crate::time::{self, Duration, Instant};
+
+/// Difference between the highest and lowest cell in V above which the alarm starts
+pub const DEFAULT_IMBALANCE_THRESHOLD: f32 = 0.1;
+/// The difference has to fall this much in V below the threshold to end the alarm
+const HYSTERESIS: f32 = 0.03;
+/// How long the difference has to stay above the threshold, or below it to end the alarm
+const DEFAULT_PERSISTENCE: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CellImbalance {
+    /// Difference in V above which the alarm starts
+    threshold: f32,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    persistence: Duration,
+    active: bool,
+    /// Since when the difference says otherwise than `active`
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pending_since: Option<Instant>,
+    /// The new state when it changed and the application didn't take it yet
+    #[cfg_attr(feature = "serde", serde(skip))]
+    changed: Option<bool>,
+}
+
+impl Default for CellImbalance {
+    fn default() -> Self {
+        Self::new(DEFAULT_IMBALANCE_THRESHOLD)
+    }
+}
+
+impl CellImbalance {
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            threshold,
+            persistence: DEFAULT_PERSISTENCE,
+            active: false,
+            pending_since: None,
+            changed: None,
+        }
+    }
+
+    /// How long the difference has to stay above the threshold to start the alarm, or below it to
+    /// end it
+    pub fn with_persistence(mut self, persistence: Duration) -> Self {
+        self.persistence = persistence;
+        self
+    }
+
+    pub fn threshold(&self) -> f32 {
+        self.threshold
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Called with the difference between the highest and lowest cell voltage in V
+    pub fn update(&mut self, difference: f32) {
+        self.update_at(difference, time::now());
+    }
+
+    fn update_at(&mut self, difference: f32, now: Instant) {
+        let toggles = if self.active {
+            difference < self.threshold - HYSTERESIS
+        } else {
+            difference > self.threshold
+        };
+        if !toggles {
+            self.pending_since = None;
+            return;
+        }
+        let since = *self.pending_since.get_or_insert(now);
+        if now.duration_since(since) >= self.persistence {
+            self.active = !self.active;
+            self.pending_since = None;
+            self.changed = Some(self.active);
+        }
+    }
+
+    /// Whether the alarm started or ended since the last call, for the application to log or
+    /// publish it
+    pub fn take_change(&mut self) -> Option<bool> {
+        self.changed.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sustained_imbalance_only() {
+        let mut imbalance = CellImbalance::default();
+        let start = Instant::now();
+        let at = |seconds| start + Duration::from_secs(seconds);
+
+        // A single noisy frame doesn't start the alarm
+        imbalance.update_at(0.25, at(0));
+        imbalance.update_at(0.02, at(1));
+        imbalance.update_at(0.12, at(12));
+        assert!(!imbalance.is_active());
+
+        imbalance.update_at(0.12, at(22));
+        assert!(imbalance.is_active());
+        assert_eq!(imbalance.take_change(), Some(true));
+        assert_eq!(imbalance.take_change(), None);
+
+        // Just below the threshold is still imbalanced
+        imbalance.update_at(0.09, at(30));
+        imbalance.update_at(0.09, at(50));
+        assert!(imbalance.is_active());
+
+        imbalance.update_at(0.05, at(60));
+        imbalance.update_at(0.05, at(70));
+        assert!(!imbalance.is_active());
+        assert_eq!(imbalance.take_change(), Some(false));
+    }
+}
@@ -0,0 +1,287 @@
+//! Lap counting by detecting when the boat crosses the start/finish line between two GNSS
+//! positions. The first crossing starts the race, every following crossing in the same direction
+//! completes a lap.
+
+use core::str::FromStr;
+
+use crate::time::{self, Duration, Instant};
+
+/// Crossings quicker after each other are GNSS jitter around the line, not a lap
+const MIN_LAP_TIME: Duration = Duration::from_secs(20);
+/// Consecutive positions further apart (in degrees, about a kilometer) are a GNSS glitch
+const MAX_POSITION_JUMP: f64 = 0.01;
+
+/// Latitude and longitude in degrees
+pub type Position = (f64, f64);
+
+/// Start/finish line between two positions, like the two buoys marking it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FinishLine {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl FinishLine {
+    /// Whether the boat crossed the line sailing from `from` to `to`
+    fn is_crossed(&self, from: Position, to: Position) -> bool {
+        // The segments intersect when both ends of each are on different sides of the other.
+        // Degrees aren't square, but that doesn't change on which side a point is
+        let from_side = side(self.start, self.end, from);
+        let to_side = side(self.start, self.end, to);
+        let start_side = side(from, to, self.start);
+        let end_side = side(from, to, self.end);
+        (from_side > 0.0) != (to_side > 0.0) && (start_side > 0.0) != (end_side > 0.0)
+    }
+
+    /// Direction of a crossing, true when the boat ended up left of the line from start to end
+    fn is_left(&self, position: Position) -> bool {
+        side(self.start, self.end, position) > 0.0
+    }
+}
+
+/// Positive when `point` is left of the line from `a` to `b`, negative when right of it
+fn side(a: Position, b: Position, point: Position) -> f64 {
+    (b.0 - a.0) * (point.1 - a.1) - (b.1 - a.1) * (point.0 - a.0)
+}
+
+impl FromStr for FinishLine {
+    type Err = &'static str;
+
+    /// Parse `latitude,longitude,latitude,longitude` of both ends of the line
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        const ERROR: &str =
+            "expected 4 comma separated coordinates, like 43.7340,7.4210,43.7345,7.4220";
+        let mut coordinates = [0.0; 4];
+        let mut values = line.split(',');
+        for coordinate in &mut coordinates {
+            *coordinate = values
+                .next()
+                .and_then(|value| value.trim().parse().ok())
+                .ok_or(ERROR)?;
+        }
+        if values.next().is_some() {
+            return Err(ERROR);
+        }
+
+        Ok(Self {
+            start: (coordinates[0], coordinates[1]),
+            end: (coordinates[2], coordinates[3]),
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct LapCounter {
+    finish_line: Option<FinishLine>,
+    latitude: Option<f64>,
+    last_position: Option<Position>,
+    race_start: Option<Instant>,
+    /// Race time before the display restarted, [`LapCounter::restore`]
+    race_time_before_restart: Duration,
+    /// Direction of the first crossing, see [`FinishLine::is_left`]. Unknown after a restart
+    race_direction: Option<bool>,
+    last_crossing: Option<Instant>,
+    laps: u16,
+    last_lap_time: Option<Duration>,
+}
+
+impl LapCounter {
+    pub fn new(finish_line: FinishLine) -> Self {
+        Self {
+            finish_line: Some(finish_line),
+            ..Default::default()
+        }
+    }
+
+    /// Completed laps
+    pub fn laps(&self) -> u16 {
+        self.laps
+    }
+
+    pub fn last_lap_time(&self) -> Option<Duration> {
+        self.last_lap_time
+    }
+
+    /// Time since the boat crossed the line the first time, `None` before the race started
+    pub fn race_time(&self) -> Option<Duration> {
+        self.race_start
+            .as_ref()
+            .map(|start| self.race_time_before_restart + time::since(*start))
+    }
+
+    /// Start over before a new race, on the same finish line
+    pub fn reset(&mut self) {
+        *self = Self {
+            finish_line: self.finish_line.take(),
+            latitude: self.latitude,
+            last_position: self.last_position,
+            ..Default::default()
+        };
+    }
+
+    /// Continue a race after the display restarted. The next crossing in either direction
+    /// completes a lap, timed from the restart
+    pub fn restore(&mut self, laps: u16, race_time: Duration) {
+        let now = time::now();
+        self.laps = laps;
+        self.race_time_before_restart = race_time;
+        self.race_start = Some(now);
+        self.race_direction = None;
+        self.last_crossing = Some(now);
+    }
+
+    pub fn update_latitude(&mut self, latitude: f64) {
+        self.latitude = Some(latitude);
+    }
+
+    /// Latitude and longitude are sent in their own frames, the longitude completes the position.
+    /// Returns true when this completed a lap
+    pub fn update_longitude(&mut self, longitude: f64) -> bool {
+        match self.latitude.take() {
+            Some(latitude) => self.update_position((latitude, longitude), time::now()),
+            None => false,
+        }
+    }
+
+    fn update_position(&mut self, position: Position, now: Instant) -> bool {
+        // Receivers without fix report NaN or 0,0
+        if !position.0.is_finite() || !position.1.is_finite() || position == (0.0, 0.0) {
+            return false;
+        }
+        let Some(finish_line) = self.finish_line else {
+            return false;
+        };
+        let Some(previous) = self.last_position.replace(position) else {
+            return false;
+        };
+        let jump = (position.0 - previous.0, position.1 - previous.1);
+        if !(-MAX_POSITION_JUMP..=MAX_POSITION_JUMP).contains(&jump.0)
+            || !(-MAX_POSITION_JUMP..=MAX_POSITION_JUMP).contains(&jump.1)
+            || !finish_line.is_crossed(previous, position)
+        {
+            return false;
+        }
+
+        match self.last_crossing {
+            None => {
+                self.race_start = Some(now);
+                self.race_direction = Some(finish_line.is_left(position));
+                self.last_crossing = Some(now);
+                false
+            }
+            Some(last_crossing) => {
+                let lap_time = now.duration_since(last_crossing);
+                let direction = finish_line.is_left(position);
+                if lap_time < MIN_LAP_TIME
+                    || *self.race_direction.get_or_insert(direction) != direction
+                {
+                    return false;
+                }
+                self.laps = self.laps.saturating_add(1);
+                self.last_lap_time = Some(lap_time);
+                self.last_crossing = Some(now);
+                true
+            }
+        }
+    }
+}
+
+/// Lap count and times in seconds, like `{"laps": 3, "last_lap_time": 83.4, "race_time": 301.2}`
+#[cfg(feature = "serde")]
+impl serde::Serialize for LapCounter {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let seconds = |duration: Duration| duration.as_millis() as f32 / 1000.0;
+        let mut state = serializer.serialize_struct("LapCounter", 3)?;
+        state.serialize_field("laps", &self.laps)?;
+        state.serialize_field("last_lap_time", &self.last_lap_time.map(seconds))?;
+        state.serialize_field("race_time", &self.race_time().map(seconds))?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Line from south to north, the boat sails from west to east over it
+    const LINE: FinishLine = FinishLine {
+        start: (43.7340, 7.4210),
+        end: (43.7350, 7.4210),
+    };
+    const WEST: Position = (43.7345, 7.4205);
+    const EAST: Position = (43.7345, 7.4215);
+
+    #[test]
+    fn parse_finish_line() {
+        assert_eq!("43.7340, 7.4210,43.7350,7.4210".parse(), Ok(LINE));
+        assert!("43.7340,7.4210,43.7350".parse::<FinishLine>().is_err());
+        assert!("43.7340,7.4210,43.7350,7.4210,1"
+            .parse::<FinishLine>()
+            .is_err());
+        assert!("43.7340,7.4210,43.7350,east".parse::<FinishLine>().is_err());
+    }
+
+    #[test]
+    fn line_crossings() {
+        assert!(LINE.is_crossed(WEST, EAST));
+        assert!(LINE.is_crossed(EAST, WEST));
+        assert!(!LINE.is_crossed(WEST, (43.7346, 7.4206)));
+        // Passing north of the line
+        assert!(!LINE.is_crossed((43.7360, 7.4205), (43.7360, 7.4215)));
+    }
+
+    #[test]
+    fn count_laps() {
+        let mut counter = LapCounter::new(LINE);
+        let start = Instant::now();
+        let at = |seconds| start + Duration::from_secs(seconds);
+
+        assert!(!counter.update_position(WEST, at(0)));
+        // Starts the race
+        assert!(!counter.update_position(EAST, at(1)));
+        assert!(counter.race_time().is_some());
+        // Jitter back and forth over the line
+        assert!(!counter.update_position(WEST, at(2)));
+        assert!(!counter.update_position(EAST, at(3)));
+        assert_eq!(counter.laps(), 0);
+
+        // Crossing the other way around isn't a lap
+        assert!(!counter.update_position(WEST, at(60)));
+        assert!(counter.update_position(EAST, at(91)));
+        assert_eq!(counter.laps(), 1);
+        assert_eq!(counter.last_lap_time(), Some(Duration::from_secs(90)));
+
+        // GNSS glitches aren't crossings
+        assert!(!counter.update_position((0.0, 0.0), at(200)));
+        assert!(!counter.update_position((43.0, 7.0), at(201)));
+        assert!(!counter.update_position(WEST, at(202)));
+        assert_eq!(counter.laps(), 1);
+    }
+
+    #[test]
+    fn latitude_and_longitude_frames() {
+        let mut counter = LapCounter::default();
+        // Without latitude there is no position
+        assert!(!counter.update_longitude(WEST.1));
+        counter.update_latitude(WEST.0);
+        assert!(!counter.update_longitude(WEST.1));
+        assert!(counter.last_position.is_none(), "No finish line configured");
+    }
+
+    #[test]
+    fn restore_after_restart() {
+        let mut counter = LapCounter::new(LINE);
+        counter.restore(3, Duration::from_secs(600));
+        assert_eq!(counter.laps(), 3);
+        assert!(counter.race_time().unwrap() >= Duration::from_secs(600));
+
+        let start = Instant::now();
+        let at = |seconds| start + Duration::from_secs(seconds);
+        // The direction of the race isn't known anymore
+        assert!(!counter.update_position(EAST, at(30)));
+        assert!(counter.update_position(WEST, at(31)));
+        assert_eq!(counter.laps(), 4);
+    }
+}
@@ -0,0 +1,154 @@
+//! Summaries of the completed laps, so the strategy page can compare the last lap with the best
+//! one. The lap in progress averages the received speeds and integrates the battery power, which
+//! doesn't depend on the application updating the trip counters.
+
+use crate::time::{self, Duration, Instant};
+
+/// Gaps between power values longer than this are a lost BMS, not energy used
+const MAX_POWER_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LapSummary {
+    /// Number of the lap, counted from 1
+    pub lap: u16,
+    pub time: Duration,
+    /// Average of the GNSS speeds received during the lap, NaN without any
+    pub average_speed_kmh: f32,
+    /// Energy taken from the battery in Wh, solar charging lowers it
+    pub energy_wh: f32,
+}
+
+/// Lap number, time in seconds, average speed and energy, like
+/// `{"average_speed_kmh": 9.8, "energy_wh": 21.3, "lap": 3, "time": 83.4}`
+#[cfg(feature = "serde")]
+impl serde::Serialize for LapSummary {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("LapSummary", 4)?;
+        state.serialize_field("average_speed_kmh", &self.average_speed_kmh)?;
+        state.serialize_field("energy_wh", &self.energy_wh)?;
+        state.serialize_field("lap", &self.lap)?;
+        state.serialize_field("time", &(self.time.as_millis() as f32 / 1000.0))?;
+        state.end()
+    }
+}
+
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LapHistory {
+    #[cfg_attr(feature = "serde", serde(skip))]
+    speed_sum_kmh: f32,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    speed_count: u32,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    energy_wh: f32,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    last_power: Option<Instant>,
+    last: Option<LapSummary>,
+    /// The quickest lap
+    best: Option<LapSummary>,
+    /// The lap completed since the application last took it
+    #[cfg_attr(feature = "serde", serde(skip))]
+    completed: Option<LapSummary>,
+}
+
+impl LapHistory {
+    pub fn last(&self) -> Option<&LapSummary> {
+        self.last.as_ref()
+    }
+
+    pub fn best(&self) -> Option<&LapSummary> {
+        self.best.as_ref()
+    }
+
+    /// The lap completed since the last call, for the application to publish it
+    pub fn take_completed(&mut self) -> Option<LapSummary> {
+        self.completed.take()
+    }
+
+    pub fn update_speed(&mut self, speed_kmh: f32) {
+        if speed_kmh.is_finite() {
+            self.speed_sum_kmh += speed_kmh;
+            self.speed_count += 1;
+        }
+    }
+
+    /// Called with the power in W taken from the battery, negative while charging
+    pub fn update_power(&mut self, power: f32) {
+        self.update_power_at(power, time::now());
+    }
+
+    fn update_power_at(&mut self, power: f32, now: Instant) {
+        if let Some(last_power) = self.last_power.replace(now) {
+            let elapsed = now.duration_since(last_power);
+            if power.is_finite() && elapsed <= MAX_POWER_INTERVAL {
+                self.energy_wh += power * elapsed.as_millis() as f32 / 3_600_000.0;
+            }
+        }
+    }
+
+    /// The race started, what happened before isn't part of a lap
+    pub fn start_lap(&mut self) {
+        self.speed_sum_kmh = 0.0;
+        self.speed_count = 0;
+        self.energy_wh = 0.0;
+    }
+
+    /// Summarize the lap in progress as lap number `lap`, which took `time`, and start the next
+    pub fn complete_lap(&mut self, lap: u16, time: Duration) {
+        let summary = LapSummary {
+            lap,
+            time,
+            average_speed_kmh: self.speed_sum_kmh / self.speed_count as f32,
+            energy_wh: self.energy_wh,
+        };
+        if self.best.is_none_or(|best| time < best.time) {
+            self.best = Some(summary);
+        }
+        self.last = Some(summary);
+        self.completed = Some(summary);
+        self.start_lap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn last_and_best_lap() {
+        let mut history = LapHistory::default();
+        let start = Instant::now();
+        let at = |seconds| start + Duration::from_secs(seconds);
+
+        // Before the start of the race
+        history.update_speed(3.0);
+        history.start_lap();
+
+        history.update_speed(10.0);
+        history.update_speed(12.0);
+        history.update_power_at(1800.0, at(0));
+        history.update_power_at(1800.0, at(2));
+        // The BMS was lost for a while
+        history.update_power_at(1800.0, at(60));
+        history.update_power_at(-3600.0, at(61));
+        history.complete_lap(1, Duration::from_secs(90));
+
+        let last = *history.last().unwrap();
+        assert_eq!(last.average_speed_kmh, 11.0);
+        assert!((last.energy_wh - 0.0).abs() < 1e-3);
+        assert_eq!(history.take_completed(), Some(last));
+        assert_eq!(history.take_completed(), None);
+
+        history.update_speed(9.0);
+        history.complete_lap(2, Duration::from_secs(95));
+        assert_eq!(history.last().unwrap().lap, 2);
+        assert_eq!(history.last().unwrap().average_speed_kmh, 9.0);
+        assert_eq!(history.best().unwrap().lap, 1);
+
+        history.complete_lap(3, Duration::from_secs(80));
+        assert_eq!(history.best().unwrap().lap, 3);
+        assert!(history.best().unwrap().average_speed_kmh.is_nan());
+    }
+}
@@ -0,0 +1,51 @@
+//! Positions and sizes of the sections of the main page. The defaults are the layout of the
+//! displays, the simulator reads them from a file with `--layout` to try another one without a
+//! new build.
+
+/// Layout of the main page, in pixels of the 800x480 screen unless told otherwise
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct Layout {
+    /// Header line of the motor driver and battery sections
+    pub sections_top: i32,
+    pub motor_driver_left: i32,
+    /// Column of the values of the motor driver section
+    pub motor_driver_values: i32,
+    pub battery_left: i32,
+    /// Right end of the values of the battery section
+    pub battery_right: i32,
+    pub cell_bar_height: i32,
+    pub cell_bar_width: i32,
+    /// Distance between the left edges of two cell bars of a 14 cell pack, a 16 cell pack fits
+    /// its bars into the same width
+    pub cell_spacing: i32,
+    /// Cell voltage in V of an empty bar
+    pub cell_bar_min: f32,
+    /// Cell voltage in V of a full bar
+    pub cell_bar_max: f32,
+    pub height_bar_height: i32,
+    pub height_bar_width: i32,
+    /// Bottom of the bars of the height sensors
+    pub height_bar_bottom: i32,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            sections_top: 160,
+            motor_driver_left: 15,
+            motor_driver_values: 250,
+            battery_left: 430,
+            battery_right: 790,
+            cell_bar_height: 80,
+            cell_bar_width: 10,
+            cell_spacing: 28,
+            cell_bar_min: 2.5,
+            cell_bar_max: 4.2,
+            height_bar_height: 200,
+            height_bar_width: 12,
+            height_bar_bottom: 355,
+        }
+    }
+}
@@ -1,9 +1,90 @@
 #![cfg_attr(not(test), no_std)]
 
+#[cfg(all(feature = "std", not(test)))]
+extern crate std;
+
+mod aggregate;
+mod alarm_history;
+mod attitude;
+mod banner;
+mod cell_frames;
+mod cell_temperatures;
+mod charge_check;
+mod clock_check;
+mod countdown;
+mod exhibition;
+mod filter;
+mod imbalance;
+mod lap_counter;
+mod lap_history;
+mod layout;
+mod node_inventory;
+mod parked;
+mod power_flow;
+mod profile;
+mod range;
+#[cfg(test)]
+mod recording_target;
+mod refresh;
+#[cfg(feature = "image")]
+mod render_image;
+mod render_stats;
+mod reserve;
+mod screensaver;
+mod soc_correction;
+mod soc_source;
+mod solar;
+mod status_led;
+mod throttle_curve;
+mod throttle_faults;
 mod time;
+mod trip;
+
+pub use aggregate::{Aggregate, AggregatePolicy, Aggregated};
+pub use alarm_history::{ActiveAlarms, Alarm, AlarmEvent, AlarmHistory, ALARM_HISTORY_LENGTH};
+pub use banner::Banner;
+pub use cell_frames::{
+    frame_of_cell, CellVoltageFrames, CELL_VOLTAGE_FRAMES, DEFAULT_CELLS, MAX_CELLS,
+};
+pub use cell_temperatures::{CellTemperatureMap, SensorGroup};
+pub use charge_check::ChargeCrossCheck;
+pub use clock_check::{ClockCheck, DEFAULT_CLOCK_DIVERGENCE};
+pub use countdown::{energy_budget_per_minute, RaceCountdown};
+pub use exhibition::{
+    co2_avoided_kg, grid_value_eur, household_hours, SolarToday, GRID_CO2_KG_PER_KWH,
+    GRID_PRICE_EUR_PER_KWH, HOUSEHOLD_KWH_PER_DAY,
+};
+pub use filter::{Ema, FilterChain, Median, SlewLimiter, MAX_MEDIAN_WINDOW};
+pub use imbalance::{CellImbalance, DEFAULT_IMBALANCE_THRESHOLD};
+pub use lap_counter::{FinishLine, LapCounter, Position};
+pub use lap_history::{LapHistory, LapSummary};
+pub use layout::Layout;
+pub use node_inventory::NodeInventory;
+pub use parked::{ParkedMode, DASHBOARD_URL_LEN, DEFAULT_PARKED_AFTER};
+pub use profile::{PageSelection, Profile};
+pub use range::{estimated_range_km, RangeTrend, TimeToEmptyEstimate, Trend};
+pub use refresh::RefreshPolicy;
+#[cfg(feature = "image")]
+pub use render_image::{render_page_to_image, render_to_image, DISPLAY_SIZE};
+pub use render_stats::{draw_page_measured, RenderMonitor, RenderStats, DEFAULT_FRAME_BUDGET};
+pub use reserve::{Economy, ReserveMode, DEFAULT_RESERVE_STATE_OF_CHARGE};
+pub use screensaver::{draw_screensaver, Screensaver, DEFAULT_SCREENSAVER_AFTER};
+pub use soc_correction::{CorrectionPoint, SocCorrection, MAX_CORRECTION_POINTS};
+pub use soc_source::{
+    state_of_charge_at_rest, SocSelection, SocSource, DEFAULT_SOC_DISAGREEMENT, REST_CURRENT_A,
+    SOC_SOURCES,
+};
+pub use solar::{underperforming_panels, PanelId, PanelInfo, SolarPanels, BOAT_PANELS, MAX_PANELS};
+pub use status_led::{LedAlarm, LedStatus, Leds};
+pub use throttle_faults::{FaultStatistics, ThrottleFault, ThrottleFaults, MOTOR_ACTIVE_POWER_W};
+#[cfg(feature = "std")]
+pub use time::VirtualClock;
+pub use time::{Clock, Instant, SystemClock};
+pub use trip::TripCounters;
 
 use core::net::Ipv4Addr;
 
+use aggregate::{draw_partial_marker, PARTIAL_MARKER_WIDTH};
 use embedded_graphics::{
     image::Image,
     mono_font::{
@@ -15,22 +96,59 @@ use embedded_graphics::{
     primitives::{Line, PrimitiveStyle, Rectangle},
     text::{Alignment, Text},
 };
+use eoi_can_decoder::current::CurrentConvention;
 use eoi_can_decoder::{
-    BatteryState, ChargeState, DischargeState, EoiBattery, EoiCanData, GnssData, GnssDateTime,
-    HeightSensorData, MpptChannel, MpptInfo, TemperatureData, ThrottleData, ThrottleErrors,
-    VescData,
+    BatteryState, ChargeState, DischargeState, DisplayControl, EoiBattery, EoiCanData, GnssData,
+    GnssDateTime, HeightSensorData, MpptChannel, MpptInfo, NodeType, PeripheralConsumer,
+    TemperatureData, ThrottleConfig, ThrottleData, ThrottleErrors, VescFault, VescMessage,
+    MAX_MPPT_DEVICES, MAX_VESC_CONTROLLERS,
 };
-use heapless::String;
-use time::{Duration, Instant};
-use tinybmp::Bmp; // Import EoICanData from the appropriate module
+use heapless::{FnvIndexMap, String};
+use time::Duration;
 
+/// Most values are sent several times a second
 const DISPLAY_VALUE_TIMEOUT: Duration = Duration::from_secs(5);
+/// The GNSS values are sent once a second
+const GNSS_TIMEOUT: Duration = Duration::from_secs(3);
+/// The BMS sends the cell voltages 10 times a second
+const CELL_VOLTAGE_TIMEOUT: Duration = Duration::from_secs(1);
+/// Temperatures in °C a sensor of the battery can read, one that isn't connected reads far
+/// outside of them
+const BATTERY_TEMPERATURE_RANGE: core::ops::RangeInclusive<i8> = -40..=100;
+/// Temperature sensors the BMS has inputs for, 4 more are sent in their own frame
+const MAX_BATTERY_TEMPERATURES: usize = 8;
+/// Height of the row of the cell temperatures under the cell bars
+const CELL_TEMPERATURE_ROW: i32 = 8;
+/// Largest power in W of a peripheral consumer shown, more is shown as this
+const MAX_PERIPHERAL_POWER_W: f32 = 9_999.0;
+/// Largest speed in km/h in the speed readout, more is shown as this
+const MAX_SPEED_KMH: f32 = 999.9;
+/// Largest minimum, maximum or average shown by the statistics overlay
+const MAX_STATISTIC: f32 = 99_999.0;
+/// Time constant of the smoothing of the GNSS speed, the speed is sent up to 5 times a second
+/// while moving and every 2 seconds when standing still
+const SPEED_SMOOTHING: Duration = Duration::from_millis(400);
+/// The temperature sensors of the VESC read single spikes when the motor draws a lot of current
+const TEMPERATURE_MEDIAN_WINDOW: usize = 3;
+/// The MPPTs only report every few seconds
+const MPPT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Throttle changes smaller than this in % are noise of the hall sensor
+pub const THROTTLE_DEAD_BAND: f32 = 0.5;
+/// Frames in a row a state of the BMS or an error flag has to be sent before it's shown, so a
+/// single corrupted frame doesn't switch the state or flap an alarm
+pub const STATE_DEBOUNCE_FRAMES: u8 = 2;
 
-mod built_info {
+/// Version and git commit of the build, also for the firmware of the display to identify itself
+pub mod built_info {
     // The file has been placed there by the build script.
     include!(concat!(env!("OUT_DIR"), "/built.rs"));
 }
 
+/// Bitmaps and fonts of `assets/`, converted by the build script
+pub mod assets {
+    include!(concat!(env!("OUT_DIR"), "/assets.rs"));
+}
+
 #[derive(Debug)]
 #[cfg_attr(
     feature = "defmt",
@@ -39,16 +157,165 @@ mod built_info {
 pub struct DisplayValue<T> {
     value: Option<T>,
     last_updated: Instant,
+    timeout: Duration,
+    /// Converts the values for the statistics, `None` when they aren't kept
+    #[cfg(feature = "statistics")]
+    to_statistic: Option<fn(&T) -> f32>,
+    #[cfg(feature = "statistics")]
+    statistics: Option<Statistics>,
+    filter: Filter<T>,
+}
+
+/// What a new value has to do before it replaces the shown one, see
+/// [`DisplayValue::with_dead_band`], [`DisplayValue::with_debounce`] and
+/// [`DisplayValue::with_filter`]
+#[derive(Debug)]
+enum Filter<T> {
+    None,
+    DeadBand {
+        band: f32,
+        to_number: fn(&T) -> f32,
+    },
+    Debounce {
+        frames: u8,
+        same: fn(&T, &T) -> bool,
+        /// The differing value and how often it was received in a row
+        pending: Option<(T, u8)>,
+    },
+    Chain {
+        chain: FilterChain,
+        /// The value run through the chain, received the duration after the one before
+        apply: fn(&mut FilterChain, &T, Duration) -> T,
+    },
+}
+
+#[cfg(feature = "defmt")]
+impl<T> defmt::Format for Filter<T> {
+    fn format(&self, fmt: defmt::Formatter) {
+        match self {
+            Filter::None => defmt::write!(fmt, "None"),
+            Filter::DeadBand { band, .. } => defmt::write!(fmt, "DeadBand({})", band),
+            Filter::Debounce { frames, .. } => defmt::write!(fmt, "Debounce({})", frames),
+            Filter::Chain { chain, .. } => defmt::write!(fmt, "Chain({})", chain),
+        }
+    }
 }
 
 impl<T> DisplayValue<T> {
+    /// A value that goes stale when it isn't updated within `timeout`, for values sent slower or
+    /// quicker than most
+    pub fn with_timeout(timeout: Duration) -> Self {
+        Self {
+            value: None,
+            last_updated: time::now(), // We need to set something as initial value, will be updated when first value is set
+            timeout,
+            #[cfg(feature = "statistics")]
+            to_statistic: None,
+            #[cfg(feature = "statistics")]
+            statistics: None,
+            filter: Filter::None,
+        }
+    }
+
+    /// Take a new value, unless the dead band or debounce of the value hold it back, or run it
+    /// through the filters of the value. A held back value still keeps the shown one from going
+    /// stale, and counts for the statistics. After a stale value the new one is always taken, and
+    /// the filters start over with it
     pub fn update(&mut self, value: T) {
-        self.value = Some(value);
-        self.last_updated = Instant::now();
+        #[cfg(feature = "statistics")]
+        if let Some(to_statistic) = self.to_statistic {
+            let statistic = to_statistic(&value);
+            match self.statistics.as_mut() {
+                Some(statistics) => statistics.add(statistic),
+                None => self.statistics = Statistics::new(statistic),
+            }
+        }
+        let valid = self.is_valid();
+        let elapsed = time::since(self.last_updated);
+        self.last_updated = time::now();
+
+        let Some(shown) = self.value.as_ref().filter(|_| valid) else {
+            match &mut self.filter {
+                Filter::Debounce { pending, .. } => *pending = None,
+                Filter::Chain { chain, apply } => {
+                    chain.reset();
+                    self.value = Some(apply(chain, &value, Duration::from_ticks(0)));
+                    return;
+                }
+                Filter::None | Filter::DeadBand { .. } => {}
+            }
+            self.value = Some(value);
+            return;
+        };
+        match &mut self.filter {
+            Filter::None => self.value = Some(value),
+            Filter::DeadBand { band, to_number } => {
+                // NaN is never within the band
+                let within = (to_number(&value) - to_number(shown)).abs() < *band;
+                if !within {
+                    self.value = Some(value);
+                }
+            }
+            Filter::Debounce {
+                frames,
+                same,
+                pending,
+            } => {
+                if same(shown, &value) {
+                    *pending = None;
+                    return;
+                }
+                let count = match pending {
+                    Some((pending, count)) if same(pending, &value) => *count + 1,
+                    _ => 1,
+                };
+                if count >= *frames {
+                    *pending = None;
+                    self.value = Some(value);
+                } else {
+                    *pending = Some((value, count));
+                }
+            }
+            Filter::Chain { chain, apply } => self.value = Some(apply(chain, &value, elapsed)),
+        }
+    }
+
+    /// Minimum, maximum and average since the start or [`DisplayValue::reset_statistics`], `None`
+    /// when no value was received, the value doesn't keep statistics or the `statistics` feature
+    /// is off
+    pub fn statistics(&self) -> Option<&Statistics> {
+        #[cfg(feature = "statistics")]
+        return self.statistics.as_ref();
+        #[cfg(not(feature = "statistics"))]
+        None
+    }
+
+    /// Start a new session of the statistics
+    pub fn reset_statistics(&mut self) {
+        #[cfg(feature = "statistics")]
+        {
+            self.statistics = None;
+        }
     }
 
     pub fn is_valid(&self) -> bool {
-        self.value.is_some() && self.last_updated.elapsed() < DISPLAY_VALUE_TIMEOUT
+        self.value.is_some() && time::since(self.last_updated) < self.timeout
+    }
+
+    /// Known, but not updated within the timeout
+    pub fn is_stale(&self) -> bool {
+        self.value.is_some() && !self.is_valid()
+    }
+
+    /// The last value, even when it is stale
+    pub fn last(&self) -> Option<&T> {
+        self.value.as_ref()
+    }
+
+    /// The last value and how long ago it was received, even when it is stale. `None` only when
+    /// no value was ever received
+    pub fn get_stale(&self) -> Option<(&T, Duration)> {
+        Some((self.value.as_ref()?, time::since(self.last_updated)))
     }
 
     pub fn get(&self) -> Option<&T> {
@@ -60,66 +327,612 @@ impl<T> DisplayValue<T> {
     }
 }
 
+impl<T: Statistic> DisplayValue<T> {
+    /// Keep the minimum, maximum and average of the values, only with the `statistics` feature
+    /// as it takes RAM for every value
+    pub fn with_statistics(self) -> Self {
+        #[cfg(feature = "statistics")]
+        return Self {
+            to_statistic: Some(|value: &T| value.to_statistic()),
+            ..self
+        };
+        #[cfg(not(feature = "statistics"))]
+        self
+    }
+}
+
+impl<T: Statistic> DisplayValue<T> {
+    /// Keep showing the value while new ones differ less than `band` from it, like the noise of
+    /// a sensor, so a slow display isn't refreshed for nothing
+    pub fn with_dead_band(self, band: f32) -> Self {
+        Self {
+            filter: Filter::DeadBand {
+                band,
+                to_number: |value: &T| value.to_statistic(),
+            },
+            ..self
+        }
+    }
+}
+
+impl<T: PartialEq> DisplayValue<T> {
+    /// Only show a different value once it was received `frames` times in a row, so a single
+    /// corrupted frame doesn't flap a state or an alarm
+    pub fn with_debounce(self, frames: u8) -> Self {
+        Self {
+            filter: Filter::Debounce {
+                frames,
+                same: T::eq,
+                pending: None,
+            },
+            ..self
+        }
+    }
+}
+
+impl DisplayValue<f32> {
+    /// Show the values run through `chain`, like a median against the spikes of a sensor or an
+    /// average against jitter. NaN isn't filtered, it's shown right away
+    pub fn with_filter(self, chain: FilterChain) -> Self {
+        Self {
+            filter: Filter::Chain {
+                chain,
+                apply: |chain, value, elapsed| chain.apply(*value, elapsed),
+            },
+            ..self
+        }
+    }
+
+    /// Show the values smoothed exponentially with `time_constant`, to calm a jittery signal, see
+    /// [`Ema`]
+    pub fn with_smoothing(self, time_constant: Duration) -> Self {
+        self.with_filter(FilterChain::new().ema(time_constant))
+    }
+}
+
+/// Numbers [`DisplayValue::with_statistics`] can keep statistics of
+pub trait Statistic {
+    fn to_statistic(&self) -> f32;
+}
+
+macro_rules! impl_statistic {
+    ($($number:ty),*) => {
+        $(impl Statistic for $number {
+            fn to_statistic(&self) -> f32 {
+                *self as f32
+            }
+        })*
+    };
+}
+impl_statistic!(f32, i8, u8, i16, u16, i32, u32);
+
+/// Minimum, maximum and average of the values of a session, NaN values are left out
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Statistics {
+    pub min: f32,
+    pub max: f32,
+    sum: f32,
+    count: u32,
+}
+
+impl Statistics {
+    #[cfg(feature = "statistics")]
+    fn new(value: f32) -> Option<Self> {
+        (!value.is_nan()).then_some(Self {
+            min: value,
+            max: value,
+            sum: value,
+            count: 1,
+        })
+    }
+
+    #[cfg(feature = "statistics")]
+    fn add(&mut self, value: f32) {
+        if value.is_nan() {
+            return;
+        }
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.sum += value;
+        self.count += 1;
+    }
+
+    pub fn average(&self) -> f32 {
+        self.sum / self.count as f32
+    }
+}
+
+/// Stale values are serialized as `null`, just like they aren't shown on the display
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for DisplayValue<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.get().serialize(serializer)
+    }
+}
+
 impl<T> Default for DisplayValue<T> {
+    fn default() -> Self {
+        Self::with_timeout(DISPLAY_VALUE_TIMEOUT)
+    }
+}
+
+/// Values reported by a single VESC motor controller
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MotorData {
+    pub battery_voltage: DisplayValue<f32>,
+    pub battery_current: DisplayValue<f32>,
+    pub current: DisplayValue<f32>,
+    pub duty_cycle: DisplayValue<f32>,
+    pub rpm: DisplayValue<i32>,
+    pub fet_temperature: DisplayValue<f32>,
+    pub temperature: DisplayValue<f32>,
+    /// Inputs of the controller in V, for the commissioning page
+    pub adc1: DisplayValue<f32>,
+    pub adc2: DisplayValue<f32>,
+    pub adc3: DisplayValue<f32>,
+    pub ppm: DisplayValue<f32>,
+    pub fault: DisplayValue<VescFault>,
+    /// The new fault code when it changed and the application didn't take it yet
+    #[cfg_attr(feature = "serde", serde(skip))]
+    fault_changed: Option<VescFault>,
+}
+
+impl Default for MotorData {
     fn default() -> Self {
         Self {
-            value: None,
-            last_updated: Instant::now(), // We need to set something as initial value, will be updated when first value is set
+            battery_voltage: DisplayValue::default(),
+            battery_current: DisplayValue::default(),
+            current: DisplayValue::default(),
+            duty_cycle: DisplayValue::default(),
+            rpm: DisplayValue::default(),
+            fet_temperature: DisplayValue::default()
+                .with_filter(FilterChain::new().median(TEMPERATURE_MEDIAN_WINDOW)),
+            temperature: DisplayValue::default()
+                .with_filter(FilterChain::new().median(TEMPERATURE_MEDIAN_WINDOW)),
+            adc1: DisplayValue::default(),
+            adc2: DisplayValue::default(),
+            adc3: DisplayValue::default(),
+            ppm: DisplayValue::default(),
+            fault: DisplayValue::default(),
+            fault_changed: None,
+        }
+    }
+}
+
+impl MotorData {
+    /// Called with the fault code broadcast by the controller
+    pub fn update_fault(&mut self, fault: VescFault) {
+        if self.fault.last().copied().unwrap_or_default() != fault {
+            self.fault_changed = Some(fault);
         }
+        self.fault.update(fault);
+    }
+
+    /// The fault the controller reports, `None` when it drives the motor or stopped broadcasting
+    pub fn active_fault(&self) -> Option<VescFault> {
+        self.fault.get().copied().filter(VescFault::is_fault)
+    }
+
+    /// The new fault code once after it changed, [`VescFault::None`] when it was cleared
+    pub fn take_fault_change(&mut self) -> Option<VescFault> {
+        self.fault_changed.take()
+    }
+
+    pub fn battery_power(&self) -> f32 {
+        self.battery_voltage.get().unwrap_or(&f32::NAN)
+            * self.battery_current.get().unwrap_or(&f32::NAN)
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct DisplayData {
     pub speed_kmh: DisplayValue<f32>,
+    /// Speed through water of the paddle wheel
+    pub water_speed_kmh: DisplayValue<f32>,
+    pub water_speed_pulse_frequency: DisplayValue<f32>,
+    /// Attitude of the hull from the IMU in degrees, see [`eoi_can_decoder::ImuAttitude`]
+    pub imu_pitch: DisplayValue<f32>,
+    pub imu_roll: DisplayValue<f32>,
+    /// m/s, only updated by an IMU which estimates the heave
+    pub imu_heave_rate: DisplayValue<f32>,
+    /// The nodes on the bus with their firmware
+    pub node_inventory: NodeInventory,
     pub gnss_fix: DisplayValue<bool>,
+    pub gnss_fix_mode: DisplayValue<u8>,
+    pub gnss_sats_used: DisplayValue<u8>,
+    pub gnss_altitude: DisplayValue<f32>,
+    pub gnss_horizontal_error: DisplayValue<f32>,
+    /// State of charge of the source picked by [`SocSelection`]
     pub battery_state_of_charge: DisplayValue<f32>,
+    /// Sources of the state of charge, their order and whether they disagree
+    pub soc_selection: SocSelection,
+    /// Minutes until the battery is empty, the estimate of the BMS while it sends one, otherwise
+    /// estimated from the falling state of charge
     pub battery_time_to_empty: DisplayValue<u16>,
-    pub battery_cell_voltages: [DisplayValue<f32>; 14],
+    /// Minutes until the battery is full, only sent by a BMS with its own estimate
+    pub battery_time_to_full: DisplayValue<u16>,
+    /// Time to empty as the BMS reports it, before the [`SocCorrection`]. The local estimate is
+    /// only taken while it is stale
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub bms_time_to_empty: DisplayValue<u16>,
+    /// [`DisplayData::battery_net_power`] taken with every current of the BMS, only for its
+    /// statistics
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub battery_net_power_samples: DisplayValue<f32>,
+    /// One per cell of the pack, 14 until the frame of cells 15 and 16 shows a 16 cell pack
+    pub battery_cell_voltages: heapless::Vec<DisplayValue<f32>, MAX_CELLS>,
+    /// Which frames of the cell voltages are missing from the latest cycle
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub battery_cell_voltage_frames: CellVoltageFrames,
     pub battery_current_pack: DisplayValue<f32>,
     pub battery_current_in: DisplayValue<f32>,
     pub battery_current_out_motor: DisplayValue<f32>,
     pub battery_current_out_peripherals: DisplayValue<f32>,
+    /// Current of every consumer of the peripheral power monitor, empty without one
+    pub peripheral_currents: FnvIndexMap<PeripheralConsumer, DisplayValue<f32>, 4>,
     pub battery_voltage: DisplayValue<f32>,
-    pub battery_temperatures: [DisplayValue<i8>; 4],
+    /// One per sensor of the pack, up to the last one reading a temperature in range
+    pub battery_temperatures: heapless::Vec<DisplayValue<i8>, MAX_BATTERY_TEMPERATURES>,
     pub battery_uptime_ms: DisplayValue<u32>,
     pub battery_error_flags: DisplayValue<u32>,
     pub battery_balancing_status: DisplayValue<u16>,
     pub battery_state: DisplayValue<BatteryState>,
     pub battery_charge_state: DisplayValue<ChargeState>,
     pub battery_discharge_state: DisplayValue<DischargeState>,
-    pub motor_battery_voltage: DisplayValue<f32>,
-    pub motor_battery_current: DisplayValue<f32>,
-    pub motor_current: DisplayValue<f32>,
-    pub motor_duty_cycle: DisplayValue<f32>,
-    pub motor_rpm: DisplayValue<i32>,
-    pub motor_fet_temperature: DisplayValue<f32>,
-    pub motor_temperature: DisplayValue<f32>,
+    pub motors: FnvIndexMap<u8, MotorData, MAX_VESC_CONTROLLERS>, // indexed by VESC controller ID
     pub throttle_value: DisplayValue<f32>,
     pub throttle_errors: DisplayValue<ThrottleErrors>,
-    pub mppt_panel_info: [DisplayValue<(f32, f32, f32)>; 11], // (Power, Voltage, Current)
+    pub throttle_gain: DisplayValue<u8>,
+    pub throttle_raw_angle: DisplayValue<i16>,
+    pub throttle_raw_deadman: DisplayValue<i16>,
+    pub throttle_config: DisplayValue<ThrottleConfig>,
+    /// Deadman and impedance faults of the throttle over the session
+    pub throttle_faults: ThrottleFaults,
+    /// What the throttle commands the VESC, only one of them at a time by the control type
+    pub throttle_to_vesc_duty_cycle: DisplayValue<f32>,
+    pub throttle_to_vesc_current: DisplayValue<f32>,
+    pub throttle_to_vesc_rpm: DisplayValue<f32>,
+    /// By the MPPT channel the panel is wired to, see [`SolarPanels`]
+    pub mppt_panel_info: SolarPanels,
+    /// Output current of each MPPT in A, indexed by its node ID
+    pub mppt_output_current: [DisplayValue<f32>; MAX_MPPT_DEVICES],
+    pub charge_check: ChargeCrossCheck,
     pub charging_disabled: DisplayValue<bool>,
+    /// UTC time of the GNSS receiver
     pub time: DisplayValue<GnssDateTime>,
+    /// Offset of the race-local time to UTC in minutes
+    pub time_utc_offset: DisplayValue<i16>,
+    /// Alarm for the clock of the application diverging from the GNSS time
+    pub clock_check: ClockCheck,
     pub ip_address: DisplayValue<Ipv4Addr>,
+    /// Signal strength of the WiFi in dBm
+    pub wifi_level_dbm: DisplayValue<f32>,
+    /// Whether frames are received from the CAN interface, only known on Linux
+    pub can_interface_up: DisplayValue<bool>,
+    /// Latest fault of the error frames on the CAN bus, only known on Linux
+    pub can_bus_fault: DisplayValue<&'static str>,
     pub display_state_of_charge: DisplayValue<f32>,
     pub display_is_charging: DisplayValue<bool>,
     pub height_sensor_front_left: DisplayValue<u16>,
     pub height_sensor_front_right: DisplayValue<u16>,
     pub temperature_height_sensors_controller: DisplayValue<i16>,
     pub temperature_rudder_controller: DisplayValue<i16>,
+    pub lap_counter: LapCounter,
+    /// Summaries of the last and the best lap, for the strategy page
+    pub lap_history: LapHistory,
+    /// Countdown to the end of the race set by the chase car
+    pub race_countdown: RaceCountdown,
+    /// Updated by the application, which knows how much time passed
+    pub trip: TripCounters,
+    /// Solar energy of the trip at the start of the local day, for the exhibition page
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub solar_today: SolarToday,
+    /// Usable fraction of the state of charge by pack temperature, for the time to empty, the
+    /// range and the energy budget of the race
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub soc_correction: SocCorrection,
+    /// Cells of every battery temperature sensor, for the temperatures under the cell bars
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub cell_temperature_map: CellTemperatureMap,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub time_to_empty_estimate: TimeToEmptyEstimate,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub range_trend: RangeTrend,
+    /// Economy layout below the reserve state of charge
+    pub reserve: ReserveMode,
+    /// QR code of the dashboard and the summary of the session while standing still
+    pub parked: ParkedMode,
+    /// Alarm for a sustained difference between the cell voltages
+    pub cell_imbalance: CellImbalance,
+    /// Raised and cleared alarms, served on its own by the status server
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub alarm_history: AlarmHistory,
+    /// Message of the chase car
+    pub banner: Banner,
+    /// Whether the shown page has the minimum, maximum and average under its readouts, set by the
+    /// application from [`PageSelection::shows_statistics`]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub statistics_overlay: bool,
+    /// Page the chase car asked for, the application takes it to switch to it
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub requested_page: Option<Page>,
+    /// Profile the chase car asked for, the application takes it to switch to it
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub requested_profile: Option<Profile>,
+    /// Frame times of the display, updated by the application
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub render: RenderMonitor,
+    /// Layout of the main page, set by the application
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub layout: Layout,
+    /// How the net power handles stale currents or voltage, set by the application
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub aggregate_policy: AggregatePolicy,
+}
+
+impl Default for DisplayData {
+    fn default() -> Self {
+        Self {
+            speed_kmh: DisplayValue::with_timeout(GNSS_TIMEOUT)
+                .with_statistics()
+                .with_smoothing(SPEED_SMOOTHING),
+            water_speed_kmh: DisplayValue::default(),
+            water_speed_pulse_frequency: DisplayValue::default(),
+            imu_pitch: DisplayValue::default().with_statistics(),
+            imu_roll: DisplayValue::default().with_statistics(),
+            imu_heave_rate: DisplayValue::default().with_statistics(),
+            node_inventory: NodeInventory::default(),
+            gnss_fix: DisplayValue::with_timeout(GNSS_TIMEOUT),
+            gnss_fix_mode: DisplayValue::with_timeout(GNSS_TIMEOUT),
+            gnss_sats_used: DisplayValue::with_timeout(GNSS_TIMEOUT),
+            gnss_altitude: DisplayValue::with_timeout(GNSS_TIMEOUT),
+            gnss_horizontal_error: DisplayValue::with_timeout(GNSS_TIMEOUT),
+            battery_state_of_charge: DisplayValue::default().with_statistics(),
+            soc_selection: SocSelection::default(),
+            battery_time_to_empty: DisplayValue::default().with_statistics(),
+            battery_time_to_full: DisplayValue::default(),
+            bms_time_to_empty: DisplayValue::default(),
+            battery_net_power_samples: DisplayValue::default().with_statistics(),
+            battery_cell_voltages: (0..DEFAULT_CELLS)
+                .map(|_| DisplayValue::with_timeout(CELL_VOLTAGE_TIMEOUT))
+                .collect(),
+            battery_cell_voltage_frames: CellVoltageFrames::default(),
+            battery_current_pack: DisplayValue::default(),
+            battery_current_in: DisplayValue::default(),
+            battery_current_out_motor: DisplayValue::default(),
+            battery_current_out_peripherals: DisplayValue::default(),
+            peripheral_currents: FnvIndexMap::new(),
+            battery_voltage: DisplayValue::default(),
+            battery_temperatures: Default::default(),
+            battery_uptime_ms: DisplayValue::default(),
+            battery_error_flags: DisplayValue::default().with_debounce(STATE_DEBOUNCE_FRAMES),
+            battery_balancing_status: DisplayValue::default(),
+            battery_state: DisplayValue::default().with_debounce(STATE_DEBOUNCE_FRAMES),
+            battery_charge_state: DisplayValue::default().with_debounce(STATE_DEBOUNCE_FRAMES),
+            battery_discharge_state: DisplayValue::default().with_debounce(STATE_DEBOUNCE_FRAMES),
+            motors: FnvIndexMap::new(),
+            throttle_value: DisplayValue::default().with_dead_band(THROTTLE_DEAD_BAND),
+            throttle_errors: DisplayValue::default().with_debounce(STATE_DEBOUNCE_FRAMES),
+            throttle_gain: DisplayValue::default(),
+            throttle_raw_angle: DisplayValue::default(),
+            throttle_raw_deadman: DisplayValue::default(),
+            throttle_config: DisplayValue::default(),
+            throttle_faults: ThrottleFaults::default(),
+            throttle_to_vesc_duty_cycle: DisplayValue::default(),
+            throttle_to_vesc_current: DisplayValue::default(),
+            throttle_to_vesc_rpm: DisplayValue::default(),
+            mppt_panel_info: SolarPanels::default(),
+            mppt_output_current: core::array::from_fn(|_| DisplayValue::with_timeout(MPPT_TIMEOUT)),
+            charge_check: ChargeCrossCheck::default(),
+            charging_disabled: DisplayValue::default(),
+            time: DisplayValue::with_timeout(GNSS_TIMEOUT),
+            time_utc_offset: DisplayValue::with_timeout(GNSS_TIMEOUT),
+            clock_check: ClockCheck::default(),
+            ip_address: DisplayValue::default(),
+            wifi_level_dbm: DisplayValue::default(),
+            can_interface_up: DisplayValue::default(),
+            can_bus_fault: DisplayValue::default(),
+            display_state_of_charge: DisplayValue::default(),
+            display_is_charging: DisplayValue::default(),
+            height_sensor_front_left: DisplayValue::default(),
+            height_sensor_front_right: DisplayValue::default(),
+            temperature_height_sensors_controller: DisplayValue::default(),
+            temperature_rudder_controller: DisplayValue::default(),
+            lap_counter: LapCounter::default(),
+            lap_history: LapHistory::default(),
+            race_countdown: RaceCountdown::default(),
+            trip: TripCounters::default(),
+            solar_today: SolarToday::default(),
+            soc_correction: SocCorrection::default(),
+            cell_temperature_map: CellTemperatureMap::default(),
+            time_to_empty_estimate: TimeToEmptyEstimate::default(),
+            range_trend: RangeTrend::default(),
+            reserve: ReserveMode::default(),
+            parked: ParkedMode::default(),
+            cell_imbalance: CellImbalance::default(),
+            alarm_history: AlarmHistory::default(),
+            banner: Banner::default(),
+            statistics_overlay: false,
+            requested_page: None,
+            requested_profile: None,
+            render: RenderMonitor::default(),
+            layout: Layout::default(),
+            aggregate_policy: AggregatePolicy::default(),
+        }
+    }
 }
 
 impl DisplayData {
+    /// Power charging the battery in W, `NaN` when not known
+    pub fn battery_input_power(&self) -> f32 {
+        self.battery_voltage.get().unwrap_or(&f32::NAN)
+            * self.battery_current_in.get().unwrap_or(&f32::NAN)
+    }
+
+    /// Power of the motors at the battery in W, negative while they draw from it
+    pub fn battery_motor_power(&self) -> f32 {
+        self.battery_voltage.get().unwrap_or(&f32::NAN)
+            * self.battery_current_out_motor.get().unwrap_or(&f32::NAN)
+    }
+
+    /// Power of the peripherals at the battery in W, negative while they draw from it
+    pub fn battery_peripherals_power(&self) -> f32 {
+        self.battery_voltage.get().unwrap_or(&f32::NAN)
+            * self
+                .battery_current_out_peripherals
+                .get()
+                .unwrap_or(&f32::NAN)
+    }
+
+    /// Power drawn by a consumer of the peripheral power monitor in W, `NaN` when not known
+    pub fn peripheral_power(&self, consumer: PeripheralConsumer) -> f32 {
+        CurrentConvention::DischargePositive.power(
+            *self.battery_voltage.get().unwrap_or(&f32::NAN),
+            *self
+                .peripheral_currents
+                .get(&consumer)
+                .and_then(|current| current.get())
+                .unwrap_or(&f32::NAN),
+        )
+    }
+
+    /// Power flowing into the battery in W, negative while discharging. `NaN` or partial by the
+    /// [`AggregatePolicy`] when some of the currents or the voltage aren't known
+    pub fn battery_net_power(&self) -> f32 {
+        self.battery_net_power_aggregated().value
+    }
+
+    /// [`DisplayData::battery_net_power`] and whether it is partial
+    pub fn battery_net_power_aggregated(&self) -> Aggregated {
+        let mut aggregate = Aggregate::new(self.aggregate_policy);
+        let voltage = aggregate.input(&self.battery_voltage);
+        for current in [
+            &self.battery_current_in,
+            &self.battery_current_out_motor,
+            &self.battery_current_out_peripherals,
+        ] {
+            let current = aggregate.input(current);
+            aggregate.add(voltage * current);
+        }
+        aggregate.finish()
+    }
+
+    /// Power drawn from the battery in W after the solar input, negative while charging
+    pub fn battery_discharge_power(&self) -> f32 {
+        CurrentConvention::DischargePositive.convert(self.battery_net_power())
+    }
+
+    /// Current along the course in km/h, the speed over ground minus the speed through water.
+    /// Positive when the current pushes the boat, `None` without both speeds
+    pub fn current_along_course_kmh(&self) -> Option<f32> {
+        Some(self.speed_kmh.get()? - self.water_speed_kmh.get()?)
+    }
+
+    /// Distance in km until the battery is empty at the current speed and consumption
+    pub fn estimated_range_km(&self) -> Option<f32> {
+        estimated_range_km(
+            self.battery_time_to_empty
+                .get()
+                .map_or(f32::NAN, |&minutes| f32::from(minutes)),
+            *self.speed_kmh.get().unwrap_or(&f32::NAN),
+            self.battery_net_power(),
+        )
+    }
+
+    /// Lowest temperature of the pack in °C, leaving out broken sensors
+    pub fn lowest_battery_temperature(&self) -> Option<i8> {
+        self.battery_temperatures
+            .iter()
+            .filter_map(|temperature| temperature.get().copied())
+            .filter(|temperature| BATTERY_TEMPERATURE_RANGE.contains(temperature))
+            .min()
+    }
+
+    /// Fraction of the state of charge the pack delivers at its lowest temperature, see
+    /// [`SocCorrection`]
+    pub fn usable_fraction(&self) -> f32 {
+        self.soc_correction.usable_fraction(
+            self.lowest_battery_temperature()
+                .map_or(f32::NAN, f32::from),
+        )
+    }
+
+    /// State of charge in % the pack can still deliver at its temperature
+    pub fn usable_state_of_charge(&self) -> Option<f32> {
+        Some(self.battery_state_of_charge.get()? * self.usable_fraction())
+    }
+
+    /// Time left until the end of the race, counted on from the last GNSS time when it is stale
+    pub fn race_time_left(&self) -> Option<Duration> {
+        let (time, age) = self.time.get_stale()?;
+        let remaining = self.race_countdown.remaining(time)?;
+        Some(remaining.checked_sub(age).unwrap_or(Duration::from_secs(0)))
+    }
+
+    /// Update the values from `data` and record the alarms it raised or cleared
     pub fn ingest_eoi_can_data(&mut self, data: EoiCanData) {
+        self.ingest(data);
+        self.parked.update(self.is_parked());
+        self.alarm_history
+            .update(self.active_alarms(), self.time.get().copied());
+    }
+
+    /// Alarms active by the current values
+    pub fn active_alarms(&self) -> ActiveAlarms {
+        let mut alarms = ActiveAlarms::new();
+        let flags = [
+            (self.reserve.is_active(), Alarm::Reserve),
+            (self.cell_imbalance.is_active(), Alarm::CellImbalance),
+            (self.clock_check.is_active(), Alarm::ClockDivergence),
+            (self.charge_check.diverging, Alarm::ChargeCheck),
+            (self.soc_selection.is_disagreeing(), Alarm::SocDisagreement),
+            (
+                matches!(
+                    self.battery_discharge_state.get(),
+                    Some(DischargeState::Error | DischargeState::PreChargeTimeout)
+                ),
+                Alarm::BatteryCutoff,
+            ),
+            (
+                self.throttle_errors
+                    .get()
+                    .is_some_and(ThrottleErrors::has_error),
+                Alarm::Throttle,
+            ),
+        ];
+        for (_, alarm) in flags.into_iter().filter(|(active, _)| *active) {
+            _ = alarms.push(alarm);
+        }
+        for (&controller_id, motor) in &self.motors {
+            if let Some(fault) = motor.active_fault() {
+                _ = alarms.push(Alarm::MotorFault {
+                    controller_id,
+                    fault,
+                });
+            }
+        }
+        alarms
+    }
+
+    fn ingest(&mut self, data: EoiCanData) {
         match data {
             EoiCanData::EoiBattery(eoi_battery) => match eoi_battery {
                 EoiBattery::ChargeAndDischargeCurrent(data) => {
                     self.battery_current_in.update(data.charge_current);
                     self.battery_current_out_motor
                         .update(data.discharge_current);
+                    self.charge_check
+                        .update(&self.mppt_output_current, data.charge_current);
                 }
                 EoiBattery::SocErrorFlagsAndBalancing(data) => {
-                    self.battery_state_of_charge.update(data.state_of_charge);
+                    if let Some(state_of_charge) = self
+                        .soc_selection
+                        .update(SocSource::Bms, data.state_of_charge)
+                    {
+                        self.update_state_of_charge(state_of_charge);
+                    }
                     self.battery_error_flags.update(data.error_flags);
                     self.battery_balancing_status.update(data.balancing_status);
                 }
@@ -127,6 +940,17 @@ impl DisplayData {
                     self.battery_current_out_peripherals
                         .update(data.perri_current);
                     self.battery_current_pack.update(data.pack_current);
+                    self.battery_net_power_samples
+                        .update(self.battery_net_power());
+                    if let Some(state_of_charge) =
+                        self.soc_selection.count_current(data.pack_current)
+                    {
+                        self.update_state_of_charge(state_of_charge);
+                    }
+                    let voltage = *self.battery_voltage.get().unwrap_or(&f32::NAN);
+                    self.lap_history.update_power(
+                        CurrentConvention::DischargePositive.power(voltage, data.pack_current),
+                    );
                 }
                 EoiBattery::CellVoltages1_4(data) => {
                     self.update_cell_voltages(0, data.cell_voltage.as_slice());
@@ -140,114 +964,178 @@ impl DisplayData {
                 EoiBattery::CellVoltages13_14PackAndStack(data) => {
                     self.update_cell_voltages(12, data.cell_voltage.as_slice());
                     self.battery_voltage.update(data.pack_voltage);
+                    if let Some(state_of_charge) = self
+                        .soc_selection
+                        .uses(SocSource::Voltage)
+                        .then(|| self.state_of_charge_at_rest())
+                        .flatten()
+                        .and_then(|estimate| {
+                            self.soc_selection.update(SocSource::Voltage, estimate)
+                        })
+                    {
+                        self.update_state_of_charge(state_of_charge);
+                    }
+                }
+                EoiBattery::CellVoltages15_16(data) => {
+                    self.update_cell_voltages(14, data.cell_voltage.as_slice());
                 }
                 EoiBattery::TemperaturesAndStates(data) => {
-                    for (index, value) in data.temperatures.iter().enumerate() {
-                        self.battery_temperatures[index].update(*value);
-                    }
+                    self.update_battery_temperatures(0, &data.temperatures);
                     self.battery_state.update(data.battery_state);
                     self.battery_charge_state.update(data.charge_state);
                     self.battery_discharge_state.update(data.discharge_state);
                 }
+                EoiBattery::Temperatures5_8(data) => {
+                    self.update_battery_temperatures(4, &data.temperatures);
+                }
                 EoiBattery::BatteryUptime(data) => {
                     self.battery_uptime_ms.update(data.uptime_ms);
                 }
+                EoiBattery::ChargeControlStatus(data) => {
+                    self.charging_disabled.update(data.charging_disabled);
+                }
+                // Only what the BMS reports is shown
+                EoiBattery::ChargeControlCommand(_) => {}
+                EoiBattery::TimeEstimate(data) => {
+                    if let Some(minutes) = data.time_to_empty_min {
+                        self.bms_time_to_empty.update(minutes);
+                        // The BMS doesn't know how much of the charge a cold pack delivers
+                        let usable = (f32::from(minutes) * self.usable_fraction()).round() as u16;
+                        self.battery_time_to_empty.update(usable);
+                    }
+                    if let Some(minutes) = data.time_to_full_min {
+                        self.battery_time_to_full.update(minutes);
+                    }
+                }
             },
 
-            EoiCanData::Throttle(throttle) => {
-                if let ThrottleData::Status(data) = throttle {
+            EoiCanData::Throttle(throttle) => match throttle {
+                ThrottleData::Status(data) => {
                     self.throttle_value.update(data.value);
+                    let motor_power: f32 = self
+                        .motors
+                        .values()
+                        .map(MotorData::battery_power)
+                        .filter(|power| power.is_finite())
+                        .sum();
+                    self.throttle_faults.update(&data.error, motor_power);
                     self.throttle_errors.update(data.error);
+                    self.throttle_gain.update(data.gain);
+                    self.throttle_raw_angle.update(data.raw_angle);
+                    self.throttle_raw_deadman.update(data.raw_deadmen);
                 }
-            }
-
-            EoiCanData::Vesc(vesc) => match vesc {
-                VescData::StatusMessage1 {
-                    rpm,
-                    total_current,
-                    duty_cycle,
-                } => {
-                    self.motor_rpm.update(rpm);
-                    self.motor_current.update(total_current);
-                    self.motor_duty_cycle.update(duty_cycle);
-                }
-                VescData::StatusMessage4 {
-                    fet_temp,
-                    motor_temp,
-                    total_input_current,
-                    current_pid_position: _,
-                } => {
-                    self.motor_battery_current.update(total_input_current);
-                    self.motor_fet_temperature.update(fet_temp);
-                    self.motor_temperature.update(motor_temp);
+                ThrottleData::Config(config) => self.throttle_config.update(config),
+                ThrottleData::ToVescDutyCycle(value) => {
+                    self.throttle_to_vesc_duty_cycle.update(value)
                 }
-                VescData::StatusMessage5 {
-                    input_voltage,
-                    tachometer: _,
-                } => {
-                    self.motor_battery_voltage.update(input_voltage);
-                }
-                _ => {}
+                ThrottleData::ToVescCurrent(value) => self.throttle_to_vesc_current.update(value),
+                ThrottleData::ToVescRpm(value) => self.throttle_to_vesc_rpm.update(value),
             },
+
+            EoiCanData::Vesc(vesc) => {
+                let (controller_id, message) = vesc.into_parts();
+                let Some(motor) = self.motor_mut(controller_id) else {
+                    return; // more controllers on the bus than we can keep track of
+                };
+                match message {
+                    VescMessage::StatusMessage1 {
+                        rpm,
+                        total_current,
+                        duty_cycle,
+                    } => {
+                        motor.rpm.update(rpm);
+                        motor.current.update(total_current);
+                        motor.duty_cycle.update(duty_cycle);
+                    }
+                    VescMessage::StatusMessage4 {
+                        fet_temp,
+                        motor_temp,
+                        total_input_current,
+                        current_pid_position: _,
+                    } => {
+                        motor.battery_current.update(total_input_current);
+                        motor.fet_temperature.update(fet_temp);
+                        motor.temperature.update(motor_temp);
+                    }
+                    VescMessage::StatusMessage5 {
+                        input_voltage,
+                        tachometer: _,
+                    } => {
+                        motor.battery_voltage.update(input_voltage);
+                    }
+                    VescMessage::StatusMessage6 {
+                        adc1,
+                        adc2,
+                        adc3,
+                        ppm,
+                    } => {
+                        motor.adc1.update(adc1);
+                        motor.adc2.update(adc2);
+                        motor.adc3.update(adc3);
+                        motor.ppm.update(ppm);
+                    }
+                    VescMessage::Fault { fault } => motor.update_fault(fault),
+                    _ => {}
+                }
+            }
             EoiCanData::Mppt(mppt_data) => {
-                let (panel_id, channel_power) = match mppt_data {
-                    eoi_can_decoder::MpptData::Id2(MpptInfo::Channel1(MpptChannel::Power(
-                        power,
-                    ))) => (0, power),
-                    eoi_can_decoder::MpptData::Id2(MpptInfo::Channel2(MpptChannel::Power(
-                        power,
-                    ))) => (1, power),
-                    eoi_can_decoder::MpptData::Id2(MpptInfo::Channel3(MpptChannel::Power(
-                        power,
-                    ))) => (2, power),
-
-                    eoi_can_decoder::MpptData::Id5(MpptInfo::Channel0(MpptChannel::Power(
-                        power,
-                    ))) => (3, power),
-                    eoi_can_decoder::MpptData::Id5(MpptInfo::Channel1(MpptChannel::Power(
-                        power,
-                    ))) => (4, power),
-                    eoi_can_decoder::MpptData::Id5(MpptInfo::Channel2(MpptChannel::Power(
-                        power,
-                    ))) => (5, power),
-
-                    eoi_can_decoder::MpptData::Id4(MpptInfo::Channel1(MpptChannel::Power(
-                        power,
-                    ))) => (6, power),
-                    eoi_can_decoder::MpptData::Id4(MpptInfo::Channel3(MpptChannel::Power(
-                        power,
-                    ))) => (7, power),
-
-                    eoi_can_decoder::MpptData::Id6(MpptInfo::Channel2(MpptChannel::Power(
-                        power,
-                    ))) => (8, power),
-                    eoi_can_decoder::MpptData::Id6(MpptInfo::Channel3(MpptChannel::Power(
-                        power,
-                    ))) => (9, power),
-                    eoi_can_decoder::MpptData::Id6(MpptInfo::Channel0(MpptChannel::Power(
-                        power,
-                    ))) => (10, power),
-
-                    _ => return, // not used mppt id or channel
+                if let (id, MpptInfo::Power(power)) = mppt_data.as_parts() {
+                    self.mppt_output_current[usize::from(id)].update(power.current_out);
+                    return;
+                }
+                let (mppt, info) = mppt_data.as_parts();
+                let (channel, power) = match info {
+                    MpptInfo::Channel0(MpptChannel::Power(power)) => (0, power),
+                    MpptInfo::Channel1(MpptChannel::Power(power)) => (1, power),
+                    MpptInfo::Channel2(MpptChannel::Power(power)) => (2, power),
+                    MpptInfo::Channel3(MpptChannel::Power(power)) => (3, power),
+                    _ => return,
                 };
-
-                // update the panel info
-                self.mppt_panel_info[panel_id].update((
-                    channel_power.voltage_in * channel_power.current_in,
-                    channel_power.voltage_in,
-                    channel_power.current_in,
-                ));
+                self.mppt_panel_info.update(
+                    PanelId::new(mppt, channel),
+                    (
+                        power.voltage_in * power.current_in,
+                        power.voltage_in,
+                        power.current_in,
+                    ),
+                );
             }
             EoiCanData::Gnss(gnss) => match gnss {
                 GnssData::GnssSpeedAndHeading(speed_kmh, _) => {
                     self.speed_kmh.update(speed_kmh);
+                    self.lap_history.update_speed(speed_kmh);
+                }
+                GnssData::GnssDateTime(data) => {
+                    let offset = self.time_utc_offset.last().copied().unwrap_or_default();
+                    self.solar_today
+                        .update(&data.with_offset(offset), self.trip.solar_energy_wh);
+                    self.time.update(data);
                 }
-                GnssData::GnssDateTime(data) => self.time.update(data),
                 GnssData::GnssStatus(data) => {
                     self.gnss_fix.update(data.fix != 0);
+                    self.gnss_sats_used.update(data.sats_used);
+                }
+                GnssData::GnssLatitude(latitude) => self.lap_counter.update_latitude(latitude),
+                GnssData::GnssLongitude(longitude) => {
+                    let started = self.lap_counter.race_time().is_some();
+                    if self.lap_counter.update_longitude(longitude) {
+                        if let Some(lap_time) = self.lap_counter.last_lap_time() {
+                            self.lap_history
+                                .complete_lap(self.lap_counter.laps(), lap_time);
+                        }
+                    } else if !started && self.lap_counter.race_time().is_some() {
+                        self.lap_history.start_lap();
+                    }
+                }
+                GnssData::GnssAltitude(altitude) => self.gnss_altitude.update(altitude),
+                GnssData::GnssUtcOffset(offset) => self.time_utc_offset.update(offset),
+                GnssData::GnssTimeSync(_) => {}
+                GnssData::GnssFixQuality(quality) => {
+                    self.gnss_fix_mode.update(quality.mode);
+                    if let Some(error) = quality.horizontal_error {
+                        self.gnss_horizontal_error.update(error);
+                    }
                 }
-                GnssData::GnssLatitude(_) => {}
-                GnssData::GnssLongitude(_) => {}
             },
             EoiCanData::RudderController(_) => {}
             EoiCanData::HeightSensors(height) => match height {
@@ -268,38 +1156,377 @@ impl DisplayData {
                     self.temperature_rudder_controller.update(value);
                 }
             },
+            EoiCanData::PeripheralPower(power) => {
+                if let Some(current) = self.peripheral_currents.get_mut(&power.consumer) {
+                    current.update(power.current);
+                } else {
+                    let mut current = DisplayValue::default();
+                    current.update(power.current);
+                    // Room for every consumer
+                    _ = self.peripheral_currents.insert(power.consumer, current);
+                }
+            }
+            EoiCanData::WaterSpeed(speed) => {
+                self.water_speed_kmh.update(speed.speed_kmh);
+                self.water_speed_pulse_frequency
+                    .update(speed.pulse_frequency);
+            }
+            EoiCanData::Imu(attitude) => {
+                self.imu_pitch.update(attitude.pitch);
+                self.imu_roll.update(attitude.roll);
+                if let Some(heave_rate) = attitude.heave_rate {
+                    self.imu_heave_rate.update(heave_rate);
+                }
+            }
+            EoiCanData::NodeIdentification(identification) => {
+                self.node_inventory.update(identification)
+            }
+            EoiCanData::DisplayHeartbeat(_)
+            | EoiCanData::DisplayLog(_)
+            | EoiCanData::DisplayPanic(_) => {}
+            EoiCanData::DisplayBattery(battery) => {
+                self.display_state_of_charge.update(battery.state_of_charge);
+                self.display_is_charging.update(battery.charging);
+            }
+            EoiCanData::DisplayControl(control) => match control {
+                DisplayControl::Page(page) => self.requested_page = Page::from_index(page),
+                DisplayControl::Message(message) => self.banner.push(message),
+                DisplayControl::Profile(profile) => {
+                    self.requested_profile = Profile::from_index(profile)
+                }
+                DisplayControl::RaceEnd(end_s) => self.race_countdown.set_end(end_s),
+                DisplayControl::TripReset => {
+                    self.trip = TripCounters::default();
+                    self.lap_counter.reset();
+                }
+            },
+            EoiCanData::DisplayAcknowledge(acknowledge) => {
+                self.banner.acknowledged_elsewhere(acknowledge.message_id);
+            }
+        }
+    }
+
+    /// Get the motor data for a controller, adds it when it is seen for the first time
+    pub fn motor_mut(&mut self, controller_id: u8) -> Option<&mut MotorData> {
+        if !self.motors.contains_key(&controller_id) {
+            self.motors
+                .insert(controller_id, MotorData::default())
+                .ok()?;
         }
+        self.motors.get_mut(&controller_id)
+    }
+
+    /// Controller ID and new fault code of a motor controller whose fault changed, once
+    pub fn take_motor_fault_change(&mut self) -> Option<(u8, VescFault)> {
+        self.motors
+            .iter_mut()
+            .find_map(|(id, motor)| Some((*id, motor.take_fault_change()?)))
     }
 
+    /// Called with the voltages of a frame of the BMS, `offset` is the index of its first cell.
+    /// The cells of the frame are added when the pack has more than known so far
     pub fn update_cell_voltages(&mut self, offset: usize, values: &[f32]) {
+        while self.battery_cell_voltages.len() < (offset + values.len()).min(MAX_CELLS) {
+            let _ = self
+                .battery_cell_voltages
+                .push(DisplayValue::with_timeout(CELL_VOLTAGE_TIMEOUT));
+        }
         for (index, value) in values.iter().enumerate() {
-            self.battery_cell_voltages[offset + index].update(*value);
+            if let Some(voltage) = self.battery_cell_voltages.get_mut(offset + index) {
+                voltage.update(*value);
+            }
+        }
+        self.battery_cell_voltage_frames
+            .update(frame_of_cell(offset));
+        if let Some(difference) = self.cell_voltage_difference() {
+            self.cell_imbalance.update(difference);
+        }
+    }
+
+    /// Show the state of charge of the source [`SocSelection`] picked, and update what follows
+    /// from it
+    fn update_state_of_charge(&mut self, state_of_charge: f32) {
+        self.battery_state_of_charge.update(state_of_charge);
+        let usable_fraction = self.usable_fraction();
+        // A cold pack runs empty early, the reserve has to start by what it can still deliver
+        self.reserve.update(state_of_charge * usable_fraction);
+        let estimate = self.soc_selection.selected().and_then(|source| {
+            self.time_to_empty_estimate
+                .update(source, state_of_charge, usable_fraction)
+        });
+        match estimate {
+            Some(minutes) if !self.bms_time_to_empty.is_valid() => {
+                self.battery_time_to_empty.update(minutes);
+            }
+            _ => {}
+        }
+        self.range_trend.update(self.estimated_range_km());
+    }
+
+    /// State of charge by the average cell voltage, `None` while too much current flows for the
+    /// voltage to be near its rest voltage
+    fn state_of_charge_at_rest(&self) -> Option<f32> {
+        if !self
+            .battery_current_pack
+            .get()
+            .is_some_and(|current| current.abs() < REST_CURRENT_A)
+        {
+            return None;
+        }
+        let (sum, count) = self
+            .battery_cell_voltages
+            .iter()
+            .filter_map(|voltage| voltage.get())
+            .fold((0.0, 0), |(sum, count), voltage| (sum + voltage, count + 1));
+        (count > 0).then(|| state_of_charge_at_rest(sum / count as f32))
+    }
+
+    /// Called with the temperatures of the inputs of the BMS from `first` on, the inputs of a
+    /// frame. The pack has sensors up to the last input reading a temperature in range, the ones
+    /// before it are kept even when out of range as that is a broken sensor
+    pub fn update_battery_temperatures(&mut self, first: usize, values: &[i8]) {
+        let sensors = values
+            .iter()
+            .rposition(|value| BATTERY_TEMPERATURE_RANGE.contains(value))
+            .map_or(0, |last| first + last + 1);
+        while self.battery_temperatures.len() < sensors.min(MAX_BATTERY_TEMPERATURES) {
+            let _ = self.battery_temperatures.push(DisplayValue::default());
+        }
+        for (temperature, value) in self.battery_temperatures.iter_mut().skip(first).zip(values) {
+            temperature.update(*value);
         }
     }
+
+    /// Compare the clock of the application, in µs since the Unix epoch, with the GNSS time. Called
+    /// by the application now and then, nothing is compared without a current GNSS time
+    pub fn check_clock(&mut self, clock_utc_us: u64) {
+        let Some((time, age)) = self.time.get_stale().filter(|_| self.time.is_valid()) else {
+            return;
+        };
+        let gnss_utc_us = time.unix_s() * 1_000_000 + age.as_micros() as i64;
+        let divergence = (clock_utc_us as i64 - gnss_utc_us) as f32 / 1_000_000.0;
+        self.clock_check.update(divergence);
+        self.alarm_history
+            .update(self.active_alarms(), self.time.get().copied());
+    }
+
+    /// Difference between the highest and lowest cell voltage, `None` with less than two cells
+    pub fn cell_voltage_difference(&self) -> Option<f32> {
+        let mut valid = self
+            .battery_cell_voltages
+            .iter()
+            .filter_map(|voltage| voltage.get().copied());
+        let first = valid.next()?;
+        let (min, max, count) = valid.fold((first, first, 1), |(min, max, count), voltage| {
+            (min.min(voltage), max.max(voltage), count + 1)
+        });
+        (count > 1).then_some(max - min)
+    }
+
+    /// Whether the BMS balances the cell with index `cell`, a bit per cell of the balancing status
+    pub fn is_cell_balancing(&self, cell: usize) -> bool {
+        self.battery_balancing_status
+            .get()
+            .is_some_and(|status| status & (1 << cell) != 0)
+    }
 }
 
-pub fn draw_display<D, C>(display: &mut D, data: &DisplayData) -> Result<(), D::Error>
+/// Pages the display can switch between
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Page {
+    /// Everything needed while racing
+    #[default]
+    Main,
+    /// Raw values and configuration of the boards, for checking the boat in the harbour
+    Diagnostics,
+    /// Frame rate and frame times, not shown to the pilot
+    Render,
+    /// The last lap compared with the best one
+    Strategy,
+    /// What the throttle commands next to what the motor controllers measure, for calibrating the
+    /// throttle without VESC Tool
+    Commissioning,
+    /// Pitch, roll and heave of the IMU, for trimming the foils
+    Trim,
+    /// Raised and cleared alarms, for reviewing a run at the dock
+    Alarms,
+    /// Only the speed and the state of charge in large figures, readable at a glance
+    Pilot,
+    /// The voltage of every cell in mV with its balancing, for the pit crew
+    Cells,
+    /// The solar energy of today and what it is worth, for visitors at demo events
+    Exhibition,
+    /// The nodes on the bus with their firmware, for the scrutineers and after flashing a node
+    Inventory,
+    /// QR code of the dashboard and the summary of the session, replaces the main page while
+    /// parked
+    Parked,
+    /// Response curve of the throttle with the current operating point and the gain of its
+    /// sensor, for tuning the throttle on the water
+    Throttle,
+}
+
+impl Page {
+    /// Page by its index on the CAN bus, see [`DisplayControl::Page`]
+    pub fn from_index(index: u8) -> Option<Self> {
+        match index {
+            0 => Some(Page::Main),
+            1 => Some(Page::Diagnostics),
+            2 => Some(Page::Render),
+            3 => Some(Page::Strategy),
+            4 => Some(Page::Commissioning),
+            5 => Some(Page::Trim),
+            6 => Some(Page::Alarms),
+            7 => Some(Page::Pilot),
+            8 => Some(Page::Cells),
+            9 => Some(Page::Exhibition),
+            10 => Some(Page::Inventory),
+            11 => Some(Page::Parked),
+            12 => Some(Page::Throttle),
+            _ => None,
+        }
+    }
+
+    /// The page after this one, wrapping around to the main page. Hidden pages are skipped
+    pub fn next(self) -> Self {
+        match self {
+            Page::Main => Page::Diagnostics,
+            Page::Diagnostics
+            | Page::Render
+            | Page::Strategy
+            | Page::Commissioning
+            | Page::Trim
+            | Page::Alarms
+            | Page::Pilot
+            | Page::Cells
+            | Page::Exhibition
+            | Page::Inventory
+            | Page::Parked
+            | Page::Throttle => Page::Main,
+        }
+    }
+}
+
+pub fn draw_page<D, C>(display: &mut D, data: &DisplayData, page: Page) -> Result<(), D::Error>
 where
     D: DrawTarget<Color = C>,
     C: PixelColor + From<BinaryColor>,
 {
-    display.clear(BinaryColor::On.into())?;
-    let mut string_helper: String<64> = String::new();
+    match page {
+        Page::Main if data.reserve.is_active() => reserve::draw_reserve(display, data)?,
+        Page::Main if data.parked.is_active() => parked::draw_parked(display, data)?,
+        Page::Main => draw_display(display, data)?,
+        Page::Diagnostics => draw_diagnostics(display, data)?,
+        Page::Render => draw_render_stats(display, data)?,
+        Page::Strategy => draw_strategy(display, data)?,
+        Page::Commissioning => draw_commissioning(display, data)?,
+        Page::Trim => draw_trim(display, data)?,
+        Page::Alarms => draw_alarms(display, data)?,
+        Page::Pilot => draw_pilot(display, data)?,
+        Page::Cells => draw_cells(display, data)?,
+        Page::Exhibition => exhibition::draw_exhibition(display, data)?,
+        Page::Inventory => draw_inventory(display, data)?,
+        Page::Parked => parked::draw_parked(display, data)?,
+        Page::Throttle => throttle_curve::draw_throttle(display, data)?,
+    }
+    draw_banner(display, data)
+}
 
-    let bmp: Bmp<BinaryColor> =
-        Bmp::from_slice(include_bytes!("../eoi-logo-mark--monochrome-black.bmp")).unwrap();
-    Image::new(&bmp, Point::new(800 - 70, 0)).draw(&mut display.color_converted())?;
+/// Minimum, maximum and average of `value` below its readout centered at `x`, nothing before the
+/// first value or without statistics
+fn draw_statistics<D, C, T>(
+    display: &mut D,
+    value: &DisplayValue<T>,
+    x: i32,
+    decimals: usize,
+    font: MonoTextStyle<'_, C>,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor + From<BinaryColor>,
+{
+    use core::fmt::Write;
 
-    let font_normal_inverted: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
-        .font(&FONT_10X20)
-        .text_color(BinaryColor::On.into())
-        .background_color(BinaryColor::Off.into())
-        .build();
+    let Some(statistics) = value.statistics() else {
+        return Ok(());
+    };
+    // Garbage from a bad frame is clamped, so the line always fits
+    let clamp = |value: f32| value.clamp(-MAX_STATISTIC, MAX_STATISTIC);
+    let mut string_helper: String<64> = String::new();
+    let _ = write!(
+        &mut string_helper,
+        "min {:.*} max {:.*} avg {:.*}",
+        decimals,
+        clamp(statistics.min),
+        decimals,
+        clamp(statistics.max),
+        decimals,
+        clamp(statistics.average())
+    );
+    Text::with_alignment(
+        string_helper.as_str(),
+        Point::new(x, 134),
+        font,
+        Alignment::Center,
+    )
+    .draw(display)?;
+    Ok(())
+}
 
-    let font_normal: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+/// Message of the chase car across the bottom of every page, inverted so it stands out
+fn draw_banner<D, C>(display: &mut D, data: &DisplayData) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor + From<BinaryColor>,
+{
+    let Some(text) = data.banner.text() else {
+        return Ok(());
+    };
+
+    Rectangle::new(Point::new(0, 440), Size::new(800, 40))
+        .into_styled(PrimitiveStyle::with_fill(C::from(BinaryColor::Off)))
+        .draw(display)?;
+
+    let font_banner: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::On.into())
+        .background_color(BinaryColor::Off.into())
+        .build();
+    Text::with_alignment(text, Point::new(400, 466), font_banner, Alignment::Center)
+        .draw(display)?;
+
+    Ok(())
+}
+
+/// Draw the main page
+pub fn draw_display<D, C>(display: &mut D, data: &DisplayData) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor + From<BinaryColor>,
+{
+    display.clear(BinaryColor::On.into())?;
+    let mut string_helper: String<64> = String::new();
+
+    Image::new(&assets::EOI_LOGO, Point::new(800 - 70, 0)).draw(&mut display.color_converted())?;
+
+    let font_normal_inverted: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::On.into())
+        .background_color(BinaryColor::Off.into())
+        .build();
+
+    let font_normal: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .build();
+
+    // Values that weren't updated in time are struck through, the e-paper can't dim them
+    let font_normal_stale: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
         .font(&FONT_10X20)
         .text_color(BinaryColor::Off.into())
         .background_color(BinaryColor::On.into())
+        .strikethrough()
         .build();
 
     let font_normal_header: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
@@ -316,6 +1543,13 @@ where
         .background_color(BinaryColor::On.into())
         .build();
 
+    let font_small_stale: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_6X10)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .strikethrough()
+        .build();
+
     let font_small_inverted: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
         .font(&FONT_6X10)
         .text_color(BinaryColor::On.into())
@@ -330,31 +1564,101 @@ where
         .build();
     const _FONT_TINY_SPACE: i32 = 8;
 
-    const MOTOR_DRIVER_AND_BATTERY_OFFSET_START: i32 = 160;
+    let layout = &data.layout;
 
     string_helper.clear();
-    if let Some(data) = data.time.get() {
+    if let Some(time) = data.time.last() {
+        // Without offset the time is shown in UTC
+        let local = time.with_offset(data.time_utc_offset.last().copied().unwrap_or_default());
         string_helper.clear();
         write!(
             &mut string_helper,
             "Time: {:02}:{:02}:{:02}",
-            data.hours, data.minutes, data.seconds
+            local.hours, local.minutes, local.seconds
         )
         .unwrap();
+        write_age(&mut string_helper, &data.time).unwrap();
     } else {
         string_helper.push_str("Time: N/A").unwrap();
     }
     Text::with_alignment(
         string_helper.as_str(),
         Point::new(400, FONT_NORMAL_SPACE * 2),
-        font_normal,
+        if data.time.is_stale() {
+            font_normal_stale
+        } else {
+            font_normal
+        },
         Alignment::Center,
     )
     .draw(display)?;
 
-    // TODO: implement start of race
+    string_helper.clear();
+    match data.estimated_range_km() {
+        Some(range_km) => {
+            write!(&mut string_helper, "Range: {:.1} km", range_km).unwrap();
+            if let Some(trend) = data.range_trend.trend() {
+                write!(&mut string_helper, ", {}", trend).unwrap();
+            }
+        }
+        None => string_helper.push_str("Range: N/A").unwrap(),
+    }
+    Text::new(
+        string_helper.as_str(),
+        Point::new(15, FONT_NORMAL_SPACE * 2),
+        font_normal,
+    )
+    .draw(display)?;
+
+    if let Some(left) = data.race_time_left() {
+        let seconds = left.as_secs();
+        string_helper.clear();
+        write!(
+            &mut string_helper,
+            "Left {}:{:02}",
+            seconds / 60,
+            seconds % 60
+        )
+        .unwrap();
+        let state_of_charge = data.usable_state_of_charge().unwrap_or(f32::NAN);
+        if let Some(budget) = energy_budget_per_minute(state_of_charge, left) {
+            write!(&mut string_helper, "  {:.2} %/min", budget).unwrap();
+        }
+        Text::with_alignment(
+            string_helper.as_str(),
+            Point::new(720, FONT_NORMAL_SPACE * 2),
+            font_normal,
+            Alignment::Right,
+        )
+        .draw(display)?;
+    }
+
+    string_helper.clear();
+    if let Some(race_time) = data.lap_counter.race_time() {
+        let seconds = race_time.as_secs();
+        write!(
+            &mut string_helper,
+            "Since Race Start: {}:{:02}  Laps: {}",
+            seconds / 60,
+            seconds % 60,
+            data.lap_counter.laps()
+        )
+        .unwrap();
+        if let Some(lap_time) = data.lap_counter.last_lap_time() {
+            let seconds = lap_time.as_secs();
+            write!(
+                &mut string_helper,
+                "  Last: {}:{:02}",
+                seconds / 60,
+                seconds % 60
+            )
+            .unwrap();
+        }
+    } else {
+        string_helper.push_str("Since Race Start: N/A").unwrap();
+    }
     Text::with_alignment(
-        "Since Race Start: N/A",
+        string_helper.as_str(),
         Point::new(400, FONT_NORMAL_SPACE * 3),
         font_normal,
         Alignment::Center,
@@ -387,60 +1691,73 @@ where
         .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 2))
         .draw(display)?;
 
-    if let Some(data) = data.charging_disabled.get() {
-        if *data {
+    if let Some(charging_disabled) = data.charging_disabled.get() {
+        if *charging_disabled {
             Text::with_alignment(
-                "Charging enabled",
+                "Charging disabled !!!",
                 Point::new(400, 50),
-                font_normal,
+                font_normal_inverted,
                 Alignment::Center,
             )
             .draw(display)?;
         } else {
             Text::with_alignment(
-                "Charging disabled !!!",
+                "Charging enabled",
                 Point::new(400, 50),
-                font_normal_inverted,
+                font_normal,
                 Alignment::Center,
             )
             .draw(display)?;
         }
     }
 
+    // The statistics overlay moves the readouts up to make room below them
+    let (readout_label_y, readout_value_y) = if data.statistics_overlay {
+        (92, 118)
+    } else {
+        (100, 130)
+    };
+
     Text::with_alignment(
         "Net Power",
-        Point::new(300, 100),
+        Point::new(300, readout_label_y),
         font_normal,
         Alignment::Center,
     )
     .draw(display)?;
 
+    let net_power = data.battery_net_power_aggregated();
     string_helper.clear();
-    let voltage = data.battery_voltage.get().unwrap_or(&f32::NAN);
-    let current = data.battery_current_in.get().unwrap_or(&f32::NAN)
-        + data.battery_current_out_motor.get().unwrap_or(&f32::NAN)
-        + data
-            .battery_current_out_peripherals
-            .get()
-            .unwrap_or(&f32::NAN);
-    let power = voltage * current;
-    write!(&mut string_helper, "{:.1} W", power).unwrap();
+    let _ = write!(&mut string_helper, "{:.1} W", net_power.value);
 
-    Text::with_alignment(
+    let net_power_text = Text::with_alignment(
         string_helper.as_str(),
-        Point::new(300, 130),
+        Point::new(300, readout_value_y),
         font_normal,
         Alignment::Center,
-    )
-    .draw(display)?;
+    );
+    net_power_text.draw(display)?;
+    if net_power.partial {
+        let text = net_power_text.bounding_box();
+        draw_partial_marker(
+            display,
+            text.top_left + Point::new(-PARTIAL_MARKER_WIDTH, 7),
+        )?;
+    }
+    if data.statistics_overlay {
+        draw_statistics(display, &data.battery_net_power_samples, 300, 0, font_small)?;
+    }
 
     Line::new(Point::new(0, 140), Point::new(800, 140))
         .into_styled(PrimitiveStyle::with_stroke(C::from(BinaryColor::Off), 2))
         .draw(display)?;
 
+    // With the paddle wheel on the bus the speed through water follows the speed over ground,
+    // which also keeps a speed on the display when the GNSS loses its fix
+    let water_speed = data.water_speed_kmh.last().is_some();
     Text::with_alignment(
-        "Speed",
-        Point::new(100, 100),
+        if water_speed { "SOG / STW" } else { "Speed" },
+        Point::new(100, readout_label_y),
         font_normal,
         Alignment::Center,
     )
@@ -448,53 +1765,88 @@ where
 
     string_helper.clear();
 
+    // Garbage from a bad frame is clamped, so both speeds with the age always fit
+    let clamp_speed = |speed: &f32| speed.clamp(-MAX_SPEED_KMH, MAX_SPEED_KMH);
     if *data.gnss_fix.get().unwrap_or(&true) {
-        write!(
+        let _ = write!(
             &mut string_helper,
-            "{:2.1} km/h",
-            data.speed_kmh.get().unwrap_or(&f32::NAN)
-        )
-        .unwrap();
+            "{:2.1}",
+            data.speed_kmh.last().map_or(f32::NAN, clamp_speed)
+        );
     } else {
-        string_helper.push_str("No fix").unwrap();
+        let _ = string_helper.push_str("No fix");
+    }
+    if water_speed {
+        let _ = write!(
+            &mut string_helper,
+            "/{:.1}",
+            data.water_speed_kmh.get().map_or(f32::NAN, clamp_speed)
+        );
+    }
+    let _ = string_helper.push_str(" km/h");
+    if *data.gnss_fix.get().unwrap_or(&true) {
+        let _ = write_age(&mut string_helper, &data.speed_kmh);
     }
 
     Text::with_alignment(
         string_helper.as_str(),
-        Point::new(100, 130),
-        font_normal,
+        Point::new(100, readout_value_y),
+        if data.speed_kmh.is_stale() {
+            font_normal_stale
+        } else {
+            font_normal
+        },
         Alignment::Center,
     )
     .draw(display)?;
+    if data.statistics_overlay {
+        draw_statistics(display, &data.speed_kmh, 100, 1, font_small)?;
+    }
 
-    // state of charge
+    // state of charge, with its source
+    string_helper.clear();
+    string_helper.push_str("State of Charge").unwrap();
+    if let Some(source) = data.soc_selection.selected() {
+        write!(&mut string_helper, ", {}", source.label()).unwrap();
+    }
     Text::with_alignment(
-        "State of Charge",
-        Point::new(500, 100),
+        string_helper.as_str(),
+        Point::new(500, readout_label_y),
         font_normal,
         Alignment::Center,
     )
     .draw(display)?;
 
     string_helper.clear();
-    write!(
-        &mut string_helper,
-        "{:3.1} %",
-        data.battery_state_of_charge.get().unwrap_or(&f32::NAN)
-    )
+    match data
+        .battery_state_of_charge
+        .last()
+        .filter(|state_of_charge| state_of_charge.is_finite())
+    {
+        Some(state_of_charge) => write!(&mut string_helper, "{:3.1} %", state_of_charge),
+        None => write!(&mut string_helper, "N/A"),
+    }
     .unwrap();
+    write_age(&mut string_helper, &data.battery_state_of_charge).unwrap();
 
     Text::with_alignment(
         string_helper.as_str(),
-        Point::new(500, 130),
-        font_normal,
+        Point::new(500, readout_value_y),
+        if data.battery_state_of_charge.is_stale() {
+            font_normal_stale
+        } else {
+            font_normal
+        },
         Alignment::Center,
     )
     .draw(display)?;
+    if data.statistics_overlay {
+        draw_statistics(display, &data.battery_state_of_charge, 500, 1, font_small)?;
+    }
 
     Text::with_alignment(
         "Time to empty",
-        Point::new(700, 100),
+        Point::new(700, readout_label_y),
         font_normal,
         Alignment::Center,
     )
@@ -506,18 +1858,26 @@ where
         &mut string_helper,
         "{:3} Min",
         data.battery_time_to_empty
-            .get()
+            .last()
             .map_or(f32::NAN, |&i| i as f32)
     )
     .unwrap();
+    write_age(&mut string_helper, &data.battery_time_to_empty).unwrap();
 
     Text::with_alignment(
         string_helper.as_str(),
-        Point::new(700, 130),
-        font_normal,
+        Point::new(700, readout_value_y),
+        if data.battery_time_to_empty.is_stale() {
+            font_normal_stale
+        } else {
+            font_normal
+        },
         Alignment::Center,
     )
     .draw(display)?;
+    if data.statistics_overlay {
+        draw_statistics(display, &data.battery_time_to_empty, 700, 0, font_small)?;
+    }
 
     // Solar panels information
     Text::new(
@@ -527,37 +1887,61 @@ where
     )
     .draw(display)?;
     use core::fmt::Write;
-    for (panel, info) in data.mppt_panel_info.iter().enumerate() {
+    let underperforming = underperforming_panels(&data.mppt_panel_info);
+    let panel_layout = PanelLayout::new(data.mppt_panel_info.len(), FONT_SMALL_SPACE);
+    for (panel, (_, info)) in data.mppt_panel_info.iter().enumerate() {
+        let bottom_left = panel_layout.bottom_left(panel);
         string_helper.clear();
-        if let Some((power, voltage, current)) = info.get() {
-            write!(
-                &mut string_helper,
-                "{:2}: {:4.0} W {:3.0} V {:4.1} A",
-                panel + 1,
-                power,
-                voltage,
-                current
-            )
-            .unwrap();
+        if let Some((power, voltage, current)) = info.last() {
+            if panel_layout.bars {
+                write!(
+                    &mut string_helper,
+                    "{:2}: {:4.0} W {:3.0} V {:4.1} A",
+                    panel + 1,
+                    power,
+                    voltage,
+                    current
+                )
+                .unwrap();
+            } else {
+                // Without the voltage to fit half the width
+                write!(
+                    &mut string_helper,
+                    "{:2}: {:4.0} W {:4.1} A",
+                    panel + 1,
+                    power,
+                    current
+                )
+                .unwrap();
+            }
+            write_age(&mut string_helper, info).unwrap();
         } else {
             write!(&mut string_helper, "{:2}: N/A", panel + 1).unwrap();
         }
         Text::new(
             string_helper.as_str(),
-            Point::new(15, (panel as i32 * FONT_SMALL_SPACE) + 375),
-            font_small,
+            bottom_left,
+            if info.is_stale() {
+                font_small_stale
+            } else if underperforming[panel] {
+                // Shaded or damaged, producing much less than the panels next to it
+                font_small_inverted
+            } else {
+                font_small
+            },
         )
         .draw(display)?;
-    }
 
-    for panel in 0..data.mppt_panel_info.len() {
-        let bottom_left = Point::new(220, (panel as i32 * FONT_SMALL_SPACE) + 375 + 2);
+        if !panel_layout.bars {
+            continue;
+        }
+        let bottom_left = bottom_left + Point::new(205, 2);
         let panel_box = Point::new(150, -FONT_SMALL_SPACE);
         // draw outline of cell voltages boxes
         Rectangle::with_corners(bottom_left, bottom_left + panel_box)
             .into_styled(PrimitiveStyle::with_stroke(C::from(BinaryColor::Off), 1))
             .draw(display)?;
-        if let Some((power, _, _)) = data.mppt_panel_info[panel].get() {
+        if let Some((power, _, _)) = info.get() {
             let panel_level =
                 Point::new(scale_to_range(0.0, 150.0, *power, 150), -FONT_SMALL_SPACE);
             // draw infill for level indication
@@ -569,9 +1953,9 @@ where
 
     // battery information
 
-    let mut battery_offset_y = MOTOR_DRIVER_AND_BATTERY_OFFSET_START;
-    let battery_offset_left = 430;
-    let battery_offset_right = 790;
+    let mut battery_offset_y = layout.sections_top;
+    let battery_offset_left = layout.battery_left;
+    let battery_offset_right = layout.battery_right;
 
     Text::new(
         "Battery",
@@ -581,68 +1965,13 @@ where
     .draw(display)?;
     battery_offset_y += FONT_NORMAL_SPACE + 5;
 
-    string_helper.clear();
-    let input_power = data.battery_voltage.get().unwrap_or(&f32::NAN)
-        * data.battery_current_in.get().unwrap_or(&f32::NAN);
-    write!(&mut string_helper, "{:6.0} W", input_power).unwrap();
-
-    Text::new(
-        "Input",
-        Point::new(battery_offset_left, battery_offset_y),
-        font_normal,
-    )
-    .draw(display)?;
-    Text::with_alignment(
-        string_helper.as_str(),
-        Point::new(battery_offset_right, battery_offset_y),
-        font_normal,
-        Alignment::Right,
-    )
-    .draw(display)?;
-    battery_offset_y += FONT_NORMAL_SPACE;
-
-    string_helper.clear();
-    let motor_power = data.battery_voltage.get().unwrap_or(&f32::NAN)
-        * data.battery_current_out_motor.get().unwrap_or(&f32::NAN);
-    write!(&mut string_helper, "{:6.0} W", motor_power).unwrap();
-
-    Text::new(
-        "Output motor",
-        Point::new(battery_offset_left, battery_offset_y),
-        font_normal,
-    )
-    .draw(display)?;
-    Text::with_alignment(
-        string_helper.as_str(),
-        Point::new(battery_offset_right, battery_offset_y),
-        font_normal,
-        Alignment::Right,
-    )
-    .draw(display)?;
-    battery_offset_y += FONT_NORMAL_SPACE;
-
-    string_helper.clear();
-    let peripherals_power = data.battery_voltage.get().unwrap_or(&f32::NAN)
-        * data
-            .battery_current_out_peripherals
-            .get()
-            .unwrap_or(&f32::NAN);
-    write!(&mut string_helper, "{:6.0} W", peripherals_power).unwrap();
-
-    Text::new(
-        "Output peripherals",
-        Point::new(battery_offset_left, battery_offset_y),
-        font_normal,
-    )
-    .draw(display)?;
-    Text::with_alignment(
-        string_helper.as_str(),
-        Point::new(battery_offset_right, battery_offset_y),
-        font_normal,
-        Alignment::Right,
-    )
-    .draw(display)?;
-    battery_offset_y += FONT_NORMAL_SPACE;
+    // Where the power goes, in the place of three rows
+    power_flow::draw_power_flow(
+        display,
+        data,
+        Point::new(battery_offset_left, battery_offset_y - 15),
+    )?;
+    battery_offset_y += 3 * FONT_NORMAL_SPACE;
 
     // get array of temperatures
     let valid_temperatures = data
@@ -650,7 +1979,7 @@ where
         .iter()
         .filter(|temp| temp.is_valid())
         .map(|temp| temp.get().unwrap_or(&i8::MIN))
-        .collect::<heapless::Vec<&i8, 4>>();
+        .collect::<heapless::Vec<&i8, MAX_BATTERY_TEMPERATURES>>();
 
     let max_temp = valid_temperatures
         .iter()
@@ -698,7 +2027,7 @@ where
         .iter()
         .filter(|voltage| voltage.is_valid())
         .map(|voltage| voltage.get().unwrap_or(&f32::NAN))
-        .collect::<heapless::Vec<&f32, 14>>();
+        .collect::<heapless::Vec<&f32, MAX_CELLS>>();
 
     let max_voltage = valid_voltages
         .iter()
@@ -752,7 +2081,11 @@ where
     Text::with_alignment(
         string_helper.as_str(),
         Point::new(battery_offset_right, battery_offset_y),
-        font_normal,
+        if data.cell_imbalance.is_active() {
+            font_normal_inverted
+        } else {
+            font_normal
+        },
         Alignment::Right,
     )
     .draw(display)?;
@@ -858,38 +2191,108 @@ where
     )
     .draw(display)?;
 
-    // Cell voltages
-    const CELL_VOLTAGES_HEIGTH: i32 = 80;
-    const CELL_VOLTAGES_WIDTH: i32 = 10;
-    const CELL_SPACING: i32 = 28;
+    // What the peripherals draw, with a power monitor in front of them
+    if !data.peripheral_currents.is_empty() {
+        battery_offset_y += FONT_NORMAL_SPACE;
+        let mut label: String<32> = String::new();
+        string_helper.clear();
+        for (index, consumer) in PeripheralConsumer::ALL.into_iter().enumerate() {
+            if index > 0 {
+                let _ = label.push('/');
+                let _ = string_helper.push('/');
+            }
+            let _ = label.push_str(consumer.label());
+            let power = data.peripheral_power(consumer);
+            if power.is_nan() {
+                let _ = string_helper.push('-');
+            } else {
+                // Garbage from a bad frame is clamped, so the row always fits
+                let power = power.clamp(-MAX_PERIPHERAL_POWER_W, MAX_PERIPHERAL_POWER_W);
+                let _ = write!(&mut string_helper, "{:.0}", power);
+            }
+        }
+        let _ = string_helper.push_str(" W");
+        Text::new(
+            label.as_str(),
+            Point::new(battery_offset_left, battery_offset_y),
+            font_normal,
+        )
+        .draw(display)?;
+        Text::with_alignment(
+            string_helper.as_str(),
+            Point::new(battery_offset_right, battery_offset_y),
+            font_normal,
+            Alignment::Right,
+        )
+        .draw(display)?;
+    }
 
-    for cell in 0..data.battery_cell_voltages.len() {
+    // Cell voltages, closer together for a pack with more cells than the layout is made for. The
+    // bars are shorter by a row when the temperatures of the cells are shown under them
+    let cells = data.battery_cell_voltages.len();
+    let cell_spacing = layout.cell_spacing * DEFAULT_CELLS as i32 / cells.max(DEFAULT_CELLS) as i32;
+    let cell_temperature_row = if data.cell_temperature_map.is_empty() {
+        0
+    } else {
+        CELL_TEMPERATURE_ROW
+    };
+    let cell_bar_bottom = 480 - 10 - cell_temperature_row;
+    let cell_bar_height = layout.cell_bar_height - cell_temperature_row;
+    for cell in 0..cells {
         let bottom_left = Point::new(
-            battery_offset_left - 15 + cell as i32 * CELL_SPACING,
-            480 - 10,
+            battery_offset_left - 15 + cell as i32 * cell_spacing,
+            cell_bar_bottom,
         );
-        let cell_box = Point::new(CELL_VOLTAGES_WIDTH, -CELL_VOLTAGES_HEIGTH);
+        let cell_box = Point::new(layout.cell_bar_width, -cell_bar_height);
         let text_top_left = bottom_left + cell_box.y_axis() + Point::new(1, -3);
         // draw outline of cell voltages boxes
         Rectangle::with_corners(bottom_left, bottom_left + cell_box)
             .into_styled(PrimitiveStyle::with_stroke(C::from(BinaryColor::Off), 1))
             .draw(display)?;
         let cell_level = scale_to_range(
-            2.5,
-            4.2,
+            layout.cell_bar_min,
+            layout.cell_bar_max,
             *data.battery_cell_voltages[cell].get().unwrap_or(&f32::NAN),
-            CELL_VOLTAGES_HEIGTH,
+            cell_bar_height,
         );
-        // draw infill for level indication
-        let cell_level = Point::new(CELL_VOLTAGES_WIDTH, -cell_level);
-        Rectangle::with_corners(bottom_left, bottom_left + cell_level)
-            .into_styled(PrimitiveStyle::with_fill(C::from(BinaryColor::Off)))
-            .draw(display)?;
+        // draw infill for level indication, only a line at the level for cells still showing a
+        // voltage of an earlier cycle of the BMS
+        let cell_level = Point::new(layout.cell_bar_width, -cell_level);
+        if data.battery_cell_voltage_frames.is_cell_behind(cell) {
+            Line::new(bottom_left + cell_level.y_axis(), bottom_left + cell_level)
+                .into_styled(PrimitiveStyle::with_stroke(C::from(BinaryColor::Off), 2))
+                .draw(display)?;
+        } else {
+            Rectangle::with_corners(bottom_left, bottom_left + cell_level)
+                .into_styled(PrimitiveStyle::with_fill(C::from(BinaryColor::Off)))
+                .draw(display)?;
+        }
         // set cell id on top
         string_helper.clear();
         write!(&mut string_helper, "{:2}", cell + 1).unwrap();
         Text::new(string_helper.as_str(), text_top_left, font_tiny).draw(display)?;
     }
+    // The temperature of every group of cells under their bars
+    for (cells, temperature) in data.cell_temperature_map.temperatures(data) {
+        let left = battery_offset_left - 15 + *cells.start() as i32 * cell_spacing;
+        let right =
+            battery_offset_left - 15 + *cells.end() as i32 * cell_spacing + layout.cell_bar_width;
+        string_helper.clear();
+        match temperature {
+            Some(temperature) => write!(&mut string_helper, "{}C", temperature).unwrap(),
+            None => string_helper.push_str("--").unwrap(),
+        }
+        Text::with_alignment(
+            string_helper.as_str(),
+            Point::new(
+                (left + right) / 2,
+                cell_bar_bottom + cell_temperature_row - 2,
+            ),
+            font_tiny,
+            Alignment::Center,
+        )
+        .draw(display)?;
+    }
 
     Line::new(Point::new(400, 140), Point::new(400, 480))
         .into_styled(PrimitiveStyle::with_stroke(C::from(BinaryColor::Off), 2))
@@ -897,21 +2300,21 @@ where
 
     // Height sensor bars
     {
-        const HEIGHT_BAR_HEIGHT: i32 = 200;
-        const HEIGHT_BAR_WIDTH: i32 = 12;
-        let bar_bottom_y = 355;
-        let bar_top_y = bar_bottom_y - HEIGHT_BAR_HEIGHT;
+        let height_bar_height = layout.height_bar_height;
+        let height_bar_width = layout.height_bar_width;
+        let bar_bottom_y = layout.height_bar_bottom;
+        let bar_top_y = bar_bottom_y - height_bar_height;
 
         // Front Left bar - left of center
         let fl_x = 378;
         let fl_bottom_left = Point::new(fl_x, bar_bottom_y);
-        let fl_box = Point::new(HEIGHT_BAR_WIDTH, -HEIGHT_BAR_HEIGHT);
+        let fl_box = Point::new(height_bar_width, -height_bar_height);
         Rectangle::with_corners(fl_bottom_left, fl_bottom_left + fl_box)
             .into_styled(PrimitiveStyle::with_stroke(C::from(BinaryColor::Off), 1))
             .draw(display)?;
         if let Some(&value) = data.height_sensor_front_left.get() {
-            let level = scale_to_range(0.0, 2000.0, value as f32, HEIGHT_BAR_HEIGHT);
-            let level_pt = Point::new(HEIGHT_BAR_WIDTH, -level);
+            let level = scale_to_range(0.0, 2000.0, value as f32, height_bar_height);
+            let level_pt = Point::new(height_bar_width, -level);
             Rectangle::with_corners(fl_bottom_left, fl_bottom_left + level_pt)
                 .into_styled(PrimitiveStyle::with_fill(C::from(BinaryColor::Off)))
                 .draw(display)?;
@@ -919,7 +2322,7 @@ where
             write!(&mut string_helper, "{}", value).unwrap();
             Text::with_alignment(
                 string_helper.as_str(),
-                Point::new(fl_x + HEIGHT_BAR_WIDTH / 2, bar_bottom_y + 12),
+                Point::new(fl_x + height_bar_width / 2, bar_bottom_y + 12),
                 font_small,
                 Alignment::Center,
             )
@@ -927,7 +2330,7 @@ where
         }
         Text::with_alignment(
             "FL",
-            Point::new(fl_x + HEIGHT_BAR_WIDTH / 2, bar_top_y - 3),
+            Point::new(fl_x + height_bar_width / 2, bar_top_y - 3),
             font_small,
             Alignment::Center,
         )
@@ -936,13 +2339,13 @@ where
         // Front Right bar - right of center
         let fr_x = 408;
         let fr_bottom_left = Point::new(fr_x, bar_bottom_y);
-        let fr_box = Point::new(HEIGHT_BAR_WIDTH, -HEIGHT_BAR_HEIGHT);
+        let fr_box = Point::new(height_bar_width, -height_bar_height);
         Rectangle::with_corners(fr_bottom_left, fr_bottom_left + fr_box)
             .into_styled(PrimitiveStyle::with_stroke(C::from(BinaryColor::Off), 1))
             .draw(display)?;
         if let Some(&value) = data.height_sensor_front_right.get() {
-            let level = scale_to_range(0.0, 2000.0, value as f32, HEIGHT_BAR_HEIGHT);
-            let level_pt = Point::new(HEIGHT_BAR_WIDTH, -level);
+            let level = scale_to_range(0.0, 2000.0, value as f32, height_bar_height);
+            let level_pt = Point::new(height_bar_width, -level);
             Rectangle::with_corners(fr_bottom_left, fr_bottom_left + level_pt)
                 .into_styled(PrimitiveStyle::with_fill(C::from(BinaryColor::Off)))
                 .draw(display)?;
@@ -950,7 +2353,7 @@ where
             write!(&mut string_helper, "{}", value).unwrap();
             Text::with_alignment(
                 string_helper.as_str(),
-                Point::new(fr_x + HEIGHT_BAR_WIDTH / 2, bar_bottom_y + 12),
+                Point::new(fr_x + height_bar_width / 2, bar_bottom_y + 12),
                 font_small,
                 Alignment::Center,
             )
@@ -958,7 +2361,7 @@ where
         }
         Text::with_alignment(
             "FR",
-            Point::new(fr_x + HEIGHT_BAR_WIDTH / 2, bar_top_y - 3),
+            Point::new(fr_x + height_bar_width / 2, bar_top_y - 3),
             font_small,
             Alignment::Center,
         )
@@ -966,9 +2369,9 @@ where
     }
 
     // Create a new window
-    let mut motor_driver_offset_y = MOTOR_DRIVER_AND_BATTERY_OFFSET_START;
-    let motor_driver_offset_left = 15;
-    let motor_driver_offset_right = 250;
+    let mut motor_driver_offset_y = layout.sections_top;
+    let motor_driver_offset_left = layout.motor_driver_left;
+    let motor_driver_offset_right = layout.motor_driver_values;
 
     Text::new(
         "Motor driver",
@@ -978,158 +2381,115 @@ where
     .draw(display)?;
     motor_driver_offset_y += FONT_NORMAL_SPACE + 5;
 
-    let motor_battery_power = data.motor_battery_voltage.get().unwrap_or(&f32::NAN)
-        * data.motor_battery_current.get().unwrap_or(&f32::NAN);
+    let no_motor = MotorData::default();
+    // One column per motor controller, sorted by controller ID so the columns don't swap around
+    let mut motors = data
+        .motors
+        .iter()
+        .map(|(id, motor)| (*id, motor))
+        .collect::<heapless::Vec<(u8, &MotorData), MAX_VESC_CONTROLLERS>>();
+    motors.sort_unstable_by_key(|(id, _)| *id);
+    if motors.is_empty() {
+        motors.push((0, &no_motor)).ok();
+    }
+    motors.truncate(2);
 
-    string_helper.clear();
-    write!(&mut string_helper, "{:6.0} W", motor_battery_power).unwrap();
-    Text::new(
-        "Battery power usage",
-        Point::new(motor_driver_offset_left, motor_driver_offset_y),
-        font_normal,
-    )
-    .draw(display)?;
-    Text::new(
-        string_helper.as_str(),
-        Point::new(motor_driver_offset_right, motor_driver_offset_y),
-        font_normal,
-    )
-    .draw(display)?;
-    motor_driver_offset_y += FONT_NORMAL_SPACE;
+    // a single motor keeps the values left aligned, two motors are right aligned in two columns
+    let motor_driver_columns: &[(i32, Alignment)] = if motors.len() > 1 {
+        &[(290, Alignment::Right), (370, Alignment::Right)]
+    } else {
+        &[(motor_driver_offset_right, Alignment::Left)]
+    };
 
-    string_helper.clear();
-    write!(
-        &mut string_helper,
-        "{:6.1} A",
-        data.motor_battery_current.get().unwrap_or(&f32::NAN)
-    )
-    .unwrap();
-    Text::new(
-        "Battery Current",
-        Point::new(motor_driver_offset_left, motor_driver_offset_y),
-        font_normal,
-    )
-    .draw(display)?;
-    Text::new(
-        string_helper.as_str(),
-        Point::new(motor_driver_offset_right, motor_driver_offset_y),
-        font_normal,
-    )
-    .draw(display)?;
-    motor_driver_offset_y += FONT_NORMAL_SPACE;
-
-    string_helper.clear();
-    write!(
-        &mut string_helper,
-        "{:6.1} A",
-        data.motor_current.get().unwrap_or(&f32::NAN)
-    )
-    .unwrap();
-    Text::new(
-        "Motor Current",
-        Point::new(motor_driver_offset_left, motor_driver_offset_y),
-        font_normal,
-    )
-    .draw(display)?;
-    Text::new(
-        string_helper.as_str(),
-        Point::new(motor_driver_offset_right, motor_driver_offset_y),
-        font_normal,
-    )
-    .draw(display)?;
-    motor_driver_offset_y += FONT_NORMAL_SPACE;
-
-    string_helper.clear();
-    write!(
-        &mut string_helper,
-        "{:6.1} %",
-        data.motor_duty_cycle.get().unwrap_or(&f32::NAN)
-    )
-    .unwrap();
-    Text::new(
-        "Duty cycle",
-        Point::new(motor_driver_offset_left, motor_driver_offset_y),
-        font_normal,
-    )
-    .draw(display)?;
-    Text::new(
-        string_helper.as_str(),
-        Point::new(motor_driver_offset_right, motor_driver_offset_y),
-        font_normal,
-    )
-    .draw(display)?;
-    motor_driver_offset_y += FONT_NORMAL_SPACE;
-
-    string_helper.clear();
-    write!(
-        &mut string_helper,
-        "{:6.0}",
-        data.motor_rpm.get().map_or(f32::NAN, |&i| i as f32)
-    )
-    .unwrap();
-    Text::new(
-        "RPM",
-        Point::new(motor_driver_offset_left, motor_driver_offset_y),
-        font_normal,
-    )
-    .draw(display)?;
-    Text::new(
-        string_helper.as_str(),
-        Point::new(motor_driver_offset_right, motor_driver_offset_y),
-        font_normal,
-    )
-    .draw(display)?;
-    motor_driver_offset_y += FONT_NORMAL_SPACE;
+    // The header line names the columns, or the fault of a controller which stopped the motor
+    for ((id, motor), (x, alignment)) in motors.iter().zip(motor_driver_columns) {
+        string_helper.clear();
+        let font = match motor.active_fault() {
+            Some(fault) if motors.len() > 1 => {
+                write!(&mut string_helper, "{}", fault.label()).unwrap();
+                font_normal_inverted
+            }
+            Some(fault) => {
+                write!(&mut string_helper, "Fault: {}", fault.label()).unwrap();
+                font_normal_inverted
+            }
+            None if motors.len() > 1 => {
+                write!(&mut string_helper, "ID {}", id).unwrap();
+                font_normal
+            }
+            None => continue,
+        };
+        Text::with_alignment(
+            string_helper.as_str(),
+            Point::new(*x, motor_driver_offset_y - FONT_NORMAL_SPACE - 5),
+            font,
+            *alignment,
+        )
+        .draw(display)?;
+    }
 
-    string_helper.clear();
-    write!(
-        &mut string_helper,
-        "{:6.1} C",
-        data.motor_fet_temperature.get().unwrap_or(&f32::NAN)
-    )
-    .unwrap();
-    Text::new(
-        "FET temperature",
-        Point::new(motor_driver_offset_left, motor_driver_offset_y),
-        font_normal,
-    )
-    .draw(display)?;
-    Text::new(
-        string_helper.as_str(),
-        Point::new(motor_driver_offset_right, motor_driver_offset_y),
-        font_normal,
-    )
-    .draw(display)?;
-    motor_driver_offset_y += FONT_NORMAL_SPACE;
+    type MotorValueFormatter = fn(&mut String<64>, &MotorData) -> core::fmt::Result;
+    let motor_rows: [(&str, MotorValueFormatter); 7] = [
+        ("Battery power usage", |s, motor| {
+            write!(s, "{:6.0} W", motor.battery_power())
+        }),
+        ("Battery Current", |s, motor| {
+            write!(
+                s,
+                "{:6.1} A",
+                motor.battery_current.get().unwrap_or(&f32::NAN)
+            )
+        }),
+        ("Motor Current", |s, motor| {
+            write!(s, "{:6.1} A", motor.current.get().unwrap_or(&f32::NAN))
+        }),
+        ("Duty cycle", |s, motor| {
+            write!(s, "{:6.1} %", motor.duty_cycle.get().unwrap_or(&f32::NAN))
+        }),
+        ("RPM", |s, motor| {
+            write!(s, "{:6.0}", motor.rpm.get().map_or(f32::NAN, |&i| i as f32))
+        }),
+        ("FET temperature", |s, motor| {
+            write!(
+                s,
+                "{:6.1} C",
+                motor.fet_temperature.get().unwrap_or(&f32::NAN)
+            )
+        }),
+        ("Motor temperature", |s, motor| {
+            write!(s, "{:6.1} C", motor.temperature.get().unwrap_or(&f32::NAN))
+        }),
+    ];
 
-    string_helper.clear();
-    write!(
-        &mut string_helper,
-        "{:6.1} C",
-        data.motor_temperature.get().unwrap_or(&f32::NAN)
-    )
-    .unwrap();
-    Text::new(
-        "Motor temperature",
-        Point::new(motor_driver_offset_left, motor_driver_offset_y),
-        font_normal,
-    )
-    .draw(display)?;
-    Text::new(
-        string_helper.as_str(),
-        Point::new(motor_driver_offset_right, motor_driver_offset_y),
-        font_normal,
-    )
-    .draw(display)?;
-    motor_driver_offset_y += FONT_NORMAL_SPACE;
+    for (label, formatter) in motor_rows {
+        Text::new(
+            label,
+            Point::new(motor_driver_offset_left, motor_driver_offset_y),
+            font_normal,
+        )
+        .draw(display)?;
+        for ((_, motor), (x, alignment)) in motors.iter().zip(motor_driver_columns) {
+            string_helper.clear();
+            formatter(&mut string_helper, motor).unwrap();
+            Text::with_alignment(
+                string_helper.as_str(),
+                Point::new(*x, motor_driver_offset_y),
+                font_normal,
+                *alignment,
+            )
+            .draw(display)?;
+        }
+        motor_driver_offset_y += FONT_NORMAL_SPACE;
+    }
 
     string_helper.clear();
     write!(
         &mut string_helper,
         "{:6.1} %",
-        data.throttle_value.get().unwrap_or(&f32::NAN)
+        data.throttle_value.last().unwrap_or(&f32::NAN)
     )
     .unwrap();
+    write_age(&mut string_helper, &data.throttle_value).unwrap();
     Text::new(
         "Throttle value",
         Point::new(motor_driver_offset_left, motor_driver_offset_y),
@@ -1139,13 +2499,20 @@ where
     Text::new(
         string_helper.as_str(),
         Point::new(motor_driver_offset_right, motor_driver_offset_y),
-        font_normal,
+        if data.throttle_value.is_stale() {
+            font_normal_stale
+        } else {
+            font_normal
+        },
     )
     .draw(display)?;
 
     string_helper.clear();
-    if let Some(data) = data.ip_address.get() {
-        write!(&mut string_helper, "Ip address: {}", data).unwrap();
+    if let Some(ip) = data.ip_address.get() {
+        write!(&mut string_helper, "Ip address: {}", ip).unwrap();
+        if let Some(level) = data.wifi_level_dbm.get() {
+            write!(&mut string_helper, " ({:.0} dBm)", level).unwrap();
+        }
     } else {
         string_helper.push_str("Ip address: N/A").unwrap();
     }
@@ -1158,6 +2525,26 @@ where
     )
     .draw(display)?;
 
+    if data.can_interface_up.get() == Some(&false) {
+        Text::with_alignment(
+            "CAN interface down",
+            Point::new(400, 65),
+            font_small_inverted,
+            Alignment::Center,
+        )
+        .draw(display)?;
+    } else if let Some(fault) = data.can_bus_fault.get() {
+        string_helper.clear();
+        write!(&mut string_helper, "CAN {}", fault).unwrap();
+        Text::with_alignment(
+            string_helper.as_str(),
+            Point::new(400, 65),
+            font_small_inverted,
+            Alignment::Center,
+        )
+        .draw(display)?;
+    }
+
     if let Some(charging) = data.display_is_charging.get() {
         string_helper.clear();
         write!(
@@ -1210,26 +2597,2169 @@ where
     Ok(())
 }
 
-fn scale_to_range(in_min: f32, in_max: f32, input: f32, out_max: i32) -> i32 {
-    let corrected_input = if input.is_nan() {
-        in_min
-    } else {
-        input.clamp(in_min, in_max)
-    };
-    (((corrected_input - in_min) / (in_max - in_min)) * out_max as f32) as i32
+/// Shown while the display sleeps because the CAN bus is silent, the e-paper keeps showing it
+/// without power
+pub fn draw_sleep_screen<D, C>(display: &mut D) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor + From<BinaryColor>,
+{
+    display.clear(BinaryColor::On.into())?;
+
+    Image::with_center(&assets::EOI_LOGO, Point::new(400, 180))
+        .draw(&mut display.color_converted())?;
+
+    let font_normal: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .build();
+
+    Text::with_alignment(
+        "Sleeping, no CAN data",
+        Point::new(400, 280),
+        font_normal,
+        Alignment::Center,
+    )
+    .draw(display)?;
+    Text::with_alignment(
+        "Wakes up with the next frame or a button press",
+        Point::new(400, 310),
+        font_normal,
+        Alignment::Center,
+    )
+    .draw(display)?;
+
+    Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use std::f32;
+/// Shown by the framebuffer display before it powers off the datalogger, because the battery of
+/// the display is almost empty
+pub fn draw_shutdown_screen<D, C>(display: &mut D, state_of_charge: f32) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor + From<BinaryColor>,
+{
+    use core::fmt::Write;
 
-    use super::*;
-    #[test]
-    fn scale_cell_voltages() {
-        let range_to_scale_to = 100;
-        assert_eq!(scale_to_range(2.5, 4.2, 4.2, range_to_scale_to), 100);
-        assert_eq!(scale_to_range(2.5, 4.2, 2.5, range_to_scale_to), 0);
-        assert_eq!(scale_to_range(2.5, 4.2, 3.35, range_to_scale_to), 50);
-        assert_eq!(scale_to_range(2.5, 4.2, f32::NAN, range_to_scale_to), 0);
+    display.clear(BinaryColor::On.into())?;
+
+    Image::with_center(&assets::EOI_LOGO, Point::new(400, 180))
+        .draw(&mut display.color_converted())?;
+
+    let font_normal: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .build();
+
+    let mut string_helper: String<64> = String::new();
+    write!(
+        &mut string_helper,
+        "Shutting down, display battery at {:.0}%",
+        state_of_charge
+    )
+    .unwrap();
+    Text::with_alignment(
+        string_helper.as_str(),
+        Point::new(400, 280),
+        font_normal,
+        Alignment::Center,
+    )
+    .draw(display)?;
+    Text::with_alignment(
+        "Logs are saved, charge the display to start again",
+        Point::new(400, 310),
+        font_normal,
+        Alignment::Center,
+    )
+    .draw(display)?;
+
+    Ok(())
+}
+
+fn draw_diagnostics<D, C>(display: &mut D, data: &DisplayData) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor + From<BinaryColor>,
+{
+    use core::fmt::Write;
+
+    display.clear(BinaryColor::On.into())?;
+    let mut string_helper: String<64> = String::new();
+
+    let font_normal: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .build();
+
+    let font_normal_header: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .underline()
+        .build();
+
+    let font_normal_inverted: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::On.into())
+        .background_color(BinaryColor::Off.into())
+        .build();
+    const FONT_NORMAL_SPACE: i32 = 20;
+
+    Text::with_alignment(
+        "Diagnostics",
+        Point::new(400, FONT_NORMAL_SPACE),
+        font_normal_header,
+        Alignment::Center,
+    )
+    .draw(display)?;
+
+    Line::new(Point::new(0, 70), Point::new(800, 70))
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 1))
+        .draw(display)?;
+
+    // Throttle
+    let throttle_offset_left = 15;
+    let mut throttle_offset_y = 100;
+    Text::with_alignment(
+        "Throttle",
+        Point::new(throttle_offset_left, throttle_offset_y),
+        font_normal_header,
+        Alignment::Left,
+    )
+    .draw(display)?;
+
+    let config = data.throttle_config.get();
+    let lines: [(&str, Option<&dyn core::fmt::Display>); 8] = [
+        (
+            "Control type",
+            config.map(|config| &config.control_type as &dyn core::fmt::Display),
+        ),
+        (
+            "Lever forward",
+            config.map(|config| &config.lever_forward as &dyn core::fmt::Display),
+        ),
+        (
+            "Lever backward",
+            config.map(|config| &config.lever_backward as &dyn core::fmt::Display),
+        ),
+        (
+            "Gain",
+            data.throttle_gain
+                .get()
+                .map(|gain| gain as &dyn core::fmt::Display),
+        ),
+        (
+            "Raw angle",
+            data.throttle_raw_angle
+                .get()
+                .map(|angle| angle as &dyn core::fmt::Display),
+        ),
+        (
+            "Raw deadman",
+            data.throttle_raw_deadman
+                .get()
+                .map(|deadman| deadman as &dyn core::fmt::Display),
+        ),
+        (
+            "Value (%)",
+            data.throttle_value
+                .get()
+                .map(|value| value as &dyn core::fmt::Display),
+        ),
+        (
+            "Errors",
+            data.throttle_errors
+                .get()
+                .map(|errors| errors as &dyn core::fmt::Display),
+        ),
+    ];
+    for (label, value) in lines {
+        throttle_offset_y += FONT_NORMAL_SPACE;
+        string_helper.clear();
+        match value {
+            Some(value) => write!(&mut string_helper, "{}: {:.1}", label, value),
+            None => write!(&mut string_helper, "{}: N/A", label),
+        }
+        .unwrap();
+        Text::with_alignment(
+            string_helper.as_str(),
+            Point::new(throttle_offset_left, throttle_offset_y),
+            font_normal,
+            Alignment::Left,
+        )
+        .draw(display)?;
+    }
+
+    // Safety faults since the start of the session, inverted while one is active
+    let faults = &data.throttle_faults;
+    throttle_offset_y += FONT_NORMAL_SPACE;
+    string_helper.clear();
+    write!(
+        &mut string_helper,
+        "Faults: {} deadman, {} impedance",
+        faults.deadman_missing.count, faults.impedance_high.count
+    )
+    .unwrap();
+    Text::with_alignment(
+        string_helper.as_str(),
+        Point::new(throttle_offset_left, throttle_offset_y),
+        if faults.iter().any(|(_, statistics)| statistics.is_active()) {
+            font_normal_inverted
+        } else {
+            font_normal
+        },
+        Alignment::Left,
+    )
+    .draw(display)?;
+
+    // Paddle wheel against the GNSS
+    throttle_offset_y += 2 * FONT_NORMAL_SPACE;
+    Text::with_alignment(
+        "Water speed",
+        Point::new(throttle_offset_left, throttle_offset_y),
+        font_normal_header,
+        Alignment::Left,
+    )
+    .draw(display)?;
+
+    let lines: [(&str, Option<f32>, &str); 4] = [
+        ("Through water", data.water_speed_kmh.get().copied(), "km/h"),
+        ("Over ground", data.speed_kmh.get().copied(), "km/h"),
+        ("Current", data.current_along_course_kmh(), "km/h"),
+        (
+            "Paddle wheel",
+            data.water_speed_pulse_frequency.get().copied(),
+            "Hz",
+        ),
+    ];
+    for (label, value, unit) in lines {
+        throttle_offset_y += FONT_NORMAL_SPACE;
+        string_helper.clear();
+        let _ = match value {
+            Some(value) => write!(&mut string_helper, "{}: {:.1} {}", label, value, unit),
+            None => write!(&mut string_helper, "{}: N/A", label),
+        };
+        Text::with_alignment(
+            string_helper.as_str(),
+            Point::new(throttle_offset_left, throttle_offset_y),
+            font_normal,
+            Alignment::Left,
+        )
+        .draw(display)?;
+    }
+
+    // Trip
+    let trip_offset_left = 415;
+    let mut trip_offset_y = 100;
+    Text::with_alignment(
+        "Trip",
+        Point::new(trip_offset_left, trip_offset_y),
+        font_normal_header,
+        Alignment::Left,
+    )
+    .draw(display)?;
+
+    let trip = &data.trip;
+    let lines: [(&str, f32, &str); 3] = [
+        ("Distance", trip.distance_km, "km"),
+        ("Motor energy", trip.motor_energy_wh, "Wh"),
+        ("Solar energy", trip.solar_energy_wh, "Wh"),
+    ];
+    for (label, value, unit) in lines {
+        trip_offset_y += FONT_NORMAL_SPACE;
+        string_helper.clear();
+        write!(&mut string_helper, "{}: {:.1} {}", label, value, unit).unwrap();
+        Text::with_alignment(
+            string_helper.as_str(),
+            Point::new(trip_offset_left, trip_offset_y),
+            font_normal,
+            Alignment::Left,
+        )
+        .draw(display)?;
+    }
+
+    // Charge current of the MPPTs against the BMS
+    trip_offset_y += 2 * FONT_NORMAL_SPACE;
+    Text::with_alignment(
+        "Charge check",
+        Point::new(trip_offset_left, trip_offset_y),
+        font_normal_header,
+        Alignment::Left,
+    )
+    .draw(display)?;
+
+    let check = &data.charge_check;
+    let lines: [(&str, Option<f32>); 2] = [
+        ("MPPT output", check.mppt_current),
+        ("BMS charge", check.battery_current),
+    ];
+    for (label, value) in lines {
+        trip_offset_y += FONT_NORMAL_SPACE;
+        string_helper.clear();
+        match value {
+            Some(value) => write!(&mut string_helper, "{}: {:.1} A", label, value),
+            None => write!(&mut string_helper, "{}: N/A", label),
+        }
+        .unwrap();
+        Text::with_alignment(
+            string_helper.as_str(),
+            Point::new(trip_offset_left, trip_offset_y),
+            font_normal,
+            Alignment::Left,
+        )
+        .draw(display)?;
+    }
+    if check.diverging {
+        trip_offset_y += FONT_NORMAL_SPACE;
+        Text::with_alignment(
+            "Diverging, check wiring",
+            Point::new(trip_offset_left, trip_offset_y),
+            font_normal_inverted,
+            Alignment::Left,
+        )
+        .draw(display)?;
+    }
+
+    // Clock of the application against the GNSS time, the logs are stamped by it
+    trip_offset_y += 2 * FONT_NORMAL_SPACE;
+    Text::with_alignment(
+        "Clock",
+        Point::new(trip_offset_left, trip_offset_y),
+        font_normal_header,
+        Alignment::Left,
+    )
+    .draw(display)?;
+    trip_offset_y += FONT_NORMAL_SPACE;
+    string_helper.clear();
+    match data.clock_check.divergence() {
+        Some(divergence) => write!(&mut string_helper, "Off GNSS time: {:+.1} s", divergence),
+        None => write!(&mut string_helper, "Off GNSS time: N/A"),
+    }
+    .unwrap();
+    Text::with_alignment(
+        string_helper.as_str(),
+        Point::new(trip_offset_left, trip_offset_y),
+        if data.clock_check.is_active() {
+            font_normal_inverted
+        } else {
+            font_normal
+        },
+        Alignment::Left,
+    )
+    .draw(display)?;
+
+    Ok(())
+}
+
+/// The commands of the throttle next to the duty cycle, current and RPM the motor controllers
+/// measure, with the inputs of the controllers
+fn draw_commissioning<D, C>(display: &mut D, data: &DisplayData) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor + From<BinaryColor>,
+{
+    use core::fmt::Write;
+
+    display.clear(BinaryColor::On.into())?;
+    let mut string_helper: String<64> = String::new();
+
+    let font_normal: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .build();
+
+    let font_normal_header: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .underline()
+        .build();
+    const FONT_NORMAL_SPACE: i32 = 20;
+
+    Text::with_alignment(
+        "Commissioning",
+        Point::new(400, FONT_NORMAL_SPACE),
+        font_normal_header,
+        Alignment::Center,
+    )
+    .draw(display)?;
+
+    string_helper.clear();
+    match data.throttle_config.get() {
+        Some(config) => write!(&mut string_helper, "Control type: {}", config.control_type),
+        None => write!(&mut string_helper, "Control type: N/A"),
+    }
+    .unwrap();
+    Text::with_alignment(
+        string_helper.as_str(),
+        Point::new(400, FONT_NORMAL_SPACE * 3),
+        font_normal,
+        Alignment::Center,
+    )
+    .draw(display)?;
+
+    Line::new(Point::new(0, 70), Point::new(800, 70))
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 1))
+        .draw(display)?;
+
+    let no_motor = MotorData::default();
+    // Like the main page, the first two controllers sorted by controller ID
+    let mut motors = data
+        .motors
+        .iter()
+        .map(|(id, motor)| (*id, motor))
+        .collect::<heapless::Vec<(u8, &MotorData), MAX_VESC_CONTROLLERS>>();
+    motors.sort_unstable_by_key(|(id, _)| *id);
+    if motors.is_empty() {
+        motors.push((0, &no_motor)).ok();
+    }
+    motors.truncate(2);
+
+    // Label, the command of the throttle and one column per motor controller
+    const COLUMNS: [i32; 4] = [15, 400, 580, 760];
+    let mut offset_y = 110;
+    Text::with_alignment(
+        "Throttle",
+        Point::new(COLUMNS[1], offset_y),
+        font_normal_header,
+        Alignment::Right,
+    )
+    .draw(display)?;
+    for ((id, _), column) in motors.iter().zip(&COLUMNS[2..]) {
+        string_helper.clear();
+        write!(&mut string_helper, "VESC {}", id).unwrap();
+        Text::with_alignment(
+            string_helper.as_str(),
+            Point::new(*column, offset_y),
+            font_normal_header,
+            Alignment::Right,
+        )
+        .draw(display)?;
+    }
+
+    type MotorValue = fn(&MotorData) -> Option<f32>;
+    /// Label, what the throttle commands, what each controller measures, decimals and unit
+    type Row<'a> = (
+        &'a str,
+        Option<&'a DisplayValue<f32>>,
+        Option<MotorValue>,
+        usize,
+        &'a str,
+    );
+    // A value only the throttle or only the controllers have leaves the other columns empty
+    let rows: [Row; 8] = [
+        ("Lever", Some(&data.throttle_value), None, 1, " %"),
+        (
+            "Duty cycle",
+            Some(&data.throttle_to_vesc_duty_cycle),
+            Some(|motor| motor.duty_cycle.get().copied()),
+            1,
+            " %",
+        ),
+        (
+            "Current",
+            Some(&data.throttle_to_vesc_current),
+            Some(|motor| motor.current.get().copied()),
+            1,
+            " A",
+        ),
+        (
+            "RPM",
+            Some(&data.throttle_to_vesc_rpm),
+            Some(|motor| motor.rpm.get().map(|&rpm| rpm as f32)),
+            0,
+            "",
+        ),
+        (
+            "ADC1",
+            None,
+            Some(|motor| motor.adc1.get().copied()),
+            3,
+            " V",
+        ),
+        (
+            "ADC2",
+            None,
+            Some(|motor| motor.adc2.get().copied()),
+            3,
+            " V",
+        ),
+        (
+            "ADC3",
+            None,
+            Some(|motor| motor.adc3.get().copied()),
+            3,
+            " V",
+        ),
+        ("PPM", None, Some(|motor| motor.ppm.get().copied()), 3, ""),
+    ];
+    for (label, command, measured, decimals, unit) in rows {
+        offset_y += FONT_NORMAL_SPACE + 10;
+        Text::new(label, Point::new(COLUMNS[0], offset_y), font_normal).draw(display)?;
+
+        let mut values: heapless::Vec<(i32, Option<f32>), 3> = heapless::Vec::new();
+        if let Some(command) = command {
+            values.push((COLUMNS[1], command.get().copied())).ok();
+        }
+        if let Some(measured) = measured {
+            for ((_, motor), column) in motors.iter().zip(&COLUMNS[2..]) {
+                values.push((*column, measured(motor))).ok();
+            }
+        }
+        for (column, value) in values {
+            string_helper.clear();
+            match value {
+                Some(value) => write!(&mut string_helper, "{:.*}{}", decimals, value, unit),
+                None => write!(&mut string_helper, "N/A"),
+            }
+            .unwrap();
+            Text::with_alignment(
+                string_helper.as_str(),
+                Point::new(column, offset_y),
+                font_normal,
+                Alignment::Right,
+            )
+            .draw(display)?;
+        }
+    }
+
+    // The raw angle against the configured ends of the lever, to check the calibration
+    offset_y += 2 * FONT_NORMAL_SPACE;
+    string_helper.clear();
+    match data.throttle_raw_angle.get() {
+        Some(angle) => write!(&mut string_helper, "Raw angle: {}", angle),
+        None => write!(&mut string_helper, "Raw angle: N/A"),
+    }
+    .unwrap();
+    if let Some(config) = data.throttle_config.get() {
+        write!(
+            &mut string_helper,
+            " (forward {}, backward {})",
+            config.lever_forward, config.lever_backward
+        )
+        .unwrap();
+    }
+    Text::new(
+        string_helper.as_str(),
+        Point::new(COLUMNS[0], offset_y),
+        font_normal,
+    )
+    .draw(display)?;
+
+    Ok(())
+}
+
+/// The speed and the state of charge in the large digit font, the values a pilot reads most often
+/// and from the furthest away
+fn draw_pilot<D, C>(display: &mut D, data: &DisplayData) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor + From<BinaryColor>,
+{
+    use core::fmt::Write;
+
+    display.clear(BinaryColor::On.into())?;
+    let mut string_helper: String<64> = String::new();
+
+    let font_normal: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .build();
+
+    let font_normal_header: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .underline()
+        .build();
+
+    let font_large: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&assets::FONT_DIGITS_40X64)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .build();
+
+    let font_large_stale: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&assets::FONT_DIGITS_40X64)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .strikethrough()
+        .build();
+
+    Text::with_alignment(
+        "Pilot",
+        Point::new(400, 20),
+        font_normal_header,
+        Alignment::Center,
+    )
+    .draw(display)?;
+
+    Line::new(Point::new(0, 70), Point::new(800, 70))
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 1))
+        .draw(display)?;
+    Line::new(Point::new(400, 70), Point::new(400, 480))
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 1))
+        .draw(display)?;
+
+    // The digit font has no letters, missing values are dashes
+    let fix = *data.gnss_fix.get().unwrap_or(&true);
+    let readouts = [
+        (
+            200,
+            if fix {
+                "Speed km/h"
+            } else {
+                "Speed km/h, no fix"
+            },
+            &data.speed_kmh,
+            data.speed_kmh.last().copied().filter(|_| fix),
+            "",
+        ),
+        (
+            600,
+            "State of Charge",
+            &data.battery_state_of_charge,
+            data.battery_state_of_charge.last().copied(),
+            "%",
+        ),
+    ];
+    for (center_x, label, value, last, unit) in readouts {
+        string_helper.clear();
+        string_helper.push_str(label).unwrap();
+        write_age(&mut string_helper, value).unwrap();
+        Text::with_alignment(
+            string_helper.as_str(),
+            Point::new(center_x, 160),
+            font_normal,
+            Alignment::Center,
+        )
+        .draw(display)?;
+
+        string_helper.clear();
+        match last.filter(|last| last.is_finite()) {
+            Some(last) => write!(&mut string_helper, "{:.1}{}", last, unit),
+            None => write!(&mut string_helper, "--{}", unit),
+        }
+        .unwrap();
+        Text::with_alignment(
+            string_helper.as_str(),
+            Point::new(center_x, 290),
+            if value.is_stale() {
+                font_large_stale
+            } else {
+                font_large
+            },
+            Alignment::Center,
+        )
+        .draw(display)?;
+    }
+
+    Ok(())
+}
+
+/// Artificial horizon of the IMU with the pitch, roll and heave rate, and their range since the
+/// start for comparing runs with different foil settings
+fn draw_trim<D, C>(display: &mut D, data: &DisplayData) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor + From<BinaryColor>,
+{
+    use core::fmt::Write;
+
+    display.clear(BinaryColor::On.into())?;
+    let mut string_helper: String<64> = String::new();
+
+    let font_normal: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .build();
+
+    let font_normal_header: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .underline()
+        .build();
+
+    let font_small: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_6X10)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .build();
+    const FONT_NORMAL_SPACE: i32 = 20;
+
+    Text::with_alignment(
+        "Trim",
+        Point::new(400, FONT_NORMAL_SPACE),
+        font_normal_header,
+        Alignment::Center,
+    )
+    .draw(display)?;
+
+    Line::new(Point::new(0, 70), Point::new(800, 70))
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 1))
+        .draw(display)?;
+
+    let attitude = data
+        .imu_pitch
+        .get()
+        .copied()
+        .zip(data.imu_roll.get().copied());
+    attitude::draw_attitude(display, attitude, Point::new(220, 265), 160)?;
+
+    let offset_left = 460;
+    let mut offset_y = 130;
+    let lines: [(&str, &DisplayValue<f32>, usize, &str); 3] = [
+        ("Pitch", &data.imu_pitch, 1, "deg"),
+        ("Roll", &data.imu_roll, 1, "deg"),
+        ("Heave rate", &data.imu_heave_rate, 2, "m/s"),
+    ];
+    for (label, value, decimals, unit) in lines {
+        string_helper.clear();
+        match value.get() {
+            Some(value) => write!(
+                &mut string_helper,
+                "{}: {:+.*} {}",
+                label, decimals, value, unit
+            ),
+            None => write!(&mut string_helper, "{}: N/A", label),
+        }
+        .unwrap();
+        Text::new(
+            string_helper.as_str(),
+            Point::new(offset_left, offset_y),
+            font_normal,
+        )
+        .draw(display)?;
+
+        if let Some(statistics) = value.statistics() {
+            string_helper.clear();
+            write!(
+                &mut string_helper,
+                "min {:+.*} max {:+.*} avg {:+.*}",
+                decimals,
+                statistics.min,
+                decimals,
+                statistics.max,
+                decimals,
+                statistics.average()
+            )
+            .unwrap();
+            Text::new(
+                string_helper.as_str(),
+                Point::new(offset_left, offset_y + 16),
+                font_small,
+            )
+            .draw(display)?;
+        }
+        offset_y += 3 * FONT_NORMAL_SPACE;
+    }
+
+    Ok(())
+}
+
+/// The cell voltages in two columns, with the lowest and highest cell and the ones the BMS balances
+/// marked
+fn draw_cells<D, C>(display: &mut D, data: &DisplayData) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor + From<BinaryColor>,
+{
+    use core::fmt::Write;
+
+    display.clear(BinaryColor::On.into())?;
+    let mut string_helper: String<64> = String::new();
+
+    let font_normal: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .build();
+
+    let font_normal_stale: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .strikethrough()
+        .build();
+
+    let font_normal_inverted: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::On.into())
+        .background_color(BinaryColor::Off.into())
+        .build();
+
+    let font_normal_header: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .underline()
+        .build();
+    const FONT_NORMAL_SPACE: i32 = 20;
+    const ROW_SPACE: i32 = 40;
+    let voltages = &data.battery_cell_voltages;
+    // Cells per column
+    let rows = voltages.len().div_ceil(2);
+
+    Text::with_alignment(
+        "Cells",
+        Point::new(400, FONT_NORMAL_SPACE),
+        font_normal_header,
+        Alignment::Center,
+    )
+    .draw(display)?;
+
+    Line::new(Point::new(0, 70), Point::new(800, 70))
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 1))
+        .draw(display)?;
+    Line::new(
+        Point::new(400, 130),
+        Point::new(400, 130 + rows as i32 * ROW_SPACE),
+    )
+    .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 1))
+    .draw(display)?;
+
+    let by_voltage = |(_, voltage): &(usize, f32)| *voltage;
+    let valid = || {
+        voltages
+            .iter()
+            .enumerate()
+            .filter_map(|(cell, voltage)| Some((cell, *voltage.get()?)))
+    };
+    let lowest = valid().min_by(|a, b| by_voltage(a).total_cmp(&by_voltage(b)));
+    let highest = valid().max_by(|a, b| by_voltage(a).total_cmp(&by_voltage(b)));
+
+    string_helper.clear();
+    match (lowest, highest) {
+        (Some((_, min)), Some((_, max))) => write!(
+            &mut string_helper,
+            "Min {:4.0} mV  Max {:4.0} mV  Difference {:3.0} mV",
+            min * 1000.0,
+            max * 1000.0,
+            (max - min) * 1000.0
+        ),
+        _ => write!(&mut string_helper, "No cell voltages"),
+    }
+    .unwrap();
+    Text::new(string_helper.as_str(), Point::new(15, 100), font_normal).draw(display)?;
+
+    for (cell, voltage) in voltages.iter().enumerate() {
+        let left = 15 + (cell / rows) as i32 * 400;
+        let offset_y = 160 + (cell % rows) as i32 * ROW_SPACE;
+
+        string_helper.clear();
+        write!(&mut string_helper, "Cell {:2}", cell + 1).unwrap();
+        Text::new(
+            string_helper.as_str(),
+            Point::new(left, offset_y),
+            font_normal,
+        )
+        .draw(display)?;
+
+        // A stale voltage is still shown, struck through
+        string_helper.clear();
+        match voltage.last() {
+            Some(voltage) => write!(&mut string_helper, "{:4.0} mV", voltage * 1000.0),
+            None => write!(&mut string_helper, "N/A"),
+        }
+        .unwrap();
+        Text::with_alignment(
+            string_helper.as_str(),
+            Point::new(left + 180, offset_y),
+            if voltage.is_stale() {
+                font_normal_stale
+            } else {
+                font_normal
+            },
+            Alignment::Right,
+        )
+        .draw(display)?;
+
+        if data.is_cell_balancing(cell) {
+            Text::new(
+                "BAL",
+                Point::new(left + 200, offset_y),
+                font_normal_inverted,
+            )
+            .draw(display)?;
+        }
+        let extreme = if lowest.is_some_and(|(lowest, _)| lowest == cell) {
+            "min"
+        } else if highest.is_some_and(|(highest, _)| highest == cell) {
+            "max"
+        } else {
+            ""
+        };
+        Text::new(extreme, Point::new(left + 250, offset_y), font_normal).draw(display)?;
+    }
+
+    Ok(())
+}
+
+/// Every node type with the firmware it identified itself with, missing nodes inverted
+fn draw_inventory<D, C>(display: &mut D, data: &DisplayData) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor + From<BinaryColor>,
+{
+    use core::fmt::Write;
+
+    display.clear(BinaryColor::On.into())?;
+    let mut string_helper: String<64> = String::new();
+
+    let font_normal: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .build();
+
+    let font_normal_inverted: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::On.into())
+        .background_color(BinaryColor::Off.into())
+        .build();
+
+    let font_normal_header: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .underline()
+        .build();
+    const FONT_NORMAL_SPACE: i32 = 20;
+    const ROW_SPACE: i32 = 28;
+
+    Text::with_alignment(
+        "Nodes",
+        Point::new(400, FONT_NORMAL_SPACE),
+        font_normal_header,
+        Alignment::Center,
+    )
+    .draw(display)?;
+
+    Line::new(Point::new(0, 70), Point::new(800, 70))
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 1))
+        .draw(display)?;
+
+    let columns = [15, 250, 550];
+    for (column, header) in columns.into_iter().zip(["Node", "Firmware", "Status"]) {
+        Text::new(header, Point::new(column, 110), font_normal_header).draw(display)?;
+    }
+
+    for (row, node) in NodeType::ALL.into_iter().enumerate() {
+        let offset_y = 110 + (row as i32 + 1) * ROW_SPACE;
+        Text::new(node.label(), Point::new(columns[0], offset_y), font_normal).draw(display)?;
+
+        // A missing node keeps the firmware it last identified itself with
+        let identification = data.node_inventory.get(node);
+        string_helper.clear();
+        match identification.last() {
+            Some(identification) => write!(&mut string_helper, "{}", identification),
+            None => write!(&mut string_helper, "-"),
+        }
+        .unwrap();
+        Text::new(
+            string_helper.as_str(),
+            Point::new(columns[1], offset_y),
+            font_normal,
+        )
+        .draw(display)?;
+
+        let (status, font) = match identification.last() {
+            None => ("Not seen", font_normal),
+            Some(_) if identification.is_stale() => ("Missing", font_normal_inverted),
+            Some(_) => ("OK", font_normal),
+        };
+        Text::new(status, Point::new(columns[2], offset_y), font).draw(display)?;
+    }
+
+    Ok(())
+}
+
+/// The active alarms and the history of raised and cleared ones, newest first, with the
+/// race-local time of the GNSS or how long ago without it
+fn draw_alarms<D, C>(display: &mut D, data: &DisplayData) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor + From<BinaryColor>,
+{
+    use core::fmt::Write;
+
+    display.clear(BinaryColor::On.into())?;
+    let mut string_helper: String<64> = String::new();
+
+    let font_normal: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .build();
+
+    let font_normal_inverted: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::On.into())
+        .background_color(BinaryColor::Off.into())
+        .build();
+
+    let font_normal_header: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .underline()
+        .build();
+    const FONT_NORMAL_SPACE: i32 = 20;
+    const ROW_SPACE: i32 = 24;
+    /// Rows end above the banner
+    const LAST_ROW: i32 = 430;
+
+    Text::with_alignment(
+        "Alarms",
+        Point::new(400, FONT_NORMAL_SPACE),
+        font_normal_header,
+        Alignment::Center,
+    )
+    .draw(display)?;
+
+    Line::new(Point::new(0, 70), Point::new(800, 70))
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 1))
+        .draw(display)?;
+
+    let history = &data.alarm_history;
+    let mut position = Text::new("Active:", Point::new(15, 100), font_normal).draw(display)?;
+    if history.active().is_empty() {
+        Text::new(" none", position, font_normal).draw(display)?;
+    }
+    for alarm in history.active() {
+        string_helper.clear();
+        write!(&mut string_helper, "{}", alarm).unwrap();
+        position = Text::new(" ", position, font_normal).draw(display)?;
+        position =
+            Text::new(string_helper.as_str(), position, font_normal_inverted).draw(display)?;
+    }
+
+    let columns = [15, 130, 250];
+    for (column, header) in columns.into_iter().zip(["Time", "Event", "Alarm"]) {
+        Text::new(header, Point::new(column, 145), font_normal_header).draw(display)?;
+    }
+    if history.events().next().is_none() {
+        Text::new(
+            "No alarms since the start",
+            Point::new(15, 175),
+            font_normal,
+        )
+        .draw(display)?;
+    }
+
+    let offset = data.time_utc_offset.last().copied().unwrap_or_default();
+    let rows = (145 + ROW_SPACE..LAST_ROW).step_by(ROW_SPACE as usize);
+    for (offset_y, event) in rows.zip(history.events().rev()) {
+        string_helper.clear();
+        match event.time {
+            Some(time) => {
+                let local = time.with_offset(offset);
+                write!(
+                    &mut string_helper,
+                    "{:02}:{:02}:{:02}",
+                    local.hours, local.minutes, local.seconds
+                )
+            }
+            None => match event.at.map(|at| time::since(at).as_secs()) {
+                Some(seconds @ 0..60) => write!(&mut string_helper, "-{} s", seconds),
+                Some(seconds @ 60..3600) => write!(&mut string_helper, "-{} min", seconds / 60),
+                Some(seconds) => write!(&mut string_helper, "-{} h", seconds / 3600),
+                None => write!(&mut string_helper, "Earlier"),
+            },
+        }
+        .unwrap();
+        Text::new(
+            string_helper.as_str(),
+            Point::new(columns[0], offset_y),
+            font_normal,
+        )
+        .draw(display)?;
+
+        let (event_text, font) = if event.raised {
+            ("Raised", font_normal_inverted)
+        } else {
+            ("Cleared", font_normal)
+        };
+        Text::new(event_text, Point::new(columns[1], offset_y), font).draw(display)?;
+
+        string_helper.clear();
+        write!(&mut string_helper, "{}", event.alarm).unwrap();
+        Text::new(
+            string_helper.as_str(),
+            Point::new(columns[2], offset_y),
+            font_normal,
+        )
+        .draw(display)?;
+    }
+
+    Ok(())
+}
+
+/// The last lap next to the best one, with the difference between them
+fn draw_strategy<D, C>(display: &mut D, data: &DisplayData) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor + From<BinaryColor>,
+{
+    use core::fmt::Write;
+
+    display.clear(BinaryColor::On.into())?;
+    let mut string_helper: String<64> = String::new();
+
+    let font_normal: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .build();
+
+    let font_normal_header: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .underline()
+        .build();
+    const FONT_NORMAL_SPACE: i32 = 20;
+
+    Text::with_alignment(
+        "Strategy",
+        Point::new(400, FONT_NORMAL_SPACE),
+        font_normal_header,
+        Alignment::Center,
+    )
+    .draw(display)?;
+
+    string_helper.clear();
+    write!(&mut string_helper, "Laps: {}", data.lap_counter.laps()).unwrap();
+    Text::with_alignment(
+        string_helper.as_str(),
+        Point::new(400, FONT_NORMAL_SPACE * 3),
+        font_normal,
+        Alignment::Center,
+    )
+    .draw(display)?;
+
+    Line::new(Point::new(0, 70), Point::new(800, 70))
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 1))
+        .draw(display)?;
+
+    let history = &data.lap_history;
+    let (Some(last), Some(best)) = (history.last(), history.best()) else {
+        Text::with_alignment(
+            "No lap completed yet",
+            Point::new(400, 200),
+            font_normal,
+            Alignment::Center,
+        )
+        .draw(display)?;
+        return Ok(());
+    };
+
+    // Label and one column each for the last lap, the best lap and their difference
+    const COLUMNS: [i32; 4] = [15, 330, 530, 730];
+    let mut offset_y = 110;
+    for (column, header) in COLUMNS.iter().zip(["", "Last", "Best", "Difference"]) {
+        Text::with_alignment(
+            header,
+            Point::new(*column, offset_y),
+            font_normal_header,
+            if *column == COLUMNS[0] {
+                Alignment::Left
+            } else {
+                Alignment::Right
+            },
+        )
+        .draw(display)?;
+    }
+
+    let seconds = |summary: &LapSummary| summary.time.as_millis() as f32 / 1000.0;
+    let rows: [(&str, f32, f32, &str); 4] = [
+        ("Lap", f32::from(last.lap), f32::from(best.lap), ""),
+        ("Time", seconds(last), seconds(best), " s"),
+        (
+            "Average speed",
+            last.average_speed_kmh,
+            best.average_speed_kmh,
+            " km/h",
+        ),
+        ("Energy", last.energy_wh, best.energy_wh, " Wh"),
+    ];
+    for (label, last, best, unit) in rows {
+        offset_y += FONT_NORMAL_SPACE + 10;
+        Text::new(label, Point::new(COLUMNS[0], offset_y), font_normal).draw(display)?;
+        let decimals = if unit.is_empty() { 0 } else { 1 };
+        for (column, value, difference) in [
+            (COLUMNS[1], last, false),
+            (COLUMNS[2], best, false),
+            (COLUMNS[3], last - best, true),
+        ] {
+            string_helper.clear();
+            match (difference, unit.is_empty()) {
+                // The difference of the lap numbers says nothing
+                (true, true) => continue,
+                (true, false) => write!(&mut string_helper, "{:+.*}{}", decimals, value, unit),
+                (false, _) => write!(&mut string_helper, "{:.*}{}", decimals, value, unit),
+            }
+            .unwrap();
+            Text::with_alignment(
+                string_helper.as_str(),
+                Point::new(column, offset_y),
+                font_normal,
+                Alignment::Right,
+            )
+            .draw(display)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Hidden page with the frame rate and where the time of a frame goes
+fn draw_render_stats<D, C>(display: &mut D, data: &DisplayData) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor + From<BinaryColor>,
+{
+    use core::fmt::Write;
+
+    display.clear(BinaryColor::On.into())?;
+    let mut string_helper: String<64> = String::new();
+
+    let font_normal: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .build();
+
+    let font_normal_header: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .underline()
+        .build();
+    const FONT_NORMAL_SPACE: i32 = 20;
+
+    Text::with_alignment(
+        "Render",
+        Point::new(400, FONT_NORMAL_SPACE),
+        font_normal_header,
+        Alignment::Center,
+    )
+    .draw(display)?;
+
+    Line::new(Point::new(0, 70), Point::new(800, 70))
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 1))
+        .draw(display)?;
+
+    let render = &data.render;
+    let offset_left = 15;
+    let mut offset_y = 100;
+
+    string_helper.clear();
+    match render.fps {
+        Some(fps) => write!(&mut string_helper, "Frame rate: {:.1} fps", fps),
+        None => write!(&mut string_helper, "Frame rate: N/A"),
+    }
+    .unwrap();
+    Text::with_alignment(
+        string_helper.as_str(),
+        Point::new(offset_left, offset_y),
+        font_normal,
+        Alignment::Left,
+    )
+    .draw(display)?;
+
+    // The frame drawn before this one, this one is still being drawn
+    let millis = |duration: Duration| duration.as_micros() as f32 / 1000.0;
+    let last = render.last.as_ref();
+    let lines: [(&str, Option<Duration>); 9] = [
+        ("Frame time", last.map(|last| last.frame_time())),
+        ("Text", last.map(|last| last.text)),
+        ("Rectangles", last.map(|last| last.rectangles)),
+        ("Pixels", last.map(|last| last.pixels)),
+        ("Clear", last.map(|last| last.clear)),
+        ("Drawing", last.map(|last| last.total)),
+        ("Flush", last.map(|last| last.flush)),
+        ("Slowest", render.fps.map(|_| render.slowest)),
+        ("Budget", Some(render.budget())),
+    ];
+    for (label, value) in lines {
+        offset_y += FONT_NORMAL_SPACE;
+        string_helper.clear();
+        match value {
+            Some(value) => write!(&mut string_helper, "{}: {:.1} ms", label, millis(value)),
+            None => write!(&mut string_helper, "{}: N/A", label),
+        }
+        .unwrap();
+        Text::with_alignment(
+            string_helper.as_str(),
+            Point::new(offset_left, offset_y),
+            font_normal,
+            Alignment::Left,
+        )
+        .draw(display)?;
+    }
+
+    offset_y += FONT_NORMAL_SPACE;
+    string_helper.clear();
+    write!(
+        &mut string_helper,
+        "Over budget: {} frames",
+        render.over_budget
+    )
+    .unwrap();
+    Text::with_alignment(
+        string_helper.as_str(),
+        Point::new(offset_left, offset_y),
+        font_normal,
+        Alignment::Left,
+    )
+    .draw(display)?;
+
+    Ok(())
+}
+
+/// Rows of the solar section of the main page: one column with a bar of the power while the panels
+/// fit below each other, two columns without the bars for more
+struct PanelLayout {
+    rows: usize,
+    row_height: i32,
+    bars: bool,
+}
+
+impl PanelLayout {
+    /// Rows from the header of the section to the bottom of the display
+    const MAX_ROWS: usize = 11;
+    const TOP: i32 = 375;
+    const COLUMN_WIDTH: i32 = 190;
+
+    fn new(panels: usize, row_height: i32) -> Self {
+        let columns = panels.div_ceil(Self::MAX_ROWS).max(1);
+        Self {
+            rows: panels.div_ceil(columns).max(1),
+            row_height,
+            bars: columns == 1,
+        }
+    }
+
+    /// Bottom left of the text of the panel, the columns are filled one after the other
+    fn bottom_left(&self, panel: usize) -> Point {
+        let column = (panel / self.rows) as i32;
+        let row = (panel % self.rows) as i32;
+        Point::new(
+            15 + column * Self::COLUMN_WIDTH,
+            Self::TOP + row * self.row_height,
+        )
+    }
+}
+
+/// How long ago a stale value was updated, like ` (12 s)`, nothing for valid values
+fn write_age<T>(string: &mut String<64>, value: &DisplayValue<T>) -> core::fmt::Result {
+    use core::fmt::Write;
+
+    match value.get_stale() {
+        Some((_, age)) if value.is_stale() => match age.as_secs() {
+            seconds @ 0..60 => write!(string, " ({} s)", seconds),
+            seconds @ 60..3600 => write!(string, " ({} min)", seconds / 60),
+            seconds => write!(string, " ({} h)", seconds / 3600),
+        },
+        _ => Ok(()),
+    }
+}
+
+fn scale_to_range(in_min: f32, in_max: f32, input: f32, out_max: i32) -> i32 {
+    let corrected_input = if input.is_nan() {
+        in_min
+    } else {
+        input.clamp(in_min, in_max)
+    };
+    (((corrected_input - in_min) / (in_max - in_min)) * out_max as f32) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32;
+
+    use super::*;
+    use eoi_can_decoder::parse_eoi_can_data;
+    use eoi_can_decoder::signals::{sample_frames, MESSAGES};
+    #[test]
+    fn scale_cell_voltages() {
+        let range_to_scale_to = 100;
+        assert_eq!(scale_to_range(2.5, 4.2, 4.2, range_to_scale_to), 100);
+        assert_eq!(scale_to_range(2.5, 4.2, 2.5, range_to_scale_to), 0);
+        assert_eq!(scale_to_range(2.5, 4.2, 3.35, range_to_scale_to), 50);
+        assert_eq!(scale_to_range(2.5, 4.2, f32::NAN, range_to_scale_to), 0);
+    }
+
+    #[test]
+    fn dead_band_and_debounce() {
+        let mut throttle = DisplayValue::default().with_dead_band(0.5);
+        throttle.update(40.0_f32);
+        throttle.update(40.4);
+        assert_eq!(throttle.get(), Some(&40.0));
+        throttle.update(39.5);
+        assert_eq!(throttle.get(), Some(&39.5));
+        throttle.update(f32::NAN);
+        assert!(throttle.get().unwrap().is_nan());
+
+        let mut state = DisplayValue::default().with_debounce(2);
+        state.update(BatteryState::On);
+        // A single corrupted frame in between
+        state.update(BatteryState::Idle);
+        state.update(BatteryState::On);
+        state.update(BatteryState::Idle);
+        assert_eq!(state.get(), Some(&BatteryState::On));
+        state.update(BatteryState::Idle);
+        assert_eq!(state.get(), Some(&BatteryState::Idle));
+    }
+
+    #[test]
+    fn smoothing_follows_the_time_not_the_rate() {
+        let clock = VirtualClock::starting_at(time::Instant::now());
+        clock.install();
+        let smoothed = |interval: Duration, updates: u32| {
+            let mut speed = DisplayValue::default().with_smoothing(Duration::from_millis(400));
+            speed.update(0.0_f32);
+            for _ in 0..updates {
+                clock.advance(interval);
+                speed.update(10.0);
+            }
+            *speed.get().unwrap()
+        };
+        // A second at 5 Hz ends up where a single value after a second does
+        let fast = smoothed(Duration::from_millis(200), 5);
+        let slow = smoothed(Duration::from_secs(1), 1);
+        assert!((fast - slow).abs() < 0.01, "{} {}", fast, slow);
+        assert!(slow > 9.0 && slow < 10.0);
+        VirtualClock::uninstall();
+
+        let mut speed = DisplayValue::default().with_smoothing(Duration::from_millis(400));
+        speed.update(f32::NAN);
+        speed.update(4.0);
+        assert_eq!(speed.get(), Some(&4.0));
+    }
+
+    #[test]
+    fn motors_are_kept_per_controller() {
+        let mut data = DisplayData::default();
+        let status = |rpm| VescMessage::StatusMessage1 {
+            rpm,
+            total_current: 0.0,
+            duty_cycle: 0.0,
+        };
+        data.ingest_eoi_can_data(EoiCanData::Vesc(
+            eoi_can_decoder::VescData::from_node_id(9, status(1000)).unwrap(),
+        ));
+        data.ingest_eoi_can_data(EoiCanData::Vesc(
+            eoi_can_decoder::VescData::from_node_id(10, status(2000)).unwrap(),
+        ));
+        assert_eq!(data.motors.len(), 2);
+        assert_eq!(data.motors[&9].rpm.get(), Some(&1000));
+        assert_eq!(data.motors[&10].rpm.get(), Some(&2000));
+    }
+
+    #[test]
+    fn panels_by_mppt_channel() {
+        let power = |voltage_in, current_in| {
+            MpptInfo::Channel1(MpptChannel::Power(eoi_can_decoder::MpptChannelPower {
+                voltage_in,
+                current_in,
+            }))
+        };
+        let mut data = DisplayData::default();
+        data.ingest_eoi_can_data(EoiCanData::Mppt(eoi_can_decoder::MpptData::Id4(power(
+            40.0, 2.5,
+        ))));
+        // Not a panel of the boat
+        data.ingest_eoi_can_data(EoiCanData::Mppt(eoi_can_decoder::MpptData::Id1(power(
+            40.0, 2.5,
+        ))));
+        assert_eq!(data.mppt_panel_info.len(), BOAT_PANELS.len());
+        assert_eq!(
+            data.mppt_panel_info.get(PanelId::new(4, 1)).unwrap().get(),
+            Some(&(100.0, 40.0, 2.5))
+        );
+        assert!(data.mppt_panel_info.get(PanelId::new(1, 1)).is_none());
+
+        data.mppt_panel_info = SolarPanels::observed();
+        data.ingest_eoi_can_data(EoiCanData::Mppt(eoi_can_decoder::MpptData::Id1(power(
+            40.0, 2.5,
+        ))));
+        assert_eq!(data.mppt_panel_info.len(), 1);
+
+        // Two columns once the panels don't fit below each other
+        let layout = PanelLayout::new(BOAT_PANELS.len(), 10);
+        assert!(layout.bars);
+        assert_eq!(layout.bottom_left(10), Point::new(15, 475));
+        let layout = PanelLayout::new(MAX_PANELS, 10);
+        assert!(!layout.bars);
+        assert_eq!(layout.bottom_left(8), Point::new(205, 375));
+    }
+
+    #[test]
+    fn motor_fault_changes() {
+        let mut data = DisplayData::default();
+        let fault = |fault| {
+            EoiCanData::Vesc(
+                eoi_can_decoder::VescData::from_node_id(9, VescMessage::Fault { fault }).unwrap(),
+            )
+        };
+        data.ingest_eoi_can_data(fault(VescFault::None));
+        assert_eq!(data.take_motor_fault_change(), None);
+        assert_eq!(data.motors[&9].active_fault(), None);
+
+        data.ingest_eoi_can_data(fault(VescFault::OverTempFet));
+        data.ingest_eoi_can_data(fault(VescFault::OverTempFet));
+        assert_eq!(
+            data.take_motor_fault_change(),
+            Some((9, VescFault::OverTempFet))
+        );
+        assert_eq!(data.take_motor_fault_change(), None);
+        assert_eq!(data.motors[&9].active_fault(), Some(VescFault::OverTempFet));
+
+        data.ingest_eoi_can_data(fault(VescFault::None));
+        assert_eq!(data.take_motor_fault_change(), Some((9, VescFault::None)));
+        assert_eq!(data.motors[&9].active_fault(), None);
+    }
+
+    #[test]
+    fn alarm_history_of_the_ingested_data() {
+        let mut data = DisplayData::default();
+        let fault = |fault| {
+            EoiCanData::Vesc(
+                eoi_can_decoder::VescData::from_node_id(9, VescMessage::Fault { fault }).unwrap(),
+            )
+        };
+        data.ingest_eoi_can_data(fault(VescFault::OverTempFet));
+        data.ingest_eoi_can_data(EoiCanData::Gnss(GnssData::GnssDateTime(GnssDateTime {
+            year: 2024,
+            month: 6,
+            day: 20,
+            hours: 14,
+            minutes: 5,
+            seconds: 30,
+        })));
+        data.ingest_eoi_can_data(fault(VescFault::None));
+
+        let events: heapless::Vec<_, 4> = data
+            .alarm_history
+            .events()
+            .map(|event| (event.raised, event.time.map(|time| time.minutes)))
+            .collect();
+        // Raised before the GNSS time was known
+        assert_eq!(events, [(true, None), (false, Some(5))]);
+        assert_eq!(data.alarm_history.active(), []);
+        assert_eq!(Page::from_index(6), Some(Page::Alarms));
+
+        data.battery_discharge_state.update(DischargeState::Error);
+        assert_eq!(data.active_alarms(), [Alarm::BatteryCutoff]);
+        assert!(Alarm::BatteryCutoff.is_critical());
+        assert!(!Alarm::Reserve.is_critical());
+    }
+
+    #[test]
+    fn throttle_faults_under_motor_power() {
+        let mut data = DisplayData::default();
+        let vesc = |message| {
+            EoiCanData::Vesc(eoi_can_decoder::VescData::from_node_id(9, message).unwrap())
+        };
+        data.ingest_eoi_can_data(vesc(VescMessage::StatusMessage4 {
+            fet_temp: 40.0,
+            motor_temp: 50.0,
+            total_input_current: 20.0,
+            current_pid_position: 0.0,
+        }));
+        data.ingest_eoi_can_data(vesc(VescMessage::StatusMessage5 {
+            input_voltage: 48.0,
+            tachometer: 0,
+        }));
+        let status = |deadman_missing| {
+            EoiCanData::Throttle(ThrottleData::Status(eoi_can_decoder::ThrottleStatus {
+                value: 80.0,
+                raw_angle: 0,
+                raw_deadmen: 0,
+                gain: 0,
+                error: ThrottleErrors {
+                    deadman_missing,
+                    ..ThrottleErrors::default()
+                },
+            }))
+        };
+        data.ingest_eoi_can_data(status(true));
+        data.ingest_eoi_can_data(status(true));
+        assert!(data.throttle_faults.deadman_missing.is_active());
+        data.ingest_eoi_can_data(status(false));
+
+        assert_eq!(data.throttle_faults.deadman_missing.count, 1);
+        assert_eq!(data.throttle_faults.deadman_missing.under_power, 1);
+        assert_eq!(data.throttle_faults.impedance_high.count, 0);
+    }
+
+    #[test]
+    fn clock_against_the_gnss_time() {
+        let mut data = DisplayData::default();
+        // 2024-06-20 14:05:30 UTC
+        let gnss_utc_us = 1_718_892_330_000_000;
+        data.check_clock(gnss_utc_us + 60_000_000);
+        assert_eq!(data.clock_check.divergence(), None);
+
+        data.ingest_eoi_can_data(EoiCanData::Gnss(GnssData::GnssDateTime(GnssDateTime {
+            year: 2024,
+            month: 6,
+            day: 20,
+            hours: 14,
+            minutes: 5,
+            seconds: 30,
+        })));
+        data.check_clock(gnss_utc_us + 1_000_000);
+        assert!(!data.clock_check.is_active());
+        data.check_clock(gnss_utc_us + 60_000_000);
+        assert!(data.clock_check.divergence().unwrap() > 59.0);
+        assert_eq!(data.alarm_history.active(), [Alarm::ClockDivergence]);
+    }
+
+    #[test]
+    fn throttle_commands_next_to_the_measurements() {
+        let mut data = DisplayData::default();
+        data.ingest_eoi_can_data(EoiCanData::Throttle(ThrottleData::ToVescDutyCycle(42.5)));
+        data.ingest_eoi_can_data(EoiCanData::Vesc(
+            eoi_can_decoder::VescData::from_node_id(
+                9,
+                VescMessage::StatusMessage6 {
+                    adc1: 1.25,
+                    adc2: 0.0,
+                    adc3: 0.0,
+                    ppm: 0.5,
+                },
+            )
+            .unwrap(),
+        ));
+        assert_eq!(data.throttle_to_vesc_duty_cycle.get(), Some(&42.5));
+        assert_eq!(data.throttle_to_vesc_current.get(), None);
+        assert_eq!(data.motors[&9].adc1.get(), Some(&1.25));
+        assert_eq!(Page::from_index(4), Some(Page::Commissioning));
+        assert_eq!(Page::Commissioning.next(), Page::Main);
+    }
+
+    #[test]
+    fn current_from_the_water_speed() {
+        let mut data = DisplayData::default();
+        data.ingest_eoi_can_data(EoiCanData::WaterSpeed(eoi_can_decoder::WaterSpeed {
+            speed_kmh: 12.0,
+            pulse_frequency: 26.4,
+        }));
+        assert_eq!(data.current_along_course_kmh(), None);
+
+        data.ingest_eoi_can_data(EoiCanData::Gnss(GnssData::GnssSpeedAndHeading(10.5, 90.0)));
+        assert_eq!(data.current_along_course_kmh(), Some(-1.5));
+        assert_eq!(data.water_speed_pulse_frequency.get(), Some(&26.4));
+
+        // Garbage from a bad frame still fits in the speed readout
+        data.ingest_eoi_can_data(EoiCanData::WaterSpeed(eoi_can_decoder::WaterSpeed {
+            speed_kmh: 1e30,
+            pulse_frequency: 26.4,
+        }));
+        let mut target = recording_target::RecordingTarget::new();
+        draw_page(&mut target, &data, Page::Main).unwrap();
+        assert!(target.find("/999.9 km/h").is_some());
+    }
+
+    #[test]
+    fn imu_without_heave() {
+        let mut data = DisplayData::default();
+        data.ingest_eoi_can_data(EoiCanData::Imu(eoi_can_decoder::ImuAttitude {
+            pitch: 2.5,
+            roll: -4.0,
+            heave_rate: None,
+        }));
+        assert_eq!(data.imu_pitch.get(), Some(&2.5));
+        assert_eq!(data.imu_roll.get(), Some(&-4.0));
+        assert_eq!(data.imu_heave_rate.get(), None);
+        assert_eq!(Page::from_index(5), Some(Page::Trim));
+    }
+
+    #[test]
+    fn balancing_per_cell() {
+        let mut data = DisplayData::default();
+        assert!(!data.is_cell_balancing(0));
+        data.battery_balancing_status.update(0b10_0000_0000_0101);
+        let balancing: heapless::Vec<usize, 14> = (0..14)
+            .filter(|cell| data.is_cell_balancing(*cell))
+            .collect();
+        assert_eq!(balancing, [0, 2, 13]);
+        assert_eq!(Page::from_index(8), Some(Page::Cells));
+        assert_eq!(Page::Cells.next(), Page::Main);
+        assert_eq!(Page::from_index(9), Some(Page::Exhibition));
+    }
+
+    #[test]
+    fn node_inventory_from_identifications() {
+        let mut data = DisplayData::default();
+        data.ingest_eoi_can_data(EoiCanData::NodeIdentification(
+            eoi_can_decoder::NodeIdentification {
+                node: NodeType::Throttle,
+                version_major: 1,
+                version_minor: 4,
+                version_patch: 2,
+                dirty: true,
+                git_hash: 0x1a2b_3c4d,
+            },
+        ));
+        let throttle = data.node_inventory.get(NodeType::Throttle).get();
+        assert_eq!(throttle.map(|id| id.version_minor), Some(4));
+        assert!(data.node_inventory.get(NodeType::Bms).get().is_none());
+        assert!(data.node_inventory.take_change());
+        assert_eq!(Page::from_index(10), Some(Page::Inventory));
+        assert_eq!(Page::Inventory.next(), Page::Main);
+        assert_eq!(Page::from_index(12), Some(Page::Throttle));
+        assert_eq!(Page::Throttle.next(), Page::Main);
+    }
+
+    #[test]
+    fn pack_configuration_from_frames() {
+        let mut data = DisplayData::default();
+        assert_eq!(data.battery_cell_voltages.len(), 14);
+        assert!(data.battery_temperatures.is_empty());
+
+        data.ingest_eoi_can_data(EoiCanData::EoiBattery(EoiBattery::CellVoltages15_16(
+            eoi_can_decoder::TwoCellVoltages {
+                cell_voltage: [3.7, 3.8],
+            },
+        )));
+        assert_eq!(data.battery_cell_voltages.len(), 16);
+        assert_eq!(data.battery_cell_voltages[15].get(), Some(&3.8));
+        assert_eq!(data.battery_cell_voltage_frames.cells(), 16);
+
+        // The third input has no sensor
+        data.update_battery_temperatures(0, &[25, 26, -128, 24]);
+        assert_eq!(data.battery_temperatures.len(), 4);
+        data = DisplayData::default();
+        data.update_battery_temperatures(0, &[25, 26, -128, -128]);
+        assert_eq!(data.battery_temperatures.len(), 2);
+        assert_eq!(data.battery_temperatures[1].get(), Some(&26));
+        // A BMS with 8 sensors sends the others in their own frame
+        data.ingest_eoi_can_data(EoiCanData::EoiBattery(EoiBattery::Temperatures5_8(
+            eoi_can_decoder::FourTemperatures {
+                temperatures: [30, 31, -128, -128],
+            },
+        )));
+        assert_eq!(data.battery_temperatures.len(), 6);
+        assert_eq!(data.battery_temperatures[5].get(), Some(&31));
+    }
+
+    #[test]
+    fn state_of_charge_of_a_cold_pack() {
+        let mut data = DisplayData::default();
+        data.battery_state_of_charge.update(60.0);
+        data.soc_correction = SocCorrection {
+            points: [(0.0, 0.5), (20.0, 1.0)]
+                .into_iter()
+                .map(|(temperature, usable)| CorrectionPoint {
+                    temperature,
+                    usable,
+                })
+                .collect(),
+        };
+        // Nothing is corrected without a temperature
+        assert_eq!(data.usable_state_of_charge(), Some(60.0));
+
+        // The coldest sensor counts, the broken one is left out
+        data.update_battery_temperatures(0, &[15, 10, -128, 18]);
+        assert_eq!(data.lowest_battery_temperature(), Some(10));
+        assert_eq!(data.usable_state_of_charge(), Some(45.0));
+
+        // So is the time to empty of the BMS
+        data.ingest_eoi_can_data(EoiCanData::EoiBattery(EoiBattery::TimeEstimate(
+            eoi_can_decoder::BmsTimeEstimate {
+                time_to_empty_min: Some(100),
+                time_to_full_min: None,
+            },
+        )));
+        assert_eq!(data.bms_time_to_empty.get(), Some(&100));
+        assert_eq!(data.battery_time_to_empty.get(), Some(&75));
+
+        // 18 % of which 13.5 % can be used is below the reserve
+        data.ingest_eoi_can_data(EoiCanData::EoiBattery(
+            EoiBattery::SocErrorFlagsAndBalancing(eoi_can_decoder::SocErrorFlagsAndBalancing {
+                state_of_charge: 18.0,
+                error_flags: 0,
+                balancing_status: 0,
+            }),
+        ));
+        assert!(data.reserve.is_active());
+    }
+
+    #[test]
+    fn time_to_empty_of_the_bms_is_preferred() {
+        let clock = VirtualClock::starting_at(time::Instant::now());
+        clock.install();
+        let mut data = DisplayData::default();
+        let state_of_charge = |data: &mut DisplayData, state_of_charge| {
+            data.ingest_eoi_can_data(EoiCanData::EoiBattery(
+                EoiBattery::SocErrorFlagsAndBalancing(eoi_can_decoder::SocErrorFlagsAndBalancing {
+                    state_of_charge,
+                    error_flags: 0,
+                    balancing_status: 0,
+                }),
+            ));
+        };
+        let bms = |data: &mut DisplayData, time_to_empty_min| {
+            data.ingest_eoi_can_data(EoiCanData::EoiBattery(EoiBattery::TimeEstimate(
+                eoi_can_decoder::BmsTimeEstimate {
+                    time_to_empty_min,
+                    time_to_full_min: None,
+                },
+            )));
+        };
+        let minute = Duration::from_secs(60);
+
+        // Without the BMS the time to empty is estimated, 1 % a minute
+        state_of_charge(&mut data, 80.0);
+        clock.advance(minute);
+        state_of_charge(&mut data, 79.0);
+        assert_eq!(data.battery_time_to_empty.get(), Some(&79));
+
+        bms(&mut data, Some(95));
+        assert_eq!(data.battery_time_to_empty.get(), Some(&95));
+        assert_eq!(data.battery_time_to_full.get(), None);
+        clock.advance(minute);
+        bms(&mut data, Some(94));
+        state_of_charge(&mut data, 78.0);
+        assert_eq!(data.battery_time_to_empty.get(), Some(&94));
+
+        // A BMS without an estimate or gone quiet leaves it to the local one again
+        bms(&mut data, None);
+        clock.advance(minute);
+        state_of_charge(&mut data, 77.0);
+        assert_eq!(data.battery_time_to_empty.get(), Some(&77));
+        VirtualClock::uninstall();
+    }
+
+    #[test]
+    fn peripheral_power_per_consumer() {
+        let mut data = DisplayData::default();
+        data.battery_voltage.update(50.0);
+        for (consumer, current) in [
+            (PeripheralConsumer::Pump, -0.5),
+            (PeripheralConsumer::Radio, -0.1),
+            (PeripheralConsumer::Pump, -0.4),
+        ] {
+            data.ingest_eoi_can_data(EoiCanData::PeripheralPower(
+                eoi_can_decoder::PeripheralPower { consumer, current },
+            ));
+        }
+        assert_eq!(data.peripheral_currents.len(), 2);
+        assert_eq!(data.peripheral_power(PeripheralConsumer::Pump), 20.0);
+        assert_eq!(data.peripheral_power(PeripheralConsumer::Radio), 5.0);
+        assert!(data.peripheral_power(PeripheralConsumer::Display).is_nan());
+
+        // Garbage from a bad frame still fits in the row
+        data.ingest_eoi_can_data(EoiCanData::PeripheralPower(
+            eoi_can_decoder::PeripheralPower {
+                consumer: PeripheralConsumer::Display,
+                current: -1e30,
+            },
+        ));
+        let mut target = recording_target::RecordingTarget::new();
+        draw_page(&mut target, &data, Page::Main).unwrap();
+        assert!(target.find("20/-/9999/5 W").is_some());
+    }
+
+    #[test]
+    fn net_power_without_a_current() {
+        let mut data = DisplayData::default();
+        data.battery_voltage.update(50.0);
+        data.battery_current_in.update(4.0);
+        data.battery_current_out_motor.update(-10.0);
+        assert!(data.battery_net_power().is_nan());
+
+        data.aggregate_policy = AggregatePolicy::Partial;
+        assert_eq!(
+            data.battery_net_power_aggregated(),
+            Aggregated {
+                value: -300.0,
+                partial: true
+            }
+        );
+    }
+
+    #[test]
+    fn page_requested_over_can() {
+        let mut data = DisplayData::default();
+        data.ingest_eoi_can_data(EoiCanData::DisplayControl(DisplayControl::Page(1)));
+        assert_eq!(data.requested_page.take(), Some(Page::Diagnostics));
+        data.ingest_eoi_can_data(EoiCanData::DisplayControl(DisplayControl::Page(13)));
+        assert_eq!(data.requested_page, None);
+
+        data.ingest_eoi_can_data(EoiCanData::DisplayControl(DisplayControl::Profile(1)));
+        assert_eq!(data.requested_profile, Some(Profile::TestBench));
+
+        data.trip.distance_km = 12.5;
+        data.lap_counter.restore(3, Duration::from_secs(600));
+        data.ingest_eoi_can_data(EoiCanData::DisplayControl(DisplayControl::TripReset));
+        assert_eq!(data.trip, TripCounters::default());
+        assert_eq!(data.lap_counter.laps(), 0);
+        assert_eq!(data.lap_counter.race_time(), None);
+    }
+
+    #[test]
+    fn stale_values_are_kept() {
+        let mut value = DisplayValue::with_timeout(Duration::from_secs(0));
+        assert_eq!(value.last(), None);
+        assert!(!value.is_stale());
+
+        value.update(12.5);
+        assert_eq!(value.get(), None);
+        assert_eq!(value.last(), Some(&12.5));
+        assert!(value.is_stale());
+
+        let mut value = DisplayValue::default();
+        value.update(12.5);
+        assert_eq!(value.get(), Some(&12.5));
+        assert!(!value.is_stale());
+    }
+
+    #[test]
+    fn statistics_of_the_session() {
+        let mut value = DisplayValue::<u16>::default().with_statistics();
+        assert_eq!(value.statistics(), None);
+        for minutes in [90, 60, 30] {
+            value.update(minutes);
+        }
+        let statistics = value.statistics().unwrap();
+        assert_eq!((statistics.min, statistics.max), (30.0, 90.0));
+        assert_eq!(statistics.average(), 60.0);
+
+        value.reset_statistics();
+        assert_eq!(value.statistics(), None);
+
+        // Only the values asked for keep statistics
+        let mut value = DisplayValue::default();
+        value.update(12.5);
+        assert_eq!(value.statistics(), None);
+    }
+
+    #[test]
+    fn statistics_of_the_net_power() {
+        let mut data = DisplayData::default();
+        data.battery_voltage.update(50.0);
+        data.battery_current_in.update(4.0);
+        data.battery_current_out_motor.update(-10.0);
+        for perri_current in [-1.0, -3.0] {
+            data.ingest_eoi_can_data(EoiCanData::EoiBattery(EoiBattery::PackAndPerriCurrent(
+                eoi_can_decoder::PackAndPerriCurrent {
+                    pack_current: -8.0,
+                    perri_current,
+                },
+            )));
+        }
+        let statistics = data.battery_net_power_samples.statistics().unwrap();
+        assert_eq!((statistics.min, statistics.max), (-450.0, -350.0));
+
+        data.statistics_overlay = true;
+        let mut target = recording_target::RecordingTarget::new();
+        draw_page(&mut target, &data, Page::Main).unwrap();
+        assert!(target.find("min -450 max -350 avg -400").is_some());
+    }
+
+    #[test]
+    fn age_of_stale_values() {
+        let mut value = DisplayValue::with_timeout(Duration::from_secs(0));
+        assert_eq!(value.get_stale(), None);
+
+        value.update(3);
+        let (last, age) = value.get_stale().unwrap();
+        assert_eq!(*last, 3);
+        assert!(age < Duration::from_secs(1));
+
+        let mut string = String::new();
+        write_age(&mut string, &value).unwrap();
+        // A slow test machine may already count a second
+        let seconds: u64 = string
+            .strip_prefix(" (")
+            .and_then(|age| age.strip_suffix(" s)"))
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(seconds <= 1);
+        // Valid values don't need an age
+        let mut value = DisplayValue::default();
+        value.update(3);
+        string.clear();
+        write_age(&mut string, &value).unwrap();
+        assert_eq!(string, "");
+    }
+
+    #[test]
+    fn throttle_config_is_kept() {
+        let mut data = DisplayData::default();
+        data.ingest_eoi_can_data(EoiCanData::Throttle(ThrottleData::Config(ThrottleConfig {
+            control_type: eoi_can_decoder::ThrottleControlType::Current,
+            lever_forward: 1200,
+            lever_backward: -800,
+        })));
+        let config = data.throttle_config.get().unwrap();
+        assert_eq!(config.lever_forward, 1200);
+        assert_eq!(config.lever_backward, -800);
+        assert_eq!(Page::Main.next(), Page::Diagnostics);
+        assert_eq!(Page::Diagnostics.next(), Page::Main);
+    }
+
+    /// Messages of the registry the display doesn't show, every other message has to end up in
+    /// some field of [`DisplayData`]
+    const NOT_SHOWN: &[&str] = &[
+        // Boards and values without a place on the display yet
+        "ServoRudderSetpoint",
+        "ServoRudderStatus",
+        "ServoRudderCommand",
+        "HeightSensorReserved1",
+        "HeightSensorReserved2",
+        "MpptChannel0State",
+        "MpptChannel1State",
+        "MpptChannel2State",
+        "MpptChannel3State",
+        "MpptStatus",
+        "GanMpptPower",
+        "GanMpptStatus",
+        "GanMpptSweepData",
+        "VescStatusMessage2",
+        "VescStatusMessage3",
+        // Commands, only what the boards report is shown
+        "ChargeControlCommand",
+        "VescPing",
+        // Sent by the displays themselves
+        "DisplayHeartbeat",
+        "DisplayLog",
+        "DisplayPanic",
+        // Only clears a banner this display shows
+        "DisplayAcknowledge",
+        // Syncs the clocks of the boards
+        "GnssTimeSync",
+        // Starts the trip over, the samples don't make a trip
+        "DisplayTripReset",
+    ];
+
+    #[test]
+    fn every_registered_message_is_shown() {
+        let mut not_shown = std::vec::Vec::new();
+        for message in MESSAGES {
+            // The other messages first, some values like the position need more than one
+            let mut display_data = DisplayData::default();
+            for (_, _, frame) in
+                sample_frames(0x00).filter(|(other, _, _)| other.name != message.name)
+            {
+                display_data.ingest_eoi_can_data(parse_eoi_can_data(&frame).unwrap());
+            }
+            let shown = sample_frames(0x01)
+                .filter(|(other, _, _)| other.name == message.name)
+                .any(|(_, _, frame)| {
+                    let before = format!("{:?}", display_data);
+                    display_data.ingest_eoi_can_data(parse_eoi_can_data(&frame).unwrap());
+                    format!("{:?}", display_data) != before
+                });
+            if !shown {
+                not_shown.push(message.name);
+            }
+        }
+        let mut expected = NOT_SHOWN.to_vec();
+        expected.sort_unstable();
+        not_shown.sort_unstable();
+        assert_eq!(not_shown, expected);
+    }
+
+    #[test]
+    fn digit_font_is_the_normal_font_scaled_up() {
+        use embedded_graphics::mock_display::MockDisplay;
+        use embedded_graphics::mono_font::MonoTextStyle;
+
+        for glyph in "0123456789.-+%: ".chars() {
+            let mut glyph_string: String<4> = String::new();
+            glyph_string.push(glyph).unwrap();
+            let mut normal = MockDisplay::new();
+            Text::new(
+                &glyph_string,
+                Point::new(0, 15),
+                MonoTextStyle::new(&FONT_10X20, BinaryColor::On),
+            )
+            .draw(&mut normal)
+            .unwrap();
+            let mut digits = MockDisplay::new();
+            Text::new(
+                &glyph_string,
+                Point::new(0, 45),
+                MonoTextStyle::new(&assets::FONT_DIGITS_30X60, BinaryColor::On),
+            )
+            .draw(&mut digits)
+            .unwrap();
+
+            for y in 0..60 {
+                for x in 0..30 {
+                    assert_eq!(
+                        digits.get_pixel(Point::new(x, y)),
+                        normal.get_pixel(Point::new(x / 3, y / 3)),
+                        "{:?} at {}, {}",
+                        glyph,
+                        x,
+                        y
+                    );
+                }
+            }
+        }
+        assert_eq!(assets::EOI_LOGO.size(), Size::new(70, 70));
+    }
+
+    #[test]
+    fn large_digit_font_has_no_letters() {
+        use embedded_graphics::mock_display::MockDisplay;
+        use embedded_graphics::mono_font::MonoTextStyle;
+
+        let style = MonoTextStyle::new(&assets::FONT_DIGITS_40X64, BinaryColor::On);
+        for glyph in ["0", "1", "5", "8", "%", "-", "."] {
+            let mut display = MockDisplay::new();
+            Text::new(glyph, Point::new(0, 59), style)
+                .draw(&mut display)
+                .unwrap();
+            assert!(display.affected_area().size.height > 0, "{}", glyph);
+        }
+        // Unknown characters are blank like the space
+        let mut display: MockDisplay<BinaryColor> = MockDisplay::new();
+        Text::new("N", Point::new(0, 59), style)
+            .draw(&mut display)
+            .unwrap();
+        assert_eq!(display.affected_area().size, Size::zero());
+    }
+
+    #[test]
+    fn state_of_charge_in_its_readout() {
+        let mut data = DisplayData::default();
+        data.battery_state_of_charge.update(97.7);
+        let mut target = recording_target::RecordingTarget::new();
+        draw_page(&mut target, &data, Page::Main).unwrap();
+
+        let soc = target.find("97.7 %").unwrap();
+        assert_eq!(soc.text, "97.7 %");
+        assert!(!soc.inverted);
+        assert_eq!(soc.bounding_box.center().x, 500);
+        let label = target.find("State of Charge").unwrap();
+        let readout = Rectangle::new(
+            Point::new(400, label.bounding_box.top_left.y),
+            Size::new(200, 80),
+        );
+        assert!(target.texts_in(readout).any(|text| text == soc));
+
+        // Without data the readout says so, neither 0 nor NaN
+        let mut target = recording_target::RecordingTarget::new();
+        draw_page(&mut target, &DisplayData::default(), Page::Main).unwrap();
+        assert!(target.texts_in(readout).any(|text| text.text == "N/A"));
+        assert!(!target
+            .texts_in(readout)
+            .any(|text| text.text.contains("NaN")));
     }
 }
@@ -0,0 +1,111 @@
+//! The nodes seen on the bus with their firmware, from the identification every node sends at boot
+//! and every minute. A node that stopped sending is kept, but shows as missing.
+
+use eoi_can_decoder::{NodeIdentification, NodeType, NODE_IDENTIFICATION_INTERVAL_S};
+
+use crate::time::Duration;
+use crate::DisplayValue;
+
+/// A node is missing after it skipped this many identifications
+const NODE_TIMEOUT: Duration = Duration::from_secs(3 * NODE_IDENTIFICATION_INTERVAL_S as u64);
+
+#[derive(Debug)]
+pub struct NodeInventory {
+    /// In the order of [`NodeType::ALL`]
+    nodes: [DisplayValue<NodeIdentification>; NodeType::ALL.len()],
+    changed: bool,
+}
+
+impl Default for NodeInventory {
+    fn default() -> Self {
+        Self {
+            nodes: core::array::from_fn(|_| DisplayValue::with_timeout(NODE_TIMEOUT)),
+            changed: false,
+        }
+    }
+}
+
+impl NodeInventory {
+    pub fn update(&mut self, identification: NodeIdentification) {
+        let node = &mut self.nodes[identification.node as usize];
+        // A new node, or one that booted another firmware
+        if node.last() != Some(&identification) {
+            self.changed = true;
+        }
+        node.update(identification);
+    }
+
+    pub fn get(&self, node: NodeType) -> &DisplayValue<NodeIdentification> {
+        &self.nodes[node as usize]
+    }
+
+    /// The nodes that identified themselves since the start, the missing ones are stale
+    pub fn iter(&self) -> impl Iterator<Item = (NodeType, &DisplayValue<NodeIdentification>)> {
+        NodeType::ALL
+            .into_iter()
+            .zip(&self.nodes)
+            .filter(|(_, identification)| identification.last().is_some())
+    }
+
+    /// Whether a node showed up or changed its firmware since the last call, for publishing the
+    /// inventory again
+    pub fn take_change(&mut self) -> bool {
+        core::mem::take(&mut self.changed)
+    }
+}
+
+/// The nodes by their label, `null` while missing, like
+/// `{"Throttle": {"node": "Throttle", "version_major": 1, ..., "git_hash": "1a2b3c4d"}}`
+#[cfg(feature = "serde")]
+impl serde::Serialize for NodeInventory {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        let mut map = serializer.serialize_map(Some(self.iter().count()))?;
+        for (node, identification) in self.iter() {
+            map.serialize_entry(node.label(), identification)?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identification(node: NodeType, version_patch: u8) -> NodeIdentification {
+        NodeIdentification {
+            node,
+            version_major: 1,
+            version_minor: 4,
+            version_patch,
+            dirty: false,
+            git_hash: 0x1a2b_3c4d,
+        }
+    }
+
+    #[test]
+    fn nodes_and_changes() {
+        let mut inventory = NodeInventory::default();
+        assert!(!inventory.take_change());
+        inventory.update(identification(NodeType::Throttle, 2));
+        inventory.update(identification(NodeType::Display, 0));
+        assert!(inventory.take_change());
+
+        // The same firmware every minute isn't a change
+        inventory.update(identification(NodeType::Throttle, 2));
+        assert!(!inventory.take_change());
+        inventory.update(identification(NodeType::Throttle, 3));
+        assert!(inventory.take_change());
+
+        let nodes: heapless::Vec<_, 4> = inventory
+            .iter()
+            .map(|(node, identification)| (node, identification.get().map(|id| id.version_patch)))
+            .collect();
+        assert_eq!(
+            nodes,
+            [(NodeType::Display, Some(0)), (NodeType::Throttle, Some(3))]
+        );
+        assert!(inventory.get(NodeType::Bms).get().is_none());
+    }
+}
@@ -0,0 +1,362 @@
+//! Parked screen for the dock. While the boat stands still and isn't charging, the main page is
+//! replaced by a QR code of the live dashboard and the summary of the session, so visitors and the
+//! team can follow the boat on their phones without asking for the address. The address of the
+//! dashboard is configured by the application with the IP address of the WiFi filled in, the QR
+//! code is generated when the page is drawn.
+
+use core::fmt::Write;
+
+use embedded_graphics::{
+    mono_font::{
+        ascii::{FONT_10X20, FONT_6X10},
+        MonoTextStyle, MonoTextStyleBuilder,
+    },
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::{Line, PrimitiveStyle, Rectangle},
+    text::{Alignment, Text},
+};
+use heapless::String;
+
+use crate::time::{self, Duration, Instant};
+use crate::DisplayData;
+
+/// Time standing still and not charging after which the parked screen is shown
+pub const DEFAULT_PARKED_AFTER: Duration = Duration::from_secs(5 * 60);
+/// Longest dashboard address, with the IP address filled in
+pub const DASHBOARD_URL_LEN: usize = 96;
+/// Below this speed in km/h the boat stands still
+const MOVING_KMH: f32 = 1.0;
+/// Above this charge current in A the battery is charging
+const CHARGING_A: f32 = 0.5;
+/// Placeholder of the dashboard address for the IP address of the WiFi
+const IP_PLACEHOLDER: &str = "{ip}";
+/// Area of the QR code with its quiet zone, the address is shown below it
+const QR_AREA: Rectangle = Rectangle::new(Point::new(40, 90), Size::new(320, 320));
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ParkedMode {
+    #[cfg_attr(feature = "serde", serde(skip))]
+    after: Duration,
+    /// Address of the dashboard, `{ip}` is replaced by the IP address of the WiFi
+    #[cfg_attr(feature = "serde", serde(skip))]
+    dashboard_url: Option<String<DASHBOARD_URL_LEN>>,
+    /// Since when the boat stands still and isn't charging
+    #[cfg_attr(feature = "serde", serde(skip))]
+    still_since: Option<Instant>,
+    active: bool,
+    /// The new state when it changed and the application didn't take it yet
+    #[cfg_attr(feature = "serde", serde(skip))]
+    changed: Option<bool>,
+}
+
+impl Default for ParkedMode {
+    fn default() -> Self {
+        Self::new(DEFAULT_PARKED_AFTER)
+    }
+}
+
+impl ParkedMode {
+    pub fn new(after: Duration) -> Self {
+        Self {
+            after,
+            dashboard_url: None,
+            still_since: None,
+            active: false,
+            changed: None,
+        }
+    }
+
+    /// Address of the dashboard in the QR code, like `http://{ip}:8080/api/state`. Too long
+    /// addresses are left out
+    pub fn with_dashboard_url(mut self, template: &str) -> Self {
+        self.dashboard_url = String::try_from(template).ok();
+        self
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Called with every received message, whether the boat stands still and isn't charging
+    pub fn update(&mut self, parked: bool) {
+        self.update_at(parked, time::now());
+    }
+
+    fn update_at(&mut self, parked: bool, now: Instant) {
+        let active = if parked {
+            let since = *self.still_since.get_or_insert(now);
+            now.duration_since(since) >= self.after
+        } else {
+            self.still_since = None;
+            false
+        };
+        if active != self.active {
+            self.active = active;
+            self.changed = Some(active);
+        }
+    }
+
+    /// Whether the parked screen started or ended since the last call, for the application to log
+    /// it
+    pub fn take_change(&mut self) -> Option<bool> {
+        self.changed.take()
+    }
+
+    /// The dashboard address with the IP address filled in, `None` when there is none or the IP
+    /// address isn't known
+    pub fn dashboard_url(&self, data: &DisplayData) -> Option<String<DASHBOARD_URL_LEN>> {
+        let template = self.dashboard_url.as_ref()?;
+        let Some((before, after)) = template.split_once(IP_PLACEHOLDER) else {
+            return Some(template.clone());
+        };
+        let mut url = String::new();
+        write!(&mut url, "{}{}{}", before, data.ip_address.get()?, after).ok()?;
+        Some(url)
+    }
+}
+
+impl DisplayData {
+    /// Whether the boat stands still by a known speed and the battery isn't charging
+    pub(crate) fn is_parked(&self) -> bool {
+        let still = self
+            .speed_kmh
+            .get()
+            .is_some_and(|&speed| speed < MOVING_KMH);
+        let charging = self
+            .battery_current_in
+            .get()
+            .is_some_and(|&current| current > CHARGING_A);
+        still && !charging
+    }
+}
+
+/// The QR code of the dashboard on the left and the summary of the session on the right
+pub fn draw_parked<D, C>(display: &mut D, data: &DisplayData) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor + From<BinaryColor>,
+{
+    display.clear(BinaryColor::On.into())?;
+    let mut string_helper: String<64> = String::new();
+
+    let font_normal: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .build();
+
+    let font_small: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_6X10)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .build();
+
+    let font_normal_header: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .underline()
+        .build();
+
+    Text::with_alignment(
+        "Parked",
+        Point::new(400, 20),
+        font_normal_header,
+        Alignment::Center,
+    )
+    .draw(display)?;
+
+    Line::new(Point::new(0, 70), Point::new(800, 70))
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 1))
+        .draw(display)?;
+    Line::new(Point::new(400, 70), Point::new(400, 480))
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 1))
+        .draw(display)?;
+
+    // The dashboard
+    match data.parked.dashboard_url(data) {
+        Some(url) => {
+            #[cfg(feature = "qr")]
+            draw_qr_code(display, &url)?;
+            #[cfg(not(feature = "qr"))]
+            Text::with_alignment(
+                "Live dashboard at",
+                QR_AREA.center(),
+                font_normal,
+                Alignment::Center,
+            )
+            .draw(display)?;
+            Text::with_alignment(
+                url.as_str(),
+                Point::new(QR_AREA.center().x, 440),
+                font_small,
+                Alignment::Center,
+            )
+            .draw(display)?;
+        }
+        None if data.parked.dashboard_url.is_some() => {
+            Text::with_alignment("No WiFi", QR_AREA.center(), font_normal, Alignment::Center)
+                .draw(display)?;
+        }
+        None => {}
+    }
+
+    // The session
+    Text::new("Session", Point::new(440, 110), font_normal_header).draw(display)?;
+    let trip = &data.trip;
+    write!(&mut string_helper, "{:.1} km", trip.distance_km).unwrap();
+    draw_row(display, 0, "Distance", &string_helper, font_normal)?;
+
+    string_helper.clear();
+    write!(&mut string_helper, "{:.0} Wh", trip.motor_energy_wh).unwrap();
+    draw_row(display, 1, "Motor energy", &string_helper, font_normal)?;
+
+    string_helper.clear();
+    write!(&mut string_helper, "{:.0} Wh", trip.solar_energy_wh).unwrap();
+    draw_row(display, 2, "Solar energy", &string_helper, font_normal)?;
+
+    string_helper.clear();
+    write!(&mut string_helper, "{}", data.lap_counter.laps()).unwrap();
+    draw_row(display, 3, "Laps", &string_helper, font_normal)?;
+
+    string_helper.clear();
+    match data.lap_history.best() {
+        Some(best) => write!(
+            &mut string_helper,
+            "{}:{:02}",
+            best.time.as_secs() / 60,
+            best.time.as_secs() % 60
+        ),
+        None => write!(&mut string_helper, "-"),
+    }
+    .unwrap();
+    draw_row(display, 4, "Best lap", &string_helper, font_normal)?;
+
+    string_helper.clear();
+    match data.battery_state_of_charge.last() {
+        Some(state_of_charge) => write!(&mut string_helper, "{:.1} %", state_of_charge),
+        None => write!(&mut string_helper, "N/A"),
+    }
+    .unwrap();
+    draw_row(display, 5, "State of Charge", &string_helper, font_normal)?;
+
+    Ok(())
+}
+
+/// A figure of the session with its label on the left and the value aligned on the right
+fn draw_row<D, C>(
+    display: &mut D,
+    row: i32,
+    label: &str,
+    value: &str,
+    font: MonoTextStyle<'_, C>,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor + From<BinaryColor>,
+{
+    const ROW_SPACE: i32 = 40;
+    let offset_y = 110 + (row + 1) * ROW_SPACE;
+    Text::new(label, Point::new(440, offset_y), font).draw(display)?;
+    Text::with_alignment(value, Point::new(770, offset_y), font, Alignment::Right).draw(display)?;
+    Ok(())
+}
+
+/// Dark modules on the light page, scaled to fill [`QR_AREA`] with a quiet zone of 4 modules
+#[cfg(feature = "qr")]
+fn draw_qr_code<D, C>(display: &mut D, url: &str) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor + From<BinaryColor>,
+{
+    use qrcodegen::{QrCode, QrCodeEcc};
+
+    let Ok(qr) = QrCode::encode_text(url, QrCodeEcc::Medium) else {
+        return Ok(());
+    };
+    let modules = qr.size() + 2 * 4;
+    let scale = QR_AREA.size.width as i32 / modules;
+    let origin = QR_AREA.center() - Point::new(qr.size() * scale / 2, qr.size() * scale / 2);
+    let style = PrimitiveStyle::with_fill(BinaryColor::Off.into());
+    for y in 0..qr.size() {
+        for x in 0..qr.size() {
+            if qr.get_module(x, y) {
+                Rectangle::new(
+                    origin + Point::new(x * scale, y * scale),
+                    Size::new(scale as u32, scale as u32),
+                )
+                .into_styled(style)
+                .draw(display)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::net::Ipv4Addr;
+
+    #[test]
+    fn shown_after_standing_still() {
+        let start = time::now();
+        let mut parked = ParkedMode::new(Duration::from_secs(300));
+        parked.update_at(true, start);
+        parked.update_at(true, start + Duration::from_secs(299));
+        assert!(!parked.is_active());
+        parked.update_at(true, start + Duration::from_secs(300));
+        assert!(parked.is_active());
+        assert_eq!(parked.take_change(), Some(true));
+
+        // Moving or charging leaves it right away and waits a full time again
+        parked.update_at(false, start + Duration::from_secs(400));
+        assert!(!parked.is_active());
+        parked.update_at(true, start + Duration::from_secs(401));
+        parked.update_at(true, start + Duration::from_secs(700));
+        assert!(!parked.is_active());
+    }
+
+    #[test]
+    fn dashboard_url_with_the_ip_address() {
+        let mut data = DisplayData::default();
+        assert_eq!(data.parked.dashboard_url(&data), None);
+        data.parked = ParkedMode::default().with_dashboard_url("http://{ip}:8080/api/state");
+        assert_eq!(data.parked.dashboard_url(&data), None);
+        data.ip_address.update(Ipv4Addr::new(10, 12, 0, 208));
+        assert_eq!(
+            data.parked.dashboard_url(&data).as_deref(),
+            Some("http://10.12.0.208:8080/api/state")
+        );
+    }
+
+    #[test]
+    fn replaces_the_main_page() {
+        let mut data = DisplayData {
+            parked: ParkedMode::default().with_dashboard_url("http://{ip}:8080/api/state"),
+            ..Default::default()
+        };
+        data.ip_address.update(Ipv4Addr::new(10, 12, 0, 208));
+        data.trip.distance_km = 12.34;
+        data.parked.active = true;
+        let mut target = crate::recording_target::RecordingTarget::new();
+        crate::draw_page(&mut target, &data, crate::Page::Main).unwrap();
+
+        assert!(target.find("Parked").is_some());
+        let session = Rectangle::new(Point::new(400, 70), Size::new(400, 410));
+        assert!(target.texts_in(session).any(|text| text.text == "12.3 km"));
+        let url = target.find("http://10.12.0.208:8080/api/state").unwrap();
+        assert_eq!(url.bounding_box.center().x, QR_AREA.center().x);
+        #[cfg(feature = "qr")]
+        assert!(
+            target
+                .shapes()
+                .iter()
+                .filter(|shape| QR_AREA.contains(shape.center()))
+                .count()
+                > 100
+        );
+    }
+}
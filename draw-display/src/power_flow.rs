@@ -0,0 +1,204 @@
+//! Widget drawing where the energy goes: from the solar panels into the battery and out of it to
+//! the motors and the peripherals. The arrows point the way the power flows, which a pilot sees
+//! quicker than the sign of a number.
+
+use core::fmt::Write;
+
+use embedded_graphics::{
+    mono_font::{ascii::FONT_6X10, MonoTextStyle, MonoTextStyleBuilder},
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::{Line, PrimitiveStyle, Rectangle, Triangle},
+    text::{Alignment, Text},
+};
+use heapless::String;
+
+use crate::aggregate::{draw_partial_marker, PARTIAL_MARKER_WIDTH};
+use crate::DisplayData;
+
+/// Below this power in W an arrow has no head, the flow is too small to have a direction
+const MIN_FLOW_POWER: f32 = 1.0;
+const ARROW_HEAD_LENGTH: i32 = 6;
+const ARROW_HEAD_WIDTH: i32 = 4;
+/// Largest power in W shown, more is shown as this
+const MAX_POWER_W: f32 = 99_999.0;
+
+/// Which way the power flows between two nodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Flow {
+    Forward,
+    Backward,
+    /// Too small or not known
+    None,
+}
+
+impl Flow {
+    /// Positive power flows forward
+    fn from_power(power: f32) -> Self {
+        if power >= MIN_FLOW_POWER {
+            Flow::Forward
+        } else if power <= -MIN_FLOW_POWER {
+            Flow::Backward
+        } else {
+            Flow::None
+        }
+    }
+}
+
+/// Draw the flow from the solar panels over the battery to the motors and the peripherals with
+/// the top left corner at `top_left`. It is 360x48 pixels, the place of three rows of the normal
+/// font
+pub fn draw_power_flow<D, C>(
+    display: &mut D,
+    data: &DisplayData,
+    top_left: Point,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor + From<BinaryColor>,
+{
+    let font_small: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_6X10)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .build();
+
+    let nodes = [
+        ("Solar", Point::new(0, 14), Size::new(56, 20)),
+        ("Battery", Point::new(140, 0), Size::new(80, 48)),
+        ("Motor", Point::new(300, 0), Size::new(60, 20)),
+        ("Periph.", Point::new(300, 28), Size::new(60, 20)),
+    ];
+    for (label, offset, size) in nodes {
+        Rectangle::new(top_left + offset, size)
+            .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 1))
+            .draw(display)?;
+        Text::with_alignment(
+            label,
+            top_left + offset + Point::new(size.width as i32 / 2, 13),
+            font_small,
+            Alignment::Center,
+        )
+        .draw(display)?;
+    }
+
+    // What is left for charging, negative while discharging
+    let net_power = data.battery_net_power_aggregated();
+    let mut string_helper: String<16> = String::new();
+    write_power(&mut string_helper, net_power.value, true);
+    let net_power_text = Text::with_alignment(
+        string_helper.as_str(),
+        top_left + Point::new(180, 36),
+        font_small,
+        Alignment::Center,
+    );
+    net_power_text.draw(display)?;
+    if net_power.partial {
+        let text = net_power_text.bounding_box();
+        draw_partial_marker(
+            display,
+            text.top_left + Point::new(-PARTIAL_MARKER_WIDTH, 1),
+        )?;
+    }
+
+    // The outputs are negative while they draw from the battery
+    let arrows = [
+        (58, 138, 24, data.battery_input_power()),
+        (222, 298, 10, -data.battery_motor_power()),
+        (222, 298, 38, -data.battery_peripherals_power()),
+    ];
+    for (start, end, y, power) in arrows {
+        draw_arrow(
+            display,
+            top_left + Point::new(start, y),
+            top_left + Point::new(end, y),
+            power,
+            font_small,
+        )?;
+    }
+    Ok(())
+}
+
+/// Horizontal arrow between `start` and `end`, pointing to `end` for positive power, labeled
+/// with the power above it
+fn draw_arrow<D, C>(
+    display: &mut D,
+    start: Point,
+    end: Point,
+    power: f32,
+    font: MonoTextStyle<'_, C>,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor + From<BinaryColor>,
+{
+    Line::new(start, end)
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 2))
+        .draw(display)?;
+
+    let head = match Flow::from_power(power) {
+        Flow::Forward => Some((end, -ARROW_HEAD_LENGTH)),
+        Flow::Backward => Some((start, ARROW_HEAD_LENGTH)),
+        Flow::None => None,
+    };
+    if let Some((tip, length)) = head {
+        Triangle::new(
+            tip,
+            tip + Point::new(length, -ARROW_HEAD_WIDTH),
+            tip + Point::new(length, ARROW_HEAD_WIDTH),
+        )
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::Off.into()))
+        .draw(display)?;
+    }
+
+    let mut string_helper: String<16> = String::new();
+    write_power(&mut string_helper, power.abs(), false);
+    Text::with_alignment(
+        string_helper.as_str(),
+        Point::new((start.x + end.x) / 2, start.y - 4),
+        font,
+        Alignment::Center,
+    )
+    .draw(display)?;
+    Ok(())
+}
+
+fn write_power(string: &mut String<16>, power: f32, signed: bool) {
+    if power.is_nan() {
+        let _ = string.push_str("N/A");
+        return;
+    }
+    // Garbage from a bad frame is clamped, so it always fits
+    let power = power.clamp(-MAX_POWER_W, MAX_POWER_W);
+    let _ = if signed {
+        write!(string, "{:+.0} W", power)
+    } else {
+        write!(string, "{:.0} W", power)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flow_direction() {
+        assert_eq!(Flow::from_power(250.0), Flow::Forward);
+        assert_eq!(Flow::from_power(-40.0), Flow::Backward);
+        assert_eq!(Flow::from_power(0.4), Flow::None);
+        assert_eq!(Flow::from_power(f32::NAN), Flow::None);
+
+        let mut string = String::new();
+        write_power(&mut string, -120.4, true);
+        assert_eq!(string, "-120 W");
+        string.clear();
+        write_power(&mut string, f32::NAN, false);
+        assert_eq!(string, "N/A");
+        string.clear();
+        write_power(&mut string, -1e30, true);
+        assert_eq!(string, "-99999 W");
+        string.clear();
+        write_power(&mut string, f32::INFINITY, false);
+        assert_eq!(string, "99999 W");
+    }
+}
@@ -0,0 +1,286 @@
+//! Profiles decide which pages a display goes through and whether they rotate on their own. The
+//! pilot only needs the main page and its large figures, while the test bench wants the details
+//! that would distract during a race.
+
+use core::str::FromStr;
+
+use crate::time::{self, Duration, Instant};
+use crate::Page;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    /// The main page, the button switches to the pilot page with the large figures and back
+    #[default]
+    Race,
+    /// All pages, rotating
+    TestBench,
+    /// The main page with the battery and the diagnostics, rotating slowly
+    Charging,
+    /// The detail pages, for checking the boat in the harbour
+    Diagnostics,
+    /// The exhibition page for visitors at demo events, and the main page now and then
+    Exhibition,
+}
+
+impl Profile {
+    /// Profile by its index on the CAN bus, see [`DisplayControl::Profile`]
+    ///
+    /// [`DisplayControl::Profile`]: eoi_can_decoder::DisplayControl::Profile
+    pub fn from_index(index: u8) -> Option<Self> {
+        match index {
+            0 => Some(Profile::Race),
+            1 => Some(Profile::TestBench),
+            2 => Some(Profile::Charging),
+            3 => Some(Profile::Diagnostics),
+            4 => Some(Profile::Exhibition),
+            _ => None,
+        }
+    }
+
+    /// Pages the button goes through, the first one is shown when switching to the profile
+    pub fn pages(self) -> &'static [Page] {
+        match self {
+            Profile::Race => &[Page::Main, Page::Pilot, Page::Diagnostics],
+            Profile::TestBench => &[
+                Page::Main,
+                Page::Cells,
+                Page::Strategy,
+                Page::Trim,
+                Page::Diagnostics,
+                Page::Commissioning,
+                Page::Throttle,
+                Page::Alarms,
+                Page::Render,
+            ],
+            Profile::Charging => &[Page::Main, Page::Cells, Page::Diagnostics],
+            Profile::Exhibition => &[Page::Exhibition, Page::Main],
+            Profile::Diagnostics => &[
+                Page::Diagnostics,
+                Page::Commissioning,
+                Page::Alarms,
+                Page::Inventory,
+                Page::Render,
+            ],
+        }
+    }
+
+    /// How long a page is shown before the next one, `None` when pages only change by the button
+    pub fn rotation_interval(self) -> Option<Duration> {
+        match self {
+            Profile::Race | Profile::Diagnostics => None,
+            Profile::TestBench => Some(Duration::from_secs(10)),
+            Profile::Charging => Some(Duration::from_secs(30)),
+            Profile::Exhibition => Some(Duration::from_secs(20)),
+        }
+    }
+
+    /// The profile after this one, wrapping around to the race profile
+    pub fn next(self) -> Self {
+        match self {
+            Profile::Race => Profile::TestBench,
+            Profile::TestBench => Profile::Charging,
+            Profile::Charging => Profile::Diagnostics,
+            Profile::Diagnostics => Profile::Exhibition,
+            Profile::Exhibition => Profile::Race,
+        }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = &'static str;
+
+    fn from_str(profile: &str) -> Result<Self, Self::Err> {
+        match profile {
+            "race" => Ok(Profile::Race),
+            "test-bench" => Ok(Profile::TestBench),
+            "charging" => Ok(Profile::Charging),
+            "diagnostics" => Ok(Profile::Diagnostics),
+            "exhibition" => Ok(Profile::Exhibition),
+            _ => Err("expected race, test-bench, charging, diagnostics or exhibition"),
+        }
+    }
+}
+
+/// The page shown by a display, switched by the button, the chase car and the rotation of the
+/// profile
+#[derive(Debug)]
+pub struct PageSelection {
+    profile: Profile,
+    page: Page,
+    /// When the page was switched, the rotation waits a full interval from then
+    since: Instant,
+    /// Pages showing the statistics under their readouts, one bit per page
+    statistics: u16,
+}
+
+impl PageSelection {
+    pub fn new(profile: Profile) -> Self {
+        Self {
+            profile,
+            page: profile.pages()[0],
+            since: time::now(),
+            statistics: 0,
+        }
+    }
+
+    pub fn page(&self) -> Page {
+        self.page
+    }
+
+    pub fn profile(&self) -> Profile {
+        self.profile
+    }
+
+    /// Switch to `profile` and its first page, the pages keep their statistics overlay
+    pub fn set_profile(&mut self, profile: Profile) {
+        *self = Self {
+            statistics: self.statistics,
+            ..Self::new(profile)
+        };
+    }
+
+    /// Show or hide the minimum, maximum and average under the readouts of the shown page
+    pub fn toggle_statistics(&mut self) {
+        self.statistics ^= 1 << self.page as u16;
+    }
+
+    /// Whether the shown page has the statistics overlay, see [`DisplayData::statistics_overlay`]
+    ///
+    /// [`DisplayData::statistics_overlay`]: crate::DisplayData::statistics_overlay
+    pub fn shows_statistics(&self) -> bool {
+        self.statistics & (1 << self.page as u16) != 0
+    }
+
+    /// Show `page`, also when the profile doesn't have it, like the chase car asking for it
+    pub fn show(&mut self, page: Page) {
+        self.page = page;
+        self.since = time::now();
+    }
+
+    /// Go to the next page of the profile, like by the button
+    pub fn next(&mut self) {
+        self.show(self.next_page());
+    }
+
+    /// Go back to the page before the shown one, like by a keypad or rotary encoder. The first
+    /// page of the profile when the shown one isn't one of its pages
+    pub fn previous(&mut self) {
+        let pages = self.profile.pages();
+        let page = pages
+            .iter()
+            .position(|&page| page == self.page)
+            .map(|index| pages[(index + pages.len() - 1) % pages.len()])
+            .unwrap_or(pages[0]);
+        self.show(page);
+    }
+
+    /// Rotate the pages when the profile does, returns `true` when the page changed
+    pub fn rotate(&mut self) -> bool {
+        self.rotate_at(time::now())
+    }
+
+    fn rotate_at(&mut self, now: Instant) -> bool {
+        let Some(interval) = self.profile.rotation_interval() else {
+            return false;
+        };
+        if now.duration_since(self.since) < interval {
+            return false;
+        }
+        let page = self.next_page();
+        self.since = now;
+        let changed = page != self.page;
+        self.page = page;
+        changed
+    }
+
+    /// The page after the shown one, the first page of the profile when it isn't one of its pages
+    fn next_page(&self) -> Page {
+        let pages = self.profile.pages();
+        pages
+            .iter()
+            .position(|&page| page == self.page)
+            .map(|index| pages[(index + 1) % pages.len()])
+            .unwrap_or(pages[0])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_of_the_profile() {
+        let mut selection = PageSelection::new(Profile::Race);
+        assert_eq!(selection.page(), Page::Main);
+        selection.next();
+        assert_eq!(selection.page(), Page::Pilot);
+        // The pilot can still look up a fault
+        selection.next();
+        assert_eq!(selection.page(), Page::Diagnostics);
+        selection.next();
+        assert_eq!(selection.page(), Page::Main);
+
+        // The chase car can show any page, the button goes back to the profile
+        selection.show(Page::Render);
+        assert_eq!(selection.page(), Page::Render);
+        selection.next();
+        assert_eq!(selection.page(), Page::Main);
+
+        selection.set_profile(Profile::Diagnostics);
+        assert_eq!(selection.page(), Page::Diagnostics);
+        selection.next();
+        assert_eq!(selection.page(), Page::Commissioning);
+        selection.next();
+        assert_eq!(selection.page(), Page::Alarms);
+        selection.next();
+        assert_eq!(selection.page(), Page::Inventory);
+        selection.next();
+        assert_eq!(selection.page(), Page::Render);
+        selection.next();
+        assert_eq!(selection.page(), Page::Diagnostics);
+        selection.previous();
+        assert_eq!(selection.page(), Page::Render);
+        selection.show(Page::Main);
+        selection.previous();
+        assert_eq!(selection.page(), Page::Diagnostics);
+
+        assert_eq!("test-bench".parse(), Ok(Profile::TestBench));
+        assert_eq!("exhibition".parse(), Ok(Profile::Exhibition));
+        assert!("pit".parse::<Profile>().is_err());
+    }
+
+    #[test]
+    fn statistics_per_page() {
+        let mut selection = PageSelection::new(Profile::TestBench);
+        assert!(!selection.shows_statistics());
+        selection.toggle_statistics();
+        assert!(selection.shows_statistics());
+        selection.next();
+        assert!(!selection.shows_statistics());
+
+        // Switching the profile keeps the overlay of the pages
+        selection.set_profile(Profile::Charging);
+        assert_eq!(selection.page(), Page::Main);
+        assert!(selection.shows_statistics());
+        selection.toggle_statistics();
+        assert!(!selection.shows_statistics());
+    }
+
+    #[test]
+    fn rotation() {
+        let mut selection = PageSelection::new(Profile::Race);
+        let start = selection.since;
+        assert!(!selection.rotate_at(start + Duration::from_secs(600)));
+
+        let mut selection = PageSelection::new(Profile::Charging);
+        let start = selection.since;
+        assert!(!selection.rotate_at(start + Duration::from_secs(29)));
+        assert!(selection.rotate_at(start + Duration::from_secs(30)));
+        assert_eq!(selection.page(), Page::Cells);
+        assert!(!selection.rotate_at(start + Duration::from_secs(59)));
+        assert!(selection.rotate_at(start + Duration::from_secs(60)));
+        assert_eq!(selection.page(), Page::Diagnostics);
+        assert!(selection.rotate_at(start + Duration::from_secs(90)));
+        assert_eq!(selection.page(), Page::Main);
+    }
+}
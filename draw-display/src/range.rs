@@ -0,0 +1,226 @@
+//! Range left at the current consumption. Unless the BMS sends its own, the time to empty is
+//! estimated from how fast the state of charge falls, and combined with the speed to a distance.
+
+use crate::time::{self, Duration, Instant};
+use crate::SocSource;
+
+/// The state of charge falls slowly, over shorter windows its resolution dominates the rate
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+/// Below this speed in km/h the boat isn't going anywhere and there is no range
+const MIN_SPEED_KMH: f32 = 1.0;
+/// The range is compared to the one this long ago for the trend
+const TREND_INTERVAL: Duration = Duration::from_secs(30);
+/// Changes of the range smaller than this fraction are steady
+const TREND_THRESHOLD: f32 = 0.05;
+
+/// Distance in km until the battery is empty, `None` while charging, standing still or when an
+/// input isn't known (`NaN`)
+pub fn estimated_range_km(time_to_empty_min: f32, speed_kmh: f32, net_power_w: f32) -> Option<f32> {
+    if time_to_empty_min.is_nan() || speed_kmh.is_nan() || net_power_w.is_nan() {
+        return None;
+    }
+    // The battery doesn't empty while charging
+    if net_power_w >= 0.0 || speed_kmh < MIN_SPEED_KMH || time_to_empty_min < 0.0 {
+        return None;
+    }
+    Some(time_to_empty_min / 60.0 * speed_kmh)
+}
+
+/// Estimates the time to empty from the rate the state of charge falls over a window
+#[derive(Debug, Default)]
+pub struct TimeToEmptyEstimate {
+    /// Start of the current window, the state of charge then and its source
+    window_start: Option<(Instant, f32, SocSource)>,
+}
+
+impl TimeToEmptyEstimate {
+    /// Called with every state of charge, the source it is from and the fraction of it the pack
+    /// delivers at its temperature, see [`SocCorrection`](crate::SocCorrection). Returns the time
+    /// to empty in minutes at the end of a window in which the state of charge fell. The sources
+    /// differ by a few percent, so the window starts over when the source changes
+    pub fn update(
+        &mut self,
+        source: SocSource,
+        state_of_charge: f32,
+        usable_fraction: f32,
+    ) -> Option<u16> {
+        self.update_at(source, state_of_charge, usable_fraction, time::now())
+    }
+
+    fn update_at(
+        &mut self,
+        source: SocSource,
+        state_of_charge: f32,
+        usable_fraction: f32,
+        now: Instant,
+    ) -> Option<u16> {
+        let (start, start_state_of_charge) = match self.window_start {
+            Some((start, start_state_of_charge, start_source)) if start_source == source => {
+                (start, start_state_of_charge)
+            }
+            _ => {
+                self.window_start = Some((now, state_of_charge, source));
+                return None;
+            }
+        };
+        let elapsed = now.duration_since(start);
+        if elapsed < RATE_WINDOW {
+            return None;
+        }
+        self.window_start = Some((now, state_of_charge, source));
+
+        let minutes = elapsed.as_millis() as f32 / 60_000.0;
+        let rate = (start_state_of_charge - state_of_charge) / minutes;
+        // Charging or too little to measure, the last estimate goes stale
+        if rate.is_nan() || rate <= 0.0 {
+            return None;
+        }
+        // The rate is the one the BMS counts, only less of what is left can be used
+        let usable = state_of_charge.max(0.0) * usable_fraction;
+        Some((usable / rate).min(f32::from(u16::MAX)) as u16)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Trend {
+    Rising,
+    Steady,
+    Falling,
+}
+
+impl core::fmt::Display for Trend {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Trend::Rising => "rising",
+            Trend::Steady => "steady",
+            Trend::Falling => "falling",
+        })
+    }
+}
+
+/// Whether the range grows, like when slowing down to the efficient speed, or shrinks
+#[derive(Debug, Default)]
+pub struct RangeTrend {
+    /// The range the trend compares to and when it was estimated
+    reference: Option<(Instant, f32)>,
+    trend: Option<Trend>,
+}
+
+impl RangeTrend {
+    pub fn trend(&self) -> Option<Trend> {
+        self.trend
+    }
+
+    /// Called with every new estimate, `None` resets the trend
+    pub fn update(&mut self, range_km: Option<f32>) {
+        self.update_at(range_km, time::now());
+    }
+
+    fn update_at(&mut self, range_km: Option<f32>, now: Instant) {
+        let Some(range_km) = range_km else {
+            *self = Self::default();
+            return;
+        };
+        let Some((since, reference)) = self.reference else {
+            self.reference = Some((now, range_km));
+            return;
+        };
+        if now.duration_since(since) < TREND_INTERVAL {
+            return;
+        }
+        let change = (range_km - reference) / reference.max(f32::MIN_POSITIVE);
+        self.trend = Some(if change > TREND_THRESHOLD {
+            Trend::Rising
+        } else if change < -TREND_THRESHOLD {
+            Trend::Falling
+        } else {
+            Trend::Steady
+        });
+        self.reference = Some((now, range_km));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range_edge_cases() {
+        assert_eq!(estimated_range_km(45.0, 10.0, -320.0), Some(7.5));
+        // Standing still
+        assert_eq!(estimated_range_km(45.0, 0.0, -20.0), None);
+        // Charging
+        assert_eq!(estimated_range_km(45.0, 10.0, 150.0), None);
+        assert_eq!(estimated_range_km(f32::NAN, 10.0, -320.0), None);
+        assert_eq!(estimated_range_km(45.0, f32::NAN, -320.0), None);
+        assert_eq!(estimated_range_km(45.0, 10.0, f32::NAN), None);
+    }
+
+    #[test]
+    fn time_to_empty_from_falling_state_of_charge() {
+        let mut estimate = TimeToEmptyEstimate::default();
+        let start = Instant::now();
+        assert_eq!(estimate.update_at(SocSource::Bms, 50.0, 1.0, start), None);
+        assert_eq!(
+            estimate.update_at(SocSource::Bms, 49.8, 1.0, start + Duration::from_secs(30)),
+            None
+        );
+        // 1 % per 2 min
+        assert_eq!(
+            estimate.update_at(SocSource::Bms, 49.5, 1.0, start + Duration::from_secs(60)),
+            Some(99)
+        );
+        // Charging
+        assert_eq!(
+            estimate.update_at(SocSource::Bms, 50.0, 1.0, start + Duration::from_secs(120)),
+            None
+        );
+        // 1 % per minute of a cold pack delivering 80 % of what is left
+        assert_eq!(
+            estimate.update_at(SocSource::Bms, 49.0, 0.8, start + Duration::from_secs(180)),
+            Some(39)
+        );
+    }
+
+    #[test]
+    fn time_to_empty_starts_over_with_another_source() {
+        let mut estimate = TimeToEmptyEstimate::default();
+        let start = Instant::now();
+        assert_eq!(estimate.update_at(SocSource::Bms, 50.0, 1.0, start), None);
+        // Falling by 5 % in a minute only because the counted charge is lower
+        assert_eq!(
+            estimate.update_at(
+                SocSource::AhCount,
+                45.0,
+                1.0,
+                start + Duration::from_secs(60)
+            ),
+            None
+        );
+        assert_eq!(
+            estimate.update_at(
+                SocSource::AhCount,
+                44.0,
+                1.0,
+                start + Duration::from_secs(120)
+            ),
+            Some(44)
+        );
+    }
+
+    #[test]
+    fn trend_of_the_range() {
+        let mut trend = RangeTrend::default();
+        let start = Instant::now();
+        trend.update_at(Some(10.0), start);
+        trend.update_at(Some(12.0), start + Duration::from_secs(10));
+        assert_eq!(trend.trend(), None);
+        trend.update_at(Some(9.0), start + Duration::from_secs(30));
+        assert_eq!(trend.trend(), Some(Trend::Falling));
+        trend.update_at(Some(9.2), start + Duration::from_secs(60));
+        assert_eq!(trend.trend(), Some(Trend::Steady));
+        trend.update_at(None, start + Duration::from_secs(70));
+        assert_eq!(trend.trend(), None);
+    }
+}
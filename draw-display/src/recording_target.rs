@@ -0,0 +1,296 @@
+//! A draw target for the tests which records what is drawn rather than the pixels: the text of
+//! the mono fonts with its bounding box and whether it is inverted, and the bounding box of
+//! everything else. So a test can check that a value shows up in its region of the page, formatted
+//! as expected, without comparing pixels. Text is recognized by its glyphs, which needs a
+//! background color like the pages always set.
+
+use core::convert::Infallible;
+
+use embedded_graphics::{
+    mono_font::{
+        ascii::{FONT_10X20, FONT_4X6, FONT_6X10},
+        MonoFont, MonoTextStyleBuilder,
+    },
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::Rectangle,
+    text::{Baseline, Text},
+};
+
+use crate::assets::{FONT_DIGITS_30X60, FONT_DIGITS_40X64};
+
+/// The fonts the pages are drawn with
+const FONTS: [&MonoFont<'static>; 5] = [
+    &FONT_10X20,
+    &FONT_6X10,
+    &FONT_4X6,
+    &FONT_DIGITS_30X60,
+    &FONT_DIGITS_40X64,
+];
+
+/// Text drawn in one go, the characters of a font next to each other on the same line
+#[derive(Debug, Clone, PartialEq)]
+pub struct DrawnText {
+    pub text: String,
+    pub bounding_box: Rectangle,
+    /// Light text on a dark background
+    pub inverted: bool,
+}
+
+/// The glyphs of a font as the pixels that are ink
+struct GlyphTable {
+    character_size: Size,
+    character_spacing: u32,
+    glyphs: Vec<(char, Vec<bool>)>,
+}
+
+impl GlyphTable {
+    fn new(font: &MonoFont<'static>) -> Self {
+        let style = MonoTextStyleBuilder::new()
+            .font(font)
+            .text_color(BinaryColor::On)
+            .background_color(BinaryColor::Off)
+            .build();
+        // Unknown characters are drawn as the replacement glyph, the first one of it is kept
+        let glyphs = (' '..='~')
+            .map(|c| {
+                let mut cell = GlyphCell(Vec::new());
+                let mut text = [0; 4];
+                let _ = Text::with_baseline(
+                    c.encode_utf8(&mut text),
+                    Point::zero(),
+                    style,
+                    Baseline::Top,
+                )
+                .draw(&mut cell);
+                (c, cell.0)
+            })
+            .collect();
+        Self {
+            character_size: font.character_size,
+            character_spacing: font.character_spacing,
+            glyphs,
+        }
+    }
+
+    fn recognize(&self, ink: &[bool]) -> Option<char> {
+        self.glyphs
+            .iter()
+            .find(|(_, glyph)| glyph.as_slice() == ink)
+            .map(|(c, _)| *c)
+    }
+}
+
+/// Collects the pixels of a single glyph, drawn at the origin
+struct GlyphCell(Vec<bool>);
+
+impl OriginDimensions for GlyphCell {
+    fn size(&self) -> Size {
+        Size::new(u32::MAX >> 1, u32::MAX >> 1)
+    }
+}
+
+impl DrawTarget for GlyphCell {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, _pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        Ok(())
+    }
+
+    fn fill_contiguous<I>(&mut self, _area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        self.0 = colors.into_iter().map(BinaryColor::is_on).collect();
+        Ok(())
+    }
+}
+
+pub struct RecordingTarget {
+    size: Size,
+    fonts: Vec<GlyphTable>,
+    texts: Vec<DrawnText>,
+    shapes: Vec<Rectangle>,
+}
+
+impl RecordingTarget {
+    /// Of the size of the display
+    pub fn new() -> Self {
+        Self {
+            size: Size::new(800, 480),
+            fonts: FONTS.into_iter().map(GlyphTable::new).collect(),
+            texts: Vec::new(),
+            shapes: Vec::new(),
+        }
+    }
+
+    /// Every text drawn since the last clear, in the order it was drawn
+    pub fn texts(&self) -> &[DrawnText] {
+        &self.texts
+    }
+
+    /// The first text containing `text`
+    pub fn find(&self, text: &str) -> Option<&DrawnText> {
+        self.texts.iter().find(|drawn| drawn.text.contains(text))
+    }
+
+    /// The texts entirely within `region`
+    pub fn texts_in(&self, region: Rectangle) -> impl Iterator<Item = &DrawnText> {
+        self.texts.iter().filter(move |drawn| {
+            region.contains(drawn.bounding_box.top_left)
+                && drawn
+                    .bounding_box
+                    .bottom_right()
+                    .is_some_and(|corner| region.contains(corner))
+        })
+    }
+
+    /// Bounding boxes of the lines, shapes and images drawn since the last clear
+    pub fn shapes(&self) -> &[Rectangle] {
+        &self.shapes
+    }
+
+    fn push_glyph(&mut self, c: char, area: Rectangle, inverted: bool, spacing: u32) {
+        // Continues the last text when right next to it
+        if let Some(last) = self.texts.last_mut() {
+            let next_x =
+                last.bounding_box.top_left.x + last.bounding_box.size.width as i32 + spacing as i32;
+            if last.inverted == inverted
+                && last.bounding_box.top_left.y == area.top_left.y
+                && last.bounding_box.size.height == area.size.height
+                && next_x == area.top_left.x
+            {
+                last.text.push(c);
+                last.bounding_box.size.width += spacing + area.size.width;
+                return;
+            }
+        }
+        self.texts.push(DrawnText {
+            text: c.to_string(),
+            bounding_box: area,
+            inverted,
+        });
+    }
+}
+
+impl OriginDimensions for RecordingTarget {
+    fn size(&self) -> Size {
+        self.size
+    }
+}
+
+impl DrawTarget for RecordingTarget {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let points: Vec<Point> = pixels.into_iter().map(|Pixel(point, _)| point).collect();
+        if !points.is_empty() {
+            self.shapes.push(Rectangle::with_corners(
+                Point::new(
+                    points.iter().map(|p| p.x).min().unwrap_or_default(),
+                    points.iter().map(|p| p.y).min().unwrap_or_default(),
+                ),
+                Point::new(
+                    points.iter().map(|p| p.x).max().unwrap_or_default(),
+                    points.iter().map(|p| p.y).max().unwrap_or_default(),
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        let colors: Vec<BinaryColor> = colors.into_iter().collect();
+        let glyph = self
+            .fonts
+            .iter()
+            .filter(|font| font.character_size == area.size)
+            .find_map(|font| {
+                // Dark text on the light page, or light text on a dark background
+                [(BinaryColor::Off, false), (BinaryColor::On, true)]
+                    .into_iter()
+                    .find_map(|(ink, inverted)| {
+                        let ink: Vec<bool> = colors.iter().map(|color| *color == ink).collect();
+                        font.recognize(&ink).map(|c| (c, inverted))
+                    })
+                    .map(|(c, inverted)| (c, inverted, font.character_spacing))
+            });
+        match glyph {
+            Some((c, inverted, spacing)) => self.push_glyph(c, *area, inverted, spacing),
+            None => self.shapes.push(*area),
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, _color: Self::Color) -> Result<(), Self::Error> {
+        self.shapes.push(*area);
+        Ok(())
+    }
+
+    fn clear(&mut self, _color: Self::Color) -> Result<(), Self::Error> {
+        self.texts.clear();
+        self.shapes.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_graphics::text::Alignment;
+
+    #[test]
+    fn text_and_shapes() {
+        let mut target = RecordingTarget::new();
+        let normal = MonoTextStyleBuilder::new()
+            .font(&FONT_10X20)
+            .text_color(BinaryColor::Off)
+            .background_color(BinaryColor::On)
+            .build();
+        let inverted = MonoTextStyleBuilder::new()
+            .font(&FONT_6X10)
+            .text_color(BinaryColor::On)
+            .background_color(BinaryColor::Off)
+            .build();
+        Text::with_alignment("52.4 V", Point::new(400, 100), normal, Alignment::Center)
+            .draw(&mut target)
+            .unwrap();
+        Text::new("STALE", Point::new(10, 300), inverted)
+            .draw(&mut target)
+            .unwrap();
+        Rectangle::new(Point::new(0, 440), Size::new(800, 40))
+            .into_styled(embedded_graphics::primitives::PrimitiveStyle::with_fill(
+                BinaryColor::Off,
+            ))
+            .draw(&mut target)
+            .unwrap();
+
+        let voltage = target.find("52.4").unwrap();
+        assert_eq!(voltage.text, "52.4 V");
+        assert!(!voltage.inverted);
+        assert_eq!(voltage.bounding_box.size, Size::new(60, 20));
+        assert_eq!(voltage.bounding_box.center().x, 400);
+        let stale = target.find("STALE").unwrap();
+        assert!(stale.inverted);
+        let top = Rectangle::new(Point::zero(), Size::new(800, 200));
+        assert_eq!(target.texts_in(top).count(), 1);
+        assert_eq!(
+            target.shapes(),
+            [Rectangle::new(Point::new(0, 440), Size::new(800, 40))]
+        );
+
+        target.clear(BinaryColor::On).unwrap();
+        assert!(target.texts().is_empty());
+    }
+}
@@ -0,0 +1,179 @@
+//! Decides when a slow display, like the e-paper panel, should be refreshed: right away when a
+//! value the pilot cares about changed significantly, but never quicker than the panel can take.
+
+use eoi_can_decoder::DISPLAY_MESSAGE_LEN;
+use heapless::String;
+
+use crate::time::{self, Duration, Instant};
+use crate::DisplayData;
+
+/// Speed changes smaller than this in km/h don't refresh the display
+const SPEED_THRESHOLD: f32 = 0.5;
+/// State of charge changes smaller than this in % don't refresh the display
+const STATE_OF_CHARGE_THRESHOLD: f32 = 1.0;
+
+/// The values shown at the last refresh, stale values are `None`
+#[derive(Debug, Clone, PartialEq)]
+struct Shown {
+    speed_kmh: Option<f32>,
+    state_of_charge: Option<f32>,
+    alarms: Alarms,
+    /// Every new message of the chase car refreshes the display
+    banner: Option<String<DISPLAY_MESSAGE_LEN>>,
+}
+
+/// Warnings shown on the display, every transition refreshes it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Alarms {
+    throttle_error: bool,
+    battery_error: bool,
+    cell_imbalance: bool,
+    no_gnss_fix: bool,
+    /// Switches the layout of the main page
+    reserve: bool,
+}
+
+impl Shown {
+    fn from(data: &DisplayData) -> Self {
+        Self {
+            speed_kmh: data.speed_kmh.get().copied(),
+            state_of_charge: data.battery_state_of_charge.get().copied(),
+            alarms: Alarms {
+                throttle_error: data
+                    .throttle_errors
+                    .get()
+                    .is_some_and(|errors| errors.has_error()),
+                battery_error: data
+                    .battery_error_flags
+                    .get()
+                    .is_some_and(|flags| *flags != 0),
+                cell_imbalance: data.cell_imbalance.is_active(),
+                no_gnss_fix: data.gnss_fix.get() == Some(&false),
+                reserve: data.reserve.is_active(),
+            },
+            banner: data
+                .banner
+                .text()
+                .and_then(|text| String::try_from(text).ok()),
+        }
+    }
+
+    fn differs_significantly(&self, other: &Shown) -> bool {
+        changed(self.speed_kmh, other.speed_kmh, SPEED_THRESHOLD)
+            || changed(
+                self.state_of_charge,
+                other.state_of_charge,
+                STATE_OF_CHARGE_THRESHOLD,
+            )
+            || self.alarms != other.alarms
+            || self.banner != other.banner
+    }
+}
+
+/// Values appearing or going stale are always a change
+fn changed(shown: Option<f32>, current: Option<f32>, threshold: f32) -> bool {
+    match (shown, current) {
+        (Some(shown), Some(current)) => {
+            current - shown >= threshold || shown - current >= threshold
+        }
+        (None, None) => false,
+        _ => true,
+    }
+}
+
+#[derive(Debug)]
+pub struct RefreshPolicy {
+    min_interval: Duration,
+    max_interval: Duration,
+    shown: Option<Shown>,
+    last_refresh: Option<Instant>,
+}
+
+impl RefreshPolicy {
+    /// Refresh at most every `min_interval` and at least every `max_interval`, so the clock and
+    /// small changes still show up
+    pub fn new(min_interval: Duration, max_interval: Duration) -> Self {
+        Self {
+            min_interval,
+            max_interval,
+            shown: None,
+            last_refresh: None,
+        }
+    }
+
+    /// Whether the display should be refreshed now to show `data`
+    pub fn needs_refresh(&self, data: &DisplayData) -> bool {
+        self.needs_refresh_at(data, time::now())
+    }
+
+    /// Call after the display was refreshed with `data`
+    pub fn refreshed(&mut self, data: &DisplayData) {
+        self.refreshed_at(data, time::now());
+    }
+
+    fn needs_refresh_at(&self, data: &DisplayData, now: Instant) -> bool {
+        let (Some(shown), Some(last_refresh)) = (&self.shown, self.last_refresh) else {
+            return true;
+        };
+        let since_refresh = now.duration_since(last_refresh);
+        if since_refresh < self.min_interval {
+            return false;
+        }
+        since_refresh >= self.max_interval || shown.differs_significantly(&Shown::from(data))
+    }
+
+    fn refreshed_at(&mut self, data: &DisplayData, now: Instant) {
+        self.shown = Some(Shown::from(data));
+        self.last_refresh = Some(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refresh_on_significant_changes() {
+        let mut policy = RefreshPolicy::new(Duration::from_secs(5), Duration::from_secs(30));
+        let mut data = DisplayData::default();
+        let start = Instant::now();
+        let at = |seconds| start + Duration::from_secs(seconds);
+        // The speed is smoothed, give it the time to settle on every new value
+        let clock = time::VirtualClock::starting_at(start);
+        clock.install();
+        let settled = |speed: &mut crate::DisplayValue<f32>, value| {
+            clock.advance(Duration::from_secs(2));
+            speed.update(value);
+        };
+
+        assert!(policy.needs_refresh_at(&data, at(0)));
+        data.speed_kmh.update(20.0);
+        data.battery_state_of_charge.update(80.0);
+        policy.refreshed_at(&data, at(0));
+        assert!(!policy.needs_refresh_at(&data, at(10)));
+
+        settled(&mut data.speed_kmh, 20.4);
+        data.battery_state_of_charge.update(79.5);
+        assert!(!policy.needs_refresh_at(&data, at(10)));
+        settled(&mut data.speed_kmh, 19.4);
+        // Not quicker than the minimal interval
+        assert!(!policy.needs_refresh_at(&data, at(4)));
+        assert!(policy.needs_refresh_at(&data, at(10)));
+        policy.refreshed_at(&data, at(10));
+
+        data.gnss_fix.update(false);
+        assert!(policy.needs_refresh_at(&data, at(15)));
+        policy.refreshed_at(&data, at(15));
+
+        for part in eoi_can_decoder::DisplayMessage::split(1, "PIT NOW") {
+            data.banner.push(part);
+        }
+        assert!(policy.needs_refresh_at(&data, at(20)));
+        policy.refreshed_at(&data, at(15));
+
+        // Nothing changed, but the time is refreshed regularly
+        assert!(!policy.needs_refresh_at(&data, at(44)));
+        assert!(policy.needs_refresh_at(&data, at(45)));
+        time::VirtualClock::uninstall();
+    }
+}
@@ -0,0 +1,84 @@
+//! Rendering the pages off-screen into an image, for tools without a display like an HTTP
+//! endpoint, a report generator or golden tests. Only the `image` crate, none of the window stack
+//! of the simulator.
+
+use core::convert::Infallible;
+
+use embedded_graphics::pixelcolor::{Gray8, GrayColor};
+use embedded_graphics::prelude::*;
+use image::{GrayImage, Luma};
+
+use crate::{draw_page, DisplayData, Page};
+
+/// Size of the pages in pixels, of the LCD of the boat
+pub const DISPLAY_SIZE: Size = Size::new(800, 480);
+
+/// The image as a draw target, the pages are drawn in
+/// [`BinaryColor`](embedded_graphics::pixelcolor::BinaryColor), so only black and white
+struct ImageTarget(GrayImage);
+
+impl OriginDimensions for ImageTarget {
+    fn size(&self) -> Size {
+        Size::new(self.0.width(), self.0.height())
+    }
+}
+
+impl DrawTarget for ImageTarget {
+    type Color = Gray8;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            let (Ok(x), Ok(y)) = (u32::try_from(point.x), u32::try_from(point.y)) else {
+                continue;
+            };
+            if x < self.0.width() && y < self.0.height() {
+                self.0.put_pixel(x, y, Luma([color.luma()]));
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.0.fill(color.luma());
+        Ok(())
+    }
+}
+
+/// The main page as the display shows it, white background and black text
+pub fn render_to_image(data: &DisplayData) -> GrayImage {
+    render_page_to_image(data, Page::Main)
+}
+
+/// Any page as the display shows it
+pub fn render_page_to_image(data: &DisplayData, page: Page) -> GrayImage {
+    let mut target = ImageTarget(GrayImage::new(DISPLAY_SIZE.width, DISPLAY_SIZE.height));
+    // Can't fail, drawing into the image is infallible
+    let _ = draw_page(&mut target, data, page);
+    target.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_the_main_page() {
+        let image = render_to_image(&DisplayData::default());
+        assert_eq!(
+            image.dimensions(),
+            (DISPLAY_SIZE.width, DISPLAY_SIZE.height)
+        );
+        // Black text on white
+        let ink = image
+            .pixels()
+            .filter(|pixel| pixel.0[0] == Gray8::BLACK.luma())
+            .count();
+        assert!(ink > 1000, "{ink}");
+        assert!(ink < image.len() / 2, "{ink}");
+        assert!(image.pixels().all(|pixel| [0, 255].contains(&pixel.0[0])));
+    }
+}
@@ -0,0 +1,257 @@
+//! Where the time of drawing a frame goes, to know what to optimize before optimizing it.
+//!
+//! The drawing is measured by wrapping the display, so the sections are told apart by the way
+//! embedded-graphics draws them: text with a background and images fill their area pixel by pixel,
+//! rectangles (like the bars) are filled with a single color and lines are drawn as pixels.
+
+use core::fmt;
+
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+use crate::time::{Duration, Instant};
+use crate::{draw_page, DisplayData, Page};
+
+/// The framebuffer display draws 10 frames a second
+pub const DEFAULT_FRAME_BUDGET: Duration = Duration::from_millis(100);
+/// Frames are counted this long for the frame rate
+const FPS_WINDOW: Duration = Duration::from_secs(5);
+
+/// Time spent drawing a single frame
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RenderStats {
+    /// Text and images
+    pub text: Duration,
+    /// Rectangles, like the bars and boxes, and the strokes of rectangles
+    pub rectangles: Duration,
+    /// Single pixels, mostly lines
+    pub pixels: Duration,
+    pub clear: Duration,
+    /// The whole page, including the time between drawing calls
+    pub total: Duration,
+    /// Writing the frame to the screen, set by the application
+    pub flush: Duration,
+}
+
+impl RenderStats {
+    pub fn frame_time(&self) -> Duration {
+        self.total + self.flush
+    }
+}
+
+impl fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} us (text {} us, rectangles {} us, pixels {} us, clear {} us, flush {} us)",
+            self.frame_time().as_micros(),
+            self.text.as_micros(),
+            self.rectangles.as_micros(),
+            self.pixels.as_micros(),
+            self.clear.as_micros(),
+            self.flush.as_micros()
+        )
+    }
+}
+
+/// Draw `page` like [`draw_page`] and measure where the time goes
+pub fn draw_page_measured<D, C>(
+    display: &mut D,
+    data: &DisplayData,
+    page: Page,
+) -> Result<RenderStats, D::Error>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor + From<embedded_graphics::pixelcolor::BinaryColor>,
+{
+    // How long the drawing really takes, by the system clock also while replaying a log
+    let start = Instant::now();
+    let mut measured = Measured {
+        display,
+        stats: RenderStats::default(),
+    };
+    draw_page(&mut measured, data, page)?;
+    let mut stats = measured.stats;
+    stats.total = start.elapsed();
+    Ok(stats)
+}
+
+/// Adds the time of every drawing call to its section
+struct Measured<'a, D> {
+    display: &'a mut D,
+    stats: RenderStats,
+}
+
+impl<D: Dimensions> Dimensions for Measured<'_, D> {
+    fn bounding_box(&self) -> Rectangle {
+        self.display.bounding_box()
+    }
+}
+
+impl<D: DrawTarget> DrawTarget for Measured<'_, D> {
+    type Color = D::Color;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let start = Instant::now();
+        let result = self.display.draw_iter(pixels);
+        self.stats.pixels += start.elapsed();
+        result
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        let start = Instant::now();
+        let result = self.display.fill_contiguous(area, colors);
+        self.stats.text += start.elapsed();
+        result
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let start = Instant::now();
+        let result = self.display.fill_solid(area, color);
+        self.stats.rectangles += start.elapsed();
+        result
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        let start = Instant::now();
+        let result = self.display.clear(color);
+        self.stats.clear += start.elapsed();
+        result
+    }
+}
+
+/// Frame rate and frame times, reported every few seconds
+#[derive(Debug)]
+pub struct RenderMonitor {
+    budget: Duration,
+    /// The last frame
+    pub last: Option<RenderStats>,
+    /// Frames a second over the last few seconds, `None` until they passed
+    pub fps: Option<f32>,
+    /// Longest frame time over the last few seconds
+    pub slowest: Duration,
+    /// Frames that took longer than the budget over the last few seconds
+    pub over_budget: u32,
+    window_start: Instant,
+    window_frames: u32,
+    window_slowest: Duration,
+    window_over_budget: u32,
+}
+
+impl Default for RenderMonitor {
+    fn default() -> Self {
+        Self::with_budget(DEFAULT_FRAME_BUDGET)
+    }
+}
+
+impl RenderMonitor {
+    /// Frames taking longer than `budget` are counted
+    pub fn with_budget(budget: Duration) -> Self {
+        Self {
+            budget,
+            last: None,
+            fps: None,
+            slowest: Duration::from_ticks(0),
+            over_budget: 0,
+            window_start: Instant::now(),
+            window_frames: 0,
+            window_slowest: Duration::from_ticks(0),
+            window_over_budget: 0,
+        }
+    }
+
+    /// Add a drawn frame, returns `true` when a new report is ready to be logged
+    pub fn frame(&mut self, stats: RenderStats) -> bool {
+        self.frame_at(stats, Instant::now())
+    }
+
+    fn frame_at(&mut self, stats: RenderStats, now: Instant) -> bool {
+        let frame_time = stats.frame_time();
+        self.last = Some(stats);
+        self.window_frames += 1;
+        self.window_slowest = self.window_slowest.max(frame_time);
+        if frame_time > self.budget {
+            self.window_over_budget += 1;
+        }
+
+        let elapsed = now.duration_since(self.window_start);
+        if elapsed < FPS_WINDOW {
+            return false;
+        }
+        self.fps = Some(self.window_frames as f32 * 1_000_000.0 / elapsed.as_micros() as f32);
+        self.slowest = self.window_slowest;
+        self.over_budget = self.window_over_budget;
+        self.window_start = now;
+        self.window_frames = 0;
+        self.window_slowest = Duration::from_ticks(0);
+        self.window_over_budget = 0;
+        true
+    }
+
+    pub fn budget(&self) -> Duration {
+        self.budget
+    }
+}
+
+impl fmt::Display for RenderMonitor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.fps {
+            Some(fps) => write!(f, "{:.1} fps", fps)?,
+            None => write!(f, "N/A fps")?,
+        }
+        if let Some(last) = &self.last {
+            write!(f, ", last frame {}", last)?;
+        }
+        write!(
+            f,
+            ", slowest {} us, {} over the budget of {} us",
+            self.slowest.as_micros(),
+            self.over_budget,
+            self.budget.as_micros()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_graphics::mock_display::MockDisplay;
+    use embedded_graphics::pixelcolor::BinaryColor;
+
+    #[test]
+    fn counts_frames_over_budget() {
+        let mut monitor = RenderMonitor::with_budget(Duration::from_millis(10));
+        let frame = |millis| RenderStats {
+            total: Duration::from_millis(millis),
+            ..Default::default()
+        };
+        let start = monitor.window_start;
+        assert!(!monitor.frame_at(frame(5), start));
+        assert!(!monitor.frame_at(frame(12), start + Duration::from_millis(100)));
+        assert_eq!(monitor.fps, None);
+
+        // Reported once the window passed
+        assert!(monitor.frame_at(frame(3), start + FPS_WINDOW));
+        assert_eq!(monitor.over_budget, 1);
+        assert_eq!(monitor.slowest, Duration::from_millis(12));
+        assert_eq!(monitor.fps, Some(0.6));
+        assert_eq!(monitor.last, Some(frame(3)));
+    }
+
+    #[test]
+    fn measures_the_sections() {
+        let mut display: MockDisplay<BinaryColor> = MockDisplay::new();
+        display.set_allow_overdraw(true);
+        display.set_allow_out_of_bounds_drawing(true);
+        let stats =
+            draw_page_measured(&mut display, &DisplayData::default(), Page::Diagnostics).unwrap();
+        assert!(stats.total >= stats.text + stats.rectangles + stats.pixels + stats.clear);
+    }
+}
@@ -0,0 +1,280 @@
+//! Reserve mode for a nearly empty battery. Below the reserve state of charge the main page is
+//! replaced by an economy layout with only what helps getting home: the consumption, the range
+//! left and the power that stretches the battery over the rest of the race. It is switched by
+//! the data, so every display switches at the same state of charge.
+
+use core::fmt::Write;
+
+use embedded_graphics::{
+    mono_font::{ascii::FONT_10X20, MonoTextStyle, MonoTextStyleBuilder},
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::{PrimitiveStyle, Rectangle},
+    text::{Alignment, Text},
+};
+use heapless::String;
+
+use crate::assets::FONT_DIGITS_30X60;
+use crate::time::Duration;
+use crate::DisplayData;
+
+/// State of charge in % below which the reserve mode starts
+pub const DEFAULT_RESERVE_STATE_OF_CHARGE: f32 = 15.0;
+/// The state of charge has to rise this much in % above the threshold to leave the reserve mode,
+/// so a recovering voltage doesn't switch the layout back and forth
+const HYSTERESIS: f32 = 3.0;
+/// The recommended power makes the battery last this long
+const DEFAULT_TARGET_DURATION: Duration = Duration::from_secs(30 * 60);
+/// Below this speed in km/h the consumption per distance is meaningless
+const MIN_SPEED_KMH: f32 = 1.0;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ReserveMode {
+    /// State of charge in % below which the reserve mode starts
+    threshold: f32,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    target_duration: Duration,
+    active: bool,
+    /// The new state when it changed and the application didn't take it yet
+    #[cfg_attr(feature = "serde", serde(skip))]
+    changed: Option<bool>,
+}
+
+impl Default for ReserveMode {
+    fn default() -> Self {
+        Self::new(DEFAULT_RESERVE_STATE_OF_CHARGE)
+    }
+}
+
+impl ReserveMode {
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            threshold,
+            target_duration: DEFAULT_TARGET_DURATION,
+            active: false,
+            changed: None,
+        }
+    }
+
+    /// How long the recommended power makes the battery last
+    pub fn with_target_duration(mut self, target_duration: Duration) -> Self {
+        self.target_duration = target_duration;
+        self
+    }
+
+    pub fn threshold(&self) -> f32 {
+        self.threshold
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Called with every state of charge, corrected by the temperature of the pack
+    pub fn update(&mut self, state_of_charge: f32) {
+        let active = if self.active {
+            state_of_charge < self.threshold + HYSTERESIS
+        } else {
+            state_of_charge < self.threshold
+        };
+        if active != self.active {
+            self.active = active;
+            self.changed = Some(active);
+        }
+    }
+
+    /// Whether the reserve mode started or ended since the last call, for the application to log
+    /// or publish it
+    pub fn take_change(&mut self) -> Option<bool> {
+        self.changed.take()
+    }
+}
+
+/// What the economy layout shows, `None` when not known
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Economy {
+    /// Energy taken from the battery per distance
+    pub consumption_wh_per_km: Option<f32>,
+    /// Distance until the battery is empty, see [`DisplayData::estimated_range_km`]
+    pub range_km: Option<f32>,
+    /// Power the battery can give for the target duration of the reserve mode
+    pub recommended_power_w: Option<f32>,
+}
+
+impl Economy {
+    pub fn from_data(data: &DisplayData) -> Self {
+        let discharge_power =
+            Some(data.battery_discharge_power()).filter(|power| power.is_finite());
+        let speed_kmh = data
+            .speed_kmh
+            .get()
+            .copied()
+            .filter(|&speed| speed >= MIN_SPEED_KMH);
+        let hours_to_empty = data
+            .battery_time_to_empty
+            .get()
+            .map(|&minutes| f32::from(minutes) / 60.0);
+
+        let consumption_wh_per_km = discharge_power
+            .zip(speed_kmh)
+            .map(|(power, speed)| power.max(0.0) / speed);
+        let range_km = data.estimated_range_km();
+        // The BMS estimates the time to empty at the current power
+        let target_hours = data.reserve.target_duration.as_secs() as f32 / 3600.0;
+        let recommended_power_w = discharge_power
+            .zip(hours_to_empty)
+            .filter(|&(power, _)| power > 0.0)
+            .map(|(power, hours)| power * hours / target_hours);
+
+        Self {
+            consumption_wh_per_km,
+            range_km,
+            recommended_power_w,
+        }
+    }
+}
+
+/// The economy layout replacing the main page in the reserve mode, white on black so it can't be
+/// mistaken for the main page
+pub fn draw_reserve<D, C>(display: &mut D, data: &DisplayData) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor + From<BinaryColor>,
+{
+    display.clear(BinaryColor::Off.into())?;
+    let mut string_helper: String<64> = String::new();
+
+    let font_inverted: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::On.into())
+        .background_color(BinaryColor::Off.into())
+        .build();
+
+    let font_figure: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_DIGITS_30X60)
+        .text_color(BinaryColor::On.into())
+        .background_color(BinaryColor::Off.into())
+        .build();
+
+    let font_header: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .build();
+
+    Rectangle::new(Point::zero(), Size::new(800, 50))
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On.into()))
+        .draw(display)?;
+    match data.battery_state_of_charge.last() {
+        Some(state_of_charge) => write!(
+            &mut string_helper,
+            "RESERVE - State of Charge {:.1} %",
+            state_of_charge
+        ),
+        None => write!(&mut string_helper, "RESERVE - State of Charge N/A"),
+    }
+    .unwrap();
+    Text::with_alignment(
+        string_helper.as_str(),
+        Point::new(400, 32),
+        font_header,
+        Alignment::Center,
+    )
+    .draw(display)?;
+
+    let economy = Economy::from_data(data);
+    let figures: [(Option<f32>, &str); 3] = [
+        (economy.consumption_wh_per_km, "Wh/km"),
+        (economy.range_km, "km range"),
+        (economy.recommended_power_w, "W recommended"),
+    ];
+    for (column, (value, label)) in figures.into_iter().enumerate() {
+        let center_x = 133 + column as i32 * 267;
+
+        string_helper.clear();
+        match value {
+            Some(value) if value < 100.0 => write!(&mut string_helper, "{:.1}", value),
+            Some(value) => write!(&mut string_helper, "{:.0}", value),
+            None => write!(&mut string_helper, "--"),
+        }
+        .unwrap();
+        Text::with_alignment(
+            string_helper.as_str(),
+            Point::new(center_x, 200),
+            font_figure,
+            Alignment::Center,
+        )
+        .draw(display)?;
+
+        Text::with_alignment(
+            label,
+            Point::new(center_x, 250),
+            font_inverted,
+            Alignment::Center,
+        )
+        .draw(display)?;
+    }
+
+    string_helper.clear();
+    write!(
+        &mut string_helper,
+        "Speed {:.1} km/h   Battery {:.0} W",
+        data.speed_kmh.get().unwrap_or(&f32::NAN),
+        data.battery_discharge_power()
+    )
+    .unwrap();
+    Text::with_alignment(
+        string_helper.as_str(),
+        Point::new(400, 340),
+        font_inverted,
+        Alignment::Center,
+    )
+    .draw(display)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn switches_with_hysteresis() {
+        let mut reserve = ReserveMode::new(15.0);
+        reserve.update(40.0);
+        assert!(!reserve.is_active());
+        assert_eq!(reserve.take_change(), None);
+
+        reserve.update(14.9);
+        assert!(reserve.is_active());
+        assert_eq!(reserve.take_change(), Some(true));
+        assert_eq!(reserve.take_change(), None);
+
+        // Recovering a little under load doesn't leave it
+        reserve.update(17.0);
+        assert!(reserve.is_active());
+        reserve.update(18.0);
+        assert!(!reserve.is_active());
+        assert_eq!(reserve.take_change(), Some(false));
+    }
+
+    #[test]
+    fn economy_figures() {
+        let mut data = DisplayData::default();
+        assert_eq!(Economy::from_data(&data), Economy::default());
+
+        data.speed_kmh.update(10.0);
+        data.battery_voltage.update(50.0);
+        data.battery_current_in.update(2.0);
+        data.battery_current_out_motor.update(-8.0);
+        data.battery_current_out_peripherals.update(-0.4);
+        data.battery_time_to_empty.update(45);
+        let economy = Economy::from_data(&data);
+        // 320 W from the battery at 10 km/h
+        assert_eq!(economy.consumption_wh_per_km, Some(32.0));
+        assert_eq!(economy.range_km, Some(7.5));
+        // 240 Wh left over 30 min
+        assert_eq!(economy.recommended_power_w, Some(480.0));
+    }
+}
@@ -0,0 +1,201 @@
+//! Screensaver of the always-on LCD displays. After a while without moving, like at the dock,
+//! only the clock and the state of charge are drawn, white on black at a place that moves every
+//! minute, so no part of the LCD shows the same for hours. Moving, a message of the chase car or
+//! an alarm wakes the display up again. The e-paper display doesn't need it, a still image costs
+//! it nothing.
+
+use core::fmt::Write;
+
+use embedded_graphics::{
+    mono_font::{MonoTextStyle, MonoTextStyleBuilder},
+    pixelcolor::BinaryColor,
+    prelude::*,
+    text::{Alignment, Text},
+};
+use heapless::String;
+
+use crate::assets::FONT_DIGITS_30X60;
+use crate::time::{self, Duration, Instant};
+use crate::DisplayData;
+
+/// Time without moving after which the screensaver starts
+pub const DEFAULT_SCREENSAVER_AFTER: Duration = Duration::from_secs(10 * 60);
+/// Below this speed in km/h the boat isn't moving
+const MOVING_KMH: f32 = 1.0;
+/// The clock and state of charge move on this often
+const MOVE_INTERVAL: Duration = Duration::from_secs(60);
+/// Centers of the clock and state of charge, spread over the 800x480 display
+const POSITIONS: [Point; 6] = [
+    Point::new(400, 200),
+    Point::new(180, 120),
+    Point::new(620, 300),
+    Point::new(250, 340),
+    Point::new(560, 110),
+    Point::new(400, 360),
+];
+
+#[derive(Debug)]
+pub struct Screensaver {
+    after: Duration,
+    /// Since when nothing kept the display awake
+    idle_since: Instant,
+    /// When the screensaver started, `None` while the pages are shown
+    active_since: Option<Instant>,
+}
+
+impl Default for Screensaver {
+    fn default() -> Self {
+        Self::new(DEFAULT_SCREENSAVER_AFTER)
+    }
+}
+
+impl Screensaver {
+    /// Starts after `after` without moving
+    pub fn new(after: Duration) -> Self {
+        Self {
+            after,
+            idle_since: time::now(),
+            active_since: None,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active_since.is_some()
+    }
+
+    /// Show the pages again and wait a full time before the screensaver starts, like after a key
+    /// was pressed or the chase car asked for a page
+    pub fn wake(&mut self) {
+        self.wake_at(time::now());
+    }
+
+    fn wake_at(&mut self, now: Instant) {
+        self.idle_since = now;
+        self.active_since = None;
+    }
+
+    /// Called with the display data before every frame, returns whether the screensaver is shown
+    pub fn update(&mut self, data: &DisplayData) -> bool {
+        let awake = data
+            .speed_kmh
+            .get()
+            .is_some_and(|&speed| speed >= MOVING_KMH)
+            || data.banner.text().is_some()
+            || !data.active_alarms().is_empty();
+        self.update_at(awake, time::now())
+    }
+
+    fn update_at(&mut self, awake: bool, now: Instant) -> bool {
+        if awake {
+            self.wake_at(now);
+        } else if self.active_since.is_none() && now.duration_since(self.idle_since) >= self.after {
+            self.active_since = Some(now);
+        }
+        self.is_active()
+    }
+
+    /// Center of the clock and the state of charge, `None` while the pages are shown
+    fn position_at(&self, now: Instant) -> Option<Point> {
+        let since = now.duration_since(self.active_since?);
+        let step = since.as_secs() / MOVE_INTERVAL.as_secs();
+        Some(POSITIONS[step as usize % POSITIONS.len()])
+    }
+}
+
+/// The local time of the GNSS and the state of charge, at the place of the moment
+pub fn draw_screensaver<D, C>(
+    display: &mut D,
+    data: &DisplayData,
+    screensaver: &Screensaver,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor + From<BinaryColor>,
+{
+    display.clear(BinaryColor::Off.into())?;
+    let Some(center) = screensaver.position_at(time::now()) else {
+        return Ok(());
+    };
+
+    let font: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_DIGITS_30X60)
+        .text_color(BinaryColor::On.into())
+        .background_color(BinaryColor::Off.into())
+        .build();
+
+    let mut string_helper: String<16> = String::new();
+    match data.time.get() {
+        Some(time) => {
+            let local = time.with_offset(data.time_utc_offset.last().copied().unwrap_or_default());
+            write!(
+                &mut string_helper,
+                "{:02}:{:02}",
+                local.hours, local.minutes
+            )
+            .unwrap();
+        }
+        None => string_helper.push_str("--:--").unwrap(),
+    }
+    Text::with_alignment(string_helper.as_str(), center, font, Alignment::Center).draw(display)?;
+
+    string_helper.clear();
+    match data.battery_state_of_charge.get() {
+        Some(state_of_charge) => write!(&mut string_helper, "{:.0}%", state_of_charge).unwrap(),
+        None => string_helper.push_str("--%").unwrap(),
+    }
+    Text::with_alignment(
+        string_helper.as_str(),
+        center + Point::new(0, 70),
+        font,
+        Alignment::Center,
+    )
+    .draw(display)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starts_after_standing_still() {
+        let mut screensaver = Screensaver::new(Duration::from_secs(600));
+        let start = screensaver.idle_since;
+        assert!(!screensaver.update_at(false, start + Duration::from_secs(599)));
+        assert!(screensaver.update_at(false, start + Duration::from_secs(600)));
+        assert_eq!(
+            screensaver.position_at(start + Duration::from_secs(600)),
+            Some(POSITIONS[0])
+        );
+        assert_eq!(
+            screensaver.position_at(start + Duration::from_secs(660)),
+            Some(POSITIONS[1])
+        );
+
+        // Moving wakes it up, it waits a full time again
+        let moving = start + Duration::from_secs(700);
+        assert!(!screensaver.update_at(true, moving));
+        assert_eq!(screensaver.position_at(moving), None);
+        assert!(!screensaver.update_at(false, moving + Duration::from_secs(599)));
+        assert!(screensaver.update_at(false, moving + Duration::from_secs(600)));
+    }
+
+    #[test]
+    fn woken_up_by_an_alarm() {
+        let clock = crate::VirtualClock::starting_at(time::Instant::now());
+        clock.install();
+        let mut data = DisplayData::default();
+        let mut screensaver = Screensaver::new(Duration::from_secs(60));
+        clock.advance(Duration::from_secs(60));
+        assert!(screensaver.update(&data));
+
+        data.cell_imbalance = crate::CellImbalance::new(0.0);
+        for _ in 0..20 {
+            clock.advance(Duration::from_secs(1));
+            data.update_cell_voltages(0, &[3.3, 3.5]);
+        }
+        assert!(!screensaver.update(&data));
+        crate::VirtualClock::uninstall();
+    }
+}
@@ -0,0 +1,87 @@
+//! Correction of the state of charge for a cold pack. The BMS counts the charge, but a cold pack
+//! hits the cutoff voltage earlier and delivers less of it, so the time to empty, the range and
+//! the energy budget of the race would promise more than there is. A curve of the usable fraction
+//! by pack temperature, from a config file of the application, scales the state of charge these
+//! estimates start from. Without a curve nothing is corrected.
+
+/// Points a curve can have
+pub const MAX_CORRECTION_POINTS: usize = 8;
+
+/// The usable fraction of the state of charge at a pack temperature
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct CorrectionPoint {
+    /// Lowest temperature of the pack in °C
+    pub temperature: f32,
+    /// Fraction of the state of charge the pack delivers, 1 for all of it
+    pub usable: f32,
+}
+
+/// Usable fraction of the state of charge by pack temperature, linear between the points and
+/// the one of the nearest point outside of them. The points can be in any order
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct SocCorrection {
+    pub points: heapless::Vec<CorrectionPoint, MAX_CORRECTION_POINTS>,
+}
+
+impl SocCorrection {
+    /// Fraction of the state of charge the pack delivers at `temperature` in °C, 1 without a
+    /// curve or temperature (`NaN`)
+    pub fn usable_fraction(&self, temperature: f32) -> f32 {
+        if temperature.is_nan() {
+            return 1.0;
+        }
+        let below = self
+            .points
+            .iter()
+            .filter(|point| point.temperature <= temperature)
+            .max_by(|a, b| a.temperature.total_cmp(&b.temperature));
+        let above = self
+            .points
+            .iter()
+            .filter(|point| point.temperature > temperature)
+            .min_by(|a, b| a.temperature.total_cmp(&b.temperature));
+        let usable = match (below, above) {
+            (Some(below), Some(above)) => {
+                let t = (temperature - below.temperature) / (above.temperature - below.temperature);
+                below.usable + (above.usable - below.usable) * t
+            }
+            (Some(point), None) | (None, Some(point)) => point.usable,
+            (None, None) => 1.0,
+        };
+        usable.clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn curve(points: &[(f32, f32)]) -> SocCorrection {
+        SocCorrection {
+            points: points
+                .iter()
+                .map(|&(temperature, usable)| CorrectionPoint {
+                    temperature,
+                    usable,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn usable_fraction_of_a_cold_pack() {
+        assert_eq!(SocCorrection::default().usable_fraction(-20.0), 1.0);
+
+        let correction = curve(&[(20.0, 1.0), (-10.0, 0.7), (0.0, 0.8)]);
+        assert_eq!(correction.usable_fraction(25.0), 1.0);
+        assert_eq!(correction.usable_fraction(10.0), 0.9);
+        assert_eq!(correction.usable_fraction(0.0), 0.8);
+        assert_eq!(correction.usable_fraction(-5.0), 0.75);
+        assert_eq!(correction.usable_fraction(-30.0), 0.7);
+        assert_eq!(correction.usable_fraction(f32::NAN), 1.0);
+    }
+}
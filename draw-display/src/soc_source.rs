@@ -0,0 +1,363 @@
+//! Selection of the state of charge to show among its sources: the BMS, the charge counted from
+//! the pack current, and an estimate from the cell voltages at rest. The first source of the
+//! order with a current value is shown, the others are its fallbacks. Sources that disagree for
+//! a while raise an alarm, as one of them is wrong. The voltage estimate follows a generic curve
+//! of Li-ion cells rather than the one of the pack, so it is only used when configured.
+
+use core::str::FromStr;
+
+use heapless::Vec;
+
+use crate::time::{self, Duration, Instant};
+use crate::DisplayValue;
+
+/// Number of sources of the state of charge
+pub const SOC_SOURCES: usize = 3;
+/// Difference in percentage points between two sources above which they disagree
+pub const DEFAULT_SOC_DISAGREEMENT: f32 = 10.0;
+/// The sources have to differ this long, they are updated at different times
+const DISAGREEING_AFTER: Duration = Duration::from_secs(30);
+/// Longer gaps between two pack currents are lost frames, they count nothing
+const MAX_COUNT_GAP: Duration = Duration::from_secs(5);
+/// The voltage estimate is only updated at rest, so it is stale while the boat is moving
+const VOLTAGE_TIMEOUT: Duration = Duration::from_secs(60);
+/// Below this pack current in A the cell voltages are close to their rest voltage
+pub const REST_CURRENT_A: f32 = 5.0;
+/// State of charge in % by the rest voltage of a cell in V, for the Li-ion cells of the pack
+const CELL_VOLTAGE_CURVE: [(f32, f32); 10] = [
+    (3.0, 0.0),
+    (3.3, 5.0),
+    (3.5, 10.0),
+    (3.6, 20.0),
+    (3.7, 40.0),
+    (3.8, 55.0),
+    (3.9, 70.0),
+    (4.0, 80.0),
+    (4.1, 90.0),
+    (4.2, 100.0),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum SocSource {
+    /// Sent by the BMS
+    Bms,
+    /// Counted from the pack current since the first value of another source, needs the
+    /// capacity of the pack
+    AhCount,
+    /// Estimated from the average cell voltage while hardly any current flows
+    Voltage,
+}
+
+impl SocSource {
+    /// Drawn next to the state of charge
+    pub fn label(self) -> &'static str {
+        match self {
+            SocSource::Bms => "BMS",
+            SocSource::AhCount => "Ah",
+            SocSource::Voltage => "V",
+        }
+    }
+}
+
+impl FromStr for SocSource {
+    type Err = &'static str;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        match source {
+            "bms" => Ok(SocSource::Bms),
+            "ah-count" => Ok(SocSource::AhCount),
+            "voltage" => Ok(SocSource::Voltage),
+            _ => Err("expected bms, ah-count or voltage"),
+        }
+    }
+}
+
+/// State of charge in % of a cell resting at `voltage`
+pub fn state_of_charge_at_rest(voltage: f32) -> f32 {
+    let (mut lower_voltage, mut lower_soc) = CELL_VOLTAGE_CURVE[0];
+    if voltage <= lower_voltage {
+        return lower_soc;
+    }
+    for (upper_voltage, upper_soc) in CELL_VOLTAGE_CURVE.into_iter().skip(1) {
+        if voltage <= upper_voltage {
+            let fraction = (voltage - lower_voltage) / (upper_voltage - lower_voltage);
+            return lower_soc + fraction * (upper_soc - lower_soc);
+        }
+        (lower_voltage, lower_soc) = (upper_voltage, upper_soc);
+    }
+    lower_soc
+}
+
+/// Charge counted from the pack current
+#[derive(Debug)]
+struct ChargeCounter {
+    /// State of charge in % the counting started at
+    start: f32,
+    /// Charge in Ah that went in since the start, negative when more went out
+    counted_ah: f32,
+    last_current: Instant,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SocSelection {
+    /// Preferred source first, then the fallbacks
+    #[cfg_attr(feature = "serde", serde(skip))]
+    order: Vec<SocSource, SOC_SOURCES>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    disagreement: f32,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    capacity_ah: Option<f32>,
+    pub bms: DisplayValue<f32>,
+    pub ah_count: DisplayValue<f32>,
+    pub voltage: DisplayValue<f32>,
+    /// Source of the shown state of charge, `None` before the first value
+    selected: Option<SocSource>,
+    /// Whether two sources differ by more than the disagreement for a while
+    disagreeing: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    differing_since: Option<Instant>,
+    /// The new state of the alarm when it changed and the application didn't take it yet
+    #[cfg_attr(feature = "serde", serde(skip))]
+    changed: Option<bool>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    counter: Option<ChargeCounter>,
+}
+
+impl Default for SocSelection {
+    fn default() -> Self {
+        Self::new(
+            &[SocSource::Bms, SocSource::AhCount],
+            DEFAULT_SOC_DISAGREEMENT,
+        )
+    }
+}
+
+impl SocSelection {
+    /// Sources left out of `order` are ignored, they are neither shown nor compared
+    pub fn new(order: &[SocSource], disagreement: f32) -> Self {
+        let mut unique = Vec::new();
+        for &source in order {
+            if !unique.contains(&source) {
+                // Can't fail, there are only as many sources
+                let _ = unique.push(source);
+            }
+        }
+        Self {
+            order: unique,
+            disagreement,
+            capacity_ah: None,
+            bms: DisplayValue::default(),
+            ah_count: DisplayValue::default(),
+            voltage: DisplayValue::with_timeout(VOLTAGE_TIMEOUT),
+            selected: None,
+            disagreeing: false,
+            differing_since: None,
+            changed: None,
+            counter: None,
+        }
+    }
+
+    /// Count the charge of a pack of `capacity_ah`, without it there is no [`SocSource::AhCount`]
+    pub fn with_capacity_ah(mut self, capacity_ah: f32) -> Self {
+        self.capacity_ah = Some(capacity_ah);
+        self
+    }
+
+    pub fn order(&self) -> &[SocSource] {
+        &self.order
+    }
+
+    /// Source of the shown state of charge
+    pub fn selected(&self) -> Option<SocSource> {
+        self.selected
+    }
+
+    /// Difference in percentage points above which the sources disagree
+    pub fn disagreement(&self) -> f32 {
+        self.disagreement
+    }
+
+    pub fn is_disagreeing(&self) -> bool {
+        self.disagreeing
+    }
+
+    /// Whether the alarm started or ended since the last call, for the application to log or
+    /// publish it
+    pub fn take_change(&mut self) -> Option<bool> {
+        self.changed.take()
+    }
+
+    pub fn reading(&self, source: SocSource) -> &DisplayValue<f32> {
+        match source {
+            SocSource::Bms => &self.bms,
+            SocSource::AhCount => &self.ah_count,
+            SocSource::Voltage => &self.voltage,
+        }
+    }
+
+    fn reading_mut(&mut self, source: SocSource) -> &mut DisplayValue<f32> {
+        match source {
+            SocSource::Bms => &mut self.bms,
+            SocSource::AhCount => &mut self.ah_count,
+            SocSource::Voltage => &mut self.voltage,
+        }
+    }
+
+    /// Whether `source` is in the order, the others aren't worth estimating
+    pub fn uses(&self, source: SocSource) -> bool {
+        self.order.contains(&source)
+    }
+
+    /// Take a new state of charge of `source`, returns it when it is the one to show
+    pub fn update(&mut self, source: SocSource, state_of_charge: f32) -> Option<f32> {
+        self.update_at(source, state_of_charge, time::now())
+    }
+
+    fn update_at(&mut self, source: SocSource, state_of_charge: f32, now: Instant) -> Option<f32> {
+        if !self.uses(source) {
+            return None;
+        }
+        self.reading_mut(source).update(state_of_charge);
+        self.selected = self
+            .order
+            .iter()
+            .copied()
+            .find(|&source| self.reading(source).is_valid());
+
+        let mut values = self
+            .order
+            .iter()
+            .filter_map(|&source| self.reading(source).get().copied());
+        let differing = values.next().is_some_and(|first| {
+            let (low, high) = values.fold((first, first), |(low, high), value| {
+                (low.min(value), high.max(value))
+            });
+            high - low > self.disagreement
+        });
+        self.differing_since = match self.differing_since {
+            Some(since) if differing => Some(since),
+            _ if differing => Some(now),
+            _ => None,
+        };
+        let disagreeing = self
+            .differing_since
+            .is_some_and(|since| now.duration_since(since) >= DISAGREEING_AFTER);
+        if disagreeing != self.disagreeing {
+            self.disagreeing = disagreeing;
+            self.changed = Some(disagreeing);
+        }
+
+        (self.selected == Some(source)).then_some(state_of_charge)
+    }
+
+    /// Count the pack current in A, positive while charging. Returns the counted state of charge
+    /// when it is the one to show
+    pub fn count_current(&mut self, current: f32) -> Option<f32> {
+        self.count_current_at(current, time::now())
+    }
+
+    fn count_current_at(&mut self, current: f32, now: Instant) -> Option<f32> {
+        let capacity_ah = self.capacity_ah.filter(|_| self.uses(SocSource::AhCount))?;
+        let counter = match self.counter.as_mut() {
+            Some(counter) => counter,
+            None => {
+                // Starts at the value of the first other source there is
+                let start = self
+                    .order
+                    .iter()
+                    .filter(|&&source| source != SocSource::AhCount)
+                    .find_map(|&source| self.reading(source).get().copied())?;
+                self.counter.insert(ChargeCounter {
+                    start,
+                    counted_ah: 0.0,
+                    last_current: now,
+                })
+            }
+        };
+        let elapsed = now.duration_since(counter.last_current);
+        counter.last_current = now;
+        if elapsed <= MAX_COUNT_GAP && current.is_finite() {
+            counter.counted_ah += current * elapsed.as_millis() as f32 / 3_600_000.0;
+        }
+        let state_of_charge =
+            (counter.start + counter.counted_ah / capacity_ah * 100.0).clamp(0.0, 100.0);
+        self.update_at(SocSource::AhCount, state_of_charge, now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rest_voltage_curve() {
+        assert_eq!(state_of_charge_at_rest(2.8), 0.0);
+        assert_eq!(state_of_charge_at_rest(3.75), 47.5);
+        assert_eq!(state_of_charge_at_rest(4.3), 100.0);
+    }
+
+    #[test]
+    fn fallback_in_order() {
+        let clock = crate::VirtualClock::starting_at(time::Instant::now());
+        clock.install();
+        let mut selection = SocSelection::new(&[SocSource::Bms, SocSource::Voltage], 10.0);
+        assert_eq!(selection.update(SocSource::Voltage, 62.0), Some(62.0));
+        assert_eq!(selection.selected(), Some(SocSource::Voltage));
+
+        // The BMS is preferred as soon as it sends, the voltage only fills in without it
+        assert_eq!(selection.update(SocSource::Bms, 60.0), Some(60.0));
+        assert_eq!(selection.update(SocSource::Voltage, 61.0), None);
+        assert_eq!(selection.selected(), Some(SocSource::Bms));
+
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(selection.update(SocSource::Voltage, 61.0), Some(61.0));
+        assert_eq!(selection.selected(), Some(SocSource::Voltage));
+        crate::VirtualClock::uninstall();
+    }
+
+    #[test]
+    fn counted_charge_drifting_away() {
+        let mut selection = SocSelection::default().with_capacity_ah(100.0);
+        let start = Instant::now();
+        // Nothing to start counting at yet
+        assert_eq!(selection.count_current_at(-50.0, start), None);
+
+        selection.update_at(SocSource::Bms, 80.0, start);
+        assert_eq!(selection.count_current_at(-50.0, start), None);
+        assert_eq!(selection.ah_count.get(), Some(&80.0));
+        // 50 A for 3.6 s is 0.05 Ah out of 100 Ah
+        selection.count_current_at(-50.0, start + Duration::from_millis(3600));
+        assert!((selection.ah_count.get().unwrap() - 79.95).abs() < 0.001);
+        // A gap of lost frames counts nothing
+        selection.count_current_at(-50.0, start + Duration::from_secs(20));
+        assert!((selection.ah_count.get().unwrap() - 79.95).abs() < 0.001);
+        assert!(!selection.is_disagreeing());
+
+        // The BMS jumps, the sources disagree once it stays for a while
+        selection.update_at(SocSource::Bms, 60.0, start + Duration::from_secs(21));
+        assert!(!selection.is_disagreeing());
+        selection.update_at(SocSource::Bms, 60.0, start + Duration::from_secs(51));
+        assert!(selection.is_disagreeing());
+        assert_eq!(selection.take_change(), Some(true));
+        assert_eq!(selection.take_change(), None);
+    }
+
+    #[test]
+    fn voltage_only_when_configured() {
+        let mut selection = SocSelection::default();
+        let start = Instant::now();
+        assert_eq!(selection.update_at(SocSource::Voltage, 40.0, start), None);
+        assert_eq!(selection.voltage.get(), None);
+        selection.update_at(SocSource::Bms, 80.0, start);
+        selection.update_at(SocSource::Bms, 80.0, start + Duration::from_secs(60));
+        assert!(!selection.is_disagreeing());
+        assert_eq!(selection.selected(), Some(SocSource::Bms));
+    }
+}
@@ -0,0 +1,273 @@
+//! The solar panels of the boat by the MPPT input channel they are wired to, and finding the
+//! panels producing much less than their peers, like when they are shaded or damaged. Panels at a
+//! similar voltage see similar sun, so a panel is compared to the median power of those peers.
+
+use core::str::FromStr;
+
+use heapless::Vec;
+
+use crate::DisplayValue;
+
+/// Most panels a [`SolarPanels`] keeps, two columns of rows on the main page
+pub const MAX_PANELS: usize = 16;
+/// A panel producing this fraction less than the median of its peers is underperforming
+const UNDERPERFORMANCE: f32 = 0.3;
+/// Panels within this fraction of the voltage of a panel are its peers
+const PEER_VOLTAGE_TOLERANCE: f32 = 0.2;
+/// Fewer peers don't make a meaningful median
+const MIN_PEERS: usize = 2;
+/// Below this median power in W the sun is too low to tell panels apart
+const MIN_PEER_POWER: f32 = 10.0;
+
+/// (Power, Voltage, Current) of a panel
+pub type PanelInfo = DisplayValue<(f32, f32, f32)>;
+
+/// The input channel of an MPPT a panel is wired to, written like `2.1`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PanelId {
+    pub mppt: u8,
+    pub channel: u8,
+}
+
+impl PanelId {
+    pub const fn new(mppt: u8, channel: u8) -> Self {
+        Self { mppt, channel }
+    }
+}
+
+impl core::fmt::Display for PanelId {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}.{}", self.mppt, self.channel)
+    }
+}
+
+impl FromStr for PanelId {
+    type Err = &'static str;
+
+    /// Parse `mppt.channel`, like `2.1`
+    fn from_str(panel: &str) -> Result<Self, Self::Err> {
+        const ERROR: &str = "expected the MPPT ID and its channel, like 2.1";
+        let (mppt, channel) = panel.split_once('.').ok_or(ERROR)?;
+        Ok(Self {
+            mppt: mppt.trim().parse().map_err(|_| ERROR)?,
+            channel: channel.trim().parse().map_err(|_| ERROR)?,
+        })
+    }
+}
+
+/// The panels of the boat, in the order they are numbered on the display
+pub const BOAT_PANELS: [PanelId; 11] = [
+    PanelId::new(2, 1),
+    PanelId::new(2, 2),
+    PanelId::new(2, 3),
+    PanelId::new(5, 0),
+    PanelId::new(5, 1),
+    PanelId::new(5, 2),
+    PanelId::new(4, 1),
+    PanelId::new(4, 3),
+    PanelId::new(6, 2),
+    PanelId::new(6, 3),
+    PanelId::new(6, 0),
+];
+
+/// (Power, Voltage, Current) of every panel, either of the configured panels in their order or of
+/// every panel seen on the bus, ordered by ID. A small map, looked up by going through it
+#[derive(Debug)]
+#[cfg_attr(
+    feature = "defmt",
+    cfg_attr(not(feature = "tokio"), derive(defmt::Format))
+)]
+pub struct SolarPanels {
+    panels: Vec<(PanelId, PanelInfo), MAX_PANELS>,
+    /// Whether panels are added when they are first seen
+    observed: bool,
+}
+
+impl SolarPanels {
+    /// Only the given panels in this order, also before they are seen. `None` when more than
+    /// [`MAX_PANELS`] are given
+    pub fn configured(panels: &[PanelId]) -> Option<Self> {
+        if panels.len() > MAX_PANELS {
+            return None;
+        }
+        Some(Self {
+            panels: panels
+                .iter()
+                .map(|&panel| (panel, PanelInfo::with_timeout(crate::MPPT_TIMEOUT)))
+                .collect(),
+            observed: false,
+        })
+    }
+
+    /// Every panel once it's seen on the bus, up to [`MAX_PANELS`]
+    pub fn observed() -> Self {
+        Self {
+            panels: Vec::new(),
+            observed: true,
+        }
+    }
+
+    /// Update the values of a panel, panels that aren't configured are left out
+    pub fn update(&mut self, panel: PanelId, value: (f32, f32, f32)) {
+        if let Some(index) = self.panels.iter().position(|(id, _)| *id == panel) {
+            self.panels[index].1.update(value);
+            return;
+        }
+        if !self.observed {
+            return;
+        }
+        let index = self
+            .panels
+            .binary_search_by_key(&panel, |(id, _)| *id)
+            .unwrap_or_else(|index| index);
+        let mut info = PanelInfo::with_timeout(crate::MPPT_TIMEOUT);
+        info.update(value);
+        // Panels beyond the capacity aren't shown
+        let _ = self.panels.insert(index, (panel, info));
+    }
+
+    pub fn get(&self, panel: PanelId) -> Option<&PanelInfo> {
+        self.panels
+            .iter()
+            .find(|(id, _)| *id == panel)
+            .map(|(_, info)| info)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (PanelId, &PanelInfo)> {
+        self.panels.iter().map(|(id, info)| (*id, info))
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &PanelInfo> {
+        self.panels.iter().map(|(_, info)| info)
+    }
+
+    pub fn len(&self) -> usize {
+        self.panels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.panels.is_empty()
+    }
+}
+
+impl Default for SolarPanels {
+    fn default() -> Self {
+        // Can't fail, the panels of the boat fit
+        Self::configured(&BOAT_PANELS).unwrap()
+    }
+}
+
+/// `[[power, voltage, current], ...]` in the order of the panels, like the array of the boat
+/// panels it replaced, so consumers of the JSON don't change
+#[cfg(feature = "serde")]
+impl serde::Serialize for SolarPanels {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.values())
+    }
+}
+
+/// Which of the panels produce too little, in their order, stale panels never do
+pub fn underperforming_panels(panels: &SolarPanels) -> Vec<bool, MAX_PANELS> {
+    panels
+        .values()
+        .enumerate()
+        .map(|(index, info)| {
+            let Some(&(power, voltage, _)) = info.get() else {
+                return false;
+            };
+            let mut peers: Vec<f32, MAX_PANELS> = panels
+                .values()
+                .enumerate()
+                .filter(|&(peer, _)| peer != index)
+                .filter_map(|(_, info)| info.get())
+                .filter(|(_, peer_voltage, _)| {
+                    (peer_voltage - voltage).abs() <= voltage.abs() * PEER_VOLTAGE_TOLERANCE
+                })
+                .map(|&(peer_power, _, _)| peer_power)
+                .collect();
+            if peers.len() < MIN_PEERS {
+                return false;
+            }
+            let median = median(&mut peers);
+            median >= MIN_PEER_POWER && power < median * (1.0 - UNDERPERFORMANCE)
+        })
+        .collect()
+}
+
+fn median(values: &mut [f32]) -> f32 {
+    values.sort_unstable_by(f32::total_cmp);
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[middle - 1] + values[middle]) / 2.0
+    } else {
+        values[middle]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_values(values: &[Option<(f32, f32)>]) -> SolarPanels {
+        let ids: Vec<PanelId, MAX_PANELS> = (0..values.len() as u8)
+            .map(|index| PanelId::new(index / 4, index % 4))
+            .collect();
+        let mut panels = SolarPanels::configured(&ids).unwrap();
+        for (&panel, value) in ids.iter().zip(values) {
+            if let Some((power, voltage)) = *value {
+                panels.update(panel, (power, voltage, power / voltage));
+            }
+        }
+        panels
+    }
+
+    #[test]
+    fn shaded_panel() {
+        let panels = with_values(&[
+            Some((100.0, 40.0)),
+            Some((95.0, 41.0)),
+            Some((60.0, 39.0)),
+            Some((105.0, 40.0)),
+            // Other string at another voltage, not a peer
+            Some((20.0, 20.0)),
+            None,
+        ]);
+        assert_eq!(
+            underperforming_panels(&panels),
+            [false, false, true, false, false, false]
+        );
+    }
+
+    #[test]
+    fn not_without_peers_or_sun() {
+        let panels = with_values(&[Some((100.0, 40.0)), Some((20.0, 40.0))]);
+        assert_eq!(underperforming_panels(&panels), [false, false]);
+
+        let panels = with_values(&[Some((8.0, 40.0)), Some((8.0, 40.0)), Some((1.0, 40.0))]);
+        assert_eq!(underperforming_panels(&panels), [false, false, false]);
+    }
+
+    #[test]
+    fn configured_and_observed_panels() {
+        let mut panels =
+            SolarPanels::configured(&[PanelId::new(5, 0), PanelId::new(2, 1)]).unwrap();
+        panels.update(PanelId::new(2, 1), (100.0, 40.0, 2.5));
+        panels.update(PanelId::new(3, 0), (100.0, 40.0, 2.5));
+        let ids: Vec<PanelId, MAX_PANELS> = panels.iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, [PanelId::new(5, 0), PanelId::new(2, 1)]);
+        assert!(panels.get(PanelId::new(5, 0)).unwrap().get().is_none());
+
+        let mut panels = SolarPanels::observed();
+        for panel in ["6.2", "2.3", "2.1"] {
+            panels.update(panel.parse().unwrap(), (100.0, 40.0, 2.5));
+        }
+        let ids: Vec<PanelId, MAX_PANELS> = panels.iter().map(|(id, _)| id).collect();
+        assert_eq!(
+            ids,
+            [PanelId::new(2, 1), PanelId::new(2, 3), PanelId::new(6, 2)]
+        );
+        assert!("2".parse::<PanelId>().is_err());
+        assert!(SolarPanels::configured(&[PanelId::new(0, 0); MAX_PANELS + 1]).is_none());
+    }
+}
@@ -0,0 +1,160 @@
+//! Patterns of the status LEDs, so the pit crew sees whether the display receives data and what is
+//! wrong without reading the screen: the green LED beats like a heart while CAN frames come in,
+//! the red one blinks the code of the most important alarm. The patterns only depend on the
+//! uptime, the firmware drives its LEDs with them and the simulator draws the same virtual LEDs.
+
+use crate::time::Duration;
+use crate::DisplayData;
+
+/// The green LED beats twice in every period while frames come in
+const HEARTBEAT_PERIOD: Duration = Duration::from_millis(1000);
+/// Length of a beat, and of the pause between the two beats
+const BEAT: Duration = Duration::from_millis(100);
+/// A blink of the code and the pause after it
+const BLINK_PERIOD: Duration = Duration::from_millis(500);
+const BLINK: Duration = Duration::from_millis(200);
+/// A blink code is repeated this often, the pause tells where it starts
+const CODE_PERIOD: Duration = Duration::from_millis(3000);
+
+/// What the red LED signals, by its number of blinks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LedAlarm {
+    /// No CAN frames came in lately
+    BusSilent,
+    /// The BMS reports errors
+    BatteryFault,
+    /// The display couldn't keep up with drawing
+    DisplayError,
+}
+
+impl LedAlarm {
+    pub fn blinks(self) -> u64 {
+        match self {
+            LedAlarm::BusSilent => 1,
+            LedAlarm::BatteryFault => 2,
+            LedAlarm::DisplayError => 3,
+        }
+    }
+}
+
+/// State of the display shown by the LEDs
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LedStatus {
+    pub can_flowing: bool,
+    pub battery_fault: bool,
+    pub display_error: bool,
+}
+
+/// Which LEDs are lit
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Leds {
+    pub green: bool,
+    pub red: bool,
+}
+
+impl LedStatus {
+    /// `can_flowing` is up to the application, which knows when it received its last frame
+    pub fn from_data(data: &DisplayData, can_flowing: bool) -> Self {
+        Self {
+            can_flowing,
+            battery_fault: data
+                .battery_error_flags
+                .get()
+                .is_some_and(|flags| *flags != 0),
+            display_error: data.render.over_budget > 0,
+        }
+    }
+
+    /// The alarm blinked, only the first one of a silent bus, a battery fault and a display error
+    pub fn alarm(&self) -> Option<LedAlarm> {
+        if !self.can_flowing {
+            Some(LedAlarm::BusSilent)
+        } else if self.battery_fault {
+            Some(LedAlarm::BatteryFault)
+        } else if self.display_error {
+            Some(LedAlarm::DisplayError)
+        } else {
+            None
+        }
+    }
+
+    pub fn leds_at(&self, uptime: Duration) -> Leds {
+        let us = uptime.as_micros();
+        let beat = us % HEARTBEAT_PERIOD.as_micros();
+        let green = self.can_flowing
+            && (beat < BEAT.as_micros()
+                || (2 * BEAT.as_micros()..3 * BEAT.as_micros()).contains(&beat));
+
+        let code = us % CODE_PERIOD.as_micros();
+        let red = self.alarm().is_some_and(|alarm| {
+            code < alarm.blinks() * BLINK_PERIOD.as_micros()
+                && code % BLINK_PERIOD.as_micros() < BLINK.as_micros()
+        });
+        Leds { green, red }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(status: LedStatus, period: Duration) -> Vec<Leds> {
+        (0..period.as_millis() / 100)
+            .map(|tenth| status.leds_at(Duration::from_millis(tenth * 100 + 50)))
+            .collect()
+    }
+
+    #[test]
+    fn heartbeat_while_frames_come_in() {
+        let status = LedStatus {
+            can_flowing: true,
+            ..LedStatus::default()
+        };
+        let green: Vec<bool> = pattern(status, HEARTBEAT_PERIOD)
+            .iter()
+            .map(|leds| leds.green)
+            .collect();
+        assert_eq!(
+            green,
+            [true, false, true, false, false, false, false, false, false, false]
+        );
+        assert!(pattern(status, CODE_PERIOD).iter().all(|leds| !leds.red));
+    }
+
+    #[test]
+    fn blink_code_of_the_first_alarm() {
+        let blinks = |status: LedStatus| {
+            let red: Vec<bool> = pattern(status, CODE_PERIOD)
+                .iter()
+                .map(|leds| leds.red)
+                .collect();
+            // Rising edges
+            red.windows(2).filter(|pair| !pair[0] && pair[1]).count() + red[0] as usize
+        };
+
+        let silent = LedStatus {
+            can_flowing: false,
+            battery_fault: true,
+            display_error: true,
+        };
+        assert_eq!(silent.alarm(), Some(LedAlarm::BusSilent));
+        assert_eq!(blinks(silent), 1);
+        assert!(pattern(silent, HEARTBEAT_PERIOD)
+            .iter()
+            .all(|leds| !leds.green));
+
+        let battery = LedStatus {
+            can_flowing: true,
+            ..silent
+        };
+        assert_eq!(battery.alarm(), Some(LedAlarm::BatteryFault));
+        assert_eq!(blinks(battery), 2);
+
+        let display = LedStatus {
+            battery_fault: false,
+            ..battery
+        };
+        assert_eq!(display.alarm(), Some(LedAlarm::DisplayError));
+        assert_eq!(blinks(display), 3);
+    }
+}
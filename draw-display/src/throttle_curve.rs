@@ -0,0 +1,345 @@
+//! Response curve of the throttle for tuning it on the water. The lever is neutral at 0 counts and
+//! gives full throttle at the configured forward end and full reverse at the backward end, linear
+//! in between. The page draws that curve over the range of the lever with the measured raw angle
+//! and throttle value as a point on it, and the gain of the angle sensor as a bar, so a wrong
+//! calibration or a sensor about to clip can be seen without a laptop.
+
+use core::fmt::Write;
+
+use embedded_graphics::{
+    mono_font::{
+        ascii::{FONT_10X20, FONT_6X10},
+        MonoTextStyle, MonoTextStyleBuilder,
+    },
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::{Circle, Line, PrimitiveStyle, Rectangle},
+    text::{Alignment, Text},
+};
+use eoi_can_decoder::ThrottleConfig;
+use heapless::String;
+
+use crate::DisplayData;
+
+/// Area of the curve, throttle values from -100 % at the bottom to 100 % at the top
+const PLOT: Rectangle = Rectangle::new(Point::new(80, 100), Size::new(520, 320));
+/// Bar of the gain of the angle sensor, full at 255
+const GAIN_BAR: Rectangle = Rectangle::new(Point::new(680, 100), Size::new(40, 320));
+/// Part of the range of the lever added on both sides of the plot, to show the clamping
+const MARGIN: f32 = 0.1;
+
+/// Throttle value in % the configuration gives for `angle`, clamped at the ends of the lever
+pub fn expected_value(config: &ThrottleConfig, angle: i16) -> f32 {
+    let angle = f32::from(angle);
+    let ratio = |end: i16| {
+        let end = f32::from(end);
+        (end != 0.0 && (angle > 0.0) == (end > 0.0)).then(|| (angle / end).clamp(0.0, 1.0))
+    };
+    if angle == 0.0 {
+        0.0
+    } else if let Some(ratio) = ratio(config.lever_forward) {
+        ratio * 100.0
+    } else if let Some(ratio) = ratio(config.lever_backward) {
+        -ratio * 100.0
+    } else {
+        0.0
+    }
+}
+
+/// Raw angles at the left and right edge of the plot
+fn angle_range(config: &ThrottleConfig) -> (f32, f32) {
+    let ends = [config.lever_forward, config.lever_backward, 0].map(f32::from);
+    let low = ends.iter().copied().fold(f32::INFINITY, f32::min);
+    let high = ends.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let margin = ((high - low) * MARGIN).max(1.0);
+    (low - margin, high + margin)
+}
+
+/// Row of a throttle value in %
+fn value_y(value: f32) -> i32 {
+    (PLOT.center().y as f32 - value.clamp(-100.0, 100.0) / 100.0 * PLOT.size.height as f32 / 2.0)
+        as i32
+}
+
+fn plot_point((low, high): (f32, f32), angle: f32, value: f32) -> Point {
+    let x = PLOT.top_left.x as f32 + (angle - low) / (high - low) * PLOT.size.width as f32;
+    Point::new(x as i32, value_y(value))
+}
+
+pub fn draw_throttle<D, C>(display: &mut D, data: &DisplayData) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = C>,
+    C: PixelColor + From<BinaryColor>,
+{
+    display.clear(BinaryColor::On.into())?;
+    let mut string_helper: String<64> = String::new();
+
+    let font_normal: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .build();
+
+    let font_small: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_6X10)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .build();
+
+    let font_small_inverted: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_6X10)
+        .text_color(BinaryColor::On.into())
+        .background_color(BinaryColor::Off.into())
+        .build();
+
+    let font_normal_header: MonoTextStyle<'_, C> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(BinaryColor::Off.into())
+        .background_color(BinaryColor::On.into())
+        .underline()
+        .build();
+
+    let config = data.throttle_config.get();
+    match config {
+        Some(config) => write!(&mut string_helper, "Throttle: {}", config.control_type),
+        None => write!(&mut string_helper, "Throttle"),
+    }
+    .unwrap();
+    Text::with_alignment(
+        string_helper.as_str(),
+        Point::new(400, 20),
+        font_normal_header,
+        Alignment::Center,
+    )
+    .draw(display)?;
+
+    Line::new(Point::new(0, 70), Point::new(800, 70))
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 1))
+        .draw(display)?;
+
+    PLOT.into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 1))
+        .draw(display)?;
+    for (value, label) in [(100.0, "100 %"), (0.0, "0 %"), (-100.0, "-100 %")] {
+        Text::with_alignment(
+            label,
+            Point::new(PLOT.top_left.x - 8, value_y(value) + 3),
+            font_small,
+            Alignment::Right,
+        )
+        .draw(display)?;
+    }
+
+    let measured_angle = data.throttle_raw_angle.get().copied();
+    let measured_value = data.throttle_value.get().copied();
+
+    match config {
+        Some(config) => {
+            let range = angle_range(config);
+            // Zero lines of the angle and the value
+            let neutral = plot_point(range, 0.0, 0.0);
+            Line::new(
+                Point::new(PLOT.top_left.x, neutral.y),
+                Point::new(PLOT.top_left.x + PLOT.size.width as i32 - 1, neutral.y),
+            )
+            .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 1))
+            .draw(display)?;
+            Line::new(
+                Point::new(neutral.x, PLOT.top_left.y),
+                Point::new(neutral.x, PLOT.top_left.y + PLOT.size.height as i32 - 1),
+            )
+            .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 1))
+            .draw(display)?;
+
+            // The curve is linear between its corners
+            let mut corners = [
+                range.0,
+                f32::from(config.lever_backward),
+                0.0,
+                f32::from(config.lever_forward),
+                range.1,
+            ];
+            corners.sort_unstable_by(f32::total_cmp);
+            for pair in corners.windows(2) {
+                let [start, end] = [pair[0], pair[1]]
+                    .map(|angle| plot_point(range, angle, expected_value(config, angle as i16)));
+                Line::new(start, end)
+                    .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 3))
+                    .draw(display)?;
+            }
+
+            for (label, end) in [
+                ("Backward", config.lever_backward),
+                ("Forward", config.lever_forward),
+            ] {
+                string_helper.clear();
+                write!(&mut string_helper, "{} {}", label, end).unwrap();
+                Text::with_alignment(
+                    string_helper.as_str(),
+                    Point::new(
+                        plot_point(range, f32::from(end), 0.0).x,
+                        PLOT.top_left.y + PLOT.size.height as i32 + 14,
+                    ),
+                    font_small,
+                    Alignment::Center,
+                )
+                .draw(display)?;
+            }
+
+            // The operating point as the throttle measures it, off the curve when the
+            // calibration is wrong
+            if let (Some(angle), Some(value)) = (measured_angle, measured_value) {
+                let (low, high) = range;
+                let point = plot_point(range, f32::from(angle).clamp(low, high), value);
+                Circle::with_center(point, 13)
+                    .into_styled(PrimitiveStyle::with_fill(BinaryColor::Off.into()))
+                    .draw(display)?;
+            }
+        }
+        None => {
+            Text::with_alignment(
+                "No throttle config",
+                PLOT.center(),
+                font_normal,
+                Alignment::Center,
+            )
+            .draw(display)?;
+        }
+    }
+
+    string_helper.clear();
+    match measured_angle {
+        Some(angle) => write!(&mut string_helper, "Angle {}", angle),
+        None => write!(&mut string_helper, "Angle N/A"),
+    }
+    .unwrap();
+    match measured_value {
+        Some(value) => write!(&mut string_helper, "  Value {:.1} %", value),
+        None => write!(&mut string_helper, "  Value N/A"),
+    }
+    .unwrap();
+    if let (Some(config), Some(angle)) = (config, measured_angle) {
+        write!(
+            &mut string_helper,
+            "  Expected {:.1} %",
+            expected_value(config, angle)
+        )
+        .unwrap();
+    }
+    Text::new(
+        string_helper.as_str(),
+        Point::new(PLOT.top_left.x, 465),
+        font_normal,
+    )
+    .draw(display)?;
+
+    // Gain of the angle sensor
+    GAIN_BAR
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 1))
+        .draw(display)?;
+    let gain = data.throttle_gain.get().copied();
+    if let Some(gain) = gain {
+        let height = GAIN_BAR.size.height * u32::from(gain) / u32::from(u8::MAX);
+        Rectangle::new(
+            GAIN_BAR.top_left + Point::new(0, (GAIN_BAR.size.height - height) as i32),
+            Size::new(GAIN_BAR.size.width, height),
+        )
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::Off.into()))
+        .draw(display)?;
+    }
+    string_helper.clear();
+    match gain {
+        Some(gain) => write!(&mut string_helper, "Gain {}", gain),
+        None => write!(&mut string_helper, "Gain N/A"),
+    }
+    .unwrap();
+    let bar_bottom = GAIN_BAR.top_left.y + GAIN_BAR.size.height as i32;
+    Text::with_alignment(
+        string_helper.as_str(),
+        Point::new(GAIN_BAR.center().x, bar_bottom + 14),
+        font_small,
+        Alignment::Center,
+    )
+    .draw(display)?;
+    if let Some(errors) = data.throttle_errors.get() {
+        let warnings = [
+            (errors.gain_clipping, "CLIPPING"),
+            (errors.gain_invalid, "INVALID"),
+        ];
+        let mut y = bar_bottom + 28;
+        for (_, warning) in warnings.iter().filter(|(active, _)| *active) {
+            Text::with_alignment(
+                warning,
+                Point::new(GAIN_BAR.center().x, y),
+                font_small_inverted,
+                Alignment::Center,
+            )
+            .draw(display)?;
+            y += 12;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eoi_can_decoder::{
+        EoiCanData, ThrottleControlType, ThrottleData, ThrottleErrors, ThrottleStatus,
+    };
+
+    fn config(lever_forward: i16, lever_backward: i16) -> ThrottleConfig {
+        ThrottleConfig {
+            control_type: ThrottleControlType::Current,
+            lever_forward,
+            lever_backward,
+        }
+    }
+
+    #[test]
+    fn expected_values() {
+        let config_ = config(1000, -800);
+        assert_eq!(expected_value(&config_, 0), 0.0);
+        assert_eq!(expected_value(&config_, 500), 50.0);
+        assert_eq!(expected_value(&config_, 1500), 100.0);
+        assert_eq!(expected_value(&config_, -400), -50.0);
+        assert_eq!(expected_value(&config_, -1200), -100.0);
+        // A sensor mounted the other way round
+        let reversed = config(-1000, 800);
+        assert_eq!(expected_value(&reversed, -250), 25.0);
+        assert_eq!(expected_value(&reversed, 400), -50.0);
+    }
+
+    #[test]
+    fn operating_point_and_gain() {
+        let mut data = DisplayData::default();
+        data.ingest_eoi_can_data(EoiCanData::Throttle(ThrottleData::Config(config(
+            1000, -1000,
+        ))));
+        data.ingest_eoi_can_data(EoiCanData::Throttle(ThrottleData::Status(ThrottleStatus {
+            value: 40.0,
+            raw_angle: 500,
+            raw_deadmen: 300,
+            gain: 255,
+            error: ThrottleErrors {
+                gain_clipping: true,
+                ..Default::default()
+            },
+        })));
+        let mut target = crate::recording_target::RecordingTarget::new();
+        crate::draw_page(&mut target, &data, crate::Page::Throttle).unwrap();
+
+        assert!(target.find("Throttle: Current").is_some());
+        assert!(target
+            .find("Angle 500  Value 40.0 %  Expected 50.0 %")
+            .is_some());
+        // The point is drawn at 75 % of the plot, above the curve
+        let point = plot_point(angle_range(&config(1000, -1000)), 500.0, 40.0);
+        assert!(target
+            .shapes()
+            .iter()
+            .any(|shape| shape.contains(point) && shape.size.width == 13));
+        assert!(target.shapes().contains(&GAIN_BAR));
+        assert!(target.find("Gain 255").is_some());
+        assert!(target.find("CLIPPING").unwrap().inverted);
+    }
+}
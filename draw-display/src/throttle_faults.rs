@@ -0,0 +1,160 @@
+//! Statistics of the safety faults of the throttle over the session: the deadman missing and the
+//! impedance of the deadman too high. How often they happened, how long they lasted and whether
+//! the motors were driving when they started, the evidence the safety inspections of the class ask
+//! for. Every status of the throttle counts, not only the debounced errors on the display.
+
+use eoi_can_decoder::ThrottleErrors;
+
+use crate::time::{self, Duration, Instant};
+
+/// Battery power of the motors in W above which they count as driving, regenerating too
+pub const MOTOR_ACTIVE_POWER_W: f32 = 50.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ThrottleFault {
+    DeadmanMissing,
+    ImpedanceHigh,
+}
+
+impl core::fmt::Display for ThrottleFault {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ThrottleFault::DeadmanMissing => f.write_str("Deadman missing"),
+            ThrottleFault::ImpedanceHigh => f.write_str("Impedance high"),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FaultStatistics {
+    /// Faults since the start of the session
+    pub count: u32,
+    /// Faults that started while the motors were driving
+    pub under_power: u32,
+    /// Time in the fault, of the ended ones
+    pub total: Duration,
+    /// The longest of the ended faults
+    pub longest: Duration,
+    since: Option<Instant>,
+}
+
+impl FaultStatistics {
+    /// Whether the throttle reported the fault last
+    pub fn is_active(&self) -> bool {
+        self.since.is_some()
+    }
+
+    fn update_at(&mut self, faulted: bool, driving: bool, now: Instant) {
+        match self.since {
+            None if faulted => {
+                self.count += 1;
+                if driving {
+                    self.under_power += 1;
+                }
+                self.since = Some(now);
+            }
+            Some(since) if !faulted => {
+                let duration = now.duration_since(since);
+                self.total += duration;
+                self.longest = self.longest.max(duration);
+                self.since = None;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Count, under power, total and longest time in seconds and whether it is active, like
+/// `{"active": false, "count": 2, "longest": 1.2, "total": 1.5, "under_power": 1}`
+#[cfg(feature = "serde")]
+impl serde::Serialize for FaultStatistics {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("FaultStatistics", 5)?;
+        state.serialize_field("active", &self.is_active())?;
+        state.serialize_field("count", &self.count)?;
+        state.serialize_field("longest", &(self.longest.as_millis() as f32 / 1000.0))?;
+        state.serialize_field("total", &(self.total.as_millis() as f32 / 1000.0))?;
+        state.serialize_field("under_power", &self.under_power)?;
+        state.end()
+    }
+}
+
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ThrottleFaults {
+    pub deadman_missing: FaultStatistics,
+    pub impedance_high: FaultStatistics,
+}
+
+impl ThrottleFaults {
+    /// Called for every status of the throttle, with the summed battery power of the motors. A
+    /// fault ends with the first status without it
+    pub fn update(&mut self, errors: &ThrottleErrors, motor_power_w: f32) {
+        self.update_at(errors, motor_power_w, time::now())
+    }
+
+    fn update_at(&mut self, errors: &ThrottleErrors, motor_power_w: f32, now: Instant) {
+        // NaN without a motor controller isn't driving
+        let driving = motor_power_w.abs() > MOTOR_ACTIVE_POWER_W;
+        self.deadman_missing
+            .update_at(errors.deadman_missing, driving, now);
+        self.impedance_high
+            .update_at(errors.impedance_high, driving, now);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ThrottleFault, &FaultStatistics)> {
+        [
+            (ThrottleFault::DeadmanMissing, &self.deadman_missing),
+            (ThrottleFault::ImpedanceHigh, &self.impedance_high),
+        ]
+        .into_iter()
+    }
+
+    /// Faults of both kinds since the start of the session
+    pub fn count(&self) -> u32 {
+        self.deadman_missing.count + self.impedance_high.count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_and_durations() {
+        let mut faults = ThrottleFaults::default();
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let deadman = ThrottleErrors {
+            deadman_missing: true,
+            ..ThrottleErrors::default()
+        };
+        let both = ThrottleErrors {
+            deadman_missing: true,
+            impedance_high: true,
+            ..ThrottleErrors::default()
+        };
+        let none = ThrottleErrors::default();
+
+        faults.update_at(&none, 800.0, at(0));
+        faults.update_at(&deadman, 800.0, at(100));
+        faults.update_at(&both, 800.0, at(200));
+        faults.update_at(&none, 0.0, at(1300));
+        assert!(!faults.deadman_missing.is_active());
+        // Released while the motors were off
+        faults.update_at(&deadman, f32::NAN, at(2000));
+        assert!(faults.deadman_missing.is_active());
+        faults.update_at(&none, f32::NAN, at(2300));
+
+        assert_eq!(faults.deadman_missing.count, 2);
+        assert_eq!(faults.deadman_missing.under_power, 1);
+        assert_eq!(faults.deadman_missing.total, Duration::from_millis(1500));
+        assert_eq!(faults.deadman_missing.longest, Duration::from_millis(1200));
+        assert_eq!(faults.impedance_high.count, 1);
+        assert_eq!(faults.impedance_high.longest, Duration::from_millis(1100));
+        assert_eq!(faults.count(), 3);
+    }
+}
@@ -1,3 +1,8 @@
+//! Time of the data model. Values going stale, alarms waiting for a condition to persist and the
+//! lap timing all take the current time from [`now`], which asks the clock of the platform, or a
+//! [`VirtualClock`] installed on the thread, so a replayed log gives the same results however
+//! quickly it's played.
+
 pub use embassy_time::Duration;
 
 #[cfg(not(feature = "tokio"))]
@@ -5,7 +10,7 @@ pub use embassy_time::Duration;
 pub type Instant = embassy_time::Instant;
 
 #[cfg(feature = "tokio")]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Instant(tokio::time::Instant);
 
 #[cfg(feature = "tokio")]
@@ -17,4 +22,150 @@ impl Instant {
     pub fn elapsed(&self) -> Duration {
         self.0.elapsed().try_into().unwrap()
     }
+
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        self.0.duration_since(earlier.0).try_into().unwrap()
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl core::ops::Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, duration: Duration) -> Instant {
+        Self(self.0 + duration.into())
+    }
+}
+
+/// Source of the current time
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+/// The time driver of embassy, like the timer of the microcontroller
+#[cfg(not(feature = "tokio"))]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EmbassyClock;
+
+#[cfg(not(feature = "tokio"))]
+impl Clock for EmbassyClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// The clock of tokio, which follows `tokio::time::pause` and `advance` in tests
+#[cfg(feature = "tokio")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TokioClock;
+
+#[cfg(feature = "tokio")]
+impl Clock for TokioClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// The clock of the platform, used unless a [`VirtualClock`] is installed
+#[cfg(not(feature = "tokio"))]
+pub type SystemClock = EmbassyClock;
+/// The clock of the platform, used unless a [`VirtualClock`] is installed
+#[cfg(feature = "tokio")]
+pub type SystemClock = TokioClock;
+
+/// Clock that only moves when told to, like to the timestamps of a replayed log. Clones share
+/// their time
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct VirtualClock {
+    now: std::rc::Rc<core::cell::Cell<Instant>>,
+}
+
+#[cfg(feature = "std")]
+std::thread_local! {
+    static INSTALLED: core::cell::RefCell<Option<VirtualClock>> = const { core::cell::RefCell::new(None) };
+}
+
+#[cfg(feature = "std")]
+impl VirtualClock {
+    /// A clock standing at `start`, the seed of the replay
+    pub fn starting_at(start: Instant) -> Self {
+        Self {
+            now: std::rc::Rc::new(core::cell::Cell::new(start)),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+
+    /// Move the clock to `now`, earlier times are ignored so the time never goes back
+    pub fn set(&self, now: Instant) {
+        if now > self.now.get() {
+            self.now.set(now);
+        }
+    }
+
+    /// Take the time of the data model on this thread from this clock instead of the system clock
+    pub fn install(&self) {
+        INSTALLED.with(|installed| *installed.borrow_mut() = Some(self.clone()));
+    }
+
+    /// Back to the system clock for the data model on this thread
+    pub fn uninstall() {
+        INSTALLED.with(|installed| *installed.borrow_mut() = None);
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for VirtualClock {
+    fn now(&self) -> Instant {
+        self.now.get()
+    }
+}
+
+/// The current time of the data model
+pub fn now() -> Instant {
+    #[cfg(feature = "std")]
+    if let Some(now) = INSTALLED.with(|installed| installed.borrow().as_ref().map(Clock::now)) {
+        return now;
+    }
+    SystemClock::default().now()
+}
+
+/// Time passed since `earlier` by [`now`]
+pub fn since(earlier: Instant) -> Duration {
+    now().duration_since(earlier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DisplayValue;
+
+    #[test]
+    fn replay_by_the_virtual_clock() {
+        let clock = VirtualClock::starting_at(Instant::now());
+        clock.install();
+        let mut value = DisplayValue::with_timeout(Duration::from_secs(5));
+        value.update(1.5_f32);
+
+        // However long the test takes, only the virtual time counts
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        clock.advance(Duration::from_millis(4999));
+        assert_eq!(value.get(), Some(&1.5));
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(value.get(), None);
+        assert_eq!(value.get_stale().unwrap().1, Duration::from_secs(5));
+
+        // The time doesn't go back
+        let earlier = clock.now();
+        clock.advance(Duration::from_secs(1));
+        clock.set(earlier);
+        assert!(clock.now() == earlier + Duration::from_secs(1));
+
+        // The system clock is well behind the replay
+        VirtualClock::uninstall();
+        assert!(now() < earlier);
+    }
 }
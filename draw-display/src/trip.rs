@@ -0,0 +1,65 @@
+//! Distance and energy of the trip, integrated from the received values.
+
+use crate::time::Duration;
+use crate::{DisplayData, MotorData};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TripCounters {
+    /// Distance over ground in km
+    pub distance_km: f32,
+    /// Energy the motors took from the battery in Wh, regenerating lowers it
+    pub motor_energy_wh: f32,
+    /// Energy of the solar panels in Wh
+    pub solar_energy_wh: f32,
+}
+
+impl TripCounters {
+    /// Add what happened in the `elapsed` time since the last update, stale values add nothing
+    pub fn update(&mut self, data: &DisplayData, elapsed: Duration) {
+        let hours = elapsed.as_millis() as f32 / 3_600_000.0;
+
+        if let Some(speed_kmh) = data.speed_kmh.get() {
+            self.distance_km += speed_kmh * hours;
+        }
+
+        let motor_power: f32 = data
+            .motors
+            .values()
+            .map(MotorData::battery_power)
+            .filter(|power| power.is_finite())
+            .sum();
+        self.motor_energy_wh += motor_power * hours;
+
+        let solar_power: f32 = data
+            .mppt_panel_info
+            .values()
+            .filter_map(|info| info.get())
+            .map(|(power, _, _)| power)
+            .sum();
+        self.solar_energy_wh += solar_power * hours;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PanelId;
+
+    #[test]
+    fn integrate_valid_values() {
+        let mut data = DisplayData::default();
+        data.speed_kmh.update(20.0);
+        data.mppt_panel_info
+            .update(PanelId::new(2, 1), (100.0, 40.0, 2.5));
+        data.mppt_panel_info
+            .update(PanelId::new(5, 0), (50.0, 40.0, 1.25));
+
+        let mut trip = TripCounters::default();
+        trip.update(&data, Duration::from_secs(90));
+        assert_eq!(trip.distance_km, 0.5);
+        assert_eq!(trip.solar_energy_wh, 3.75);
+        // No motor controller seen
+        assert_eq!(trip.motor_energy_wh, 0.0);
+    }
+}
@@ -0,0 +1,204 @@
+use clap::Parser;
+use embedded_can::{Frame, Id, StandardId};
+use eoi_can_decoder::black_box::{
+    BLACK_BOX_REQUEST_ID, BLACK_BOX_RESPONSE_ID, BlackBoxRecord, BlackBoxRequest, BlackBoxResponse,
+    MAX_RECORDS_PER_RESPONSE, MAX_RESPONSE_LEN,
+};
+use eoi_can_decoder::isotp::{FlowStatus, Reassembler, Received, Segmenter, flow_control};
+use eoi_can_source::LogFilter;
+use socketcan::tokio::CanSocket;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+#[allow(unused_imports)]
+use tracing::{Level, debug, error, info, trace, warn};
+use tracing_subscriber::filter::LevelFilter;
+
+/// Longer than a full refresh of the e-paper, which keeps the display from answering
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(8);
+/// Attempts per request before giving up
+const ATTEMPTS: u32 = 5;
+
+const CSV_HEADER: &str = "sequence,uptime_s,utc_s,speed_kmh,state_of_charge,pack_voltage,\
+                          pack_current,motor_current,throttle,battery_state,battery_error_flags";
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    /// CAN interface the display is on
+    #[arg(short, long, default_value_t = String::from("can0"))]
+    can_interface: String,
+
+    /// CSV file to write the records to, oldest first. Without it they go to stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Only read the records of the last minutes, without it all of them
+    #[arg(short, long)]
+    minutes: Option<u32>,
+}
+
+#[tokio::main]
+async fn main() {
+    LogFilter::init_with_writer(LevelFilter::INFO, std::io::stderr);
+    eoi_can_source::install_panic_hook("eoi-can-black-box");
+    let args = Args::parse();
+
+    let can_sock: socketcan::tokio::AsyncCanSocket<socketcan::CanSocket> =
+        CanSocket::open(args.can_interface.as_str()).expect("Unable to open CAN socket");
+
+    let wanted = args.minutes.map(|minutes| minutes.saturating_mul(60));
+    // By sequence, the display keeps recording while it is read, so responses overlap
+    let mut records = BTreeMap::new();
+    let mut skip: u16 = 0;
+    loop {
+        let request = BlackBoxRequest {
+            skip,
+            count: MAX_RECORDS_PER_RESPONSE as u8,
+        };
+        let Some(payload) = request_with_retries(&can_sock, request).await else {
+            error!("The display didn't answer, is it built with the black-box feature?");
+            break;
+        };
+        let Some((response, chunk)) = BlackBoxResponse::parse(&payload) else {
+            error!("Invalid response of the display");
+            break;
+        };
+        records.extend(chunk.map(|record| (record.sequence, record)));
+        skip = skip.saturating_add(u16::from(response.count));
+        let total = wanted.map_or(u32::from(response.stored), |wanted| {
+            wanted.min(u32::from(response.stored))
+        });
+        info!("Read {} of {} records", skip, total);
+        if response.count == 0 || u32::from(skip) >= total {
+            break;
+        }
+    }
+
+    let result = match &args.output {
+        Some(path) => File::create(path).and_then(|file| write_csv(BufWriter::new(file), &records)),
+        None => write_csv(io::stdout().lock(), &records),
+    };
+    if let Err(error) = result {
+        error!("Unable to write the records: {}", error);
+        process::exit(1);
+    }
+    info!("Wrote {} records", records.len());
+}
+
+async fn request_with_retries(
+    can_sock: &socketcan::tokio::AsyncCanSocket<socketcan::CanSocket>,
+    request: BlackBoxRequest,
+) -> Option<Vec<u8>> {
+    for attempt in 1..=ATTEMPTS {
+        match tokio::time::timeout(RESPONSE_TIMEOUT, request_records(can_sock, request)).await {
+            Ok(Ok(payload)) => return Some(payload),
+            Ok(Err(error)) => warn!("Request {} of {:?} failed: {}", attempt, request, error),
+            Err(_) => warn!("Request {} of {:?} timed out", attempt, request),
+        }
+    }
+    None
+}
+
+fn standard_id(id: u16) -> Id {
+    Id::Standard(StandardId::new(id).unwrap())
+}
+
+async fn send(
+    can_sock: &socketcan::tokio::AsyncCanSocket<socketcan::CanSocket>,
+    data: &[u8],
+) -> io::Result<()> {
+    let frame = socketcan::CanFrame::new(standard_id(BLACK_BOX_REQUEST_ID), data)
+        .ok_or_else(|| io::Error::other("Unable to create frame"))?;
+    can_sock.write_frame(frame).await
+}
+
+/// Send the request and put the frames of the response together, answering its first frame with
+/// flow control
+async fn request_records(
+    can_sock: &socketcan::tokio::AsyncCanSocket<socketcan::CanSocket>,
+    request: BlackBoxRequest,
+) -> io::Result<Vec<u8>> {
+    let request = request.to_bytes();
+    // Can't fail, a request is a single frame
+    let mut segmenter = Segmenter::new(&request).unwrap();
+    send(can_sock, &segmenter.first()).await?;
+
+    let mut reassembler = Reassembler::<MAX_RESPONSE_LEN>::new();
+    loop {
+        let frame = match can_sock.read_frame().await? {
+            socketcan::CanFrame::Data(frame)
+                if frame.id() == standard_id(BLACK_BOX_RESPONSE_ID) =>
+            {
+                frame
+            }
+            _ => continue,
+        };
+        match reassembler.push(frame.data()) {
+            Ok(Received::FlowControlNeeded) => {
+                // All the frames at once, the socket buffers them
+                send(can_sock, &flow_control(FlowStatus::ContinueToSend, 0, 0)).await?;
+            }
+            Ok(Received::Incomplete) => {}
+            Ok(Received::Complete(payload)) => return Ok(payload.to_vec()),
+            Err(error) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{:?}", error),
+                ));
+            }
+        }
+    }
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+/// A line of the CSV file, unknown values are left empty
+fn csv_line(record: &BlackBoxRecord) -> String {
+    [
+        record.sequence.to_string(),
+        record.uptime_s.to_string(),
+        optional(record.utc_s),
+        optional(record.speed_kmh),
+        optional(record.state_of_charge),
+        optional(record.pack_voltage),
+        optional(record.pack_current),
+        optional(record.motor_current),
+        optional(record.throttle),
+        optional(record.battery_state),
+        optional(record.battery_error_flags),
+    ]
+    .join(",")
+}
+
+fn write_csv(mut writer: impl Write, records: &BTreeMap<u32, BlackBoxRecord>) -> io::Result<()> {
+    writeln!(writer, "{}", CSV_HEADER)?;
+    for record in records.values() {
+        writeln!(writer, "{}", csv_line(record))?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+
+    #[test]
+    fn csv_lines() {
+        let record = BlackBoxRecord {
+            sequence: 12,
+            uptime_s: 300,
+            speed_kmh: Some(21.5),
+            throttle: Some(-10),
+            ..BlackBoxRecord::default()
+        };
+        assert!(csv_line(&record) == "12,300,,21.5,,,,,-10,,");
+        assert!(CSV_HEADER.split(',').count() == csv_line(&record).split(',').count());
+    }
+}
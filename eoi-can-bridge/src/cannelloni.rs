@@ -0,0 +1,122 @@
+//! Packets of the [cannelloni](https://github.com/mguentner/cannelloni) UDP protocol, so the
+//! bridge can also talk to cannelloni itself.
+//!
+//! A packet is a header (version, op code, sequence number and frame count) followed by the
+//! frames, every frame is its CAN ID with flags, the length and the data. CAN FD frames have the
+//! CAN FD flag in the length and a byte of CAN FD flags after it. Multi byte values are big
+//! endian.
+
+use embedded_can::{ExtendedId, Id, StandardId};
+use eoi_can_decoder::can_frame::CanFrame;
+
+const VERSION: u8 = 2;
+const OP_CODE_DATA: u8 = 0;
+const HEADER_LEN: usize = 5;
+
+const EXTENDED_FLAG: u32 = 0x8000_0000;
+const REMOTE_FLAG: u32 = 0x4000_0000;
+const ERROR_FLAG: u32 = 0x2000_0000;
+const CAN_FD_FLAG: u8 = 0x80;
+
+/// Largest packet that fits in a single ethernet frame
+pub const MAX_PACKET_LEN: usize = 1472;
+
+pub fn encode_packet(sequence_number: u8, frames: &[CanFrame]) -> Vec<u8> {
+    let mut packet = vec![VERSION, OP_CODE_DATA, sequence_number];
+    packet.extend_from_slice(&(frames.len() as u16).to_be_bytes());
+
+    for frame in frames {
+        let id = match frame.id {
+            Id::Standard(id) => id.as_raw() as u32,
+            Id::Extended(id) => id.as_raw() | EXTENDED_FLAG,
+        };
+        packet.extend_from_slice(&id.to_be_bytes());
+        packet.push(frame.data.len() as u8);
+        packet.extend_from_slice(&frame.data);
+    }
+    packet
+}
+
+/// Data frames in the packet, remote, error and CAN FD frames are skipped.
+/// Returns `None` when it isn't a (complete) cannelloni data packet
+pub fn decode_packet(packet: &[u8]) -> Option<Vec<CanFrame>> {
+    let header = packet.get(..HEADER_LEN)?;
+    if header[0] != VERSION || header[1] != OP_CODE_DATA {
+        return None;
+    }
+    let count = u16::from_be_bytes([header[3], header[4]]);
+
+    let mut frames = Vec::with_capacity(count as usize);
+    let mut rest = &packet[HEADER_LEN..];
+    for _ in 0..count {
+        let raw_id = u32::from_be_bytes(rest.get(..4)?.try_into().ok()?);
+        let len = *rest.get(4)?;
+        let is_remote = raw_id & REMOTE_FLAG != 0;
+        let is_fd = len & CAN_FD_FLAG != 0;
+        let data_start = if is_fd { 6 } else { 5 };
+        // Remote frames carry no data
+        let data_len = if is_remote {
+            0
+        } else {
+            (len & !CAN_FD_FLAG) as usize
+        };
+        let data = rest.get(data_start..data_start + data_len)?;
+        rest = &rest[data_start + data_len..];
+
+        if is_remote || raw_id & ERROR_FLAG != 0 || is_fd {
+            continue;
+        }
+        let id = if raw_id & EXTENDED_FLAG != 0 {
+            Id::Extended(ExtendedId::new(raw_id & !EXTENDED_FLAG)?)
+        } else {
+            Id::Standard(StandardId::new(raw_id as u16)?)
+        };
+        // A length over 8 without the CAN FD flag
+        let Ok(frame) = CanFrame::try_new(id, data) else {
+            continue;
+        };
+        frames.push(frame);
+    }
+    Some(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+
+    #[test]
+    fn encode_and_decode() {
+        let frames = [
+            CanFrame::from_array(Id::Standard(StandardId::new(0x123).unwrap()), [1, 2, 3]),
+            CanFrame::from_array(Id::Extended(ExtendedId::new(0x909).unwrap()), []),
+        ];
+        let packet = encode_packet(7, &frames);
+        assert!(
+            packet
+                == [
+                    2, 0, 7, 0, 2, // Header
+                    0x00, 0x00, 0x01, 0x23, 3, 1, 2, 3, // Standard frame
+                    0x80, 0x00, 0x09, 0x09, 0, // Extended frame
+                ]
+        );
+        assert!(decode_packet(&packet) == Some(frames.to_vec()));
+    }
+
+    #[test]
+    fn skip_unsupported_frames() {
+        let packet = [
+            2, 0, 0, 0, 3, // Header
+            0x40, 0x00, 0x01, 0x23, 8, // Remote frame
+            0x00, 0x00, 0x01, 0x23, 0x82, 0x01, 1, 2, // CAN FD frame with its flags
+            0x00, 0x00, 0x00, 0x10, 1, 0xAA, // Data frame
+        ];
+        let frames = decode_packet(&packet).unwrap();
+        assert!(frames.len() == 1);
+        assert!(frames[0].data == [0xAA]);
+
+        // Incomplete and acknowledge packets
+        assert!(decode_packet(&packet[..packet.len() - 1]).is_none());
+        assert!(decode_packet(&[2, 1, 0, 0, 0]).is_none());
+    }
+}
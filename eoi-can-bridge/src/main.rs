@@ -0,0 +1,148 @@
+use clap::Parser;
+use embedded_can::Frame;
+use eoi_can_source::{CanTransmitter, LogFilter, RestartPolicy};
+use socketcan::tokio::CanSocket;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::UdpSocket;
+#[allow(unused_imports)]
+use tracing::{Level, debug, error, info, trace, warn};
+use tracing_subscriber::filter::LevelFilter;
+
+mod cannelloni;
+
+/// Failed reads in a row before the bridge gives up
+const MAX_READ_RETRIES: u32 = 10;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    /// CAN interface to bridge
+    #[arg(short, long, default_value_t = String::from("can0"))]
+    can_interface: String,
+
+    /// Address to receive frames on
+    #[arg(short, long, default_value = "0.0.0.0:20000")]
+    listen: SocketAddr,
+
+    /// Address of the other side, like 192.168.1.20:20000. Frames are sent to it and only
+    /// packets from it are written to the bus
+    #[arg(short, long)]
+    peer: SocketAddr,
+
+    /// Never send anything on the bus, the frames of the bus are still forwarded to the peer but
+    /// its frames are dropped
+    #[arg(long)]
+    listen_only: bool,
+}
+
+#[tokio::main]
+async fn main() {
+    LogFilter::init(LevelFilter::INFO);
+    eoi_can_source::install_panic_hook("eoi-can-bridge");
+    let args = Args::parse();
+    info!(
+        "Bridging {} over UDP on {}",
+        args.can_interface, args.listen
+    );
+
+    // One socket for both directions, a socket doesn't receive its own frames, so frames from
+    // the other side aren't sent back
+    let can_sock: Arc<socketcan::tokio::AsyncCanSocket<socketcan::CanSocket>> =
+        Arc::new(CanSocket::open(args.can_interface.as_str()).expect("Unable to open CAN socket"));
+    let udp_sock = Arc::new(
+        UdpSocket::bind(args.listen)
+            .await
+            .expect("Unable to bind UDP socket"),
+    );
+    let peer = args.peer;
+    info!("Bridging with {}", peer);
+
+    let udp_receiver = udp_sock.clone();
+    // Commands from the other side overtake its telemetry when the bus is busy
+    let can_transmitter = if args.listen_only {
+        info!("Listen-only, frames of {} are not sent on the bus", peer);
+        CanTransmitter::listen_only()
+    } else {
+        CanTransmitter::spawn(can_sock.clone()).1
+    };
+    tokio::spawn(async move {
+        let mut buffer = [0; cannelloni::MAX_PACKET_LEN];
+        let restart_policy = RestartPolicy::default();
+        let mut retry = 0;
+        loop {
+            let (len, sender) = match udp_receiver.recv_from(&mut buffer).await {
+                Ok(received) => received,
+                Err(error) => {
+                    let backoff = restart_policy.backoff(retry);
+                    warn!(%error, retry, ?backoff, "Failed to receive UDP packet");
+                    tokio::time::sleep(backoff).await;
+                    retry += 1;
+                    continue;
+                }
+            };
+            retry = 0;
+            // Anyone on the network could write to the bus otherwise
+            if sender != peer {
+                debug!("Dropping packet from {}, not from {}", sender, peer);
+                continue;
+            }
+            let Some(frames) = cannelloni::decode_packet(&buffer[..len]) else {
+                debug!("Invalid packet from {}", sender);
+                continue;
+            };
+
+            for frame in frames {
+                trace!("Received {:?} from {}", frame, sender);
+                let Some(socket_frame) = socketcan::CanFrame::new(frame.id, &frame.data) else {
+                    continue;
+                };
+                can_transmitter.send(socket_frame);
+            }
+        }
+    });
+
+    // The socket is shared with the sender, so it isn't opened again, the bridge stops after the
+    // retries and is restarted by its service
+    let restart_policy = RestartPolicy {
+        max_retries: Some(MAX_READ_RETRIES),
+        ..RestartPolicy::default()
+    };
+    let mut retry = 0;
+    let mut sequence_number = 0_u8;
+    loop {
+        let frame = match can_sock.read_frame().await {
+            Ok(socketcan::CanFrame::Data(frame)) => {
+                match eoi_can_decoder::can_frame::CanFrame::try_new(frame.id(), frame.data()) {
+                    Ok(frame) => frame,
+                    Err(error) => {
+                        warn!("Not forwarding CAN frame {:?}: {}", frame.id(), error);
+                        continue;
+                    }
+                }
+            }
+            Ok(frame) => {
+                debug!("Not forwarding non-data CAN frame: {:?}", frame);
+                continue;
+            }
+            Err(error) => {
+                if restart_policy.gives_up(retry) {
+                    error!(%error, retries = retry, "Giving up reading CAN frames");
+                    std::process::exit(1);
+                }
+                let backoff = restart_policy.backoff(retry);
+                warn!(%error, retry, ?backoff, "Failed to read CAN frame");
+                tokio::time::sleep(backoff).await;
+                retry += 1;
+                continue;
+            }
+        };
+        retry = 0;
+
+        let packet = cannelloni::encode_packet(sequence_number, &[frame]);
+        sequence_number = sequence_number.wrapping_add(1);
+        if let Err(error) = udp_sock.send_to(&packet, peer).await {
+            warn!("Failed to send packet to {}: {:?}", peer, error);
+        }
+    }
+}
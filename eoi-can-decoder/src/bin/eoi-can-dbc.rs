@@ -0,0 +1,9 @@
+//! Writes the CAN matrix known to the decoder as DBC file to stdout
+//!
+//! Usage: `cargo run -p eoi-can-decoder --bin eoi-can-dbc > eoi-can.dbc`
+
+fn main() {
+    let mut dbc = String::new();
+    eoi_can_decoder::dbc::write_dbc(&mut dbc).expect("Failed to write DBC");
+    print!("{dbc}");
+}
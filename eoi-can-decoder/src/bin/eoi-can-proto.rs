@@ -0,0 +1,9 @@
+//! Writes the protobuf schema of the gRPC telemetry service to stdout
+//!
+//! Usage: `cargo run -p eoi-can-decoder --bin eoi-can-proto > eoi-can-decoder/proto/eoi_can.proto`
+
+fn main() {
+    let mut proto = String::new();
+    eoi_can_decoder::proto::write_proto(&mut proto).expect("Failed to write protobuf schema");
+    print!("{proto}");
+}
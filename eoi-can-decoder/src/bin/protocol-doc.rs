@@ -0,0 +1,10 @@
+//! Writes the protocol reference of all messages known to the decoder as Markdown to stdout
+//!
+//! Usage: `cargo run -p eoi-can-decoder --bin protocol-doc > eoi-can-decoder/PROTOCOL.md`
+
+fn main() {
+    let mut doc = String::new();
+    eoi_can_decoder::protocol_doc::write_protocol_doc(&mut doc)
+        .expect("Failed to write the protocol reference");
+    print!("{doc}");
+}
@@ -0,0 +1,247 @@
+//! Records of the black box of the display firmware: the key signals once a second, kept in an
+//! external SPI flash for the last half hour. They are read over CAN with [`isotp`](crate::isotp)
+//! after an incident, when the logs of the Pi are gone. The reader sends a [`BlackBoxRequest`] on
+//! [`BLACK_BOX_REQUEST_ID`], the display answers with the records on [`BLACK_BOX_RESPONSE_ID`].
+
+use serde::Serialize;
+
+/// ISO-TP ID of the reader, also for its flow control frames
+pub const BLACK_BOX_REQUEST_ID: u16 = 0x23C;
+/// ISO-TP ID of the display
+pub const BLACK_BOX_RESPONSE_ID: u16 = 0x23D;
+/// Bytes of a record in the flash and in a response
+pub const RECORD_LEN: usize = 32;
+/// Records in one response at most, so it stays well below the 4095 bytes of ISO-TP
+pub const MAX_RECORDS_PER_RESPONSE: usize = 64;
+/// Bytes in front of the records of a response
+pub const RESPONSE_HEADER_LEN: usize = 4;
+/// Longest payload of a response
+pub const MAX_RESPONSE_LEN: usize = RESPONSE_HEADER_LEN + MAX_RECORDS_PER_RESPONSE * RECORD_LEN;
+/// Sequence number of a record slot of erased flash
+pub const ERASED_SEQUENCE: u32 = u32::MAX;
+
+/// First byte of a request and its response
+const READ_RECORDS: u8 = 0x01;
+
+/// Ask for `count` records, ending `skip` records before the newest one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BlackBoxRequest {
+    pub skip: u16,
+    pub count: u8,
+}
+
+impl BlackBoxRequest {
+    pub fn to_bytes(self) -> [u8; 4] {
+        let skip = self.skip.to_le_bytes();
+        [READ_RECORDS, skip[0], skip[1], self.count]
+    }
+
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        match payload {
+            [READ_RECORDS, skip_low, skip_high, count, ..] => Some(Self {
+                skip: u16::from_le_bytes([*skip_low, *skip_high]),
+                count: (*count).min(MAX_RECORDS_PER_RESPONSE as u8),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Header of a response, followed by `count` records, oldest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BlackBoxResponse {
+    pub count: u8,
+    /// Records in the flash, how far back a request can go
+    pub stored: u16,
+}
+
+impl BlackBoxResponse {
+    pub fn to_bytes(self) -> [u8; RESPONSE_HEADER_LEN] {
+        let stored = self.stored.to_le_bytes();
+        [READ_RECORDS, self.count, stored[0], stored[1]]
+    }
+
+    /// The header and the records of a response payload
+    pub fn parse(payload: &[u8]) -> Option<(Self, impl Iterator<Item = BlackBoxRecord> + '_)> {
+        let &[READ_RECORDS, count, stored_low, stored_high] = payload.get(..RESPONSE_HEADER_LEN)?
+        else {
+            return None;
+        };
+        let records = payload[RESPONSE_HEADER_LEN..]
+            .as_chunks::<RECORD_LEN>()
+            .0
+            .iter()
+            .take(usize::from(count))
+            .filter_map(|record| BlackBoxRecord::from_bytes(record));
+        Some((
+            Self {
+                count,
+                stored: u16::from_le_bytes([stored_low, stored_high]),
+            },
+            records,
+        ))
+    }
+}
+
+/// The key signals of one second, `None` for values the display didn't have
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BlackBoxRecord {
+    /// Counts every record since the flash was erased, the newest has the highest
+    pub sequence: u32,
+    pub uptime_s: u32,
+    /// Unix time, once the clock was synced to the GNSS bridge
+    pub utc_s: Option<u32>,
+    pub speed_kmh: Option<f32>,
+    pub state_of_charge: Option<f32>,
+    pub pack_voltage: Option<f32>,
+    /// A, positive while charging
+    pub pack_current: Option<f32>,
+    /// Summed battery current of the motor controllers in A
+    pub motor_current: Option<f32>,
+    /// Throttle in %, negative in reverse
+    pub throttle: Option<i8>,
+    pub battery_state: Option<u8>,
+    pub battery_error_flags: Option<u32>,
+}
+
+fn scaled_u16(value: Option<f32>, factor: f32) -> u16 {
+    value
+        .filter(|value| value.is_finite())
+        .map_or(u16::MAX, |value| {
+            (value * factor).round().clamp(0.0, f32::from(u16::MAX - 1)) as u16
+        })
+}
+
+fn scaled_i16(value: Option<f32>, factor: f32) -> i16 {
+    value
+        .filter(|value| value.is_finite())
+        .map_or(i16::MIN, |value| {
+            (value * factor)
+                .round()
+                .clamp(f32::from(i16::MIN + 1), f32::from(i16::MAX)) as i16
+        })
+}
+
+fn unscaled_u16(bytes: &[u8], factor: f32) -> Option<f32> {
+    let raw = u16::from_le_bytes(bytes.try_into().ok()?);
+    (raw != u16::MAX).then(|| f32::from(raw) / factor)
+}
+
+fn unscaled_i16(bytes: &[u8], factor: f32) -> Option<f32> {
+    let raw = i16::from_le_bytes(bytes.try_into().ok()?);
+    (raw != i16::MIN).then(|| f32::from(raw) / factor)
+}
+
+impl BlackBoxRecord {
+    /// Bytes 0–3 sequence, 4–7 uptime in s, 8–11 Unix time (0 unknown), 12–13 speed in 0.01 km/h,
+    /// 14–15 state of charge in 0.01 %, 16–17 pack voltage in 0.01 V, 18–19 pack current and
+    /// 20–21 motor current in 0.1 A, 22 throttle in %, 23 battery state, 24–27 battery error
+    /// flags, 28–31 unused. All little endian, unknown values are all ones, or the minimum of a
+    /// signed value
+    pub fn to_bytes(&self) -> [u8; RECORD_LEN] {
+        let mut bytes = [0xFF; RECORD_LEN];
+        bytes[0..4].copy_from_slice(&self.sequence.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.uptime_s.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.utc_s.unwrap_or(0).to_le_bytes());
+        bytes[12..14].copy_from_slice(&scaled_u16(self.speed_kmh, 100.0).to_le_bytes());
+        bytes[14..16].copy_from_slice(&scaled_u16(self.state_of_charge, 100.0).to_le_bytes());
+        bytes[16..18].copy_from_slice(&scaled_u16(self.pack_voltage, 100.0).to_le_bytes());
+        bytes[18..20].copy_from_slice(&scaled_i16(self.pack_current, 10.0).to_le_bytes());
+        bytes[20..22].copy_from_slice(&scaled_i16(self.motor_current, 10.0).to_le_bytes());
+        bytes[22] = self.throttle.unwrap_or(i8::MIN) as u8;
+        bytes[23] = self.battery_state.unwrap_or(u8::MAX);
+        bytes[24..28].copy_from_slice(&self.battery_error_flags.unwrap_or(u32::MAX).to_le_bytes());
+        bytes
+    }
+
+    /// `None` for a slot of erased flash or too few bytes
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes.get(..RECORD_LEN)?;
+        let u32_at = |start: usize| u32::from_le_bytes(bytes[start..start + 4].try_into().unwrap());
+        let sequence = u32_at(0);
+        if sequence == ERASED_SEQUENCE {
+            return None;
+        }
+        Some(Self {
+            sequence,
+            uptime_s: u32_at(4),
+            utc_s: Some(u32_at(8)).filter(|&utc_s| utc_s != 0),
+            speed_kmh: unscaled_u16(&bytes[12..14], 100.0),
+            state_of_charge: unscaled_u16(&bytes[14..16], 100.0),
+            pack_voltage: unscaled_u16(&bytes[16..18], 100.0),
+            pack_current: unscaled_i16(&bytes[18..20], 10.0),
+            motor_current: unscaled_i16(&bytes[20..22], 10.0),
+            throttle: Some(bytes[22] as i8).filter(|&throttle| throttle != i8::MIN),
+            battery_state: Some(bytes[23]).filter(|&state| state != u8::MAX),
+            battery_error_flags: Some(u32_at(24)).filter(|&flags| flags != u32::MAX),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+
+    #[test]
+    fn record_round_trip() {
+        let record = BlackBoxRecord {
+            sequence: 1234,
+            uptime_s: 3600,
+            utc_s: Some(1_760_000_000),
+            speed_kmh: Some(23.45),
+            state_of_charge: Some(61.5),
+            pack_voltage: Some(52.08),
+            pack_current: Some(-85.3),
+            motor_current: Some(80.0),
+            throttle: Some(-20),
+            battery_state: Some(2),
+            battery_error_flags: Some(0),
+        };
+        assert!(BlackBoxRecord::from_bytes(&record.to_bytes()) == Some(record));
+
+        let unknown = BlackBoxRecord {
+            sequence: 1,
+            ..BlackBoxRecord::default()
+        };
+        assert!(BlackBoxRecord::from_bytes(&unknown.to_bytes()) == Some(unknown));
+        assert!(BlackBoxRecord::from_bytes(&[0xFF; RECORD_LEN]).is_none());
+    }
+
+    #[test]
+    fn request_and_response() {
+        let request = BlackBoxRequest {
+            skip: 300,
+            count: 32,
+        };
+        assert!(BlackBoxRequest::parse(&request.to_bytes()) == Some(request));
+        // More than fits a response is cut down
+        assert!(
+            BlackBoxRequest::parse(&[0x01, 0x00, 0x00, 0xFF])
+                .unwrap()
+                .count
+                == 64
+        );
+        assert!(BlackBoxRequest::parse(&[0x02, 0x00, 0x00, 0x01]).is_none());
+
+        let record = BlackBoxRecord {
+            sequence: 7,
+            ..BlackBoxRecord::default()
+        };
+        let mut payload = BlackBoxResponse {
+            count: 2,
+            stored: 900,
+        }
+        .to_bytes()
+        .to_vec();
+        payload.extend_from_slice(&record.to_bytes());
+        payload.extend_from_slice(&[0xFF; RECORD_LEN]);
+        let (response, records) = BlackBoxResponse::parse(&payload).unwrap();
+        assert!(response.stored == 900);
+        // The erased slot is left out
+        assert!(records.collect::<Vec<_>>() == [record]);
+    }
+}
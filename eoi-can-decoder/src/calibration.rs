@@ -0,0 +1,156 @@
+//! Correction of analog signals with a known error of their sensor, like a bias of the current of
+//! the peripherals. The value of the signal is scaled and offset in the frame before it is decoded,
+//! so every consumer of the decoder sees the corrected value. The table comes from a config file of
+//! the application, see
+//! [`DecoderConfig::with_calibration`](crate::DecoderConfig::with_calibration).
+
+use serde::ser::SerializeStruct;
+
+use crate::can_frame::CanFrame;
+use crate::signals::{self, MessageDefinition, SignalDefinition};
+
+/// Signals a [`Calibration`] can correct
+pub const MAX_CALIBRATIONS: usize = 8;
+
+/// Corrected value = value * `scale` + `offset`, of a signal of the [`signals::MESSAGES`] table.
+/// Signals with integer values are rounded to their resolution again
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+#[serde(try_from = "CalibrationEntry")]
+pub struct SignalCalibration {
+    pub message: &'static MessageDefinition,
+    pub signal: &'static SignalDefinition,
+    pub scale: f32,
+    pub offset: f32,
+}
+
+impl SignalCalibration {
+    /// `None` when the table has no such message or signal
+    pub fn new(message: &str, signal: &str, scale: f32, offset: f32) -> Option<Self> {
+        let message = signals::find_message_by_name(message)?;
+        Some(Self {
+            message,
+            signal: message.signals.iter().find(|other| other.name == signal)?,
+            scale,
+            offset,
+        })
+    }
+
+    fn is_of(&self, frame: &CanFrame) -> bool {
+        let (id, extended) = match frame.id {
+            embedded_can::Id::Standard(id) => (u32::from(id.as_raw()), false),
+            embedded_can::Id::Extended(id) => (id.as_raw(), true),
+        };
+        self.message.extended == extended
+            && self.message.ids().any(|(_, message_id)| message_id == id)
+    }
+
+    fn apply(&self, data: &mut [u8]) {
+        if let Some(value) = self.signal.decode(data) {
+            let _ = self
+                .signal
+                .encode(data, value * f64::from(self.scale) + f64::from(self.offset));
+        }
+    }
+}
+
+impl PartialEq for SignalCalibration {
+    fn eq(&self, other: &Self) -> bool {
+        core::ptr::eq(self.message, other.message)
+            && core::ptr::eq(self.signal, other.signal)
+            && self.scale == other.scale
+            && self.offset == other.offset
+    }
+}
+
+/// `{"message": "PackAndPerriCurrent", "signal": "PerriCurrent", "scale": 1.0, "offset": -0.3}`
+impl serde::Serialize for SignalCalibration {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut calibration = serializer.serialize_struct("SignalCalibration", 4)?;
+        calibration.serialize_field("message", self.message.name)?;
+        calibration.serialize_field("signal", self.signal.name)?;
+        calibration.serialize_field("scale", &self.scale)?;
+        calibration.serialize_field("offset", &self.offset)?;
+        calibration.end()
+    }
+}
+
+/// A signal of the config file, by the names of the table
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CalibrationEntry {
+    message: heapless::String<32>,
+    signal: heapless::String<32>,
+    #[serde(default = "unscaled")]
+    scale: f32,
+    #[serde(default)]
+    offset: f32,
+}
+
+fn unscaled() -> f32 {
+    1.0
+}
+
+impl TryFrom<CalibrationEntry> for SignalCalibration {
+    type Error = &'static str;
+
+    fn try_from(entry: CalibrationEntry) -> Result<Self, Self::Error> {
+        Self::new(&entry.message, &entry.signal, entry.scale, entry.offset)
+            .ok_or("unknown message or signal, expected their names in the signal table")
+    }
+}
+
+/// The corrected signals, none by default
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Calibration {
+    pub signals: heapless::Vec<SignalCalibration, MAX_CALIBRATIONS>,
+}
+
+impl Calibration {
+    pub fn is_empty(&self) -> bool {
+        self.signals.is_empty()
+    }
+
+    /// Correct the calibrated signals of the frame, frames that are too short are left alone
+    pub fn apply(&self, frame: &mut CanFrame) {
+        for calibration in &self.signals {
+            if calibration.is_of(frame) {
+                calibration.apply(&mut frame.data);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{frame, parse_eoi_can_data, EoiBattery, EoiCanData};
+    use assert2::assert;
+
+    #[test]
+    fn corrects_the_signal() {
+        let mut calibration = Calibration::default();
+        calibration
+            .signals
+            .push(SignalCalibration::new("PackAndPerriCurrent", "PerriCurrent", 1.0, -0.3).unwrap())
+            .unwrap();
+        assert!(SignalCalibration::new("PackAndPerriCurrent", "Perri", 1.0, 0.0).is_none());
+
+        // 20 A of the pack, 1.5 A of the peripherals
+        let mut can_frame = frame!(0x100, [0x00, 0x00, 0xA0, 0x41, 0x00, 0x00, 0xC0, 0x3F]);
+        calibration.apply(&mut can_frame);
+        let Some(EoiCanData::EoiBattery(EoiBattery::PackAndPerriCurrent(current))) =
+            parse_eoi_can_data(&can_frame)
+        else {
+            panic!("Unexpected data type");
+        };
+        assert!(current.pack_current == 20.0);
+        assert!((current.perri_current - 1.2).abs() < 0.001);
+
+        // Other messages aren't touched
+        let other = frame!(0x101, [0x00, 0x00, 0xA0, 0x41, 0x00, 0x00, 0xC0, 0x3F]);
+        let mut calibrated = other.clone();
+        calibration.apply(&mut calibrated);
+        assert!(calibrated == other);
+    }
+}
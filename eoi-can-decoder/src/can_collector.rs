@@ -2,42 +2,157 @@ use crate::can_frame::CanFrame;
 use embedded_can::Id;
 use heapless::FnvIndexMap;
 
+/// Colliding IDs kept, more than a few means a misconfigured node rather than a stray one
+pub const MAX_ID_COLLISIONS: usize = 16;
+
+/// Standard IDs, the only ones an extended ID can collide with
+const STANDARD_IDS: usize = 0x800;
+
 pub struct CanCollector {
-    latest_can_frames: FnvIndexMap<Id, CanFrame, 128>,
+    /// Keyed by bus and ID, the same ID can mean something else on another bus
+    latest_can_frames: FnvIndexMap<(u8, Id), CanFrame, 128>,
+    /// Frames a newer frame of the same ID replaced before they were taken, normal for IDs sent
+    /// more often than they are published
+    replaced_frames: usize,
+    /// Frames lost because the collector was full
     dropped_frames: usize,
+    /// Duplicates arriving within this many milliseconds are counted once, 0 keeps every frame
+    dedup_window_ms: u64,
+    /// Last frame of every ID and when it was inserted, on any bus
+    recent_frames: FnvIndexMap<Id, (CanFrame, u64), 128>,
+    suppressed_duplicates: usize,
+    /// Bit per standard ID, of the standard frames and the extended frames with an ID that fits
+    seen_standard: [u32; STANDARD_IDS / 32],
+    seen_extended: [u32; STANDARD_IDS / 32],
+    /// IDs seen as standard and as extended frame, in the order they were found
+    id_collisions: heapless::Vec<u16, MAX_ID_COLLISIONS>,
+    reported_collisions: usize,
 }
 
 impl CanCollector {
     pub const fn new() -> Self {
         Self {
             latest_can_frames: FnvIndexMap::new(),
+            replaced_frames: 0,
             dropped_frames: 0,
+            dedup_window_ms: 0,
+            recent_frames: FnvIndexMap::new(),
+            suppressed_duplicates: 0,
+            seen_standard: [0; STANDARD_IDS / 32],
+            seen_extended: [0; STANDARD_IDS / 32],
+            id_collisions: heapless::Vec::new(),
+            reported_collisions: 0,
         }
     }
 
+    /// Count a frame with the same ID and payload as one inserted with [`Self::insert_at`] less
+    /// than `window_ms` before once, no matter the bus. For buses that are also bridged, like
+    /// when the UDP bridge and the local bus deliver the same frame
+    pub fn with_dedup_window(mut self, window_ms: u64) -> Self {
+        self.dedup_window_ms = window_ms;
+        self
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = &CanFrame> {
         self.latest_can_frames.values()
     }
 
     pub fn clear(&mut self) {
+        self.replaced_frames = usize::default();
         self.dropped_frames = usize::default();
         self.latest_can_frames.clear();
     }
 
+    /// Insert a frame received at `now_ms`, a monotonic time in milliseconds, dropping it when
+    /// it duplicates a recent frame
+    pub fn insert_at(&mut self, frame: CanFrame, now_ms: u64) {
+        if self.dedup_window_ms > 0 {
+            let window_ms = self.dedup_window_ms;
+            if let Some((recent, inserted_ms)) = self.recent_frames.get(&frame.id) {
+                if recent.data == frame.data && now_ms.saturating_sub(*inserted_ms) < window_ms {
+                    self.suppressed_duplicates = self.suppressed_duplicates.saturating_add(1);
+                    return;
+                }
+            }
+            if self
+                .recent_frames
+                .insert(frame.id, (frame.clone(), now_ms))
+                .is_err()
+            {
+                // Full of IDs, only those still in the window are needed
+                self.recent_frames
+                    .retain(|_, (_, inserted_ms)| now_ms.saturating_sub(*inserted_ms) < window_ms);
+                self.recent_frames
+                    .insert(frame.id, (frame.clone(), now_ms))
+                    .ok();
+            }
+        }
+        self.insert(frame);
+    }
+
+    /// Insert a frame without checking for duplicates
     pub fn insert(&mut self, frame: CanFrame) {
-        let id = frame.id;
-        match self.latest_can_frames.insert(id, frame) {
+        self.check_id_collision(frame.id);
+        match self.latest_can_frames.insert((frame.bus, frame.id), frame) {
             Ok(None) => {}
             Ok(Some(_)) => {
-                self.dropped_frames = self.dropped_frames.saturating_add(1);
+                self.replaced_frames = self.replaced_frames.saturating_add(1);
             }
             Err(_) => self.dropped_frames = self.dropped_frames.saturating_add(1),
         }
     }
 
+    /// The decoder only looks at the number of the ID, so a third-party node sending an extended
+    /// frame with the ID of one of our standard frames would be decoded as our message
+    fn check_id_collision(&mut self, id: Id) {
+        let (raw, seen, other) = match id {
+            Id::Standard(id) => (id.as_raw(), &mut self.seen_standard, &self.seen_extended),
+            Id::Extended(id) => match u16::try_from(id.as_raw()) {
+                Ok(raw) if usize::from(raw) < STANDARD_IDS => {
+                    (raw, &mut self.seen_extended, &self.seen_standard)
+                }
+                _ => return,
+            },
+        };
+        let (word, bit) = (usize::from(raw) / 32, 1 << (raw % 32));
+        if seen[word] & bit != 0 {
+            return;
+        }
+        seen[word] |= bit;
+        if other[word] & bit != 0 {
+            // Beyond the limit the first ones are still reported
+            self.id_collisions.push(raw).ok();
+        }
+    }
+
+    /// IDs seen both as standard and as extended frame since the start, their frames may be
+    /// decoded as the wrong message. Not reset by [`Self::clear`]
+    pub fn id_collisions(&self) -> &[u16] {
+        &self.id_collisions
+    }
+
+    /// The next colliding ID found since the last call, for warning once per ID
+    pub fn take_new_id_collision(&mut self) -> Option<u16> {
+        let id = self.id_collisions.get(self.reported_collisions).copied()?;
+        self.reported_collisions += 1;
+        Some(id)
+    }
+
+    /// Frames replaced by a newer one of the same ID since the last [`Self::clear`]
+    pub fn get_replaced_frames(&self) -> usize {
+        self.replaced_frames
+    }
+
+    /// Frames lost because the collector was full since the last [`Self::clear`]
     pub fn get_dropped_frames(&self) -> usize {
         self.dropped_frames
     }
+
+    /// Duplicates dropped by [`Self::insert_at`] since the start, unlike the dropped frames not
+    /// reset by [`Self::clear`]
+    pub fn get_suppressed_duplicates(&self) -> usize {
+        self.suppressed_duplicates
+    }
 }
 
 impl Default for CanCollector {
@@ -48,31 +163,17 @@ impl Default for CanCollector {
 
 #[cfg(test)]
 mod tests {
-    use embedded_can::{ExtendedId, Id};
-
     use super::*;
-    use crate::can_frame::CanFrame;
+    use crate::frame;
     use assert2::assert;
 
     #[test]
     fn test_can_collector() {
         let mut collector = CanCollector::new();
-        let frame1 = CanFrame::from_encoded(
-            Id::Extended(ExtendedId::new(0x12345).unwrap()),
-            &[0x01, 0x02, 0x03],
-        );
-        let frame1_mirrored = CanFrame::from_encoded(
-            Id::Extended(ExtendedId::new(0x12345).unwrap()),
-            &[0x03, 0x02, 0x01],
-        );
-        let frame2 = CanFrame::from_encoded(
-            Id::Extended(ExtendedId::new(0x12346).unwrap()),
-            &[0x01, 0x02, 0x03],
-        );
-        let frame2_mirrored = CanFrame::from_encoded(
-            Id::Extended(ExtendedId::new(0x12346).unwrap()),
-            &[0x01, 0x02, 0x03],
-        );
+        let frame1 = frame!(extended 0x12345, [0x01, 0x02, 0x03]);
+        let frame1_mirrored = frame!(extended 0x12345, [0x03, 0x02, 0x01]);
+        let frame2 = frame!(extended 0x12346, [0x01, 0x02, 0x03]);
+        let frame2_mirrored = frame!(extended 0x12346, [0x01, 0x02, 0x03]);
 
         assert!(collector.iter().count() == 0);
         assert!(collector.get_dropped_frames() == 0);
@@ -94,7 +195,7 @@ mod tests {
         assert!(collector.iter().count() == 2);
         collector.insert(frame2.clone()); // Inserting the same frame again should not change the count
         assert!(collector.iter().count() == 2);
-        assert!(collector.get_dropped_frames() == 2);
+        assert!(collector.get_replaced_frames() == 2);
 
         collector.clear();
         assert!(collector.iter().count() == 0);
@@ -111,6 +212,75 @@ mod tests {
         assert!(collector.iter().count() == 2); // Should still be 2, as frame2_mirror replaces frame2
         assert!(collector.iter().next() == Some(&frame1_mirrored));
         assert!(collector.iter().nth(1) == Some(&frame2_mirrored));
-        assert!(collector.get_dropped_frames() == 2);
+        assert!(collector.get_replaced_frames() == 2);
+
+        // The same ID on another bus is another frame
+        collector.insert(frame1.clone().with_bus(1));
+        assert!(collector.iter().count() == 3);
+        assert!(collector.get_replaced_frames() == 2);
+        assert!(collector.get_dropped_frames() == 0);
+    }
+
+    #[test]
+    fn full_collector_drops_frames() {
+        let mut collector = CanCollector::new();
+        for id in 0..129 {
+            collector.insert(frame!(extended id, [0x01]));
+        }
+        assert!(collector.iter().count() == 128);
+        assert!(collector.get_dropped_frames() == 1);
+        assert!(collector.get_replaced_frames() == 0);
+    }
+
+    #[test]
+    fn standard_and_extended_ids_collide() {
+        let mut collector = CanCollector::new();
+        collector.insert(frame!(0x101, [0x01]));
+        collector.insert(frame!(extended 0x1_0101, [0x01]));
+        collector.insert(frame!(extended 0x0909, [0x01]));
+        collector.insert(frame!(extended 0x233, [0x01]));
+        assert!(collector.take_new_id_collision().is_none());
+
+        // A third-party node sending an extended frame with the number of one of ours
+        collector.insert(frame!(extended 0x101, [0x02]));
+        collector.insert(frame!(extended 0x101, [0x03]));
+        collector.insert(frame!(0x101, [0x01]));
+        assert!(collector.take_new_id_collision() == Some(0x101));
+        assert!(collector.take_new_id_collision().is_none());
+
+        collector.clear();
+        // Seen before the clear
+        collector.insert(frame!(0x233, [0x01]));
+        assert!(collector.id_collisions() == [0x101, 0x233]);
+        assert!(collector.take_new_id_collision() == Some(0x233));
+    }
+
+    #[test]
+    fn duplicates_of_bridged_buses() {
+        let mut collector = CanCollector::new().with_dedup_window(20);
+        let frame = frame!(extended 0x12345, [0x01, 0x02, 0x03]);
+
+        collector.insert_at(frame.clone(), 1000);
+        // The same frame over the bridge
+        collector.insert_at(frame.clone().with_bus(1), 1005);
+        assert!(collector.iter().count() == 1);
+        assert!(collector.get_suppressed_duplicates() == 1);
+
+        // Another payload or after the window is a new frame
+        let changed = frame!(extended 0x12345, [0x01, 0x02, 0x04]);
+        collector.insert_at(changed.clone().with_bus(1), 1010);
+        collector.insert_at(changed.clone(), 1040);
+        assert!(collector.iter().count() == 2);
+        assert!(collector.get_suppressed_duplicates() == 1);
+
+        collector.clear();
+        assert!(collector.get_suppressed_duplicates() == 1);
+
+        // Without a window every frame counts
+        let mut collector = CanCollector::new();
+        collector.insert_at(frame.clone(), 1000);
+        collector.insert_at(frame.with_bus(1), 1000);
+        assert!(collector.iter().count() == 2);
+        assert!(collector.get_suppressed_duplicates() == 0);
     }
 }
@@ -0,0 +1,969 @@
+//! Encoding of [`EoiCanData`] into CAN frames, the counterpart of [`crate::parse_eoi_can_data`].
+//!
+//! Every frame produced here decodes back into the same data (within the resolution of the
+//! scaling used on the bus).
+
+use embedded_can::{ExtendedId, Id, StandardId};
+
+use crate::can_frame::CanFrame;
+use crate::*;
+
+/// Encode data into the CAN frame it is decoded from, using the default decoder configuration.
+///
+/// Returns `None` for data that has no frame of its own, like `Unknown` enum values or VESC
+/// replies that are addressed to the node asking for them.
+pub fn encode_eoi_can_data(data: &EoiCanData) -> Option<CanFrame> {
+    encode_eoi_can_data_with_config(data, &DecoderConfig::default())
+}
+
+/// Encode data like [`encode_eoi_can_data`], the commands of the throttle go to the first VESC
+/// controller of `config`
+pub fn encode_eoi_can_data_with_config(
+    data: &EoiCanData,
+    config: &DecoderConfig,
+) -> Option<CanFrame> {
+    match data {
+        EoiCanData::EoiBattery(battery) => encode_battery(battery),
+        EoiCanData::Vesc(vesc) => {
+            let (controller_id, message) = vesc.as_parts();
+            encode_vesc(controller_id, message)
+        }
+        EoiCanData::Throttle(throttle) => {
+            encode_throttle(throttle, *config.vesc_controller_ids().first()?)
+        }
+        EoiCanData::Mppt(mppt) => {
+            let (mppt_id, info) = mppt.as_parts();
+            encode_mppt(mppt_id, info)
+        }
+        EoiCanData::Gnss(gnss) => encode_gnss(gnss),
+        EoiCanData::RudderController(RudderControllerData::Servo(servo)) => encode_servo(servo),
+        EoiCanData::HeightSensors(height) => encode_height_sensor(height),
+        EoiCanData::GanMppt(gan_mppt) => {
+            let (node_id, packet) = gan_mppt.as_parts();
+            encode_gan_mppt(node_id, packet)
+        }
+        EoiCanData::Temperature(temperature) => match temperature {
+            TemperatureData::HeightSensorsController(value) => {
+                standard_frame(0x210, &value.to_le_bytes())
+            }
+            TemperatureData::RudderController(value) => standard_frame(0x211, &value.to_le_bytes()),
+        },
+        EoiCanData::PeripheralPower(power) => standard_frame(
+            PERIPHERAL_POWER_ID + power.consumer as u16,
+            &power.current.to_le_bytes(),
+        ),
+        EoiCanData::WaterSpeed(speed) => standard_frame(
+            0x250,
+            &concat(&[
+                &speed.speed_kmh.to_le_bytes(),
+                &speed.pulse_frequency.to_le_bytes(),
+            ])?,
+        ),
+        EoiCanData::Imu(attitude) => {
+            let heave_rate = attitude.heave_rate.map_or(IMU_HEAVE_RATE_UNKNOWN, |rate| {
+                scale_to_i16(rate, 1000.0).max(IMU_HEAVE_RATE_UNKNOWN + 1)
+            });
+            standard_frame(
+                0x260,
+                &concat(&[
+                    &scale_to_i16(attitude.pitch, 100.0).to_le_bytes(),
+                    &scale_to_i16(attitude.roll, 100.0).to_le_bytes(),
+                    &heave_rate.to_le_bytes(),
+                ])?,
+            )
+        }
+        EoiCanData::NodeIdentification(identification) => standard_frame(
+            NODE_IDENTIFICATION_ID + identification.node as u16,
+            &concat(&[
+                &[
+                    identification.version_major,
+                    identification.version_minor,
+                    identification.version_patch,
+                    u8::from(identification.dirty),
+                ],
+                &identification.git_hash.to_be_bytes(),
+            ])?,
+        ),
+        EoiCanData::DisplayHeartbeat(heartbeat) => {
+            let payload = concat(&[
+                &heartbeat.uptime_s.to_le_bytes(),
+                &[
+                    heartbeat.firmware_version_major,
+                    heartbeat.firmware_version_minor,
+                    heartbeat.firmware_version_patch,
+                    heartbeat.last_refresh_age_s,
+                ],
+            ])?;
+            standard_frame(0x230, &payload)
+        }
+        EoiCanData::DisplayLog(log) => {
+            let flags = log.level as u8 | if log.continued { 0x80 } else { 0 };
+            let payload = concat(&[&[flags, log.sequence], log.text.as_bytes()])?;
+            standard_frame(0x231, &payload)
+        }
+        EoiCanData::DisplayBattery(battery) => {
+            let state_of_charge = (battery.state_of_charge * 100.0) as u16;
+            let payload = concat(&[&state_of_charge.to_le_bytes(), &[battery.charging as u8]])?;
+            standard_frame(0x232, &payload)
+        }
+        EoiCanData::DisplayControl(control) => match control {
+            DisplayControl::Page(page) => standard_frame(0x233, &[*page]),
+            DisplayControl::Profile(profile) => standard_frame(0x239, &[*profile]),
+            DisplayControl::RaceEnd(end_s) => {
+                standard_frame(0x23A, &end_s.unwrap_or(RACE_END_NONE).to_le_bytes())
+            }
+            DisplayControl::TripReset => standard_frame(0x23E, &[]),
+            DisplayControl::Message(message) => {
+                if message.part as usize >= DISPLAY_MESSAGE_PARTS
+                    || message.message_id > DISPLAY_MESSAGE_ID_MAX
+                {
+                    return None;
+                }
+                let flags = message.message_id | if message.last { 0x80 } else { 0 };
+                let payload = concat(&[&[flags], message.text.as_bytes()])?;
+                standard_frame(0x234 + message.part as u16, &payload)
+            }
+        },
+        EoiCanData::DisplayAcknowledge(acknowledge) => {
+            if acknowledge.message_id > DISPLAY_MESSAGE_ID_MAX {
+                return None;
+            }
+            standard_frame(0x238, &[acknowledge.message_id])
+        }
+        EoiCanData::DisplayPanic(panic) => {
+            let flags = panic.part & 0x7F | if panic.last { 0x80 } else { 0 };
+            let payload = concat(&[&[flags], panic.text.as_bytes()])?;
+            standard_frame(0x23B, &payload)
+        }
+    }
+}
+
+fn encode_battery(battery: &EoiBattery) -> Option<CanFrame> {
+    match battery {
+        EoiBattery::PackAndPerriCurrent(data) => standard_frame(
+            0x100,
+            &concat_8(
+                data.pack_current.to_le_bytes(),
+                data.perri_current.to_le_bytes(),
+            ),
+        ),
+        EoiBattery::ChargeAndDischargeCurrent(data) => standard_frame(
+            0x101,
+            &concat_8(
+                data.charge_current.to_le_bytes(),
+                (-data.discharge_current).to_le_bytes(),
+            ),
+        ),
+        EoiBattery::SocErrorFlagsAndBalancing(data) => {
+            let mut payload = [0; 8];
+            payload[0..2].copy_from_slice(&scale_to_u16(data.state_of_charge, 100.0).to_le_bytes());
+            payload[2..6].copy_from_slice(&data.error_flags.to_le_bytes());
+            payload[6..8].copy_from_slice(&data.balancing_status.to_le_bytes());
+            standard_frame(0x102, &payload)
+        }
+        EoiBattery::CellVoltages1_4(data) => standard_frame(0x103, &millivolts(&data.cell_voltage)),
+        EoiBattery::CellVoltages5_8(data) => standard_frame(0x104, &millivolts(&data.cell_voltage)),
+        EoiBattery::CellVoltages9_12(data) => {
+            standard_frame(0x105, &millivolts(&data.cell_voltage))
+        }
+        EoiBattery::CellVoltages13_14PackAndStack(data) => standard_frame(
+            0x106,
+            &millivolts(&[
+                data.cell_voltage[0],
+                data.cell_voltage[1],
+                data.pack_voltage,
+                data.stack_voltage,
+            ]),
+        ),
+        EoiBattery::CellVoltages15_16(data) => {
+            standard_frame(0x10C, &millivolts(&data.cell_voltage))
+        }
+        EoiBattery::TemperaturesAndStates(data) => standard_frame(
+            0x107,
+            &[
+                data.temperatures[0] as u8,
+                data.temperatures[1] as u8,
+                data.temperatures[2] as u8,
+                data.temperatures[3] as u8,
+                data.ic_temperature as u8,
+                battery_state_to_u8(&data.battery_state)?,
+                charge_state_to_u8(&data.charge_state)?,
+                discharge_state_to_u8(&data.discharge_state)?,
+            ],
+        ),
+        EoiBattery::Temperatures5_8(data) => standard_frame(
+            0x10E,
+            &data.temperatures.map(|temperature| temperature as u8),
+        ),
+        EoiBattery::BatteryUptime(data) => standard_frame(0x108, &data.uptime_ms.to_le_bytes()),
+        EoiBattery::ChargeControlStatus(data) => {
+            standard_frame(0x10A, &[data.charging_disabled as u8])
+        }
+        EoiBattery::ChargeControlCommand(data) => {
+            standard_frame(0x10B, &[data.charging_disabled as u8])
+        }
+        EoiBattery::TimeEstimate(data) => {
+            let minutes = |time: Option<u16>| {
+                time.map_or(BMS_TIME_UNKNOWN, |time| time.min(BMS_TIME_UNKNOWN - 1))
+            };
+            let payload = concat(&[
+                &minutes(data.time_to_empty_min).to_le_bytes(),
+                &minutes(data.time_to_full_min).to_le_bytes(),
+            ])?;
+            standard_frame(0x10D, &payload)
+        }
+    }
+}
+
+fn encode_vesc(controller_id: u8, message: &VescMessage) -> Option<CanFrame> {
+    let (packet_id, payload): (u32, heapless::Vec<u8, 8>) = match message {
+        VescMessage::StatusMessage1 {
+            rpm,
+            total_current,
+            duty_cycle,
+        } => (
+            9,
+            concat(&[
+                &rpm.to_be_bytes(),
+                &scale_to_i16(*total_current, 10.0).to_be_bytes(),
+                &scale_to_i16(*duty_cycle, 10.0).to_be_bytes(),
+            ])?,
+        ),
+        VescMessage::StatusMessage2 {
+            amp_hours_used,
+            amp_hours_generated,
+        } => (
+            14,
+            concat(&[
+                &scale_to_u32(*amp_hours_used, 10000.0).to_be_bytes(),
+                &scale_to_u32(*amp_hours_generated, 10000.0).to_be_bytes(),
+            ])?,
+        ),
+        VescMessage::StatusMessage3 {
+            watt_hours_used,
+            watt_hours_generated,
+        } => (
+            15,
+            concat(&[
+                &scale_to_u32(*watt_hours_used, 10000.0).to_be_bytes(),
+                &scale_to_u32(*watt_hours_generated, 10000.0).to_be_bytes(),
+            ])?,
+        ),
+        VescMessage::StatusMessage4 {
+            fet_temp,
+            motor_temp,
+            total_input_current,
+            current_pid_position,
+        } => (
+            16,
+            concat(&[
+                &scale_to_i16(*fet_temp, 10.0).to_be_bytes(),
+                &scale_to_i16(*motor_temp, 10.0).to_be_bytes(),
+                &scale_to_i16(*total_input_current, 10.0).to_be_bytes(),
+                &scale_to_i16(*current_pid_position, 50.0).to_be_bytes(),
+            ])?,
+        ),
+        VescMessage::StatusMessage5 {
+            input_voltage,
+            tachometer,
+        } => (
+            27,
+            concat(&[
+                &tachometer.to_be_bytes(),
+                &scale_to_i16(*input_voltage, 10.0).to_be_bytes(),
+                &[0, 0],
+            ])?,
+        ),
+        VescMessage::StatusMessage6 {
+            adc1,
+            adc2,
+            adc3,
+            ppm,
+        } => (
+            58,
+            concat(&[
+                &scale_to_i16(*adc1, 1000.0).to_be_bytes(),
+                &scale_to_i16(*adc2, 1000.0).to_be_bytes(),
+                &scale_to_i16(*adc3, 1000.0).to_be_bytes(),
+                &scale_to_i16(*ppm, 1000.0).to_be_bytes(),
+            ])?,
+        ),
+        VescMessage::Ping { sender_id } => (17, concat(&[&[*sender_id]])?),
+        // Broadcast short buffer reply to COMM_GET_VALUES_SELECTIVE with the fault bit only
+        VescMessage::Fault { fault } => {
+            let payload = concat(&[
+                &[controller_id, 1, 50],
+                &(1_u32 << 15).to_be_bytes(),
+                &[u8::from(*fault)],
+            ])?;
+            return extended_frame((8 << 8) | 255, &payload);
+        }
+        // Addressed to the node asking, which isn't part of the decoded data
+        VescMessage::FirmwareVersion { .. } | VescMessage::Pong { .. } => return None,
+    };
+
+    extended_frame((packet_id << 8) | controller_id as u32, &payload)
+}
+
+fn encode_throttle(throttle: &ThrottleData, vesc_controller_id: u8) -> Option<CanFrame> {
+    const THROTTLE_ID: u32 = 0x1337;
+    let vesc_command = |packet_id: u32, value: f32| {
+        extended_frame(
+            (packet_id << 8) | vesc_controller_id as u32,
+            &scale_to_i32(value, 1000.0).to_be_bytes(),
+        )
+    };
+
+    match throttle {
+        ThrottleData::ToVescDutyCycle(value) => vesc_command(0, *value),
+        ThrottleData::ToVescCurrent(value) => vesc_command(1, *value),
+        ThrottleData::ToVescRpm(value) => vesc_command(3, *value),
+        ThrottleData::Status(status) => {
+            let error_flags = status.error.bits()?;
+            let payload = concat(&[
+                &scale_to_i16(status.value, 512.0 / 100.0).to_be_bytes(),
+                &status.raw_angle.to_be_bytes(),
+                &status.raw_deadmen.to_be_bytes(),
+                &[status.gain, error_flags],
+            ])?;
+            extended_frame(THROTTLE_ID, &payload)
+        }
+        ThrottleData::Config(config) => {
+            let control_type = match config.control_type {
+                ThrottleControlType::DutyCycle => 0,
+                ThrottleControlType::FilteredDutyCycle => 1,
+                ThrottleControlType::Current => 2,
+                ThrottleControlType::Rpm => 3,
+                ThrottleControlType::CurrentRelative => 4,
+                ThrottleControlType::Unknown => return None,
+            };
+            let payload = concat(&[
+                &[control_type, 0],
+                &config.lever_forward.to_be_bytes(),
+                &config.lever_backward.to_be_bytes(),
+            ])?;
+            extended_frame(THROTTLE_ID, &payload)
+        }
+    }
+}
+
+fn encode_mppt(mppt_id: u8, info: &MpptInfo) -> Option<CanFrame> {
+    let (channel, channel_data) = match info {
+        MpptInfo::Channel0(data) => (0, data),
+        MpptInfo::Channel1(data) => (1, data),
+        MpptInfo::Channel2(data) => (2, data),
+        MpptInfo::Channel3(data) => (3, data),
+        MpptInfo::ChannelUnknown(_) => return None,
+        MpptInfo::Power(power) => {
+            return mppt_frame(
+                mppt_id,
+                8,
+                &concat_8(
+                    power.voltage_out.to_le_bytes(),
+                    power.current_out.to_le_bytes(),
+                ),
+            )
+        }
+        MpptInfo::Status(status) => {
+            let flags = status.pwm_enabled as u8 | (status.switch_on as u8) << 1;
+            let payload = concat(&[
+                &status.voltage_out_switch.to_le_bytes(),
+                &status.temperature.to_le_bytes(),
+                &[status.state, flags],
+            ])?;
+            return mppt_frame(mppt_id, 9, &payload);
+        }
+    };
+
+    match channel_data {
+        MpptChannel::Power(power) => mppt_frame(
+            mppt_id,
+            channel * 2,
+            &concat_8(
+                power.voltage_in.to_le_bytes(),
+                power.current_in.to_le_bytes(),
+            ),
+        ),
+        MpptChannel::State(state) => {
+            let payload = concat(&[
+                &state.duty_cycle.to_le_bytes(),
+                &[
+                    state.algorithm,
+                    state.algorithm_state,
+                    state.channel_active as u8,
+                ],
+            ])?;
+            mppt_frame(mppt_id, channel * 2 + 1, &payload)
+        }
+    }
+}
+
+fn mppt_frame(mppt_id: u8, info_field: u8, payload: &[u8]) -> Option<CanFrame> {
+    const MPPT_BASE_ADDRESS: u16 = 0x700;
+    standard_frame(
+        MPPT_BASE_ADDRESS | (mppt_id as u16) << 4 | info_field as u16,
+        payload,
+    )
+}
+
+fn encode_gnss(gnss: &GnssData) -> Option<CanFrame> {
+    match gnss {
+        GnssData::GnssStatus(status) => {
+            standard_frame(0x200, &[status.fix, status.sats, status.sats_used])
+        }
+        GnssData::GnssSpeedAndHeading(speed, heading) => {
+            standard_frame(0x201, &concat_8(speed.to_le_bytes(), heading.to_le_bytes()))
+        }
+        GnssData::GnssLatitude(latitude) => standard_frame(0x202, &latitude.to_le_bytes()),
+        GnssData::GnssLongitude(longitude) => standard_frame(0x203, &longitude.to_le_bytes()),
+        GnssData::GnssDateTime(date_time) => {
+            let payload = concat(&[
+                &date_time.year.to_le_bytes(),
+                &[
+                    date_time.month,
+                    date_time.day,
+                    date_time.hours,
+                    date_time.minutes,
+                    date_time.seconds,
+                ],
+            ])?;
+            standard_frame(0x204, &payload)
+        }
+        GnssData::GnssAltitude(altitude) => standard_frame(0x205, &altitude.to_le_bytes()),
+        GnssData::GnssFixQuality(quality) => {
+            let error = quality
+                .horizontal_error
+                .map_or(GNSS_HORIZONTAL_ERROR_UNKNOWN, |error| {
+                    scale_to_u16(error, 100.0).min(GNSS_HORIZONTAL_ERROR_UNKNOWN - 1)
+                });
+            let payload = concat(&[&[quality.mode], &error.to_le_bytes()])?;
+            standard_frame(0x206, &payload)
+        }
+        GnssData::GnssUtcOffset(offset) => standard_frame(0x207, &offset.to_le_bytes()),
+        GnssData::GnssTimeSync(utc_us) => standard_frame(0x208, &utc_us.to_le_bytes()),
+    }
+}
+
+fn encode_servo(servo: &ServoData) -> Option<CanFrame> {
+    match servo {
+        ServoData::Setpoint(setpoint) => standard_frame(0x010, &setpoint.to_le_bytes()),
+        ServoData::Status(status) => {
+            let state = match status.state {
+                ServoState::Uninitialized => 0,
+                ServoState::Operational => 1,
+                ServoState::Unknown => 0xFF,
+            };
+            let payload = concat(&[&[state], &status.setpoint.to_le_bytes()])?;
+            standard_frame(0x020, &payload)
+        }
+        ServoData::Command(command) => match command {
+            ServoRudderCommand::Initialize => standard_frame(0x021, &[0]),
+            ServoRudderCommand::Unknown => None,
+        },
+    }
+}
+
+fn encode_height_sensor(height: &HeightSensorData) -> Option<CanFrame> {
+    let (id, status) = match height {
+        HeightSensorData::FrontLeft(status) => (0x011, status),
+        HeightSensorData::FrontRight(status) => (0x012, status),
+        HeightSensorData::Reserved1(status) => (0x013, status),
+        HeightSensorData::Reserved2(status) => (0x014, status),
+    };
+    let state = match status.state {
+        HeightSensorState::NotPluggedIn => 0,
+        HeightSensorState::ModbusError => 1,
+        HeightSensorState::Operational => 2,
+        HeightSensorState::Unknown => 0xFF,
+    };
+    let payload = concat(&[&[state], &status.value.to_le_bytes()])?;
+    standard_frame(id, &payload)
+}
+
+fn encode_gan_mppt(node_id: u8, packet: &GanMpptPacket) -> Option<CanFrame> {
+    const GAN_MPPT_DEFAULT_NODE_ID: u16 = 64;
+
+    let (packet_id, payload) = match packet {
+        GanMpptPacket::Power(power) => (
+            0x00,
+            concat(&[
+                &scale_to_i16(power.input_voltage, 100.0).to_be_bytes(),
+                &scale_to_i16(power.input_current, 2000.0).to_be_bytes(),
+                &scale_to_i16(power.output_voltage, 100.0).to_be_bytes(),
+                &scale_to_i16(power.output_current, 2000.0).to_be_bytes(),
+            ])?,
+        ),
+        GanMpptPacket::Status(status) => (
+            0x01,
+            concat(&[&[
+                gan_phase_mode_to_u8(&status.mode)?,
+                gan_phase_fault_to_u8(&status.fault)?,
+                status.enabled as u8,
+                status.board_temp as u8,
+                status.heat_sink_temp as u8,
+            ]])?,
+        ),
+        GanMpptPacket::SweepData(sweep) => (
+            0x02,
+            concat(&[
+                &[sweep.index],
+                &scale_to_i16(sweep.current, 2000.0).to_be_bytes(),
+                &scale_to_i16(sweep.voltage, 100.0).to_be_bytes(),
+            ])?,
+        ),
+    };
+
+    standard_frame(
+        ((node_id as u16 + GAN_MPPT_DEFAULT_NODE_ID) << 4) | packet_id,
+        &payload,
+    )
+}
+
+/// The state as sent by the BMS, `None` for [`BatteryState::Unknown`]
+pub fn battery_state_to_u8(state: &BatteryState) -> Option<u8> {
+    Some(match state {
+        BatteryState::Init => 0,
+        BatteryState::Sleep => 1,
+        BatteryState::WaitingForStartup => 2,
+        BatteryState::Idle => 3,
+        BatteryState::OnlyCharge => 4,
+        BatteryState::OnlyDischarge => 5,
+        BatteryState::On => 6,
+        BatteryState::Unknown => return None,
+    })
+}
+
+fn charge_state_to_u8(state: &ChargeState) -> Option<u8> {
+    Some(match state {
+        ChargeState::Init => 0,
+        ChargeState::Idle => 1,
+        ChargeState::RelayOn => 2,
+        ChargeState::FetOn => 3,
+        ChargeState::Error => 4,
+        ChargeState::FetOff => 5,
+        ChargeState::Unknown => return None,
+    })
+}
+
+fn discharge_state_to_u8(state: &DischargeState) -> Option<u8> {
+    Some(match state {
+        DischargeState::Init => 0,
+        DischargeState::Idle => 1,
+        DischargeState::PreChargeOn => 2,
+        DischargeState::On => 3,
+        DischargeState::PreChargeTimeout => 4,
+        DischargeState::Error => 5,
+        DischargeState::Unknown => return None,
+    })
+}
+
+fn gan_phase_mode_to_u8(mode: &GanPhaseMode) -> Option<u8> {
+    Some(match mode {
+        GanPhaseMode::None => 0,
+        GanPhaseMode::Civ => 1,
+        GanPhaseMode::Cic => 2,
+        GanPhaseMode::MinInputCurrent => 3,
+        GanPhaseMode::Cov => 4,
+        GanPhaseMode::Coc => 5,
+        GanPhaseMode::TemperatureDerating => 6,
+        GanPhaseMode::Fault => 7,
+        GanPhaseMode::Unknown => return None,
+    })
+}
+
+fn gan_phase_fault_to_u8(fault: &GanPhaseFault) -> Option<u8> {
+    Some(match fault {
+        GanPhaseFault::Ok => 0,
+        GanPhaseFault::ConfigError => 1,
+        GanPhaseFault::InputOverVoltage => 2,
+        GanPhaseFault::OutputOverVoltage => 3,
+        GanPhaseFault::OutputOverCurrent => 4,
+        GanPhaseFault::InputOverCurrent => 5,
+        GanPhaseFault::InputUnderCurrent => 6,
+        GanPhaseFault::PhaseOverCurrent => 7,
+        GanPhaseFault::GeneralFault => 8,
+        GanPhaseFault::Unknown => return None,
+    })
+}
+
+fn standard_frame(id: u16, payload: &[u8]) -> Option<CanFrame> {
+    Some(CanFrame {
+        id: Id::Standard(StandardId::new(id)?),
+        data: heapless::Vec::from_slice(payload).ok()?,
+        bus: 0,
+    })
+}
+
+fn extended_frame(id: u32, payload: &[u8]) -> Option<CanFrame> {
+    Some(CanFrame {
+        id: Id::Extended(ExtendedId::new(id)?),
+        data: heapless::Vec::from_slice(payload).ok()?,
+        bus: 0,
+    })
+}
+
+fn concat(parts: &[&[u8]]) -> Option<heapless::Vec<u8, 8>> {
+    let mut payload = heapless::Vec::new();
+    for part in parts {
+        payload.extend_from_slice(part).ok()?;
+    }
+    Some(payload)
+}
+
+fn concat_8(first: [u8; 4], second: [u8; 4]) -> [u8; 8] {
+    let mut payload = [0; 8];
+    payload[0..4].copy_from_slice(&first);
+    payload[4..8].copy_from_slice(&second);
+    payload
+}
+
+fn millivolts<const N: usize>(voltages: &[f32; N]) -> heapless::Vec<u8, 8> {
+    let mut payload = heapless::Vec::new();
+    for voltage in voltages {
+        // N is at most 4, so this always fits
+        let _ = payload.extend_from_slice(&scale_to_u16(*voltage, 1000.0).to_le_bytes());
+    }
+    payload
+}
+
+// `as` saturates, rounding is done by hand since `f32::round` is not available in no_std
+
+fn round(value: f32) -> f32 {
+    if value < 0.0 {
+        value - 0.5
+    } else {
+        value + 0.5
+    }
+}
+
+fn scale_to_i16(value: f32, factor: f32) -> i16 {
+    round(value * factor) as i16
+}
+
+fn scale_to_u16(value: f32, factor: f32) -> u16 {
+    round(value * factor) as u16
+}
+
+fn scale_to_i32(value: f32, factor: f32) -> i32 {
+    round(value * factor) as i32
+}
+
+fn scale_to_u32(value: f32, factor: f32) -> u32 {
+    round(value * factor) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+
+    /// Encode, decode again and compare the debug output, the types don't implement PartialEq
+    fn assert_round_trip(data: EoiCanData) {
+        let frame = encode_eoi_can_data(&data).unwrap();
+        let decoded = parse_eoi_can_data(&frame).unwrap();
+        assert!(format!("{:?}", decoded) == format!("{:?}", data));
+    }
+
+    #[test]
+    fn battery_round_trip() {
+        assert_round_trip(EoiCanData::EoiBattery(EoiBattery::PackAndPerriCurrent(
+            PackAndPerriCurrent {
+                pack_current: 12.5,
+                perri_current: -0.25,
+            },
+        )));
+        assert_round_trip(EoiCanData::EoiBattery(
+            EoiBattery::ChargeAndDischargeCurrent(ChargeAndDischargeCurrent {
+                discharge_current: -17.5,
+                charge_current: 9.75,
+            }),
+        ));
+        assert_round_trip(EoiCanData::EoiBattery(
+            EoiBattery::SocErrorFlagsAndBalancing(SocErrorFlagsAndBalancing {
+                state_of_charge: 87.5,
+                error_flags: 0x1234_5678,
+                balancing_status: 0b1010,
+            }),
+        ));
+        assert_round_trip(EoiCanData::EoiBattery(EoiBattery::CellVoltages5_8(
+            FourCellVoltages {
+                cell_voltage: [3.5, 3.625, 4.125, 2.5],
+            },
+        )));
+        assert_round_trip(EoiCanData::EoiBattery(EoiBattery::TemperaturesAndStates(
+            TemperaturesAndStates {
+                temperatures: [20, -5, 31, 0],
+                ic_temperature: 40,
+                battery_state: BatteryState::OnlyCharge,
+                charge_state: ChargeState::FetOn,
+                discharge_state: DischargeState::PreChargeOn,
+            },
+        )));
+        assert_round_trip(EoiCanData::EoiBattery(EoiBattery::Temperatures5_8(
+            FourTemperatures {
+                temperatures: [28, 29, -3, -128],
+            },
+        )));
+        assert_round_trip(EoiCanData::EoiBattery(EoiBattery::ChargeControlStatus(
+            ChargeControl {
+                charging_disabled: true,
+            },
+        )));
+        assert_round_trip(EoiCanData::EoiBattery(EoiBattery::ChargeControlCommand(
+            ChargeControl {
+                charging_disabled: false,
+            },
+        )));
+        assert_round_trip(EoiCanData::EoiBattery(EoiBattery::BatteryUptime(
+            BatteryUptime { uptime_ms: 123456 },
+        )));
+        assert_round_trip(EoiCanData::EoiBattery(EoiBattery::TimeEstimate(
+            BmsTimeEstimate {
+                time_to_empty_min: None,
+                time_to_full_min: Some(42),
+            },
+        )));
+    }
+
+    #[test]
+    fn vesc_round_trip() {
+        assert_round_trip(EoiCanData::Vesc(
+            VescData::from_node_id(
+                9,
+                VescMessage::StatusMessage1 {
+                    rpm: -1500,
+                    total_current: 12.5,
+                    duty_cycle: -50.5,
+                },
+            )
+            .unwrap(),
+        ));
+        assert_round_trip(EoiCanData::Vesc(
+            VescData::from_node_id(
+                9,
+                VescMessage::StatusMessage5 {
+                    input_voltage: 48.5,
+                    tachometer: 100_000,
+                },
+            )
+            .unwrap(),
+        ));
+        assert_round_trip(EoiCanData::Vesc(
+            VescData::from_node_id(9, VescMessage::Ping { sender_id: 3 }).unwrap(),
+        ));
+        assert_round_trip(EoiCanData::Vesc(
+            VescData::from_node_id(
+                9,
+                VescMessage::Fault {
+                    fault: VescFault::OverTempMotor,
+                },
+            )
+            .unwrap(),
+        ));
+    }
+
+    #[test]
+    fn throttle_round_trip() {
+        assert_round_trip(EoiCanData::Throttle(ThrottleData::ToVescCurrent(-12.5)));
+        let config = DecoderConfig::with_vesc_controller_ids(&[10, 9]).unwrap();
+        let data = EoiCanData::Throttle(ThrottleData::ToVescRpm(1500.0));
+        let frame = encode_eoi_can_data_with_config(&data, &config).unwrap();
+        assert!(frame.id == Id::Extended(ExtendedId::new(0x030A).unwrap()));
+        let decoded = parse_eoi_can_data_with_config(&frame, &config).unwrap();
+        assert!(format!("{:?}", decoded) == format!("{:?}", data));
+        assert_round_trip(EoiCanData::Throttle(ThrottleData::Status(ThrottleStatus {
+            value: 50.0,
+            raw_angle: -1200,
+            raw_deadmen: 300,
+            gain: 7,
+            error: ThrottleErrors {
+                twi: ThrottleTwiErrors::SlaveNAK,
+                no_eeprom: false,
+                gain_clipping: true,
+                gain_invalid: false,
+                deadman_missing: true,
+                impedance_high: false,
+            },
+        })));
+        assert_round_trip(EoiCanData::Throttle(ThrottleData::Config(ThrottleConfig {
+            control_type: ThrottleControlType::Current,
+            lever_forward: 1000,
+            lever_backward: -1000,
+        })));
+    }
+
+    #[test]
+    fn mppt_round_trip() {
+        assert_round_trip(EoiCanData::Mppt(MpptData::Id5(MpptInfo::Channel2(
+            MpptChannel::Power(MpptChannelPower {
+                voltage_in: 42.5,
+                current_in: 3.25,
+            }),
+        ))));
+        assert_round_trip(EoiCanData::Mppt(MpptData::Id2(MpptInfo::Channel1(
+            MpptChannel::State(MpptChannelState {
+                duty_cycle: 512,
+                algorithm: 1,
+                algorithm_state: 2,
+                channel_active: true,
+            }),
+        ))));
+        assert_round_trip(EoiCanData::Mppt(MpptData::Id7(MpptInfo::Status(
+            MpptStatus {
+                voltage_out_switch: 50.0,
+                temperature: 35,
+                state: 3,
+                pwm_enabled: true,
+                switch_on: false,
+            },
+        ))));
+    }
+
+    #[test]
+    fn gnss_round_trip() {
+        assert_round_trip(EoiCanData::Gnss(GnssData::GnssSpeedAndHeading(25.5, 180.0)));
+        assert_round_trip(EoiCanData::Gnss(GnssData::GnssLatitude(52.0116)));
+        assert_round_trip(EoiCanData::Gnss(GnssData::GnssAltitude(-1.5)));
+        assert_round_trip(EoiCanData::Gnss(GnssData::GnssUtcOffset(-570)));
+        assert_round_trip(EoiCanData::Gnss(GnssData::GnssTimeSync(
+            1_749_904_205_250_000,
+        )));
+        assert_round_trip(EoiCanData::Gnss(GnssData::GnssFixQuality(GnssFixQuality {
+            mode: 3,
+            horizontal_error: Some(2.87),
+        })));
+        assert_round_trip(EoiCanData::Gnss(GnssData::GnssFixQuality(GnssFixQuality {
+            mode: 1,
+            horizontal_error: None,
+        })));
+        assert_round_trip(EoiCanData::Gnss(GnssData::GnssDateTime(GnssDateTime {
+            year: 2025,
+            month: 6,
+            day: 21,
+            hours: 13,
+            minutes: 37,
+            seconds: 42,
+        })));
+    }
+
+    #[test]
+    fn other_round_trip() {
+        assert_round_trip(EoiCanData::RudderController(RudderControllerData::Servo(
+            ServoData::Command(ServoRudderCommand::Initialize),
+        )));
+        assert_round_trip(EoiCanData::HeightSensors(HeightSensorData::FrontRight(
+            HeightSensorStatus {
+                state: HeightSensorState::Operational,
+                value: 1234,
+            },
+        )));
+        assert_round_trip(EoiCanData::GanMppt(GanMpptData::Id3(GanMpptPacket::Power(
+            GanMpptPower {
+                input_voltage: 40.5,
+                input_current: 2.5,
+                output_voltage: 50.25,
+                output_current: 1.5,
+            },
+        ))));
+        assert_round_trip(EoiCanData::Temperature(TemperatureData::RudderController(
+            2150,
+        )));
+        assert_round_trip(EoiCanData::PeripheralPower(PeripheralPower {
+            consumer: PeripheralConsumer::Radio,
+            current: -0.75,
+        }));
+        assert_round_trip(EoiCanData::WaterSpeed(WaterSpeed {
+            speed_kmh: 14.25,
+            pulse_frequency: 31.5,
+        }));
+        assert_round_trip(EoiCanData::Imu(ImuAttitude {
+            pitch: 3.25,
+            roll: -12.5,
+            heave_rate: Some(-0.35),
+        }));
+        assert_round_trip(EoiCanData::Imu(ImuAttitude {
+            pitch: -1.0,
+            roll: 0.5,
+            heave_rate: None,
+        }));
+        assert_round_trip(EoiCanData::NodeIdentification(NodeIdentification {
+            node: NodeType::Imu,
+            version_major: 1,
+            version_minor: 4,
+            version_patch: 2,
+            dirty: true,
+            git_hash: 0x1a2b_3c4d,
+        }));
+        assert_round_trip(EoiCanData::DisplayHeartbeat(DisplayHeartbeat {
+            uptime_s: 86_400,
+            firmware_version_major: 0,
+            firmware_version_minor: 1,
+            firmware_version_patch: 0,
+            last_refresh_age_s: 12,
+        }));
+        assert_round_trip(EoiCanData::DisplayLog(DisplayLog {
+            level: LogLevel::Warn,
+            sequence: 255,
+            continued: true,
+            text: heapless::String::try_from("Bus-o").unwrap(),
+        }));
+        assert_round_trip(EoiCanData::DisplayBattery(DisplayBattery {
+            state_of_charge: 87.5,
+            charging: true,
+        }));
+        assert_round_trip(EoiCanData::DisplayControl(DisplayControl::Page(1)));
+        assert_round_trip(EoiCanData::DisplayControl(DisplayControl::Profile(3)));
+        assert_round_trip(EoiCanData::DisplayControl(DisplayControl::RaceEnd(Some(
+            14 * 3600 + 30 * 60,
+        ))));
+        assert_round_trip(EoiCanData::DisplayControl(DisplayControl::RaceEnd(None)));
+        assert_round_trip(EoiCanData::DisplayControl(DisplayControl::TripReset));
+        for message in DisplayMessage::split(5, "SLOW 2 MIN") {
+            assert_round_trip(EoiCanData::DisplayControl(DisplayControl::Message(message)));
+        }
+        assert_round_trip(EoiCanData::DisplayAcknowledge(DisplayAcknowledge {
+            message_id: 5,
+        }));
+        assert_round_trip(EoiCanData::DisplayPanic(DisplayPanic {
+            part: 2,
+            last: true,
+            text: heapless::String::try_from("main.rs").unwrap(),
+        }));
+    }
+
+    #[test]
+    fn unknown_values_are_not_encoded() {
+        assert!(
+            encode_eoi_can_data(&EoiCanData::RudderController(RudderControllerData::Servo(
+                ServoData::Command(ServoRudderCommand::Unknown)
+            )))
+            .is_none()
+        );
+        assert!(encode_eoi_can_data(&EoiCanData::Vesc(
+            VescData::from_node_id(
+                9,
+                VescMessage::Pong {
+                    sender_id: 9,
+                    hw_type: VescHwType::Vesc,
+                }
+            )
+            .unwrap()
+        ))
+        .is_none());
+        // The top bit of the message ID marks the last part
+        for message in DisplayMessage::split(DISPLAY_MESSAGE_ID_MAX + 1, "PIT NOW") {
+            assert!(
+                encode_eoi_can_data(&EoiCanData::DisplayControl(DisplayControl::Message(
+                    message
+                )))
+                .is_none()
+            );
+        }
+        assert!(
+            encode_eoi_can_data(&EoiCanData::DisplayAcknowledge(DisplayAcknowledge {
+                message_id: DISPLAY_MESSAGE_ID_MAX + 1,
+            }))
+            .is_none()
+        );
+    }
+}
@@ -7,6 +7,21 @@ pub struct CanFrame {
     pub id: embedded_can::Id,
     /// The payload of the frame
     pub data: heapless::Vec<u8, { Self::MAX_LEN }>,
+    /// Index of the bus the frame was received on, 0 unless more than one interface is read
+    pub bus: u8,
+}
+
+/// A [`CanFrame`] written like the frame on the bus, `frame!(0x102, [0x25, 0x26])` with a
+/// standard ID or `frame!(extended 0x0909, [0x00, 0x64])` with an extended one. The data is any
+/// array, too long data fails to compile
+#[macro_export]
+macro_rules! frame {
+    (extended $id:expr, $data:expr $(,)?) => {
+        $crate::can_frame::CanFrame::extended($id, $data)
+    };
+    ($id:expr, $data:expr $(,)?) => {
+        $crate::can_frame::CanFrame::standard($id, $data)
+    };
 }
 
 #[cfg(feature = "arbitrary")]
@@ -26,20 +41,79 @@ impl arbitrary::Arbitrary<'_> for CanFrame {
             data.push(u.int_in_range(0..=255)?)
                 .expect("Data length exceeds MAX_LEN");
         }
-        Ok(Self { id, data })
+        Ok(Self { id, data, bus: 0 })
+    }
+}
+
+/// Why a CAN frame couldn't be created
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FrameError {
+    /// More data than a classic CAN frame holds, like from a CAN FD frame
+    DataTooLong(usize),
+}
+
+impl core::fmt::Display for FrameError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FrameError::DataTooLong(len) => write!(
+                f,
+                "{} bytes of data, a CAN frame holds at most {}",
+                len,
+                CanFrame::MAX_LEN
+            ),
+        }
     }
 }
 
+impl core::error::Error for FrameError {}
+
 impl CanFrame {
     const MAX_LEN: usize = 8;
 
-    /// Wrap an already encoded CAN frame
-    pub fn from_encoded(id: embedded_can::Id, data: &[u8]) -> Self {
+    /// Wrap an already encoded CAN frame, fails when the data doesn't fit in a CAN frame
+    pub fn try_new(id: embedded_can::Id, data: &[u8]) -> Result<Self, FrameError> {
+        Ok(Self {
+            id,
+            data: heapless::Vec::from_slice(data)
+                .map_err(|()| FrameError::DataTooLong(data.len()))?,
+            bus: 0,
+        })
+    }
+
+    /// Wrap an already encoded CAN frame, the length of the data is checked when compiling
+    pub fn from_array<const N: usize>(id: embedded_can::Id, data: [u8; N]) -> Self {
+        const { assert!(N <= Self::MAX_LEN, "Data length exceeds MAX_LEN") };
         Self {
             id,
-            data: heapless::Vec::from_slice(data).expect("Data length exceeds MAX_LEN"),
+            data: data.into_iter().collect(),
+            bus: 0,
         }
     }
+
+    /// Frame with the standard `id`, see [`frame!`](crate::frame). Panics when `id` is more
+    /// than 11 bits
+    pub fn standard<const N: usize>(id: u16, data: [u8; N]) -> Self {
+        let id = embedded_can::StandardId::new(id).expect("Standard CAN IDs have 11 bits");
+        Self::from_array(id.into(), data)
+    }
+
+    /// Frame with the extended `id`, see [`frame!`](crate::frame). Panics when `id` is more
+    /// than 29 bits
+    pub fn extended<const N: usize>(id: u32, data: [u8; N]) -> Self {
+        let id = embedded_can::ExtendedId::new(id).expect("Extended CAN IDs have 29 bits");
+        Self::from_array(id.into(), data)
+    }
+
+    /// The same frame, received on another bus
+    pub fn with_bus(self, bus: u8) -> Self {
+        Self { bus, ..self }
+    }
+
+    /// Like [`CanFrame::try_new`], for when the reason doesn't matter
+    pub fn try_from_slice(id: embedded_can::Id, data: &[u8]) -> Option<Self> {
+        Self::try_new(id, data).ok()
+    }
 }
 
 impl Debug for CanFrame {
@@ -69,10 +143,13 @@ impl Debug for CanFrame {
             }
         }
 
-        f.debug_struct("CanFrame")
-            .field("id", &DebugId(self.id))
-            .field("data", &DebugData(&self.data))
-            .finish()
+        let mut debug = f.debug_struct("CanFrame");
+        debug.field("id", &DebugId(self.id));
+        // Only shown when there is more than one bus, to keep the usual case short
+        if self.bus != 0 {
+            debug.field("bus", &self.bus);
+        }
+        debug.field("data", &DebugData(&self.data)).finish()
     }
 }
 
@@ -111,6 +188,7 @@ mod tests {
         CanFrame {
             id: ExtendedId::new(id).unwrap().into(),
             data: data.iter().copied().collect(),
+            bus: 0,
         }
     }
 
@@ -118,9 +196,38 @@ mod tests {
         CanFrame {
             id: StandardId::new(id).unwrap().into(),
             data: data.iter().copied().collect(),
+            bus: 0,
         }
     }
 
+    #[test]
+    fn constructors() {
+        let id = StandardId::new(0x2A).unwrap().into();
+        assert_eq!(CanFrame::from_array(id, [1, 2, 3]), std(0x2A, &[1, 2, 3]));
+        assert_eq!(CanFrame::try_new(id, &[1, 2, 3]), Ok(std(0x2A, &[1, 2, 3])));
+        assert_eq!(
+            CanFrame::try_from_slice(id, &[0; 8]),
+            Some(std(0x2A, &[0; 8]))
+        );
+        // CAN FD frames don't fit
+        assert_eq!(
+            CanFrame::try_new(id, &[0; 12]),
+            Err(FrameError::DataTooLong(12))
+        );
+        assert_eq!(CanFrame::try_from_slice(id, &[0; 12]), None);
+    }
+
+    #[test]
+    fn frame_macro() {
+        assert_eq!(crate::frame!(0x2A, [1, 2, 3]), std(0x2A, &[1, 2, 3]));
+        assert_eq!(crate::frame!(0x7FF, []), std(0x7FF, &[]));
+        assert_eq!(crate::frame!(extended 0x2A, [0; 8]), ext(0x2A, &[0; 8]));
+        assert_eq!(
+            crate::frame!(extended 0x1FFF_FFFF, 0x0102_u16.to_be_bytes()),
+            ext(0x1FFF_FFFF, &[1, 2])
+        );
+    }
+
     #[test]
     fn debug_formats_nicely() {
         let debug = format!("{:?}", ext(0x2A, &[]));
@@ -134,5 +241,8 @@ mod tests {
             debug,
             "CanFrame { id: 0x002A, data: [0x01, 0x02, 0x03, 0x04, 0x0F, 0x10, 0xFF] }"
         );
+
+        let debug = format!("{:?}", std(0x2A, &[1]).with_bus(1));
+        assert_eq!(debug, "CanFrame { id: 0x002A, bus: 1, data: [0x01] }");
     }
 }
@@ -0,0 +1,199 @@
+//! The receive and decode loop of an embassy node, over any async CAN receiver. The display
+//! firmware runs it in its receiver task, and other boards like the throttle or the sensors can
+//! do the same instead of copying it: they only implement [`Receiver`] for their CAN peripheral
+//! and [`Handler`] for what to do with the decoded frames.
+
+use crate::can_frame::{CanFrame, FrameError};
+use crate::{parse_eoi_can_data, EoiCanData};
+
+/// Receives frames from a CAN peripheral, waiting for the next one
+#[allow(async_fn_in_trait)]
+pub trait Receiver {
+    /// Driver frame, which may carry more than `embedded_can` knows of, like the receive time
+    type Frame: embedded_can::Frame;
+    type Error: core::fmt::Debug;
+
+    async fn receive(&mut self) -> Result<Self::Frame, Self::Error>;
+}
+
+/// What a node does with the frames of [`receive_and_decode`]
+#[allow(async_fn_in_trait)]
+pub trait Handler<R: Receiver> {
+    /// A frame was received, `data` is `None` for frames the decoder doesn't know
+    async fn on_frame(&mut self, received: &R::Frame, frame: CanFrame, data: Option<EoiCanData>);
+
+    /// The receiver failed. The same kind of error is only reported again after a frame was
+    /// received in between, a bus off would otherwise be reported on every poll
+    async fn on_error(&mut self, error: R::Error);
+
+    /// A frame that doesn't fit a [`CanFrame`] was dropped
+    async fn on_invalid_frame(&mut self, _error: FrameError) {}
+}
+
+/// Receive and decode frames of `receiver` forever, handing them to `handler` as they come
+pub async fn receive_and_decode<R: Receiver, H: Handler<R>>(
+    receiver: &mut R,
+    handler: &mut H,
+) -> ! {
+    let mut last_error = None;
+    loop {
+        match receiver.receive().await {
+            Ok(received) => {
+                last_error = None;
+                let frame = match CanFrame::try_new(
+                    embedded_can::Frame::id(&received),
+                    embedded_can::Frame::data(&received),
+                ) {
+                    Ok(frame) => frame,
+                    Err(error) => {
+                        handler.on_invalid_frame(error).await;
+                        continue;
+                    }
+                };
+                let data = parse_eoi_can_data(&frame);
+                handler.on_frame(&received, frame, data).await;
+            }
+            Err(error) => {
+                // Compare the variants, driver errors don't need to implement PartialEq
+                let kind = core::mem::discriminant(&error);
+                if last_error != Some(kind) {
+                    last_error = Some(kind);
+                    handler.on_error(error).await;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GnssData;
+    use assert2::assert;
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::{Context, Poll, Waker};
+    use embedded_can::{Frame as _, Id, StandardId};
+
+    #[derive(Debug)]
+    struct TestFrame {
+        id: Id,
+        data: heapless::Vec<u8, 64>,
+    }
+
+    impl embedded_can::Frame for TestFrame {
+        fn new(id: impl Into<Id>, data: &[u8]) -> Option<Self> {
+            Some(Self {
+                id: id.into(),
+                data: heapless::Vec::from_slice(data).ok()?,
+            })
+        }
+
+        fn new_remote(_id: impl Into<Id>, _dlc: usize) -> Option<Self> {
+            None
+        }
+
+        fn is_extended(&self) -> bool {
+            matches!(self.id, Id::Extended(_))
+        }
+
+        fn is_remote_frame(&self) -> bool {
+            false
+        }
+
+        fn id(&self) -> Id {
+            self.id
+        }
+
+        fn dlc(&self) -> usize {
+            self.data.len()
+        }
+
+        fn data(&self) -> &[u8] {
+            &self.data
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum TestError {
+        BusOff,
+        Overrun,
+    }
+
+    /// Hands out its results in order, then waits forever
+    struct TestReceiver(std::vec::Vec<Result<TestFrame, TestError>>);
+
+    impl Receiver for TestReceiver {
+        type Frame = TestFrame;
+        type Error = TestError;
+
+        async fn receive(&mut self) -> Result<TestFrame, TestError> {
+            if self.0.is_empty() {
+                core::future::pending::<()>().await;
+            }
+            self.0.remove(0)
+        }
+    }
+
+    #[derive(Default)]
+    struct TestHandler {
+        frames: std::vec::Vec<(u16, Option<EoiCanData>)>,
+        errors: std::vec::Vec<TestError>,
+        invalid: usize,
+    }
+
+    impl Handler<TestReceiver> for TestHandler {
+        async fn on_frame(&mut self, received: &TestFrame, _: CanFrame, data: Option<EoiCanData>) {
+            let Id::Standard(id) = received.id() else {
+                panic!("unexpected extended frame");
+            };
+            self.frames.push((id.as_raw(), data));
+        }
+
+        async fn on_error(&mut self, error: TestError) {
+            self.errors.push(error);
+        }
+
+        async fn on_invalid_frame(&mut self, _: FrameError) {
+            self.invalid += 1;
+        }
+    }
+
+    fn frame(id: u16, data: &[u8]) -> Result<TestFrame, TestError> {
+        Ok(TestFrame::new(StandardId::new(id).unwrap(), data).unwrap())
+    }
+
+    #[test]
+    fn frames_are_decoded_and_repeated_errors_reported_once() {
+        let mut receiver = TestReceiver(std::vec![
+            frame(0x208, &1_700_000_000_000_000u64.to_le_bytes()),
+            Err(TestError::BusOff),
+            Err(TestError::BusOff),
+            Err(TestError::Overrun),
+            frame(0x7FF, &[1, 2]),
+            Err(TestError::Overrun),
+            frame(0x208, &[0; 12]),
+        ]);
+        let mut handler = TestHandler::default();
+        {
+            let future = pin!(receive_and_decode(&mut receiver, &mut handler));
+            // Everything is handled by the first poll, then the receiver waits forever
+            let poll = future.poll(&mut Context::from_waker(Waker::noop()));
+            assert!(matches!(poll, Poll::Pending));
+        }
+
+        assert!(handler.frames.len() == 2);
+        assert!(matches!(
+            handler.frames[0],
+            (
+                0x208,
+                Some(EoiCanData::Gnss(GnssData::GnssTimeSync(
+                    1_700_000_000_000_000
+                )))
+            )
+        ));
+        assert!(matches!(handler.frames[1], (0x7FF, None)));
+        assert!(handler.errors == [TestError::BusOff, TestError::Overrun, TestError::Overrun]);
+        assert!(handler.invalid == 1);
+    }
+}
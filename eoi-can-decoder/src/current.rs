@@ -0,0 +1,177 @@
+//! Sign convention of the battery currents. The decoder gives every current of the battery and
+//! the peripherals [`CurrentConvention::ChargePositive`]: positive while it flows into the
+//! battery, negative while the motors or the peripherals draw from it. The BMS sends the
+//! discharge current as a positive magnitude, which is why [`ChargeAndDischargeCurrent`] is
+//! negated while decoding and again while encoding.
+//!
+//! Consumers that rather see the consumption as positive, like the power and energy figures of
+//! the displays, convert with these helpers instead of negating on their own.
+
+use core::str::FromStr;
+
+use crate::{ChargeAndDischargeCurrent, EoiBattery, EoiCanData, PackAndPerriCurrent};
+
+/// Which direction of a current is positive
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CurrentConvention {
+    /// Positive while charging, the convention of the decoder
+    #[default]
+    ChargePositive,
+    /// Positive while discharging
+    DischargePositive,
+}
+
+impl CurrentConvention {
+    /// The convention of every current the decoder gives
+    pub const DECODED: CurrentConvention = CurrentConvention::ChargePositive;
+
+    /// A current of the decoder in this convention
+    pub fn convert(self, current: f32) -> f32 {
+        match self {
+            CurrentConvention::ChargePositive => current,
+            CurrentConvention::DischargePositive => -current,
+        }
+    }
+
+    /// A current in this convention as the decoder gives it
+    pub fn to_decoded(self, current: f32) -> f32 {
+        // Flipping the sign is its own inverse
+        self.convert(current)
+    }
+
+    /// Power in W of a current of the decoder at `voltage`, in this convention
+    pub fn power(self, voltage: f32, current: f32) -> f32 {
+        voltage * self.convert(current)
+    }
+
+    /// A value of `signal` of `message` of the [`crate::signals`] registry in this convention,
+    /// only the currents [`EoiCanData::with_current_convention`] converts change
+    pub fn convert_signal(self, message: &str, signal: &str, value: f64) -> f64 {
+        match self {
+            CurrentConvention::DischargePositive
+                if BATTERY_CURRENT_SIGNALS.contains(&(message, signal)) =>
+            {
+                -value
+            }
+            _ => value,
+        }
+    }
+}
+
+/// Messages and signals of the registry with the currents of the battery and the peripherals
+const BATTERY_CURRENT_SIGNALS: &[(&str, &str)] = &[
+    ("PackAndPerriCurrent", "PackCurrent"),
+    ("PackAndPerriCurrent", "PerriCurrent"),
+    ("ChargeAndDischargeCurrent", "ChargeCurrent"),
+    ("ChargeAndDischargeCurrent", "DischargeCurrent"),
+    ("PeripheralPower", "Current"),
+];
+
+impl FromStr for CurrentConvention {
+    type Err = &'static str;
+
+    fn from_str(convention: &str) -> Result<Self, Self::Err> {
+        match convention {
+            "charge-positive" => Ok(CurrentConvention::ChargePositive),
+            "discharge-positive" => Ok(CurrentConvention::DischargePositive),
+            _ => Err("expected charge-positive or discharge-positive"),
+        }
+    }
+}
+
+impl EoiCanData {
+    /// The currents of the battery in `convention`, everything else as decoded
+    pub fn with_current_convention(self, convention: CurrentConvention) -> Self {
+        let current = |current| convention.convert(current);
+        match self {
+            EoiCanData::EoiBattery(EoiBattery::PackAndPerriCurrent(data)) => {
+                EoiCanData::EoiBattery(EoiBattery::PackAndPerriCurrent(PackAndPerriCurrent {
+                    pack_current: current(data.pack_current),
+                    perri_current: current(data.perri_current),
+                }))
+            }
+            EoiCanData::EoiBattery(EoiBattery::ChargeAndDischargeCurrent(data)) => {
+                EoiCanData::EoiBattery(EoiBattery::ChargeAndDischargeCurrent(
+                    ChargeAndDischargeCurrent {
+                        discharge_current: current(data.discharge_current),
+                        charge_current: current(data.charge_current),
+                    },
+                ))
+            }
+            EoiCanData::PeripheralPower(mut data) => {
+                data.current = current(data.current);
+                EoiCanData::PeripheralPower(data)
+            }
+            data => data,
+        }
+    }
+
+    /// The currents of the battery in `convention` back in the one of the decoder
+    pub fn into_decoded_convention(self, convention: CurrentConvention) -> Self {
+        // Flipping the signs is its own inverse
+        self.with_current_convention(convention)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{frame, parse_eoi_can_data};
+    use assert2::assert;
+
+    #[test]
+    fn conversion() {
+        let convention = CurrentConvention::DischargePositive;
+        assert!(convention.convert(-17.5) == 17.5);
+        assert!(convention.to_decoded(17.5) == -17.5);
+        assert!(convention.power(50.0, -2.0) == 100.0);
+        assert!(CurrentConvention::DECODED.convert(3.0) == 3.0);
+        assert!(CurrentConvention::DECODED.power(50.0, -2.0) == -100.0);
+        assert!(convention.convert_signal("PackAndPerriCurrent", "PackCurrent", -2.5) == 2.5);
+        assert!(convention.convert_signal("PackAndPerriCurrent", "PackVoltage", 50.0) == 50.0);
+
+        assert!("discharge-positive".parse() == Ok(CurrentConvention::DischargePositive));
+        assert!("charge-positive".parse() == Ok(CurrentConvention::ChargePositive));
+        assert!("positive".parse::<CurrentConvention>().is_err());
+    }
+
+    #[test]
+    fn battery_currents_in_a_convention() {
+        // Charging with 9.98 A while discharging with 17.53 A
+        let can_frame = frame!(0x101, [0xE8, 0x9F, 0x1F, 0x41, 0x50, 0x37, 0x8C, 0x41]);
+
+        let decoded = parse_eoi_can_data(&can_frame).unwrap();
+        let EoiCanData::EoiBattery(EoiBattery::ChargeAndDischargeCurrent(decoded)) = decoded else {
+            panic!("Unexpected data type");
+        };
+        assert!(decoded.charge_current > 0.0);
+        assert!(decoded.discharge_current < 0.0);
+
+        let converted = parse_eoi_can_data(&can_frame)
+            .unwrap()
+            .with_current_convention(CurrentConvention::DischargePositive);
+        let EoiCanData::EoiBattery(EoiBattery::ChargeAndDischargeCurrent(converted)) = converted
+        else {
+            panic!("Unexpected data type");
+        };
+        assert!(converted.charge_current == -decoded.charge_current);
+        assert!(converted.discharge_current == -decoded.discharge_current);
+
+        let back = EoiCanData::EoiBattery(EoiBattery::ChargeAndDischargeCurrent(converted))
+            .into_decoded_convention(CurrentConvention::DischargePositive);
+        let EoiCanData::EoiBattery(EoiBattery::ChargeAndDischargeCurrent(back)) = back else {
+            panic!("Unexpected data type");
+        };
+        assert!(back.charge_current == decoded.charge_current);
+
+        let unchanged = parse_eoi_can_data(&can_frame)
+            .unwrap()
+            .with_current_convention(CurrentConvention::ChargePositive);
+        let EoiCanData::EoiBattery(EoiBattery::ChargeAndDischargeCurrent(unchanged)) = unchanged
+        else {
+            panic!("Unexpected data type");
+        };
+        assert!(unchanged.discharge_current == decoded.discharge_current);
+    }
+}
@@ -0,0 +1,140 @@
+//! Export of the CAN matrix in [`crate::signals`] as DBC file, to be used with tools like
+//! SavvyCAN or CANalyzer.
+
+use core::fmt::{Result, Write};
+
+use crate::signals::{ByteOrder, MessageDefinition, SignalDefinition, ValueType, MESSAGES};
+
+/// DBC marks extended IDs by setting the highest bit
+const DBC_EXTENDED_ID_FLAG: u32 = 1 << 31;
+const DBC_NO_RECEIVER: &str = "Vector__XXX";
+
+pub fn write_dbc<W: Write>(w: &mut W) -> Result {
+    writeln!(w, "VERSION \"\"")?;
+    writeln!(w)?;
+    writeln!(w, "NS_ :")?;
+    writeln!(w)?;
+    writeln!(w, "BS_:")?;
+    writeln!(w)?;
+
+    write!(w, "BU_:")?;
+    for (index, message) in MESSAGES.iter().enumerate() {
+        // only write every transmitter once
+        if MESSAGES[..index]
+            .iter()
+            .all(|other| other.transmitter != message.transmitter)
+        {
+            write!(w, " {}", message.transmitter)?;
+        }
+    }
+    writeln!(w)?;
+
+    for message in MESSAGES {
+        for (node, id) in message.ids() {
+            writeln!(w)?;
+            write!(w, "BO_ {} ", dbc_id(message, id))?;
+            write_message_name(w, message, node)?;
+            writeln!(w, ": {} {}", message.dlc, message.transmitter)?;
+            for signal in message.signals {
+                write_signal(w, signal)?;
+            }
+        }
+    }
+
+    writeln!(w)?;
+    for message in MESSAGES {
+        for (_, id) in message.ids() {
+            for signal in message.signals {
+                let float_type = match signal.value_type {
+                    ValueType::Float => 1,
+                    ValueType::Double => 2,
+                    _ => continue,
+                };
+                writeln!(
+                    w,
+                    "SIG_VALTYPE_ {} {} : {};",
+                    dbc_id(message, id),
+                    signal.name,
+                    float_type
+                )?;
+            }
+        }
+    }
+
+    for message in MESSAGES {
+        for (_, id) in message.ids() {
+            for signal in message.signals.iter().filter(|s| !s.values.is_empty()) {
+                write!(w, "VAL_ {} {}", dbc_id(message, id), signal.name)?;
+                for (value, name) in signal.values {
+                    write!(w, " {} \"{}\"", value, name)?;
+                }
+                writeln!(w, " ;")?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn dbc_id(message: &MessageDefinition, id: u32) -> u32 {
+    if message.extended {
+        id | DBC_EXTENDED_ID_FLAG
+    } else {
+        id
+    }
+}
+
+fn write_message_name<W: Write>(w: &mut W, message: &MessageDefinition, node: u8) -> Result {
+    if message.node_count > 1 {
+        write!(w, "{}_{}", message.name, node)
+    } else {
+        write!(w, "{}", message.name)
+    }
+}
+
+fn write_signal<W: Write>(w: &mut W, signal: &SignalDefinition) -> Result {
+    // Big endian (Motorola) signals start at the most significant bit of their first byte
+    let (start_bit, byte_order) = match signal.byte_order {
+        ByteOrder::LittleEndian => (signal.start, 1),
+        ByteOrder::BigEndian => (signal.start + 7, 0),
+    };
+    let sign = match signal.value_type {
+        ValueType::Unsigned => '+',
+        _ => '-',
+    };
+    writeln!(
+        w,
+        " SG_ {} : {}|{}@{}{} ({},{}) [0|0] \"{}\" {}",
+        signal.name,
+        start_bit,
+        signal.length,
+        byte_order,
+        sign,
+        signal.factor,
+        signal.offset,
+        signal.unit,
+        DBC_NO_RECEIVER
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+
+    #[test]
+    fn dbc_contains_messages() {
+        let mut dbc = std::string::String::new();
+        write_dbc(&mut dbc).unwrap();
+
+        assert!(dbc.contains("BU_: RudderController HeightSensors BMS GNSS"));
+        assert!(dbc.contains("BO_ 256 PackAndPerriCurrent: 8 BMS"));
+        assert!(dbc.contains(" SG_ StateOfCharge : 0|16@1+ (0.01,0) [0|0] \"%\" Vector__XXX"));
+        assert!(dbc.contains("SIG_VALTYPE_ 256 PackCurrent : 1;"));
+        assert!(dbc.contains("BO_ 1848 MpptPower_3: 8 MPPT"));
+        // 0x80000000 | 0x909
+        assert!(dbc.contains("BO_ 2147485961 VescStatusMessage1: 8 VESC"));
+        assert!(dbc.contains(" SG_ Rpm : 7|32@0- (1,0) [0|0] \"rpm\" Vector__XXX"));
+        assert!(dbc.contains("VAL_ 263 BatteryState 0 \"Init\""));
+    }
+}
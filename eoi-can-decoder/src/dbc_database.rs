@@ -0,0 +1,291 @@
+//! Runtime loaded DBC file, used to decode frames the typed decoder doesn't know (yet) into
+//! generic named signals. Handy for new prototype boards before they get their own structs.
+//!
+//! Only the parts needed for decoding are read (`BO_`, `SG_` and `SIG_VALTYPE_`), multiplexed
+//! signals are skipped.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::can_frame::CanFrame;
+use crate::signals::{decode_raw, ByteOrder, ValueType};
+
+const DBC_EXTENDED_ID_FLAG: u32 = 1 << 31;
+const EXTENDED_ID_MASK: u32 = 0x1FFF_FFFF;
+
+#[derive(Debug)]
+pub enum DbcError {
+    Io(std::io::Error),
+    Parse { line: usize, reason: &'static str },
+}
+
+impl core::fmt::Display for DbcError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            DbcError::Io(error) => write!(f, "Unable to read DBC file: {}", error),
+            DbcError::Parse { line, reason } => {
+                write!(f, "Invalid DBC file at line {}: {}", line, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DbcError {}
+
+#[derive(Debug)]
+struct DbcSignal {
+    name: String,
+    start: u16,
+    length: u16,
+    byte_order: ByteOrder,
+    value_type: ValueType,
+    factor: f64,
+    offset: f64,
+}
+
+#[derive(Debug)]
+struct DbcMessage {
+    id: u32,
+    extended: bool,
+    name: String,
+    signals: Vec<DbcSignal>,
+}
+
+/// Message decoded with a DBC file, signals are scaled to their physical value
+#[derive(Debug, Serialize, PartialEq)]
+pub struct GenericMessage {
+    pub name: String,
+    pub signals: BTreeMap<String, f64>,
+}
+
+#[derive(Debug, Default)]
+pub struct DbcDatabase {
+    messages: Vec<DbcMessage>,
+}
+
+impl DbcDatabase {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, DbcError> {
+        let content = std::fs::read_to_string(path).map_err(DbcError::Io)?;
+        Self::parse(&content)
+    }
+
+    pub fn parse(content: &str) -> Result<Self, DbcError> {
+        let mut database = Self::default();
+
+        for (index, line) in content.lines().enumerate() {
+            let line_number = index + 1;
+            let error = |reason| DbcError::Parse {
+                line: line_number,
+                reason,
+            };
+            let line = line.trim();
+
+            if let Some(message) = line.strip_prefix("BO_ ") {
+                database
+                    .messages
+                    .push(parse_message(message).ok_or(error("invalid message"))?);
+            } else if let Some(signal) = line.strip_prefix("SG_ ") {
+                let message = database
+                    .messages
+                    .last_mut()
+                    .ok_or(error("signal without message"))?;
+                if let Some(signal) = parse_signal(signal).ok_or(error("invalid signal"))? {
+                    message.signals.push(signal);
+                }
+            } else if let Some(value_type) = line.strip_prefix("SIG_VALTYPE_ ") {
+                database
+                    .set_value_type(value_type)
+                    .ok_or(error("invalid signal value type"))?;
+            }
+        }
+
+        Ok(database)
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    pub fn decode(&self, frame: &CanFrame) -> Option<GenericMessage> {
+        let (id, extended) = match frame.id {
+            embedded_can::Id::Standard(id) => (id.as_raw() as u32, false),
+            embedded_can::Id::Extended(id) => (id.as_raw(), true),
+        };
+        let message = self
+            .messages
+            .iter()
+            .find(|message| message.id == id && message.extended == extended)?;
+
+        let signals = message
+            .signals
+            .iter()
+            .filter_map(|signal| Some((signal.name.clone(), signal.decode(&frame.data)?)))
+            .collect();
+
+        Some(GenericMessage {
+            name: message.name.clone(),
+            signals,
+        })
+    }
+
+    fn set_value_type(&mut self, line: &str) -> Option<()> {
+        // SIG_VALTYPE_ <id> <signal> : <1|2>;
+        let mut parts = line.split_whitespace();
+        let (id, extended) = split_dbc_id(parts.next()?.parse().ok()?);
+        let name = parts.next()?;
+        let value_type = match parts.find(|part| *part != ":")?.trim_end_matches(';') {
+            "1" => ValueType::Float,
+            "2" => ValueType::Double,
+            _ => return None,
+        };
+
+        let signal = self
+            .messages
+            .iter_mut()
+            .find(|message| message.id == id && message.extended == extended)?
+            .signals
+            .iter_mut()
+            .find(|signal| signal.name == name)?;
+        signal.value_type = value_type;
+        Some(())
+    }
+}
+
+impl DbcSignal {
+    fn decode(&self, data: &[u8]) -> Option<f64> {
+        let raw = decode_raw(
+            data,
+            self.start,
+            self.length,
+            self.byte_order,
+            self.value_type,
+        )?;
+        Some(raw * self.factor + self.offset)
+    }
+}
+
+fn split_dbc_id(id: u32) -> (u32, bool) {
+    if id & DBC_EXTENDED_ID_FLAG != 0 {
+        (id & EXTENDED_ID_MASK, true)
+    } else {
+        (id, false)
+    }
+}
+
+fn parse_message(line: &str) -> Option<DbcMessage> {
+    // BO_ <id> <name>: <dlc> <transmitter>
+    let mut parts = line.split_whitespace();
+    let (id, extended) = split_dbc_id(parts.next()?.parse().ok()?);
+    let name = parts.next()?.trim_end_matches(':');
+
+    Some(DbcMessage {
+        id,
+        extended,
+        name: name.to_string(),
+        signals: Vec::new(),
+    })
+}
+
+/// Returns `Some(None)` for valid signals that can't be decoded, like multiplexed ones
+fn parse_signal(line: &str) -> Option<Option<DbcSignal>> {
+    // SG_ <name> [mux] : <start>|<length>@<order><sign> (<factor>,<offset>) [<min>|<max>] "<unit>" <receivers>
+    let (name, definition) = line.split_once(':')?;
+    let mut name_parts = name.split_whitespace();
+    let name = name_parts.next()?;
+    let multiplexed = name_parts.next().is_some();
+
+    let mut parts = definition.split_whitespace();
+    let (start, rest) = parts.next()?.split_once('|')?;
+    let (length, rest) = rest.split_once('@')?;
+    let mut rest = rest.chars();
+    let byte_order = match rest.next()? {
+        '0' => ByteOrder::BigEndian,
+        '1' => ByteOrder::LittleEndian,
+        _ => return None,
+    };
+    let value_type = match rest.next()? {
+        '+' => ValueType::Unsigned,
+        '-' => ValueType::Signed,
+        _ => return None,
+    };
+    let (factor, offset) = parts
+        .next()?
+        .strip_prefix('(')?
+        .strip_suffix(')')?
+        .split_once(',')?;
+
+    let length: u16 = length.parse().ok()?;
+    if multiplexed || length == 0 || length > 64 {
+        return Some(None);
+    }
+
+    Some(Some(DbcSignal {
+        name: name.to_string(),
+        start: start.parse().ok()?,
+        length,
+        byte_order,
+        value_type,
+        factor: factor.parse().ok()?,
+        offset: offset.parse().ok()?,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dbc::write_dbc;
+    use crate::frame;
+    use assert2::assert;
+
+    fn exported_database() -> DbcDatabase {
+        let mut dbc = String::new();
+        write_dbc(&mut dbc).unwrap();
+        DbcDatabase::parse(&dbc).unwrap()
+    }
+
+    #[test]
+    fn decode_little_endian_float() {
+        let database = exported_database();
+        let frame = frame!(0x100, [0x00, 0x00, 0x20, 0x41, 0x00, 0x00, 0x80, 0xBF]);
+        let message = database.decode(&frame).unwrap();
+        assert!(message.name == "PackAndPerriCurrent");
+        assert!(message.signals["PackCurrent"] == 10.0);
+        assert!(message.signals["PerriCurrent"] == -1.0);
+    }
+
+    #[test]
+    fn decode_big_endian_signed() {
+        let database = exported_database();
+        let frame = frame!(extended 0x0909, [0xFF, 0xFF, 0xFC, 0x18, 0x00, 0x64, 0xFF, 0x9C]);
+        let message = database.decode(&frame).unwrap();
+        assert!(message.name == "VescStatusMessage1");
+        assert!(message.signals["Rpm"] == -1000.0);
+        assert!((message.signals["TotalCurrent"] - 10.0).abs() < 1e-6);
+        assert!((message.signals["DutyCycle"] + 10.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn decode_bit_fields() {
+        let database = DbcDatabase::parse(
+            "BO_ 1911 Prototype: 2 Proto\n SG_ Flag : 3|1@1+ (1,0) [0|1] \"\" Vector__XXX\n SG_ Mux M : 0|2@1+ (1,0) [0|3] \"\" Vector__XXX\n SG_ Level : 8|8@1+ (0.5,-10) [0|0] \"V\" Vector__XXX\n",
+        )
+        .unwrap();
+        let frame = frame!(1911, [0b0000_1000, 40]);
+        let message = database.decode(&frame).unwrap();
+        assert!(message.signals.len() == 2);
+        assert!(message.signals["Flag"] == 1.0);
+        assert!(message.signals["Level"] == 10.0);
+    }
+
+    #[test]
+    fn signal_without_message() {
+        let result = DbcDatabase::parse(" SG_ Flag : 3|1@1+ (1,0) [0|1] \"\" Vector__XXX\n");
+        assert!(matches!(result, Err(DbcError::Parse { line: 1, .. })));
+    }
+}
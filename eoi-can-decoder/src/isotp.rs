@@ -0,0 +1,334 @@
+//! ISO-TP (ISO 15765-2) on classic CAN, for payloads longer than a frame like the records of the
+//! [`black_box`](crate::black_box) of the display firmware. Normal addressing: the sender and the
+//! receiver each send on their own ID, the receiver answers the first frame of a long payload with
+//! a flow control frame. Only what the black box needs: payloads up to 4095 bytes, frames padded
+//! to 8 bytes, no CAN FD.
+
+/// Longest payload with the 12 bit length of a first frame
+pub const MAX_PAYLOAD_LEN: usize = 4095;
+/// Unused bytes of a frame
+const PADDING: u8 = 0xCC;
+
+/// The data of an ISO-TP frame, always padded to 8 bytes
+pub type IsoTpFrameData = [u8; 8];
+
+/// What the receiver asks of the sender in a flow control frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FlowStatus {
+    ContinueToSend,
+    Wait,
+    /// The payload doesn't fit the buffer of the receiver, the sender gives up
+    Overflow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum IsoTpFrame<'a> {
+    /// A payload of up to 7 bytes
+    Single(&'a [u8]),
+    /// The start of a longer payload of `len` bytes
+    First { len: u16, data: &'a [u8] },
+    /// The next part, `index` counts from 1 and wraps after 15
+    Consecutive { index: u8, data: &'a [u8] },
+    FlowControl {
+        status: FlowStatus,
+        /// Consecutive frames before the next flow control, 0 for all of them
+        block_size: u8,
+        /// Minimum time between consecutive frames, see [`separation_time_us`]
+        separation_time: u8,
+    },
+}
+
+impl<'a> IsoTpFrame<'a> {
+    /// `None` for frames that aren't ISO-TP or are cut short
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        let pci = *data.first()?;
+        match pci >> 4 {
+            0 => {
+                let len = usize::from(pci & 0x0F);
+                (len > 0).then_some(())?;
+                Some(IsoTpFrame::Single(data.get(1..1 + len)?))
+            }
+            1 => {
+                let len = u16::from(pci & 0x0F) << 8 | u16::from(*data.get(1)?);
+                Some(IsoTpFrame::First {
+                    len,
+                    data: data.get(2..)?,
+                })
+            }
+            2 => Some(IsoTpFrame::Consecutive {
+                index: pci & 0x0F,
+                data: &data[1..],
+            }),
+            3 => Some(IsoTpFrame::FlowControl {
+                status: match pci & 0x0F {
+                    0 => FlowStatus::ContinueToSend,
+                    1 => FlowStatus::Wait,
+                    2 => FlowStatus::Overflow,
+                    _ => return None,
+                },
+                block_size: *data.get(1)?,
+                separation_time: *data.get(2)?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Minimum time in µs between consecutive frames of the separation time byte of a flow control
+/// frame. Reserved values mean the longest time, 127 ms
+pub fn separation_time_us(separation_time: u8) -> u32 {
+    match separation_time {
+        0x00..=0x7F => u32::from(separation_time) * 1000,
+        0xF1..=0xF9 => u32::from(separation_time - 0xF0) * 100,
+        _ => 127_000,
+    }
+}
+
+fn padded(bytes: &[&[u8]]) -> IsoTpFrameData {
+    let mut frame = [PADDING; 8];
+    let mut len = 0;
+    for part in bytes {
+        frame[len..len + part.len()].copy_from_slice(part);
+        len += part.len();
+    }
+    frame
+}
+
+/// A flow control frame of the receiver
+pub fn flow_control(status: FlowStatus, block_size: u8, separation_time: u8) -> IsoTpFrameData {
+    let status = match status {
+        FlowStatus::ContinueToSend => 0,
+        FlowStatus::Wait => 1,
+        FlowStatus::Overflow => 2,
+    };
+    padded(&[&[0x30 | status, block_size, separation_time]])
+}
+
+/// Splits a payload into its frames
+#[derive(Debug)]
+pub struct Segmenter<'a> {
+    payload: &'a [u8],
+    sent: usize,
+    index: u8,
+}
+
+impl<'a> Segmenter<'a> {
+    /// `None` when the payload is empty or longer than [`MAX_PAYLOAD_LEN`]
+    pub fn new(payload: &'a [u8]) -> Option<Self> {
+        (1..=MAX_PAYLOAD_LEN)
+            .contains(&payload.len())
+            .then_some(Self {
+                payload,
+                sent: 0,
+                index: 1,
+            })
+    }
+
+    /// Whether the payload needs more than a single frame, so the receiver sends flow control
+    /// after the first one
+    pub fn is_segmented(&self) -> bool {
+        self.payload.len() > 7
+    }
+
+    /// The single frame, or the first frame of a segmented payload
+    pub fn first(&mut self) -> IsoTpFrameData {
+        if !self.is_segmented() {
+            self.sent = self.payload.len();
+            return padded(&[&[self.payload.len() as u8], self.payload]);
+        }
+        self.sent = 6;
+        let len = self.payload.len() as u16;
+        padded(&[
+            &[0x10 | (len >> 8) as u8, len as u8],
+            &self.payload[..self.sent],
+        ])
+    }
+
+    /// Whether the whole payload was sent
+    pub fn is_done(&self) -> bool {
+        self.sent >= self.payload.len()
+    }
+
+    /// The next consecutive frame, `None` once the whole payload was sent
+    pub fn next_consecutive(&mut self) -> Option<IsoTpFrameData> {
+        if self.sent >= self.payload.len() {
+            return None;
+        }
+        let end = (self.sent + 7).min(self.payload.len());
+        let frame = padded(&[&[0x20 | self.index], &self.payload[self.sent..end]]);
+        self.sent = end;
+        self.index = (self.index + 1) & 0x0F;
+        Some(frame)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum IsoTpError {
+    /// A consecutive frame without a first frame, or a frame that isn't ISO-TP
+    Unexpected,
+    /// A consecutive frame was lost
+    WrongIndex { expected: u8, received: u8 },
+    /// The payload doesn't fit the buffer, the sender should get an overflow
+    TooLong(u16),
+}
+
+/// How far a payload was received
+#[derive(Debug, PartialEq, Eq)]
+pub enum Received<'a> {
+    /// The first frame of a segmented payload, the receiver sends flow control now
+    FlowControlNeeded,
+    Incomplete,
+    Complete(&'a [u8]),
+}
+
+/// Puts the frames of the sender back together into the payload, of up to `N` bytes
+#[derive(Debug, Default)]
+pub struct Reassembler<const N: usize> {
+    payload: heapless::Vec<u8, N>,
+    len: usize,
+    next_index: Option<u8>,
+}
+
+impl<const N: usize> Reassembler<N> {
+    pub const fn new() -> Self {
+        Self {
+            payload: heapless::Vec::new(),
+            len: 0,
+            next_index: None,
+        }
+    }
+
+    /// Take the next frame of the sender. A single or first frame starts over, flow control
+    /// frames are for the sender and not expected here
+    pub fn push(&mut self, data: &[u8]) -> Result<Received<'_>, IsoTpError> {
+        match IsoTpFrame::parse(data).ok_or(IsoTpError::Unexpected)? {
+            IsoTpFrame::Single(data) => {
+                self.next_index = None;
+                self.payload.clear();
+                self.payload
+                    .extend_from_slice(data)
+                    .map_err(|()| IsoTpError::TooLong(data.len() as u16))?;
+                Ok(Received::Complete(&self.payload))
+            }
+            IsoTpFrame::First { len, data } => {
+                self.next_index = None;
+                self.payload.clear();
+                if usize::from(len) > N {
+                    return Err(IsoTpError::TooLong(len));
+                }
+                self.len = usize::from(len);
+                // Can't fail, the payload fits
+                let _ = self
+                    .payload
+                    .extend_from_slice(&data[..data.len().min(self.len)]);
+                self.next_index = Some(1);
+                Ok(Received::FlowControlNeeded)
+            }
+            IsoTpFrame::Consecutive { index, data } => {
+                let expected = self.next_index.ok_or(IsoTpError::Unexpected)?;
+                if index != expected {
+                    self.next_index = None;
+                    return Err(IsoTpError::WrongIndex {
+                        expected,
+                        received: index,
+                    });
+                }
+                let missing = self.len - self.payload.len();
+                let _ = self
+                    .payload
+                    .extend_from_slice(&data[..data.len().min(missing)]);
+                if self.payload.len() < self.len {
+                    self.next_index = Some((index + 1) & 0x0F);
+                    return Ok(Received::Incomplete);
+                }
+                self.next_index = None;
+                Ok(Received::Complete(&self.payload))
+            }
+            IsoTpFrame::FlowControl { .. } => Err(IsoTpError::Unexpected),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+
+    #[test]
+    fn single_frame() {
+        let mut segmenter = Segmenter::new(&[0x01, 0x02, 0x03]).unwrap();
+        assert!(!segmenter.is_segmented());
+        let frame = segmenter.first();
+        assert!(frame == [0x03, 0x01, 0x02, 0x03, 0xCC, 0xCC, 0xCC, 0xCC]);
+        assert!(segmenter.next_consecutive().is_none());
+
+        let mut reassembler = Reassembler::<16>::new();
+        assert!(reassembler.push(&frame) == Ok(Received::Complete(&[0x01, 0x02, 0x03])));
+    }
+
+    #[test]
+    fn segmented_round_trip() {
+        let payload: [u8; 300] = core::array::from_fn(|index| index as u8);
+        let mut segmenter = Segmenter::new(&payload).unwrap();
+        let mut reassembler = Reassembler::<512>::new();
+
+        let first = segmenter.first();
+        assert!(first[..2] == [0x11, 0x2C]);
+        assert!(reassembler.push(&first) == Ok(Received::FlowControlNeeded));
+        let mut frames = 0;
+        while let Some(frame) = segmenter.next_consecutive() {
+            frames += 1;
+            match reassembler.push(&frame) {
+                Ok(Received::Complete(received)) => {
+                    assert!(received == payload.as_slice());
+                    assert!(segmenter.next_consecutive().is_none());
+                }
+                result => assert!(result == Ok(Received::Incomplete)),
+            }
+        }
+        // 6 bytes in the first frame, 7 in each of the others
+        assert!(frames == 42);
+    }
+
+    #[test]
+    fn lost_frame_and_overflow() {
+        let payload = [0xAB; 20];
+        let mut segmenter = Segmenter::new(&payload).unwrap();
+        let mut reassembler = Reassembler::<32>::new();
+        reassembler.push(&segmenter.first()).unwrap();
+        segmenter.next_consecutive();
+        let third = segmenter.next_consecutive().unwrap();
+        assert!(
+            reassembler.push(&third)
+                == Err(IsoTpError::WrongIndex {
+                    expected: 1,
+                    received: 2
+                })
+        );
+
+        let mut small = Reassembler::<8>::new();
+        assert!(
+            small.push(&Segmenter::new(&payload).unwrap().first()) == Err(IsoTpError::TooLong(20))
+        );
+    }
+
+    #[test]
+    fn flow_control_frames() {
+        let frame = flow_control(FlowStatus::ContinueToSend, 0, 0xF5);
+        let Some(IsoTpFrame::FlowControl {
+            status,
+            block_size,
+            separation_time,
+        }) = IsoTpFrame::parse(&frame)
+        else {
+            panic!("Not a flow control frame");
+        };
+        assert!(status == FlowStatus::ContinueToSend);
+        assert!(block_size == 0);
+        assert!(separation_time_us(separation_time) == 500);
+        assert!(separation_time_us(20) == 20_000);
+    }
+}
@@ -2,8 +2,23 @@
 
 use serde::Serialize;
 
+pub mod black_box;
+pub mod calibration;
 pub mod can_collector;
+pub mod can_encoder;
 pub mod can_frame;
+#[cfg(feature = "embedded-can-async")]
+pub mod can_receiver;
+pub mod current;
+pub mod dbc;
+#[cfg(feature = "dbc")]
+pub mod dbc_database;
+pub mod isotp;
+pub mod proto;
+pub mod protocol_doc;
+pub mod signals;
+pub mod time_sync;
+pub mod transmit_queue;
 
 #[derive(Debug, Serialize)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -17,6 +32,16 @@ pub enum EoiCanData {
     HeightSensors(HeightSensorData),
     GanMppt(GanMpptData),
     Temperature(TemperatureData),
+    PeripheralPower(PeripheralPower),
+    WaterSpeed(WaterSpeed),
+    Imu(ImuAttitude),
+    NodeIdentification(NodeIdentification),
+    DisplayHeartbeat(DisplayHeartbeat),
+    DisplayLog(DisplayLog),
+    DisplayBattery(DisplayBattery),
+    DisplayControl(DisplayControl),
+    DisplayAcknowledge(DisplayAcknowledge),
+    DisplayPanic(DisplayPanic),
 }
 
 #[derive(Debug, Serialize)]
@@ -27,6 +52,13 @@ pub enum GnssData {
     GnssLatitude(f64),
     GnssLongitude(f64),
     GnssDateTime(GnssDateTime),
+    /// Altitude above mean sea level in meters
+    GnssAltitude(f32),
+    GnssFixQuality(GnssFixQuality),
+    /// Offset of the race-local time to the UTC of [`GnssData::GnssDateTime`] in minutes
+    GnssUtcOffset(i16),
+    /// UTC in microseconds since the Unix epoch when the frame was sent, see [`time_sync`]
+    GnssTimeSync(u64),
 }
 
 #[derive(Debug, Serialize)]
@@ -39,6 +71,91 @@ pub struct GnssStatus {
 
 #[derive(Debug, Serialize)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct GnssFixQuality {
+    /// Fix mode like gpsd reports it: 0 unknown, 1 no fix, 2 2D fix, 3 3D fix
+    pub mode: u8,
+    /// Horizontal position error in m with 95 % confidence, the larger of the longitude and the
+    /// latitude error like gpsd estimates them. `None` when the receiver doesn't report it
+    pub horizontal_error: Option<f32>,
+}
+
+impl GnssDateTime {
+    /// The same moment shifted by `offset_minutes`, like from UTC to race-local time
+    pub fn with_offset(&self, offset_minutes: i16) -> Self {
+        const MINUTES_PER_DAY: i32 = 24 * 60;
+        let minutes = self.hours as i32 * 60 + self.minutes as i32 + offset_minutes as i32;
+        let mut date = (self.year, self.month, self.day);
+        let days = minutes.div_euclid(MINUTES_PER_DAY);
+        for _ in 0..days.abs() {
+            date = if days > 0 {
+                next_day(date)
+            } else {
+                previous_day(date)
+            };
+        }
+        let minutes = minutes.rem_euclid(MINUTES_PER_DAY);
+
+        Self {
+            year: date.0,
+            month: date.1,
+            day: date.2,
+            hours: (minutes / 60) as u8,
+            minutes: (minutes % 60) as u8,
+            seconds: self.seconds,
+        }
+    }
+
+    /// Seconds since the Unix epoch of a UTC time
+    pub fn unix_s(&self) -> i64 {
+        // Days from the civil date of Howard Hinnant, with the years starting in March so the
+        // leap day is the last one
+        let year = self.year as i64 - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let month_from_march = (self.month as i64 + 9) % 12;
+        let day_of_year = (153 * month_from_march + 2) / 5 + self.day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146_097 + day_of_era - 719_468;
+        days * 86_400 + self.hours as i64 * 3600 + self.minutes as i64 * 60 + self.seconds as i64
+    }
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => {
+            29
+        }
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+fn next_day((year, month, day): (u16, u8, u8)) -> (u16, u8, u8) {
+    if day < days_in_month(year, month) {
+        (year, month, day + 1)
+    } else if month < 12 {
+        (year, month + 1, 1)
+    } else {
+        (year + 1, 1, 1)
+    }
+}
+
+fn previous_day((year, month, day): (u16, u8, u8)) -> (u16, u8, u8) {
+    if day > 1 {
+        (year, month, day - 1)
+    } else if month > 1 {
+        (year, month - 1, days_in_month(year, month - 1))
+    } else {
+        (year - 1, 12, 31)
+    }
+}
+
+/// Raw horizontal error sent when the receiver doesn't report it
+pub const GNSS_HORIZONTAL_ERROR_UNKNOWN: u16 = 0xFFFF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct GnssDateTime {
     pub year: u16,
     pub month: u8,
@@ -68,7 +185,7 @@ pub struct ThrottleStatus {
     pub error: ThrottleErrors,
 }
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Default, Serialize, PartialEq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct ThrottleErrors {
     pub twi: ThrottleTwiErrors,
@@ -80,6 +197,38 @@ pub struct ThrottleErrors {
 }
 
 impl ThrottleErrors {
+    /// Error flags byte of the throttle status: bits 0-2 TWI error state, bits 3-7 single errors
+    pub fn from_bits(bits: u8) -> Self {
+        Self {
+            twi: (bits & 0b111).into(),
+            no_eeprom: bits & (1 << 3) != 0,
+            gain_clipping: bits & (1 << 4) != 0,
+            gain_invalid: bits & (1 << 5) != 0,
+            deadman_missing: bits & (1 << 6) != 0,
+            impedance_high: bits & (1 << 7) != 0,
+        }
+    }
+
+    /// Error flags byte as sent by the throttle, `None` for an unknown TWI error state
+    pub fn bits(&self) -> Option<u8> {
+        let twi = match self.twi {
+            ThrottleTwiErrors::NoError => 0,
+            ThrottleTwiErrors::BusFault => 1,
+            ThrottleTwiErrors::BusCaptureTimeout => 2,
+            ThrottleTwiErrors::SlaveResponseTimeout => 3,
+            ThrottleTwiErrors::SlaveNotReady => 4,
+            ThrottleTwiErrors::SlaveNAK => 5,
+            ThrottleTwiErrors::Unknown => return None,
+        };
+        Some(
+            twi | (self.no_eeprom as u8) << 3
+                | (self.gain_clipping as u8) << 4
+                | (self.gain_invalid as u8) << 5
+                | (self.deadman_missing as u8) << 6
+                | (self.impedance_high as u8) << 7,
+        )
+    }
+
     pub fn has_error(&self) -> bool {
         !matches!(self.twi, ThrottleTwiErrors::NoError)
             || self.no_eeprom
@@ -127,7 +276,7 @@ impl core::fmt::Display for ThrottleErrors {
     }
 }
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Default, Serialize, PartialEq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ThrottleTwiErrors {
     #[default]
@@ -175,9 +324,22 @@ pub enum ThrottleControlType {
     Unknown = 255,
 }
 
-/// Generates a node-ID-dispatched enum with a `from_node_id` constructor.
-/// Produces variants `Id0`…`Id{N-1}`, each wrapping the inner type.
-/// This gives clean JSON like `{"Id3": { … }}` for MQTT telemetry.
+impl core::fmt::Display for ThrottleControlType {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            ThrottleControlType::DutyCycle => "Duty cycle",
+            ThrottleControlType::FilteredDutyCycle => "Filtered duty cycle",
+            ThrottleControlType::Current => "Current",
+            ThrottleControlType::Rpm => "RPM",
+            ThrottleControlType::CurrentRelative => "Relative current",
+            ThrottleControlType::Unknown => "Unknown",
+        })
+    }
+}
+
+/// Generates a node-ID-dispatched enum with a `from_node_id` constructor and `into_parts` /
+/// `as_parts` accessors. Produces variants `Id0`…`Id{N-1}`, each wrapping the inner type. This
+/// gives clean JSON like `{"Id3": { … }}` for MQTT telemetry.
 ///
 /// Usage: `node_enum!(EnumName, InnerType, COUNT);`
 macro_rules! node_enum {
@@ -191,12 +353,27 @@ macro_rules! node_enum {
             }
 
             impl $name {
-                pub(crate) fn from_node_id(id: u8, inner: $inner) -> Option<Self> {
+                /// Wrap data of the given node, `None` when the node ID is out of range
+                pub fn from_node_id(id: u8, inner: $inner) -> Option<Self> {
                     match id {
                         #(N => Some(Self::Id~N(inner)),)*
                         _ => None,
                     }
                 }
+
+                /// Split into the node ID and the wrapped data
+                pub fn into_parts(self) -> (u8, $inner) {
+                    match self {
+                        #(Self::Id~N(inner) => (N, inner),)*
+                    }
+                }
+
+                /// Borrow the node ID and the wrapped data
+                pub fn as_parts(&self) -> (u8, &$inner) {
+                    match self {
+                        #(Self::Id~N(inner) => (N, inner),)*
+                    }
+                }
             }
         });
     };
@@ -370,10 +547,22 @@ pub enum EoiBattery {
     CellVoltages5_8(FourCellVoltages),
     CellVoltages9_12(FourCellVoltages),
     CellVoltages13_14PackAndStack(CellVoltages13_14PackAndStack),
+    /// Only sent by the BMS of a pack with 16 cells
+    CellVoltages15_16(TwoCellVoltages),
     TemperaturesAndStates(TemperaturesAndStates),
+    /// Only sent by a BMS with 8 temperature sensors
+    Temperatures5_8(FourTemperatures),
     BatteryUptime(BatteryUptime),
+    /// Sent by the BMS
+    ChargeControlStatus(ChargeControl),
+    /// Sent to the BMS to disable or enable charging
+    ChargeControlCommand(ChargeControl),
+    /// Only sent by a BMS which estimates the times itself
+    TimeEstimate(BmsTimeEstimate),
 }
 
+/// Currents in A, positive while charging like all currents of the decoder, see
+/// [`current::CurrentConvention`]
 #[derive(Debug, Serialize)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct PackAndPerriCurrent {
@@ -381,6 +570,8 @@ pub struct PackAndPerriCurrent {
     pub perri_current: f32,
 }
 
+/// Currents in A, positive while charging. The BMS sends the discharge current as a positive
+/// magnitude, it is negated so it is negative while discharging like the others
 #[derive(Debug, Serialize)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct ChargeAndDischargeCurrent {
@@ -410,6 +601,12 @@ pub struct CellVoltages13_14PackAndStack {
     pub stack_voltage: f32,     // u16 on CAN bus with a factor of 1000
 }
 
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TwoCellVoltages {
+    pub cell_voltage: [f32; 2], // u16 on CAN bus with a factor of 1000
+}
+
 #[derive(Debug, Serialize)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct TemperaturesAndStates {
@@ -420,6 +617,13 @@ pub struct TemperaturesAndStates {
     pub discharge_state: DischargeState,
 }
 
+/// Temperatures in °C of sensors 5 to 8, a sensor that isn't connected reads far out of range
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FourTemperatures {
+    pub temperatures: [i8; 4],
+}
+
 #[derive(Debug, Serialize, Default, PartialEq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum BatteryState {
@@ -509,9 +713,95 @@ pub struct BatteryUptime {
     pub uptime_ms: u32,
 }
 
+/// Raw time sent when the BMS has no estimate, like the time to full while discharging
+pub const BMS_TIME_UNKNOWN: u16 = 0xFFFF;
+
+/// The BMS's own estimate of the minutes until the pack is empty and until it is full
 #[derive(Debug, Serialize)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
-pub enum VescData {
+pub struct BmsTimeEstimate {
+    pub time_to_empty_min: Option<u16>,
+    pub time_to_full_min: Option<u16>,
+}
+
+/// Whether the BMS lets the battery be charged, the crew disables charging to stop the solar
+/// panels from overcharging a full battery
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ChargeControl {
+    pub charging_disabled: bool,
+}
+
+impl ChargeControl {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self {
+                charging_disabled: false,
+            }),
+            1 => Some(Self {
+                charging_disabled: true,
+            }),
+            _ => None,
+        }
+    }
+}
+
+// VESC CAN ID = (PacketID << 8) | ControllerID, controller IDs are configured in DecoderConfig
+
+/// A message of the VESC with the given controller ID. Serialized like the node enums, as
+/// `{"Id9": { … }}`, without an enum variant for every controller ID
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct VescData {
+    controller_id: u8,
+    message: VescMessage,
+}
+
+impl VescData {
+    /// Controller ID addressing all the VESCs, none of them sends with it
+    pub const BROADCAST_CONTROLLER_ID: u8 = 255;
+
+    /// Wrap a message of the given controller, `None` for the broadcast ID
+    pub fn from_node_id(controller_id: u8, message: VescMessage) -> Option<Self> {
+        (controller_id != Self::BROADCAST_CONTROLLER_ID).then_some(Self {
+            controller_id,
+            message,
+        })
+    }
+
+    /// Split into the controller ID and the message
+    pub fn into_parts(self) -> (u8, VescMessage) {
+        (self.controller_id, self.message)
+    }
+
+    /// Borrow the controller ID and the message
+    pub fn as_parts(&self) -> (u8, &VescMessage) {
+        (self.controller_id, &self.message)
+    }
+}
+
+impl Serialize for VescData {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        /// `Id` and the controller ID, like the variants of the node enums
+        struct NodeName(u8);
+
+        impl Serialize for NodeName {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(&format_args!("Id{}", self.0))
+            }
+        }
+
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(&NodeName(self.controller_id), &self.message)?;
+        map.end()
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum VescMessage {
     StatusMessage1 {
         rpm: i32,
         total_current: f32,
@@ -535,6 +825,206 @@ pub enum VescData {
         input_voltage: f32,
         tachometer: i32,
     },
+    StatusMessage6 {
+        adc1: f32,
+        adc2: f32,
+        adc3: f32,
+        ppm: f32,
+    },
+    FirmwareVersion {
+        major: u8,
+        minor: u8,
+    },
+    Ping {
+        sender_id: u8,
+    },
+    Pong {
+        sender_id: u8,
+        hw_type: VescHwType,
+    },
+    Fault {
+        fault: VescFault,
+    },
+}
+
+#[derive(Debug, Default, Serialize, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum VescHwType {
+    Vesc,
+    VescBms,
+    CustomModule,
+    #[default]
+    Unknown,
+}
+
+impl From<u8> for VescHwType {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::Vesc,
+            1 => Self::VescBms,
+            2 => Self::CustomModule,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// Fault code of the motor controller, `mc_fault_code` of the VESC firmware
+#[derive(Debug, Default, Clone, Copy, Serialize, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum VescFault {
+    #[default]
+    None,
+    OverVoltage,
+    UnderVoltage,
+    Drv,
+    AbsOverCurrent,
+    OverTempFet,
+    OverTempMotor,
+    GateDriverOverVoltage,
+    GateDriverUnderVoltage,
+    McuUnderVoltage,
+    BootingFromWatchdogReset,
+    EncoderSpi,
+    EncoderSincosBelowMinAmplitude,
+    EncoderSincosAboveMaxAmplitude,
+    FlashCorruption,
+    HighOffsetCurrentSensor1,
+    HighOffsetCurrentSensor2,
+    HighOffsetCurrentSensor3,
+    UnbalancedCurrents,
+    Brk,
+    ResolverLot,
+    ResolverDos,
+    ResolverLos,
+    FlashCorruptionAppCfg,
+    FlashCorruptionMcCfg,
+    EncoderNoMagnet,
+    EncoderMagnetTooStrong,
+    PhaseFilter,
+    EncoderFault,
+    LvOutputFault,
+    /// Code added by a later firmware
+    Unknown(u8),
+}
+
+impl VescFault {
+    /// Whether the controller reports a fault, it stops driving the motor until it's cleared
+    pub fn is_fault(&self) -> bool {
+        *self != Self::None
+    }
+
+    /// Short name fitting the motor section of the display
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::None => "None",
+            Self::OverVoltage => "Over voltage",
+            Self::UnderVoltage => "Under voltage",
+            Self::Drv => "DRV",
+            Self::AbsOverCurrent => "Over current",
+            Self::OverTempFet => "FET hot",
+            Self::OverTempMotor => "Motor hot",
+            Self::GateDriverOverVoltage => "Gate over volt",
+            Self::GateDriverUnderVoltage => "Gate under volt",
+            Self::McuUnderVoltage => "MCU under volt",
+            Self::BootingFromWatchdogReset => "Watchdog reset",
+            Self::EncoderSpi => "Encoder SPI",
+            Self::EncoderSincosBelowMinAmplitude => "Sincos low",
+            Self::EncoderSincosAboveMaxAmplitude => "Sincos high",
+            Self::FlashCorruption => "Flash corrupt",
+            Self::HighOffsetCurrentSensor1 => "Sensor 1 offset",
+            Self::HighOffsetCurrentSensor2 => "Sensor 2 offset",
+            Self::HighOffsetCurrentSensor3 => "Sensor 3 offset",
+            Self::UnbalancedCurrents => "Unbalanced",
+            Self::Brk => "BRK",
+            Self::ResolverLot => "Resolver LOT",
+            Self::ResolverDos => "Resolver DOS",
+            Self::ResolverLos => "Resolver LOS",
+            Self::FlashCorruptionAppCfg => "App cfg corrupt",
+            Self::FlashCorruptionMcCfg => "MC cfg corrupt",
+            Self::EncoderNoMagnet => "No magnet",
+            Self::EncoderMagnetTooStrong => "Magnet strong",
+            Self::PhaseFilter => "Phase filter",
+            Self::EncoderFault => "Encoder",
+            Self::LvOutputFault => "LV output",
+            Self::Unknown(_) => "Unknown",
+        }
+    }
+}
+
+impl From<u8> for VescFault {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::None,
+            1 => Self::OverVoltage,
+            2 => Self::UnderVoltage,
+            3 => Self::Drv,
+            4 => Self::AbsOverCurrent,
+            5 => Self::OverTempFet,
+            6 => Self::OverTempMotor,
+            7 => Self::GateDriverOverVoltage,
+            8 => Self::GateDriverUnderVoltage,
+            9 => Self::McuUnderVoltage,
+            10 => Self::BootingFromWatchdogReset,
+            11 => Self::EncoderSpi,
+            12 => Self::EncoderSincosBelowMinAmplitude,
+            13 => Self::EncoderSincosAboveMaxAmplitude,
+            14 => Self::FlashCorruption,
+            15 => Self::HighOffsetCurrentSensor1,
+            16 => Self::HighOffsetCurrentSensor2,
+            17 => Self::HighOffsetCurrentSensor3,
+            18 => Self::UnbalancedCurrents,
+            19 => Self::Brk,
+            20 => Self::ResolverLot,
+            21 => Self::ResolverDos,
+            22 => Self::ResolverLos,
+            23 => Self::FlashCorruptionAppCfg,
+            24 => Self::FlashCorruptionMcCfg,
+            25 => Self::EncoderNoMagnet,
+            26 => Self::EncoderMagnetTooStrong,
+            27 => Self::PhaseFilter,
+            28 => Self::EncoderFault,
+            29 => Self::LvOutputFault,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<VescFault> for u8 {
+    fn from(fault: VescFault) -> Self {
+        match fault {
+            VescFault::None => 0,
+            VescFault::OverVoltage => 1,
+            VescFault::UnderVoltage => 2,
+            VescFault::Drv => 3,
+            VescFault::AbsOverCurrent => 4,
+            VescFault::OverTempFet => 5,
+            VescFault::OverTempMotor => 6,
+            VescFault::GateDriverOverVoltage => 7,
+            VescFault::GateDriverUnderVoltage => 8,
+            VescFault::McuUnderVoltage => 9,
+            VescFault::BootingFromWatchdogReset => 10,
+            VescFault::EncoderSpi => 11,
+            VescFault::EncoderSincosBelowMinAmplitude => 12,
+            VescFault::EncoderSincosAboveMaxAmplitude => 13,
+            VescFault::FlashCorruption => 14,
+            VescFault::HighOffsetCurrentSensor1 => 15,
+            VescFault::HighOffsetCurrentSensor2 => 16,
+            VescFault::HighOffsetCurrentSensor3 => 17,
+            VescFault::UnbalancedCurrents => 18,
+            VescFault::Brk => 19,
+            VescFault::ResolverLot => 20,
+            VescFault::ResolverDos => 21,
+            VescFault::ResolverLos => 22,
+            VescFault::FlashCorruptionAppCfg => 23,
+            VescFault::FlashCorruptionMcCfg => 24,
+            VescFault::EncoderNoMagnet => 25,
+            VescFault::EncoderMagnetTooStrong => 26,
+            VescFault::PhaseFilter => 27,
+            VescFault::EncoderFault => 28,
+            VescFault::LvOutputFault => 29,
+            VescFault::Unknown(value) => value,
+        }
+    }
 }
 
 // --- RudderController ---
@@ -646,17 +1136,533 @@ pub enum TemperatureData {
     RudderController(i16),
 }
 
+// --- Peripheral Power Monitor ---
+
+/// First CAN ID of the peripheral power monitor, the channel of the consumer is added to it
+pub const PERIPHERAL_POWER_ID: u16 = 0x240;
+
+/// What is connected to a channel of the peripheral power monitor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum PeripheralConsumer {
+    Pump = 0,
+    Electronics = 1,
+    Display = 2,
+    Radio = 3,
+}
+
+impl PeripheralConsumer {
+    pub const ALL: [PeripheralConsumer; 4] = [
+        PeripheralConsumer::Pump,
+        PeripheralConsumer::Electronics,
+        PeripheralConsumer::Display,
+        PeripheralConsumer::Radio,
+    ];
+
+    pub fn from_channel(channel: u8) -> Option<Self> {
+        Self::ALL.get(usize::from(channel)).copied()
+    }
+
+    /// Short name for the display
+    pub fn label(self) -> &'static str {
+        match self {
+            PeripheralConsumer::Pump => "Pump",
+            PeripheralConsumer::Electronics => "Elec",
+            PeripheralConsumer::Display => "Disp",
+            PeripheralConsumer::Radio => "Radio",
+        }
+    }
+}
+
+/// Current of one consumer, negative while it draws from the battery like
+/// [`PackAndPerriCurrent::perri_current`], which is the sum of all of them
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PeripheralPower {
+    pub consumer: PeripheralConsumer,
+    /// A
+    pub current: f32,
+}
+
+// --- Water Speed Sensor ---
+
+/// Speed through the water of the paddle wheel under the hull, its Hall-effect sensor counts the
+/// turns of the wheel. The speed over ground of the GNSS minus this is the current along the course
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct WaterSpeed {
+    /// km/h, calibrated by the sensor
+    pub speed_kmh: f32,
+    /// Pulses of the Hall-effect sensor per second, for calibrating the sensor
+    pub pulse_frequency: f32,
+}
+
+// --- IMU ---
+
+/// Heave rate sent by an IMU which doesn't estimate the heave
+pub const IMU_HEAVE_RATE_UNKNOWN: i16 = i16::MIN;
+
+/// Attitude of the hull, for trimming the foils
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ImuAttitude {
+    /// Degrees, positive with the bow up
+    pub pitch: f32,
+    /// Degrees, positive heeling to starboard
+    pub roll: f32,
+    /// m/s, positive rising, `None` when the IMU doesn't estimate the heave
+    pub heave_rate: Option<f32>,
+}
+
+// --- Node identification ---
+
+/// CAN ID of the identification of the first [`NodeType`], the others follow in its order
+pub const NODE_IDENTIFICATION_ID: u16 = 0x270;
+/// Seconds between the identifications a node sends after the one at boot
+pub const NODE_IDENTIFICATION_INTERVAL_S: u32 = 60;
+
+/// Kind of node on the bus, one CAN ID of [`NodeIdentification`] each
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum NodeType {
+    Display = 0,
+    Datalogger = 1,
+    Throttle = 2,
+    Bms = 3,
+    Gnss = 4,
+    RudderController = 5,
+    HeightSensors = 6,
+    PowerMonitor = 7,
+    WaterSpeedSensor = 8,
+    Imu = 9,
+}
+
+impl NodeType {
+    pub const ALL: [NodeType; 10] = [
+        NodeType::Display,
+        NodeType::Datalogger,
+        NodeType::Throttle,
+        NodeType::Bms,
+        NodeType::Gnss,
+        NodeType::RudderController,
+        NodeType::HeightSensors,
+        NodeType::PowerMonitor,
+        NodeType::WaterSpeedSensor,
+        NodeType::Imu,
+    ];
+
+    pub fn from_index(index: u8) -> Option<Self> {
+        Self::ALL.get(usize::from(index)).copied()
+    }
+
+    /// Name for the display and the MQTT topics
+    pub fn label(self) -> &'static str {
+        match self {
+            NodeType::Display => "Display",
+            NodeType::Datalogger => "Datalogger",
+            NodeType::Throttle => "Throttle",
+            NodeType::Bms => "BMS",
+            NodeType::Gnss => "GNSS",
+            NodeType::RudderController => "Rudder",
+            NodeType::HeightSensors => "Height sensors",
+            NodeType::PowerMonitor => "Power monitor",
+            NodeType::WaterSpeedSensor => "Water speed",
+            NodeType::Imu => "IMU",
+        }
+    }
+}
+
+/// Firmware of a node, sent at boot and every [`NODE_IDENTIFICATION_INTERVAL_S`], so a session
+/// records which firmware every node ran
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NodeIdentification {
+    pub node: NodeType,
+    pub version_major: u8,
+    pub version_minor: u8,
+    pub version_patch: u8,
+    /// Built from a tree with uncommitted changes
+    pub dirty: bool,
+    /// First 8 hex digits of the commit the firmware was built from, serialized like `"1a2b3c4d"`
+    #[serde(serialize_with = "serialize_git_hash")]
+    pub git_hash: u32,
+}
+
+fn serialize_git_hash<S: serde::Serializer>(hash: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+    use core::fmt::Write;
+
+    let mut text = heapless::String::<8>::new();
+    let _ = write!(text, "{:08x}", hash);
+    serializer.serialize_str(&text)
+}
+
+/// Like `1.4.2 (1a2b3c4d-dirty)`
+impl core::fmt::Display for NodeIdentification {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{}.{}.{} ({:08x}{})",
+            self.version_major,
+            self.version_minor,
+            self.version_patch,
+            self.git_hash,
+            if self.dirty { "-dirty" } else { "" }
+        )
+    }
+}
+
+// --- Display ---
+
+/// Sent by the display every second, so a stuck or rebooting display shows up on the bus
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DisplayHeartbeat {
+    pub uptime_s: u32,
+    pub firmware_version_major: u8,
+    pub firmware_version_minor: u8,
+    pub firmware_version_patch: u8,
+    /// Seconds since the screen was last refreshed, saturates at 255
+    pub last_refresh_age_s: u8,
+}
+
+/// Bytes of text in one [`DisplayLog`] frame
+pub const DISPLAY_LOG_TEXT_LEN: usize = 6;
+
+/// Part of a log record of the display firmware, longer records are split over consecutive frames
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DisplayLog {
+    pub level: LogLevel,
+    /// Counts every frame and wraps around, a gap means frames were lost
+    pub sequence: u8,
+    /// The record continues in the frame with the next sequence number
+    pub continued: bool,
+    pub text: heapless::String<DISPLAY_LOG_TEXT_LEN>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum LogLevel {
+    Trace = 0,
+    Debug = 1,
+    Info = 2,
+    Warn = 3,
+    Error = 4,
+}
+
+impl LogLevel {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(LogLevel::Trace),
+            1 => Some(LogLevel::Debug),
+            2 => Some(LogLevel::Info),
+            3 => Some(LogLevel::Warn),
+            4 => Some(LogLevel::Error),
+            _ => None,
+        }
+    }
+}
+
+/// Bytes of text in one [`DisplayPanic`] frame
+pub const DISPLAY_PANIC_TEXT_LEN: usize = 7;
+
+/// Part of the panic message of the display firmware, sent once after the restart the panic caused.
+/// The message is split over consecutive frames numbered from 0
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DisplayPanic {
+    /// Index of the part, wraps at 128
+    pub part: u8,
+    /// No more parts follow
+    pub last: bool,
+    pub text: heapless::String<DISPLAY_PANIC_TEXT_LEN>,
+}
+
+/// Battery of the PiSugar powering the framebuffer display, sent by the datalogger so the other
+/// displays can show it too
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DisplayBattery {
+    /// Percent, with a resolution of 0.01 %
+    pub state_of_charge: f32,
+    pub charging: bool,
+}
+
+/// Sent by the chase car through the datalogger, to tell the driver something on the displays
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DisplayControl {
+    /// Switch the displays to a page, 0 is the main page and 1 the diagnostics page
+    Page(u8),
+    Message(DisplayMessage),
+    /// Switch the displays to a profile, which decides the pages and how they rotate: 0 race,
+    /// 1 test bench, 2 charging and 3 diagnostics
+    Profile(u8),
+    /// End of the race as second of the UTC day, the displays count down to it. `None` clears the
+    /// countdown
+    RaceEnd(Option<u32>),
+    /// Start the trip counters, the laps and the race time over, like before a new race. The
+    /// e-paper display also forgets the ones it kept over resets
+    TripReset,
+}
+
+/// Race end of [`DisplayControl::RaceEnd`] clearing the countdown
+pub const RACE_END_NONE: u32 = u32::MAX;
+
+/// Bytes of text in one [`DisplayMessage`] frame
+pub const DISPLAY_MESSAGE_TEXT_LEN: usize = 7;
+/// Number of frames a message can be split over, every part has its own CAN ID
+pub const DISPLAY_MESSAGE_PARTS: usize = 4;
+/// Bytes of text in a whole message
+pub const DISPLAY_MESSAGE_LEN: usize = DISPLAY_MESSAGE_TEXT_LEN * DISPLAY_MESSAGE_PARTS;
+/// Largest message ID, the top bit of its byte marks the last part
+pub const DISPLAY_MESSAGE_ID_MAX: u8 = 0x7F;
+
+/// Part of a short message shown in a banner, like "PIT NOW". An empty message clears the banner
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DisplayMessage {
+    /// Same for all parts of a message, so parts of two messages aren't mixed up. Up to
+    /// [`DISPLAY_MESSAGE_ID_MAX`], the encoder refuses larger ones
+    pub message_id: u8,
+    /// Index of the part, given by the CAN ID
+    pub part: u8,
+    /// No more parts follow
+    pub last: bool,
+    pub text: heapless::String<DISPLAY_MESSAGE_TEXT_LEN>,
+}
+
+impl DisplayMessage {
+    /// Split `text` into the parts to send, what doesn't fit into [`DISPLAY_MESSAGE_LEN`] bytes is
+    /// cut off. Parts are only split between characters, so they may be shorter than
+    /// [`DISPLAY_MESSAGE_TEXT_LEN`].
+    pub fn split(
+        message_id: u8,
+        text: &str,
+    ) -> heapless::Vec<DisplayMessage, DISPLAY_MESSAGE_PARTS> {
+        let mut parts: heapless::Vec<DisplayMessage, DISPLAY_MESSAGE_PARTS> = heapless::Vec::new();
+        let mut part = heapless::String::new();
+        for character in text.chars() {
+            if part.push(character).is_ok() {
+                continue;
+            }
+            let message = DisplayMessage {
+                message_id,
+                part: parts.len() as u8,
+                last: false,
+                text: core::mem::take(&mut part),
+            };
+            if parts.push(message).is_err() {
+                break;
+            }
+            // Can't fail, a character is at most 4 bytes long
+            let _ = part.push(character);
+        }
+        if !parts.is_full() {
+            let _ = parts.push(DisplayMessage {
+                message_id,
+                part: parts.len() as u8,
+                last: false,
+                text: part,
+            });
+        }
+        if let Some(last) = parts.last_mut() {
+            last.last = true;
+        }
+        parts
+    }
+}
+
+/// Sent by the display when the pilot acknowledged a [`DisplayMessage`] with the button, so the
+/// chase car knows it was read
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DisplayAcknowledge {
+    pub message_id: u8,
+}
+
+/// Maximum number of VESC controllers a single decoder can follow
+pub const MAX_VESC_CONTROLLERS: usize = 4;
+/// MPPTs the node IDs of their messages can address, 3 bits of the CAN ID
+pub const MAX_MPPT_DEVICES: usize = 8;
+
+/// Boat specific settings for the decoder
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecoderConfig {
+    vesc_controller_ids: heapless::Vec<u8, MAX_VESC_CONTROLLERS>,
+    permissive_dlc: bool,
+    mppt_devices: u8,
+    calibration: calibration::Calibration,
+}
+
+impl DecoderConfig {
+    /// Controller ID the VESC on the boat has been configured with
+    pub const DEFAULT_VESC_CONTROLLER_ID: u8 = 9;
+
+    /// Follow the given VESC controller IDs instead of the default one,
+    /// returns `None` when more than `MAX_VESC_CONTROLLERS` IDs are given
+    pub fn with_vesc_controller_ids(ids: &[u8]) -> Option<Self> {
+        Some(Self {
+            vesc_controller_ids: heapless::Vec::from_slice(ids).ok()?,
+            ..Self::default()
+        })
+    }
+
+    /// Also decode frames longer than their message, like from a node with a newer protocol that
+    /// appended signals. Too short frames are never decoded
+    pub fn with_permissive_dlc(self, permissive_dlc: bool) -> Self {
+        Self {
+            permissive_dlc,
+            ..self
+        }
+    }
+
+    /// Only decode the messages of the MPPTs with IDs below `count`, up to [`MAX_MPPT_DEVICES`],
+    /// so other nodes may use the IDs of the MPPTs the boat doesn't have
+    pub fn with_mppt_devices(self, count: u8) -> Self {
+        Self {
+            mppt_devices: count.min(MAX_MPPT_DEVICES as u8),
+            ..self
+        }
+    }
+
+    /// Correct the signals of the calibration before decoding them, like a known bias of a sensor
+    pub fn with_calibration(self, calibration: calibration::Calibration) -> Self {
+        Self {
+            calibration,
+            ..self
+        }
+    }
+
+    pub fn vesc_controller_ids(&self) -> &[u8] {
+        &self.vesc_controller_ids
+    }
+
+    pub fn mppt_devices(&self) -> u8 {
+        self.mppt_devices
+    }
+
+    pub fn calibration(&self) -> &calibration::Calibration {
+        &self.calibration
+    }
+}
+
+impl Default for DecoderConfig {
+    fn default() -> Self {
+        let mut vesc_controller_ids = heapless::Vec::new();
+        // Capacity is at least one, so this can not fail
+        let _ = vesc_controller_ids.push(Self::DEFAULT_VESC_CONTROLLER_ID);
+        Self {
+            vesc_controller_ids,
+            permissive_dlc: false,
+            mppt_devices: MAX_MPPT_DEVICES as u8,
+            calibration: calibration::Calibration::default(),
+        }
+    }
+}
+
+/// Why a frame of a known message wasn't decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DecodeError {
+    /// Less data than the message has, like a truncated frame
+    TooShort { expected: u8, len: u8 },
+    /// More data than the message has, decoded with [`DecoderConfig::with_permissive_dlc`]
+    TooLong { expected: u8, len: u8 },
+}
+
+impl core::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            DecodeError::TooShort { expected, len } => {
+                write!(f, "{} bytes of data, expected {}", len, expected)
+            }
+            DecodeError::TooLong { expected, len } => {
+                write!(f, "{} bytes of data, expected at most {}", len, expected)
+            }
+        }
+    }
+}
+
+impl core::error::Error for DecodeError {}
+
+/// Decode a CAN frame using the default decoder configuration
 pub fn parse_eoi_can_data(can_frame: &can_frame::CanFrame) -> Option<EoiCanData> {
-    let id = match can_frame.id {
-        embedded_can::Id::Standard(id) => id.as_raw() as u32,
-        embedded_can::Id::Extended(id) => id.as_raw(),
+    parse_eoi_can_data_with_config(can_frame, &DecoderConfig::default())
+}
+
+/// Decode a CAN frame, `None` for unknown frames and frames with another length than their
+/// message
+pub fn parse_eoi_can_data_with_config(
+    can_frame: &can_frame::CanFrame,
+    config: &DecoderConfig,
+) -> Option<EoiCanData> {
+    try_parse_eoi_can_data_with_config(can_frame, config)
+        .ok()
+        .flatten()
+}
+
+/// Decode a CAN frame, `Ok(None)` for unknown frames. Frames of a known message have to be as long
+/// as the message in the signal table, else they are truncated or of another protocol version
+pub fn try_parse_eoi_can_data_with_config(
+    can_frame: &can_frame::CanFrame,
+    config: &DecoderConfig,
+) -> Result<Option<EoiCanData>, DecodeError> {
+    check_dlc(can_frame, config)?;
+    if config.calibration.is_empty() {
+        return Ok(parse_frame(can_frame, config));
+    }
+    let mut calibrated = can_frame.clone();
+    config.calibration.apply(&mut calibrated);
+    Ok(parse_frame(&calibrated, config))
+}
+
+fn check_dlc(can_frame: &can_frame::CanFrame, config: &DecoderConfig) -> Result<(), DecodeError> {
+    let (id, extended) = match can_frame.id {
+        embedded_can::Id::Standard(id) => (id.as_raw() as u32, false),
+        embedded_can::Id::Extended(id) => (id.as_raw(), true),
+    };
+    // Messages missing in the table, like the replies of the VESC, are checked while decoding
+    let Some((message, _)) = signals::find_message(id, extended) else {
+        return Ok(());
+    };
+    let len = can_frame.data.len() as u8;
+    if len < message.min_dlc {
+        Err(DecodeError::TooShort {
+            expected: message.min_dlc,
+            len,
+        })
+    } else if len > message.dlc && !config.permissive_dlc {
+        Err(DecodeError::TooLong {
+            expected: message.dlc,
+            len,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+fn parse_frame(can_frame: &can_frame::CanFrame, config: &DecoderConfig) -> Option<EoiCanData> {
+    let (id, extended) = match can_frame.id {
+        embedded_can::Id::Standard(id) => (id.as_raw() as u32, false),
+        embedded_can::Id::Extended(id) => (id.as_raw(), true),
     };
     let data = &can_frame.data;
 
-    const MPPT_MAX_DEVICES: u32 = 8;
+    if let Some(vesc) = parse_vesc_data(id, data, config) {
+        return Some(EoiCanData::Vesc(vesc));
+    }
+    if let Some(command) = parse_vesc_command(id, extended, data, config) {
+        return Some(EoiCanData::Throttle(command));
+    }
+
     const MPPT_BASE_ADDRESS: u32 = 0x700;
     const MPPT_INFO_FIELDS: u32 = 16;
-    const MPPT_STOP_ADDRESS: u32 = MPPT_BASE_ADDRESS + (MPPT_MAX_DEVICES * MPPT_INFO_FIELDS) - 1;
+    const MPPT_STOP_ADDRESS: u32 =
+        MPPT_BASE_ADDRESS + (MAX_MPPT_DEVICES as u32 * MPPT_INFO_FIELDS) - 1;
 
     match id {
         0x10 => Some(EoiCanData::RudderController(RudderControllerData::Servo(
@@ -701,6 +1707,83 @@ pub fn parse_eoi_can_data(can_frame: &can_frame::CanFrame) -> Option<EoiCanData>
         0x211 => Some(EoiCanData::Temperature(TemperatureData::RudderController(
             bytes_le_to_i16(data.get(0..2)?)?,
         ))),
+        0x240..=0x243 => Some(EoiCanData::PeripheralPower(PeripheralPower {
+            consumer: PeripheralConsumer::from_channel(
+                (id - u32::from(PERIPHERAL_POWER_ID)) as u8,
+            )?,
+            current: bytes_le_to_f32(data.get(0..4)?)?,
+        })),
+        0x250 => Some(EoiCanData::WaterSpeed(WaterSpeed {
+            speed_kmh: bytes_le_to_f32(data.get(0..4)?)?,
+            pulse_frequency: bytes_le_to_f32(data.get(4..8)?)?,
+        })),
+        0x260 => Some(EoiCanData::Imu(ImuAttitude {
+            pitch: bytes_le_to_i16(data.get(0..2)?)? as f32 / 100.0,
+            roll: bytes_le_to_i16(data.get(2..4)?)? as f32 / 100.0,
+            heave_rate: match bytes_le_to_i16(data.get(4..6)?)? {
+                IMU_HEAVE_RATE_UNKNOWN => None,
+                heave_rate => Some(heave_rate as f32 / 1000.0),
+            },
+        })),
+        0x270..=0x27F => Some(EoiCanData::NodeIdentification(NodeIdentification {
+            node: NodeType::from_index((id - u32::from(NODE_IDENTIFICATION_ID)) as u8)?,
+            version_major: *data.first()?,
+            version_minor: *data.get(1)?,
+            version_patch: *data.get(2)?,
+            dirty: *data.get(3)? & 1 != 0,
+            git_hash: bytes_be_to_u32(data.get(4..8)?)?,
+        })),
+        0x230 => Some(EoiCanData::DisplayHeartbeat(DisplayHeartbeat {
+            uptime_s: bytes_le_to_u32(data.get(0..4)?)?,
+            firmware_version_major: *data.get(4)?,
+            firmware_version_minor: *data.get(5)?,
+            firmware_version_patch: *data.get(6)?,
+            last_refresh_age_s: *data.get(7)?,
+        })),
+        0x231 => Some(EoiCanData::DisplayLog(DisplayLog {
+            level: LogLevel::from_u8(data.first()? & 0x07)?,
+            continued: data.first()? & 0x80 != 0,
+            sequence: *data.get(1)?,
+            text: heapless::String::try_from(core::str::from_utf8(data.get(2..)?).ok()?).ok()?,
+        })),
+        0x232 => Some(EoiCanData::DisplayBattery(DisplayBattery {
+            state_of_charge: bytes_le_to_u16(data.get(0..2)?)? as f32 / 100.0,
+            charging: *data.get(2)? != 0,
+        })),
+        0x233 => Some(EoiCanData::DisplayControl(DisplayControl::Page(
+            *data.first()?,
+        ))),
+        0x234..=0x237 => Some(EoiCanData::DisplayControl(DisplayControl::Message(
+            DisplayMessage {
+                message_id: data.first()? & 0x7F,
+                part: (id - 0x234) as u8,
+                last: data.first()? & 0x80 != 0,
+                text: heapless::String::try_from(
+                    core::str::from_utf8(data.get(1..)?)
+                        .ok()?
+                        .trim_end_matches('\0'),
+                )
+                .ok()?,
+            },
+        ))),
+        0x238 => Some(EoiCanData::DisplayAcknowledge(DisplayAcknowledge {
+            message_id: data.first()? & 0x7F,
+        })),
+        0x239 => Some(EoiCanData::DisplayControl(DisplayControl::Profile(
+            *data.first()?,
+        ))),
+        0x23A => {
+            let end_s = u32::from_le_bytes(data.get(0..4)?.try_into().ok()?);
+            Some(EoiCanData::DisplayControl(DisplayControl::RaceEnd(
+                (end_s != RACE_END_NONE).then_some(end_s),
+            )))
+        }
+        0x23B => Some(EoiCanData::DisplayPanic(DisplayPanic {
+            part: data.first()? & 0x7F,
+            last: data.first()? & 0x80 != 0,
+            text: heapless::String::try_from(core::str::from_utf8(data.get(1..)?).ok()?).ok()?,
+        })),
+        0x23E => Some(EoiCanData::DisplayControl(DisplayControl::TripReset)),
         0x100 => Some(EoiCanData::EoiBattery(EoiBattery::PackAndPerriCurrent(
             PackAndPerriCurrent {
                 pack_current: bytes_le_to_f32(data.get(0..4)?)?,
@@ -779,7 +1862,40 @@ pub fn parse_eoi_can_data(can_frame: &can_frame::CanFrame) -> Option<EoiCanData>
                 uptime_ms: bytes_le_to_u32(data.get(0..4)?)?,
             },
         ))),
-
+        // Extended 0x10A and 0x10B are VESC SET_CURRENT for controllers 10 and 11
+        0x10A if !extended => Some(EoiCanData::EoiBattery(EoiBattery::ChargeControlStatus(
+            ChargeControl::from_u8(*data.first()?)?,
+        ))),
+        0x10B if !extended => Some(EoiCanData::EoiBattery(EoiBattery::ChargeControlCommand(
+            ChargeControl::from_u8(*data.first()?)?,
+        ))),
+        // Extended 0x10C is VESC SET_CURRENT for controller 12
+        0x10C if !extended => Some(EoiCanData::EoiBattery(EoiBattery::CellVoltages15_16(
+            TwoCellVoltages {
+                cell_voltage: [
+                    bytes_le_to_u16(data.get(0..2)?)? as f32 / 1000.0,
+                    bytes_le_to_u16(data.get(2..4)?)? as f32 / 1000.0,
+                ],
+            },
+        ))),
+        // Extended 0x10D is VESC SET_CURRENT for controller 13
+        0x10D if !extended => Some(EoiCanData::EoiBattery(EoiBattery::TimeEstimate(
+            BmsTimeEstimate {
+                time_to_empty_min: bms_time(bytes_le_to_u16(data.get(0..2)?)?),
+                time_to_full_min: bms_time(bytes_le_to_u16(data.get(2..4)?)?),
+            },
+        ))),
+        // Extended 0x10E is VESC SET_CURRENT for controller 14
+        0x10E if !extended => Some(EoiCanData::EoiBattery(EoiBattery::Temperatures5_8(
+            FourTemperatures {
+                temperatures: [
+                    *data.first()? as i8,
+                    *data.get(1)? as i8,
+                    *data.get(2)? as i8,
+                    *data.get(3)? as i8,
+                ],
+            },
+        ))),
         0x200 => Some(EoiCanData::Gnss(GnssData::GnssStatus(GnssStatus {
             fix: *data.first()?,
             sats: *data.get(1)?,
@@ -803,8 +1919,26 @@ pub fn parse_eoi_can_data(can_frame: &can_frame::CanFrame) -> Option<EoiCanData>
             minutes: *data.get(5)?,
             seconds: *data.get(6)?,
         }))),
+        0x205 => Some(EoiCanData::Gnss(GnssData::GnssAltitude(bytes_le_to_f32(
+            data.get(0..4)?,
+        )?))),
+        0x206 => Some(EoiCanData::Gnss(GnssData::GnssFixQuality(GnssFixQuality {
+            mode: *data.first()?,
+            horizontal_error: match bytes_le_to_u16(data.get(1..3)?)? {
+                GNSS_HORIZONTAL_ERROR_UNKNOWN => None,
+                error => Some(error as f32 / 100.0),
+            },
+        }))),
+        0x207 => Some(EoiCanData::Gnss(GnssData::GnssUtcOffset(bytes_le_to_i16(
+            data.get(0..2)?,
+        )?))),
+        0x208 => Some(EoiCanData::Gnss(GnssData::GnssTimeSync(bytes_le_to_u64(
+            data.get(0..8)?,
+        )?))),
 
-        MPPT_BASE_ADDRESS..MPPT_STOP_ADDRESS => {
+        MPPT_BASE_ADDRESS..MPPT_STOP_ADDRESS
+            if (id - MPPT_BASE_ADDRESS) / MPPT_INFO_FIELDS < u32::from(config.mppt_devices) =>
+        {
             let mppt_id = ((id >> 4) & 0x7) as u8;
             let info_field = id as u8 & 0xF;
             let channel = match info_field >> 1 {
@@ -845,38 +1979,6 @@ pub fn parse_eoi_can_data(can_frame: &can_frame::CanFrame) -> Option<EoiCanData>
             )?))
         }
 
-        0x0909 => Some(EoiCanData::Vesc(VescData::StatusMessage1 {
-            rpm: bytes_be_to_i32(data.get(0..4)?)?,
-            total_current: bytes_be_to_i16(data.get(4..6)?)? as f32 / 10.0,
-            duty_cycle: bytes_be_to_i16(data.get(6..8)?)? as f32 / 10.0,
-        })),
-        0x0E09 => Some(EoiCanData::Vesc(VescData::StatusMessage2 {
-            amp_hours_used: bytes_be_to_u32(data.get(0..4)?)? as f32 / 10000.0,
-            amp_hours_generated: bytes_be_to_u32(data.get(4..8)?)? as f32 / 10000.0,
-        })),
-        0x0F09 => Some(EoiCanData::Vesc(VescData::StatusMessage3 {
-            watt_hours_used: bytes_be_to_u32(data.get(0..4)?)? as f32 / 10000.0,
-            watt_hours_generated: bytes_be_to_u32(data.get(4..8)?)? as f32 / 10000.0,
-        })),
-        0x1009 => Some(EoiCanData::Vesc(VescData::StatusMessage4 {
-            fet_temp: bytes_be_to_i16(data.get(0..2)?)? as f32 / 10.0,
-            motor_temp: bytes_be_to_i16(data.get(2..4)?)? as f32 / 10.0,
-            total_input_current: bytes_be_to_i16(data.get(4..6)?)? as f32 / 10.0,
-            current_pid_position: bytes_be_to_i16(data.get(6..8)?)? as f32 / 50.0,
-        })),
-        0x1B09 => Some(EoiCanData::Vesc(VescData::StatusMessage5 {
-            input_voltage: bytes_be_to_i16(data.get(4..6)?)? as f32 / 10.0,
-            tachometer: bytes_be_to_i32(data.get(0..4)?)?,
-        })),
-        0x0009 => Some(EoiCanData::Throttle(ThrottleData::ToVescDutyCycle(
-            bytes_be_to_i32(data.get(0..4)?)? as f32 / 1000.0,
-        ))),
-        0x0109 => Some(EoiCanData::Throttle(ThrottleData::ToVescCurrent(
-            bytes_be_to_i32(data.get(0..4)?)? as f32 / 1000.0,
-        ))),
-        0x0309 => Some(EoiCanData::Throttle(ThrottleData::ToVescRpm(
-            bytes_be_to_i32(data.get(0..4)?)? as f32 / 1000.0,
-        ))),
         0x400..=0x4FF => {
             const GAN_MPPT_DEFAULT_NODE_ID: u8 = 64;
             let node_id = (id >> 4) as u8 - GAN_MPPT_DEFAULT_NODE_ID;
@@ -915,14 +2017,7 @@ pub fn parse_eoi_can_data(can_frame: &can_frame::CanFrame) -> Option<EoiCanData>
                 raw_angle: bytes_be_to_i16(data.get(2..4)?)?,
                 raw_deadmen: bytes_be_to_i16(data.get(4..6)?)?,
                 gain: *data.get(6)?,
-                error: ThrottleErrors {
-                    twi: (*data.get(7)? & 0b111).into(),
-                    no_eeprom: *data.get(7)? & (1 << 3) != 0,
-                    gain_clipping: *data.get(7)? & (1 << 4) != 0,
-                    gain_invalid: *data.get(7)? & (1 << 5) != 0,
-                    deadman_missing: *data.get(7)? & (1 << 6) != 0,
-                    impedance_high: *data.get(7)? & (1 << 7) != 0,
-                },
+                error: ThrottleErrors::from_bits(*data.get(7)?),
             }))),
             6 => Some(EoiCanData::Throttle(ThrottleData::Config(ThrottleConfig {
                 control_type: match *data.first()? {
@@ -942,8 +2037,147 @@ pub fn parse_eoi_can_data(can_frame: &can_frame::CanFrame) -> Option<EoiCanData>
     }
 }
 
+fn parse_vesc_data(id: u32, data: &[u8], config: &DecoderConfig) -> Option<VescData> {
+    const CAN_PACKET_PROCESS_SHORT_BUFFER: u32 = 8;
+    const CAN_PACKET_STATUS: u32 = 9;
+    const CAN_PACKET_STATUS_2: u32 = 14;
+    const CAN_PACKET_STATUS_3: u32 = 15;
+    const CAN_PACKET_STATUS_4: u32 = 16;
+    const CAN_PACKET_PING: u32 = 17;
+    const CAN_PACKET_PONG: u32 = 18;
+    const CAN_PACKET_STATUS_5: u32 = 27;
+    const CAN_PACKET_STATUS_6: u32 = 58;
+    const COMM_FW_VERSION: u8 = 0;
+    const COMM_GET_VALUES_SELECTIVE: u8 = 50;
+    /// Bit of the fault code in the mask of COMM_GET_VALUES_SELECTIVE
+    const SELECTIVE_FAULT: u32 = 1 << 15;
+
+    let packet_id = id >> 8;
+    let target_id = id as u8;
+
+    let (controller_id, message) = match packet_id {
+        CAN_PACKET_STATUS => (
+            target_id,
+            VescMessage::StatusMessage1 {
+                rpm: bytes_be_to_i32(data.get(0..4)?)?,
+                total_current: bytes_be_to_i16(data.get(4..6)?)? as f32 / 10.0,
+                duty_cycle: bytes_be_to_i16(data.get(6..8)?)? as f32 / 10.0,
+            },
+        ),
+        CAN_PACKET_STATUS_2 => (
+            target_id,
+            VescMessage::StatusMessage2 {
+                amp_hours_used: bytes_be_to_u32(data.get(0..4)?)? as f32 / 10000.0,
+                amp_hours_generated: bytes_be_to_u32(data.get(4..8)?)? as f32 / 10000.0,
+            },
+        ),
+        CAN_PACKET_STATUS_3 => (
+            target_id,
+            VescMessage::StatusMessage3 {
+                watt_hours_used: bytes_be_to_u32(data.get(0..4)?)? as f32 / 10000.0,
+                watt_hours_generated: bytes_be_to_u32(data.get(4..8)?)? as f32 / 10000.0,
+            },
+        ),
+        CAN_PACKET_STATUS_4 => (
+            target_id,
+            VescMessage::StatusMessage4 {
+                fet_temp: bytes_be_to_i16(data.get(0..2)?)? as f32 / 10.0,
+                motor_temp: bytes_be_to_i16(data.get(2..4)?)? as f32 / 10.0,
+                total_input_current: bytes_be_to_i16(data.get(4..6)?)? as f32 / 10.0,
+                current_pid_position: bytes_be_to_i16(data.get(6..8)?)? as f32 / 50.0,
+            },
+        ),
+        CAN_PACKET_STATUS_5 => (
+            target_id,
+            VescMessage::StatusMessage5 {
+                input_voltage: bytes_be_to_i16(data.get(4..6)?)? as f32 / 10.0,
+                tachometer: bytes_be_to_i32(data.get(0..4)?)?,
+            },
+        ),
+        CAN_PACKET_STATUS_6 => (
+            target_id,
+            VescMessage::StatusMessage6 {
+                adc1: bytes_be_to_i16(data.get(0..2)?)? as f32 / 1000.0,
+                adc2: bytes_be_to_i16(data.get(2..4)?)? as f32 / 1000.0,
+                adc3: bytes_be_to_i16(data.get(4..6)?)? as f32 / 1000.0,
+                ppm: bytes_be_to_i16(data.get(6..8)?)? as f32 / 1000.0,
+            },
+        ),
+        // Ping addressed to the VESC, the payload holds the ID of the node asking
+        CAN_PACKET_PING => (
+            target_id,
+            VescMessage::Ping {
+                sender_id: *data.first()?,
+            },
+        ),
+        // Pong is addressed to whoever sent the ping, so only the sender tells us which VESC it is
+        CAN_PACKET_PONG => (
+            *data.first()?,
+            VescMessage::Pong {
+                sender_id: *data.first()?,
+                hw_type: (*data.get(1)?).into(),
+            },
+        ),
+        // Short buffer reply from the VESC carrying the COMM_FW_VERSION command
+        CAN_PACKET_PROCESS_SHORT_BUFFER if *data.get(2)? == COMM_FW_VERSION => (
+            *data.first()?,
+            VescMessage::FirmwareVersion {
+                major: *data.get(3)?,
+                minor: *data.get(4)?,
+            },
+        ),
+        // Short buffer reply to COMM_GET_VALUES_SELECTIVE asking for the fault code only, which
+        // the controller broadcasts while its status messages don't carry it
+        CAN_PACKET_PROCESS_SHORT_BUFFER
+            if *data.get(2)? == COMM_GET_VALUES_SELECTIVE
+                && bytes_be_to_u32(data.get(3..7)?)? == SELECTIVE_FAULT =>
+        {
+            (
+                *data.first()?,
+                VescMessage::Fault {
+                    fault: (*data.get(7)?).into(),
+                },
+            )
+        }
+        _ => return None,
+    };
+
+    if !config.vesc_controller_ids().contains(&controller_id) {
+        return None;
+    }
+
+    VescData::from_node_id(controller_id, message)
+}
+
+/// Commands of the throttle to the VESC, addressed to one of the controllers of the config
+fn parse_vesc_command(
+    id: u32,
+    extended: bool,
+    data: &[u8],
+    config: &DecoderConfig,
+) -> Option<ThrottleData> {
+    const CAN_PACKET_SET_DUTY: u32 = 0;
+    const CAN_PACKET_SET_CURRENT: u32 = 1;
+    const CAN_PACKET_SET_RPM: u32 = 3;
+
+    if !extended || !config.vesc_controller_ids().contains(&(id as u8)) {
+        return None;
+    }
+    let value = || Some(bytes_be_to_i32(data.get(0..4)?)? as f32 / 1000.0);
+    match id >> 8 {
+        CAN_PACKET_SET_DUTY => Some(ThrottleData::ToVescDutyCycle(value()?)),
+        CAN_PACKET_SET_CURRENT => Some(ThrottleData::ToVescCurrent(value()?)),
+        CAN_PACKET_SET_RPM => Some(ThrottleData::ToVescRpm(value()?)),
+        _ => None,
+    }
+}
+
 // Helper functions now return Option<T> instead of panicking
 
+fn bms_time(minutes: u16) -> Option<u16> {
+    (minutes != BMS_TIME_UNKNOWN).then_some(minutes)
+}
+
 fn bytes_le_to_u16(bytes: &[u8]) -> Option<u16> {
     let arr: [u8; 2] = bytes.try_into().ok()?;
     Some(u16::from_le_bytes(arr))
@@ -974,6 +2208,11 @@ fn bytes_le_to_f64(bytes: &[u8]) -> Option<f64> {
     Some(f64::from_le_bytes(arr))
 }
 
+fn bytes_le_to_u64(bytes: &[u8]) -> Option<u64> {
+    let arr: [u8; 8] = bytes.try_into().ok()?;
+    Some(u64::from_le_bytes(arr))
+}
+
 fn bytes_le_to_i16(bytes: &[u8]) -> Option<i16> {
     let arr: [u8; 2] = bytes.try_into().ok()?;
     Some(i16::from_le_bytes(arr))
@@ -987,23 +2226,21 @@ fn bytes_be_to_i32(bytes: &[u8]) -> Option<i32> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::frame;
     use assert2::assert;
-    use embedded_can::StandardId;
 
     const PERRI_CURRENT: f32 = -0.2421;
     const CHARGE_CURRENT: f32 = 9.9765;
     const DISCHARGE_CURRENT: f32 = -17.5270;
 
-    // This sum seems a bit odd, but it is the result of the test data
+    // All currents are charge positive, see `current`. The recorded pack current is the sum with
+    // the magnitude of the discharge current instead of the net current, which would subtract it
     // TODO: investigate if this is a bug in the test data / battery
     const PACK_CURRENT: f32 = CHARGE_CURRENT + (-DISCHARGE_CURRENT) + PERRI_CURRENT;
 
     #[test]
     fn pack_and_perri_current() {
-        let can_frame = can_frame::CanFrame::from_encoded(
-            embedded_can::Id::Standard(StandardId::new(0x100).unwrap()),
-            &0x5817DA41EBF577BE_u64.to_be_bytes(),
-        );
+        let can_frame = frame!(0x100, [0x58, 0x17, 0xDA, 0x41, 0xEB, 0xF5, 0x77, 0xBE]);
 
         let data = parse_eoi_can_data(&can_frame).unwrap();
         let data = if let EoiCanData::EoiBattery(EoiBattery::PackAndPerriCurrent(data)) = data {
@@ -1017,10 +2254,7 @@ mod tests {
 
     #[test]
     fn charge_and_discharge_current() {
-        let can_frame = can_frame::CanFrame::from_encoded(
-            embedded_can::Id::Standard(StandardId::new(0x101).unwrap()),
-            &0xE89F1F4150378C41_u64.to_be_bytes(),
-        );
+        let can_frame = frame!(0x101, [0xE8, 0x9F, 0x1F, 0x41, 0x50, 0x37, 0x8C, 0x41]);
 
         let data = parse_eoi_can_data(&can_frame).unwrap();
         let data = if let EoiCanData::EoiBattery(EoiBattery::ChargeAndDischargeCurrent(data)) = data
@@ -1035,10 +2269,7 @@ mod tests {
 
     #[test]
     fn soc_error_flags_and_balancing() {
-        let can_frame = can_frame::CanFrame::from_encoded(
-            embedded_can::Id::Standard(StandardId::new(0x102).unwrap()),
-            &0x2526000000000000_u64.to_be_bytes(),
-        );
+        let can_frame = frame!(0x102, [0x25, 0x26, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
 
         let data = parse_eoi_can_data(&can_frame).unwrap();
         let data = if let EoiCanData::EoiBattery(EoiBattery::SocErrorFlagsAndBalancing(data)) = data
@@ -1054,10 +2285,7 @@ mod tests {
 
     #[test]
     fn cell_voltages_1_4() {
-        let can_frame = can_frame::CanFrame::from_encoded(
-            embedded_can::Id::Standard(StandardId::new(0x103).unwrap()),
-            &0x36102C102D103710_u64.to_be_bytes(),
-        );
+        let can_frame = frame!(0x103, [0x36, 0x10, 0x2C, 0x10, 0x2D, 0x10, 0x37, 0x10]);
 
         let data = parse_eoi_can_data(&can_frame).unwrap();
         let data = if let EoiCanData::EoiBattery(EoiBattery::CellVoltages1_4(data)) = data {
@@ -1073,10 +2301,7 @@ mod tests {
 
     #[test]
     fn cell_voltages_5_8() {
-        let can_frame = can_frame::CanFrame::from_encoded(
-            embedded_can::Id::Standard(StandardId::new(0x104).unwrap()),
-            &0x34103A1030103410_u64.to_be_bytes(),
-        );
+        let can_frame = frame!(0x104, [0x34, 0x10, 0x3A, 0x10, 0x30, 0x10, 0x34, 0x10]);
 
         let data = parse_eoi_can_data(&can_frame).unwrap();
         let data = if let EoiCanData::EoiBattery(EoiBattery::CellVoltages5_8(data)) = data {
@@ -1092,10 +2317,7 @@ mod tests {
 
     #[test]
     fn cell_voltages_9_12() {
-        let can_frame = can_frame::CanFrame::from_encoded(
-            embedded_can::Id::Standard(StandardId::new(0x105).unwrap()),
-            &0x3810391038103410_u64.to_be_bytes(),
-        );
+        let can_frame = frame!(0x105, [0x38, 0x10, 0x39, 0x10, 0x38, 0x10, 0x34, 0x10]);
 
         let data = parse_eoi_can_data(&can_frame).unwrap();
         let data = if let EoiCanData::EoiBattery(EoiBattery::CellVoltages9_12(data)) = data {
@@ -1111,10 +2333,7 @@ mod tests {
 
     #[test]
     fn cell_voltages_13_14_pack_and_stack() {
-        let can_frame = can_frame::CanFrame::from_encoded(
-            embedded_can::Id::Standard(StandardId::new(0x106).unwrap()),
-            &0x39103110C0DA0EE2_u64.to_be_bytes(),
-        );
+        let can_frame = frame!(0x106, [0x39, 0x10, 0x31, 0x10, 0xC0, 0xDA, 0x0E, 0xE2]);
 
         let data = parse_eoi_can_data(&can_frame).unwrap();
         let data =
@@ -1129,12 +2348,61 @@ mod tests {
         assert!(data.stack_voltage == 57.87);
     }
 
+    #[test]
+    fn charge_control_only_from_standard_frames() {
+        let can_frame = frame!(0x10A, [0x01]);
+        assert!(matches!(
+            parse_eoi_can_data(&can_frame),
+            Some(EoiCanData::EoiBattery(EoiBattery::ChargeControlStatus(_)))
+        ));
+
+        // VESC SET_CURRENT for controller 10
+        let can_frame = frame!(extended 0x10A, [0x00, 0x00, 0x03, 0xE8]);
+        assert!(!matches!(
+            parse_eoi_can_data(&can_frame),
+            Some(EoiCanData::EoiBattery(_))
+        ));
+    }
+
+    #[test]
+    fn cell_voltages_15_16() {
+        let can_frame = frame!(0x10C, [0x39, 0x10, 0x31, 0x10]);
+
+        let data = parse_eoi_can_data(&can_frame).unwrap();
+        let EoiCanData::EoiBattery(EoiBattery::CellVoltages15_16(data)) = data else {
+            panic!("Unexpected data type");
+        };
+        assert!(data.cell_voltage == [4.153, 4.145]);
+
+        // VESC SET_CURRENT for controller 12 doesn't make a 16 cell pack
+        let can_frame = frame!(extended 0x10C, [0x00, 0x00, 0x03, 0xE8]);
+        assert!(!matches!(
+            parse_eoi_can_data(&can_frame),
+            Some(EoiCanData::EoiBattery(_))
+        ));
+    }
+
+    #[test]
+    fn temperatures_5_8() {
+        let can_frame = frame!(0x10E, [0x1E, 0x1F, 0xF6, 0x80]);
+
+        let data = parse_eoi_can_data(&can_frame).unwrap();
+        let EoiCanData::EoiBattery(EoiBattery::Temperatures5_8(data)) = data else {
+            panic!("Unexpected data type");
+        };
+        assert!(data.temperatures == [30, 31, -10, -128]);
+
+        // VESC SET_CURRENT for controller 14 doesn't add temperature sensors
+        let can_frame = frame!(extended 0x10E, [0x00, 0x00, 0x03, 0xE8]);
+        assert!(!matches!(
+            parse_eoi_can_data(&can_frame),
+            Some(EoiCanData::EoiBattery(_))
+        ));
+    }
+
     #[test]
     fn temperatures_and_states() {
-        let can_frame = can_frame::CanFrame::from_encoded(
-            embedded_can::Id::Standard(StandardId::new(0x107).unwrap()),
-            &0x2424262836060303_u64.to_be_bytes(),
-        );
+        let can_frame = frame!(0x107, [0x24, 0x24, 0x26, 0x28, 0x36, 0x06, 0x03, 0x03]);
 
         let data = parse_eoi_can_data(&can_frame).unwrap();
         let data = if let EoiCanData::EoiBattery(EoiBattery::TemperaturesAndStates(data)) = data {
@@ -1153,11 +2421,48 @@ mod tests {
     }
 
     #[test]
-    fn battery_uptime() {
-        let can_frame = can_frame::CanFrame::from_encoded(
-            embedded_can::Id::Standard(StandardId::new(0x108).unwrap()),
-            &0x6CB0223B_u32.to_be_bytes(),
+    fn frames_of_another_length_are_rejected() {
+        let config = DecoderConfig::default();
+        // Truncated, the states would be read from whatever the bytes are missing
+        let truncated = frame!(0x107, [0x24, 0x24, 0x26, 0x28, 0x36, 0x06]);
+        assert!(
+            try_parse_eoi_can_data_with_config(&truncated, &config).err()
+                == Some(DecodeError::TooShort {
+                    expected: 8,
+                    len: 6
+                })
+        );
+        assert!(parse_eoi_can_data(&truncated).is_none());
+
+        let longer = frame!(0x108, [0x6C, 0xB0, 0x22, 0x3B, 0x01]);
+        assert!(
+            try_parse_eoi_can_data_with_config(&longer, &config).err()
+                == Some(DecodeError::TooLong {
+                    expected: 4,
+                    len: 5
+                })
         );
+        let permissive = config.clone().with_permissive_dlc(true);
+        let Ok(Some(EoiCanData::EoiBattery(EoiBattery::BatteryUptime(_)))) =
+            try_parse_eoi_can_data_with_config(&longer, &permissive)
+        else {
+            panic!("Unexpected data type");
+        };
+        assert!(try_parse_eoi_can_data_with_config(&truncated, &permissive).is_err());
+
+        // Text of any length, and frames the table doesn't know
+        let log = frame!(0x231, [0x01, 0x00, b'O', b'K']);
+        assert!(try_parse_eoi_can_data_with_config(&log, &config).is_ok_and(|data| data.is_some()));
+        let unknown = frame!(0x7FF, [0x01]);
+        assert!(matches!(
+            try_parse_eoi_can_data_with_config(&unknown, &config),
+            Ok(None)
+        ));
+    }
+
+    #[test]
+    fn battery_uptime() {
+        let can_frame = frame!(0x108, [0x6C, 0xB0, 0x22, 0x3B]);
 
         let data = parse_eoi_can_data(&can_frame).unwrap();
         let data = if let EoiCanData::EoiBattery(EoiBattery::BatteryUptime(data)) = data {
@@ -1168,12 +2473,44 @@ mod tests {
         assert!(data.uptime_ms == 992129132);
     }
 
+    #[test]
+    fn display_panic() {
+        let can_frame = frame!(0x23B, [0x82, b'a', b't', b' ', b'4', b'2']);
+        let Some(EoiCanData::DisplayPanic(panic)) = parse_eoi_can_data(&can_frame) else {
+            panic!("Unexpected data type");
+        };
+        assert!(panic.part == 2);
+        assert!(panic.last);
+        assert!(panic.text == "at 42");
+
+        // Not even the flags
+        assert!(parse_eoi_can_data(&frame!(0x23B, [])).is_none());
+    }
+
+    #[test]
+    fn bms_time_estimate() {
+        // 95 minutes to empty, no time to full while discharging
+        let can_frame = frame!(0x10D, [0x5F, 0x00, 0xFF, 0xFF]);
+        let Some(EoiCanData::EoiBattery(EoiBattery::TimeEstimate(data))) =
+            parse_eoi_can_data(&can_frame)
+        else {
+            panic!("Unexpected data type");
+        };
+        assert!(data.time_to_empty_min == Some(95));
+        assert!(data.time_to_full_min.is_none());
+
+        // VESC SET_CURRENT for controller 13
+        let can_frame = frame!(extended 0x10D, [0x00, 0x00, 0x03, 0xE8]);
+        assert!(!matches!(
+            parse_eoi_can_data(&can_frame),
+            Some(EoiCanData::EoiBattery(_))
+        ));
+    }
+
     #[test]
     fn servo_rudder_setpoint() {
-        let can_frame = can_frame::CanFrame::from_encoded(
-            embedded_can::Id::Standard(StandardId::new(0x10).unwrap()),
-            &[0xE8, 0x03], // 1000 little-endian
-        );
+        // 1000 little-endian
+        let can_frame = frame!(0x10, [0xE8, 0x03]);
         let data = parse_eoi_can_data(&can_frame).unwrap();
         let EoiCanData::RudderController(RudderControllerData::Servo(ServoData::Setpoint(
             setpoint,
@@ -1186,10 +2523,8 @@ mod tests {
 
     #[test]
     fn servo_rudder_command_initialize() {
-        let can_frame = can_frame::CanFrame::from_encoded(
-            embedded_can::Id::Standard(StandardId::new(0x21).unwrap()),
-            &[0x00], // Initialize
-        );
+        // Initialize
+        let can_frame = frame!(0x21, [0x00]);
         let data = parse_eoi_can_data(&can_frame).unwrap();
         let EoiCanData::RudderController(RudderControllerData::Servo(ServoData::Command(command))) =
             data
@@ -1201,10 +2536,8 @@ mod tests {
 
     #[test]
     fn servo_rudder_status() {
-        let can_frame = can_frame::CanFrame::from_encoded(
-            embedded_can::Id::Standard(StandardId::new(0x20).unwrap()),
-            &[0x01, 0xD0, 0x07], // Operational, setpoint=2000 little-endian
-        );
+        // Operational, setpoint=2000 little-endian
+        let can_frame = frame!(0x20, [0x01, 0xD0, 0x07]);
         let data = parse_eoi_can_data(&can_frame).unwrap();
         let EoiCanData::RudderController(RudderControllerData::Servo(ServoData::Status(status))) =
             data
@@ -1217,10 +2550,8 @@ mod tests {
 
     #[test]
     fn height_sensor_front_left() {
-        let can_frame = can_frame::CanFrame::from_encoded(
-            embedded_can::Id::Standard(StandardId::new(0x11).unwrap()),
-            &[0x02, 0x2C, 0x01], // Operational, value=300 little-endian
-        );
+        // Operational, value=300 little-endian
+        let can_frame = frame!(0x11, [0x02, 0x2C, 0x01]);
         let data = parse_eoi_can_data(&can_frame).unwrap();
         let EoiCanData::HeightSensors(HeightSensorData::FrontLeft(status)) = data else {
             panic!("Unexpected data type");
@@ -1231,10 +2562,8 @@ mod tests {
 
     #[test]
     fn height_sensor_front_right() {
-        let can_frame = can_frame::CanFrame::from_encoded(
-            embedded_can::Id::Standard(StandardId::new(0x12).unwrap()),
-            &[0x02, 0x2C, 0x01], // Operational, value=300 little-endian
-        );
+        // Operational, value=300 little-endian
+        let can_frame = frame!(0x12, [0x02, 0x2C, 0x01]);
         let data = parse_eoi_can_data(&can_frame).unwrap();
         let EoiCanData::HeightSensors(HeightSensorData::FrontRight(status)) = data else {
             panic!("Unexpected data type");
@@ -1254,10 +2583,7 @@ mod tests {
         // Output voltage: 48.00 V -> raw = 4800 = 0x12C0
         // Output current: 1.5 A  -> raw = 3000 = 0x0BB8
         let raw: u64 = 0x07081B5812C00BB8;
-        let can_frame = can_frame::CanFrame::from_encoded(
-            embedded_can::Id::Standard(StandardId::new(0x400).unwrap()),
-            &raw.to_be_bytes(),
-        );
+        let can_frame = frame!(0x400, raw.to_be_bytes());
 
         let data = parse_eoi_can_data(&can_frame).unwrap();
         let data = if let EoiCanData::GanMppt(GanMpptData::Id0(GanMpptPacket::Power(data))) = data {
@@ -1275,10 +2601,7 @@ mod tests {
     fn gan_mppt_status() {
         // Mode=1 (CIV), Fault=0 (OK), Enabled=1, BoardTemp=25, HeatSinkTemp=40
         let raw: [u8; 5] = [0x01, 0x00, 0x01, 25, 40];
-        let can_frame = can_frame::CanFrame::from_encoded(
-            embedded_can::Id::Standard(StandardId::new(0x401).unwrap()),
-            &raw,
-        );
+        let can_frame = frame!(0x401, raw);
 
         let data = parse_eoi_can_data(&can_frame).unwrap();
         let data = if let EoiCanData::GanMppt(GanMpptData::Id0(GanMpptPacket::Status(data))) = data
@@ -1298,10 +2621,7 @@ mod tests {
     fn gan_mppt_sweep_data() {
         // Index=5, Current=2.0 A -> raw=4000=0x0FA0, Voltage=20.00 V -> raw=2000=0x07D0
         let raw: [u8; 5] = [0x05, 0x0F, 0xA0, 0x07, 0xD0];
-        let can_frame = can_frame::CanFrame::from_encoded(
-            embedded_can::Id::Standard(StandardId::new(0x402).unwrap()),
-            &raw,
-        );
+        let can_frame = frame!(0x402, raw);
 
         let data = parse_eoi_can_data(&can_frame).unwrap();
         let data =
@@ -1319,12 +2639,279 @@ mod tests {
     fn gan_mppt_node_id_offset() {
         // Hardware offset 3: node_id = 64+3 = 67, CAN ID = (67 << 4) | 0 = 0x430
         let raw: u64 = 0x07081B5812C00BB8;
-        let can_frame = can_frame::CanFrame::from_encoded(
-            embedded_can::Id::Standard(StandardId::new(0x430).unwrap()),
-            &raw.to_be_bytes(),
-        );
+        let can_frame = frame!(0x430, raw.to_be_bytes());
 
         let data = parse_eoi_can_data(&can_frame).unwrap();
         assert!(matches!(data, EoiCanData::GanMppt(GanMpptData::Id3(_))));
     }
+
+    // VESC frames use extended IDs: (packet id << 8) | controller id, controller id = 9
+
+    fn vesc_message(data: EoiCanData) -> (u8, VescMessage) {
+        let EoiCanData::Vesc(vesc) = data else {
+            panic!("Unexpected data type");
+        };
+        vesc.into_parts()
+    }
+
+    #[test]
+    fn vesc_status_message_6() {
+        // ADC1=1.5 V, ADC2=0.25 V, ADC3=-0.1 V, PPM=0.75
+        let can_frame = frame!(extended 0x3A09, [0x05, 0xDC, 0x00, 0xFA, 0xFF, 0x9C, 0x02, 0xEE]);
+        let data = parse_eoi_can_data(&can_frame).unwrap();
+        let (
+            9,
+            VescMessage::StatusMessage6 {
+                adc1,
+                adc2,
+                adc3,
+                ppm,
+            },
+        ) = vesc_message(data)
+        else {
+            panic!("Unexpected data type");
+        };
+        assert!((adc1 - 1.5).abs() < 0.001);
+        assert!((adc2 - 0.25).abs() < 0.001);
+        assert!((adc3 - -0.1).abs() < 0.001);
+        assert!((ppm - 0.75).abs() < 0.001);
+    }
+
+    #[test]
+    fn vesc_ping() {
+        let can_frame = frame!(extended 0x1109, [0x42]);
+        let data = parse_eoi_can_data(&can_frame).unwrap();
+        let (9, VescMessage::Ping { sender_id }) = vesc_message(data) else {
+            panic!("Unexpected data type");
+        };
+        assert!(sender_id == 0x42);
+    }
+
+    #[test]
+    fn vesc_pong() {
+        // Pong from controller 9 to the node with ID 0x42
+        let can_frame = frame!(extended 0x1242, [0x09, 0x00]);
+        let data = parse_eoi_can_data(&can_frame).unwrap();
+        let (9, VescMessage::Pong { sender_id, hw_type }) = vesc_message(data) else {
+            panic!("Unexpected data type");
+        };
+        assert!(sender_id == 0x09);
+        assert!(hw_type == VescHwType::Vesc);
+
+        // Pong from another controller is not ours
+        let can_frame = frame!(extended 0x1242, [0x0A, 0x00]);
+        assert!(parse_eoi_can_data(&can_frame).is_none());
+    }
+
+    #[test]
+    fn vesc_firmware_version() {
+        // Sender 9, send mode 1, COMM_FW_VERSION, major 6, minor 2
+        let can_frame = frame!(extended 0x0842, [0x09, 0x01, 0x00, 0x06, 0x02]);
+        let data = parse_eoi_can_data(&can_frame).unwrap();
+        let (9, VescMessage::FirmwareVersion { major, minor }) = vesc_message(data) else {
+            panic!("Unexpected data type");
+        };
+        assert!(major == 6);
+        assert!(minor == 2);
+    }
+
+    #[test]
+    fn vesc_fault() {
+        // Sender 9, send mode 1, COMM_GET_VALUES_SELECTIVE with the fault bit, FET over temperature
+        let can_frame = frame!(extended 0x08FF, [0x09, 0x01, 0x32, 0x00, 0x00, 0x80, 0x00, 0x05]);
+        let data = parse_eoi_can_data(&can_frame).unwrap();
+        let (9, VescMessage::Fault { fault }) = vesc_message(data) else {
+            panic!("Unexpected data type");
+        };
+        assert!(fault == VescFault::OverTempFet);
+        assert!(fault.is_fault());
+        assert!(VescFault::from(200) == VescFault::Unknown(200));
+        assert!(u8::from(VescFault::from(200)) == 200);
+
+        // Other values than the fault alone are not decoded
+        let can_frame = frame!(extended 0x08FF, [0x09, 0x01, 0x32, 0x00, 0x00, 0x80, 0x01, 0x05]);
+        assert!(parse_eoi_can_data(&can_frame).is_none());
+    }
+
+    #[test]
+    fn vesc_configured_controller_ids() {
+        // Status message 1 from controller 10
+        let can_frame = frame!(extended 0x090A, [0x00, 0x00, 0x03, 0xE8, 0x00, 0x64, 0x01, 0xF4]);
+        // Not followed by default
+        assert!(parse_eoi_can_data(&can_frame).is_none());
+
+        let config = DecoderConfig::with_vesc_controller_ids(&[9, 10]).unwrap();
+        let data = parse_eoi_can_data_with_config(&can_frame, &config).unwrap();
+        let EoiCanData::Vesc(vesc) = data else {
+            panic!("Unexpected data type");
+        };
+        let (
+            controller_id,
+            VescMessage::StatusMessage1 {
+                rpm,
+                total_current,
+                duty_cycle,
+            },
+        ) = vesc.into_parts()
+        else {
+            panic!("Unexpected data type");
+        };
+        assert!(controller_id == 10);
+        assert!(rpm == 1000);
+        assert!((total_current - 10.0).abs() < 0.001);
+        assert!((duty_cycle - 50.0).abs() < 0.001);
+
+        assert!(DecoderConfig::with_vesc_controller_ids(&[1, 2, 3, 4, 5]).is_none());
+
+        // Every controller ID but the broadcast one
+        let ping = || VescMessage::Ping { sender_id: 0x42 };
+        assert!(VescData::from_node_id(200, ping()).unwrap().as_parts().0 == 200);
+        assert!(VescData::from_node_id(VescData::BROADCAST_CONTROLLER_ID, ping()).is_none());
+    }
+
+    #[test]
+    fn vesc_commands_to_configured_controllers() {
+        // Current of 12.5 A to controller 10
+        let can_frame = frame!(extended 0x010A, [0x00, 0x00, 0x30, 0xD4]);
+        assert!(parse_eoi_can_data(&can_frame).is_none());
+
+        let config = DecoderConfig::with_vesc_controller_ids(&[9, 10]).unwrap();
+        let data = parse_eoi_can_data_with_config(&can_frame, &config);
+        let Some(EoiCanData::Throttle(ThrottleData::ToVescCurrent(current))) = data else {
+            panic!("Unexpected data type");
+        };
+        assert!((current - 12.5).abs() < 0.001);
+
+        // The standard IDs of packet 0 belong to other nodes, like the servo setpoint
+        let config = DecoderConfig::with_vesc_controller_ids(&[0x10]).unwrap();
+        let can_frame = frame!(0x010, [0xE8, 0x03]);
+        assert!(matches!(
+            parse_eoi_can_data_with_config(&can_frame, &config),
+            Some(EoiCanData::RudderController(_))
+        ));
+    }
+
+    #[test]
+    fn mppt_configured_devices() {
+        // Output power of MPPT 5
+        let can_frame = frame!(0x758, [0, 0, 0x48, 0x42, 0, 0, 0x20, 0x41]);
+        assert!(matches!(
+            parse_eoi_can_data(&can_frame),
+            Some(EoiCanData::Mppt(MpptData::Id5(MpptInfo::Power(_))))
+        ));
+
+        let config = DecoderConfig::default().with_mppt_devices(3);
+        assert!(parse_eoi_can_data_with_config(&can_frame, &config).is_none());
+        let can_frame = frame!(0x728, [0, 0, 0x48, 0x42, 0, 0, 0x20, 0x41]);
+        assert!(parse_eoi_can_data_with_config(&can_frame, &config).is_some());
+        assert!(
+            DecoderConfig::default()
+                .with_mppt_devices(12)
+                .mppt_devices()
+                == 8
+        );
+    }
+
+    #[test]
+    fn date_time_with_offset() {
+        let date_time = |year, month, day, hours, minutes| GnssDateTime {
+            year,
+            month,
+            day,
+            hours,
+            minutes,
+            seconds: 42,
+        };
+        let parts = |date_time: GnssDateTime| {
+            (
+                date_time.year,
+                date_time.month,
+                date_time.day,
+                date_time.hours,
+                date_time.minutes,
+                date_time.seconds,
+            )
+        };
+
+        assert!(
+            parts(date_time(2025, 6, 21, 13, 37).with_offset(120)) == (2025, 6, 21, 15, 37, 42)
+        );
+        assert!(parts(date_time(2025, 6, 21, 23, 30).with_offset(120)) == (2025, 6, 22, 1, 30, 42));
+        assert!(parts(date_time(2025, 12, 31, 23, 0).with_offset(60)) == (2026, 1, 1, 0, 0, 42));
+        assert!(parts(date_time(2024, 3, 1, 0, 15).with_offset(-30)) == (2024, 2, 29, 23, 45, 42));
+        assert!(parts(date_time(2025, 1, 1, 1, 0).with_offset(-570)) == (2024, 12, 31, 15, 30, 42));
+    }
+
+    #[test]
+    fn date_time_unix_s() {
+        let date_time = |year, month, day, hours, minutes, seconds| GnssDateTime {
+            year,
+            month,
+            day,
+            hours,
+            minutes,
+            seconds,
+        };
+        assert!(date_time(1970, 1, 1, 0, 0, 0).unix_s() == 0);
+        assert!(date_time(2024, 2, 29, 0, 0, 0).unix_s() == 1_709_164_800);
+        assert!(date_time(2025, 6, 21, 13, 37, 42).unix_s() == 1_750_513_062);
+    }
+
+    #[test]
+    fn throttle_errors() {
+        let errors = ThrottleErrors::from_bits(0b1001_0101);
+        assert!(matches!(errors.twi, ThrottleTwiErrors::SlaveNAK));
+        assert!(errors.gain_clipping && errors.impedance_high);
+        assert!(!errors.no_eeprom && !errors.gain_invalid && !errors.deadman_missing);
+        assert!(errors.bits() == Some(0b1001_0101));
+        assert!(errors.to_string() == "TWI: SlaveNAK, Gain Clipping, Impedance High");
+
+        let no_errors = ThrottleErrors::from_bits(0);
+        assert!(!no_errors.has_error());
+        assert!(no_errors.to_string() == "No Error");
+        // TWI error states 6 and 7 aren't defined
+        assert!(ThrottleErrors::from_bits(0b110).bits().is_none());
+    }
+
+    #[test]
+    fn node_identification() {
+        // The throttle, firmware 1.4.2 of commit 1a2b3c4d with uncommitted changes
+        let can_frame = frame!(0x272, [0x01, 0x04, 0x02, 0x01, 0x1A, 0x2B, 0x3C, 0x4D]);
+        let Some(EoiCanData::NodeIdentification(identification)) = parse_eoi_can_data(&can_frame)
+        else {
+            panic!("Unexpected data type");
+        };
+        assert!(identification.node == NodeType::Throttle);
+        assert!(identification.to_string() == "1.4.2 (1a2b3c4d-dirty)");
+
+        // No node type for the last IDs of the range yet
+        let can_frame = frame!(0x27F, [0x01, 0x04, 0x02, 0x00, 0x1A, 0x2B, 0x3C, 0x4D]);
+        assert!(parse_eoi_can_data(&can_frame).is_none());
+    }
+
+    #[test]
+    fn split_display_message() {
+        let texts = |parts: &[DisplayMessage]| -> Vec<String> {
+            parts.iter().map(|part| part.text.to_string()).collect()
+        };
+
+        let parts = DisplayMessage::split(3, "PIT NOW");
+        assert!(texts(&parts) == ["PIT NOW"]);
+        assert!(parts[0].last && parts[0].part == 0 && parts[0].message_id == 3);
+
+        let parts = DisplayMessage::split(127, "SLOW 2 MIN");
+        assert!(texts(&parts) == ["SLOW 2 ", "MIN"]);
+        assert!(!parts[0].last && parts[1].last && parts[1].part == 1);
+        assert!(parts.iter().all(|part| part.message_id == 127));
+
+        // Parts end between characters, the rest of a long text is cut off
+        assert!(texts(&DisplayMessage::split(0, "ÄÄÄÄ")) == ["ÄÄÄ", "Ä"]);
+        let parts = DisplayMessage::split(0, "ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789");
+        assert!(texts(&parts) == ["ABCDEFG", "HIJKLMN", "OPQRSTU", "VWXYZ01"]);
+        assert!(parts[3].last);
+
+        // Clears the banner
+        let parts = DisplayMessage::split(0, "");
+        assert!(parts.len() == 1 && parts[0].last && parts[0].text.is_empty());
+    }
 }
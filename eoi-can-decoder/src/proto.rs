@@ -0,0 +1,345 @@
+//! Export of the signals in [`crate::signals`] as protobuf schema, for race-ops software reading
+//! the telemetry over gRPC instead of MQTT.
+//!
+//! Every signal is a value of the `Signal` enum. The numbers are part of the wire format of the
+//! clients, so they never change: a new message is appended to [`FIRST_SIGNAL_NUMBERS`] with the
+//! numbers after the last one, and the numbers of removed signals go to [`RESERVED`].
+
+use core::fmt::{Result, Write};
+
+use crate::can_frame::CanFrame;
+use crate::current::CurrentConvention;
+use crate::signals::{find_message, MessageDefinition, SignalDefinition, MESSAGES};
+
+/// Number in the `Signal` enum of the first signal of every message, the other signals of the
+/// message follow it. Append only, in the order the messages were added
+const FIRST_SIGNAL_NUMBERS: &[(&str, i32)] = &[
+    ("ServoRudderSetpoint", 1),
+    ("ServoRudderStatus", 2),
+    ("ServoRudderCommand", 4),
+    ("HeightSensorFrontLeft", 5),
+    ("HeightSensorFrontRight", 7),
+    ("HeightSensorReserved1", 9),
+    ("HeightSensorReserved2", 11),
+    ("TemperatureHeightSensorsController", 13),
+    ("TemperatureRudderController", 14),
+    ("PackAndPerriCurrent", 15),
+    ("ChargeAndDischargeCurrent", 17),
+    ("SocErrorFlagsAndBalancing", 19),
+    ("CellVoltages1To4", 22),
+    ("CellVoltages5To8", 26),
+    ("CellVoltages9To12", 30),
+    ("CellVoltages13To14PackAndStack", 34),
+    ("TemperaturesAndStates", 38),
+    ("BatteryUptime", 46),
+    ("ChargeControlStatus", 47),
+    ("ChargeControlCommand", 48),
+    ("GnssStatus", 49),
+    ("GnssSpeedAndHeading", 52),
+    ("GnssLatitude", 54),
+    ("GnssLongitude", 55),
+    ("GnssDateTime", 56),
+    ("GnssAltitude", 62),
+    ("GnssFixQuality", 63),
+    ("GnssUtcOffset", 65),
+    ("DisplayHeartbeat", 66),
+    ("DisplayLog", 71),
+    ("DisplayBattery", 74),
+    ("DisplayPage", 76),
+    ("DisplayMessage", 77),
+    ("DisplayAcknowledge", 79),
+    ("DisplayProfile", 80),
+    ("DisplayRaceEnd", 81),
+    ("MpptChannel0Power", 82),
+    ("MpptChannel0State", 84),
+    ("MpptChannel1Power", 88),
+    ("MpptChannel1State", 90),
+    ("MpptChannel2Power", 94),
+    ("MpptChannel2State", 96),
+    ("MpptChannel3Power", 100),
+    ("MpptChannel3State", 102),
+    ("MpptPower", 106),
+    ("MpptStatus", 108),
+    ("GanMpptPower", 118),
+    ("GanMpptStatus", 122),
+    ("GanMpptSweepData", 127),
+    ("ThrottleToVescDutyCycle", 130),
+    ("ThrottleToVescCurrent", 131),
+    ("ThrottleToVescRpm", 132),
+    ("ThrottleStatus", 133),
+    ("ThrottleStatusLegacy", 143),
+    ("VescStatusMessage1", 153),
+    ("VescStatusMessage2", 156),
+    ("VescStatusMessage3", 158),
+    ("VescStatusMessage4", 160),
+    ("VescPing", 164),
+    ("VescStatusMessage5", 165),
+    ("VescStatusMessage6", 167),
+    ("GnssTimeSync", 171),
+    ("PeripheralPower", 172),
+    ("WaterSpeed", 173),
+    ("ImuAttitude", 175),
+    ("CellVoltages15To16", 178),
+    ("TimeEstimate", 180),
+    ("DisplayPanic", 182),
+    ("NodeIdentification", 184),
+    ("Temperatures5To8", 189),
+];
+
+/// Numbers of removed signals, never to be used again
+const RESERVED: &[i32] = &[113, 114, 115, 116, 117];
+
+/// Number of the signal in the `Signal` enum, 0 is left for unknown signals
+pub fn signal_number(message: &MessageDefinition, signal_index: usize) -> Option<i32> {
+    let (_, first) = FIRST_SIGNAL_NUMBERS
+        .iter()
+        .find(|(name, _)| *name == message.name)?;
+    (signal_index < message.signals.len()).then_some(first + signal_index as i32)
+}
+
+/// Every signal with its number, in the order of the registry
+pub fn signals(
+) -> impl Iterator<Item = (i32, &'static MessageDefinition, &'static SignalDefinition)> {
+    MESSAGES.iter().flat_map(|message| {
+        message
+            .signals
+            .iter()
+            .enumerate()
+            .filter_map(move |(index, signal)| {
+                Some((signal_number(message, index)?, message, signal))
+            })
+    })
+}
+
+/// Number, node and physical value of every signal in `frame`, nothing for frames the registry
+/// doesn't know
+pub fn signal_values(frame: &CanFrame) -> impl Iterator<Item = (i32, u8, f64)> + '_ {
+    signal_values_in(frame, CurrentConvention::DECODED)
+}
+
+/// Like [`signal_values`], with the currents of the battery in `convention`
+pub fn signal_values_in(
+    frame: &CanFrame,
+    convention: CurrentConvention,
+) -> impl Iterator<Item = (i32, u8, f64)> + '_ {
+    let (id, extended) = match frame.id {
+        embedded_can::Id::Standard(id) => (id.as_raw() as u32, false),
+        embedded_can::Id::Extended(id) => (id.as_raw(), true),
+    };
+    find_message(id, extended)
+        .and_then(|(message, node)| Some((message, node, signal_number(message, 0)?)))
+        .into_iter()
+        .flat_map(move |(message, node, first)| {
+            message
+                .signals
+                .iter()
+                .zip(first..)
+                .filter_map(move |(signal, number)| {
+                    let value = signal.decode(&frame.data)?;
+                    Some((
+                        number,
+                        node,
+                        convention.convert_signal(message.name, signal.name, value),
+                    ))
+                })
+        })
+}
+
+pub fn write_proto<W: Write>(w: &mut W) -> Result {
+    writeln!(
+        w,
+        "// Generated from the signal registry of eoi-can-decoder with"
+    )?;
+    writeln!(
+        w,
+        "// `cargo run -p eoi-can-decoder --bin eoi-can-proto`, don't edit"
+    )?;
+    writeln!(w, "syntax = \"proto3\";")?;
+    writeln!(w)?;
+    writeln!(w, "package eoi_can;")?;
+    writeln!(w)?;
+
+    writeln!(
+        w,
+        "// Signals of the CAN bus with their unit and the message they are sent in"
+    )?;
+    writeln!(w, "enum Signal {{")?;
+    writeln!(w, "  SIGNAL_UNSPECIFIED = 0;")?;
+    if !RESERVED.is_empty() {
+        write!(w, "  reserved ")?;
+        for (index, number) in RESERVED.iter().enumerate() {
+            if index > 0 {
+                write!(w, ", ")?;
+            }
+            write!(w, "{}", number)?;
+        }
+        writeln!(w, ";")?;
+    }
+    for (number, message, signal) in signals() {
+        write!(w, "  ")?;
+        write_screaming_snake_case(w, message.name)?;
+        write!(w, "_")?;
+        write_screaming_snake_case(w, signal.name)?;
+        write!(w, " = {};", number)?;
+        if !signal.unit.is_empty() {
+            write!(w, " // {},", signal.unit)?;
+        } else {
+            write!(w, " //")?;
+        }
+        writeln!(w, " {} {:#X}", message.name, message.id)?;
+    }
+    writeln!(w, "}}")?;
+
+    w.write_str(
+        r#"
+message SignalValue {
+  Signal signal = 1;
+  // Node of messages sent by several devices, like the number of the MPPT
+  uint32 node = 2;
+  // Physical value, scaled like in the DBC export
+  double value = 3;
+}
+
+message Snapshot {
+  // Milliseconds since the Unix epoch
+  uint64 timestamp_ms = 1;
+  // The latest value of every signal received
+  repeated SignalValue values = 2;
+}
+
+message LiveRequest {}
+
+message HistoryRequest {
+  // Milliseconds since the Unix epoch, 0 for the oldest snapshot kept
+  uint64 from_ms = 1;
+  // 0 for up to now
+  uint64 to_ms = 2;
+}
+
+service Telemetry {
+  // A snapshot every publish interval
+  rpc Live(LiveRequest) returns (stream Snapshot);
+  // The snapshots kept in memory, oldest first
+  rpc History(HistoryRequest) returns (stream Snapshot);
+}
+"#,
+    )
+}
+
+/// `PackAndPerriCurrent` as `PACK_AND_PERRI_CURRENT`
+fn write_screaming_snake_case<W: Write>(w: &mut W, name: &str) -> Result {
+    let mut previous: Option<char> = None;
+    for c in name.chars() {
+        if c.is_ascii_uppercase()
+            && previous
+                .is_some_and(|previous| previous.is_ascii_lowercase() || previous.is_ascii_digit())
+        {
+            w.write_char('_')?;
+        }
+        w.write_char(c.to_ascii_uppercase())?;
+        previous = Some(c);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+
+    #[test]
+    fn proto_contains_signals() {
+        let mut proto = std::string::String::new();
+        write_proto(&mut proto).unwrap();
+
+        assert!(proto.contains("package eoi_can;"));
+        assert!(proto.contains("  SERVO_RUDDER_SETPOINT_SETPOINT = 1; // ServoRudderSetpoint 0x10"));
+        assert!(proto.contains("rpc Live(LiveRequest) returns (stream Snapshot);"));
+
+        for (number, message, signal) in signals() {
+            let index = message
+                .signals
+                .iter()
+                .position(|other| other.name == signal.name)
+                .unwrap();
+            assert!(signal_number(message, index) == Some(number));
+        }
+    }
+
+    #[test]
+    fn signal_numbers_are_stable() {
+        let mut numbers = std::vec::Vec::new();
+        for message in MESSAGES {
+            for index in 0..message.signals.len() {
+                let number = signal_number(message, index);
+                assert!(
+                    number.is_some(),
+                    "Append {} to FIRST_SIGNAL_NUMBERS",
+                    message.name
+                );
+                numbers.push(number.unwrap());
+            }
+        }
+        numbers.sort_unstable();
+        for pair in numbers.windows(2) {
+            assert!(
+                pair[0] != pair[1],
+                "Signal number {} is used twice",
+                pair[0]
+            );
+        }
+        for number in RESERVED {
+            assert!(
+                !numbers.contains(number),
+                "Signal number {} is reserved",
+                number
+            );
+        }
+
+        // Numbers clients were generated with
+        let gnss_status = MESSAGES.iter().find(|m| m.name == "GnssStatus").unwrap();
+        assert!(signal_number(gnss_status, 0) == Some(49));
+    }
+
+    #[test]
+    fn signal_names_are_unique() {
+        let mut proto = std::string::String::new();
+        write_proto(&mut proto).unwrap();
+        let names: std::vec::Vec<&str> = proto
+            .lines()
+            .filter_map(|line| line.trim().split_once(" = "))
+            .map(|(name, _)| name)
+            .collect();
+        for (index, name) in names.iter().enumerate() {
+            assert!(!names[..index].contains(name), "{} is not unique", name);
+        }
+    }
+
+    #[test]
+    fn values_of_frame() {
+        let frame = crate::frame!(0x010, [0x34, 0x12]);
+        let values: std::vec::Vec<_> = signal_values(&frame).collect();
+        assert!(values == [(1, 0, f64::from(0x1234))]);
+
+        // Only the currents of the battery change with the convention
+        let frame = crate::frame!(0x100, [0x00, 0x00, 0x20, 0x41, 0x00, 0x00, 0x80, 0xBF]);
+        let values: std::vec::Vec<_> =
+            signal_values_in(&frame, CurrentConvention::DischargePositive).collect();
+        assert!(values == [(15, 0, -10.0), (16, 0, 1.0)]);
+        let frame = crate::frame!(0x010, [0x34, 0x12]);
+        let values: std::vec::Vec<_> =
+            signal_values_in(&frame, CurrentConvention::DischargePositive).collect();
+        assert!(values == [(1, 0, f64::from(0x1234))]);
+    }
+
+    /// The schema the gRPC clients are generated from is checked in
+    #[test]
+    fn checked_in_proto_is_current() {
+        let mut proto = std::string::String::new();
+        write_proto(&mut proto).unwrap();
+        assert!(
+            proto == include_str!("../proto/eoi_can.proto"),
+            "Run `cargo run -p eoi-can-decoder --bin eoi-can-proto > eoi-can-decoder/proto/eoi_can.proto`"
+        );
+    }
+}
@@ -0,0 +1,175 @@
+//! Protocol reference of the messages in [`crate::signals`] as Markdown, for teammates
+//! implementing the nodes in C. It is generated from the same table the decoder is checked
+//! against, so the byte order and scaling can't drift from what the decoder expects.
+
+use core::fmt::{Result, Write};
+
+use crate::signals::{ByteOrder, MessageDefinition, SignalDefinition, ValueType, MESSAGES};
+
+pub fn write_protocol_doc<W: Write>(w: &mut W) -> Result {
+    writeln!(w, "# EOI CAN protocol reference")?;
+    writeln!(w)?;
+    writeln!(
+        w,
+        "Generated by `cargo run -p eoi-can-decoder --bin protocol-doc` from the signal table of \
+         the decoder, do not edit. `CAN_MESSAGES.md` explains what the messages are used for."
+    )?;
+    writeln!(w)?;
+    writeln!(
+        w,
+        "Physical value = raw × factor + offset. Little endian signals are Intel byte order, big \
+         endian signals Motorola byte order starting at the most significant byte. Bits are \
+         counted from bit 0 of byte 0."
+    )?;
+    writeln!(w)?;
+
+    writeln!(w, "| CAN ID | Message | Transmitter | DLC |")?;
+    writeln!(w, "| --- | --- | --- | --- |")?;
+    for message in MESSAGES {
+        write!(w, "| ")?;
+        write_ids(w, message)?;
+        write!(
+            w,
+            " | [{}](#{}) | {} | ",
+            message.name,
+            message.name.to_ascii_lowercase(),
+            message.transmitter,
+        )?;
+        write_dlc(w, message)?;
+        writeln!(w, " |")?;
+    }
+
+    for message in MESSAGES {
+        writeln!(w)?;
+        writeln!(w, "## {}", message.name)?;
+        writeln!(w)?;
+        write!(w, "CAN ID ")?;
+        write_ids(w, message)?;
+        write!(w, ", ")?;
+        write_dlc(w, message)?;
+        writeln!(w, " bytes, sent by {}", message.transmitter)?;
+        if message.signals.is_empty() {
+            writeln!(w)?;
+            writeln!(w, "No signals, the frame itself is the message.")?;
+            continue;
+        }
+        writeln!(w)?;
+        writeln!(
+            w,
+            "| Signal | Position | Type | Byte order | Factor | Offset | Unit | Values |"
+        )?;
+        writeln!(w, "| --- | --- | --- | --- | --- | --- | --- | --- |")?;
+        for signal in message.signals {
+            write_signal(w, signal)?;
+        }
+    }
+    Ok(())
+}
+
+/// The ID of a single node, or the IDs of all nodes with the step between them
+fn write_ids<W: Write>(w: &mut W, message: &MessageDefinition) -> Result {
+    let width = if message.extended { 8 } else { 3 };
+    write!(w, "0x{:0width$X}", message.id, width = width)?;
+    if message.node_count > 1 {
+        let last = message
+            .id_for_node(message.node_count - 1)
+            .unwrap_or(message.id);
+        write!(
+            w,
+            "–0x{:0width$X} ({} nodes, every 0x{:X})",
+            last,
+            message.node_count,
+            message.node_id_stride,
+            width = width
+        )?;
+    }
+    if message.extended {
+        write!(w, " (extended)")?;
+    }
+    Ok(())
+}
+
+/// The DLC, or the range of DLCs for messages with text of any length
+fn write_dlc<W: Write>(w: &mut W, message: &MessageDefinition) -> Result {
+    if message.min_dlc < message.dlc {
+        write!(w, "{}–{}", message.min_dlc, message.dlc)
+    } else {
+        write!(w, "{}", message.dlc)
+    }
+}
+
+fn write_signal<W: Write>(w: &mut W, signal: &SignalDefinition) -> Result {
+    write!(w, "| {} | ", signal.name)?;
+    write_position(w, signal)?;
+    let prefix = match signal.value_type {
+        ValueType::Unsigned => 'u',
+        ValueType::Signed => 'i',
+        ValueType::Float | ValueType::Double => 'f',
+    };
+    let byte_order = match signal.byte_order {
+        // A single byte or less has no order
+        _ if signal.length <= 8 => "",
+        ByteOrder::LittleEndian => "little endian",
+        ByteOrder::BigEndian => "big endian",
+    };
+    write!(
+        w,
+        " | {}{} | {} | {} | {} | {} | ",
+        prefix, signal.length, byte_order, signal.factor, signal.offset, signal.unit
+    )?;
+    for (index, (value, name)) in signal.values.iter().enumerate() {
+        if index > 0 {
+            write!(w, ", ")?;
+        }
+        write!(w, "{} = {}", value, name)?;
+    }
+    writeln!(w, " |")
+}
+
+/// Whole bytes like `bytes 2–3`, or bits like `bits 3–5` for signals not filling their bytes
+fn write_position<W: Write>(w: &mut W, signal: &SignalDefinition) -> Result {
+    let (start, length) = (u16::from(signal.start), u16::from(signal.length));
+    if start % 8 == 0 && length % 8 == 0 {
+        let (first, last) = (start / 8, (start + length) / 8 - 1);
+        if first == last {
+            write!(w, "byte {}", first)
+        } else {
+            write!(w, "bytes {}–{}", first, last)
+        }
+    } else if length == 1 {
+        write!(w, "bit {}", start)
+    } else {
+        write!(w, "bits {}–{}", start, start + length - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+
+    #[test]
+    fn doc_contains_messages() {
+        let mut doc = std::string::String::new();
+        write_protocol_doc(&mut doc).unwrap();
+
+        assert!(doc.contains("| 0x100 | [PackAndPerriCurrent](#packandperricurrent) | BMS | 8 |"));
+        assert!(doc.contains("## SocErrorFlagsAndBalancing\n\nCAN ID 0x102, 8 bytes, sent by BMS"));
+        assert!(
+            doc.contains("| StateOfCharge | bytes 0–1 | u16 | little endian | 0.01 | 0 | % |  |")
+        );
+        assert!(doc.contains("| Rpm | bytes 0–3 | i32 | big endian | 1 | 0 | rpm |  |"));
+        assert!(doc.contains("0x00000909 (extended)"));
+        assert!(doc.contains("0 = Init, 1 = Sleep"));
+    }
+
+    #[test]
+    fn doc_is_up_to_date() {
+        let mut doc = std::string::String::new();
+        write_protocol_doc(&mut doc).unwrap();
+        assert!(
+            doc == include_str!("../PROTOCOL.md"),
+            "Run `cargo run -p eoi-can-decoder --bin protocol-doc > eoi-can-decoder/PROTOCOL.md`"
+        );
+    }
+}
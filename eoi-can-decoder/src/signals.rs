@@ -0,0 +1,1255 @@
+//! Description of every message and signal the decoder knows about.
+//!
+//! The table mirrors the parsing in [`crate::parse_eoi_can_data`] and is the source for
+//! exporting the CAN matrix to other tools (see [`crate::dbc`]) and of the protocol reference (see
+//! [`crate::protocol_doc`]). When adding a message to the decoder, add it here as well.
+
+use crate::can_frame::CanFrame;
+use crate::{
+    DecoderConfig, NodeType, PeripheralConsumer, DISPLAY_MESSAGE_PARTS, NODE_IDENTIFICATION_ID,
+    PERIPHERAL_POWER_ID,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ByteOrder {
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValueType {
+    Unsigned,
+    Signed,
+    Float,
+    Double,
+}
+
+#[derive(Debug)]
+pub struct SignalDefinition {
+    pub name: &'static str,
+    /// Bit position of the signal in the payload (`byte * 8 + bit`), for big endian signals this is
+    /// the start of the first byte
+    pub start: u8,
+    /// Length in bits
+    pub length: u8,
+    pub byte_order: ByteOrder,
+    pub value_type: ValueType,
+    /// Physical value = raw * factor + offset
+    pub factor: f32,
+    pub offset: f32,
+    pub unit: &'static str,
+    /// Names for enum like raw values
+    pub values: &'static [(u32, &'static str)],
+}
+
+impl SignalDefinition {
+    const fn new(
+        name: &'static str,
+        start: u8,
+        length: u8,
+        byte_order: ByteOrder,
+        value_type: ValueType,
+    ) -> Self {
+        Self {
+            name,
+            start,
+            length,
+            byte_order,
+            value_type,
+            factor: 1.0,
+            offset: 0.0,
+            unit: "",
+            values: &[],
+        }
+    }
+
+    const fn scaled(self, factor: f32, unit: &'static str) -> Self {
+        Self {
+            factor,
+            unit,
+            ..self
+        }
+    }
+
+    const fn with_values(self, values: &'static [(u32, &'static str)]) -> Self {
+        Self { values, ..self }
+    }
+
+    /// Big endian (Motorola) signals start at the most significant bit of their first byte, like
+    /// in the DBC export
+    fn start_bit(&self) -> u16 {
+        match self.byte_order {
+            ByteOrder::LittleEndian => self.start.into(),
+            ByteOrder::BigEndian => u16::from(self.start) + 7,
+        }
+    }
+
+    /// Physical value of the signal in `data`, `None` when the data is too short
+    pub fn decode(&self, data: &[u8]) -> Option<f64> {
+        let raw = decode_raw(
+            data,
+            self.start_bit(),
+            self.length.into(),
+            self.byte_order,
+            self.value_type,
+        )?;
+        Some(raw * f64::from(self.factor) + f64::from(self.offset))
+    }
+
+    /// Write the physical `value` into `data`, rounded and limited to what the signal can hold.
+    /// `None` when the data is too short, then nothing is written
+    pub fn encode(&self, data: &mut [u8], value: f64) -> Option<()> {
+        self.decode(data)?;
+        let scaled = (value - f64::from(self.offset)) / f64::from(self.factor);
+        let length = u16::from(self.length);
+        let mask = u64::MAX >> (64 - length);
+        let raw = match self.value_type {
+            ValueType::Unsigned => scaled.round().clamp(0.0, mask as f64) as u64,
+            ValueType::Signed => {
+                let max = (mask >> 1) as f64;
+                (scaled.round().clamp(-max - 1.0, max) as i64) as u64 & mask
+            }
+            ValueType::Float => u64::from((scaled as f32).to_bits()),
+            ValueType::Double => scaled.to_bits(),
+        };
+
+        let start = self.start_bit();
+        match self.byte_order {
+            ByteOrder::LittleEndian => {
+                for bit in 0..length {
+                    write_bit(data, start + bit, raw >> bit & 1);
+                }
+            }
+            ByteOrder::BigEndian => {
+                // Like decoding, from the most significant bit
+                let mut position = start;
+                for bit in (0..length).rev() {
+                    write_bit(data, position, raw >> bit & 1);
+                    position = if position.is_multiple_of(8) {
+                        position + 15
+                    } else {
+                        position - 1
+                    };
+                }
+            }
+        }
+        Some(())
+    }
+}
+
+/// Unscaled value of a signal starting at bit `start`, shared with the DBC database
+pub(crate) fn decode_raw(
+    data: &[u8],
+    start: u16,
+    length: u16,
+    byte_order: ByteOrder,
+    value_type: ValueType,
+) -> Option<f64> {
+    let raw = match byte_order {
+        ByteOrder::LittleEndian => {
+            let mut raw = 0u64;
+            for bit in (0..length).rev() {
+                raw = (raw << 1) | read_bit(data, start + bit)?;
+            }
+            raw
+        }
+        ByteOrder::BigEndian => {
+            // Motorola bit numbering, the start is the most significant bit
+            let mut raw = 0u64;
+            let mut position = start;
+            for _ in 0..length {
+                raw = (raw << 1) | read_bit(data, position)?;
+                position = if position.is_multiple_of(8) {
+                    position + 15
+                } else {
+                    position - 1
+                };
+            }
+            raw
+        }
+    };
+
+    Some(match value_type {
+        ValueType::Unsigned => raw as f64,
+        ValueType::Signed => {
+            let shift = 64 - length as u32;
+            ((raw << shift) as i64 >> shift) as f64
+        }
+        ValueType::Float => f32::from_bits(raw as u32) as f64,
+        ValueType::Double => f64::from_bits(raw),
+    })
+}
+
+fn read_bit(data: &[u8], position: u16) -> Option<u64> {
+    let byte = data.get(position as usize / 8)?;
+    Some(((byte >> (position % 8)) & 1) as u64)
+}
+
+fn write_bit(data: &mut [u8], position: u16, bit: u64) {
+    if let Some(byte) = data.get_mut(position as usize / 8) {
+        *byte = *byte & !(1 << (position % 8)) | ((bit as u8) << (position % 8));
+    }
+}
+
+#[derive(Debug)]
+pub struct MessageDefinition {
+    pub name: &'static str,
+    /// CAN ID of the first node
+    pub id: u32,
+    pub extended: bool,
+    pub dlc: u8,
+    /// Fewest bytes a frame has, less than `dlc` for messages ending in text of any length
+    pub min_dlc: u8,
+    /// Device sending this message
+    pub transmitter: &'static str,
+    /// Number of nodes sending this message, each node gets its own ID
+    pub node_count: u8,
+    /// Distance between the IDs of two consecutive nodes
+    pub node_id_stride: u32,
+    pub signals: &'static [SignalDefinition],
+}
+
+impl MessageDefinition {
+    const fn new(
+        name: &'static str,
+        id: u32,
+        dlc: u8,
+        transmitter: &'static str,
+        signals: &'static [SignalDefinition],
+    ) -> Self {
+        Self {
+            name,
+            id,
+            extended: false,
+            dlc,
+            min_dlc: dlc,
+            transmitter,
+            node_count: 1,
+            node_id_stride: 0,
+            signals,
+        }
+    }
+
+    const fn extended(self) -> Self {
+        Self {
+            extended: true,
+            ..self
+        }
+    }
+
+    /// Frames may be as short as `min_dlc`
+    const fn variable_length(self, min_dlc: u8) -> Self {
+        Self { min_dlc, ..self }
+    }
+
+    const fn per_node(self, node_count: u8, node_id_stride: u32) -> Self {
+        Self {
+            node_count,
+            node_id_stride,
+            ..self
+        }
+    }
+
+    pub fn id_for_node(&self, node: u8) -> Option<u32> {
+        (node < self.node_count).then_some(self.id + node as u32 * self.node_id_stride)
+    }
+
+    /// Iterate over `(node, id)` for all nodes sending this message
+    pub fn ids(&self) -> impl Iterator<Item = (u8, u32)> + '_ {
+        (0..self.node_count).filter_map(|node| Some((node, self.id_for_node(node)?)))
+    }
+
+    /// Standard or extended CAN ID of `node`, for building frames of this message
+    pub fn can_id(&self, node: u8) -> Option<embedded_can::Id> {
+        let id = self.id_for_node(node)?;
+        if self.extended {
+            embedded_can::ExtendedId::new(id).map(embedded_can::Id::Extended)
+        } else {
+            embedded_can::StandardId::new(u16::try_from(id).ok()?).map(embedded_can::Id::Standard)
+        }
+    }
+}
+
+const fn le_u(name: &'static str, start: u8, length: u8) -> SignalDefinition {
+    SignalDefinition::new(
+        name,
+        start,
+        length,
+        ByteOrder::LittleEndian,
+        ValueType::Unsigned,
+    )
+}
+
+const fn le_i(name: &'static str, start: u8, length: u8) -> SignalDefinition {
+    SignalDefinition::new(
+        name,
+        start,
+        length,
+        ByteOrder::LittleEndian,
+        ValueType::Signed,
+    )
+}
+
+const fn le_f32(name: &'static str, start: u8, unit: &'static str) -> SignalDefinition {
+    SignalDefinition::new(name, start, 32, ByteOrder::LittleEndian, ValueType::Float)
+        .scaled(1.0, unit)
+}
+
+const fn le_f64(name: &'static str, start: u8, unit: &'static str) -> SignalDefinition {
+    SignalDefinition::new(name, start, 64, ByteOrder::LittleEndian, ValueType::Double)
+        .scaled(1.0, unit)
+}
+
+const fn be_u(name: &'static str, start: u8, length: u8) -> SignalDefinition {
+    SignalDefinition::new(
+        name,
+        start,
+        length,
+        ByteOrder::BigEndian,
+        ValueType::Unsigned,
+    )
+}
+
+const fn be_i(name: &'static str, start: u8, length: u8) -> SignalDefinition {
+    SignalDefinition::new(name, start, length, ByteOrder::BigEndian, ValueType::Signed)
+}
+
+const BATTERY_STATES: &[(u32, &str)] = &[
+    (0, "Init"),
+    (1, "Sleep"),
+    (2, "WaitingForStartup"),
+    (3, "Idle"),
+    (4, "OnlyCharge"),
+    (5, "OnlyDischarge"),
+    (6, "On"),
+];
+const CHARGE_STATES: &[(u32, &str)] = &[
+    (0, "Init"),
+    (1, "Idle"),
+    (2, "RelayOn"),
+    (3, "FetOn"),
+    (4, "Error"),
+    (5, "FetOff"),
+];
+const DISCHARGE_STATES: &[(u32, &str)] = &[
+    (0, "Init"),
+    (1, "Idle"),
+    (2, "PreChargeOn"),
+    (3, "On"),
+    (4, "PreChargeTimeout"),
+    (5, "Error"),
+];
+const GNSS_FIX_MODES: &[(u32, &str)] = &[(0, "Unknown"), (1, "NoFix"), (2, "Fix2D"), (3, "Fix3D")];
+const SERVO_STATES: &[(u32, &str)] = &[(0, "Uninitialized"), (1, "Operational"), (0xFF, "Unknown")];
+const SERVO_COMMANDS: &[(u32, &str)] = &[(0, "Initialize")];
+const DISPLAY_PAGES: &[(u32, &str)] = &[
+    (0, "Main"),
+    (1, "Diagnostics"),
+    (2, "Render"),
+    (3, "Strategy"),
+    (4, "Commissioning"),
+    (5, "Trim"),
+    (6, "Alarms"),
+    (7, "Pilot"),
+    (8, "Cells"),
+    (9, "Exhibition"),
+    (10, "Inventory"),
+    (11, "Parked"),
+    (12, "Throttle"),
+];
+const DISPLAY_PROFILES: &[(u32, &str)] = &[
+    (0, "Race"),
+    (1, "TestBench"),
+    (2, "Charging"),
+    (3, "Diagnostics"),
+    (4, "Exhibition"),
+];
+const HEIGHT_SENSOR_STATES: &[(u32, &str)] = &[
+    (0, "NotPluggedIn"),
+    (1, "ModbusError"),
+    (2, "Operational"),
+    (0xFF, "Unknown"),
+];
+const GAN_PHASE_MODES: &[(u32, &str)] = &[
+    (0, "None"),
+    (1, "Civ"),
+    (2, "Cic"),
+    (3, "MinInputCurrent"),
+    (4, "Cov"),
+    (5, "Coc"),
+    (6, "TemperatureDerating"),
+    (7, "Fault"),
+];
+const GAN_PHASE_FAULTS: &[(u32, &str)] = &[
+    (0, "Ok"),
+    (1, "ConfigError"),
+    (2, "InputOverVoltage"),
+    (3, "OutputOverVoltage"),
+    (4, "OutputOverCurrent"),
+    (5, "InputOverCurrent"),
+    (6, "InputUnderCurrent"),
+    (7, "PhaseOverCurrent"),
+    (8, "GeneralFault"),
+];
+
+const HEIGHT_SENSOR_SIGNALS: &[SignalDefinition] = &[
+    le_u("State", 0, 8).with_values(HEIGHT_SENSOR_STATES),
+    le_u("Value", 8, 16),
+];
+
+const MPPT_CHANNEL_POWER_SIGNALS: &[SignalDefinition] =
+    &[le_f32("VoltageIn", 0, "V"), le_f32("CurrentIn", 32, "A")];
+
+const MPPT_CHANNEL_STATE_SIGNALS: &[SignalDefinition] = &[
+    le_u("DutyCycle", 0, 16),
+    le_u("Algorithm", 16, 8),
+    le_u("AlgorithmState", 24, 8),
+    le_u("ChannelActive", 32, 8),
+];
+
+const MPPT_NODES: u8 = 8;
+const MPPT_NODE_STRIDE: u32 = 0x10;
+const GAN_MPPT_NODES: u8 = 16;
+const GAN_MPPT_NODE_STRIDE: u32 = 0x10;
+const VESC_ID: u32 = DecoderConfig::DEFAULT_VESC_CONTROLLER_ID as u32;
+
+pub const MESSAGES: &[MessageDefinition] = &[
+    // Rudder controller
+    MessageDefinition::new(
+        "ServoRudderSetpoint",
+        0x010,
+        2,
+        "RudderController",
+        &[le_u("Setpoint", 0, 16)],
+    ),
+    MessageDefinition::new(
+        "ServoRudderStatus",
+        0x020,
+        3,
+        "RudderController",
+        &[
+            le_u("State", 0, 8).with_values(SERVO_STATES),
+            le_u("Setpoint", 8, 16),
+        ],
+    ),
+    MessageDefinition::new(
+        "ServoRudderCommand",
+        0x021,
+        1,
+        "RudderController",
+        &[le_u("Command", 0, 8).with_values(SERVO_COMMANDS)],
+    ),
+    // Height sensors
+    MessageDefinition::new(
+        "HeightSensorFrontLeft",
+        0x011,
+        3,
+        "HeightSensors",
+        HEIGHT_SENSOR_SIGNALS,
+    ),
+    MessageDefinition::new(
+        "HeightSensorFrontRight",
+        0x012,
+        3,
+        "HeightSensors",
+        HEIGHT_SENSOR_SIGNALS,
+    ),
+    MessageDefinition::new(
+        "HeightSensorReserved1",
+        0x013,
+        3,
+        "HeightSensors",
+        HEIGHT_SENSOR_SIGNALS,
+    ),
+    MessageDefinition::new(
+        "HeightSensorReserved2",
+        0x014,
+        3,
+        "HeightSensors",
+        HEIGHT_SENSOR_SIGNALS,
+    ),
+    // Controller temperatures
+    MessageDefinition::new(
+        "TemperatureHeightSensorsController",
+        0x210,
+        2,
+        "HeightSensors",
+        &[le_i("Temperature", 0, 16).scaled(0.01, "C")],
+    ),
+    MessageDefinition::new(
+        "TemperatureRudderController",
+        0x211,
+        2,
+        "RudderController",
+        &[le_i("Temperature", 0, 16).scaled(0.01, "C")],
+    ),
+    // Battery management system
+    MessageDefinition::new(
+        "PackAndPerriCurrent",
+        0x100,
+        8,
+        "BMS",
+        &[
+            le_f32("PackCurrent", 0, "A"),
+            le_f32("PerriCurrent", 32, "A"),
+        ],
+    ),
+    MessageDefinition::new(
+        "ChargeAndDischargeCurrent",
+        0x101,
+        8,
+        "BMS",
+        &[
+            le_f32("ChargeCurrent", 0, "A"),
+            le_f32("DischargeCurrent", 32, "A").scaled(-1.0, "A"),
+        ],
+    ),
+    MessageDefinition::new(
+        "SocErrorFlagsAndBalancing",
+        0x102,
+        8,
+        "BMS",
+        &[
+            le_u("StateOfCharge", 0, 16).scaled(0.01, "%"),
+            le_u("ErrorFlags", 16, 32),
+            le_u("BalancingStatus", 48, 16),
+        ],
+    ),
+    MessageDefinition::new(
+        "CellVoltages1To4",
+        0x103,
+        8,
+        "BMS",
+        &[
+            le_u("CellVoltage1", 0, 16).scaled(0.001, "V"),
+            le_u("CellVoltage2", 16, 16).scaled(0.001, "V"),
+            le_u("CellVoltage3", 32, 16).scaled(0.001, "V"),
+            le_u("CellVoltage4", 48, 16).scaled(0.001, "V"),
+        ],
+    ),
+    MessageDefinition::new(
+        "CellVoltages5To8",
+        0x104,
+        8,
+        "BMS",
+        &[
+            le_u("CellVoltage5", 0, 16).scaled(0.001, "V"),
+            le_u("CellVoltage6", 16, 16).scaled(0.001, "V"),
+            le_u("CellVoltage7", 32, 16).scaled(0.001, "V"),
+            le_u("CellVoltage8", 48, 16).scaled(0.001, "V"),
+        ],
+    ),
+    MessageDefinition::new(
+        "CellVoltages9To12",
+        0x105,
+        8,
+        "BMS",
+        &[
+            le_u("CellVoltage9", 0, 16).scaled(0.001, "V"),
+            le_u("CellVoltage10", 16, 16).scaled(0.001, "V"),
+            le_u("CellVoltage11", 32, 16).scaled(0.001, "V"),
+            le_u("CellVoltage12", 48, 16).scaled(0.001, "V"),
+        ],
+    ),
+    MessageDefinition::new(
+        "CellVoltages13To14PackAndStack",
+        0x106,
+        8,
+        "BMS",
+        &[
+            le_u("CellVoltage13", 0, 16).scaled(0.001, "V"),
+            le_u("CellVoltage14", 16, 16).scaled(0.001, "V"),
+            le_u("PackVoltage", 32, 16).scaled(0.001, "V"),
+            le_u("StackVoltage", 48, 16).scaled(0.001, "V"),
+        ],
+    ),
+    MessageDefinition::new(
+        "TemperaturesAndStates",
+        0x107,
+        8,
+        "BMS",
+        &[
+            le_i("Temperature1", 0, 8).scaled(1.0, "C"),
+            le_i("Temperature2", 8, 8).scaled(1.0, "C"),
+            le_i("Temperature3", 16, 8).scaled(1.0, "C"),
+            le_i("Temperature4", 24, 8).scaled(1.0, "C"),
+            le_i("IcTemperature", 32, 8).scaled(1.0, "C"),
+            le_u("BatteryState", 40, 8).with_values(BATTERY_STATES),
+            le_u("ChargeState", 48, 8).with_values(CHARGE_STATES),
+            le_u("DischargeState", 56, 8).with_values(DISCHARGE_STATES),
+        ],
+    ),
+    MessageDefinition::new(
+        "BatteryUptime",
+        0x108,
+        4,
+        "BMS",
+        &[le_u("Uptime", 0, 32).scaled(1.0, "ms")],
+    ),
+    MessageDefinition::new(
+        "ChargeControlStatus",
+        0x10A,
+        1,
+        "BMS",
+        &[le_u("ChargingDisabled", 0, 8)],
+    ),
+    MessageDefinition::new(
+        "ChargeControlCommand",
+        0x10B,
+        1,
+        "BMS",
+        &[le_u("ChargingDisabled", 0, 8)],
+    ),
+    MessageDefinition::new(
+        "CellVoltages15To16",
+        0x10C,
+        4,
+        "BMS",
+        &[
+            le_u("CellVoltage15", 0, 16).scaled(0.001, "V"),
+            le_u("CellVoltage16", 16, 16).scaled(0.001, "V"),
+        ],
+    ),
+    MessageDefinition::new(
+        "TimeEstimate",
+        0x10D,
+        4,
+        "BMS",
+        &[
+            le_u("TimeToEmpty", 0, 16).scaled(1.0, "min"),
+            le_u("TimeToFull", 16, 16).scaled(1.0, "min"),
+        ],
+    ),
+    MessageDefinition::new(
+        "Temperatures5To8",
+        0x10E,
+        4,
+        "BMS",
+        &[
+            le_i("Temperature5", 0, 8).scaled(1.0, "C"),
+            le_i("Temperature6", 8, 8).scaled(1.0, "C"),
+            le_i("Temperature7", 16, 8).scaled(1.0, "C"),
+            le_i("Temperature8", 24, 8).scaled(1.0, "C"),
+        ],
+    ),
+    // GNSS
+    MessageDefinition::new(
+        "GnssStatus",
+        0x200,
+        3,
+        "GNSS",
+        &[
+            le_u("Fix", 0, 8),
+            le_u("Satellites", 8, 8),
+            le_u("SatellitesUsed", 16, 8),
+        ],
+    ),
+    MessageDefinition::new(
+        "GnssSpeedAndHeading",
+        0x201,
+        8,
+        "GNSS",
+        &[le_f32("Speed", 0, "km/h"), le_f32("Heading", 32, "deg")],
+    ),
+    MessageDefinition::new(
+        "GnssLatitude",
+        0x202,
+        8,
+        "GNSS",
+        &[le_f64("Latitude", 0, "deg")],
+    ),
+    MessageDefinition::new(
+        "GnssLongitude",
+        0x203,
+        8,
+        "GNSS",
+        &[le_f64("Longitude", 0, "deg")],
+    ),
+    MessageDefinition::new(
+        "GnssDateTime",
+        0x204,
+        7,
+        "GNSS",
+        &[
+            le_u("Year", 0, 16),
+            le_u("Month", 16, 8),
+            le_u("Day", 24, 8),
+            le_u("Hours", 32, 8),
+            le_u("Minutes", 40, 8),
+            le_u("Seconds", 48, 8),
+        ],
+    ),
+    MessageDefinition::new(
+        "GnssAltitude",
+        0x205,
+        4,
+        "GNSS",
+        &[le_f32("Altitude", 0, "m")],
+    ),
+    MessageDefinition::new(
+        "GnssFixQuality",
+        0x206,
+        3,
+        "GNSS",
+        &[
+            le_u("FixMode", 0, 8).with_values(GNSS_FIX_MODES),
+            le_u("HorizontalError", 8, 16).scaled(0.01, "m"),
+        ],
+    ),
+    MessageDefinition::new(
+        "GnssUtcOffset",
+        0x207,
+        2,
+        "GNSS",
+        &[le_i("UtcOffset", 0, 16).scaled(1.0, "min")],
+    ),
+    MessageDefinition::new(
+        "GnssTimeSync",
+        0x208,
+        8,
+        "GNSS",
+        &[le_u("Utc", 0, 64).scaled(1.0, "us")],
+    ),
+    // Peripheral power monitor, one message per consumer
+    MessageDefinition::new(
+        "PeripheralPower",
+        PERIPHERAL_POWER_ID as u32,
+        4,
+        "PowerMonitor",
+        &[le_f32("Current", 0, "A")],
+    )
+    .per_node(PeripheralConsumer::ALL.len() as u8, 1),
+    MessageDefinition::new(
+        "WaterSpeed",
+        0x250,
+        8,
+        "WaterSpeedSensor",
+        &[
+            le_f32("Speed", 0, "km/h"),
+            le_f32("PulseFrequency", 32, "Hz"),
+        ],
+    ),
+    // The heave rate is -32768 when the IMU doesn't estimate it
+    MessageDefinition::new(
+        "ImuAttitude",
+        0x260,
+        6,
+        "IMU",
+        &[
+            le_i("Pitch", 0, 16).scaled(0.01, "deg"),
+            le_i("Roll", 16, 16).scaled(0.01, "deg"),
+            le_i("HeaveRate", 32, 16).scaled(0.001, "m/s"),
+        ],
+    ),
+    // One CAN ID per node type, sent at boot and every minute
+    MessageDefinition::new(
+        "NodeIdentification",
+        NODE_IDENTIFICATION_ID as u32,
+        8,
+        "Node",
+        &[
+            le_u("VersionMajor", 0, 8),
+            le_u("VersionMinor", 8, 8),
+            le_u("VersionPatch", 16, 8),
+            le_u("Dirty", 24, 1),
+            be_u("GitHash", 32, 32),
+        ],
+    )
+    .per_node(NodeType::ALL.len() as u8, 1),
+    // Display
+    MessageDefinition::new(
+        "DisplayHeartbeat",
+        0x230,
+        8,
+        "Display",
+        &[
+            le_u("Uptime", 0, 32).scaled(1.0, "s"),
+            le_u("FirmwareVersionMajor", 32, 8),
+            le_u("FirmwareVersionMinor", 40, 8),
+            le_u("FirmwareVersionPatch", 48, 8),
+            le_u("LastRefreshAge", 56, 8).scaled(1.0, "s"),
+        ],
+    ),
+    // The text in bytes 2 to 7 has no signal
+    MessageDefinition::new(
+        "DisplayLog",
+        0x231,
+        8,
+        "Display",
+        &[
+            le_u("Level", 0, 3),
+            le_u("Continued", 7, 1),
+            le_u("Sequence", 8, 8),
+        ],
+    )
+    .variable_length(2),
+    MessageDefinition::new(
+        "DisplayBattery",
+        0x232,
+        3,
+        "Datalogger",
+        &[
+            le_u("StateOfCharge", 0, 16).scaled(0.01, "%"),
+            le_u("Charging", 16, 8),
+        ],
+    ),
+    MessageDefinition::new(
+        "DisplayPage",
+        0x233,
+        1,
+        "Datalogger",
+        &[le_u("Page", 0, 8).with_values(DISPLAY_PAGES)],
+    ),
+    // One ID per part, counted as nodes. The text in bytes 1 to 7 has no signal
+    MessageDefinition::new(
+        "DisplayMessage",
+        0x234,
+        8,
+        "Datalogger",
+        &[le_u("MessageId", 0, 7), le_u("Last", 7, 1)],
+    )
+    .per_node(DISPLAY_MESSAGE_PARTS as u8, 1)
+    .variable_length(1),
+    MessageDefinition::new(
+        "DisplayAcknowledge",
+        0x238,
+        1,
+        "Display",
+        &[le_u("MessageId", 0, 7)],
+    ),
+    MessageDefinition::new(
+        "DisplayProfile",
+        0x239,
+        1,
+        "Datalogger",
+        &[le_u("Profile", 0, 8).with_values(DISPLAY_PROFILES)],
+    ),
+    MessageDefinition::new(
+        "DisplayRaceEnd",
+        0x23A,
+        4,
+        "Datalogger",
+        &[le_u("RaceEnd", 0, 32).scaled(1.0, "s")],
+    ),
+    MessageDefinition::new("DisplayTripReset", 0x23E, 0, "Datalogger", &[]),
+    // The text in bytes 1 to 7 has no signal
+    MessageDefinition::new(
+        "DisplayPanic",
+        0x23B,
+        8,
+        "Display",
+        &[le_u("Part", 0, 7), le_u("Last", 7, 1)],
+    )
+    .variable_length(1),
+    // MPPT solar controllers, CAN ID = 0x700 | (mppt_id << 4) | field_id
+    MessageDefinition::new(
+        "MpptChannel0Power",
+        0x700,
+        8,
+        "MPPT",
+        MPPT_CHANNEL_POWER_SIGNALS,
+    )
+    .per_node(MPPT_NODES, MPPT_NODE_STRIDE),
+    MessageDefinition::new(
+        "MpptChannel0State",
+        0x701,
+        5,
+        "MPPT",
+        MPPT_CHANNEL_STATE_SIGNALS,
+    )
+    .per_node(MPPT_NODES, MPPT_NODE_STRIDE),
+    MessageDefinition::new(
+        "MpptChannel1Power",
+        0x702,
+        8,
+        "MPPT",
+        MPPT_CHANNEL_POWER_SIGNALS,
+    )
+    .per_node(MPPT_NODES, MPPT_NODE_STRIDE),
+    MessageDefinition::new(
+        "MpptChannel1State",
+        0x703,
+        5,
+        "MPPT",
+        MPPT_CHANNEL_STATE_SIGNALS,
+    )
+    .per_node(MPPT_NODES, MPPT_NODE_STRIDE),
+    MessageDefinition::new(
+        "MpptChannel2Power",
+        0x704,
+        8,
+        "MPPT",
+        MPPT_CHANNEL_POWER_SIGNALS,
+    )
+    .per_node(MPPT_NODES, MPPT_NODE_STRIDE),
+    MessageDefinition::new(
+        "MpptChannel2State",
+        0x705,
+        5,
+        "MPPT",
+        MPPT_CHANNEL_STATE_SIGNALS,
+    )
+    .per_node(MPPT_NODES, MPPT_NODE_STRIDE),
+    MessageDefinition::new(
+        "MpptChannel3Power",
+        0x706,
+        8,
+        "MPPT",
+        MPPT_CHANNEL_POWER_SIGNALS,
+    )
+    .per_node(MPPT_NODES, MPPT_NODE_STRIDE),
+    MessageDefinition::new(
+        "MpptChannel3State",
+        0x707,
+        5,
+        "MPPT",
+        MPPT_CHANNEL_STATE_SIGNALS,
+    )
+    .per_node(MPPT_NODES, MPPT_NODE_STRIDE),
+    MessageDefinition::new(
+        "MpptPower",
+        0x708,
+        8,
+        "MPPT",
+        &[le_f32("VoltageOut", 0, "V"), le_f32("CurrentOut", 32, "A")],
+    )
+    .per_node(MPPT_NODES, MPPT_NODE_STRIDE),
+    MessageDefinition::new(
+        "MpptStatus",
+        0x709,
+        8,
+        "MPPT",
+        &[
+            le_f32("VoltageOutSwitch", 0, "V"),
+            le_i("Temperature", 32, 16).scaled(1.0, "C"),
+            le_u("State", 48, 8),
+            le_u("PwmEnabled", 56, 1),
+            le_u("SwitchOn", 57, 1),
+        ],
+    )
+    .per_node(MPPT_NODES, MPPT_NODE_STRIDE),
+    // GaN MPPT solar controllers, CAN ID = ((node_id + 64) << 4) | packet_id
+    MessageDefinition::new(
+        "GanMpptPower",
+        0x400,
+        8,
+        "GanMPPT",
+        &[
+            be_i("InputVoltage", 0, 16).scaled(0.01, "V"),
+            be_i("InputCurrent", 16, 16).scaled(0.0005, "A"),
+            be_i("OutputVoltage", 32, 16).scaled(0.01, "V"),
+            be_i("OutputCurrent", 48, 16).scaled(0.0005, "A"),
+        ],
+    )
+    .per_node(GAN_MPPT_NODES, GAN_MPPT_NODE_STRIDE),
+    MessageDefinition::new(
+        "GanMpptStatus",
+        0x401,
+        5,
+        "GanMPPT",
+        &[
+            le_u("Mode", 0, 8).with_values(GAN_PHASE_MODES),
+            le_u("Fault", 8, 8).with_values(GAN_PHASE_FAULTS),
+            le_u("Enabled", 16, 8),
+            le_i("BoardTemperature", 24, 8).scaled(1.0, "C"),
+            le_i("HeatSinkTemperature", 32, 8).scaled(1.0, "C"),
+        ],
+    )
+    .per_node(GAN_MPPT_NODES, GAN_MPPT_NODE_STRIDE),
+    MessageDefinition::new(
+        "GanMpptSweepData",
+        0x402,
+        5,
+        "GanMPPT",
+        &[
+            le_u("Index", 0, 8),
+            be_i("Current", 8, 16).scaled(0.0005, "A"),
+            be_i("Voltage", 24, 16).scaled(0.01, "V"),
+        ],
+    )
+    .per_node(GAN_MPPT_NODES, GAN_MPPT_NODE_STRIDE),
+    // Throttle, the commands to the VESC use the VESC CAN ID scheme
+    MessageDefinition::new(
+        "ThrottleToVescDutyCycle",
+        VESC_ID,
+        4,
+        "Throttle",
+        &[be_i("DutyCycle", 0, 32).scaled(0.001, "%")],
+    )
+    .extended(),
+    MessageDefinition::new(
+        "ThrottleToVescCurrent",
+        (1 << 8) | VESC_ID,
+        4,
+        "Throttle",
+        &[be_i("Current", 0, 32).scaled(0.001, "A")],
+    )
+    .extended(),
+    MessageDefinition::new(
+        "ThrottleToVescRpm",
+        (3 << 8) | VESC_ID,
+        4,
+        "Throttle",
+        &[be_i("Rpm", 0, 32).scaled(0.001, "rpm")],
+    )
+    .extended(),
+    // ThrottleConfig uses the same IDs and is only told apart by its DLC of 6, which can't be
+    // described here, so only the status is listed. Its DLC is let through as the shortest one
+    MessageDefinition::new(
+        "ThrottleStatus",
+        0x1337,
+        8,
+        "Throttle",
+        THROTTLE_STATUS_SIGNALS,
+    )
+    .extended()
+    .variable_length(6),
+    MessageDefinition::new(
+        "ThrottleStatusLegacy",
+        0x0337,
+        8,
+        "Throttle",
+        THROTTLE_STATUS_SIGNALS,
+    )
+    .variable_length(6),
+    // VESC motor controller, CAN ID = (packet_id << 8) | controller_id. Firmware version and pong
+    // are addressed to the requesting node and the fault is told apart by its payload, therefore
+    // they are not listed
+    MessageDefinition::new(
+        "VescStatusMessage1",
+        (9 << 8) | VESC_ID,
+        8,
+        "VESC",
+        &[
+            be_i("Rpm", 0, 32).scaled(1.0, "rpm"),
+            be_i("TotalCurrent", 32, 16).scaled(0.1, "A"),
+            be_i("DutyCycle", 48, 16).scaled(0.1, "%"),
+        ],
+    )
+    .extended(),
+    MessageDefinition::new(
+        "VescStatusMessage2",
+        (14 << 8) | VESC_ID,
+        8,
+        "VESC",
+        &[
+            be_u("AmpHoursUsed", 0, 32).scaled(0.0001, "Ah"),
+            be_u("AmpHoursGenerated", 32, 32).scaled(0.0001, "Ah"),
+        ],
+    )
+    .extended(),
+    MessageDefinition::new(
+        "VescStatusMessage3",
+        (15 << 8) | VESC_ID,
+        8,
+        "VESC",
+        &[
+            be_u("WattHoursUsed", 0, 32).scaled(0.0001, "Wh"),
+            be_u("WattHoursGenerated", 32, 32).scaled(0.0001, "Wh"),
+        ],
+    )
+    .extended(),
+    MessageDefinition::new(
+        "VescStatusMessage4",
+        (16 << 8) | VESC_ID,
+        8,
+        "VESC",
+        &[
+            be_i("FetTemperature", 0, 16).scaled(0.1, "C"),
+            be_i("MotorTemperature", 16, 16).scaled(0.1, "C"),
+            be_i("TotalInputCurrent", 32, 16).scaled(0.1, "A"),
+            be_i("CurrentPidPosition", 48, 16).scaled(0.02, ""),
+        ],
+    )
+    .extended(),
+    MessageDefinition::new(
+        "VescPing",
+        (17 << 8) | VESC_ID,
+        1,
+        "VESC",
+        &[le_u("SenderId", 0, 8)],
+    )
+    .extended(),
+    MessageDefinition::new(
+        "VescStatusMessage5",
+        (27 << 8) | VESC_ID,
+        8,
+        "VESC",
+        &[
+            be_i("Tachometer", 0, 32),
+            be_i("InputVoltage", 32, 16).scaled(0.1, "V"),
+        ],
+    )
+    .extended(),
+    MessageDefinition::new(
+        "VescStatusMessage6",
+        (58 << 8) | VESC_ID,
+        8,
+        "VESC",
+        &[
+            be_i("Adc1", 0, 16).scaled(0.001, "V"),
+            be_i("Adc2", 16, 16).scaled(0.001, "V"),
+            be_i("Adc3", 32, 16).scaled(0.001, "V"),
+            be_i("Ppm", 48, 16).scaled(0.001, ""),
+        ],
+    )
+    .extended(),
+];
+
+const THROTTLE_STATUS_SIGNALS: &[SignalDefinition] = &[
+    be_i("Value", 0, 16).scaled(100.0 / 512.0, "%"),
+    be_i("RawAngle", 16, 16),
+    be_i("RawDeadman", 32, 16),
+    le_u("Gain", 48, 8),
+    le_u("TwiError", 56, 3),
+    le_u("NoEeprom", 59, 1),
+    le_u("GainClipping", 60, 1),
+    le_u("GainInvalid", 61, 1),
+    le_u("DeadmanMissing", 62, 1),
+    le_u("ImpedanceHigh", 63, 1),
+];
+
+/// Find a message definition by its name, like "MpptPower"
+pub fn find_message_by_name(name: &str) -> Option<&'static MessageDefinition> {
+    MESSAGES.iter().find(|message| message.name == name)
+}
+
+/// A frame of every message and node with all bytes set to `pattern`, for tests checking that no
+/// signal of the registry gets lost between the decoder and the displays or bridges
+pub fn sample_frames(
+    pattern: u8,
+) -> impl Iterator<Item = (&'static MessageDefinition, u8, CanFrame)> {
+    MESSAGES.iter().flat_map(move |message| {
+        message.ids().filter_map(move |(node, _)| {
+            let data = [pattern; 8];
+            let frame = CanFrame::try_new(message.can_id(node)?, &data[..message.dlc.into()]);
+            Some((message, node, frame.ok()?))
+        })
+    })
+}
+
+/// Find the message definition and node for a received CAN ID
+pub fn find_message(id: u32, extended: bool) -> Option<(&'static MessageDefinition, u8)> {
+    MESSAGES.iter().find_map(|message| {
+        if message.extended != extended {
+            return None;
+        }
+        message
+            .ids()
+            .find(|(_, node_id)| *node_id == id)
+            .map(|(node, _)| (message, node))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_eoi_can_data;
+    use assert2::assert;
+
+    #[test]
+    fn every_message_is_known_to_the_decoder() {
+        for pattern in [0x00, 0x01] {
+            for (message, node, frame) in sample_frames(pattern) {
+                assert!(
+                    parse_eoi_can_data(&frame).is_some(),
+                    "{} of node {} not decoded from {:#04X}",
+                    message.name,
+                    node,
+                    pattern
+                );
+                for signal in message.signals {
+                    assert!(
+                        signal.decode(&frame.data).is_some(),
+                        "{}.{}",
+                        message.name,
+                        signal.name
+                    );
+                }
+            }
+        }
+        assert!(
+            sample_frames(0).count()
+                == MESSAGES
+                    .iter()
+                    .map(|message| usize::from(message.node_count))
+                    .sum()
+        );
+    }
+
+    #[test]
+    fn signals_fit_in_the_message() {
+        for message in MESSAGES {
+            for signal in message.signals {
+                assert!(
+                    signal.start as u32 + signal.length as u32 <= message.dlc as u32 * 8,
+                    "{}.{} does not fit",
+                    message.name,
+                    signal.name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn ids_are_unique() {
+        for message in MESSAGES {
+            for (_, id) in message.ids() {
+                let (found, _) = find_message(id, message.extended).unwrap();
+                assert!(found.name == message.name);
+            }
+        }
+    }
+
+    #[test]
+    fn big_endian_signals_decode_like_the_decoder() {
+        // Motorola start bit, the registry gives the first byte like the DBC export
+        let data = crate::VescData::from_node_id(
+            VESC_ID as u8,
+            crate::VescMessage::StatusMessage1 {
+                rpm: -1200,
+                total_current: 12.5,
+                duty_cycle: 40.0,
+            },
+        )
+        .unwrap();
+        let frame =
+            crate::can_encoder::encode_eoi_can_data(&crate::EoiCanData::Vesc(data)).unwrap();
+        let status = find_message_by_name("VescStatusMessage1").unwrap();
+        assert!(status.signals[0].decode(&frame.data) == Some(-1200.0));
+        let total_current = status.signals[1].decode(&frame.data).unwrap();
+        assert!((total_current - 12.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn encode_round_trips() {
+        for message in MESSAGES {
+            let pattern = [0xA5; 8];
+            for signal in message.signals {
+                let value = signal.decode(&pattern).unwrap();
+                let mut data = [0; 8];
+                signal.encode(&mut data, value).unwrap();
+                assert!(
+                    signal.decode(&data) == Some(value),
+                    "{}.{}",
+                    message.name,
+                    signal.name
+                );
+            }
+        }
+
+        let soc = find_message_by_name("SocErrorFlagsAndBalancing").unwrap();
+        let mut data = [0; 2];
+        // Limited to the 16 bits of the signal
+        soc.signals[0].encode(&mut data, 1e9).unwrap();
+        assert!(data == [0xFF, 0xFF]);
+        assert!(soc.signals[1].encode(&mut data, 1.0).is_none());
+    }
+
+    #[test]
+    fn find_mppt_node() {
+        let (message, node) = find_message(0x738, false).unwrap();
+        assert!(message.name == "MpptPower");
+        assert!(node == 3);
+    }
+}
@@ -0,0 +1,169 @@
+//! A shared time base from the [`GnssData::GnssTimeSync`](crate::GnssData::GnssTimeSync) frames
+//! of the GNSS bridge. A node keeps the offset of its own monotonic clock to UTC, so its log
+//! timestamps can be correlated with those of the data logger. Works without an allocator for the
+//! firmware, the local time is whatever monotonic clock the node has in microseconds, like the
+//! uptime of embassy.
+
+use crate::GnssDateTime;
+
+/// Syncs closer together than this don't update the drift, the jitter of the bus would dominate
+const MIN_DRIFT_INTERVAL_US: u64 = 10_000_000;
+/// An offset changing more than this at once is a step of the clock of the data logger, like when
+/// it got the time from GNSS after booting. The drift starts over then
+const MAX_OFFSET_CHANGE_US: i64 = 1_000_000;
+/// Weight of a new drift measurement, the rest is the estimate so far
+const DRIFT_SMOOTHING: f32 = 0.25;
+
+/// Offset and drift of the local clock to UTC
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ClockSync {
+    /// UTC minus local time at the latest sync in microseconds
+    offset_us: Option<i64>,
+    /// Local time of the latest sync
+    synced_at_us: u64,
+    /// Local time the drift is measured from
+    drift_since_us: u64,
+    offset_at_drift_since_us: i64,
+    /// How much faster UTC runs than the local clock, in parts per million
+    drift_ppm: f32,
+}
+
+impl ClockSync {
+    pub const fn new() -> Self {
+        Self {
+            offset_us: None,
+            synced_at_us: 0,
+            drift_since_us: 0,
+            offset_at_drift_since_us: 0,
+            drift_ppm: 0.0,
+        }
+    }
+
+    /// Take a sync frame with the UTC `utc_us` received at local time `local_us`. Returns whether
+    /// the clock stepped, like on the first sync
+    pub fn sync(&mut self, local_us: u64, utc_us: u64) -> bool {
+        let offset_us = utc_us as i64 - local_us as i64;
+        let stepped = self.offset_us.is_none_or(|previous| {
+            let predicted = previous + self.drift_us(local_us);
+            (offset_us - predicted).abs() > MAX_OFFSET_CHANGE_US
+        });
+        if stepped {
+            self.drift_since_us = local_us;
+            self.offset_at_drift_since_us = offset_us;
+            self.drift_ppm = 0.0;
+        } else if local_us.saturating_sub(self.drift_since_us) >= MIN_DRIFT_INTERVAL_US {
+            let elapsed_us = (local_us - self.drift_since_us) as f32;
+            let measured_ppm =
+                (offset_us - self.offset_at_drift_since_us) as f32 / elapsed_us * 1e6;
+            self.drift_ppm += DRIFT_SMOOTHING * (measured_ppm - self.drift_ppm);
+            self.drift_since_us = local_us;
+            self.offset_at_drift_since_us = offset_us;
+        }
+        self.offset_us = Some(offset_us);
+        self.synced_at_us = local_us;
+        stepped
+    }
+
+    /// UTC in microseconds since the Unix epoch at local time `local_us`, `None` before the first
+    /// sync
+    pub fn utc_us(&self, local_us: u64) -> Option<u64> {
+        let offset_us = self.offset_us?;
+        let utc_us = local_us as i64 + offset_us + self.drift_us(local_us);
+        u64::try_from(utc_us).ok()
+    }
+
+    /// UTC date and time at local time `local_us`, like for setting a real time clock
+    pub fn date_time(&self, local_us: u64) -> Option<GnssDateTime> {
+        Some(date_time(self.utc_us(local_us)?))
+    }
+
+    /// UTC minus local time at the latest sync in microseconds
+    pub fn offset_us(&self) -> Option<i64> {
+        self.offset_us
+    }
+
+    /// How much faster UTC runs than the local clock, in parts per million
+    pub fn drift_ppm(&self) -> f32 {
+        self.drift_ppm
+    }
+
+    /// Change of the offset since the latest sync from the drift
+    fn drift_us(&self, local_us: u64) -> i64 {
+        let since_sync_us = local_us as i64 - self.synced_at_us as i64;
+        (since_sync_us as f32 * self.drift_ppm / 1e6) as i64
+    }
+}
+
+/// UTC date and time of `utc_us` microseconds since the Unix epoch
+pub fn date_time(utc_us: u64) -> GnssDateTime {
+    let seconds = utc_us / 1_000_000;
+    // From https://howardhinnant.github.io/date_algorithms.html
+    let days = (seconds / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u8;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u8;
+    let year = (year_of_era + era * 400 + i64::from(month <= 2)) as u16;
+    GnssDateTime {
+        year,
+        month,
+        day,
+        hours: (seconds % 86400 / 3600) as u8,
+        minutes: (seconds % 3600 / 60) as u8,
+        seconds: (seconds % 60) as u8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+
+    /// 2025-06-14 12:30:05 UTC
+    const UTC_US: u64 = 1_749_904_205_000_000;
+
+    #[test]
+    fn offset_and_drift() {
+        let mut clock = ClockSync::new();
+        assert!(clock.utc_us(1_000).is_none());
+
+        assert!(clock.sync(5_000_000, UTC_US));
+        assert!(clock.utc_us(6_000_000) == Some(UTC_US + 1_000_000));
+
+        // The local clock runs 100 ppm slow, UTC gains 1 ms in 10 s
+        for second in 1..=20 {
+            let local_us = 5_000_000 + second * 10_000_000;
+            assert!(!clock.sync(local_us, UTC_US + second * 10_001_000));
+        }
+        assert!((clock.drift_ppm() - 100.0).abs() < 1.0);
+        let local_us = 5_000_000 + 210_000_000;
+        let expected = UTC_US + 21 * 10_001_000;
+        assert!(clock.utc_us(local_us).unwrap().abs_diff(expected) < 50);
+
+        // The data logger set its clock, the drift starts over
+        assert!(clock.sync(local_us, expected + 60_000_000));
+        assert!(clock.drift_ppm() == 0.0);
+        assert!(clock.utc_us(local_us) == Some(expected + 60_000_000));
+    }
+
+    #[test]
+    fn dates() {
+        let date_time = date_time(UTC_US + 250_000);
+        assert!(date_time.year == 2025);
+        assert!(date_time.month == 6);
+        assert!(date_time.day == 14);
+        assert!(date_time.hours == 12);
+        assert!(date_time.minutes == 30);
+        assert!(date_time.seconds == 5);
+        assert!(super::date_time(0).year == 1970);
+    }
+}
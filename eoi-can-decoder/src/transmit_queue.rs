@@ -0,0 +1,181 @@
+//! Frames waiting for the bus, sent by priority. When the bus is busy commands mustn't wait
+//! behind telemetry, and when frames pile up the oldest of their class are dropped, the newer ones
+//! are more up to date. Works without an allocator for the firmware, the Linux binaries use it
+//! through `eoi-can-source`.
+
+use heapless::Deque;
+
+use crate::{EoiBattery, EoiCanData, GnssData, RudderControllerData, ServoData, ThrottleData};
+
+/// Priority classes, sent in this order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Priority {
+    /// What stops the motor or the charging
+    Safety,
+    /// Commands and what the pilot did, like acknowledging a message
+    Control,
+    Telemetry,
+    /// Log records of the nodes, a burst of them mustn't push out the heartbeat
+    Log,
+}
+
+impl Priority {
+    const ALL: [Priority; 4] = [
+        Priority::Safety,
+        Priority::Control,
+        Priority::Telemetry,
+        Priority::Log,
+    ];
+
+    pub fn of(data: &EoiCanData) -> Self {
+        match data {
+            EoiCanData::Throttle(
+                ThrottleData::ToVescDutyCycle(_)
+                | ThrottleData::ToVescCurrent(_)
+                | ThrottleData::ToVescRpm(_),
+            )
+            | EoiCanData::EoiBattery(EoiBattery::ChargeControlCommand(_)) => Priority::Safety,
+            EoiCanData::Throttle(ThrottleData::Config(_))
+            | EoiCanData::RudderController(RudderControllerData::Servo(
+                ServoData::Setpoint(_) | ServoData::Command(_),
+            ))
+            | EoiCanData::DisplayControl(_)
+            | EoiCanData::DisplayAcknowledge(_) => Priority::Control,
+            // Time spent queued is an error of the synced clocks
+            EoiCanData::Gnss(GnssData::GnssTimeSync(_)) => Priority::Control,
+            EoiCanData::DisplayLog(_) => Priority::Log,
+            _ => Priority::Telemetry,
+        }
+    }
+}
+
+/// Up to `N` frames per priority class
+#[derive(Debug)]
+pub struct TransmitQueue<T, const N: usize> {
+    classes: [Deque<T, N>; 4],
+    dropped: [u32; 4],
+}
+
+impl<T, const N: usize> Default for TransmitQueue<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> TransmitQueue<T, N> {
+    pub const fn new() -> Self {
+        Self {
+            classes: [Deque::new(), Deque::new(), Deque::new(), Deque::new()],
+            dropped: [0; 4],
+        }
+    }
+
+    /// Queue `frame`, returns the oldest frame of its class when that had to be dropped for it
+    pub fn push(&mut self, priority: Priority, frame: T) -> Option<T> {
+        let class = &mut self.classes[priority as usize];
+        let dropped = if class.is_full() {
+            self.dropped[priority as usize] = self.dropped[priority as usize].saturating_add(1);
+            class.pop_front()
+        } else {
+            None
+        };
+        // There is room after dropping, unless the capacity is zero
+        class.push_back(frame).ok();
+        dropped
+    }
+
+    /// The next frame to send, the oldest of the highest priority
+    pub fn pop(&mut self) -> Option<(Priority, T)> {
+        Priority::ALL.into_iter().find_map(|priority| {
+            self.classes[priority as usize]
+                .pop_front()
+                .map(|frame| (priority, frame))
+        })
+    }
+
+    /// Hand the frames to `send` by priority until it returns `false`, like when the transmit
+    /// mailboxes are full. That frame stays first in the queue. Returns how many were sent
+    pub fn transmit(&mut self, mut send: impl FnMut(&T) -> bool) -> usize {
+        let mut sent = 0;
+        for class in &mut self.classes {
+            while let Some(frame) = class.front() {
+                if !send(frame) {
+                    return sent;
+                }
+                class.pop_front();
+                sent += 1;
+            }
+        }
+        sent
+    }
+
+    pub fn len(&self) -> usize {
+        self.classes.iter().map(Deque::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.classes.iter().all(Deque::is_empty)
+    }
+
+    /// Frames of the class dropped since the queue was created
+    pub fn dropped(&self, priority: Priority) -> u32 {
+        self.dropped[priority as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ChargeControl, DisplayAcknowledge, DisplayHeartbeat, DisplayLog, LogLevel};
+    use assert2::assert;
+
+    #[test]
+    fn priority_classes() {
+        let stop = EoiCanData::EoiBattery(EoiBattery::ChargeControlCommand(ChargeControl {
+            charging_disabled: true,
+        }));
+        assert!(Priority::of(&stop) == Priority::Safety);
+        let acknowledge = EoiCanData::DisplayAcknowledge(DisplayAcknowledge { message_id: 3 });
+        assert!(Priority::of(&acknowledge) == Priority::Control);
+        let heartbeat = EoiCanData::DisplayHeartbeat(DisplayHeartbeat {
+            uptime_s: 10,
+            firmware_version_major: 0,
+            firmware_version_minor: 1,
+            firmware_version_patch: 0,
+            last_refresh_age_s: 2,
+        });
+        assert!(Priority::of(&heartbeat) == Priority::Telemetry);
+        let log = EoiCanData::DisplayLog(DisplayLog {
+            level: LogLevel::Warn,
+            sequence: 0,
+            continued: false,
+            text: heapless::String::try_from("Start").unwrap(),
+        });
+        assert!(Priority::of(&log) == Priority::Log);
+    }
+
+    #[test]
+    fn commands_first_and_oldest_dropped() {
+        let mut queue: TransmitQueue<u32, 2> = TransmitQueue::new();
+        assert!(queue.push(Priority::Telemetry, 1).is_none());
+        assert!(queue.push(Priority::Telemetry, 2).is_none());
+        assert!(queue.push(Priority::Telemetry, 3) == Some(1));
+        assert!(queue.dropped(Priority::Telemetry) == 1);
+        queue.push(Priority::Control, 10);
+        queue.push(Priority::Safety, 20);
+        assert!(queue.len() == 4);
+
+        assert!(queue.pop() == Some((Priority::Safety, 20)));
+        // The mailboxes take one frame
+        let mut mailboxes = 1;
+        let sent = queue.transmit(|_| {
+            mailboxes -= 1;
+            mailboxes >= 0
+        });
+        assert!(sent == 1);
+        assert!(queue.pop() == Some((Priority::Telemetry, 2)));
+        assert!(queue.pop() == Some((Priority::Telemetry, 3)));
+        assert!(queue.is_empty());
+    }
+}
@@ -0,0 +1,53 @@
+//! Detection of the bitrate of the bus, so the same display works on the boat at 1 Mbit/s and on
+//! the test bench at 500 kbit/s. The peripheral only listens while probing: at the wrong bitrate a
+//! normal node would send error frames and disturb the bus.
+
+use embassy_stm32::can::Can;
+use embassy_time::{with_timeout, Duration, Instant};
+
+/// Bitrates probed in this order, after the one of the last start
+pub const BITRATES: [u32; 3] = [1_000_000, 500_000, 250_000];
+/// Bitrate of the boat, used when no frame was received at any bitrate
+pub const DEFAULT_BITRATE: u32 = BITRATES[0];
+
+/// How long to listen at a bitrate. The BMS alone sends several frames in this time
+const PROBE_WINDOW: Duration = Duration::from_millis(300);
+/// The display starts anyway after this, with the bus silent there is nothing to detect
+const DETECTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The bitrate a frame was received at, trying `last` first. `None` when the bus stayed silent.
+/// Leaves `can` enabled in silent mode
+pub async fn detect(can: &mut Can<'_>, last: Option<u32>) -> Option<u32> {
+    let start = Instant::now();
+    let candidates = last.into_iter().chain(
+        BITRATES
+            .into_iter()
+            .filter(|bitrate| Some(*bitrate) != last),
+    );
+    let candidates = candidates
+        .cycle()
+        .take_while(|_| start.elapsed() <= DETECTION_TIMEOUT);
+    for bitrate in candidates {
+        can.modify_config().set_loopback(false).set_silent(true);
+        can.set_bitrate(bitrate);
+        can.enable().await;
+        if receives_frame(can).await {
+            return Some(bitrate);
+        }
+    }
+    None
+}
+
+/// Whether a valid frame arrives within the window. At the wrong bitrate the peripheral only
+/// reports errors
+async fn receives_frame(can: &mut Can<'_>) -> bool {
+    let window_end = Instant::now() + PROBE_WINDOW;
+    while let Some(left) = window_end.checked_duration_since(Instant::now()) {
+        match with_timeout(left, can.read()).await {
+            Ok(Ok(_)) => return true,
+            Ok(Err(_)) => continue,
+            Err(_) => return false,
+        }
+    }
+    false
+}
@@ -0,0 +1,354 @@
+//! Black box of the display with the `black-box` feature: the key signals once a second as
+//! [`BlackBoxRecord`]s in an external SPI NOR flash, like a W25Q16 on SPI1, for the last half hour.
+//! After an incident they are read over CAN with ISO-TP by `eoi-can-black-box`, also when the logs
+//! of the Pi are gone. The flash is a ring of sectors, the oldest sector is erased when the newest
+//! one is full, so a power loss loses one record at most.
+
+use embassy_futures::select::{select, Either};
+use embassy_stm32::can::{CanTx, Frame};
+use embassy_stm32::gpio::Output;
+use embassy_stm32::mode::Blocking;
+use embassy_stm32::spi::Spi;
+use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::mutex::Mutex;
+use embassy_time::{with_timeout, Delay, Duration, Instant, Timer};
+use embedded_can::{Id, StandardId};
+use embedded_hal::spi::{Operation, SpiDevice};
+use embedded_hal_bus::spi::ExclusiveDevice;
+
+#[allow(unused_imports)]
+use defmt::{debug, error, info, trace, warn};
+use draw_display::DisplayData;
+use eoi_can_decoder::black_box::{
+    BlackBoxRecord, BlackBoxRequest, BlackBoxResponse, BLACK_BOX_REQUEST_ID, BLACK_BOX_RESPONSE_ID,
+    ERASED_SEQUENCE, MAX_RESPONSE_LEN, RECORD_LEN,
+};
+use eoi_can_decoder::can_encoder::battery_state_to_u8;
+use eoi_can_decoder::can_frame::CanFrame;
+use eoi_can_decoder::isotp::{
+    separation_time_us, FlowStatus, IsoTpFrame, IsoTpFrameData, Reassembler, Received, Segmenter,
+};
+
+pub type FlashSpi = ExclusiveDevice<Spi<'static, Blocking>, Output<'static>, Delay>;
+pub type SharedCanTx = Mutex<ThreadModeRawMutex, CanTx<'static>>;
+
+/// Erased at once, 4 KiB on all the usual NOR flashes
+const SECTOR_LEN: u32 = 4096;
+/// 16 of them keep 2048 records, 34 minutes. The rest of the flash stays unused
+const SECTORS: u32 = 16;
+const RECORDS_PER_SECTOR: u32 = SECTOR_LEN / RECORD_LEN as u32;
+const RECORDS: u32 = SECTORS * RECORDS_PER_SECTOR;
+const RECORD_INTERVAL: Duration = Duration::from_secs(1);
+/// How long the reader may take to answer the first frame of a response with flow control
+const FLOW_CONTROL_TIMEOUT: Duration = Duration::from_secs(1);
+/// Time between polls while the flash erases or programs, an erase takes up to 400 ms
+const BUSY_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+const WRITE_ENABLE: u8 = 0x06;
+const READ_STATUS: u8 = 0x05;
+const READ_DATA: u8 = 0x03;
+const PAGE_PROGRAM: u8 = 0x02;
+const SECTOR_ERASE: u8 = 0x20;
+/// Write in progress bit of the status register
+const BUSY: u8 = 0x01;
+
+/// Frames of the reader, forwarded by the CAN task
+static REQUEST_FRAMES: Channel<ThreadModeRawMutex, CanFrame, 4> = Channel::new();
+
+/// Whether the frame is for the black box, never without the `black-box` feature
+pub fn is_request(frame: &CanFrame) -> bool {
+    cfg!(feature = "black-box")
+        && frame.id == Id::Standard(StandardId::new(BLACK_BOX_REQUEST_ID).unwrap())
+}
+
+/// Called by the receiver for the frames of [`is_request`]
+pub fn receive(frame: CanFrame) {
+    if REQUEST_FRAMES.try_send(frame).is_err() {
+        warn!("Black box busy, dropping a frame of the reader");
+    }
+}
+
+fn command(command: u8, address: u32) -> [u8; 4] {
+    let address = address.to_be_bytes();
+    [command, address[1], address[2], address[3]]
+}
+
+/// The JEDEC commands every SPI NOR flash has
+struct Flash<S> {
+    spi: S,
+}
+
+impl<S: SpiDevice> Flash<S> {
+    fn read(&mut self, address: u32, buffer: &mut [u8]) -> Result<(), S::Error> {
+        self.spi.transaction(&mut [
+            Operation::Write(&command(READ_DATA, address)),
+            Operation::Read(buffer),
+        ])
+    }
+
+    async fn wait_ready(&mut self) -> Result<(), S::Error> {
+        loop {
+            let mut status = [0];
+            self.spi.transaction(&mut [
+                Operation::Write(&[READ_STATUS]),
+                Operation::Read(&mut status),
+            ])?;
+            if status[0] & BUSY == 0 {
+                return Ok(());
+            }
+            Timer::after(BUSY_POLL_INTERVAL).await;
+        }
+    }
+
+    async fn erase_sector(&mut self, address: u32) -> Result<(), S::Error> {
+        self.spi.write(&[WRITE_ENABLE])?;
+        self.spi.write(&command(SECTOR_ERASE, address))?;
+        self.wait_ready().await
+    }
+
+    /// `data` may not cross a 256 byte page, records never do
+    async fn program(&mut self, address: u32, data: &[u8]) -> Result<(), S::Error> {
+        self.spi.write(&[WRITE_ENABLE])?;
+        self.spi.transaction(&mut [
+            Operation::Write(&command(PAGE_PROGRAM, address)),
+            Operation::Write(data),
+        ])?;
+        self.wait_ready().await
+    }
+}
+
+struct BlackBox<S> {
+    flash: Flash<S>,
+    /// Slot the next record goes to
+    next_slot: u32,
+    next_sequence: u32,
+    /// Records in the flash, the newest is the one before `next_slot`
+    stored: u32,
+}
+
+fn slot_address(slot: u32) -> u32 {
+    slot * RECORD_LEN as u32
+}
+
+impl<S: SpiDevice> BlackBox<S> {
+    /// Find the newest record, the next one goes after it
+    fn open(spi: S) -> Result<Self, S::Error> {
+        let mut flash = Flash { spi };
+        let mut newest: Option<(u32, u32)> = None;
+        let mut stored = 0;
+        for slot in 0..RECORDS {
+            let mut sequence = [0; 4];
+            flash.read(slot_address(slot), &mut sequence)?;
+            let sequence = u32::from_le_bytes(sequence);
+            if sequence == ERASED_SEQUENCE {
+                continue;
+            }
+            stored += 1;
+            if newest.is_none_or(|(_, newest_sequence)| sequence > newest_sequence) {
+                newest = Some((slot, sequence));
+            }
+        }
+        let (mut next_slot, next_sequence) = match newest {
+            Some((slot, sequence)) => ((slot + 1) % RECORDS, sequence + 1),
+            None => (0, 0),
+        };
+        // Not erased after a power loss in the middle of an erase, go on with the next sector
+        let mut sequence = [0; 4];
+        flash.read(slot_address(next_slot), &mut sequence)?;
+        if next_slot % RECORDS_PER_SECTOR != 0 && u32::from_le_bytes(sequence) != ERASED_SEQUENCE {
+            next_slot = (next_slot / RECORDS_PER_SECTOR + 1) % SECTORS * RECORDS_PER_SECTOR;
+        }
+        Ok(Self {
+            flash,
+            next_slot,
+            next_sequence,
+            stored,
+        })
+    }
+
+    async fn record(&mut self, mut record: BlackBoxRecord) -> Result<(), S::Error> {
+        if self.next_slot % RECORDS_PER_SECTOR == 0 {
+            self.flash
+                .erase_sector(slot_address(self.next_slot))
+                .await?;
+            // Once the ring is full the erased sector held the oldest records
+            self.stored = self.stored.min(RECORDS - RECORDS_PER_SECTOR);
+        }
+        record.sequence = self.next_sequence;
+        self.flash
+            .program(slot_address(self.next_slot), &record.to_bytes())
+            .await?;
+        self.next_slot = (self.next_slot + 1) % RECORDS;
+        self.next_sequence += 1;
+        self.stored += 1;
+        Ok(())
+    }
+
+    /// The response to `request`, the records oldest first
+    fn read(
+        &mut self,
+        request: BlackBoxRequest,
+        response: &mut heapless::Vec<u8, MAX_RESPONSE_LEN>,
+    ) -> Result<(), S::Error> {
+        let skip = u32::from(request.skip).min(self.stored);
+        let count = u32::from(request.count).min(self.stored.saturating_sub(skip));
+        response.clear();
+        // Can't fail, the header and the records of a request fit
+        let _ = response.extend_from_slice(
+            &BlackBoxResponse {
+                count: count as u8,
+                stored: self.stored as u16,
+            }
+            .to_bytes(),
+        );
+        // The oldest record of the response, counted back from the next slot
+        let first = (self.next_slot + RECORDS * 2 - skip - count) % RECORDS;
+        for offset in 0..count {
+            let mut record = [0; RECORD_LEN];
+            self.flash
+                .read(slot_address((first + offset) % RECORDS), &mut record)?;
+            let _ = response.extend_from_slice(&record);
+        }
+        Ok(())
+    }
+}
+
+/// The record of the moment, the sequence is assigned when it is written
+fn sample(data: &DisplayData) -> BlackBoxRecord {
+    let uptime_us = Instant::now().as_micros();
+    let motor_current = data
+        .motors
+        .values()
+        .filter_map(|motor| motor.battery_current.get().copied())
+        .fold(None, |sum: Option<f32>, current| {
+            Some(sum.unwrap_or(0.0) + current)
+        });
+    BlackBoxRecord {
+        sequence: 0,
+        uptime_s: (uptime_us / 1_000_000) as u32,
+        utc_s: crate::CLOCK
+            .lock(|clock| clock.get().utc_us(uptime_us))
+            .map(|utc_us| (utc_us / 1_000_000) as u32),
+        speed_kmh: data.speed_kmh.get().copied(),
+        state_of_charge: data.battery_state_of_charge.get().copied(),
+        pack_voltage: data.battery_voltage.get().copied(),
+        pack_current: data.battery_current_pack.get().copied(),
+        motor_current,
+        throttle: data
+            .throttle_value
+            .get()
+            .map(|&throttle| throttle.round().clamp(-127.0, 127.0) as i8),
+        battery_state: data.battery_state.get().and_then(battery_state_to_u8),
+        battery_error_flags: data.battery_error_flags.get().copied(),
+    }
+}
+
+async fn write(can_tx: &SharedCanTx, data: &IsoTpFrameData) {
+    let id = StandardId::new(BLACK_BOX_RESPONSE_ID).unwrap();
+    match Frame::new_data(id, data) {
+        Ok(frame) => {
+            can_tx.lock().await.write(&frame).await;
+        }
+        Err(error) => error!("Failed to create frame: {}", defmt::Debug2Format(&error)),
+    }
+}
+
+/// Send `payload` to the reader, following its flow control
+async fn send(can_tx: &SharedCanTx, payload: &[u8]) {
+    let Some(mut segmenter) = Segmenter::new(payload) else {
+        return;
+    };
+    write(can_tx, &segmenter.first()).await;
+    if !segmenter.is_segmented() {
+        return;
+    }
+    while !segmenter.is_done() {
+        let (block_size, separation) = loop {
+            let Ok(frame) = with_timeout(FLOW_CONTROL_TIMEOUT, REQUEST_FRAMES.receive()).await
+            else {
+                warn!("No flow control of the black box reader");
+                return;
+            };
+            match IsoTpFrame::parse(&frame.data) {
+                Some(IsoTpFrame::FlowControl {
+                    status: FlowStatus::ContinueToSend,
+                    block_size,
+                    separation_time,
+                }) => break (block_size, separation_time_us(separation_time)),
+                Some(IsoTpFrame::FlowControl {
+                    status: FlowStatus::Wait,
+                    ..
+                }) => continue,
+                _ => {
+                    warn!("Black box reader aborted the response");
+                    return;
+                }
+            }
+        };
+        let mut sent: u16 = 0;
+        while let Some(frame) = segmenter.next_consecutive() {
+            Timer::after_micros(separation.into()).await;
+            write(can_tx, &frame).await;
+            sent += 1;
+            if sent == u16::from(block_size) {
+                break;
+            }
+        }
+    }
+}
+
+/// Records the display data once a second and answers the requests of the reader. Records are
+/// missed while a refresh of the e-paper blocks, the reader tries again
+#[embassy_executor::task]
+pub async fn recorder(
+    spi: FlashSpi,
+    display_data: &'static crate::SharedDisplayData,
+    can_tx: &'static SharedCanTx,
+) {
+    let mut black_box = match BlackBox::open(spi) {
+        Ok(black_box) => black_box,
+        Err(error) => {
+            error!("Black box flash failed: {}", defmt::Debug2Format(&error));
+            return;
+        }
+    };
+    info!(
+        "Black box with {} records, next {}",
+        black_box.stored, black_box.next_sequence
+    );
+
+    let mut requests = Reassembler::<8>::new();
+    let mut response = heapless::Vec::new();
+    let mut next_record = Instant::now();
+    loop {
+        match select(Timer::at(next_record), REQUEST_FRAMES.receive()).await {
+            Either::First(()) => {
+                let record = sample(&*display_data.lock().await);
+                if let Err(error) = black_box.record(record).await {
+                    error!("Black box write failed: {}", defmt::Debug2Format(&error));
+                }
+                // Skip what was missed instead of recording the same values again
+                next_record = (next_record + RECORD_INTERVAL).max(Instant::now());
+            }
+            Either::Second(frame) => {
+                let request = match requests.push(&frame.data) {
+                    Ok(Received::Complete(payload)) => BlackBoxRequest::parse(payload),
+                    _ => None,
+                };
+                let Some(request) = request else {
+                    debug!("Ignoring a frame of the black box reader");
+                    continue;
+                };
+                debug!("Black box request: {}", request);
+                if cfg!(feature = "listen-only") {
+                    continue;
+                }
+                match black_box.read(request, &mut response) {
+                    Ok(()) => send(can_tx, &response).await,
+                    Err(error) => error!("Black box read failed: {}", defmt::Debug2Format(&error)),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,52 @@
+//! Important log messages sent over CAN as [`DisplayLog`] records, so the data logger records them
+//! during a race without a debug probe attached. Nothing is sent without the `can-log` feature, the
+//! defmt log over RTT stays as it is either way.
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+#[allow(unused_imports)]
+use defmt::{debug, error, info, trace, warn};
+use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
+use embassy_sync::channel::Channel;
+use eoi_can_decoder::{DisplayLog, EoiCanData, LogLevel, DISPLAY_LOG_TEXT_LEN};
+
+/// Frames waiting to be sent by the main loop
+static FRAMES: Channel<ThreadModeRawMutex, DisplayLog, 16> = Channel::new();
+/// Assigned when a frame is queued, so frames that couldn't be sent show up as a gap
+static SEQUENCE: AtomicU8 = AtomicU8::new(0);
+
+/// Queue `text` to be sent, split over as many frames as needed. Bytes that aren't ASCII are sent
+/// as `?`, a record that doesn't fit in the queue anymore is dropped as a whole.
+pub fn log(level: LogLevel, text: &str) {
+    if !cfg!(feature = "can-log") {
+        return;
+    }
+
+    let mut chunks = text.as_bytes().chunks(DISPLAY_LOG_TEXT_LEN).peekable();
+    if FRAMES.free_capacity() < chunks.len() {
+        warn!("CAN log queue full, dropping: {}", text);
+        return;
+    }
+    while let Some(chunk) = chunks.next() {
+        let mut text = heapless::String::new();
+        for &byte in chunk {
+            // Can't fail, the chunk is at most as long as the string
+            let _ = text.push(if byte.is_ascii() { byte as char } else { '?' });
+        }
+        let frame = DisplayLog {
+            level,
+            sequence: SEQUENCE.fetch_add(1, Ordering::Relaxed),
+            continued: chunks.peek().is_some(),
+            text,
+        };
+        // Can't fail either, the free capacity was checked and nothing runs in between
+        let _ = FRAMES.try_send(frame);
+    }
+}
+
+/// Move the frames into the transmit queue of the main loop, in the log class behind the telemetry
+pub fn queue_pending(tx_queue: &mut crate::TxQueue) {
+    while let Ok(frame) = FRAMES.try_receive() {
+        crate::queue(tx_queue, &EoiCanData::DisplayLog(frame));
+    }
+}
@@ -1,24 +1,62 @@
 #![no_std]
 #![no_main]
 
+use arrform::{arrform, ArrForm};
+use black_box::SharedCanTx;
+use core::cell::Cell;
+use core::sync::atomic::{AtomicU32, Ordering};
 #[allow(unused_imports)]
 use defmt::{debug, error, info, trace, warn};
+use defmt_rtt as _;
+use draw_display::{
+    built_info, DisplayData, LedStatus, PageSelection, Profile, RefreshPolicy, RenderMonitor,
+};
 use embassy_executor::Spawner;
+use embassy_futures::select::{select, Either};
 use embassy_stm32::can::enums::BusError;
 use embassy_stm32::can::filter::Mask32;
+use embassy_stm32::can::frame::Envelope;
 use embassy_stm32::can::{
-    Can, Fifo, Rx0InterruptHandler, Rx1InterruptHandler, SceInterruptHandler, TxInterruptHandler,
+    Can, CanRx, Fifo, Frame, Rx0InterruptHandler, Rx1InterruptHandler, SceInterruptHandler,
+    TxInterruptHandler,
 };
+use embassy_stm32::exti::ExtiInput;
 use embassy_stm32::gpio::{Input, Level, Output, Pull, Speed};
-use embassy_stm32::peripherals::CAN1;
+use embassy_stm32::mode::Blocking;
+use embassy_stm32::peripherals::{CAN1, PB13, PB14, PB15, PB6, PB8, PB9, SPI2};
+use embassy_stm32::rtc::{Rtc, RtcConfig};
+use embassy_stm32::spi::Spi;
 use embassy_stm32::time::Hertz;
+use embassy_stm32::wdg::IndependentWatchdog;
 use embassy_stm32::{bind_interrupts, spi, Peripherals};
-use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
+use embassy_sync::blocking_mutex;
+use embassy_sync::blocking_mutex::raw::{CriticalSectionRawMutex, ThreadModeRawMutex};
+use embassy_sync::channel::Channel;
 use embassy_sync::mutex::Mutex;
-use embassy_time::{Delay, Duration, Instant, Timer};
-use eoi_can_decoder::can_collector::CanCollector;
-use eoi_can_decoder::can_frame::CanFrame;
-use {defmt_rtt as _, panic_probe as _};
+use embassy_time::{with_timeout, Delay, Duration, Instant, Timer};
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+use embedded_hal_bus::spi::ExclusiveDevice;
+use eoi_can_decoder::can_encoder::encode_eoi_can_data;
+use eoi_can_decoder::can_frame::{CanFrame, FrameError};
+use eoi_can_decoder::can_receiver::{receive_and_decode, Handler, Receiver};
+use eoi_can_decoder::time_sync::ClockSync;
+use eoi_can_decoder::transmit_queue::{Priority, TransmitQueue};
+use eoi_can_decoder::{
+    DisplayAcknowledge, DisplayHeartbeat, EoiCanData, GnssData, LogLevel, NodeIdentification,
+    NodeType, NODE_IDENTIFICATION_INTERVAL_S,
+};
+use stack_usage::Section;
+use static_cell::StaticCell;
+
+mod bitrate;
+mod black_box;
+mod can_log;
+mod panic_report;
+mod persistence;
+mod stack_usage;
+mod status_led;
+mod stop_mode;
 
 bind_interrupts!(struct CanInterrupts {
     CAN1_RX0 => Rx0InterruptHandler<CAN1>;
@@ -32,48 +70,123 @@ use epd_waveshare::{
     prelude::*,
 };
 
-static SHARED_CAN_COLLECTOR: Mutex<ThreadModeRawMutex, CanCollector> =
-    Mutex::new(CanCollector::new());
+/// The display data, kept up to date by the CAN task with every frame and sampled by the main
+/// loop when it draws. Only the latest state is kept, nothing queues up during a refresh
+static DISPLAY_DATA: StaticCell<SharedDisplayData> = StaticCell::new();
+type SharedDisplayData = Mutex<ThreadModeRawMutex, DisplayData>;
+/// Given to the executor, which keeps the time with it in STOP mode
+static RTC: StaticCell<Rtc> = StaticCell::new();
+/// Shared by the main loop and the black box, which answers its reader from its own task
+static CAN_TX: StaticCell<SharedCanTx> = StaticCell::new();
+/// Frames received since the main loop looked last, for waking up and the status LEDs
+static RECEIVED_FRAMES: AtomicU32 = AtomicU32::new(0);
 
-pub fn embassy_init() -> Peripherals {
-    use embassy_stm32::rcc::{Pll, PllMul, PllPreDiv, PllRDiv, PllSource};
+/// Offset of the uptime to UTC, synced to the time sync frames of the GNSS bridge. A blocking
+/// mutex as the log timestamps need it, also in interrupts
+static CLOCK: blocking_mutex::Mutex<CriticalSectionRawMutex, Cell<ClockSync>> =
+    blocking_mutex::Mutex::new(Cell::new(ClockSync::new()));
+
+// Log timestamps in UTC once synced, so they line up with those of the other boards and the CAN
+// logs. Before that the uptime, which shows as a time on 1 January 1970
+defmt::timestamp!("{=u64:iso8601ms}", {
+    let uptime_us = Instant::now().as_micros();
+    CLOCK
+        .lock(|clock| clock.get().utc_us(uptime_us))
+        .unwrap_or(uptime_us)
+        / 1000
+});
+
+/// Start of the current bus-off condition, cleared as soon as frames are received again
+static BUS_OFF_SINCE: Mutex<ThreadModeRawMutex, Option<Instant>> = Mutex::new(None);
+
+/// Wait in bus-off before the first recovery, doubled after every further one on a faulty bus, so
+/// the display doesn't disturb it over and over
+const BUS_OFF_MIN_BACKOFF: Duration = Duration::from_secs(1);
+const BUS_OFF_MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// Has to be longer than a full refresh of the e-paper, which blocks the main loop
+const WATCHDOG_TIMEOUT_US: u32 = 15_000_000;
+
+/// A full refresh of the e-paper takes seconds and wears the panel, so it isn't refreshed quicker
+/// than this even when values change
+const SCREEN_MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+/// Refresh this often even when nothing changed significantly, so the time stays current
+const SCREEN_MAX_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+/// Drawing and a full refresh of the e-paper, frames taking longer are counted on the render page
+const FRAME_BUDGET: Duration = Duration::from_secs(5);
+
+/// The heartbeat is sent this often, also the time the main loop waits for the button
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The node identification is sent at boot and this often after
+const IDENTIFICATION_INTERVAL: Duration =
+    Duration::from_secs(NODE_IDENTIFICATION_INTERVAL_S as u64);
+
+/// Without CAN frames for this long the boat is parked, so the display goes to sleep and the MCU
+/// into STOP mode, see `stop_mode`
+const SLEEP_AFTER_SILENCE: Duration = Duration::from_secs(10 * 60);
+
+static BUTTON_PRESSES: Channel<ThreadModeRawMutex, ButtonPress, 4> = Channel::new();
+
+const BUTTON_DEBOUNCE: Duration = Duration::from_millis(30);
+const BUTTON_LONG_PRESS: Duration = Duration::from_secs(1);
+const BUTTON_PROFILE_PRESS: Duration = Duration::from_secs(4);
+
+#[derive(Debug, Clone, Copy, defmt::Format)]
+enum ButtonPress {
+    /// Acknowledge the message of the chase car, without a message switch to the next page
+    Short,
+    /// Toggle the inverted theme, which also forces a full refresh
+    Long,
+    /// Switch to the next profile, like from race to the test bench
+    Profile,
+}
+
+/// Draws with black and white swapped, the e-paper has no backlight or contrast to adjust, so this
+/// is the way to make it readable in other light
+struct Inverted<'a, D>(&'a mut D);
+
+impl<D: OriginDimensions> OriginDimensions for Inverted<'_, D> {
+    fn size(&self) -> Size {
+        self.0.size()
+    }
+}
+
+impl<D, C> DrawTarget for Inverted<'_, D>
+where
+    D: DrawTarget<Color = C> + OriginDimensions,
+    C: PixelColor + From<BinaryColor>,
+{
+    type Color = BinaryColor;
+    type Error = D::Error;
 
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        self.0.draw_iter(
+            pixels
+                .into_iter()
+                .map(|Pixel(point, color)| Pixel(point, color.invert().into())),
+        )
+    }
+}
+
+pub fn embassy_init() -> Peripherals {
     let mut config = embassy_stm32::Config::default();
+    // The clocks kept running for the debugger would draw more than STOP mode saves
+    config.enable_debug_during_sleep = false;
     let mut mux = embassy_stm32::rcc::mux::ClockMux::default();
     mux.adcsel = embassy_stm32::rcc::mux::Adcsel::SYS;
     config.rcc = embassy_stm32::rcc::Config {
-        msi: None,
+        // run everything on the MSI at 48 MHz, trimmed by the LSE. The MCU wakes up from STOP
+        // mode on it, the HSE and the PLLs would stay off as embassy-stm32 doesn't restart them
+        msi: Some(embassy_stm32::rcc::MSIRange::RANGE48M),
         hsi: false,
-        hse: Some(embassy_stm32::rcc::Hse {
-            freq: Hertz::mhz(16),
-            mode: embassy_stm32::rcc::HseMode::Oscillator,
-        }),
-        sys: embassy_stm32::rcc::Sysclk::PLL1_R,
-        // run everything on 64 Mhz
-        pll: Some(Pll {
-            source: PllSource::HSE,
-            prediv: PllPreDiv::DIV1,
-            mul: PllMul::MUL8,
-            divp: None,
-            divq: None,
-            divr: Some(PllRDiv::DIV2),
-        }),
-        pllsai1: Some(Pll {
-            source: PllSource::HSE,
-            prediv: PllPreDiv::DIV1,
-            mul: PllMul::MUL8,
-            divp: None,
-            divq: Some(embassy_stm32::rcc::PllQDiv::DIV2),
-            divr: None,
-        }),
-        pllsai2: Some(Pll {
-            source: PllSource::HSE,
-            prediv: PllPreDiv::DIV1,
-            mul: PllMul::MUL8,
-            divp: None,
-            divq: Some(embassy_stm32::rcc::PllQDiv::DIV2),
-            divr: None,
-        }),
+        hse: None,
+        sys: embassy_stm32::rcc::Sysclk::MSI,
+        pll: None,
+        pllsai1: None,
+        pllsai2: None,
         mux,
         ahb_pre: embassy_stm32::rcc::AHBPrescaler::DIV1,
         apb1_pre: embassy_stm32::rcc::APBPrescaler::DIV1,
@@ -93,78 +206,335 @@ pub fn embassy_init() -> Peripherals {
     embassy_stm32::init(config)
 }
 
+/// A frame of the CAN peripheral together with the time it was received
+struct Received(Envelope);
+
+impl embedded_can::Frame for Received {
+    fn new(id: impl Into<embedded_can::Id>, data: &[u8]) -> Option<Self> {
+        let frame = embedded_can::Frame::new(id, data)?;
+        Some(Self(Envelope {
+            ts: Instant::now(),
+            frame,
+        }))
+    }
+
+    fn new_remote(id: impl Into<embedded_can::Id>, dlc: usize) -> Option<Self> {
+        let frame = embedded_can::Frame::new_remote(id, dlc)?;
+        Some(Self(Envelope {
+            ts: Instant::now(),
+            frame,
+        }))
+    }
+
+    fn is_extended(&self) -> bool {
+        embedded_can::Frame::is_extended(&self.0.frame)
+    }
+
+    fn is_remote_frame(&self) -> bool {
+        embedded_can::Frame::is_remote_frame(&self.0.frame)
+    }
+
+    fn id(&self) -> embedded_can::Id {
+        embedded_can::Frame::id(&self.0.frame)
+    }
+
+    fn dlc(&self) -> usize {
+        embedded_can::Frame::dlc(&self.0.frame)
+    }
+
+    fn data(&self) -> &[u8] {
+        embedded_can::Frame::data(&self.0.frame)
+    }
+}
+
+/// The SPI of the e-paper, dropped while the display sleeps as its clock keeps the MCU out of STOP
+/// mode
+type EpaperSpi = ExclusiveDevice<Spi<'static, Blocking>, Output<'static>, Delay>;
+
+fn epaper_spi(spi: SPI2, sck: PB13, mosi: PB15, miso: PB14, cs: PB6) -> EpaperSpi {
+    let mut spi_config = spi::Config::default();
+    spi_config.frequency = Hertz::mhz(2); // max 5 on display
+    let spi = Spi::new_blocking(spi, sck, mosi, miso, spi_config);
+    let cs = Output::new(cs, Level::High, Speed::VeryHigh);
+    ExclusiveDevice::new(spi, cs, Delay).unwrap()
+}
+
+/// Takes all frames into FIFO 0, the bus is joined by [`start_can`]
+fn new_can(peripheral: CAN1, rx: PB8, tx: PB9) -> Can<'static> {
+    let mut can = Can::new(peripheral, rx, tx, CanInterrupts);
+    can.modify_filters()
+        .enable_bank(0, Fifo::Fifo0, Mask32::accept_all());
+    can
+}
+
+async fn start_can(can: &mut Can<'static>, bitrate: u32) {
+    // Silent like while detecting the bitrate, the frames only go to the receiver of the peripheral
+    can.modify_config()
+        .set_loopback(false)
+        .set_silent(cfg!(feature = "listen-only"));
+    can.set_bitrate(bitrate);
+    can.set_tx_fifo_scheduling(true);
+    can.enable().await;
+}
+
+struct CanReceiver(CanRx<'static>);
+
+impl Receiver for CanReceiver {
+    type Frame = Received;
+    type Error = BusError;
+
+    async fn receive(&mut self) -> Result<Received, BusError> {
+        self.0.read().await.map(Received)
+    }
+}
+
+/// Takes the frames into the display data, and syncs the clock with the receive time
+struct Collector(&'static SharedDisplayData);
+
+impl Handler<CanReceiver> for Collector {
+    async fn on_frame(&mut self, received: &Received, frame: CanFrame, data: Option<EoiCanData>) {
+        *BUS_OFF_SINCE.lock().await = None;
+        trace!("CAN frame: {}", frame);
+        RECEIVED_FRAMES.fetch_add(1, Ordering::Relaxed);
+        status_led::frame_received();
+        if black_box::is_request(&frame) {
+            black_box::receive(frame);
+            return;
+        }
+        let Some(data) = data else {
+            warn!("Failed to parse data from CAN frame: {:?}", frame);
+            return;
+        };
+        if let EoiCanData::Gnss(GnssData::GnssTimeSync(utc_us)) = data {
+            sync_clock(received.0.ts.as_micros(), utc_us);
+        }
+        let mut display_data = self.0.lock().await;
+        stack_usage::measure(Section::Decode, || display_data.ingest_eoi_can_data(data));
+    }
+
+    async fn on_error(&mut self, bus_error: BusError) {
+        error!("CAN frame try read error: {}", bus_error);
+        can_log::log(
+            LogLevel::Error,
+            arrform!(40, "CAN error {:?}", bus_error).as_str(),
+        );
+        if matches!(bus_error, BusError::BusOff) {
+            BUS_OFF_SINCE.lock().await.get_or_insert_with(Instant::now);
+        }
+    }
+
+    async fn on_invalid_frame(&mut self, error: FrameError) {
+        error!("Dropping CAN frame: {}", error);
+    }
+}
+
 #[embassy_executor::task]
-pub async fn can_receiver(
-    mut can_rx: embassy_stm32::can::CanRx<'static>,
-    mut output_led: Output<'static>,
-) {
-    let mut last_bus_error: Option<BusError> = None;
+pub async fn can_receiver(can_rx: CanRx<'static>, display_data: &'static SharedDisplayData) {
+    receive_and_decode(&mut CanReceiver(can_rx), &mut Collector(display_data)).await
+}
+
+fn sync_clock(uptime_us: u64, utc_us: u64) {
+    let (stepped, clock) = CLOCK.lock(|cell| {
+        let mut clock = cell.get();
+        let stepped = clock.sync(uptime_us, utc_us);
+        cell.set(clock);
+        (stepped, clock)
+    });
+    if stepped {
+        info!("Clock synced to UTC, offset {} us", clock.offset_us());
+    } else {
+        trace!(
+            "Clock offset {} us, drift {} ppm",
+            clock.offset_us(),
+            clock.drift_ppm()
+        );
+    }
+}
+
+/// The button is low active, a press longer than [`BUTTON_LONG_PRESS`] is a long press, one longer
+/// than [`BUTTON_PROFILE_PRESS`] switches the profile
+#[embassy_executor::task]
+pub async fn button_handler(mut button: ExtiInput<'static>) {
     loop {
-        let envelope = can_rx.read().await;
-        if let Ok(envelope) = envelope {
-            last_bus_error = None;
-            let data_len = envelope.frame.header().len() as usize;
-            let data_slice = &envelope.frame.data()[..data_len];
-            let data_vec: heapless::Vec<u8, 8> = heapless::Vec::from_slice(data_slice)
-                .expect("CAN messages are at most 8 bytes, so this should never fail");
-            let frame = CanFrame {
-                id: *envelope.frame.header().id(),
-                data: data_vec,
-            };
-            trace!("CAN frame: {}", frame);
-            SHARED_CAN_COLLECTOR.lock().await.insert(frame);
-            output_led.toggle();
-        } else if let Err(bus_error) = envelope {
-            // Compare the discriminant to avoid needing PartialEq
-            let is_same_error = match last_bus_error {
-                Some(ref last) => {
-                    core::mem::discriminant(last) == core::mem::discriminant(&bus_error)
+        button.wait_for_falling_edge().await;
+        Timer::after(BUTTON_DEBOUNCE).await;
+        if button.is_high() {
+            continue; // bounce or glitch
+        }
+
+        let press = match with_timeout(BUTTON_LONG_PRESS, button.wait_for_high()).await {
+            Ok(()) => ButtonPress::Short,
+            Err(_) => {
+                let rest = BUTTON_PROFILE_PRESS - BUTTON_LONG_PRESS;
+                match with_timeout(rest, button.wait_for_high()).await {
+                    Ok(()) => ButtonPress::Long,
+                    Err(_) => {
+                        button.wait_for_high().await;
+                        ButtonPress::Profile
+                    }
                 }
-                None => false,
-            };
-            if is_same_error {
-                error!("CAN frame try read error: {}", bus_error);
             }
+        };
+        Timer::after(BUTTON_DEBOUNCE).await;
+
+        debug!("Button press: {}", press);
+        if BUTTON_PRESSES.try_send(press).is_err() {
+            warn!("Too many button presses, dropping {}", press);
+        }
+    }
+}
+
+/// Frames waiting for a transmit mailbox, per priority class
+const TRANSMIT_QUEUE_SIZE: usize = 8;
+
+type TxQueue = TransmitQueue<CanFrame, TRANSMIT_QUEUE_SIZE>;
+
+/// Queue the frame of `data` by its priority. When the bus is congested or nobody acknowledges for
+/// long enough to fill its class, the oldest frame of the class is dropped
+fn queue(tx_queue: &mut TxQueue, data: &EoiCanData) {
+    let Some(frame) = encode_eoi_can_data(data) else {
+        error!("Failed to encode {}", data);
+        return;
+    };
+    if let Some(dropped) = tx_queue.push(Priority::of(data), frame) {
+        debug!("CAN transmit queue full, dropped {}", dropped);
+    }
+}
+
+/// Move the queued frames into the transmit mailboxes without waiting, commands before telemetry.
+/// While the black box sends, the frames wait for the next time
+fn transmit(can_tx: &SharedCanTx, tx_queue: &mut TxQueue) {
+    if cfg!(feature = "listen-only") {
+        tx_queue.transmit(|frame| {
+            trace!("Listen-only, not sending {}", frame);
+            true
+        });
+        return;
+    }
+    let Ok(mut can_tx) = can_tx.try_lock() else {
+        return;
+    };
+    tx_queue.transmit(|frame| match Frame::new_data(frame.id, &frame.data) {
+        Ok(frame) => can_tx.try_write(&frame).is_ok(),
+        Err(error) => {
+            error!("Failed to create frame: {}", defmt::Debug2Format(&error));
+            true
         }
+    });
+}
+
+/// Recover from bus-off like the bxCAN reference manual describes without automatic bus-off
+/// management, by entering and leaving the initialization mode. The controller joins the bus again
+/// after it saw 128 times 11 recessive bits
+async fn recover_from_bus_off() {
+    let can = embassy_stm32::pac::CAN1;
+    can.mcr().modify(|w| w.set_inrq(true));
+    // Right away in bus-off, the controller takes no part on the bus
+    let entered = with_timeout(Duration::from_millis(10), async {
+        while !can.msr().read().inak() {
+            Timer::after_micros(100).await;
+        }
+    })
+    .await;
+    if entered.is_err() {
+        warn!("CAN controller didn't enter initialization mode");
     }
+    can.mcr().modify(|w| w.set_inrq(false));
+}
+
+fn heartbeat(last_refresh_age: Duration) -> EoiCanData {
+    EoiCanData::DisplayHeartbeat(DisplayHeartbeat {
+        uptime_s: Instant::now().as_secs() as u32,
+        firmware_version_major: env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap_or(0),
+        firmware_version_minor: env!("CARGO_PKG_VERSION_MINOR").parse().unwrap_or(0),
+        firmware_version_patch: env!("CARGO_PKG_VERSION_PATCH").parse().unwrap_or(0),
+        last_refresh_age_s: last_refresh_age.as_secs().min(u8::MAX as u64) as u8,
+    })
+}
+
+fn identification() -> EoiCanData {
+    EoiCanData::NodeIdentification(NodeIdentification {
+        node: NodeType::Display,
+        version_major: env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap_or(0),
+        version_minor: env!("CARGO_PKG_VERSION_MINOR").parse().unwrap_or(0),
+        version_patch: env!("CARGO_PKG_VERSION_PATCH").parse().unwrap_or(0),
+        dirty: built_info::GIT_DIRTY.unwrap_or(false),
+        // 0 when built outside of a git checkout
+        git_hash: built_info::GIT_COMMIT_HASH
+            .and_then(|hash| hash.get(..8))
+            .and_then(|hash| u32::from_str_radix(hash, 16).ok())
+            .unwrap_or(0),
+    })
 }
 
-#[embassy_executor::main]
-async fn main(spawner: Spawner) {
+#[cortex_m_rt::entry]
+fn main() -> ! {
+    embassy_stm32::low_power::Executor::take().run(|spawner| {
+        spawner.must_spawn(async_main(spawner));
+    })
+}
+
+#[embassy_executor::task]
+async fn async_main(spawner: Spawner) {
     let p = embassy_init();
     info!("Hello Rust!");
 
-    // leds are low active
-    let mut led_green = Output::new(p.PC1, Level::High, Speed::Low);
-    let mut led_red = Output::new(p.PC2, Level::High, Speed::Low);
-    let led_blue = Output::new(p.PC3, Level::High, Speed::Low);
-
-    led_red.set_low();
+    // leds are low active, the blue one is lit while the e-paper is busy
+    let led_green = Output::new(p.PC1, Level::High, Speed::Low);
+    let led_red = Output::new(p.PC2, Level::High, Speed::Low);
+    let mut led_blue = Output::new(p.PC3, Level::Low, Speed::Low);
+    spawner.must_spawn(status_led::status_leds(led_green, led_red));
 
     let busy = Input::new(p.PA8, Pull::Down);
     let dc = Output::new(p.PC9, Level::High, Speed::VeryHigh);
     let reset = Output::new(p.PC8, Level::Low, Speed::VeryHigh);
 
-    let mut spi_config = spi::Config::default();
-    spi_config.frequency = Hertz::mhz(2); // max 5 on display
-    let spi = spi::Spi::new_blocking(p.SPI2, p.PB13, p.PB15, p.PB14, spi_config);
-
-    let cs = Output::new(p.PB6, Level::High, Speed::VeryHigh);
+    let mut spi_device = epaper_spi(p.SPI2, p.PB13, p.PB15, p.PB14, p.PB6);
 
-    let mut spi_device = embedded_hal_bus::spi::ExclusiveDevice::new(spi, cs, Delay).unwrap();
+    // Keeps the statistics of the race and the bitrate over a brownout or a watchdog reset, and
+    // the time in STOP mode
+    let rtc: &'static Rtc = RTC.init(Rtc::new(p.RTC, RtcConfig::default()));
+    embassy_stm32::low_power::stop_with_rtc(rtc);
 
     let can_standby = Output::new(p.PB7, Level::Low, Speed::Low);
     core::mem::forget(can_standby);
-    let mut can = Can::new(p.CAN1, p.PB8, p.PB9, CanInterrupts);
-    can.modify_filters()
-        .enable_bank(0, Fifo::Fifo0, Mask32::accept_all());
-    can.modify_config().set_loopback(false).set_silent(false);
-    can.set_bitrate(1_000_000);
-    can.set_tx_fifo_scheduling(true);
-    can.enable().await;
-    let (_, can_rx) = can.split();
+    let mut can = new_can(p.CAN1, p.PB8, p.PB9);
+    let last_bitrate = persistence::load_bitrate(rtc);
+    let bitrate = match bitrate::detect(&mut can, last_bitrate).await {
+        Some(bitrate) => {
+            info!("CAN bitrate detected: {} bit/s", bitrate);
+            persistence::save_bitrate(rtc, bitrate);
+            bitrate
+        }
+        None => {
+            let bitrate = last_bitrate.unwrap_or(bitrate::DEFAULT_BITRATE);
+            warn!("No CAN frames received, assuming {} bit/s", bitrate);
+            bitrate
+        }
+    };
+    start_can(&mut can, bitrate).await;
+    let (can_tx, can_rx) = can.split();
+    let can_tx: &'static SharedCanTx = CAN_TX.init(Mutex::new(can_tx));
 
-    spawner.must_spawn(can_receiver(can_rx, led_blue));
+    let mut display_data = DisplayData::default();
+    display_data.render = RenderMonitor::with_budget(FRAME_BUDGET);
+    let shared_display_data: &'static SharedDisplayData =
+        DISPLAY_DATA.init(Mutex::new(display_data));
+    spawner.must_spawn(can_receiver(can_rx, shared_display_data));
+
+    if cfg!(feature = "black-box") {
+        let mut flash_config = spi::Config::default();
+        flash_config.frequency = Hertz::mhz(8);
+        let flash_spi = spi::Spi::new_blocking(p.SPI1, p.PA5, p.PA7, p.PA6, flash_config);
+        let flash_cs = Output::new(p.PA4, Level::High, Speed::VeryHigh);
+        let flash =
+            embedded_hal_bus::spi::ExclusiveDevice::new(flash_spi, flash_cs, Delay).unwrap();
+        spawner.must_spawn(black_box::recorder(flash, shared_display_data, can_tx));
+    }
+
+    // Button to ground next to the display connector
+    let button = ExtiInput::new(p.PC13, p.EXTI13, Pull::Up);
+    spawner.must_spawn(button_handler(button));
 
     Timer::after_secs(1).await;
 
@@ -174,47 +544,303 @@ async fn main(spawner: Spawner) {
 
     info!("Init done");
 
-    led_red.set_high();
-
     let mut display = Display7in5::default();
-    let mut display_data = draw_display::DisplayData::default();
+    let mut display_data = shared_display_data.lock().await;
+    if let Some(state) = persistence::load(rtc) {
+        can_log::log(
+            LogLevel::Warn,
+            "Restarted during a race, restored its state",
+        );
+        info!(
+            "Restoring {} km, {} laps and race time {} s",
+            state.trip.distance_km, state.laps, state.race_time_s
+        );
+        display_data.trip = state.trip;
+        if let Some(race_time_s) = state.race_time_s {
+            display_data
+                .lap_counter
+                .restore(state.laps, Duration::from_secs(race_time_s.into()));
+        }
+        display_data.race_countdown.set_end(state.race_end_s);
+    }
+    let mut last_trip_update = Instant::now();
+
     draw_display::draw_display(&mut display, &display_data).unwrap();
+    let mut refresh_policy =
+        RefreshPolicy::new(SCREEN_MIN_REFRESH_INTERVAL, SCREEN_MAX_REFRESH_INTERVAL);
+    refresh_policy.refreshed(&display_data);
+    // The frame buffer is the snapshot of the data, the CAN task can go on during the refresh
+    drop(display_data);
 
     epd.update_and_display_frame(&mut spi_device, display.buffer(), &mut Delay)
         .unwrap();
+    led_blue.set_high();
+
+    // A panicking task or a hanging main loop restarts the display instead of freezing stale data
+    let mut watchdog = IndependentWatchdog::new(p.IWDG, WATCHDOG_TIMEOUT_US);
+    watchdog.unleash();
+    let mut can_rx_exti = p.EXTI8;
 
     let mut last_update_screen = Instant::now();
+    let mut pages = PageSelection::new(Profile::default());
+    // Kept until the frames fit in a transmit mailbox
+    let mut tx_queue = TxQueue::new();
+    let mut inverted = false;
+    let mut last_can_activity = Instant::now();
+    let mut next_heartbeat = Instant::now();
+    let mut next_identification = Instant::now();
+    // Time of the next recovery from bus-off and the backoff after it
+    let mut bus_off_recovery: Option<(Instant, Duration)> = None;
+    let mut pending_panic = panic_report::take();
+    if let Some(panic) = &pending_panic {
+        error!("Restarted after a panic: {}", panic.text());
+    }
     info!("Starting main loop");
+    can_log::log(LogLevel::Info, "Started");
 
     loop {
-        if last_update_screen.elapsed() > Duration::from_secs(30) {
-            led_green.set_low();
-            info!("Decoding CAN data");
-            let mut can_collector = SHARED_CAN_COLLECTOR.lock().await;
-            if can_collector.get_dropped_frames() > 0 {
-                debug!("Dropped frames: {}", can_collector.get_dropped_frames());
+        watchdog.pet();
+
+        match *BUS_OFF_SINCE.lock().await {
+            Some(bus_off_since) => {
+                let (next_recovery, backoff) = bus_off_recovery
+                    .get_or_insert((bus_off_since + BUS_OFF_MIN_BACKOFF, BUS_OFF_MIN_BACKOFF));
+                if Instant::now() >= *next_recovery {
+                    warn!(
+                        "CAN bus-off for {}, recovering, next try in {}",
+                        bus_off_since.elapsed(),
+                        backoff
+                    );
+                    recover_from_bus_off().await;
+                    *next_recovery = Instant::now() + *backoff;
+                    *backoff = (*backoff * 2).min(BUS_OFF_MAX_BACKOFF);
+                }
+            }
+            // Frames came in again
+            None => bus_off_recovery = None,
+        }
+
+        // Not on every button press, only when due
+        if Instant::now() >= next_heartbeat {
+            queue(&mut tx_queue, &heartbeat(last_update_screen.elapsed()));
+            // Late after a refresh, the missed heartbeats are skipped instead of sent in a burst
+            while next_heartbeat <= Instant::now() {
+                next_heartbeat += HEARTBEAT_INTERVAL;
             }
-            let mut parsed_frames = 0_u32;
-            can_collector.iter().for_each(|frame| {
-                trace!("Paring CAN frame: {:?}", frame);
-                if let Some(parsed_data) = eoi_can_decoder::parse_eoi_can_data(frame) {
-                    display_data.ingest_eoi_can_data(parsed_data);
-                    parsed_frames = parsed_frames.saturating_add(1);
+        }
+        if Instant::now() >= next_identification {
+            queue(&mut tx_queue, &identification());
+            next_identification = Instant::now() + IDENTIFICATION_INTERVAL;
+        }
+        can_log::queue_pending(&mut tx_queue);
+        if pending_panic
+            .as_mut()
+            .is_some_and(|panic| panic.queue_next(&mut tx_queue))
+        {
+            pending_panic = None;
+        }
+        transmit(can_tx, &mut tx_queue);
+
+        let heartbeat_due = Timer::at(next_heartbeat);
+        let woken_by = select(heartbeat_due, BUTTON_PRESSES.receive()).await;
+        // Held until the next wait or refresh, the CAN task takes the frames in meanwhile
+        let mut display_data = shared_display_data.lock().await;
+        let mut force_refresh = match woken_by {
+            Either::First(()) => false,
+            Either::Second(ButtonPress::Short) => {
+                if let Some(message_id) = display_data.banner.acknowledge() {
+                    info!("Acknowledged message {} of the chase car", message_id);
+                    let acknowledge =
+                        EoiCanData::DisplayAcknowledge(DisplayAcknowledge { message_id });
+                    queue(&mut tx_queue, &acknowledge);
+                    transmit(can_tx, &mut tx_queue);
                 } else {
-                    warn!("Failed to parse data from CAN frame: {:?}", frame);
+                    pages.next();
+                    info!("Switching to page {}", defmt::Debug2Format(&pages.page()));
                 }
-            });
-            debug!("Parsed frames: {}", parsed_frames);
-            can_collector.clear();
+                true
+            }
+            Either::Second(ButtonPress::Long) => {
+                inverted = !inverted;
+                info!("Inverted theme: {}", inverted);
+                true
+            }
+            Either::Second(ButtonPress::Profile) => {
+                pages.set_profile(pages.profile().next());
+                info!(
+                    "Switching to profile {}",
+                    defmt::Debug2Format(&pages.profile())
+                );
+                true
+            }
+        };
+
+        let received_frames = RECEIVED_FRAMES.swap(0, Ordering::Relaxed);
+        trace!("Received frames: {}", received_frames);
+
+        // The synced clock stamps the log, which is only useful while it agrees with the GNSS time
+        if let Some(utc_us) = CLOCK.lock(|clock| clock.get().utc_us(Instant::now().as_micros())) {
+            display_data.check_clock(utc_us);
+        }
+
+        // The chase car asked for a page, the button still switches away from it
+        if let Some(requested_page) = display_data.requested_page.take() {
+            if requested_page != pages.page() {
+                pages.show(requested_page);
+                info!(
+                    "Switching to page {} for the chase car",
+                    defmt::Debug2Format(&requested_page)
+                );
+                force_refresh = true;
+            }
+        }
+        if let Some(requested_profile) = display_data.requested_profile.take() {
+            if requested_profile != pages.profile() {
+                pages.set_profile(requested_profile);
+                info!(
+                    "Switching to profile {} for the chase car",
+                    defmt::Debug2Format(&requested_profile)
+                );
+                force_refresh = true;
+            }
+        }
+        // The main page switches its layout, which shouldn't wait for the refresh policy
+        if let Some(active) = display_data.reserve.take_change() {
+            info!("Reserve mode: {}", active);
+            force_refresh = true;
+        }
+        if let Some(active) = display_data.cell_imbalance.take_change() {
+            warn!("Cell imbalance: {}", active);
+            can_log::log(
+                LogLevel::Warn,
+                if active {
+                    "Cell imbalance"
+                } else {
+                    "Cell imbalance cleared"
+                },
+            );
+        }
+        if let Some(active) = display_data.clock_check.take_change() {
+            warn!("Clock off GNSS time: {}", active);
+            can_log::log(
+                LogLevel::Warn,
+                if active {
+                    "Clock off GNSS time"
+                } else {
+                    "Clock back on GNSS time"
+                },
+            );
+        }
+        // The fault replaces the header of the motor section, which shouldn't wait either
+        while let Some((controller_id, fault)) = display_data.take_motor_fault_change() {
+            warn!("Motor controller {} fault: {}", controller_id, fault);
+            can_log::log(
+                LogLevel::Warn,
+                if fault.is_fault() {
+                    fault.label()
+                } else {
+                    "Motor fault cleared"
+                },
+            );
+            force_refresh = true;
+        }
+        status_led::set_status(LedStatus::from_data(&display_data, received_frames > 0));
+
+        let mut trip = display_data.trip;
+        trip.update(&display_data, last_trip_update.elapsed());
+        last_trip_update = Instant::now();
+        display_data.trip = trip;
+        persistence::save(
+            rtc,
+            &persistence::RaceState {
+                trip,
+                laps: display_data.lap_counter.laps(),
+                race_time_s: display_data
+                    .lap_counter
+                    .race_time()
+                    .map(|race_time| race_time.as_secs().min(u32::MAX as u64) as u32),
+                race_end_s: display_data.race_countdown.end_s(),
+            },
+        );
+
+        if received_frames > 0 {
+            last_can_activity = Instant::now();
+        }
+        if last_can_activity.elapsed() > SLEEP_AFTER_SILENCE {
+            info!(
+                "No CAN frames for {}, going to sleep",
+                last_can_activity.elapsed()
+            );
+            can_log::log(LogLevel::Info, "Going to sleep, the CAN bus is silent");
+            drop(display_data);
+            if inverted {
+                draw_display::draw_sleep_screen(&mut Inverted(&mut display)).unwrap();
+            } else {
+                draw_display::draw_sleep_screen(&mut display).unwrap();
+            }
+            epd.update_and_display_frame(&mut spi_device, display.buffer(), &mut Delay)
+                .unwrap();
+            epd.sleep(&mut spi_device, &mut Delay).unwrap();
+
+            status_led::set_sleeping(true);
+            drop(spi_device);
+            stop_mode::release_can(can);
+            stop_mode::sleep(&mut watchdog, &mut can_rx_exti).await;
+            // Safety: released above, nothing else uses these peripherals. The drivers reset them,
+            // so they are set up again like at boot
+            can = unsafe { new_can(CAN1::steal(), PB8::steal(), PB9::steal()) };
+            start_can(&mut can, bitrate).await;
+            spi_device = unsafe {
+                epaper_spi(
+                    SPI2::steal(),
+                    PB13::steal(),
+                    PB15::steal(),
+                    PB14::steal(),
+                    PB6::steal(),
+                )
+            };
+            status_led::set_sleeping(false);
+
+            info!("Waking up");
+            can_log::log(LogLevel::Info, "Woke up");
+            epd.wake_up(&mut spi_device, &mut Delay).unwrap();
+            last_can_activity = Instant::now();
+            force_refresh = true;
+            display_data = shared_display_data.lock().await;
+        }
+
+        if pages.rotate() {
+            force_refresh = true;
+        }
+
+        if force_refresh || refresh_policy.needs_refresh(&display_data) {
+            led_blue.set_low();
             info!("Updating display");
-            draw_display::draw_display(&mut display, &display_data).unwrap();
+            let mut stats = stack_usage::measure(Section::Draw, || {
+                if inverted {
+                    draw_display::draw_page_measured(
+                        &mut Inverted(&mut display),
+                        &display_data,
+                        pages.page(),
+                    )
+                    .unwrap()
+                } else {
+                    draw_display::draw_page_measured(&mut display, &display_data, pages.page())
+                        .unwrap()
+                }
+            });
+            refresh_policy.refreshed(&display_data);
+            // The frame buffer is the snapshot of the data, the CAN task can go on during the
+            // refresh, which takes seconds
+            drop(display_data);
+            let flush_start = Instant::now();
             epd.update_and_display_frame(&mut spi_device, display.buffer(), &mut Delay)
                 .unwrap();
             last_update_screen = Instant::now();
-            info!("Display updated");
-            led_green.set_high();
+            stats.flush = flush_start.elapsed();
+            shared_display_data.lock().await.render.frame(stats);
+            info!("Display updated in {}", defmt::Display2Format(&stats));
+            led_blue.set_high();
         }
-
-        Timer::after_secs(1).await;
     }
 }
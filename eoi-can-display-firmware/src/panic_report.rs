@@ -0,0 +1,128 @@
+//! Panic messages kept over the restart the panic causes, in RAM the startup code doesn't
+//! initialize. After the restart the message is sent once as [`DisplayPanic`] frames, so the data
+//! logger records why the display restarted during a race without a debug probe attached.
+
+use core::fmt::{self, Write};
+use core::mem::MaybeUninit;
+use core::panic::PanicInfo;
+use core::ptr::addr_of_mut;
+
+#[allow(unused_imports)]
+use defmt::{debug, error, info, trace, warn};
+use eoi_can_decoder::{DisplayPanic, EoiCanData, DISPLAY_PANIC_TEXT_LEN};
+
+/// Marks a report written by the panic handler, anything else is garbage after a power loss
+const MAGIC: u32 = 0xE01C_9A41;
+/// Longer messages are cut off, 18 frames at most
+const TEXT_LEN: usize = 126;
+/// Frames queued per iteration of the main loop, the rest of the telemetry still gets through
+const PARTS_PER_LOOP: usize = 2;
+
+#[repr(C)]
+struct Report {
+    magic: u32,
+    len: u32,
+    /// ASCII only, so it can be cut anywhere
+    text: [u8; TEXT_LEN],
+}
+
+#[link_section = ".uninit.PANIC_REPORT"]
+static mut REPORT: MaybeUninit<Report> = MaybeUninit::uninit();
+
+/// Writes into the report, cutting off what doesn't fit and replacing what isn't ASCII with `?`
+struct ReportWriter<'a> {
+    text: &'a mut [u8; TEXT_LEN],
+    len: usize,
+}
+
+impl Write for ReportWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &byte in s.as_bytes() {
+            let Some(slot) = self.text.get_mut(self.len) else {
+                break;
+            };
+            *slot = if byte.is_ascii() { byte } else { b'?' };
+            self.len += 1;
+        }
+        Ok(())
+    }
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    cortex_m::interrupt::disable();
+    error!("{}", defmt::Display2Format(info));
+
+    // Safety: the interrupts are disabled and the handler doesn't return, nothing else runs
+    let report = unsafe { (*addr_of_mut!(REPORT)).as_mut_ptr() };
+    unsafe {
+        let mut writer = ReportWriter {
+            text: &mut (*report).text,
+            len: 0,
+        };
+        let _ = write!(writer, "{}", info.message());
+        if let Some(location) = info.location() {
+            let _ = write!(writer, " at {}:{}", location.file(), location.line());
+        }
+        (*report).len = writer.len as u32;
+        (*report).magic = MAGIC;
+    }
+
+    cortex_m::peripheral::SCB::sys_reset();
+}
+
+/// The panic message left by the last run, taken so it is only sent once
+pub fn take() -> Option<PendingPanic> {
+    // Safety: called once at startup, before anything could panic again
+    let report = unsafe { (*addr_of_mut!(REPORT)).as_mut_ptr() };
+    let (magic, len) = unsafe {
+        (
+            core::ptr::read_volatile(core::ptr::addr_of!((*report).magic)),
+            core::ptr::read_volatile(core::ptr::addr_of!((*report).len)) as usize,
+        )
+    };
+    if magic != MAGIC || len > TEXT_LEN {
+        return None;
+    }
+    unsafe { core::ptr::write_volatile(addr_of_mut!((*report).magic), 0) };
+
+    let mut text = heapless::String::new();
+    for &byte in unsafe { &(*report).text[..len] } {
+        // Can't fail, the text is at most as long as the string
+        let _ = text.push(if byte.is_ascii() { byte as char } else { '?' });
+    }
+    Some(PendingPanic { text, sent: 0 })
+}
+
+/// A panic message of the last run, sent a few parts at a time by the main loop
+pub struct PendingPanic {
+    text: heapless::String<TEXT_LEN>,
+    /// Bytes already queued
+    sent: usize,
+}
+
+impl PendingPanic {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Queue the next parts as telemetry, returns whether the last part was queued
+    pub fn queue_next(&mut self, tx_queue: &mut crate::TxQueue) -> bool {
+        for _ in 0..PARTS_PER_LOOP {
+            let end = (self.sent + DISPLAY_PANIC_TEXT_LEN).min(self.text.len());
+            let part = DisplayPanic {
+                part: (self.sent / DISPLAY_PANIC_TEXT_LEN) as u8 & 0x7F,
+                last: end == self.text.len(),
+                // Can't fail, the text is ASCII and the part not longer than a frame
+                text: heapless::String::try_from(&self.text[self.sent..end]).unwrap_or_default(),
+            };
+            let last = part.last;
+            crate::queue(tx_queue, &EoiCanData::DisplayPanic(part));
+            self.sent = end;
+            if last {
+                return true;
+            }
+        }
+        false
+    }
+}
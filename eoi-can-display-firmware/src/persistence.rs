@@ -0,0 +1,103 @@
+//! Race state kept in the RTC backup registers, which keep their value over resets and brownouts as
+//! long as the backup battery is connected. A CRC protects against garbage after a full power loss.
+//! The bitrate of the bus is kept next to it, so a restart doesn't have to detect it again.
+
+use draw_display::TripCounters;
+use embassy_stm32::rtc::Rtc;
+
+/// Change this when the layout changes, so an old layout isn't loaded as the new one
+const MAGIC: u32 = 0xE01C_0002;
+/// Words of the state, followed by the CRC in the next backup register
+const WORDS: usize = 7;
+/// Race time of a race that didn't start yet
+const NOT_STARTED: u32 = u32::MAX;
+/// Race end without a countdown
+const NO_RACE_END: u32 = u32::MAX;
+/// Register of the bitrate, after the CRC of the race state
+const BITRATE_REGISTER: usize = WORDS + 1;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RaceState {
+    pub trip: TripCounters,
+    pub laps: u16,
+    /// `None` before the race started
+    pub race_time_s: Option<u32>,
+    /// End of the race as second of the UTC day, `None` without a countdown
+    pub race_end_s: Option<u32>,
+}
+
+impl RaceState {
+    fn to_words(self) -> [u32; WORDS] {
+        [
+            MAGIC,
+            self.trip.distance_km.to_bits(),
+            self.trip.motor_energy_wh.to_bits(),
+            self.trip.solar_energy_wh.to_bits(),
+            self.laps as u32,
+            self.race_time_s.unwrap_or(NOT_STARTED),
+            self.race_end_s.unwrap_or(NO_RACE_END),
+        ]
+    }
+
+    fn from_words(words: [u32; WORDS]) -> Option<Self> {
+        if words[0] != MAGIC {
+            return None;
+        }
+        Some(Self {
+            trip: TripCounters {
+                distance_km: f32::from_bits(words[1]),
+                motor_energy_wh: f32::from_bits(words[2]),
+                solar_energy_wh: f32::from_bits(words[3]),
+            },
+            laps: words[4].try_into().ok()?,
+            race_time_s: (words[5] != NOT_STARTED).then_some(words[5]),
+            race_end_s: (words[6] != NO_RACE_END).then_some(words[6]),
+        })
+    }
+}
+
+pub fn save(rtc: &Rtc, state: &RaceState) {
+    let words = state.to_words();
+    for (register, word) in words.iter().enumerate() {
+        rtc.write_backup_register(register, *word);
+    }
+    rtc.write_backup_register(WORDS, crc32(&words));
+}
+
+/// The state saved before the last reset, `None` when there is none or it is corrupted
+pub fn load(rtc: &Rtc) -> Option<RaceState> {
+    let mut words = [0; WORDS];
+    for (register, word) in words.iter_mut().enumerate() {
+        *word = rtc.read_backup_register(register)?;
+    }
+    if rtc.read_backup_register(WORDS)? != crc32(&words) {
+        return None;
+    }
+    RaceState::from_words(words)
+}
+
+pub fn save_bitrate(rtc: &Rtc, bitrate: u32) {
+    rtc.write_backup_register(BITRATE_REGISTER, bitrate);
+}
+
+/// The bitrate detected before the last reset, `None` when there is none or it is garbage
+pub fn load_bitrate(rtc: &Rtc) -> Option<u32> {
+    rtc.read_backup_register(BITRATE_REGISTER)
+        .filter(|bitrate| crate::bitrate::BITRATES.contains(bitrate))
+}
+
+/// CRC-32 (IEEE) over the little endian bytes of the words
+fn crc32(words: &[u32]) -> u32 {
+    let mut crc = !0_u32;
+    for byte in words.iter().flat_map(|word| word.to_le_bytes()) {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
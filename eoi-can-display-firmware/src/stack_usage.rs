@@ -0,0 +1,111 @@
+//! Stack high-water marks of drawing a page and taking in the frames, so the growing display
+//! features are known to still fit the RAM of the STM32L4. Nothing is measured without the
+//! `stack-usage` feature.
+//!
+//! The free stack below the stack pointer is painted with a pattern before a section runs, the
+//! lowest word that doesn't hold it anymore afterwards is how deep the section went. A new worst
+//! case is logged with defmt as one line of numbers, like
+//! `stack-usage draw=9872 decode=1424 size=81920`, which CI can grep from the output of probe-rs.
+//! All tasks run on the one stack of the executor, the futures themselves live in its arena.
+//!
+//! The firmware has no allocator, so all formatting is heap-free already: a `String` or `format!`
+//! would fail to link.
+
+use core::ptr;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+#[allow(unused_imports)]
+use defmt::{debug, error, info, trace, warn};
+
+/// Written into the free stack, words still holding it were never used
+const PAINT: u32 = 0xCAFE_F00D;
+/// Left alone below the stack pointer while painting, for the frames of the painting itself
+const MARGIN: usize = 256;
+
+extern "C" {
+    /// Top of the stack, from the linker script of cortex-m-rt
+    static _stack_start: u32;
+    /// End of the statics, the stack can grow down to here
+    static __sheap: u32;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    /// Drawing a page into the frame buffer
+    Draw,
+    /// Taking a decoded frame into the display data
+    Decode,
+}
+
+/// Worst stack usage in bytes per section, the sections run in different tasks
+static DRAW: AtomicUsize = AtomicUsize::new(0);
+static DECODE: AtomicUsize = AtomicUsize::new(0);
+
+/// Run `f`, and log its stack usage when it is the worst so far for `section`
+pub fn measure<R>(section: Section, f: impl FnOnce() -> R) -> R {
+    if !cfg!(feature = "stack-usage") {
+        return f();
+    }
+    let entry = stack_pointer();
+    let painted_to = paint();
+    let result = f();
+    let used = entry - deepest(painted_to);
+
+    let worst = match section {
+        Section::Draw => &DRAW,
+        Section::Decode => &DECODE,
+    };
+    if used > worst.fetch_max(used, Ordering::Relaxed) {
+        let (bottom, top) = bounds();
+        info!(
+            "stack-usage draw={=usize} decode={=usize} size={=usize}",
+            DRAW.load(Ordering::Relaxed),
+            DECODE.load(Ordering::Relaxed),
+            top - bottom
+        );
+    }
+    result
+}
+
+/// Lowest and highest address of the stack
+fn bounds() -> (usize, usize) {
+    // Only the addresses of the symbols are taken, they are never read
+    unsafe {
+        (
+            ptr::addr_of!(__sheap) as usize,
+            ptr::addr_of!(_stack_start) as usize,
+        )
+    }
+}
+
+fn stack_pointer() -> usize {
+    cortex_m::register::msp::read() as usize
+}
+
+/// Fill the free stack up to a margin below the stack pointer with the pattern, returns the end
+/// of the painted area
+#[inline(never)]
+fn paint() -> usize {
+    let (bottom, _) = bounds();
+    let end = stack_pointer() - MARGIN;
+    let mut word = bottom as *mut u32;
+    while (word as usize) < end {
+        // Below the stack pointer nothing else uses the stack, the statics end at the bottom
+        unsafe {
+            word.write_volatile(PAINT);
+            word = word.add(1);
+        }
+    }
+    end
+}
+
+/// Lowest address below `end` that was written since it was painted, `end` when none was
+fn deepest(end: usize) -> usize {
+    let (bottom, _) = bounds();
+    let mut word = bottom as *const u32;
+    // Only painted words are read
+    while (word as usize) < end && unsafe { word.read_volatile() } == PAINT {
+        word = unsafe { word.add(1) };
+    }
+    word as usize
+}
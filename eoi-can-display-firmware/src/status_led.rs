@@ -0,0 +1,82 @@
+//! Drives the status LEDs with the patterns of [`draw_display::LedStatus`]: the green heartbeat
+//! while CAN frames come in and the red blink code of the most important alarm. It runs in a task
+//! of its own, a refresh of the e-paper blocks the main loop for seconds.
+
+use core::cell::Cell;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use defmt::info;
+use draw_display::LedStatus;
+use embassy_stm32::gpio::{Level, Output};
+use embassy_sync::blocking_mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Timer};
+
+/// Without a frame for this long the bus counts as silent
+const CAN_FLOWING_TIMEOUT: Duration = Duration::from_secs(2);
+/// Quick enough for the shortest beat of the patterns
+const TICK: Duration = Duration::from_millis(20);
+
+/// The alarms of the display data, set by the main loop
+static STATUS: blocking_mutex::Mutex<CriticalSectionRawMutex, Cell<LedStatus>> =
+    blocking_mutex::Mutex::new(Cell::new(LedStatus {
+        can_flowing: false,
+        battery_fault: false,
+        display_error: false,
+    }));
+
+static LAST_FRAME: blocking_mutex::Mutex<CriticalSectionRawMutex, Cell<Option<Instant>>> =
+    blocking_mutex::Mutex::new(Cell::new(None));
+
+/// The LEDs are off while the display sleeps, their tick would keep the MCU out of STOP mode
+static SLEEPING: AtomicBool = AtomicBool::new(false);
+static WOKEN_UP: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Called by the receiver for every frame
+pub fn frame_received() {
+    LAST_FRAME.lock(|last| last.set(Some(Instant::now())));
+}
+
+/// Called by the main loop after taking in new data, whether frames come in is tracked here
+pub fn set_status(status: LedStatus) {
+    STATUS.lock(|cell| cell.set(status));
+}
+
+/// Called by the main loop when the display goes to sleep and wakes up
+pub fn set_sleeping(sleeping: bool) {
+    SLEEPING.store(sleeping, Ordering::Relaxed);
+    if !sleeping {
+        WOKEN_UP.signal(());
+    }
+}
+
+/// The LEDs are low active
+#[embassy_executor::task]
+pub async fn status_leds(mut green: Output<'static>, mut red: Output<'static>) {
+    let mut last_alarm = None;
+    loop {
+        if SLEEPING.load(Ordering::Relaxed) {
+            green.set_high();
+            red.set_high();
+            WOKEN_UP.wait().await;
+            continue;
+        }
+        let can_flowing = LAST_FRAME
+            .lock(|last| last.get())
+            .is_some_and(|at| at.elapsed() < CAN_FLOWING_TIMEOUT);
+        let status = LedStatus {
+            can_flowing,
+            ..STATUS.lock(|cell| cell.get())
+        };
+        if status.alarm() != last_alarm {
+            last_alarm = status.alarm();
+            info!("Status LED: {}", defmt::Debug2Format(&last_alarm));
+        }
+
+        let leds = status.leds_at(Duration::from_micros(Instant::now().as_micros()));
+        green.set_level(if leds.green { Level::Low } else { Level::High });
+        red.set_level(if leds.red { Level::Low } else { Level::High });
+        Timer::after(TICK).await;
+    }
+}
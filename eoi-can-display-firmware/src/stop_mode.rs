@@ -0,0 +1,53 @@
+//! STOP mode while the display sleeps. The low-power executor of embassy-stm32 enters it whenever
+//! no driver holds the clock of a peripheral and the next timer is far enough away, the RTC keeps
+//! the time meanwhile. The CAN peripheral and the SPI of the e-paper are turned off for it, so the
+//! start of a frame is noticed on the RX pin through the EXTI, and the frame itself is lost. With
+//! the `black-box` feature the SPI of its flash keeps the MCU running.
+
+use defmt::info;
+use embassy_futures::select::{select3, Either3};
+use embassy_stm32::can::Can;
+use embassy_stm32::exti::ExtiInput;
+use embassy_stm32::gpio::Pull;
+use embassy_stm32::peripherals::{CAN1, EXTI8, IWDG, PB8};
+use embassy_stm32::wdg::IndependentWatchdog;
+use embassy_time::{Duration, Timer};
+
+use crate::BUTTON_PRESSES;
+
+/// The RTC wakes the MCU this often to pet the watchdog, which keeps running in STOP mode
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Resets the CAN peripheral and gates its clock, which the driver only enables
+pub fn release_can(can: Can<'static>) {
+    drop(can);
+    embassy_stm32::rcc::disable::<CAN1>();
+}
+
+/// Wait in STOP mode until a frame starts on the bus or the button is pressed, the press only
+/// wakes the display. The CAN peripheral has to be released before
+pub async fn sleep(watchdog: &mut IndependentWatchdog<'_, IWDG>, exti: &mut EXTI8) {
+    // Safety: the CAN peripheral is off, nothing else uses its RX pin until this returns
+    let mut can_rx = ExtiInput::new(unsafe { PB8::steal() }, exti, Pull::None);
+    loop {
+        watchdog.pet();
+        // Dominant is low, the start of frame is the first falling edge
+        match select3(
+            Timer::after(WATCHDOG_INTERVAL),
+            can_rx.wait_for_falling_edge(),
+            BUTTON_PRESSES.receive(),
+        )
+        .await
+        {
+            Either3::First(()) => {}
+            Either3::Second(()) => {
+                info!("Woken up by a frame");
+                return;
+            }
+            Either3::Third(press) => {
+                info!("Woken up by a button press: {}", press);
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,233 @@
+//! Raised and cleared alarms appended to a file on the datalogger, so the history survives the
+//! power being switched off at the dock. One line per event with the system time like the
+//! candump logs, and the GNSS time when there was a fix:
+//! `(1718900000.250000) raised motor-fault 2024-06-20T14:05:30Z VESC 9: FET hot`
+//!
+//! On start the last events of the log are read back into the history, so the alarm page and the
+//! API still show what happened before a restart.
+
+use draw_display::{ALARM_HISTORY_LENGTH, Alarm, AlarmEvent, AlarmHistory};
+use eoi_can_decoder::{GnssDateTime, VescFault};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub struct AlarmLog {
+    file: File,
+    /// Events of the history already written
+    logged: u32,
+}
+
+impl AlarmLog {
+    /// Append to the log at `path`, which is created if it doesn't exist yet
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file, logged: 0 })
+    }
+
+    /// Write the events recorded since the last call and sync them to the disk
+    pub fn write(&mut self, history: &AlarmHistory) -> io::Result<()> {
+        if history.recorded() == self.logged {
+            return Ok(());
+        }
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        for event in history.since(self.logged) {
+            self.file
+                .write_all(format_alarm_line(timestamp, event).as_bytes())?;
+        }
+        self.logged = history.recorded();
+        self.file.sync_data()
+    }
+}
+
+/// The last events of the log at `path` that fit in the history, oldest first. Lines that can't
+/// be parsed are skipped, a log that doesn't exist yet has none
+pub fn read_alarm_log(path: &Path) -> io::Result<Vec<AlarmEvent>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error),
+    };
+    let mut events = VecDeque::with_capacity(ALARM_HISTORY_LENGTH);
+    for line in BufReader::new(file).lines() {
+        let Some(event) = parse_alarm_line(&line?) else {
+            continue;
+        };
+        if events.len() == ALARM_HISTORY_LENGTH {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+    Ok(events.into())
+}
+
+/// A line of [`format_alarm_line`], without the time it was recorded at, which is of an earlier
+/// run
+pub fn parse_alarm_line(line: &str) -> Option<AlarmEvent> {
+    let mut fields = line.splitn(5, ' ');
+    let _timestamp = fields.next()?;
+    let raised = match fields.next()? {
+        "raised" => true,
+        "cleared" => false,
+        _ => return None,
+    };
+    let name = fields.next()?;
+    let time = match fields.next()? {
+        "-" => None,
+        time => Some(parse_time(time)?),
+    };
+    let alarm = match name {
+        "reserve" => Alarm::Reserve,
+        "cell-imbalance" => Alarm::CellImbalance,
+        "clock-divergence" => Alarm::ClockDivergence,
+        "charge-check" => Alarm::ChargeCheck,
+        "soc-disagreement" => Alarm::SocDisagreement,
+        "battery-cutoff" => Alarm::BatteryCutoff,
+        "throttle" => Alarm::Throttle,
+        "motor-fault" => {
+            let (controller_id, label) = fields.next()?.strip_prefix("VESC ")?.split_once(": ")?;
+            Alarm::MotorFault {
+                controller_id: controller_id.parse().ok()?,
+                fault: (0..=u8::MAX)
+                    .map(VescFault::from)
+                    .find(|fault| fault.label() == label)?,
+            }
+        }
+        _ => return None,
+    };
+    Some(AlarmEvent {
+        alarm,
+        raised,
+        at: None,
+        time,
+    })
+}
+
+/// Like `2024-06-20T14:05:30Z`
+fn parse_time(time: &str) -> Option<GnssDateTime> {
+    let (date, time) = time.strip_suffix('Z')?.split_once('T')?;
+    let mut date = date.splitn(3, '-');
+    let mut time = time.splitn(3, ':');
+    Some(GnssDateTime {
+        year: date.next()?.parse().ok()?,
+        month: date.next()?.parse().ok()?,
+        day: date.next()?.parse().ok()?,
+        hours: time.next()?.parse().ok()?,
+        minutes: time.next()?.parse().ok()?,
+        seconds: time.next()?.parse().ok()?,
+    })
+}
+
+pub fn format_alarm_line(timestamp: Duration, event: &AlarmEvent) -> String {
+    let time = match event.time {
+        Some(time) => format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            time.year, time.month, time.day, time.hours, time.minutes, time.seconds
+        ),
+        None => String::from("-"),
+    };
+    format!(
+        "({}.{:06}) {} {} {} {}\n",
+        timestamp.as_secs(),
+        timestamp.subsec_micros(),
+        if event.raised { "raised" } else { "cleared" },
+        event.alarm.name(),
+        time,
+        event.alarm
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use draw_display::{Alarm, Instant};
+    use eoi_can_decoder::{GnssDateTime, VescFault};
+
+    #[test]
+    fn line_per_event() {
+        let mut event = AlarmEvent {
+            alarm: Alarm::MotorFault {
+                controller_id: 9,
+                fault: VescFault::OverTempFet,
+            },
+            raised: true,
+            at: Some(Instant::now()),
+            time: Some(GnssDateTime {
+                year: 2024,
+                month: 6,
+                day: 20,
+                hours: 14,
+                minutes: 5,
+                seconds: 30,
+            }),
+        };
+        let timestamp = Duration::from_millis(1_718_900_000_250);
+        assert!(
+            format_alarm_line(timestamp, &event)
+                == "(1718900000.250000) raised motor-fault 2024-06-20T14:05:30Z VESC 9: FET hot\n"
+        );
+
+        event.alarm = Alarm::CellImbalance;
+        event.raised = false;
+        event.time = None;
+        assert!(
+            format_alarm_line(timestamp, &event)
+                == "(1718900000.250000) cleared cell-imbalance - Cell imbalance\n"
+        );
+    }
+
+    #[test]
+    fn lines_read_back() {
+        let fault = Alarm::MotorFault {
+            controller_id: 9,
+            fault: VescFault::OverTempFet,
+        };
+        let time = GnssDateTime {
+            year: 2024,
+            month: 6,
+            day: 20,
+            hours: 14,
+            minutes: 5,
+            seconds: 30,
+        };
+        for event in [
+            AlarmEvent {
+                alarm: fault,
+                raised: true,
+                at: Some(Instant::now()),
+                time: Some(time),
+            },
+            AlarmEvent {
+                alarm: Alarm::BatteryCutoff,
+                raised: false,
+                at: Some(Instant::now()),
+                time: None,
+            },
+        ] {
+            let line = format_alarm_line(Duration::from_secs(1_718_900_000), &event);
+            let read = parse_alarm_line(line.trim_end()).unwrap();
+            assert!(read == AlarmEvent { at: None, ..event });
+        }
+        assert!(parse_alarm_line("(1718900000.250000) raised unknown - Unknown").is_none());
+        assert!(parse_alarm_line("").is_none());
+
+        let path = std::env::temp_dir().join(format!("alarm-log-test-{}", std::process::id()));
+        assert!(read_alarm_log(&path).unwrap().is_empty());
+        let mut log = AlarmLog::open(&path).unwrap();
+        let mut history = AlarmHistory::default();
+        for _ in 0..ALARM_HISTORY_LENGTH {
+            history.update(std::iter::once(Alarm::Throttle).collect(), None);
+            history.update(Default::default(), None);
+            log.write(&history).unwrap();
+        }
+        let events = read_alarm_log(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert!(events.len() == ALARM_HISTORY_LENGTH);
+        assert!(!events.last().unwrap().raised);
+    }
+}
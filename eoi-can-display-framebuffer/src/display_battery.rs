@@ -0,0 +1,140 @@
+//! The battery of the display, polled from the PiSugar server in a task of its own and taken
+//! into the display data by the main loop. A PiSugar that doesn't answer leaves the last reading,
+//! which goes stale like any other value.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use draw_display::DisplayData;
+use embedded_can::Frame;
+use eoi_can_decoder::can_encoder::encode_eoi_can_data;
+use eoi_can_decoder::{DisplayBattery, EoiCanData};
+use pisugar::PiSugarClient;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+/// How often the PiSugar is asked for the state of the display battery, well within the timeout
+/// of the display values
+pub const PISUGAR_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Latest reading of the PiSugar not taken by the main loop yet, the state of charge in percent
+/// and whether it charges
+pub type SharedDisplayBattery = Arc<Mutex<Option<(f32, bool)>>>;
+
+/// Poll the PiSugar every `interval` for the state of the display battery and hand it to the main
+/// loop, which takes it out again. Also sends it on the CAN bus when a socket is given.
+pub async fn poll_display_battery(
+    pisugar: PiSugarClient,
+    interval: Duration,
+    display_battery: SharedDisplayBattery,
+    can_sock: Option<socketcan::tokio::AsyncCanSocket<socketcan::CanSocket>>,
+) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        let (state_of_charge, charging) = match pisugar.battery_info().await {
+            Ok(battery) => battery,
+            Err(error) => {
+                debug!(
+                    "Failed to get the display battery from the PiSugar: {}",
+                    error
+                );
+                continue;
+            }
+        };
+        *display_battery.lock().unwrap() = Some((state_of_charge, charging));
+
+        let Some(can_sock) = &can_sock else {
+            continue;
+        };
+        let data = EoiCanData::DisplayBattery(DisplayBattery {
+            state_of_charge,
+            charging,
+        });
+        let Some(frame) = encode_eoi_can_data(&data) else {
+            warn!("Unable to encode {:?}", data);
+            continue;
+        };
+        let Some(socket_frame) = socketcan::CanFrame::new(frame.id, &frame.data) else {
+            continue;
+        };
+        if let Err(error) = can_sock.write_frame(socket_frame).await {
+            warn!("Failed to send the display battery: {:?}", error);
+        }
+    }
+}
+
+/// Take the reading of the PiSugar into the display data, returns it when there was a new one
+/// since the last call
+pub fn take_display_battery(
+    display_battery: &SharedDisplayBattery,
+    display_data: &mut DisplayData,
+) -> Option<(f32, bool)> {
+    let (state_of_charge, charging) = display_battery.lock().unwrap().take()?;
+    display_data.display_state_of_charge.update(state_of_charge);
+    display_data.display_is_charging.update(charging);
+    Some((state_of_charge, charging))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use pisugar::simulated::{Fault, SimulatedPiSugar};
+
+    const INTERVAL: Duration = Duration::from_millis(10);
+
+    /// Wait for the poll task to hand over a reading
+    async fn next_reading(display_battery: &SharedDisplayBattery, data: &mut DisplayData) -> bool {
+        for _ in 0..50 {
+            if take_display_battery(display_battery, data).is_some() {
+                return true;
+            }
+            tokio::time::sleep(INTERVAL).await;
+        }
+        false
+    }
+
+    async fn wait_for_requests(server: &SimulatedPiSugar, count: usize) {
+        let requests = server.requests();
+        while server.requests() < requests + count {
+            tokio::time::sleep(INTERVAL).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn display_battery_from_the_pisugar() {
+        let server = SimulatedPiSugar::start().await.unwrap();
+        server.set_state_of_charge(42.0);
+        server.set_plugged(true);
+        let display_battery = SharedDisplayBattery::default();
+        let poll = tokio::spawn(poll_display_battery(
+            server.client(),
+            INTERVAL,
+            display_battery.clone(),
+            None,
+        ));
+
+        let mut data = DisplayData::default();
+        assert!(next_reading(&display_battery, &mut data).await);
+        assert!(data.display_state_of_charge.get() == Some(&42.0));
+        assert!(data.display_is_charging.get() == Some(&true));
+
+        // A cut off answer isn't taken as a state of charge, the display keeps the last one. A
+        // reading from before the fault may still be waiting
+        server.set_fault(Some(Fault::PartialResponse));
+        wait_for_requests(&server, 3).await;
+        take_display_battery(&display_battery, &mut data);
+        wait_for_requests(&server, 3).await;
+        assert!(take_display_battery(&display_battery, &mut data).is_none());
+        assert!(data.display_state_of_charge.get() == Some(&42.0));
+
+        server.set_state_of_charge(41.0);
+        server.set_plugged(false);
+        server.set_fault(None);
+        assert!(next_reading(&display_battery, &mut data).await);
+        assert!(data.display_state_of_charge.get() == Some(&41.0));
+        assert!(data.display_is_charging.get() == Some(&false));
+        poll.abort();
+    }
+}
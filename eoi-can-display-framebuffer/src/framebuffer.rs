@@ -0,0 +1,415 @@
+//! Linux framebuffer (`/dev/fb0`) drawn off-screen and flushed by changed lines only. Most of
+//! the page stays the same between frames, so this saves writing the whole screen 10 times a
+//! second, which keeps a Pi Zero busy.
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use embedded_graphics::pixelcolor::Rgb888;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+pub const DEFAULT_DEVICE: &str = "/dev/fb0";
+
+/// Frames between flushing every line, the console or another program may draw on the
+/// framebuffer too and this repairs it
+const FULL_FLUSH_INTERVAL: u32 = 100;
+
+/// Offsets the image is drawn at in turn, a pixel at most so it isn't noticed
+const PIXEL_SHIFTS: [Point; 4] = [
+    Point::new(0, 0),
+    Point::new(1, 0),
+    Point::new(1, 1),
+    Point::new(0, 1),
+];
+
+/// Moves the image by a pixel every interval, so the edges of the lines and labels that stay the
+/// same on every page don't burn into an LCD that is always on
+#[derive(Debug)]
+pub struct PixelShift {
+    interval: Duration,
+    last: Instant,
+    step: usize,
+}
+
+impl PixelShift {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last: Instant::now(),
+            step: 0,
+        }
+    }
+
+    /// The offset to draw the next frame at
+    pub fn update(&mut self, now: Instant) -> Point {
+        if now.duration_since(self.last) >= self.interval {
+            self.last = now;
+            self.step = (self.step + 1) % PIXEL_SHIFTS.len();
+        }
+        PIXEL_SHIFTS[self.step]
+    }
+}
+
+/// Layout of a pixel in the framebuffer, little-endian like on the Pi
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PixelFormat {
+    Rgb565,
+    Bgr888,
+    Xrgb8888,
+}
+
+impl PixelFormat {
+    fn from_bits_per_pixel(bits_per_pixel: u32) -> Option<Self> {
+        match bits_per_pixel {
+            16 => Some(Self::Rgb565),
+            24 => Some(Self::Bgr888),
+            32 => Some(Self::Xrgb8888),
+            _ => None,
+        }
+    }
+
+    fn bytes_per_pixel(self) -> usize {
+        match self {
+            Self::Rgb565 => 2,
+            Self::Bgr888 => 3,
+            Self::Xrgb8888 => 4,
+        }
+    }
+
+    /// The bytes of `color`, only the first [`Self::bytes_per_pixel`] are used
+    fn encode(self, color: Rgb888) -> [u8; 4] {
+        let (r, g, b) = (color.r(), color.g(), color.b());
+        match self {
+            Self::Rgb565 => {
+                let [low, high] =
+                    ((u16::from(r >> 3) << 11) | (u16::from(g >> 2) << 5) | u16::from(b >> 3))
+                        .to_le_bytes();
+                [low, high, 0, 0]
+            }
+            Self::Bgr888 => [b, g, r, 0],
+            Self::Xrgb8888 => [b, g, r, 0xFF],
+        }
+    }
+}
+
+pub struct FrameBuffer {
+    device: File,
+    size: Size,
+    format: PixelFormat,
+    /// Bytes of a line, can be more than the pixels of a line
+    stride: usize,
+    /// Drawn into, the next frame
+    back: Vec<u8>,
+    /// What was flushed to the device
+    front: Vec<u8>,
+    frames_since_full_flush: u32,
+    /// Colors are drawn inverted, for a dark theme at night or a bright one in the sun
+    inverted: bool,
+    /// Offset of the image, see [`PixelShift`]
+    shift: Point,
+}
+
+impl FrameBuffer {
+    /// Open a framebuffer device like [`DEFAULT_DEVICE`], its size and pixel format are read from
+    /// `/sys/class/graphics`
+    pub fn open(device: impl AsRef<Path>) -> io::Result<Self> {
+        let device = device.as_ref();
+        let name = device
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Not a framebuffer"))?;
+        let sysfs = Path::new("/sys/class/graphics").join(name);
+        let read = |attribute: &str| -> io::Result<String> {
+            Ok(std::fs::read_to_string(sysfs.join(attribute))?
+                .trim()
+                .to_string())
+        };
+        let invalid = |attribute: &str, value: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unsupported framebuffer {}: {}", attribute, value),
+            )
+        };
+
+        let virtual_size = read("virtual_size")?;
+        let (width, height) = virtual_size
+            .split_once(',')
+            .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+            .ok_or_else(|| invalid("virtual_size", &virtual_size))?;
+        let bits_per_pixel = read("bits_per_pixel")?;
+        let format = bits_per_pixel
+            .parse()
+            .ok()
+            .and_then(PixelFormat::from_bits_per_pixel)
+            .ok_or_else(|| invalid("bits_per_pixel", &bits_per_pixel))?;
+        let stride = read("stride")?;
+        let stride = stride.parse().map_err(|_| invalid("stride", &stride))?;
+
+        let device = OpenOptions::new().read(true).write(true).open(device)?;
+        Self::new(device, Size::new(width, height), format, stride)
+    }
+
+    fn new(device: File, size: Size, format: PixelFormat, stride: usize) -> io::Result<Self> {
+        if stride < size.width as usize * format.bytes_per_pixel() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Framebuffer stride is shorter than a line",
+            ));
+        }
+        let length = stride * size.height as usize;
+        Ok(Self {
+            device,
+            size,
+            format,
+            stride,
+            back: vec![0; length],
+            front: vec![0; length],
+            // Nothing is known about the screen yet
+            frames_since_full_flush: FULL_FLUSH_INTERVAL,
+            inverted: false,
+            shift: Point::zero(),
+        })
+    }
+
+    /// Draw the following frames moved by `shift`, what is moved out of the screen is cut off
+    pub fn set_shift(&mut self, shift: Point) {
+        if shift != self.shift {
+            self.shift = shift;
+            // The edge the image moved away from is left black
+            self.back.fill(0);
+        }
+    }
+
+    /// Draw the following frames with inverted colors, like the theme of the e-paper display
+    pub fn set_inverted(&mut self, inverted: bool) {
+        self.inverted = inverted;
+    }
+
+    pub fn is_inverted(&self) -> bool {
+        self.inverted
+    }
+
+    /// The bytes of `color` in the theme
+    fn encode(&self, color: Rgb888) -> [u8; 4] {
+        let color = if self.inverted {
+            Rgb888::new(!color.r(), !color.g(), !color.b())
+        } else {
+            color
+        };
+        self.format.encode(color)
+    }
+
+    /// Write the lines that changed since the last flush to the device
+    pub fn flush(&mut self) -> io::Result<()> {
+        let lines = self.flush_changed_lines()?;
+        trace!("Flushed {} lines", lines);
+        Ok(())
+    }
+
+    /// Returns the number of written lines, neighbouring lines are written together
+    fn flush_changed_lines(&mut self) -> io::Result<usize> {
+        let full = self.frames_since_full_flush >= FULL_FLUSH_INTERVAL;
+        self.frames_since_full_flush = if full {
+            0
+        } else {
+            self.frames_since_full_flush + 1
+        };
+
+        let stride = self.stride;
+        let lines = self.size.height as usize;
+        let changed = |front: &[u8], back: &[u8], line: usize| {
+            let bytes = line * stride..(line + 1) * stride;
+            full || front[bytes.clone()] != back[bytes]
+        };
+
+        let mut written = 0;
+        let mut line = 0;
+        while line < lines {
+            if !changed(&self.front, &self.back, line) {
+                line += 1;
+                continue;
+            }
+            let first = line;
+            while line < lines && changed(&self.front, &self.back, line) {
+                line += 1;
+            }
+            let bytes = first * stride..line * stride;
+            self.device
+                .write_all_at(&self.back[bytes.clone()], bytes.start as u64)?;
+            self.front[bytes.clone()].copy_from_slice(&self.back[bytes]);
+            written += line - first;
+        }
+        Ok(written)
+    }
+
+    fn offset(&self, point: Point) -> Option<usize> {
+        let x = usize::try_from(point.x).ok()?;
+        let y = usize::try_from(point.y).ok()?;
+        if x >= self.size.width as usize || y >= self.size.height as usize {
+            return None;
+        }
+        Some(y * self.stride + x * self.format.bytes_per_pixel())
+    }
+}
+
+impl OriginDimensions for FrameBuffer {
+    fn size(&self) -> Size {
+        self.size
+    }
+}
+
+impl DrawTarget for FrameBuffer {
+    type Color = Rgb888;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let bytes_per_pixel = self.format.bytes_per_pixel();
+        for Pixel(point, color) in pixels {
+            if let Some(offset) = self.offset(point + self.shift) {
+                let bytes = self.encode(color);
+                self.back[offset..offset + bytes_per_pixel]
+                    .copy_from_slice(&bytes[..bytes_per_pixel]);
+            }
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let area = area
+            .translate(self.shift)
+            .intersection(&self.bounding_box());
+        let Some(bottom_right) = area.bottom_right() else {
+            return Ok(());
+        };
+        let bytes_per_pixel = self.format.bytes_per_pixel();
+        let bytes = self.encode(color);
+        for y in area.top_left.y..=bottom_right.y {
+            // Both corners are within the bounds after the intersection
+            let start = self.offset(Point::new(area.top_left.x, y)).unwrap();
+            let end = self.offset(Point::new(bottom_right.x, y)).unwrap() + bytes_per_pixel;
+            for pixel in self.back[start..end].chunks_exact_mut(bytes_per_pixel) {
+                pixel.copy_from_slice(&bytes[..bytes_per_pixel]);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+
+    #[test]
+    fn flushes_changed_lines() {
+        let path = std::env::temp_dir().join(format!("framebuffer-{}", std::process::id()));
+        let device = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .read(true)
+            .write(true)
+            .open(&path)
+            .unwrap();
+        // Padded lines, like some drivers do
+        let mut framebuffer =
+            FrameBuffer::new(device, Size::new(4, 6), PixelFormat::Rgb565, 10).unwrap();
+
+        // The first frame is written completely
+        assert!(framebuffer.flush_changed_lines().unwrap() == 6);
+        assert!(framebuffer.flush_changed_lines().unwrap() == 0);
+
+        Pixel(Point::new(1, 1), Rgb888::WHITE)
+            .draw(&mut framebuffer)
+            .unwrap();
+        Rectangle::new(Point::new(2, 3), Size::new(5, 2))
+            .into_styled(embedded_graphics::primitives::PrimitiveStyle::with_fill(
+                Rgb888::RED,
+            ))
+            .draw(&mut framebuffer)
+            .unwrap();
+        Pixel(Point::new(9, 9), Rgb888::WHITE)
+            .draw(&mut framebuffer)
+            .unwrap();
+        assert!(framebuffer.flush_changed_lines().unwrap() == 3);
+
+        let written = std::fs::read(&path).unwrap();
+        assert!(written.len() == 60);
+        assert!(written[12..14] == [0xFF, 0xFF]);
+        assert!(written[30..38] == [0, 0, 0, 0, 0x00, 0xF8, 0x00, 0xF8]);
+        assert!(written[40..44] == [0, 0, 0, 0]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn shifted_image() {
+        let path = std::env::temp_dir().join(format!("framebuffer-shift-{}", std::process::id()));
+        let device = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .read(true)
+            .write(true)
+            .open(&path)
+            .unwrap();
+        let mut framebuffer =
+            FrameBuffer::new(device, Size::new(2, 2), PixelFormat::Rgb565, 4).unwrap();
+        framebuffer.set_shift(Point::new(1, 1));
+        framebuffer.clear(Rgb888::WHITE).unwrap();
+        Pixel(Point::new(0, 0), Rgb888::RED)
+            .draw(&mut framebuffer)
+            .unwrap();
+        framebuffer.flush().unwrap();
+
+        // Moved to the bottom right, the rest of the clear is cut off
+        let written = std::fs::read(&path).unwrap();
+        assert!(written == [0, 0, 0, 0, 0, 0, 0x00, 0xF8]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn pixel_shift_moves_on() {
+        let mut shift = PixelShift::new(Duration::from_secs(60));
+        let start = shift.last;
+        assert!(shift.update(start + Duration::from_secs(59)) == Point::zero());
+        assert!(shift.update(start + Duration::from_secs(60)) == Point::new(1, 0));
+        assert!(shift.update(start + Duration::from_secs(120)) == Point::new(1, 1));
+    }
+
+    #[test]
+    fn inverted_theme() {
+        let path =
+            std::env::temp_dir().join(format!("framebuffer-inverted-{}", std::process::id()));
+        let device = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .read(true)
+            .write(true)
+            .open(&path)
+            .unwrap();
+        let mut framebuffer =
+            FrameBuffer::new(device, Size::new(2, 1), PixelFormat::Xrgb8888, 8).unwrap();
+        framebuffer.set_inverted(true);
+        Pixel(Point::new(0, 0), Rgb888::WHITE)
+            .draw(&mut framebuffer)
+            .unwrap();
+        Pixel(Point::new(1, 0), Rgb888::new(0xFF, 0x80, 0))
+            .draw(&mut framebuffer)
+            .unwrap();
+        framebuffer.flush().unwrap();
+
+        let written = std::fs::read(&path).unwrap();
+        assert!(written == [0, 0, 0, 0xFF, 0xFF, 0x7F, 0, 0xFF]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
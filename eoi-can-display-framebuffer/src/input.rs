@@ -0,0 +1,153 @@
+//! Keypads and rotary encoders at the helm, read from their evdev devices. They drive the display
+//! like the button drives the e-paper display: switch the pages, acknowledge the message of the
+//! chase car and toggle the inverted theme. A device that was unplugged is opened again once it is
+//! back.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use eoi_can_decoder::{DisplayAcknowledge, EoiCanData};
+use eoi_can_source::CanTransmitter;
+use evdev::{Device, EventStream, EventSummary, InputEvent, KeyCode, RelativeAxisCode};
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+/// How often a missing input device is tried again
+const REOPEN_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputAction {
+    NextPage,
+    PreviousPage,
+    /// Acknowledge the message of the chase car, without a message switch to the next page
+    Acknowledge,
+    /// Toggle the inverted theme
+    ToggleTheme,
+}
+
+/// What an input event does, `None` for most, like releasing or holding a key
+pub fn action(event: InputEvent) -> Option<InputAction> {
+    match event.destructure() {
+        EventSummary::Key(_, key, 1) => match key {
+            KeyCode::KEY_RIGHT
+            | KeyCode::KEY_DOWN
+            | KeyCode::KEY_PAGEDOWN
+            | KeyCode::KEY_TAB
+            | KeyCode::KEY_KPPLUS
+            | KeyCode::KEY_NEXTSONG => Some(InputAction::NextPage),
+            KeyCode::KEY_LEFT
+            | KeyCode::KEY_UP
+            | KeyCode::KEY_PAGEUP
+            | KeyCode::KEY_BACKSPACE
+            | KeyCode::KEY_KPMINUS
+            | KeyCode::KEY_PREVIOUSSONG => Some(InputAction::PreviousPage),
+            KeyCode::KEY_ENTER
+            | KeyCode::KEY_KPENTER
+            | KeyCode::KEY_SPACE
+            | KeyCode::KEY_OK
+            | KeyCode::KEY_SELECT
+            | KeyCode::KEY_PLAYPAUSE => Some(InputAction::Acknowledge),
+            KeyCode::KEY_T | KeyCode::KEY_KPASTERISK | KeyCode::KEY_MENU => {
+                Some(InputAction::ToggleTheme)
+            }
+            _ => None,
+        },
+        // A detent of a rotary encoder, clockwise is positive
+        EventSummary::RelativeAxis(
+            _,
+            RelativeAxisCode::REL_DIAL | RelativeAxisCode::REL_WHEEL | RelativeAxisCode::REL_HWHEEL,
+            value,
+        ) => match value {
+            1.. => Some(InputAction::NextPage),
+            ..0 => Some(InputAction::PreviousPage),
+            0 => None,
+        },
+        _ => None,
+    }
+}
+
+/// Read the input device at `path` and send its actions to the main loop, until the main loop is
+/// gone. The device is opened again after it was unplugged
+pub async fn read_input(path: PathBuf, actions: mpsc::Sender<InputAction>) {
+    loop {
+        match open(&path) {
+            Ok(mut events) => {
+                info!("Reading input from {:?}", path);
+                loop {
+                    let event = match events.next_event().await {
+                        Ok(event) => event,
+                        Err(error) => {
+                            warn!("Lost the input device {:?}: {}", path, error);
+                            break;
+                        }
+                    };
+                    let Some(action) = action(event) else {
+                        continue;
+                    };
+                    debug!("Input: {:?}", action);
+                    match actions.try_send(action) {
+                        Ok(()) => {}
+                        Err(TrySendError::Full(action)) => {
+                            warn!("Too many inputs, dropping {:?}", action);
+                        }
+                        Err(TrySendError::Closed(_)) => return,
+                    }
+                }
+            }
+            Err(error) => debug!("Unable to open the input device {:?}: {}", path, error),
+        }
+        tokio::time::sleep(REOPEN_INTERVAL).await;
+    }
+}
+
+fn open(path: &Path) -> io::Result<EventStream> {
+    let mut device = Device::open(path)?;
+    // Otherwise the keys also end up on the console of the Pi
+    if let Err(error) = device.grab() {
+        warn!("Unable to grab the input device {:?}: {}", path, error);
+    }
+    device.into_event_stream()
+}
+
+/// Tell the chase car and the other displays that the message `message_id` was acknowledged
+pub fn send_acknowledge(transmitter: &CanTransmitter, message_id: u8) {
+    transmitter.send_data(&EoiCanData::DisplayAcknowledge(DisplayAcknowledge {
+        message_id,
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use evdev::EventType;
+
+    fn key(key: KeyCode, value: i32) -> InputEvent {
+        InputEvent::new(EventType::KEY.0, key.0, value)
+    }
+
+    fn dial(value: i32) -> InputEvent {
+        InputEvent::new(EventType::RELATIVE.0, RelativeAxisCode::REL_DIAL.0, value)
+    }
+
+    #[test]
+    fn actions_of_keys_and_encoders() {
+        assert!(action(key(KeyCode::KEY_RIGHT, 1)) == Some(InputAction::NextPage));
+        assert!(action(key(KeyCode::KEY_PAGEUP, 1)) == Some(InputAction::PreviousPage));
+        assert!(action(key(KeyCode::KEY_KPENTER, 1)) == Some(InputAction::Acknowledge));
+        assert!(action(key(KeyCode::KEY_T, 1)) == Some(InputAction::ToggleTheme));
+        // Only the press counts, not releasing or holding the key
+        assert!(action(key(KeyCode::KEY_ENTER, 0)).is_none());
+        assert!(action(key(KeyCode::KEY_ENTER, 2)).is_none());
+        assert!(action(key(KeyCode::KEY_A, 1)).is_none());
+
+        assert!(action(dial(1)) == Some(InputAction::NextPage));
+        assert!(action(dial(-2)) == Some(InputAction::PreviousPage));
+        // A mouse moving isn't an encoder
+        let mouse = InputEvent::new(EventType::RELATIVE.0, RelativeAxisCode::REL_X.0, 5);
+        assert!(action(mouse).is_none());
+    }
+}
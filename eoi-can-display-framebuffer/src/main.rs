@@ -1,84 +1,448 @@
-use clap::Parser;
-use embedded_can::Frame;
-use embedded_graphics_framebuffer::FrameBufferDisplay;
-use eoi_can_decoder::{can_collector, parse_eoi_can_data};
-use get_wifi_ip::get_wifi_ip;
+use alarm_log::{AlarmLog, read_alarm_log};
+use can_logger::CanLogger;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
+use display_battery::{
+    PISUGAR_POLL_INTERVAL, SharedDisplayBattery, poll_display_battery, take_display_battery,
+};
+use draw_display::{
+    AggregatePolicy, CellImbalance, ClockCheck, DEFAULT_CLOCK_DIVERGENCE,
+    DEFAULT_IMBALANCE_THRESHOLD, DEFAULT_RESERVE_STATE_OF_CHARGE, DEFAULT_SOC_DISAGREEMENT,
+    FinishLine, LapCounter, PageSelection, PanelId, ParkedMode, Profile, ReserveMode, Screensaver,
+    SocSelection, SocSource, SolarPanels,
+};
+use eoi_can_decoder::calibration::Calibration;
+use eoi_can_decoder::{
+    DecoderConfig, MAX_MPPT_DEVICES, MAX_VESC_CONTROLLERS, VescData, can_collector,
+    parse_eoi_can_data_with_config,
+};
+use eoi_can_source::{CanReader, CanTransmitter, LogFilter};
+use framebuffer::{FrameBuffer, PixelShift};
+use get_wifi_ip::watch_ip;
+use input::{InputAction, read_input, send_acknowledge};
+use pisugar::PiSugarClient;
+use shutdown::LowBatteryWatch;
+#[cfg(feature = "http")]
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 #[allow(unused_imports)]
 use tracing::{Level, debug, error, info, trace, warn};
-use tracing_subscriber::filter::{EnvFilter, LevelFilter};
-use tracing_subscriber::prelude::*;
+use tracing_subscriber::filter::LevelFilter;
+
+mod alarm_log;
+mod display_battery;
+mod framebuffer;
+mod input;
+mod shutdown;
+
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    /// CAN interface
-    #[arg(short, long, default_value_t = String::from("can0"))]
-    can_interface: String,
-}
+    /// CAN interface, give it multiple times to read more than one bus
+    #[arg(short, long = "can-interface", default_values_t = [String::from("can0")])]
+    can_interfaces: Vec<String>,
 
-fn register_tracing_subscriber(level_filter: LevelFilter) {
-    tracing_subscriber::registry()
-        .with(
-            EnvFilter::builder()
-                .with_default_directive(level_filter.into())
-                .from_env_lossy(),
-        )
-        .with(
-            tracing_subscriber::fmt::layer()
-                .with_file(true)
-                .with_line_number(true),
-        )
-        .init();
+    /// Bring the CAN interface up with this bitrate when it is down, like 500000, which needs
+    /// `CAP_NET_ADMIN`
+    #[arg(long)]
+    can_bitrate: Option<u32>,
+
+    /// Never send anything on the bus, for attaching to a bus inspected by others. An interface
+    /// brought up with `--can-bitrate` doesn't even acknowledge frames, the display battery isn't
+    /// broadcast
+    #[arg(long)]
+    listen_only: bool,
+
+    /// Count frames with the same ID and payload arriving within this many milliseconds once,
+    /// like when a bus is read directly and over the UDP bridge. 0 keeps every frame
+    #[arg(long, default_value_t = 0)]
+    dedup_window: u64,
+
+    /// Name of the WiFi interface shown with its IP address, `*` matches any characters
+    #[arg(long, default_value_t = String::from(get_wifi_ip::DEFAULT_INTERFACE_PATTERN))]
+    wifi_interface: String,
+
+    /// VESC controller ID, give it multiple times for boats with more than one motor
+    #[arg(
+        long = "vesc-id",
+        default_values_t = [DecoderConfig::DEFAULT_VESC_CONTROLLER_ID],
+        value_parser = clap::value_parser!(u8).range(..i64::from(VescData::BROADCAST_CONTROLLER_ID))
+    )]
+    vesc_ids: Vec<u8>,
+
+    /// Also decode frames longer than their message, like from nodes with a newer protocol
+    #[arg(long)]
+    permissive_dlc: bool,
+
+    /// MPPTs on the bus, with the IDs from 0 up. Other nodes may use the IDs of the others
+    #[arg(long, default_value_t = MAX_MPPT_DEVICES as u8)]
+    mppt_devices: u8,
+
+    /// Solar panels in the order they are numbered, by the MPPT ID and the channel they are wired
+    /// to, like 2.1,2.2,5.0. Without it the panels of the boat
+    #[arg(long, value_delimiter = ',')]
+    mppt_panels: Vec<PanelId>,
+
+    /// Every solar panel seen on the bus ordered by MPPT ID and channel, instead of `--mppt-panels`
+    #[arg(long, conflicts_with = "mppt_panels")]
+    observed_panels: bool,
+
+    /// Correct signals with a known error of their sensor before decoding them, by the scale and
+    /// offset per signal of a TOML file like `eoi-can-decoder/calibration.toml`
+    #[arg(long)]
+    calibration: Option<PathBuf>,
+
+    /// Directory to write candump compatible logs of all received frames to
+    #[arg(long)]
+    log_dir: Option<PathBuf>,
+
+    /// File to append the raised and cleared alarms to, so they can be reviewed after a run
+    #[arg(long)]
+    alarm_log: Option<PathBuf>,
+
+    /// Start/finish line for counting laps, as latitude,longitude of both ends,
+    /// like 43.7340,7.4210,43.7345,7.4220
+    #[arg(long)]
+    finish_line: Option<FinishLine>,
+
+    /// Profile deciding the pages and how they rotate: race, test-bench, charging, diagnostics
+    /// or exhibition
+    #[arg(long, default_value = "race")]
+    profile: Profile,
+
+    /// State of charge in percent below which the main page switches to the economy layout of
+    /// the reserve mode
+    #[arg(long, default_value_t = DEFAULT_RESERVE_STATE_OF_CHARGE)]
+    reserve_soc: f32,
+
+    /// Difference between the highest and lowest cell voltage in V which, sustained for 10
+    /// seconds, raises the cell imbalance alarm
+    #[arg(long, default_value_t = DEFAULT_IMBALANCE_THRESHOLD)]
+    imbalance_threshold: f32,
+
+    /// Divergence in s of the system clock from the GNSS time which raises the clock alarm, the
+    /// CAN logs of the Pi are stamped by it
+    #[arg(long, default_value_t = DEFAULT_CLOCK_DIVERGENCE)]
+    clock_divergence: f32,
+
+    /// Sources of the state of charge in the order they are preferred, a later one is shown
+    /// while the ones before it are stale: bms, ah-count and voltage. The others are ignored,
+    /// the voltage follows a generic curve and has to be added for packs it fits
+    #[arg(long, value_delimiter = ',', default_value = "bms,ah-count")]
+    soc_sources: Vec<SocSource>,
+
+    /// Capacity of the pack in Ah, for counting its charge from the pack current
+    #[arg(long)]
+    pack_capacity: Option<f32>,
+
+    /// Difference in percentage points between two sources of the state of charge which,
+    /// sustained for 30 seconds, raises the SoC disagreement alarm
+    #[arg(long, default_value_t = DEFAULT_SOC_DISAGREEMENT)]
+    soc_disagreement: f32,
+
+    /// Correct the time to empty, the range and the energy budget of the race for a cold pack,
+    /// with the usable state of charge by temperature of a TOML file like
+    /// `draw-display/soc-correction.toml`
+    #[arg(long)]
+    soc_correction: Option<PathBuf>,
+
+    /// Show the temperature of every group of cells under the cell bars, with the cells of every
+    /// temperature sensor of a TOML file like `draw-display/cell-temperatures.toml`
+    #[arg(long)]
+    cell_temperatures: Option<PathBuf>,
+
+    /// How the net power handles stale or missing currents: strict shows nothing, partial
+    /// leaves them out and last-known takes their last value, both marked with a "≈"
+    #[arg(long, default_value = "strict")]
+    aggregate_policy: AggregatePolicy,
+
+    /// Show the minimum, maximum and average since the start under the readouts of the first page
+    /// of the profile
+    #[arg(long)]
+    statistics: bool,
+
+    /// Keypad or rotary encoder at the helm switching the pages, acknowledging the message of the
+    /// chase car and toggling the inverted theme, an evdev device like
+    /// `/dev/input/by-id/usb-...-event-kbd`. Give it multiple times for more than one
+    #[arg(long = "input")]
+    inputs: Vec<PathBuf>,
+
+    /// Start with the inverted theme
+    #[arg(long)]
+    inverted: bool,
+
+    /// Move the image by a pixel every this many seconds, so the static parts of the pages don't
+    /// burn into the LCD. 0 keeps it still
+    #[arg(long, default_value_t = 120)]
+    pixel_shift: u64,
+
+    /// Minutes without moving after which only the clock and the state of charge are shown,
+    /// moving around the screen, against burn-in of the LCD at the dock. 0 never starts it
+    #[arg(long, default_value_t = 10)]
+    screensaver_after: u16,
+
+    /// Minutes standing still and not charging after which the main page shows the QR code of the
+    /// dashboard and the summary of the session
+    #[arg(long, default_value_t = 5)]
+    parked_after: u16,
+
+    /// Address of the dashboard in the QR code of the parked screen, `{ip}` is replaced by the IP
+    /// address of the WiFi, like http://{ip}:3000/d/boat for a Grafana dashboard. Without it
+    /// there is no QR code
+    #[arg(long)]
+    dashboard_url: Option<String>,
+
+    /// Framebuffer device of the display
+    #[arg(long, default_value_t = String::from(framebuffer::DEFAULT_DEVICE))]
+    framebuffer: String,
+
+    /// Address of the PiSugar server reporting the display battery
+    #[arg(long, default_value_t = String::from(PiSugarClient::DEFAULT_ADDRESS))]
+    pisugar: String,
+
+    /// Send the state of the display battery on the CAN bus, so the other displays can show it.
+    /// It is sent on the first CAN interface
+    #[arg(long)]
+    broadcast_display_battery: bool,
+
+    /// Power off the datalogger when the display battery stays below this state of charge in
+    /// percent, so the logs are synced before the power is gone
+    #[arg(long)]
+    shutdown_below: Option<f32>,
+
+    /// Command used to power off the datalogger
+    #[arg(long, default_value_t = String::from("systemctl poweroff"))]
+    shutdown_command: String,
+
+    /// Address to serve the display data on over HTTP (`/api/state`, `/api/alarms` and `/metrics`),
+    /// like 0.0.0.0:8080
+    #[cfg(feature = "http")]
+    #[arg(long)]
+    http: Option<SocketAddr>,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), core::convert::Infallible> {
-    register_tracing_subscriber(LevelFilter::DEBUG);
+    #[cfg_attr(not(feature = "http"), allow(unused_variables))]
+    let log_filter = LogFilter::init(LevelFilter::DEBUG);
+    eoi_can_source::install_panic_hook("eoi-can-display-framebuffer");
     let args = Args::parse();
-    info!("CAN interface: {}", args.can_interface);
+    info!("CAN interfaces: {:?}", args.can_interfaces);
+    info!("VESC controller IDs: {:?}", args.vesc_ids);
+    let calibration = args
+        .calibration
+        .as_ref()
+        .map_or_else(Calibration::default, |path| {
+            let text = std::fs::read_to_string(path).expect("Unable to read the calibration");
+            toml::from_str(&text).expect("Invalid calibration")
+        });
+    for signal in &calibration.signals {
+        info!(
+            "Calibrated {} of {}: * {} + {}",
+            signal.signal.name, signal.message.name, signal.scale, signal.offset
+        );
+    }
+    let Some(decoder_config) = DecoderConfig::with_vesc_controller_ids(&args.vesc_ids) else {
+        Args::command()
+            .error(
+                ErrorKind::TooManyValues,
+                format!(
+                    "--vesc-id can be given at most {} times",
+                    MAX_VESC_CONTROLLERS
+                ),
+            )
+            .exit();
+    };
+    let decoder_config = decoder_config
+        .with_permissive_dlc(args.permissive_dlc)
+        .with_mppt_devices(args.mppt_devices)
+        .with_calibration(calibration);
 
-    let shared_can_collector = Arc::new(Mutex::new(can_collector::CanCollector::new()));
+    let shared_can_collector = Arc::new(Mutex::new(
+        can_collector::CanCollector::new().with_dedup_window(args.dedup_window),
+    ));
 
     let can_collector_receiver = shared_can_collector.clone();
 
-    let can_sock: socketcan::tokio::AsyncCanSocket<socketcan::CanSocket> =
-        socketcan::tokio::AsyncCanSocket::open(args.can_interface.as_str())
-            .expect("Unable to open CAN socket");
-    info!("Connected to CAN interface: {}", args.can_interface);
+    let (_, mut can_frames) =
+        eoi_can_source::spawn_can_readers(args.can_interfaces.iter().map(|interface| {
+            let reader = CanReader::new(interface).with_listen_only(args.listen_only);
+            match args.can_bitrate {
+                Some(bitrate) => reader.with_bring_up(bitrate),
+                None => reader,
+            }
+        }));
+    let mut can_states = can_frames.states();
+
+    // Shared, so it can be synced and closed before shutting down
+    let can_logger = Arc::new(Mutex::new(args.log_dir.as_ref().map(|dir| {
+        info!("Logging CAN frames to {:?}", dir);
+        CanLogger::new(dir, &args.can_interfaces[0])
+            .expect("Unable to create CAN log file")
+            .with_interfaces(&args.can_interfaces)
+    })));
+    let can_logger_receiver = can_logger.clone();
+
+    let mut alarm_log = args.alarm_log.as_ref().map(|path| {
+        info!("Logging alarms to {:?}", path);
+        AlarmLog::open(path).expect("Unable to open the alarm log")
+    });
+    let earlier_alarms = match &args.alarm_log {
+        Some(path) => read_alarm_log(path).unwrap_or_else(|error| {
+            warn!("Unable to read the earlier alarms of {:?}: {}", path, error);
+            Vec::new()
+        }),
+        None => Vec::new(),
+    };
 
     // Spawn a task to read CAN frames
     tokio::spawn(async move {
-        loop {
-            let frame = can_sock.read_frame().await.unwrap();
-
-            let embedded_frame = if let socketcan::CanFrame::Data(frame) = frame {
-                trace!(
-                    "Received CAN frame: ID: {:?}, Data: {:?}",
-                    frame.id(),
-                    frame.data()
-                );
-
-                eoi_can_decoder::can_frame::CanFrame::from_encoded(frame.id(), frame.data())
-            } else {
-                debug!("Received non-data CAN frame: {:?}", frame);
-                continue;
-            };
+        // Time base of the de-duplication window
+        let reader_start = std::time::Instant::now();
+        while let Some((frame, received)) = can_frames.recv_timestamped().await {
+            if let Some(logger) = can_logger_receiver.lock().unwrap().as_mut()
+                && let Err(error) = logger.log_at(
+                    received.duration_since(UNIX_EPOCH).unwrap_or_default(),
+                    &frame,
+                )
+            {
+                warn!("Failed to log CAN frame: {:?}", error);
+            }
 
             if let Ok(mut collector) = can_collector_receiver.lock() {
-                collector.insert(embedded_frame);
+                collector.insert_at(frame, reader_start.elapsed().as_millis() as u64);
             }
         }
     });
 
-    let mut display = FrameBufferDisplay::new();
+    let display_battery = SharedDisplayBattery::default();
+    if args.broadcast_display_battery && args.listen_only {
+        warn!("Listen-only, the display battery is not broadcast");
+    }
+    let broadcast_sock = if args.broadcast_display_battery && !args.listen_only {
+        socketcan::tokio::AsyncCanSocket::open(args.can_interfaces[0].as_str())
+            .inspect_err(|error| {
+                error!(
+                    "Unable to open CAN socket, the display battery is not broadcast: {}",
+                    error
+                )
+            })
+            .ok()
+    } else {
+        None
+    };
+    tokio::spawn(poll_display_battery(
+        PiSugarClient::new(args.pisugar.as_str()),
+        PISUGAR_POLL_INTERVAL,
+        display_battery.clone(),
+        broadcast_sock,
+    ));
+
+    #[cfg(feature = "http")]
+    let status = args.http.map(|address| {
+        status_server::Status::default()
+            .with_log_filter(log_filter)
+            .start(address)
+    });
+
+    let (input_sender, mut input_actions) = tokio::sync::mpsc::channel(8);
+    for path in &args.inputs {
+        tokio::spawn(read_input(path.clone(), input_sender.clone()));
+    }
+    // Acknowledged messages are hidden on the other displays too
+    let acknowledge_transmitter = if args.inputs.is_empty() || args.listen_only {
+        CanTransmitter::listen_only()
+    } else {
+        match socketcan::tokio::AsyncCanSocket::open(args.can_interfaces[0].as_str()) {
+            Ok(can_sock) => CanTransmitter::spawn(can_sock).1,
+            Err(error) => {
+                error!(
+                    "Unable to open CAN socket, acknowledges are not sent: {}",
+                    error
+                );
+                CanTransmitter::listen_only()
+            }
+        }
+    };
+
+    let mut display = FrameBuffer::open(&args.framebuffer).expect("Unable to open the framebuffer");
+    display.set_inverted(args.inverted);
     display.flush().unwrap();
 
     let mut display_data = draw_display::DisplayData::default();
-    draw_display::draw_display(&mut display, &display_data).unwrap();
+    if !earlier_alarms.is_empty() {
+        info!("Read {} earlier alarm events", earlier_alarms.len());
+        display_data.alarm_history.restore(earlier_alarms);
+    }
+    if let Some(finish_line) = args.finish_line {
+        info!("Counting laps over the finish line {:?}", finish_line);
+        display_data.lap_counter = LapCounter::new(finish_line);
+    }
+    display_data.reserve = ReserveMode::new(args.reserve_soc);
+    let parked = ParkedMode::new(
+        Duration::from_secs(u64::from(args.parked_after) * 60)
+            .try_into()
+            .unwrap(),
+    );
+    display_data.parked = match &args.dashboard_url {
+        Some(url) => parked.with_dashboard_url(url),
+        None => parked,
+    };
+    display_data.cell_imbalance = CellImbalance::new(args.imbalance_threshold);
+    display_data.clock_check = ClockCheck::new(args.clock_divergence);
+    let soc_selection = SocSelection::new(&args.soc_sources, args.soc_disagreement);
+    display_data.soc_selection = match args.pack_capacity {
+        Some(capacity_ah) => soc_selection.with_capacity_ah(capacity_ah),
+        None => soc_selection,
+    };
+    info!("SoC sources: {:?}", display_data.soc_selection.order());
+    if let Some(path) = &args.soc_correction {
+        let text = std::fs::read_to_string(path).expect("Unable to read the SoC correction");
+        display_data.soc_correction = toml::from_str(&text).expect("Invalid SoC correction");
+        info!("SoC correction: {:?}", display_data.soc_correction);
+    }
+    if let Some(path) = &args.cell_temperatures {
+        let text = std::fs::read_to_string(path).expect("Unable to read the cell temperatures");
+        display_data.cell_temperature_map =
+            toml::from_str(&text).expect("Invalid cell temperatures");
+        info!("Cell temperatures: {:?}", display_data.cell_temperature_map);
+    }
+    display_data.aggregate_policy = args.aggregate_policy;
+    display_data.mppt_panel_info = if args.observed_panels {
+        SolarPanels::observed()
+    } else if args.mppt_panels.is_empty() {
+        SolarPanels::default()
+    } else {
+        SolarPanels::configured(&args.mppt_panels).expect("Too many solar panels given")
+    };
+    // Switched by the inputs, the chase car and the rotation of the profile
+    let mut pages = PageSelection::new(args.profile);
+    if args.statistics {
+        pages.toggle_statistics();
+    }
+    display_data.statistics_overlay = pages.shows_statistics();
+    draw_display::draw_page(&mut display, &display_data, pages.page()).unwrap();
     display.flush().unwrap();
 
-    let mut display_battery_last_update = std::time::Instant::now();
+    let mut pixel_shift =
+        (args.pixel_shift > 0).then(|| PixelShift::new(Duration::from_secs(args.pixel_shift)));
+    let mut screensaver = (args.screensaver_after > 0).then(|| {
+        Screensaver::new(
+            Duration::from_secs(u64::from(args.screensaver_after) * 60)
+                .try_into()
+                .unwrap(),
+        )
+    });
+
+    let mut low_battery_watch = args.shutdown_below.map(LowBatteryWatch::new);
+    let mut last_trip_update = std::time::Instant::now();
+
+    // Without netlink the IP address just isn't shown
+    let wifi = watch_ip(&args.wifi_interface)
+        .inspect_err(|error| warn!("Unable to watch the WiFi interface: {:?}", error))
+        .ok();
 
     loop {
         if let Ok(mut can_collector) = shared_can_collector.lock() {
@@ -88,7 +452,16 @@ async fn main() -> Result<(), core::convert::Infallible> {
             let mut parsed_frames = 0_u32;
             can_collector.iter().for_each(|frame| {
                 trace!("Paring CAN frame: {:?}", frame);
-                if let Some(parsed_data) = parse_eoi_can_data(frame) {
+                #[cfg(feature = "http")]
+                if let Some(status) = &status {
+                    let interface = args.can_interfaces.get(usize::from(frame.bus));
+                    status.publish_frame(interface.unwrap_or(&args.can_interfaces[0]), frame);
+                }
+                if let Some(parsed_data) = parse_eoi_can_data_with_config(frame, &decoder_config) {
+                    #[cfg(feature = "http")]
+                    if let Some(status) = &status {
+                        status.publish(&parsed_data);
+                    }
                     display_data.ingest_eoi_can_data(parsed_data);
                     parsed_frames = parsed_frames.saturating_add(1);
                 } else {
@@ -96,23 +469,160 @@ async fn main() -> Result<(), core::convert::Infallible> {
                 }
             });
             trace!("Parsed frames: {}", parsed_frames);
+            eoi_can_source::log_new_id_collisions(&mut can_collector);
             can_collector.clear();
         }
 
-        if let Some(ip) = get_wifi_ip() {
-            display_data.ip_address.update(ip);
+        while let Ok(action) = input_actions.try_recv() {
+            // The first key only wakes the display up
+            if let Some(screensaver) = screensaver.as_mut()
+                && screensaver.is_active()
+            {
+                screensaver.wake();
+                continue;
+            }
+            match action {
+                InputAction::NextPage => pages.next(),
+                InputAction::PreviousPage => pages.previous(),
+                InputAction::Acknowledge => {
+                    if let Some(message_id) = display_data.banner.acknowledge() {
+                        info!("Acknowledged message {} of the chase car", message_id);
+                        send_acknowledge(&acknowledge_transmitter, message_id);
+                    } else {
+                        pages.next();
+                    }
+                }
+                InputAction::ToggleTheme => {
+                    let inverted = !display.is_inverted();
+                    display.set_inverted(inverted);
+                    info!("Inverted theme: {}", inverted);
+                }
+            }
+        }
+
+        if let Some(requested_page) = display_data.requested_page.take() {
+            info!("Showing page {:?} for the chase car", requested_page);
+            pages.show(requested_page);
+            if let Some(screensaver) = screensaver.as_mut() {
+                screensaver.wake();
+            }
+        }
+        if let Some(requested_profile) = display_data.requested_profile.take() {
+            info!(
+                "Switching to profile {:?} for the chase car",
+                requested_profile
+            );
+            pages.set_profile(requested_profile);
+        }
+        if let Some(active) = display_data.reserve.take_change() {
+            info!("Reserve mode: {}", active);
+        }
+        if let Some(active) = display_data.parked.take_change() {
+            info!("Parked: {}", active);
+        }
+        let clock_utc_us = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+        display_data.check_clock(clock_utc_us);
+        if let Some(active) = display_data.cell_imbalance.take_change() {
+            warn!("Cell imbalance: {}", active);
+        }
+        if let Some(active) = display_data.clock_check.take_change() {
+            warn!("Clock off GNSS time: {}", active);
+        }
+        while let Some((controller_id, fault)) = display_data.take_motor_fault_change() {
+            warn!("Motor controller {} fault: {:?}", controller_id, fault);
+        }
+        if let Some(log) = alarm_log.as_mut()
+            && let Err(error) = log.write(&display_data.alarm_history)
+        {
+            warn!("Failed to log the alarms: {:?}", error);
+        }
+        pages.rotate();
+
+        // The distance and energy of the trip, the exhibition page shows the solar energy
+        let mut trip = display_data.trip;
+        trip.update(
+            &display_data,
+            last_trip_update.elapsed().try_into().unwrap(),
+        );
+        last_trip_update = std::time::Instant::now();
+        display_data.trip = trip;
+
+        if let Some(wifi) = &wifi
+            && let Some(wifi) = wifi.borrow().as_ref()
+        {
+            if let Some(ip) = wifi.ipv4() {
+                display_data.ip_address.update(ip);
+            }
+            if let Some(signal) = wifi.signal {
+                display_data.wifi_level_dbm.update(signal.level_dbm);
+            }
         }
 
-        if display_battery_last_update.elapsed() > Duration::from_secs(1) {
-            display_battery_last_update = std::time::Instant::now();
-            if let Ok((state_of_charge, charging)) = pisugar::battery_info().await {
-                display_data.display_state_of_charge.update(state_of_charge);
-                display_data.display_is_charging.update(charging);
+        display_data.can_interface_up.update(!can_states.any_down());
+        if let Some(fault) = can_states.new_fault() {
+            display_data.can_bus_fault.update(fault.label());
+        }
+
+        if let Some((state_of_charge, charging)) =
+            take_display_battery(&display_battery, &mut display_data)
+            && let Some(watch) = low_battery_watch.as_mut()
+            && watch.update(state_of_charge, charging, std::time::Instant::now())
+        {
+            warn!(
+                "Display battery at {:.0}%, shutting down the datalogger",
+                state_of_charge
+            );
+            draw_display::draw_shutdown_screen(&mut display, state_of_charge).unwrap();
+            display.flush().unwrap();
+            // Closes the log, frames received from now on aren't logged anymore
+            if let Some(mut logger) = can_logger.lock().unwrap().take()
+                && let Err(error) = logger.sync()
+            {
+                error!("Failed to sync the CAN log: {:?}", error);
+            }
+            match shutdown::power_off(&args.shutdown_command) {
+                // Keep showing the shutdown screen until the power is gone
+                Ok(()) => std::future::pending().await,
+                Err(error) => {
+                    error!("Failed to shut down: {}", error);
+                    low_battery_watch = None;
+                }
+            }
+        }
+
+        #[cfg(feature = "http")]
+        if let Some(status) = &status {
+            status.update(&display_data);
+        }
+
+        if let Some(pixel_shift) = pixel_shift.as_mut() {
+            display.set_shift(pixel_shift.update(std::time::Instant::now()));
+        }
+        if let Some(screensaver) = screensaver.as_mut() {
+            let was_active = screensaver.is_active();
+            if screensaver.update(&display_data) != was_active {
+                info!("Screensaver: {}", screensaver.is_active());
+            }
+            if screensaver.is_active() {
+                draw_display::draw_screensaver(&mut display, &display_data, screensaver).unwrap();
+                display.flush().unwrap();
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
             }
         }
 
-        draw_display::draw_display(&mut display, &display_data).unwrap();
+        display_data.statistics_overlay = pages.shows_statistics();
+        let mut stats =
+            draw_display::draw_page_measured(&mut display, &display_data, pages.page()).unwrap();
+        let flush_start = std::time::Instant::now();
         display.flush().unwrap();
+        stats.flush = flush_start.elapsed().try_into().unwrap();
+        if display_data.render.frame(stats) {
+            debug!("Render: {}", display_data.render);
+        }
 
         tokio::time::sleep(Duration::from_millis(100)).await
     }
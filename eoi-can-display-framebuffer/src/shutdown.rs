@@ -0,0 +1,76 @@
+//! Safe shutdown of the datalogger when the PiSugar battery of the display runs out, so the log
+//! files are synced instead of corrupted by the power cut.
+
+use std::io;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+/// The battery has to stay low for this long, so a single bad reading doesn't power off the
+/// datalogger in the middle of a race
+const LOW_BATTERY_GRACE: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub struct LowBatteryWatch {
+    threshold: f32,
+    low_since: Option<Instant>,
+}
+
+impl LowBatteryWatch {
+    /// Watch for the state of charge to drop below `threshold` percent
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            threshold,
+            low_since: None,
+        }
+    }
+
+    /// Add a reading of the battery, returns `true` when it is time to shut down
+    pub fn update(&mut self, state_of_charge: f32, charging: bool, now: Instant) -> bool {
+        if charging || state_of_charge >= self.threshold {
+            self.low_since = None;
+            return false;
+        }
+        let low_since = *self.low_since.get_or_insert(now);
+        now.duration_since(low_since) >= LOW_BATTERY_GRACE
+    }
+}
+
+/// Run the shutdown command, like `systemctl poweroff`
+pub fn power_off(command: &str) -> io::Result<()> {
+    let mut parts = command.split_whitespace();
+    let program = parts
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Empty shutdown command"))?;
+    let status = Command::new(program).args(parts).status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "Shutdown command failed with {}",
+            status
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+
+    #[test]
+    fn shut_down_after_grace_period() {
+        let start = Instant::now();
+        let mut watch = LowBatteryWatch::new(10.0);
+        assert!(!watch.update(50.0, false, start));
+        assert!(!watch.update(5.0, false, start));
+        assert!(!watch.update(5.0, false, start + Duration::from_secs(29)));
+        assert!(watch.update(5.0, false, start + Duration::from_secs(30)));
+
+        // Plugging in the charger or a good reading starts over
+        assert!(!watch.update(5.0, true, start + Duration::from_secs(31)));
+        assert!(!watch.update(5.0, false, start + Duration::from_secs(32)));
+        assert!(!watch.update(12.0, false, start + Duration::from_secs(50)));
+        assert!(!watch.update(5.0, false, start + Duration::from_secs(70)));
+        assert!(watch.update(5.0, false, start + Duration::from_secs(100)));
+    }
+}
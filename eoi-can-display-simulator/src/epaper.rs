@@ -0,0 +1,124 @@
+//! Emulation of the e-paper panel of the firmware, so layouts decided on the desktop hold on the
+//! boat: the panel refreshes only when the firmware would, a refresh takes seconds during which
+//! the old image stays, and black pixels leave a faint ghost behind. Like the panel it is black
+//! and white only, the ghosts are the only gray.
+
+use std::time::Duration;
+
+use draw_display::{DisplayData, RefreshPolicy};
+use embedded_graphics::pixelcolor::{BinaryColor, Gray8};
+use embedded_graphics::prelude::*;
+use embedded_graphics_simulator::SimulatorDisplay;
+use tokio::time::Instant;
+
+/// A full refresh of the 7.5" panel takes this long
+const REFRESH_LATENCY: Duration = Duration::from_secs(2);
+/// Like `SCREEN_MIN_REFRESH_INTERVAL` of the firmware
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+/// Like `SCREEN_MAX_REFRESH_INTERVAL` of the firmware
+const MAX_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+/// White pixels that were black before the last refresh
+const GHOST: Gray8 = Gray8::new(0xD0);
+
+pub struct EPaper {
+    size: Size,
+    /// Drawn by the application
+    frame: Vec<BinaryColor>,
+    /// The frame being refreshed and when the refresh started
+    refreshing: Option<(Instant, Vec<BinaryColor>)>,
+    /// On the panel, without the ghosts
+    shown: Vec<BinaryColor>,
+    panel: SimulatorDisplay<Gray8>,
+    policy: RefreshPolicy,
+}
+
+impl EPaper {
+    pub fn new(size: Size) -> Self {
+        let pixels = (size.width * size.height) as usize;
+        let mut panel = SimulatorDisplay::new(size);
+        panel.clear(Gray8::WHITE).unwrap();
+        Self {
+            size,
+            frame: vec![BinaryColor::On; pixels],
+            refreshing: None,
+            shown: vec![BinaryColor::On; pixels],
+            panel,
+            policy: RefreshPolicy::new(
+                MIN_REFRESH_INTERVAL.try_into().unwrap(),
+                MAX_REFRESH_INTERVAL.try_into().unwrap(),
+            ),
+        }
+    }
+
+    /// Call after drawing `data`, which starts a refresh when the firmware would or when `force`d,
+    /// like by the button. Returns `true` when a refresh finished and the window has to be updated
+    pub fn update(&mut self, data: &DisplayData, force: bool) -> bool {
+        if let Some((started, _)) = &self.refreshing {
+            // The firmware blocks during a refresh, so nothing else happens
+            if started.elapsed() < REFRESH_LATENCY {
+                return false;
+            }
+            let (_, frame) = self.refreshing.take().unwrap();
+            self.show(frame);
+            return true;
+        }
+
+        if force || self.policy.needs_refresh(data) {
+            self.policy.refreshed(data);
+            self.refreshing = Some((Instant::now(), self.frame.clone()));
+        }
+        false
+    }
+
+    pub fn panel(&self) -> &SimulatorDisplay<Gray8> {
+        &self.panel
+    }
+
+    /// For drawing over the panel until the next refresh
+    pub fn panel_mut(&mut self) -> &mut SimulatorDisplay<Gray8> {
+        &mut self.panel
+    }
+
+    fn show(&mut self, frame: Vec<BinaryColor>) {
+        let width = self.size.width as usize;
+        let pixels = frame
+            .iter()
+            .zip(&self.shown)
+            .enumerate()
+            .map(|(index, (&color, &before))| {
+                let point = Point::new((index % width) as i32, (index / width) as i32);
+                let gray = match (color, before) {
+                    (BinaryColor::Off, _) => Gray8::BLACK,
+                    (BinaryColor::On, BinaryColor::Off) => GHOST,
+                    (BinaryColor::On, BinaryColor::On) => Gray8::WHITE,
+                };
+                Pixel(point, gray)
+            });
+        self.panel.draw_iter(pixels).unwrap();
+        self.shown = frame;
+    }
+}
+
+impl OriginDimensions for EPaper {
+    fn size(&self) -> Size {
+        self.size
+    }
+}
+
+impl DrawTarget for EPaper {
+    type Color = BinaryColor;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let (width, height) = (self.size.width as i32, self.size.height as i32);
+        for Pixel(point, color) in pixels {
+            if (0..width).contains(&point.x) && (0..height).contains(&point.y) {
+                self.frame[(point.y * width + point.x) as usize] = color;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+//! Conditions injected with the keyboard, to review the states of the display without crafting
+//! CAN traffic. Injected values override what is received from the bus until they are reset.
+
+use draw_display::DisplayData;
+use embedded_graphics_simulator::sdl2::Keycode;
+use eoi_can_decoder::ThrottleErrors;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+/// How much the arrow keys change the state of charge, in percent
+const STATE_OF_CHARGE_STEP: f32 = 5.0;
+
+#[derive(Debug, Default)]
+pub struct Injection {
+    throttle_error: Option<bool>,
+    state_of_charge: Option<f32>,
+    charging_disabled: Option<bool>,
+}
+
+impl Injection {
+    /// Keys:
+    /// - `t` toggles a throttle error (deadman missing)
+    /// - up and down arrows raise and lower the battery state of charge
+    /// - `c` toggles charging disabled
+    /// - `r` resets everything to the values from the bus
+    ///
+    /// Returns whether the key injected something, so the display can be redrawn right away
+    pub fn key_down(&mut self, keycode: Keycode, repeat: bool, data: &DisplayData) -> bool {
+        // Holding a toggle key shouldn't flip it back and forth, the arrows may repeat
+        if repeat && !matches!(keycode, Keycode::Up | Keycode::Down) {
+            return false;
+        }
+        match keycode {
+            Keycode::T => {
+                let throttle_error = !self.throttle_error.unwrap_or(false);
+                self.throttle_error = Some(throttle_error);
+                info!("Injecting throttle error: {}", throttle_error);
+            }
+            Keycode::Up | Keycode::Down => {
+                let step = if keycode == Keycode::Up {
+                    STATE_OF_CHARGE_STEP
+                } else {
+                    -STATE_OF_CHARGE_STEP
+                };
+                // Start from what is shown, so the first step doesn't jump
+                let state_of_charge = self
+                    .state_of_charge
+                    .or_else(|| data.battery_state_of_charge.get().copied())
+                    .unwrap_or(50.0);
+                let state_of_charge = (state_of_charge + step).clamp(0.0, 100.0);
+                self.state_of_charge = Some(state_of_charge);
+                info!("Injecting state of charge: {} %", state_of_charge);
+            }
+            Keycode::C => {
+                let charging_disabled = !self
+                    .charging_disabled
+                    .or_else(|| data.charging_disabled.get().copied())
+                    .unwrap_or(false);
+                self.charging_disabled = Some(charging_disabled);
+                info!("Injecting charging disabled: {}", charging_disabled);
+            }
+            Keycode::R => {
+                *self = Self::default();
+                info!("Reset injected values, showing the bus again");
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Override the data received from the bus, call it after ingesting the CAN frames
+    pub fn apply(&self, data: &mut DisplayData) {
+        if let Some(throttle_error) = self.throttle_error {
+            data.throttle_errors.update(ThrottleErrors {
+                deadman_missing: throttle_error,
+                ..Default::default()
+            });
+        }
+        if let Some(state_of_charge) = self.state_of_charge {
+            data.battery_state_of_charge.update(state_of_charge);
+        }
+        if let Some(charging_disabled) = self.charging_disabled {
+            data.charging_disabled.update(charging_disabled);
+        }
+    }
+}
@@ -0,0 +1,51 @@
+//! Layout of the main page read from a TOML file, and read again whenever the file changes, for
+//! nudging the sections around without building `draw-display` again. Keys left out keep their
+//! default, see `layout.toml` for all of them.
+
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use draw_display::Layout;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+#[derive(Debug)]
+pub struct LayoutFile {
+    path: PathBuf,
+    /// Modification time of the last read, also of a file that failed to parse
+    modified: Option<SystemTime>,
+}
+
+impl LayoutFile {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            modified: None,
+        }
+    }
+
+    /// The layout of the file when it changed since the last call. Errors are logged and the
+    /// display keeps its layout until the file is fixed
+    pub fn reload(&mut self) -> Option<Layout> {
+        let modified = match std::fs::metadata(&self.path).and_then(|meta| meta.modified()) {
+            Ok(modified) => modified,
+            Err(error) => {
+                if self.modified.take().is_some() {
+                    warn!("Unable to read the layout {:?}: {}", self.path, error);
+                }
+                return None;
+            }
+        };
+        if self.modified == Some(modified) {
+            return None;
+        }
+        self.modified = Some(modified);
+
+        let text = std::fs::read_to_string(&self.path)
+            .inspect_err(|error| warn!("Unable to read the layout {:?}: {}", self.path, error))
+            .ok()?;
+        toml::from_str(&text)
+            .inspect_err(|error| warn!("Invalid layout {:?}: {}", self.path, error))
+            .ok()
+    }
+}
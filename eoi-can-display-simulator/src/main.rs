@@ -1,58 +1,273 @@
+mod epaper;
+mod injection;
+mod layout_file;
+mod replay;
+
+use std::path::PathBuf;
 use std::time::Duration;
 
-use clap::Parser;
-use embedded_can::Frame;
-use embedded_graphics::{pixelcolor::BinaryColor, prelude::*};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
+use draw_display::{
+    AggregatePolicy, CellImbalance, FinishLine, LapCounter, LedStatus, Leds, Page, PageSelection,
+    PanelId, ParkedMode, Profile, ReserveMode, SolarPanels, DEFAULT_IMBALANCE_THRESHOLD,
+    DEFAULT_RESERVE_STATE_OF_CHARGE,
+};
+use embedded_graphics::{
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::{Circle, PrimitiveStyle, Rectangle},
+};
 use embedded_graphics_simulator::{
-    OutputSettingsBuilder, SimulatorDisplay, SimulatorEvent, Window,
+    sdl2::Keycode, OutputSettingsBuilder, SimulatorDisplay, SimulatorEvent, Window,
 };
-use eoi_can_decoder::{can_collector, parse_eoi_can_data};
-use get_wifi_ip::get_wifi_ip;
+use eoi_can_decoder::calibration::Calibration;
+use eoi_can_decoder::{
+    can_collector, parse_eoi_can_data_with_config, DecoderConfig, VescData, MAX_MPPT_DEVICES,
+    MAX_VESC_CONTROLLERS,
+};
+use eoi_can_source::LogFilter;
+use epaper::EPaper;
+use get_wifi_ip::watch_ip;
+use injection::Injection;
+use layout_file::LayoutFile;
+use replay::LogReplay;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use tokio::time::Instant;
 #[allow(unused_imports)]
 use tracing::{debug, error, info, trace, warn, Level};
-use tracing_subscriber::filter::{EnvFilter, LevelFilter};
-use tracing_subscriber::prelude::*;
+use tracing_subscriber::filter::LevelFilter;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    /// CAN interface
-    #[arg(short, long, default_value_t = String::from("vcan0"))]
-    can_interface: String,
+    /// CAN interface, give it multiple times to read more than one bus
+    #[arg(short, long = "can-interface", default_values_t = [String::from("vcan0")])]
+    can_interfaces: Vec<String>,
+
+    /// Name of the WiFi interface shown with its IP address, `*` matches any characters
+    #[arg(long, default_value_t = String::from(get_wifi_ip::DEFAULT_INTERFACE_PATTERN))]
+    wifi_interface: String,
+
+    /// VESC controller ID, give it multiple times for boats with more than one motor
+    #[arg(
+        long = "vesc-id",
+        default_values_t = [DecoderConfig::DEFAULT_VESC_CONTROLLER_ID],
+        value_parser = clap::value_parser!(u8).range(..i64::from(VescData::BROADCAST_CONTROLLER_ID))
+    )]
+    vesc_ids: Vec<u8>,
+
+    /// Also decode frames longer than their message, like from nodes with a newer protocol
+    #[arg(long)]
+    permissive_dlc: bool,
+
+    /// MPPTs on the bus, with the IDs from 0 up. Other nodes may use the IDs of the others
+    #[arg(long, default_value_t = MAX_MPPT_DEVICES as u8)]
+    mppt_devices: u8,
+
+    /// Solar panels in the order they are numbered, by the MPPT ID and the channel they are wired
+    /// to, like 2.1,2.2,5.0. Without it the panels of the boat
+    #[arg(long, value_delimiter = ',')]
+    mppt_panels: Vec<PanelId>,
+
+    /// Every solar panel seen on the bus ordered by MPPT ID and channel, instead of `--mppt-panels`
+    #[arg(long, conflicts_with = "mppt_panels")]
+    observed_panels: bool,
+
+    /// Correct signals with a known error of their sensor before decoding them, by the scale and
+    /// offset per signal of a TOML file like `eoi-can-decoder/calibration.toml`
+    #[arg(long)]
+    calibration: Option<PathBuf>,
+
+    /// Start/finish line for counting laps, as latitude,longitude of both ends,
+    /// like 43.7340,7.4210,43.7345,7.4220
+    #[arg(long)]
+    finish_line: Option<FinishLine>,
+
+    /// Profile deciding the pages and how they rotate: race, test-bench, charging, diagnostics
+    /// or exhibition
+    #[arg(long, default_value = "race")]
+    profile: Profile,
+
+    /// State of charge in percent below which the main page switches to the economy layout of
+    /// the reserve mode
+    #[arg(long, default_value_t = DEFAULT_RESERVE_STATE_OF_CHARGE)]
+    reserve_soc: f32,
+
+    /// Minutes standing still and not charging after which the main page shows the QR code of the
+    /// dashboard and the summary of the session
+    #[arg(long, default_value_t = 5)]
+    parked_after: u16,
+
+    /// Address of the dashboard in the QR code of the parked screen, `{ip}` is replaced by the IP
+    /// address of the WiFi, like http://{ip}:3000/d/boat for a Grafana dashboard. Without it
+    /// there is no QR code
+    #[arg(long)]
+    dashboard_url: Option<String>,
+
+    /// Difference between the highest and lowest cell voltage in V which, sustained for 10
+    /// seconds, raises the cell imbalance alarm
+    #[arg(long, default_value_t = DEFAULT_IMBALANCE_THRESHOLD)]
+    imbalance_threshold: f32,
+
+    /// Correct the time to empty, the range and the energy budget of the race for a cold pack,
+    /// with the usable state of charge by temperature of a TOML file like
+    /// `draw-display/soc-correction.toml`
+    #[arg(long)]
+    soc_correction: Option<PathBuf>,
+
+    /// Show the temperature of every group of cells under the cell bars, with the cells of every
+    /// temperature sensor of a TOML file like `draw-display/cell-temperatures.toml`
+    #[arg(long)]
+    cell_temperatures: Option<PathBuf>,
+
+    /// How the net power handles stale or missing currents: strict shows nothing, partial
+    /// leaves them out and last-known takes their last value, both marked with a "≈"
+    #[arg(long, default_value = "strict")]
+    aggregate_policy: AggregatePolicy,
+
+    /// Start in e-paper mode, which refreshes like the e-paper display of the firmware, slow and
+    /// with ghosting. `e` toggles it
+    #[arg(long)]
+    epaper: bool,
+
+    /// Play a candump log instead of reading the CAN interfaces. Values go stale by the
+    /// timestamps of the log, however fast it's played
+    #[arg(long)]
+    log: Option<PathBuf>,
+
+    /// How many times faster than recorded the log is played
+    #[arg(long, default_value_t = 1.0, value_parser = parse_speed)]
+    speed: f64,
+
+    /// Read the layout of the main page from a TOML file, and again whenever it changes, like
+    /// `eoi-can-display-simulator/layout.toml`
+    #[arg(long)]
+    layout: Option<PathBuf>,
+
+    /// Create the CAN interfaces as virtual ones when they don't exist, and delete them again on
+    /// exit. Needs `CAP_NET_ADMIN`
+    #[arg(long)]
+    auto_vcan: bool,
+}
+
+fn parse_speed(speed: &str) -> Result<f64, String> {
+    match speed.parse::<f64>() {
+        Ok(speed) if speed > 0.0 && speed.is_finite() => Ok(speed),
+        _ => Err(format!("{} is not a speed above 0", speed)),
+    }
 }
 
-fn register_tracing_subscriber(level_filter: LevelFilter) {
-    tracing_subscriber::registry()
-        .with(
-            EnvFilter::builder()
-                .with_default_directive(level_filter.into())
-                .from_env_lossy(),
-        )
-        .with(
-            tracing_subscriber::fmt::layer()
-                .with_file(true)
-                .with_line_number(true),
-        )
-        .init();
-
-    info!(
-        "Environment override for debug level to: {:?}",
-        level_filter
+/// Like the firmware, the bus is silent without a frame for this long
+const CAN_FLOWING_TIMEOUT: Duration = Duration::from_secs(2);
+const LED_DIAMETER: u32 = 7;
+
+/// Virtual status LEDs in the bottom right corner, green left of red. A lit LED is filled
+fn draw_leds<D>(target: &mut D, leds: Leds) -> Result<(), D::Error>
+where
+    D: DrawTarget,
+    D::Color: From<BinaryColor>,
+{
+    let paper = PrimitiveStyle::with_fill(BinaryColor::On.into());
+    let lit = PrimitiveStyle::with_fill(BinaryColor::Off.into());
+    let off = PrimitiveStyle::with_stroke(BinaryColor::Off.into(), 1);
+
+    let size = target.bounding_box().size;
+    let step = LED_DIAMETER as i32 + 2;
+    let corner = Point::new(
+        size.width as i32 - 2 * step - 1,
+        size.height as i32 - step - 1,
     );
+    Rectangle::new(corner, Size::new(2 * step as u32 + 1, step as u32 + 1))
+        .into_styled(paper)
+        .draw(target)?;
+    for (index, on) in [leds.green, leds.red].into_iter().enumerate() {
+        let top_left = corner + Point::new(2 + index as i32 * step, 2);
+        Circle::new(top_left, LED_DIAMETER)
+            .into_styled(if on { lit } else { off })
+            .draw(target)?;
+    }
+    Ok(())
 }
 
 #[tokio::main]
 async fn main() -> Result<(), core::convert::Infallible> {
-    register_tracing_subscriber(LevelFilter::DEBUG);
+    LogFilter::init(LevelFilter::DEBUG);
+    eoi_can_source::install_panic_hook("eoi-can-display-simulator");
     let args = Args::parse();
-    info!("CAN interface: {}", args.can_interface);
+    info!("CAN interfaces: {:?}", args.can_interfaces);
+    info!("VESC controller IDs: {:?}", args.vesc_ids);
+    let calibration = args
+        .calibration
+        .as_ref()
+        .map_or_else(Calibration::default, |path| {
+            let text = std::fs::read_to_string(path).expect("Unable to read the calibration");
+            toml::from_str(&text).expect("Invalid calibration")
+        });
+    for signal in &calibration.signals {
+        info!(
+            "Calibrated {} of {}: * {} + {}",
+            signal.signal.name, signal.message.name, signal.scale, signal.offset
+        );
+    }
+    let Some(decoder_config) = DecoderConfig::with_vesc_controller_ids(&args.vesc_ids) else {
+        Args::command()
+            .error(
+                ErrorKind::TooManyValues,
+                format!(
+                    "--vesc-id can be given at most {} times",
+                    MAX_VESC_CONTROLLERS
+                ),
+            )
+            .exit();
+    };
+    let decoder_config = decoder_config
+        .with_permissive_dlc(args.permissive_dlc)
+        .with_mppt_devices(args.mppt_devices)
+        .with_calibration(calibration);
+
+    // Before any value of the display data, so they all take the time of the log
+    let mut replay = args.log.as_ref().map(|path| {
+        let replay = LogReplay::open(path, args.speed).expect("Unable to read the CAN log");
+        info!("Playing {} frames of {:?}", replay.len(), path);
+        replay
+    });
+    let interfaces = match replay {
+        Some(_) => &[][..],
+        None => &args.can_interfaces[..],
+    };
+    // Deleted again when leaving the main loop, also after Ctrl-C
+    let virtual_interfaces: Vec<_> = interfaces
+        .iter()
+        .filter(|_| args.auto_vcan)
+        .filter_map(|interface| {
+            eoi_can_source::VirtualCan::ensure(interface)
+                .inspect_err(|error| {
+                    warn!(
+                        "Unable to create virtual CAN interface {}: {}",
+                        interface, error
+                    )
+                })
+                .ok()
+        })
+        .collect();
+    let interrupted = Arc::new(AtomicBool::new(false));
+    if virtual_interfaces.iter().any(|vcan| vcan.is_created()) {
+        let interrupted = interrupted.clone();
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                interrupted.store(true, Ordering::Relaxed);
+            }
+        });
+    }
 
-    let can_sock: socketcan::tokio::AsyncCanSocket<socketcan::CanSocket> =
-        socketcan::tokio::AsyncCanSocket::open(args.can_interface.as_str())
-            .expect("Unable to open CAN socket");
-    info!("Connected to CAN interface: {}", args.can_interface);
+    let (_, mut can_frames) = eoi_can_source::spawn_can_readers(
+        interfaces
+            .iter()
+            .map(|interface| eoi_can_source::CanReader::new(interface)),
+    );
+    let mut can_states = can_frames.states();
 
     let shared_can_collector = Arc::new(Mutex::new(can_collector::CanCollector::new()));
 
@@ -60,24 +275,9 @@ async fn main() -> Result<(), core::convert::Infallible> {
 
     // Spawn a task to read CAN frames
     tokio::spawn(async move {
-        loop {
-            let frame = can_sock.read_frame().await.unwrap();
-
-            let embedded_frame = if let socketcan::CanFrame::Data(frame) = frame {
-                trace!(
-                    "Received CAN frame: ID: {:?}, Data: {:?}",
-                    frame.id(),
-                    frame.data()
-                );
-
-                eoi_can_decoder::can_frame::CanFrame::from_encoded(frame.id(), frame.data())
-            } else {
-                debug!("Received non-data CAN frame: {:?}", frame);
-                continue;
-            };
-
+        while let Some(frame) = can_frames.recv().await {
             if let Ok(mut collector) = can_collector_receiver.lock() {
-                collector.insert(embedded_frame);
+                collector.insert(frame);
             }
         }
     });
@@ -91,23 +291,100 @@ async fn main() -> Result<(), core::convert::Infallible> {
     );
 
     let mut display_data = draw_display::DisplayData::default();
+    if let Some(finish_line) = args.finish_line {
+        info!("Counting laps over the finish line {:?}", finish_line);
+        display_data.lap_counter = LapCounter::new(finish_line);
+    }
+    display_data.reserve = ReserveMode::new(args.reserve_soc);
+    let parked = ParkedMode::new(
+        std::time::Duration::from_secs(u64::from(args.parked_after) * 60)
+            .try_into()
+            .unwrap(),
+    );
+    display_data.parked = match &args.dashboard_url {
+        Some(url) => parked.with_dashboard_url(url),
+        None => parked,
+    };
+    display_data.cell_imbalance = CellImbalance::new(args.imbalance_threshold);
+    if let Some(path) = &args.soc_correction {
+        let text = std::fs::read_to_string(path).expect("Unable to read the SoC correction");
+        display_data.soc_correction = toml::from_str(&text).expect("Invalid SoC correction");
+        info!("SoC correction: {:?}", display_data.soc_correction);
+    }
+    if let Some(path) = &args.cell_temperatures {
+        let text = std::fs::read_to_string(path).expect("Unable to read the cell temperatures");
+        display_data.cell_temperature_map =
+            toml::from_str(&text).expect("Invalid cell temperatures");
+        info!("Cell temperatures: {:?}", display_data.cell_temperature_map);
+    }
+    display_data.aggregate_policy = args.aggregate_policy;
+    display_data.mppt_panel_info = if args.observed_panels {
+        SolarPanels::observed()
+    } else if args.mppt_panels.is_empty() {
+        SolarPanels::default()
+    } else {
+        SolarPanels::configured(&args.mppt_panels).expect("Too many solar panels given")
+    };
 
-    draw_display::draw_display(&mut display, &display_data).unwrap();
+    let mut layout_file = args.layout.map(LayoutFile::new);
+
+    // Keys inject conditions on top of the bus data, see `Injection::key_down`
+    let mut injection = Injection::default();
+
+    // Clicking the window switches to the next page
+    let mut pages = PageSelection::new(args.profile);
+    draw_display::draw_page(&mut display, &display_data, pages.page()).unwrap();
+
+    let mut epaper = args.epaper.then(|| EPaper::new(display.size()));
+    // Like the button of the e-paper display, which refreshes it right away
+    let mut force_refresh = false;
 
     tokio::time::sleep(Duration::from_millis(1000)).await; // load CAN data
     let mut last_time_updated_display = Instant::now() - Duration::from_secs(100);
 
+    // Without netlink the IP address just isn't shown
+    let wifi = watch_ip(&args.wifi_interface)
+        .inspect_err(|error| warn!("Unable to watch the WiFi interface: {:?}", error))
+        .ok();
+
+    // For the patterns of the virtual status LEDs
+    let started = Instant::now();
+    let mut last_frame = None;
+
     'running: loop {
+        if interrupted.load(Ordering::Relaxed) {
+            warn!("Interrupted, exiting...");
+            break 'running;
+        }
+
         // Check if we have new CAN frames to process
         if last_time_updated_display.elapsed() > Duration::from_millis(100) {
             last_time_updated_display = Instant::now();
+            if let Some(replay) = replay.as_mut().filter(|replay| !replay.is_finished()) {
+                let played = replay.play(|frame| {
+                    if let Some(parsed_data) =
+                        parse_eoi_can_data_with_config(frame, &decoder_config)
+                    {
+                        display_data.ingest_eoi_can_data(parsed_data);
+                    }
+                });
+                debug!("Played frames: {}", played);
+                if played > 0 {
+                    last_frame = Some(Instant::now());
+                }
+                if replay.is_finished() {
+                    info!("End of the CAN log");
+                }
+            }
             if let Ok(mut can_collector) = shared_can_collector.lock() {
                 if can_collector.get_dropped_frames() > 0 {
                     debug!("Dropped frames: {}", can_collector.get_dropped_frames());
                 }
                 let mut parsed_frames = 0_u32;
                 can_collector.iter().for_each(|frame| {
-                    if let Some(parsed_data) = parse_eoi_can_data(frame) {
+                    if let Some(parsed_data) =
+                        parse_eoi_can_data_with_config(frame, &decoder_config)
+                    {
                         display_data.ingest_eoi_can_data(parsed_data);
                         parsed_frames = parsed_frames.saturating_add(1);
                     } else {
@@ -115,21 +392,140 @@ async fn main() -> Result<(), core::convert::Infallible> {
                     }
                 });
                 debug!("Parsed frames: {}", parsed_frames);
+                if parsed_frames > 0 {
+                    last_frame = Some(Instant::now());
+                }
+                eoi_can_source::log_new_id_collisions(&mut can_collector);
                 can_collector.clear();
             }
 
-            if let Some(ip) = get_wifi_ip() {
-                display_data.ip_address.update(ip);
+            if let Some(requested_page) = display_data.requested_page.take() {
+                debug!("Showing page {:?} for the chase car", requested_page);
+                pages.show(requested_page);
+            }
+            if let Some(requested_profile) = display_data.requested_profile.take() {
+                debug!(
+                    "Switching to profile {:?} for the chase car",
+                    requested_profile
+                );
+                pages.set_profile(requested_profile);
+            }
+            if let Some(active) = display_data.reserve.take_change() {
+                info!("Reserve mode: {}", active);
+                force_refresh = true;
+            }
+            if let Some(active) = display_data.parked.take_change() {
+                info!("Parked: {}", active);
+                force_refresh = true;
+            }
+            if let Some(active) = display_data.cell_imbalance.take_change() {
+                warn!("Cell imbalance: {}", active);
+            }
+            if pages.rotate() {
+                force_refresh = true;
+            }
+            if let Some(layout) = layout_file.as_mut().and_then(LayoutFile::reload) {
+                info!("Layout changed: {:?}", layout);
+                display_data.layout = layout;
+                force_refresh = true;
+            }
+
+            if let Some(wifi) = wifi.as_ref().map(|wifi| wifi.borrow()) {
+                if let Some(ip) = wifi.as_ref().and_then(|wifi| wifi.ipv4()) {
+                    display_data.ip_address.update(ip);
+                }
+                if let Some(signal) = wifi.as_ref().and_then(|wifi| wifi.signal) {
+                    display_data.wifi_level_dbm.update(signal.level_dbm);
+                }
+            }
+
+            display_data.can_interface_up.update(!can_states.any_down());
+            if let Some(fault) = can_states.new_fault() {
+                display_data.can_bus_fault.update(fault.label());
             }
+            injection.apply(&mut display_data);
+            display_data.statistics_overlay = pages.shows_statistics();
 
-            draw_display::draw_display(&mut display, &display_data).unwrap();
-            window.update(&display);
+            let mut stats = match epaper.as_mut() {
+                Some(epaper) => {
+                    draw_display::draw_page_measured(epaper, &display_data, pages.page())
+                }
+                None => draw_display::draw_page_measured(&mut display, &display_data, pages.page()),
+            }
+            .unwrap();
+            let flush_start = Instant::now();
+            let can_flowing = last_frame.is_some_and(|at| at.elapsed() < CAN_FLOWING_TIMEOUT);
+            let leds = LedStatus::from_data(&display_data, can_flowing)
+                .leds_at(started.elapsed().try_into().unwrap());
+            match epaper.as_mut() {
+                Some(epaper) => {
+                    epaper.update(&display_data, force_refresh);
+                    // The LEDs are next to the panel, they don't wait for a refresh
+                    draw_leds(epaper.panel_mut(), leds).unwrap();
+                    window.update(epaper.panel());
+                }
+                None => {
+                    draw_leds(&mut display, leds).unwrap();
+                    window.update(&display);
+                }
+            }
+            force_refresh = false;
+            stats.flush = flush_start.elapsed().try_into().unwrap();
+            if display_data.render.frame(stats) {
+                debug!("Render: {}", display_data.render);
+            }
         }
 
         for event in window.events() {
             if let SimulatorEvent::Quit = event {
                 warn!("Received quit event, exiting...");
                 break 'running;
+            } else if let SimulatorEvent::MouseButtonDown { .. } = event {
+                // Like the button of the e-paper display, but nothing is sent on the bus
+                if let Some(message_id) = display_data.banner.acknowledge() {
+                    info!("Acknowledged message {} of the chase car", message_id);
+                } else {
+                    pages.next();
+                    debug!("Showing page {:?}", pages.page());
+                }
+                // Redraw right away instead of waiting for new CAN data
+                force_refresh = true;
+                last_time_updated_display = Instant::now() - Duration::from_secs(100);
+            } else if let SimulatorEvent::KeyDown {
+                keycode, repeat, ..
+            } = event
+            {
+                // The render page isn't one of the pages of the race profile
+                if keycode == Keycode::F && !repeat {
+                    pages.show(Page::Render);
+                    debug!("Showing page {:?}", pages.page());
+                    force_refresh = true;
+                    last_time_updated_display = Instant::now() - Duration::from_secs(100);
+                } else if keycode == Keycode::P && !repeat {
+                    // Like holding the button of the e-paper display
+                    pages.set_profile(pages.profile().next());
+                    info!("Switching to profile {:?}", pages.profile());
+                    force_refresh = true;
+                    last_time_updated_display = Instant::now() - Duration::from_secs(100);
+                } else if keycode == Keycode::O && !repeat {
+                    pages.toggle_statistics();
+                    info!(
+                        "Statistics overlay on page {:?}: {}",
+                        pages.page(),
+                        pages.shows_statistics()
+                    );
+                    force_refresh = true;
+                    last_time_updated_display = Instant::now() - Duration::from_secs(100);
+                } else if keycode == Keycode::E && !repeat {
+                    epaper = match epaper {
+                        Some(_) => None,
+                        None => Some(EPaper::new(display.size())),
+                    };
+                    info!("E-paper mode: {}", epaper.is_some());
+                    last_time_updated_display = Instant::now() - Duration::from_secs(100);
+                } else if injection.key_down(keycode, repeat, &display_data) {
+                    last_time_updated_display = Instant::now() - Duration::from_secs(100);
+                }
             } else {
                 trace!("Event: {:?}", event);
             }
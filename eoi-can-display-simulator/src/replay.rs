@@ -0,0 +1,78 @@
+//! Candump log played into the simulator instead of reading a CAN interface. The data model
+//! takes its time from a virtual clock following the timestamps of the log, so the values go
+//! stale after the same frames whether the log is played at real speed or ten times as fast.
+
+use std::path::Path;
+use std::time::Duration;
+
+use can_logger::parse_candump_line;
+use draw_display::{Instant, VirtualClock};
+use eoi_can_decoder::can_frame::CanFrame;
+
+pub struct LogReplay {
+    /// Frames with their time since the first frame of the log
+    frames: Vec<(Duration, CanFrame)>,
+    next: usize,
+    speed: f64,
+    started: std::time::Instant,
+    /// Stands at the first frame when the replay starts
+    clock: VirtualClock,
+    seed: Instant,
+}
+
+impl LogReplay {
+    pub fn open(path: &Path, speed: f64) -> std::io::Result<Self> {
+        let log = std::fs::read_to_string(path)?;
+        let mut frames: Vec<_> = log
+            .lines()
+            .filter_map(parse_candump_line)
+            .map(|(timestamp, _, frame)| (timestamp, frame))
+            .collect();
+        let first = frames.first().map(|(timestamp, _)| *timestamp);
+        for (timestamp, _) in &mut frames {
+            *timestamp = timestamp.saturating_sub(first.unwrap_or_default());
+        }
+
+        let seed = Instant::now();
+        let clock = VirtualClock::starting_at(seed);
+        clock.install();
+        Ok(Self {
+            frames,
+            next: 0,
+            speed,
+            started: std::time::Instant::now(),
+            clock,
+            seed,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.frames.len()
+    }
+
+    /// Hand over the frames which are due by the time played so far. The clock is moved to the
+    /// timestamp of every frame before it's handed over, and to the time played at the end
+    pub fn play(&mut self, mut ingest: impl FnMut(&CanFrame)) -> usize {
+        let played = self.started.elapsed().mul_f64(self.speed);
+        let first = self.next;
+        while let Some((timestamp, frame)) = self.frames.get(self.next) {
+            if *timestamp > played {
+                break;
+            }
+            self.clock.set(self.at(*timestamp));
+            ingest(frame);
+            self.next += 1;
+        }
+        self.clock.set(self.at(played));
+        self.next - first
+    }
+
+    /// Time of the virtual clock `played` into the log
+    fn at(&self, played: Duration) -> Instant {
+        self.seed + played.try_into().unwrap_or_default()
+    }
+}
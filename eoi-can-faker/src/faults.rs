@@ -0,0 +1,212 @@
+//! Malformed frames in place of some of the faked ones, to exercise the error paths of the
+//! decoder and how the displays handle NaN and values out of range on a live bus.
+
+use clap::ValueEnum;
+use embedded_can::{ExtendedId, Id};
+use eoi_can_decoder::can_frame::CanFrame;
+use eoi_can_decoder::signals::{MessageDefinition, SignalDefinition, ValueType, find_message};
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+/// What is wrong with a malformed frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Fault {
+    /// One byte less or more than the message has
+    WrongDlc,
+    /// A signal with named values set to a value without a name
+    EnumOutOfRange,
+    /// A float signal set to NaN, infinity or its largest value, other signals to their limits
+    ExtremeValue,
+    /// The frame sent with an extended ID of the same number
+    ExtendedIdCollision,
+}
+
+pub struct FaultInjector {
+    faults: Vec<Fault>,
+    /// Share of the frames replaced by a malformed one
+    rate: f32,
+    rng: Rng,
+}
+
+impl FaultInjector {
+    pub fn new(faults: Vec<Fault>, rate: f32, seed: u64) -> Self {
+        Self {
+            faults,
+            rate,
+            rng: Rng::new(seed),
+        }
+    }
+
+    /// Replace some of the `frames` by a malformed version, with one of the faults picked at
+    /// random. Frames the picked fault doesn't apply to, like a message without named values,
+    /// stay as they are
+    pub fn apply(&mut self, frames: &mut [CanFrame]) {
+        if self.faults.is_empty() {
+            return;
+        }
+        for frame in frames {
+            if !self.rng.chance(self.rate) {
+                continue;
+            }
+            let fault = self.faults[self.rng.below(self.faults.len())];
+            if let Some(malformed) = self.malform(fault, frame) {
+                debug!("{:?}: {:?} instead of {:?}", fault, malformed, frame);
+                *frame = malformed;
+            }
+        }
+    }
+
+    fn malform(&mut self, fault: Fault, frame: &CanFrame) -> Option<CanFrame> {
+        let (message, _) = match frame.id {
+            Id::Standard(id) => find_message(id.as_raw().into(), false),
+            Id::Extended(id) => find_message(id.as_raw(), true),
+        }?;
+        let mut malformed = frame.clone();
+        match fault {
+            Fault::WrongDlc => {
+                // Classic CAN frames can't be longer than 8 bytes
+                let shorter = malformed.data.len() >= 8
+                    || (!malformed.data.is_empty() && self.rng.chance(0.5));
+                if shorter {
+                    malformed.data.pop();
+                } else {
+                    malformed.data.push(0xFF).ok()?;
+                }
+            }
+            Fault::EnumOutOfRange => {
+                let signal = self.pick_signal(message, |signal| !signal.values.is_empty())?;
+                let mask = u32::MAX >> (32 - u32::from(signal.length.min(32)));
+                let raw = (0..=mask)
+                    .rev()
+                    .find(|raw| signal.values.iter().all(|(value, _)| value != raw))?;
+                let value = f64::from(raw) * f64::from(signal.factor) + f64::from(signal.offset);
+                signal.encode(&mut malformed.data, value)?;
+            }
+            Fault::ExtremeValue => {
+                let signal = self.pick_signal(message, |_| true)?;
+                let values: &[f64] = match signal.value_type {
+                    ValueType::Float | ValueType::Double => {
+                        &[f64::NAN, f64::INFINITY, f64::NEG_INFINITY, f32::MAX as f64]
+                    }
+                    // Limited to the largest and smallest raw value
+                    ValueType::Unsigned => &[f64::MAX],
+                    ValueType::Signed => &[f64::MAX, f64::MIN],
+                };
+                let value = values[self.rng.below(values.len())];
+                signal.encode(&mut malformed.data, value)?;
+            }
+            Fault::ExtendedIdCollision => {
+                let Id::Standard(id) = frame.id else {
+                    return None;
+                };
+                malformed.id = Id::Extended(ExtendedId::new(id.as_raw().into())?);
+            }
+        }
+        Some(malformed)
+    }
+
+    fn pick_signal(
+        &mut self,
+        message: &'static MessageDefinition,
+        filter: impl Fn(&SignalDefinition) -> bool,
+    ) -> Option<&'static SignalDefinition> {
+        let signals: Vec<_> = message
+            .signals
+            .iter()
+            .filter(|signal| filter(signal))
+            .collect();
+        (!signals.is_empty()).then(|| signals[self.rng.below(signals.len())])
+    }
+}
+
+/// Xorshift generator, the faults don't need better randomness but should repeat with a seed
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // Xorshift stays at zero forever
+        Self(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+
+    /// `true` with the `probability` between 0 and 1
+    fn chance(&mut self, probability: f32) -> bool {
+        let fraction = (self.next() >> 40) as f32 / (1u64 << 24) as f32;
+        fraction < probability
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use eoi_can_decoder::signals::find_message_by_name;
+    use eoi_can_decoder::{DecodeError, DecoderConfig, frame, try_parse_eoi_can_data_with_config};
+
+    fn malformed(fault: Fault, frame: &CanFrame) -> CanFrame {
+        let mut injector = FaultInjector::new(vec![fault], 1.0, 42);
+        let mut frames = [frame.clone()];
+        injector.apply(&mut frames);
+        frames[0].clone()
+    }
+
+    #[test]
+    fn malformed_frames() {
+        let config = DecoderConfig::default();
+        // State of charge, error flags and balancing of the BMS
+        let frame = frame!(0x101, [50, 0, 0, 0, 0, 0, 0, 0]);
+
+        let wrong_dlc = malformed(Fault::WrongDlc, &frame);
+        assert!(wrong_dlc.data.len() == 7);
+        assert!(matches!(
+            try_parse_eoi_can_data_with_config(&wrong_dlc, &config),
+            Err(DecodeError::TooShort { .. })
+        ));
+
+        let extreme = malformed(Fault::ExtremeValue, &frame);
+        assert!(extreme.data != frame.data);
+
+        let collision = malformed(Fault::ExtendedIdCollision, &frame);
+        assert!(collision.id == Id::Extended(ExtendedId::new(0x101).unwrap()));
+        assert!(collision.data == frame.data);
+    }
+
+    #[test]
+    fn enum_without_a_name() {
+        let message = find_message_by_name("DisplayProfile").unwrap();
+        let signal = &message.signals[0];
+        let frame = CanFrame::try_new(message.can_id(0).unwrap(), &[0]).unwrap();
+
+        let raw = signal
+            .decode(&malformed(Fault::EnumOutOfRange, &frame).data)
+            .unwrap();
+        assert!(
+            signal
+                .values
+                .iter()
+                .all(|(value, _)| f64::from(*value) != raw)
+        );
+    }
+
+    #[test]
+    fn faults_at_the_rate() {
+        let mut injector = FaultInjector::new(vec![Fault::ExtendedIdCollision], 0.25, 7);
+        let mut frames = vec![frame!(0x101, [50, 0, 0, 0, 0, 0, 0, 0]); 1000];
+        injector.apply(&mut frames);
+        let malformed = frames
+            .iter()
+            .filter(|frame| matches!(frame.id, Id::Extended(_)))
+            .count();
+        assert!((200..300).contains(&malformed), "{}", malformed);
+    }
+}
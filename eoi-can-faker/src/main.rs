@@ -0,0 +1,184 @@
+use clap::Parser;
+use embedded_can::Frame;
+use eoi_can_decoder::can_encoder::encode_eoi_can_data;
+use eoi_can_decoder::{DecoderConfig, DisplayControl, DisplayMessage, EoiCanData};
+use eoi_can_source::{CanTransmitter, LogFilter, PeriodicSender, VirtualCan};
+use faults::{Fault, FaultInjector};
+use scenario::Scenario;
+use script::Script;
+use socketcan::tokio::CanSocket;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::time::Instant;
+#[allow(unused_imports)]
+use tracing::{Level, debug, error, info, trace, warn};
+use tracing_subscriber::filter::LevelFilter;
+
+mod faults;
+mod scenario;
+mod script;
+
+/// Time between two rounds of frames
+const SEND_INTERVAL: Duration = Duration::from_millis(100);
+/// The display message is sent again this often, so the displays keep showing it
+const DISPLAY_MESSAGE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    /// CAN interface to send the frames on
+    #[arg(short, long, default_value_t = String::from("vcan0"))]
+    can_interface: String,
+
+    /// What the faked boat is doing
+    #[arg(short, long, value_enum, default_value_t = Scenario::Cruise)]
+    scenario: Scenario,
+
+    /// VESC controller ID of the faked motor
+    #[arg(long = "vesc-id", default_value_t = DecoderConfig::DEFAULT_VESC_CONTROLLER_ID)]
+    vesc_id: u8,
+
+    /// Switch the displays to this page once at the start, like the chase car does over MQTT.
+    /// 0 is the main page, 1 the diagnostics page
+    #[arg(long)]
+    display_page: Option<u8>,
+
+    /// Show this message in the banner of the displays, like "PIT NOW"
+    #[arg(long)]
+    display_message: Option<String>,
+
+    /// Rhai script changing the traffic of the scenario over time, like
+    /// `scenarios/overheat.rhai`
+    #[arg(long)]
+    script: Option<PathBuf>,
+
+    /// Speed of the virtual clock the scenario and the script run on, 10 plays ten minutes of
+    /// the race in one
+    #[arg(long, default_value_t = 1.0)]
+    time_scale: f32,
+
+    /// Replace some frames by malformed ones, to test the error handling of the decoder and the
+    /// displays: wrong-dlc, enum-out-of-range, extreme-value (NaN, infinity, limits) or
+    /// extended-id-collision. Give it multiple times or comma separated for several faults
+    #[arg(long = "fault", value_enum, value_delimiter = ',')]
+    faults: Vec<Fault>,
+
+    /// Share of the frames replaced by a malformed one, between 0 and 1
+    #[arg(long, default_value_t = 0.05)]
+    fault_rate: f32,
+
+    /// Seed of the random choice of the malformed frames, for repeating a run. By default the
+    /// time of the start
+    #[arg(long)]
+    fault_seed: Option<u64>,
+
+    /// Create the CAN interface as a virtual one when it doesn't exist, and delete it again on
+    /// exit. Needs `CAP_NET_ADMIN`
+    #[arg(long)]
+    auto_vcan: bool,
+}
+
+#[tokio::main]
+async fn main() {
+    LogFilter::init(LevelFilter::INFO);
+    eoi_can_source::install_panic_hook("eoi-can-faker");
+    let args = Args::parse();
+    info!(
+        "Sending {:?} traffic on {}",
+        args.scenario, args.can_interface
+    );
+
+    // Deleted again when main returns, also after Ctrl-C
+    let _vcan = match args.auto_vcan {
+        true => match VirtualCan::ensure(&args.can_interface) {
+            Ok(vcan) => Some(vcan),
+            Err(error) => {
+                error!(
+                    "Unable to create virtual CAN interface {}: {}",
+                    args.can_interface, error
+                );
+                return;
+            }
+        },
+        false => None,
+    };
+    let can_sock: socketcan::tokio::AsyncCanSocket<socketcan::CanSocket> =
+        CanSocket::open(args.can_interface.as_str()).expect("Unable to open CAN socket");
+
+    let (_, transmitter) = CanTransmitter::spawn(can_sock);
+    if let Some(page) = args.display_page {
+        transmitter.send_data(&EoiCanData::DisplayControl(DisplayControl::Page(page)));
+    }
+
+    assert!(
+        args.time_scale > 0.0 && args.time_scale.is_finite(),
+        "The time scale has to be more than 0"
+    );
+    assert!(
+        (0.0..=1.0).contains(&args.fault_rate),
+        "The fault rate has to be between 0 and 1"
+    );
+    let start = Instant::now();
+    let (scenario, vesc_id, time_scale) = (args.scenario, args.vesc_id, args.time_scale);
+    let time = move || start.elapsed().as_secs_f32() * time_scale;
+    let mut script = match &args.script {
+        Some(path) => match Script::load(path, vesc_id) {
+            Ok(script) => {
+                info!("Running the script {:?}", path);
+                Some(script)
+            }
+            Err(error) => {
+                error!("Unable to load the script {:?}: {}", path, error);
+                return;
+            }
+        },
+        None => None,
+    };
+    let fault_seed = args.fault_seed.unwrap_or_else(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64
+    });
+    if !args.faults.is_empty() {
+        info!(
+            "Replacing {:.0} % of the frames by malformed ones ({:?}), seed {}",
+            args.fault_rate * 100.0,
+            args.faults,
+            fault_seed
+        );
+    }
+    let mut faults = FaultInjector::new(args.faults, args.fault_rate, fault_seed);
+    let mut sender = PeriodicSender::new().with_frames(SEND_INTERVAL, move || {
+        let time = time();
+        let mut frames: Vec<_> = scenario
+            .data(time, vesc_id)
+            .iter()
+            .filter_map(encode_eoi_can_data)
+            .collect();
+        if let Some(script) = script.as_mut() {
+            frames = script.apply(time.into(), frames);
+        }
+        faults.apply(&mut frames);
+        frames
+            .into_iter()
+            .filter_map(|frame| socketcan::CanFrame::new(frame.id, &frame.data))
+            .collect::<Vec<_>>()
+    });
+    if let Some(text) = args.display_message {
+        sender = sender.with_data(DISPLAY_MESSAGE_INTERVAL, move || {
+            // The same ID every time, the displays just show it for longer
+            DisplayMessage::split(0, &text)
+                .into_iter()
+                .map(|part| EoiCanData::DisplayControl(DisplayControl::Message(part)))
+        });
+    }
+    tokio::select! {
+        result = sender.spawn(transmitter) => {
+            if let Err(error) = result {
+                error!("Sending the faked traffic stopped: {}", error);
+            }
+        }
+        _ = tokio::signal::ctrl_c() => info!("Interrupted, exiting"),
+    }
+}
@@ -0,0 +1,251 @@
+//! Time varying data of the boat for the different scenarios
+
+use chrono::{Datelike, Local, Timelike, Utc};
+use clap::ValueEnum;
+use eoi_can_decoder::*;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Scenario {
+    /// Sailing at a moderate speed on solar power
+    Cruise,
+    /// Moored in the sun, charging the battery
+    Charging,
+    /// Racing with the throttle fully forward
+    FullThrottle,
+    /// Cruising with errors on the battery, throttle and a motor controller faulted by the heat
+    Fault,
+}
+
+/// Number of solar MPPTs that are faked
+const MPPT_COUNT: u8 = 4;
+const CELL_COUNT: usize = 14;
+/// Start position of the boat, in the harbour of Monaco
+const START_LATITUDE: f64 = 43.7347;
+const START_LONGITUDE: f64 = 7.4206;
+
+impl Scenario {
+    /// All data the boat sends at `time` seconds since the start
+    pub fn data(self, time: f32, vesc_id: u8) -> Vec<EoiCanData> {
+        let wave = |period: f32| (time * core::f32::consts::TAU / period).sin();
+
+        let (throttle, solar) = match self {
+            Scenario::Cruise | Scenario::Fault => (0.4 + 0.05 * wave(20.0), 0.8),
+            Scenario::Charging => (0.0, 0.9),
+            Scenario::FullThrottle => (1.0, 0.8),
+        };
+
+        let solar_power = 250.0 * solar * (1.0 + 0.1 * wave(13.0));
+        let motor_current = 80.0 * throttle;
+        let motor_voltage = 50.0;
+        let pack_current = solar_power * MPPT_COUNT as f32 / motor_voltage - motor_current;
+        // Keep the state of charge slowly moving, up when charging and down otherwise
+        let state_of_charge = (80.0 + pack_current * time / 3600.0).clamp(0.0, 100.0);
+        let cell_voltage = 3.3 + 0.9 * state_of_charge / 100.0;
+        let fault = self == Scenario::Fault;
+
+        let mut data = vec![
+            EoiCanData::EoiBattery(EoiBattery::PackAndPerriCurrent(PackAndPerriCurrent {
+                pack_current,
+                perri_current: -0.25,
+            })),
+            EoiCanData::EoiBattery(EoiBattery::ChargeAndDischargeCurrent(
+                ChargeAndDischargeCurrent {
+                    charge_current: pack_current.max(0.0),
+                    discharge_current: pack_current.min(0.0),
+                },
+            )),
+            EoiCanData::EoiBattery(EoiBattery::SocErrorFlagsAndBalancing(
+                SocErrorFlagsAndBalancing {
+                    state_of_charge,
+                    error_flags: if fault { 0b101 } else { 0 },
+                    balancing_status: if self == Scenario::Charging { 0b11 } else { 0 },
+                },
+            )),
+        ];
+        // Adds up to the peripheral current of the BMS
+        data.extend(
+            PeripheralConsumer::ALL
+                .into_iter()
+                .zip([-0.1, -0.08, -0.02, -0.05])
+                .map(|(consumer, current)| {
+                    EoiCanData::PeripheralPower(PeripheralPower { consumer, current })
+                }),
+        );
+
+        let cells: [f32; CELL_COUNT] =
+            core::array::from_fn(|cell| cell_voltage + 0.005 * (cell as f32 + time).sin());
+        let pack_voltage = cells.iter().sum();
+        data.extend([
+            EoiCanData::EoiBattery(EoiBattery::CellVoltages1_4(FourCellVoltages {
+                cell_voltage: [cells[0], cells[1], cells[2], cells[3]],
+            })),
+            EoiCanData::EoiBattery(EoiBattery::CellVoltages5_8(FourCellVoltages {
+                cell_voltage: [cells[4], cells[5], cells[6], cells[7]],
+            })),
+            EoiCanData::EoiBattery(EoiBattery::CellVoltages9_12(FourCellVoltages {
+                cell_voltage: [cells[8], cells[9], cells[10], cells[11]],
+            })),
+            EoiCanData::EoiBattery(EoiBattery::CellVoltages13_14PackAndStack(
+                CellVoltages13_14PackAndStack {
+                    cell_voltage: [cells[12], cells[13]],
+                    pack_voltage,
+                    stack_voltage: pack_voltage + 0.1,
+                },
+            )),
+            EoiCanData::EoiBattery(EoiBattery::TemperaturesAndStates(TemperaturesAndStates {
+                temperatures: [25, 26, 27, 25].map(|t| t + (throttle * 10.0) as i8),
+                ic_temperature: 35,
+                battery_state: match self {
+                    Scenario::Charging => BatteryState::OnlyCharge,
+                    _ => BatteryState::On,
+                },
+                charge_state: if fault {
+                    ChargeState::Error
+                } else {
+                    ChargeState::FetOn
+                },
+                discharge_state: DischargeState::On,
+            })),
+            EoiCanData::EoiBattery(EoiBattery::BatteryUptime(BatteryUptime {
+                uptime_ms: (time * 1000.0) as u32,
+            })),
+        ]);
+
+        for mppt_id in 0..MPPT_COUNT {
+            let power = solar_power * (1.0 + 0.05 * (mppt_id as f32 + time / 7.0).sin());
+            let voltage_in = 40.0 + mppt_id as f32;
+            let info = [
+                MpptInfo::Channel0(MpptChannel::Power(MpptChannelPower {
+                    voltage_in,
+                    current_in: power / voltage_in,
+                })),
+                MpptInfo::Power(MpptPower {
+                    voltage_out: motor_voltage,
+                    current_out: power / motor_voltage,
+                }),
+                MpptInfo::Status(MpptStatus {
+                    voltage_out_switch: motor_voltage,
+                    temperature: 40,
+                    state: 3,
+                    pwm_enabled: true,
+                    switch_on: true,
+                }),
+            ];
+            data.extend(
+                info.into_iter()
+                    .filter_map(|info| MpptData::from_node_id(mppt_id, info))
+                    .map(EoiCanData::Mppt),
+            );
+        }
+
+        // Speed follows the throttle, the boat sails a large circle
+        let speed = 45.0 * throttle;
+        let heading = (time * 2.0) % 360.0;
+        let angle = heading.to_radians() as f64;
+        let now = Utc::now();
+        data.extend([
+            EoiCanData::Gnss(GnssData::GnssStatus(GnssStatus {
+                fix: 3,
+                sats: 12,
+                sats_used: 9,
+            })),
+            EoiCanData::Gnss(GnssData::GnssSpeedAndHeading(speed, heading)),
+            EoiCanData::Gnss(GnssData::GnssLatitude(START_LATITUDE + 0.01 * angle.sin())),
+            EoiCanData::Gnss(GnssData::GnssLongitude(
+                START_LONGITUDE + 0.01 * angle.cos(),
+            )),
+            EoiCanData::Gnss(GnssData::GnssAltitude(1.5 + 0.2 * wave(5.0))),
+            EoiCanData::Gnss(GnssData::GnssFixQuality(GnssFixQuality {
+                mode: 3,
+                horizontal_error: Some(0.9),
+            })),
+            EoiCanData::Gnss(GnssData::GnssUtcOffset(
+                (Local::now().offset().local_minus_utc() / 60) as i16,
+            )),
+            EoiCanData::Gnss(GnssData::GnssTimeSync(now.timestamp_micros() as u64)),
+            EoiCanData::Gnss(GnssData::GnssDateTime(GnssDateTime {
+                year: now.year() as u16,
+                month: now.month() as u8,
+                day: now.day() as u8,
+                hours: now.hour() as u8,
+                minutes: now.minute() as u8,
+                seconds: now.second() as u8,
+            })),
+        ]);
+
+        // A current of 2 km/h to the north, which the boat meets all around the circle
+        let water_speed = (speed - 2.0 * heading.to_radians().cos()).max(0.0);
+        data.push(EoiCanData::WaterSpeed(WaterSpeed {
+            speed_kmh: water_speed,
+            pulse_frequency: 2.2 * water_speed,
+        }));
+        // The bow comes up with the speed, the boat heels into the circle and rides the waves
+        data.push(EoiCanData::Imu(ImuAttitude {
+            pitch: 4.0 * throttle + 0.5 * wave(3.0),
+            roll: -3.0 * throttle + wave(7.0),
+            heave_rate: Some(0.2 * wave(3.0)),
+        }));
+
+        let fet_temp = if fault { 95.0 } else { 35.0 + 20.0 * throttle };
+        let vesc = [
+            VescMessage::StatusMessage1 {
+                rpm: (3000.0 * throttle) as i32,
+                total_current: motor_current,
+                duty_cycle: 95.0 * throttle,
+            },
+            VescMessage::StatusMessage4 {
+                fet_temp,
+                motor_temp: fet_temp + 5.0,
+                total_input_current: motor_current,
+                current_pid_position: 0.0,
+            },
+            VescMessage::StatusMessage5 {
+                input_voltage: motor_voltage,
+                tachometer: (time * 50.0 * throttle) as i32,
+            },
+            VescMessage::Fault {
+                fault: if fault {
+                    VescFault::OverTempFet
+                } else {
+                    VescFault::None
+                },
+            },
+        ];
+        data.extend(
+            vesc.into_iter()
+                .filter_map(|message| VescData::from_node_id(vesc_id, message))
+                .map(EoiCanData::Vesc),
+        );
+
+        data.push(EoiCanData::Throttle(ThrottleData::Status(ThrottleStatus {
+            value: throttle * 100.0,
+            raw_angle: (throttle * 1000.0) as i16,
+            raw_deadmen: 300,
+            gain: 7,
+            error: ThrottleErrors {
+                deadman_missing: fault,
+                ..Default::default()
+            },
+        })));
+
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use eoi_can_decoder::can_encoder::encode_eoi_can_data;
+
+    #[test]
+    fn all_data_can_be_encoded() {
+        for scenario in Scenario::value_variants() {
+            for data in scenario.data(12.5, DecoderConfig::DEFAULT_VESC_CONTROLLER_ID) {
+                let frame = encode_eoi_can_data(&data);
+                assert!(frame.is_some(), "{:?}", data);
+                assert!(parse_eoi_can_data(&frame.unwrap()).is_some());
+            }
+        }
+    }
+}
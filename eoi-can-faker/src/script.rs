@@ -0,0 +1,328 @@
+//! Test scenarios written as [Rhai](https://rhai.rs) scripts on top of the faked traffic, so a
+//! condition like a failing MPPT or an overheating motor doesn't need a new build.
+//!
+//! Before every round of frames the script function `tick(t)` is called with the seconds since
+//! the start on the virtual clock. What it asks for holds for that round only:
+//!
+//! - `set(message, signal, value)` or `set(message, node, signal, value)` overrides a signal, by
+//!   the names of the signal registry like `set("VescStatusMessage4", "MotorTemperature", 95.0)`.
+//!   A message the scenario doesn't send is sent with the other signals zero
+//! - `drop_message(message)` or `drop_message(message, node)` leaves out the frames of a message,
+//!   `drop_device("MPPT", 3)` those of every message the node sends
+//! - `send(id, [bytes])` sends one more frame, IDs above 0x7FF are extended
+//!
+//! The messages of the VESC are sent with the controller ID of `--vesc-id`.
+//!
+//! Code outside of functions runs once when the script is loaded, `print` logs.
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use embedded_can::{ExtendedId, Id, StandardId};
+use eoi_can_decoder::DecoderConfig;
+use eoi_can_decoder::can_frame::CanFrame;
+use eoi_can_decoder::signals::{
+    MESSAGES, MessageDefinition, SignalDefinition, find_message_by_name,
+};
+use rhai::{AST, Array, Engine, EvalAltResult, Scope};
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+/// What the script asked for in the current round
+#[derive(Default)]
+struct Round {
+    signals: Vec<(
+        &'static MessageDefinition,
+        u8,
+        &'static SignalDefinition,
+        f64,
+    )>,
+    dropped: Vec<(&'static MessageDefinition, Option<u8>)>,
+    frames: Vec<CanFrame>,
+}
+
+type ScriptResult = Result<(), Box<EvalAltResult>>;
+
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    round: Arc<Mutex<Round>>,
+    has_tick: bool,
+    vesc_id: u8,
+}
+
+impl Script {
+    /// Compile the script and run its top level code
+    pub fn load(path: &Path, vesc_id: u8) -> Result<Self, Box<EvalAltResult>> {
+        let engine = Engine::new();
+        let ast = engine.compile_file(path.into())?;
+        Self::new(engine, ast, vesc_id)
+    }
+
+    fn new(mut engine: Engine, ast: AST, vesc_id: u8) -> Result<Self, Box<EvalAltResult>> {
+        let round = Arc::new(Mutex::new(Round::default()));
+        engine.on_print(|text| info!("Script: {}", text));
+
+        let signals = round.clone();
+        let set = move |message: &str, node: i64, signal: &str, value: f64| -> ScriptResult {
+            let message = message_definition(message)?;
+            let node = node_of(message, node)?;
+            let signal = message
+                .signals
+                .iter()
+                .find(|definition| definition.name == signal)
+                .ok_or_else(|| format!("{} has no signal {}", message.name, signal))?;
+            lock(&signals).signals.push((message, node, signal, value));
+            Ok(())
+        };
+        let set_first_node = set.clone();
+        engine.register_fn("set", set);
+        engine.register_fn(
+            "set",
+            move |message: &str, signal: &str, value: f64| -> ScriptResult {
+                set_first_node(message, 0, signal, value)
+            },
+        );
+
+        let dropped = round.clone();
+        engine.register_fn("drop_message", move |message: &str| -> ScriptResult {
+            let message = message_definition(message)?;
+            lock(&dropped).dropped.push((message, None));
+            Ok(())
+        });
+        let dropped = round.clone();
+        engine.register_fn(
+            "drop_message",
+            move |message: &str, node: i64| -> ScriptResult {
+                let message = message_definition(message)?;
+                let node = node_of(message, node)?;
+                lock(&dropped).dropped.push((message, Some(node)));
+                Ok(())
+            },
+        );
+
+        let dropped = round.clone();
+        engine.register_fn(
+            "drop_device",
+            move |transmitter: &str, node: i64| -> ScriptResult {
+                let messages: Vec<_> = MESSAGES
+                    .iter()
+                    .filter(|message| message.transmitter == transmitter)
+                    .collect();
+                let node = messages
+                    .first()
+                    .ok_or_else(|| format!("Unknown device {}", transmitter))
+                    .and_then(|message| {
+                        node_of(message, node).map_err(|error| error.to_string())
+                    })?;
+                lock(&dropped)
+                    .dropped
+                    .extend(messages.into_iter().map(|message| (message, Some(node))));
+                Ok(())
+            },
+        );
+
+        let frames = round.clone();
+        engine.register_fn("send", move |id: i64, data: Array| -> ScriptResult {
+            let data = data
+                .into_iter()
+                .map(|byte| {
+                    byte.as_int()
+                        .ok()
+                        .and_then(|byte| u8::try_from(byte).ok())
+                        .ok_or_else(|| format!("{} is not a byte", byte))
+                })
+                .collect::<Result<Vec<u8>, _>>()?;
+            let id = u32::try_from(id)
+                .ok()
+                .and_then(|id| can_id(id, id > u32::from(StandardId::MAX.as_raw())))
+                .ok_or_else(|| format!("{:#X} is not a CAN ID", id))?;
+            let frame = CanFrame::try_new(id, &data).map_err(|error| error.to_string())?;
+            lock(&frames).frames.push(frame);
+            Ok(())
+        });
+
+        let mut scope = Scope::new();
+        engine.run_ast_with_scope(&mut scope, &ast)?;
+        let has_tick = ast.iter_functions().any(|function| function.name == "tick");
+        if !has_tick {
+            warn!("The script has no tick(t) function, the traffic stays unchanged");
+        }
+        Ok(Self {
+            engine,
+            ast,
+            scope,
+            round,
+            has_tick,
+            vesc_id,
+        })
+    }
+
+    /// Let the script change the `frames` of the round at `time` seconds on the virtual clock
+    pub fn apply(&mut self, time: f64, mut frames: Vec<CanFrame>) -> Vec<CanFrame> {
+        if !self.has_tick {
+            return frames;
+        }
+        *lock(&self.round) = Round::default();
+        if let Err(error) = self
+            .engine
+            .call_fn::<()>(&mut self.scope, &self.ast, "tick", (time,))
+        {
+            warn!("Script failed at {:.1} s: {}", time, error);
+        }
+        let round = std::mem::take(&mut *lock(&self.round));
+
+        for (message, node, signal, value) in round.signals {
+            let Some(id) = can_id_of(message, node, self.vesc_id) else {
+                continue;
+            };
+            let index = match frames.iter().position(|frame| frame.id == id) {
+                Some(index) => index,
+                None => {
+                    let data = vec![0; message.dlc.into()];
+                    let Ok(frame) = CanFrame::try_new(id, &data) else {
+                        continue;
+                    };
+                    frames.push(frame);
+                    frames.len() - 1
+                }
+            };
+            if signal.encode(&mut frames[index].data, value).is_none() {
+                debug!("{}.{} doesn't fit the frame", message.name, signal.name);
+            }
+        }
+
+        let vesc_id = self.vesc_id;
+        frames.retain(|frame| {
+            !round.dropped.iter().any(|&(message, node)| match node {
+                Some(node) => can_id_of(message, node, vesc_id) == Some(frame.id),
+                None => message
+                    .ids()
+                    .any(|(node, _)| can_id_of(message, node, vesc_id) == Some(frame.id)),
+            })
+        });
+        frames.extend(round.frames);
+        frames
+    }
+}
+
+fn lock(round: &Mutex<Round>) -> std::sync::MutexGuard<'_, Round> {
+    // A panicking script function leaves nothing half written
+    round
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn message_definition(name: &str) -> Result<&'static MessageDefinition, Box<EvalAltResult>> {
+    find_message_by_name(name).ok_or_else(|| format!("Unknown message {}", name).into())
+}
+
+fn node_of(message: &MessageDefinition, node: i64) -> Result<u8, Box<EvalAltResult>> {
+    u8::try_from(node)
+        .ok()
+        .filter(|&node| node < message.node_count)
+        .ok_or_else(|| format!("{} has no node {}", message.name, node).into())
+}
+
+/// CAN ID of `node` of the message. The registry has the messages of the VESC CAN ID scheme at the
+/// default controller ID, they get `vesc_id` instead like the frames of the scenario
+fn can_id_of(message: &MessageDefinition, node: u8, vesc_id: u8) -> Option<Id> {
+    let default_vesc_id = u32::from(DecoderConfig::DEFAULT_VESC_CONTROLLER_ID);
+    if message.extended && message.id & 0xFF == default_vesc_id {
+        let id = message.id_for_node(node)? & !0xFF | u32::from(vesc_id);
+        return ExtendedId::new(id).map(Id::Extended);
+    }
+    message.can_id(node)
+}
+
+fn can_id(id: u32, extended: bool) -> Option<Id> {
+    if extended {
+        ExtendedId::new(id).map(Id::Extended)
+    } else {
+        StandardId::new(u16::try_from(id).ok()?).map(Id::Standard)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use eoi_can_decoder::signals::find_message;
+
+    fn script(source: &str) -> Script {
+        let engine = Engine::new();
+        let ast = engine.compile(source).unwrap();
+        Script::new(engine, ast, DecoderConfig::DEFAULT_VESC_CONTROLLER_ID).unwrap()
+    }
+
+    #[test]
+    fn overrides_and_drops_frames() {
+        let mut script = script(
+            r#"
+            let start = 10.0;
+            fn tick(t) {
+                if t > 120.0 {
+                    drop_device("MPPT", 3);
+                }
+                set("VescStatusMessage4", "MotorTemperature", 40.0 + t);
+                send(0x123, [1, 2]);
+            }
+            "#,
+        );
+        let mppt = find_message_by_name("MpptPower").unwrap();
+        let frame = |node| {
+            let id = mppt.can_id(node).unwrap();
+            CanFrame::try_new(id, &[0; 8]).unwrap()
+        };
+
+        let frames = script.apply(60.0, vec![frame(2), frame(3)]);
+        assert!(frames.len() == 4);
+        let temperature = frames
+            .iter()
+            .find_map(|frame| {
+                let Id::Extended(id) = frame.id else {
+                    return None;
+                };
+                let (message, _) = find_message(id.as_raw(), true)?;
+                (message.name == "VescStatusMessage4")
+                    .then(|| message.signals[1].decode(&frame.data).unwrap())
+            })
+            .unwrap();
+        assert!((temperature - 100.0).abs() < 0.1);
+        assert!(frames[3].data == [1, 2]);
+
+        // Only MPPT 3 is dropped, the override holds for one round only
+        let frames = script.apply(121.0, vec![frame(2), frame(3)]);
+        assert!(frames.len() == 3);
+        assert!(frames[0] == frame(2));
+    }
+
+    #[test]
+    fn example_script_runs() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenarios/overheat.rhai");
+        let mut script = Script::load(&path, DecoderConfig::DEFAULT_VESC_CONTROLLER_ID).unwrap();
+        assert!(script.apply(30.0, Vec::new()).is_empty());
+        assert!(script.apply(90.0, Vec::new()).len() == 1);
+    }
+
+    #[test]
+    fn vesc_messages_use_the_controller_id() {
+        let engine = Engine::new();
+        let ast = engine
+            .compile(r#"fn tick(t) { set("VescStatusMessage4", "MotorTemperature", 60.0); }"#)
+            .unwrap();
+        let mut script = Script::new(engine, ast, 42).unwrap();
+        let frames = script.apply(1.0, Vec::new());
+        let status = find_message_by_name("VescStatusMessage4").unwrap();
+        assert!(frames.len() == 1);
+        assert!(frames[0].id == Id::Extended(ExtendedId::new(status.id & !0xFF | 42).unwrap()));
+    }
+
+    #[test]
+    fn unknown_names_fail_the_round() {
+        let mut script = script(r#"fn tick(t) { set("NoSuchMessage", "Value", 1.0); }"#);
+        let frames = script.apply(1.0, Vec::new());
+        assert!(frames.is_empty());
+    }
+}
@@ -0,0 +1,68 @@
+/*
+ * C interface of eoi-can-decoder, see eoi-can-ffi/src/lib.rs.
+ *
+ * Link against libeoi_can_ffi.so (or libeoi_can_ffi.a with -lpthread -ldl -lm), built with
+ * `cargo build --release -p eoi-can-ffi`.
+ *
+ *     EoiCanMessage message;
+ *     if (eoi_can_parse(frame.can_id, extended, frame.data, frame.len, &message) == EOI_CAN_OK) {
+ *         for (uint8_t i = 0; i < message.signal_count; i++) {
+ *             printf("%s.%s = %f %s\n", message.name, message.signals[i].name,
+ *                    message.signals[i].value, message.signals[i].unit);
+ *         }
+ *     }
+ */
+
+#ifndef EOI_CAN_H
+#define EOI_CAN_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Room for the signals of a message, the message with the most of them has to fit */
+#define EOI_CAN_MAX_SIGNALS 16
+/* Length of the names including the terminating NUL, longer names are cut */
+#define EOI_CAN_NAME_LEN 48
+#define EOI_CAN_UNIT_LEN 8
+
+/* The frame was decoded */
+#define EOI_CAN_OK 0
+/* The decoder doesn't know the frame, like a message of another team on a shared bus */
+#define EOI_CAN_UNKNOWN 1
+/* A null pointer, more than 8 bytes or an ID out of range */
+#define EOI_CAN_INVALID (-1)
+
+typedef struct {
+    char name[EOI_CAN_NAME_LEN];
+    char unit[EOI_CAN_UNIT_LEN];
+    /* Physical value, NaN when the signal doesn't fit the received bytes */
+    double value;
+} EoiCanSignal;
+
+typedef struct {
+    /* Name in the signal registry, like "MpptPower" */
+    char name[EOI_CAN_NAME_LEN];
+    /* Node of messages sent by more than one device, like the MPPT, otherwise 0 */
+    uint8_t node;
+    uint8_t signal_count;
+    EoiCanSignal signals[EOI_CAN_MAX_SIGNALS];
+} EoiCanMessage;
+
+/*
+ * Decode the frame with the CAN ID `id` and the `len` bytes at `data` into `message`. Returns
+ * EOI_CAN_OK, EOI_CAN_UNKNOWN or EOI_CAN_INVALID, `message` is only written on success.
+ * VESC frames are decoded for the default controller ID.
+ */
+int32_t eoi_can_parse(uint32_t id, bool extended, const uint8_t *data, size_t len,
+                      EoiCanMessage *message);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* EOI_CAN_H */
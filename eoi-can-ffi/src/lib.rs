@@ -0,0 +1,211 @@
+//! C interface of the decoder, for the C++ ground station and other tools that can't link Rust.
+//! A frame is checked with [`parse_eoi_can_data`] and its signals are filled in from the signal
+//! registry, so a message comes out with the same names, scaling and units as in the DBC export
+//! and the MQTT payloads. The header is `include/eoi_can.h`.
+//!
+//! VESC frames are decoded for the default controller ID, see [`DecoderConfig`].
+//!
+//! [`DecoderConfig`]: eoi_can_decoder::DecoderConfig
+
+use core::ffi::c_char;
+
+use embedded_can::{ExtendedId, Id, StandardId};
+use eoi_can_decoder::can_frame::CanFrame;
+use eoi_can_decoder::parse_eoi_can_data;
+use eoi_can_decoder::signals::find_message;
+
+/// Room for the signals of a message, the message with the most of them has to fit
+pub const EOI_CAN_MAX_SIGNALS: usize = 16;
+/// Length of the names including the terminating NUL, longer names are cut
+pub const EOI_CAN_NAME_LEN: usize = 48;
+pub const EOI_CAN_UNIT_LEN: usize = 8;
+
+/// The frame was decoded
+pub const EOI_CAN_OK: i32 = 0;
+/// The decoder doesn't know the frame, like a message of another team on a shared bus
+pub const EOI_CAN_UNKNOWN: i32 = 1;
+/// A null pointer, more than 8 bytes or an ID out of range
+pub const EOI_CAN_INVALID: i32 = -1;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct EoiCanSignal {
+    pub name: [c_char; EOI_CAN_NAME_LEN],
+    pub unit: [c_char; EOI_CAN_UNIT_LEN],
+    /// Physical value, NaN when the signal doesn't fit the received bytes
+    pub value: f64,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct EoiCanMessage {
+    /// Name in the signal registry, like "MpptPower"
+    pub name: [c_char; EOI_CAN_NAME_LEN],
+    /// Node of messages sent by more than one device, like the MPPT, otherwise 0
+    pub node: u8,
+    pub signal_count: u8,
+    pub signals: [EoiCanSignal; EOI_CAN_MAX_SIGNALS],
+}
+
+impl Default for EoiCanMessage {
+    fn default() -> Self {
+        let signal = EoiCanSignal {
+            name: [0; EOI_CAN_NAME_LEN],
+            unit: [0; EOI_CAN_UNIT_LEN],
+            value: f64::NAN,
+        };
+        Self {
+            name: [0; EOI_CAN_NAME_LEN],
+            node: 0,
+            signal_count: 0,
+            signals: [signal; EOI_CAN_MAX_SIGNALS],
+        }
+    }
+}
+
+/// Decode the frame with the CAN ID `id` and the `len` bytes at `data` into `message`. Returns
+/// `EOI_CAN_OK`, `EOI_CAN_UNKNOWN` or `EOI_CAN_INVALID`, `message` is only written on success.
+///
+/// # Safety
+///
+/// `data` has to point to `len` readable bytes, or may be null when `len` is 0, and `message` to
+/// a writable `EoiCanMessage`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn eoi_can_parse(
+    id: u32,
+    extended: bool,
+    data: *const u8,
+    len: usize,
+    message: *mut EoiCanMessage,
+) -> i32 {
+    if message.is_null() || (data.is_null() && len > 0) {
+        return EOI_CAN_INVALID;
+    }
+    let data = if len == 0 {
+        &[]
+    } else {
+        // SAFETY: the caller guarantees `len` readable bytes at `data`
+        unsafe { core::slice::from_raw_parts(data, len) }
+    };
+    match parse(id, extended, data) {
+        Ok(parsed) => {
+            // SAFETY: the caller guarantees `message` is writable
+            unsafe { message.write(parsed) };
+            EOI_CAN_OK
+        }
+        Err(code) => code,
+    }
+}
+
+fn parse(id: u32, extended: bool, data: &[u8]) -> Result<EoiCanMessage, i32> {
+    let can_id = if extended {
+        ExtendedId::new(id).map(Id::Extended)
+    } else {
+        u16::try_from(id)
+            .ok()
+            .and_then(StandardId::new)
+            .map(Id::Standard)
+    }
+    .ok_or(EOI_CAN_INVALID)?;
+    let frame = CanFrame::try_new(can_id, data).map_err(|_| EOI_CAN_INVALID)?;
+    parse_eoi_can_data(&frame).ok_or(EOI_CAN_UNKNOWN)?;
+    let (definition, node) = find_message(id, extended).ok_or(EOI_CAN_UNKNOWN)?;
+
+    let mut message = EoiCanMessage::default();
+    copy_name(&mut message.name, definition.name);
+    message.node = node;
+    for (signal, definition) in message.signals.iter_mut().zip(definition.signals) {
+        copy_name(&mut signal.name, definition.name);
+        copy_name(&mut signal.unit, definition.unit);
+        signal.value = definition.decode(&frame.data).unwrap_or(f64::NAN);
+        message.signal_count += 1;
+    }
+    Ok(message)
+}
+
+/// Copy `name` NUL terminated into `target`, cut to fit
+fn copy_name(target: &mut [c_char], name: &str) {
+    let len = name.len().min(target.len() - 1);
+    for (target, byte) in target.iter_mut().zip(&name.as_bytes()[..len]) {
+        *target = *byte as c_char;
+    }
+    target[len] = 0;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use core::ffi::CStr;
+    use eoi_can_decoder::PERIPHERAL_POWER_ID;
+    use eoi_can_decoder::signals::{MESSAGES, sample_frames};
+
+    fn text(name: &[c_char]) -> &str {
+        // SAFETY: `copy_name` always terminates the names
+        unsafe { CStr::from_ptr(name.as_ptr()) }.to_str().unwrap()
+    }
+
+    #[test]
+    fn registry_fits_the_header() {
+        let header = include_str!("../include/eoi_can.h");
+        for (name, value) in [
+            ("EOI_CAN_MAX_SIGNALS", EOI_CAN_MAX_SIGNALS as i32),
+            ("EOI_CAN_NAME_LEN", EOI_CAN_NAME_LEN as i32),
+            ("EOI_CAN_UNIT_LEN", EOI_CAN_UNIT_LEN as i32),
+            ("EOI_CAN_OK", EOI_CAN_OK),
+            ("EOI_CAN_UNKNOWN", EOI_CAN_UNKNOWN),
+            ("EOI_CAN_INVALID", EOI_CAN_INVALID),
+        ] {
+            let define = format!("#define {} {}\n", name, value);
+            let define = define.replace(" -1\n", " (-1)\n");
+            assert!(header.contains(&define), "{}", define);
+        }
+
+        for message in MESSAGES {
+            assert!(
+                message.signals.len() <= EOI_CAN_MAX_SIGNALS,
+                "{}",
+                message.name
+            );
+            assert!(message.name.len() < EOI_CAN_NAME_LEN, "{}", message.name);
+            for signal in message.signals {
+                assert!(signal.name.len() < EOI_CAN_NAME_LEN, "{}", signal.name);
+                assert!(signal.unit.len() < EOI_CAN_UNIT_LEN, "{}", signal.unit);
+            }
+        }
+    }
+
+    #[test]
+    fn parse_frames() {
+        let mut message = EoiCanMessage::default();
+        let data = 1.5f32.to_le_bytes();
+        let id = u32::from(PERIPHERAL_POWER_ID) + 2;
+        let result = unsafe { eoi_can_parse(id, false, data.as_ptr(), data.len(), &mut message) };
+        assert!(result == EOI_CAN_OK);
+        assert!(text(&message.name) == "PeripheralPower");
+        assert!(message.node == 2);
+        assert!(message.signal_count == 1);
+        assert!(text(&message.signals[0].name) == "Current");
+        assert!(text(&message.signals[0].unit) == "A");
+        assert!(message.signals[0].value == 1.5);
+
+        let unknown = unsafe { eoi_can_parse(0x7FF, false, data.as_ptr(), 4, &mut message) };
+        assert!(unknown == EOI_CAN_UNKNOWN);
+        let invalid = unsafe { eoi_can_parse(0x800, false, data.as_ptr(), 4, &mut message) };
+        assert!(invalid == EOI_CAN_INVALID);
+        let null = unsafe { eoi_can_parse(id, false, data.as_ptr(), 4, core::ptr::null_mut()) };
+        assert!(null == EOI_CAN_INVALID);
+
+        // Every message of the registry comes out with all its signals
+        for (definition, node, frame) in sample_frames(0x01) {
+            let (id, extended) = match frame.id {
+                Id::Standard(id) => (u32::from(id.as_raw()), false),
+                Id::Extended(id) => (id.as_raw(), true),
+            };
+            let message = parse(id, extended, &frame.data).unwrap();
+            assert!(text(&message.name) == definition.name);
+            assert!(message.node == node);
+            assert!(usize::from(message.signal_count) == definition.signals.len());
+        }
+    }
+}
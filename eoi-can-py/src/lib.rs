@@ -0,0 +1,249 @@
+//! Python module `eoi_can` with the decoder, the signal registry and the candump logs, so the
+//! notebooks analysing a race scale the values exactly like the display and MQTT instead of
+//! carrying their own copy of the factors. Built with `maturin develop -m eoi-can-py/Cargo.toml`.
+//!
+//! - `parse(can_id, data, extended=False)` decodes a frame with `parse_eoi_can_data` into nested
+//!   dicts shaped like the MQTT payloads, `None` for frames the decoder doesn't know
+//! - `signals(can_id, data, extended=False)` gives `(message, node, {signal: value})` by the
+//!   signal registry, the names of the DBC export
+//! - `encode(message, {signal: value}, node=0)` builds `(can_id, extended, data)` of a message of
+//!   the registry, signals not given are 0
+//! - `read_candump(path)` reads a candump log into `(timestamp, interface, can_id, extended, data)`
+//!   tuples, the timestamp in seconds since the Unix epoch
+//!
+//! VESC frames are decoded for the default controller ID, see [`DecoderConfig`].
+//!
+//! [`DecoderConfig`]: eoi_can_decoder::DecoderConfig
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use can_logger::parse_candump_line;
+use embedded_can::{ExtendedId, Id, StandardId};
+use eoi_can_decoder::can_frame::CanFrame;
+use eoi_can_decoder::parse_eoi_can_data;
+use eoi_can_decoder::signals::{find_message, find_message_by_name};
+use pyo3::IntoPyObjectExt;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use serde_json::Value;
+
+/// `(can_id, extended, data)` of a frame
+type RawFrame = (u32, bool, Vec<u8>);
+/// `(timestamp, interface, can_id, extended, data)` of a frame of a candump log
+type LoggedFrame = (f64, String, u32, bool, Vec<u8>);
+
+#[pymodule]
+fn eoi_can(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(parse, module)?)?;
+    module.add_function(wrap_pyfunction!(signals, module)?)?;
+    module.add_function(wrap_pyfunction!(encode, module)?)?;
+    module.add_function(wrap_pyfunction!(read_candump, module)?)?;
+    Ok(())
+}
+
+/// Decode a frame into nested dicts like the MQTT payloads, `None` when the decoder doesn't know
+/// it
+#[pyfunction]
+#[pyo3(signature = (can_id, data, extended = false))]
+fn parse<'py>(
+    py: Python<'py>,
+    can_id: u32,
+    data: &[u8],
+    extended: bool,
+) -> PyResult<Option<Bound<'py, PyAny>>> {
+    match parse_json(can_id, extended, data).map_err(PyValueError::new_err)? {
+        Some(value) => Ok(Some(to_python(py, &value)?)),
+        None => Ok(None),
+    }
+}
+
+/// Decode a frame by the signal registry into `(message, node, {signal: value})`, `None` when
+/// the decoder doesn't know it
+#[pyfunction]
+#[pyo3(signature = (can_id, data, extended = false))]
+fn signals<'py>(
+    py: Python<'py>,
+    can_id: u32,
+    data: &[u8],
+    extended: bool,
+) -> PyResult<Option<(&'static str, u8, Bound<'py, PyDict>)>> {
+    let Some((message, node, values)) =
+        decode_signals(can_id, extended, data).map_err(PyValueError::new_err)?
+    else {
+        return Ok(None);
+    };
+    // A dict keeps the order of the registry
+    let dict = PyDict::new(py);
+    for (name, value) in values {
+        dict.set_item(name, value)?;
+    }
+    Ok(Some((message, node, dict)))
+}
+
+/// Build `(can_id, extended, data)` of `message` of the signal registry from its signal values
+#[pyfunction]
+#[pyo3(signature = (message, signals, node = 0))]
+fn encode(message: &str, signals: HashMap<String, f64>, node: u8) -> PyResult<RawFrame> {
+    let signals: Vec<_> = signals.into_iter().collect();
+    encode_signals(message, &signals, node).map_err(PyValueError::new_err)
+}
+
+/// Read the data frames of a candump log into `(timestamp, interface, can_id, extended, data)`
+#[pyfunction]
+fn read_candump(path: PathBuf) -> PyResult<Vec<LoggedFrame>> {
+    let log = std::fs::read_to_string(path)?;
+    Ok(read_candump_lines(&log))
+}
+
+fn frame(can_id: u32, extended: bool, data: &[u8]) -> Result<CanFrame, String> {
+    let id = if extended {
+        ExtendedId::new(can_id).map(Id::Extended)
+    } else {
+        u16::try_from(can_id)
+            .ok()
+            .and_then(StandardId::new)
+            .map(Id::Standard)
+    }
+    .ok_or_else(|| format!("{:#X} is not a CAN ID", can_id))?;
+    CanFrame::try_new(id, data).map_err(|error| error.to_string())
+}
+
+fn parse_json(can_id: u32, extended: bool, data: &[u8]) -> Result<Option<Value>, String> {
+    let frame = frame(can_id, extended, data)?;
+    Ok(parse_eoi_can_data(&frame).map(|data| serde_json::json!(data)))
+}
+
+type Signals = (&'static str, u8, Vec<(&'static str, f64)>);
+
+fn decode_signals(can_id: u32, extended: bool, data: &[u8]) -> Result<Option<Signals>, String> {
+    let frame = frame(can_id, extended, data)?;
+    if parse_eoi_can_data(&frame).is_none() {
+        return Ok(None);
+    }
+    let Some((message, node)) = find_message(can_id, extended) else {
+        return Ok(None);
+    };
+    let values = message
+        .signals
+        .iter()
+        .map(|signal| (signal.name, signal.decode(&frame.data).unwrap_or(f64::NAN)))
+        .collect();
+    Ok(Some((message.name, node, values)))
+}
+
+fn encode_signals(message: &str, signals: &[(String, f64)], node: u8) -> Result<RawFrame, String> {
+    let definition =
+        find_message_by_name(message).ok_or_else(|| format!("Unknown message {}", message))?;
+    let id = definition
+        .id_for_node(node)
+        .ok_or_else(|| format!("{} has no node {}", message, node))?;
+    let mut data = vec![0; definition.dlc.into()];
+    for (name, value) in signals {
+        let signal = definition
+            .signals
+            .iter()
+            .find(|signal| signal.name == name)
+            .ok_or_else(|| format!("{} has no signal {}", message, name))?;
+        signal
+            .encode(&mut data, *value)
+            .ok_or_else(|| format!("{}.{} doesn't fit the frame", message, name))?;
+    }
+    Ok((id, definition.extended, data))
+}
+
+fn read_candump_lines(log: &str) -> Vec<LoggedFrame> {
+    log.lines()
+        .filter_map(parse_candump_line)
+        .map(|(timestamp, interface, frame)| {
+            let (id, extended) = match frame.id {
+                Id::Standard(id) => (u32::from(id.as_raw()), false),
+                Id::Extended(id) => (id.as_raw(), true),
+            };
+            (
+                timestamp.as_secs_f64(),
+                interface.to_string(),
+                id,
+                extended,
+                frame.data.to_vec(),
+            )
+        })
+        .collect()
+}
+
+fn to_python<'py>(py: Python<'py>, value: &Value) -> PyResult<Bound<'py, PyAny>> {
+    match value {
+        Value::Null => Ok(py.None().into_bound(py)),
+        Value::Bool(value) => value.into_bound_py_any(py),
+        Value::Number(number) => match number.as_i64() {
+            Some(integer) => integer.into_bound_py_any(py),
+            None => number.as_f64().into_bound_py_any(py),
+        },
+        Value::String(text) => text.into_bound_py_any(py),
+        Value::Array(values) => {
+            let values = values
+                .iter()
+                .map(|value| to_python(py, value))
+                .collect::<PyResult<Vec<_>>>()?;
+            PyList::new(py, values)?.into_bound_py_any(py)
+        }
+        Value::Object(map) => {
+            let dict = PyDict::new(py);
+            for (key, value) in map {
+                dict.set_item(key, to_python(py, value)?)?;
+            }
+            dict.into_bound_py_any(py)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use eoi_can_decoder::PERIPHERAL_POWER_ID;
+
+    #[test]
+    fn encode_and_decode_signals() {
+        let (id, extended, data) =
+            encode_signals("PeripheralPower", &[("Current".into(), 1.5)], 2).unwrap();
+        assert!(id == u32::from(PERIPHERAL_POWER_ID) + 2);
+        assert!(!extended);
+
+        let (message, node, values) = decode_signals(id, extended, &data).unwrap().unwrap();
+        assert!(message == "PeripheralPower");
+        assert!(node == 2);
+        assert!(values == [("Current", 1.5)]);
+
+        let parsed = parse_json(id, extended, &data).unwrap().unwrap();
+        assert!(parsed["PeripheralPower"]["current"] == 1.5);
+
+        assert!(decode_signals(0x7FF, false, &data).unwrap().is_none());
+        assert!(parse_json(0x800, false, &data).is_err());
+        assert!(encode_signals("PeripheralPower", &[("Voltage".into(), 1.0)], 0).is_err());
+        assert!(encode_signals("PeripheralPower", &[], 4).is_err());
+    }
+
+    #[test]
+    fn candump_lines() {
+        let frames = read_candump_lines(
+            "(1718900000.250000) can0 242#0000C03F\n\
+             (1718900001.000000) can1 1F2#R\n\
+             (1718900002.000000) can1 0000090A#01\n",
+        );
+        assert!(frames.len() == 2);
+        assert!(
+            frames[0]
+                == (
+                    1718900000.25,
+                    "can0".into(),
+                    0x242,
+                    false,
+                    vec![0, 0, 0xC0, 0x3F]
+                )
+        );
+        assert!(frames[1].2 == 0x90A);
+        assert!(frames[1].3);
+    }
+}
@@ -0,0 +1,136 @@
+use can_logger::parse_candump_line;
+use clap::Parser;
+use embedded_can::Frame;
+use eoi_can_source::{LogFilter, VirtualCan};
+use socketcan::{CanFrame, tokio::CanSocket};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+use tokio::time::Instant;
+#[allow(unused_imports)]
+use tracing::{Level, debug, error, info, trace, warn};
+use tracing_subscriber::filter::LevelFilter;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    /// candump log file to replay
+    log_file: PathBuf,
+
+    /// CAN interface to send the frames on
+    #[arg(short, long, default_value_t = String::from("vcan0"))]
+    can_interface: String,
+
+    /// Replay speed, 2 replays twice as fast as recorded
+    #[arg(short, long, default_value_t = 1.0)]
+    speed: f64,
+
+    /// Start over when the end of the log is reached
+    #[arg(short, long = "loop")]
+    repeat: bool,
+
+    /// Create the CAN interface as a virtual one when it doesn't exist, and delete it again on
+    /// exit. Needs `CAP_NET_ADMIN`
+    #[arg(long)]
+    auto_vcan: bool,
+}
+
+#[tokio::main]
+async fn main() {
+    LogFilter::init(LevelFilter::INFO);
+    eoi_can_source::install_panic_hook("eoi-can-replay");
+    let args = Args::parse();
+    if !(args.speed > 0.0 && args.speed.is_finite()) {
+        error!("Speed has to be more than 0");
+        process::exit(1);
+    }
+    info!("Replaying {:?} on {}", args.log_file, args.can_interface);
+
+    // Deleted again before exiting, also after Ctrl-C
+    let vcan = match args.auto_vcan {
+        true => match VirtualCan::ensure(&args.can_interface) {
+            Ok(vcan) => Some(vcan),
+            Err(error) => {
+                error!(
+                    "Unable to create virtual CAN interface {}: {}",
+                    args.can_interface, error
+                );
+                process::exit(1);
+            }
+        },
+        false => None,
+    };
+    let can_sock: socketcan::tokio::AsyncCanSocket<socketcan::CanSocket> =
+        CanSocket::open(args.can_interface.as_str()).expect("Unable to open CAN socket");
+
+    let result = tokio::select! {
+        result = replay_log(&args, &can_sock) => result,
+        _ = tokio::signal::ctrl_c() => {
+            info!("Interrupted, exiting");
+            Ok(())
+        }
+    };
+    drop(vcan);
+    if let Err(error) = result {
+        error!("Unable to open {:?}: {}", args.log_file, error);
+        process::exit(1);
+    }
+}
+
+/// Replay the log once, or over and over with `--loop`
+async fn replay_log(
+    args: &Args,
+    can_sock: &socketcan::tokio::AsyncCanSocket<socketcan::CanSocket>,
+) -> std::io::Result<()> {
+    loop {
+        let file = File::open(&args.log_file)?;
+
+        let frames = replay(BufReader::new(file), can_sock, args.speed).await;
+        info!("Replayed {} frames", frames);
+
+        if !args.repeat {
+            return Ok(());
+        }
+    }
+}
+
+/// Send all frames of the log, keeping the time between them. Returns the number of frames sent
+async fn replay(
+    log: impl BufRead,
+    can_sock: &socketcan::tokio::AsyncCanSocket<socketcan::CanSocket>,
+    speed: f64,
+) -> usize {
+    let start = Instant::now();
+    let mut first_timestamp = None;
+    let mut frames = 0;
+
+    for line in log.lines() {
+        let Ok(line) = line else {
+            warn!("Unable to read log line");
+            break;
+        };
+        let Some((timestamp, _, frame)) = parse_candump_line(&line) else {
+            debug!("Skipping line: {}", line);
+            continue;
+        };
+
+        let first_timestamp = *first_timestamp.get_or_insert(timestamp);
+        let offset = timestamp.saturating_sub(first_timestamp);
+        tokio::time::sleep_until(start + Duration::from_secs_f64(offset.as_secs_f64() / speed))
+            .await;
+
+        let Some(socket_frame) = CanFrame::new(frame.id, &frame.data) else {
+            continue;
+        };
+        if let Err(error) = can_sock.write_frame(socket_frame).await {
+            warn!("Failed to send frame {:?}: {}", frame, error);
+        } else {
+            trace!("Sent {:?}", frame);
+            frames += 1;
+        }
+    }
+
+    frames
+}
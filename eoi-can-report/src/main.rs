@@ -0,0 +1,182 @@
+mod report;
+mod summary;
+
+use can_logger::parse_candump_line;
+use chrono::{Days, NaiveDate};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand};
+use draw_display::render_to_image;
+use eoi_can_decoder::calibration::Calibration;
+use eoi_can_decoder::{DecoderConfig, MAX_VESC_CONTROLLERS, VescData};
+use eoi_can_source::LogFilter;
+use std::path::PathBuf;
+use std::process;
+#[allow(unused_imports)]
+use tracing::{Level, debug, error, info, trace, warn};
+use tracing_subscriber::filter::LevelFilter;
+
+use report::{Format, Report};
+use summary::{Session, day_of};
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    #[command(subcommand)]
+    period: Period,
+}
+
+#[derive(Subcommand, Debug)]
+enum Period {
+    /// Report of a single UTC day
+    Daily(ReportArgs),
+    /// Report of the seven UTC days up to the date, with a table of the days
+    Weekly(ReportArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct ReportArgs {
+    /// candump log files of the session, the ones of all buses
+    #[arg(required = true)]
+    log_files: Vec<PathBuf>,
+
+    /// Day of the report, or the last day of the week, like 2025-06-01. The day of the last
+    /// frame of the logs by default
+    #[arg(short, long)]
+    date: Option<NaiveDate>,
+
+    #[arg(short, long, value_enum, default_value_t = Format::Markdown)]
+    format: Format,
+
+    /// File to write the report to, with a PNG of the display at the end of the log next to it.
+    /// Printed without the display when not given
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// CAN IDs of the VESC motor controllers
+    #[arg(
+        long = "vesc-id",
+        default_values_t = [DecoderConfig::DEFAULT_VESC_CONTROLLER_ID],
+        value_parser = clap::value_parser!(u8).range(..i64::from(VescData::BROADCAST_CONTROLLER_ID))
+    )]
+    vesc_ids: Vec<u8>,
+
+    /// Correct signals with a known error of their sensor before decoding them, by the scale and
+    /// offset per signal of a TOML file like `eoi-can-decoder/calibration.toml`
+    #[arg(long)]
+    calibration: Option<PathBuf>,
+}
+
+fn main() {
+    LogFilter::init_with_writer(LevelFilter::INFO, std::io::stderr);
+    eoi_can_source::install_panic_hook("eoi-can-report");
+    let (args, days) = match Args::parse().period {
+        Period::Daily(args) => (args, 1),
+        Period::Weekly(args) => (args, 7),
+    };
+
+    let calibration = match &args.calibration {
+        None => Calibration::default(),
+        Some(path) => {
+            let text = match std::fs::read_to_string(path) {
+                Ok(text) => text,
+                Err(error) => {
+                    error!("Unable to read the calibration {:?}: {}", path, error);
+                    process::exit(1);
+                }
+            };
+            match toml::from_str(&text) {
+                Ok(calibration) => calibration,
+                Err(error) => {
+                    error!("Invalid calibration {:?}: {}", path, error);
+                    process::exit(1);
+                }
+            }
+        }
+    };
+    let Some(decoder_config) = DecoderConfig::with_vesc_controller_ids(&args.vesc_ids) else {
+        Args::command()
+            .error(
+                ErrorKind::TooManyValues,
+                format!(
+                    "--vesc-id can be given at most {} times",
+                    MAX_VESC_CONTROLLERS
+                ),
+            )
+            .exit();
+    };
+    let decoder_config = decoder_config.with_calibration(calibration);
+
+    let mut frames = Vec::new();
+    for path in &args.log_files {
+        let log = match std::fs::read_to_string(path) {
+            Ok(log) => log,
+            Err(error) => {
+                error!("Unable to read {:?}: {}", path, error);
+                process::exit(1);
+            }
+        };
+        let before = frames.len();
+        frames.extend(
+            log.lines()
+                .filter_map(parse_candump_line)
+                .map(|(timestamp, _, frame)| (timestamp, frame)),
+        );
+        info!("Read {} frames of {:?}", frames.len() - before, path);
+    }
+    // The logs of the buses interleaved, stable so frames of the same time keep their order
+    frames.sort_by_key(|(timestamp, _)| *timestamp);
+
+    let Some(last_day) = args
+        .date
+        .or_else(|| frames.last().and_then(|(timestamp, _)| day_of(*timestamp)))
+    else {
+        error!("No frames in the logs");
+        process::exit(1);
+    };
+    let first_day = last_day - Days::new(days - 1);
+    let title = if days == 1 {
+        format!("Daily report {last_day}")
+    } else {
+        format!("Weekly report {first_day} to {last_day}")
+    };
+
+    let mut session = Session::new(decoder_config);
+    for (timestamp, frame) in &frames {
+        if day_of(*timestamp).is_some_and(|day| (first_day..=last_day).contains(&day)) {
+            session.ingest(*timestamp, frame);
+        }
+    }
+    if session.days().is_empty() {
+        warn!("No frames from {} to {}", first_day, last_day);
+    }
+
+    let screenshot = args.output.as_ref().and_then(|output| {
+        let path = output.with_extension("png");
+        match render_to_image(session.data()).save(&path) {
+            Ok(()) => path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned()),
+            Err(error) => {
+                warn!("Unable to save the display to {:?}: {}", path, error);
+                None
+            }
+        }
+    });
+    let report = Report {
+        title,
+        days: session.days(),
+        screenshot,
+    }
+    .render(args.format);
+
+    match &args.output {
+        Some(output) => {
+            if let Err(error) = std::fs::write(output, report) {
+                error!("Unable to write {:?}: {}", output, error);
+                process::exit(1);
+            }
+            info!("Wrote {:?}", output);
+        }
+        None => print!("{report}"),
+    }
+}
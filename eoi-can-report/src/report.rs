@@ -0,0 +1,397 @@
+//! The report of the summaries of the days, as tables in Markdown or HTML. Both formats are
+//! rendered from the same sections, so they always show the same figures.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate};
+use draw_display::ThrottleFault;
+
+use crate::summary::{SPEED_BIN_KMH, SPEED_BINS, Summary};
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Format {
+    Markdown,
+    Html,
+}
+
+pub struct Report<'a> {
+    pub title: String,
+    pub days: &'a BTreeMap<NaiveDate, Summary>,
+    /// Path of the image of the display at the end of the log, relative to the report
+    pub screenshot: Option<String>,
+}
+
+/// A table under a heading
+struct Section {
+    heading: &'static str,
+    header: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Report<'_> {
+    pub fn render(&self, format: Format) -> String {
+        let sections = self.sections();
+        match format {
+            Format::Markdown => self.markdown(&sections),
+            Format::Html => self.html(&sections),
+        }
+    }
+
+    fn sections(&self) -> Vec<Section> {
+        let mut total = Summary::default();
+        for summary in self.days.values() {
+            total.merge(summary);
+        }
+        let mut sections = vec![overview(&total)];
+
+        if self.days.len() > 1 {
+            sections.push(Section {
+                heading: "Days",
+                header: vec![
+                    "Day",
+                    "Distance",
+                    "Solar",
+                    "Motors",
+                    "Charged",
+                    "Discharged",
+                ],
+                rows: self
+                    .days
+                    .iter()
+                    .map(|(day, summary)| {
+                        vec![
+                            day.to_string(),
+                            format!("{:.1} km", summary.distance_km),
+                            watt_hours(summary.solar_energy_wh),
+                            watt_hours(summary.motor_energy_wh),
+                            watt_hours(summary.battery_charged_wh),
+                            watt_hours(summary.battery_discharged_wh),
+                        ]
+                    })
+                    .collect(),
+            });
+        }
+
+        if !total.panel_energy_wh.is_empty() {
+            sections.push(Section {
+                heading: "MPPT yield per panel",
+                header: vec!["Panel", "Yield"],
+                rows: total
+                    .panel_energy_wh
+                    .iter()
+                    .map(|(panel, energy_wh)| vec![panel.to_string(), watt_hours(*energy_wh)])
+                    .collect(),
+            });
+        }
+
+        let moving_s: f32 = total.speed_histogram_s.iter().sum();
+        if moving_s > 0.0 {
+            sections.push(Section {
+                heading: "Speed",
+                header: vec!["Speed", "Time", "Share"],
+                rows: total
+                    .speed_histogram_s
+                    .iter()
+                    .enumerate()
+                    .map(|(bin, time_s)| {
+                        let from = bin as f32 * SPEED_BIN_KMH;
+                        let speed = if bin == SPEED_BINS - 1 {
+                            format!("{from} km/h and more")
+                        } else {
+                            format!("{from} to {} km/h", from + SPEED_BIN_KMH)
+                        };
+                        vec![
+                            speed,
+                            time(Duration::from_secs_f32(*time_s)),
+                            format!("{:.0} %", time_s / moving_s * 100.0),
+                        ]
+                    })
+                    .collect(),
+            });
+        }
+
+        // Always, no faults is the evidence the safety inspections want too
+        sections.push(Section {
+            heading: "Throttle safety",
+            header: vec![
+                "Fault",
+                "Count",
+                "Under motor power",
+                "Total time",
+                "Longest",
+            ],
+            rows: [ThrottleFault::DeadmanMissing, ThrottleFault::ImpedanceHigh]
+                .into_iter()
+                .map(|fault| {
+                    let episodes = || {
+                        total
+                            .throttle_faults
+                            .iter()
+                            .filter(move |episode| episode.fault == fault)
+                    };
+                    let durations = || episodes().filter_map(|episode| episode.duration);
+                    vec![
+                        fault.to_string(),
+                        episodes().count().to_string(),
+                        episodes()
+                            .filter(|episode| episode.under_power)
+                            .count()
+                            .to_string(),
+                        time(durations().sum()),
+                        time(durations().max().unwrap_or_default()),
+                    ]
+                })
+                .collect(),
+        });
+        if !total.throttle_faults.is_empty() {
+            sections.push(Section {
+                heading: "Throttle faults",
+                header: vec!["Time", "Fault", "Duration", "Motors"],
+                rows: total
+                    .throttle_faults
+                    .iter()
+                    .map(|episode| {
+                        vec![
+                            timestamp(episode.at),
+                            episode.fault.to_string(),
+                            episode
+                                .duration
+                                .map_or("To the end of the log".to_string(), time),
+                            if episode.under_power {
+                                "Driving"
+                            } else {
+                                "Off"
+                            }
+                            .to_string(),
+                        ]
+                    })
+                    .collect(),
+            });
+        }
+
+        if !total.alarms.is_empty() {
+            sections.push(Section {
+                heading: "Alarms",
+                header: vec!["Time", "Alarm"],
+                rows: total
+                    .alarms
+                    .iter()
+                    .map(|raised| vec![timestamp(raised.at), raised.alarm.clone()])
+                    .collect(),
+            });
+        }
+
+        sections
+    }
+
+    fn markdown(&self, sections: &[Section]) -> String {
+        let mut markdown = format!("# {}\n", self.title);
+        for section in sections {
+            let _ = write!(
+                markdown,
+                "\n## {}\n\n| {} |\n|{}\n",
+                section.heading,
+                section.header.join(" | "),
+                "---|".repeat(section.header.len())
+            );
+            for row in &section.rows {
+                let _ = writeln!(markdown, "| {} |", row.join(" | "));
+            }
+        }
+        if let Some(screenshot) = &self.screenshot {
+            let _ = write!(
+                markdown,
+                "\n## Display\n\n![The main page at the end of the log]({screenshot})\n"
+            );
+        }
+        markdown
+    }
+
+    fn html(&self, sections: &[Section]) -> String {
+        let title = escape(&self.title);
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+             </head>\n<body>\n<h1>{title}</h1>\n"
+        );
+        for section in sections {
+            let _ = write!(html, "<h2>{}</h2>\n<table>\n<tr>", section.heading);
+            for cell in &section.header {
+                let _ = write!(html, "<th>{cell}</th>");
+            }
+            html.push_str("</tr>\n");
+            for row in &section.rows {
+                html.push_str("<tr>");
+                for cell in row {
+                    let _ = write!(html, "<td>{}</td>", escape(cell));
+                }
+                html.push_str("</tr>\n");
+            }
+            html.push_str("</table>\n");
+        }
+        if let Some(screenshot) = &self.screenshot {
+            let _ = writeln!(
+                html,
+                "<h2>Display</h2>\n<img src=\"{}\" alt=\"The main page at the end of the log\">",
+                escape(screenshot)
+            );
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
+
+fn overview(total: &Summary) -> Section {
+    let logged = match (total.first_frame, total.last_frame) {
+        (Some(first), Some(last)) => format!(
+            "{} to {}, {} frames",
+            timestamp(first),
+            timestamp(last),
+            total.frames
+        ),
+        _ => "No frames".to_string(),
+    };
+    let voltage = |voltage: Option<f32>| voltage.map_or("-".to_string(), |v| format!("{v:.3} V"));
+    let rows = [
+        ("Logged", logged),
+        ("Distance", format!("{:.1} km", total.distance_km)),
+        (
+            "Top speed",
+            total
+                .max_speed_kmh
+                .map_or("-".to_string(), |speed| format!("{speed:.1} km/h")),
+        ),
+        ("Solar energy in", watt_hours(total.solar_energy_wh)),
+        ("Battery charged", watt_hours(total.battery_charged_wh)),
+        (
+            "Battery discharged",
+            watt_hours(total.battery_discharged_wh),
+        ),
+        ("Motor energy out", watt_hours(total.motor_energy_wh)),
+        ("Lowest cell voltage", voltage(total.min_cell_voltage)),
+        ("Highest cell voltage", voltage(total.max_cell_voltage)),
+        ("Alarms raised", total.alarms.len().to_string()),
+        (
+            "Throttle faults",
+            format!(
+                "{} ({} under motor power)",
+                total.throttle_faults.len(),
+                total
+                    .throttle_faults
+                    .iter()
+                    .filter(|episode| episode.under_power)
+                    .count()
+            ),
+        ),
+    ];
+    Section {
+        heading: "Overview",
+        header: vec!["", ""],
+        rows: rows
+            .into_iter()
+            .map(|(figure, value)| vec![figure.to_string(), value])
+            .collect(),
+    }
+}
+
+fn watt_hours(energy_wh: f32) -> String {
+    format!("{energy_wh:.0} Wh")
+}
+
+/// Like `1 h 05 min`, `4 min 30 s` or `1.2 s`
+fn time(time: Duration) -> String {
+    let seconds = time.as_secs();
+    if seconds >= 3600 {
+        format!("{} h {:02} min", seconds / 3600, seconds % 3600 / 60)
+    } else if seconds >= 60 {
+        format!("{} min {:02} s", seconds / 60, seconds % 60)
+    } else {
+        format!("{:.1} s", time.as_secs_f32())
+    }
+}
+
+/// UTC time of a timestamp of the log
+fn timestamp(timestamp: Duration) -> String {
+    i64::try_from(timestamp.as_secs())
+        .ok()
+        .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+        .map_or_else(
+            || format!("{timestamp:?}"),
+            |time| time.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+        )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::summary::{RaisedAlarm, ThrottleFaultEpisode};
+    use assert2::assert;
+    use draw_display::PanelId;
+
+    #[test]
+    fn markdown_and_html() {
+        let day = Summary {
+            first_frame: Some(Duration::from_secs(1_748_764_800)),
+            last_frame: Some(Duration::from_secs(1_748_800_800)),
+            frames: 1000,
+            distance_km: 42.24,
+            solar_energy_wh: 1234.4,
+            min_cell_voltage: Some(3.3),
+            panel_energy_wh: BTreeMap::from([(PanelId::new(2, 1), 120.0)]),
+            speed_histogram_s: [0.0, 0.0, 0.0, 0.0, 0.0, 3900.0, 0.0, 1300.0],
+            alarms: vec![RaisedAlarm {
+                at: Duration::from_secs(1_748_775_600),
+                alarm: "Cell <3.0 V".to_string(),
+            }],
+            throttle_faults: vec![ThrottleFaultEpisode {
+                at: Duration::from_secs(1_748_775_660),
+                fault: ThrottleFault::DeadmanMissing,
+                under_power: true,
+                duration: Some(Duration::from_millis(1200)),
+            }],
+            ..Summary::default()
+        };
+        let days = BTreeMap::from([(NaiveDate::from_ymd_opt(2025, 6, 1).unwrap(), day)]);
+        let report = Report {
+            title: "Daily report 2025-06-01".to_string(),
+            days: &days,
+            screenshot: Some("report.png".to_string()),
+        };
+
+        let markdown = report.render(Format::Markdown);
+        for line in [
+            "# Daily report 2025-06-01",
+            "| Logged | 2025-06-01 08:00:00 UTC to 2025-06-01 18:00:00 UTC, 1000 frames |",
+            "| Distance | 42.2 km |",
+            "| Solar energy in | 1234 Wh |",
+            "| Lowest cell voltage | 3.300 V |",
+            "| Highest cell voltage | - |",
+            "| 2.1 | 120 Wh |",
+            "| 10 to 12 km/h | 1 h 05 min | 75 % |",
+            "| 14 km/h and more | 21 min 40 s | 25 % |",
+            "| 2025-06-01 11:00:00 UTC | Cell <3.0 V |",
+            "| Throttle faults | 1 (1 under motor power) |",
+            "| Deadman missing | 1 | 1 | 1.2 s | 1.2 s |",
+            "| Impedance high | 0 | 0 | 0.0 s | 0.0 s |",
+            "| 2025-06-01 11:01:00 UTC | Deadman missing | 1.2 s | Driving |",
+            "![The main page at the end of the log](report.png)",
+        ] {
+            assert!(markdown.lines().any(|other| other == line), "{line}");
+        }
+        // No table of the days for a single day
+        assert!(!markdown.contains("## Days"));
+
+        let html = report.render(Format::Html);
+        assert!(html.contains("<tr><td>Distance</td><td>42.2 km</td></tr>"));
+        assert!(html.contains("<td>Cell &lt;3.0 V</td>"));
+        assert!(html.contains("<img src=\"report.png\""));
+    }
+}
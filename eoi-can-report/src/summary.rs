@@ -0,0 +1,331 @@
+//! The figures of a report, taken by playing the frames of the logs through the data model of the
+//! display. The model takes its time from a virtual clock following the timestamps of the log, so
+//! values go stale in a gap of the log like they do on the boat and the gap adds nothing.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate};
+use draw_display::{
+    DisplayData, FaultStatistics, Instant, PanelId, SolarPanels, ThrottleFault, TripCounters,
+    VirtualClock,
+};
+use eoi_can_decoder::can_frame::CanFrame;
+use eoi_can_decoder::{DecoderConfig, parse_eoi_can_data_with_config};
+
+/// The figures are summed up in steps of this much time of the log
+const STEP: Duration = Duration::from_secs(1);
+/// Width of the bins of the speed histogram
+pub const SPEED_BIN_KMH: f32 = 2.0;
+/// Bins of the speed histogram, the last one takes every faster speed too
+pub const SPEED_BINS: usize = 8;
+
+/// An alarm the display data raised while the log was played
+#[derive(Debug, Clone, PartialEq)]
+pub struct RaisedAlarm {
+    /// Timestamp of the frame that raised it, since the Unix epoch
+    pub at: Duration,
+    pub alarm: String,
+}
+
+/// A deadman or impedance fault of the throttle, by the day it started
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThrottleFaultEpisode {
+    /// Timestamp of the status that started it, since the Unix epoch
+    pub at: Duration,
+    pub fault: ThrottleFault,
+    /// Whether the motors were driving when it started
+    pub under_power: bool,
+    /// `None` while it lasted to the end of the log
+    pub duration: Option<Duration>,
+}
+
+/// Figures of a day, or of several days by [`Summary::merge`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Summary {
+    /// Timestamp of the first frame, since the Unix epoch
+    pub first_frame: Option<Duration>,
+    /// Timestamp of the last frame, since the Unix epoch
+    pub last_frame: Option<Duration>,
+    pub frames: u64,
+    pub distance_km: f32,
+    pub max_speed_kmh: Option<f32>,
+    pub solar_energy_wh: f32,
+    /// Energy the motors took from the battery, regenerating lowers it
+    pub motor_energy_wh: f32,
+    pub battery_charged_wh: f32,
+    pub battery_discharged_wh: f32,
+    pub min_cell_voltage: Option<f32>,
+    pub max_cell_voltage: Option<f32>,
+    /// Yield of every panel that reported its power, in Wh
+    pub panel_energy_wh: BTreeMap<PanelId, f32>,
+    /// Time in every [`SPEED_BIN_KMH`] wide bin of the speed over ground, in seconds
+    pub speed_histogram_s: [f32; SPEED_BINS],
+    pub alarms: Vec<RaisedAlarm>,
+    pub throttle_faults: Vec<ThrottleFaultEpisode>,
+}
+
+impl Summary {
+    /// Add the figures of another day
+    pub fn merge(&mut self, other: &Summary) {
+        self.first_frame = extreme(self.first_frame, other.first_frame, Ord::min);
+        self.last_frame = extreme(self.last_frame, other.last_frame, Ord::max);
+        self.frames += other.frames;
+        self.distance_km += other.distance_km;
+        self.max_speed_kmh = extreme(self.max_speed_kmh, other.max_speed_kmh, f32::max);
+        self.solar_energy_wh += other.solar_energy_wh;
+        self.motor_energy_wh += other.motor_energy_wh;
+        self.battery_charged_wh += other.battery_charged_wh;
+        self.battery_discharged_wh += other.battery_discharged_wh;
+        self.min_cell_voltage = extreme(self.min_cell_voltage, other.min_cell_voltage, f32::min);
+        self.max_cell_voltage = extreme(self.max_cell_voltage, other.max_cell_voltage, f32::max);
+        for (panel, energy_wh) in &other.panel_energy_wh {
+            *self.panel_energy_wh.entry(*panel).or_default() += energy_wh;
+        }
+        for (time_s, other) in self
+            .speed_histogram_s
+            .iter_mut()
+            .zip(other.speed_histogram_s)
+        {
+            *time_s += other;
+        }
+        self.alarms.extend(other.alarms.iter().cloned());
+        self.throttle_faults
+            .extend(other.throttle_faults.iter().cloned());
+    }
+}
+
+fn extreme<T>(value: Option<T>, other: Option<T>, pick: impl Fn(T, T) -> T) -> Option<T> {
+    match (value, other) {
+        (Some(value), Some(other)) => Some(pick(value, other)),
+        (value, other) => value.or(other),
+    }
+}
+
+/// UTC day of a timestamp of the log
+pub fn day_of(timestamp: Duration) -> Option<NaiveDate> {
+    let seconds = i64::try_from(timestamp.as_secs()).ok()?;
+    DateTime::from_timestamp(seconds, timestamp.subsec_nanos()).map(|time| time.date_naive())
+}
+
+/// Frames of the logs in, a [`Summary`] per UTC day out
+pub struct Session {
+    data: DisplayData,
+    config: DecoderConfig,
+    /// Stands at the first frame when the session starts
+    clock: VirtualClock,
+    seed: Instant,
+    first_frame: Option<Duration>,
+    last_step: Option<Duration>,
+    alarms_recorded: u32,
+    /// Of the display data after the last frame, to tell the faults that started or ended
+    throttle_faults: [FaultStatistics; 2],
+    /// Day and index of the faults in progress
+    open_throttle_faults: [Option<(NaiveDate, usize)>; 2],
+    days: BTreeMap<NaiveDate, Summary>,
+}
+
+impl Session {
+    /// Installs the virtual clock on the thread, so it has to be made before any display data
+    pub fn new(config: DecoderConfig) -> Self {
+        let seed = Instant::now();
+        let clock = VirtualClock::starting_at(seed);
+        clock.install();
+        let data = DisplayData {
+            mppt_panel_info: SolarPanels::observed(),
+            ..DisplayData::default()
+        };
+        Self {
+            data,
+            config,
+            clock,
+            seed,
+            first_frame: None,
+            last_step: None,
+            alarms_recorded: 0,
+            throttle_faults: [FaultStatistics::default(); 2],
+            open_throttle_faults: [None; 2],
+            days: BTreeMap::new(),
+        }
+    }
+
+    /// A frame of the logs with its timestamp since the Unix epoch, in the order of the timestamps
+    pub fn ingest(&mut self, timestamp: Duration, frame: &CanFrame) {
+        let Some(day) = day_of(timestamp) else {
+            return;
+        };
+        let first_frame = *self.first_frame.get_or_insert(timestamp);
+        self.clock.set(
+            self.seed
+                + timestamp
+                    .saturating_sub(first_frame)
+                    .try_into()
+                    .unwrap_or_default(),
+        );
+
+        // Before the frame, so the values from before a gap of the log are stale by now
+        let last_step = *self.last_step.get_or_insert(timestamp);
+        let elapsed = timestamp.saturating_sub(last_step);
+        if elapsed >= STEP {
+            self.step(day, elapsed);
+            self.last_step = Some(timestamp);
+        }
+
+        if let Some(data) = parse_eoi_can_data_with_config(frame, &self.config) {
+            self.data.ingest_eoi_can_data(data);
+        }
+
+        let summary = self.days.entry(day).or_default();
+        summary.frames += 1;
+        summary.first_frame.get_or_insert(timestamp);
+        summary.last_frame = Some(timestamp);
+        for event in self.data.alarm_history.since(self.alarms_recorded) {
+            if event.raised {
+                summary.alarms.push(RaisedAlarm {
+                    at: timestamp,
+                    alarm: event.alarm.to_string(),
+                });
+            }
+        }
+        self.alarms_recorded = self.data.alarm_history.recorded();
+        self.track_throttle_faults(day, timestamp);
+    }
+
+    fn track_throttle_faults(&mut self, day: NaiveDate, timestamp: Duration) {
+        for (index, (fault, statistics)) in self.data.throttle_faults.iter().enumerate() {
+            let last = &mut self.throttle_faults[index];
+            let open = &mut self.open_throttle_faults[index];
+            if last.is_active()
+                && !statistics.is_active()
+                && let Some((day, episode)) = open.take()
+                && let Some(summary) = self.days.get_mut(&day)
+            {
+                let ended = statistics.total - last.total;
+                summary.throttle_faults[episode].duration =
+                    Some(Duration::from_micros(ended.as_micros()));
+            }
+            if statistics.count > last.count {
+                let summary = self.days.entry(day).or_default();
+                *open = Some((day, summary.throttle_faults.len()));
+                summary.throttle_faults.push(ThrottleFaultEpisode {
+                    at: timestamp,
+                    fault,
+                    under_power: statistics.under_power > last.under_power,
+                    duration: None,
+                });
+            }
+            *last = *statistics;
+        }
+    }
+
+    fn step(&mut self, day: NaiveDate, elapsed: Duration) {
+        let summary = self.days.entry(day).or_default();
+        let hours = elapsed.as_secs_f32() / 3600.0;
+
+        let mut trip = TripCounters::default();
+        trip.update(&self.data, elapsed.try_into().unwrap_or_default());
+        summary.distance_km += trip.distance_km;
+        summary.motor_energy_wh += trip.motor_energy_wh;
+        summary.solar_energy_wh += trip.solar_energy_wh;
+
+        // NaN while it isn't known, which adds to neither
+        let battery_power = self.data.battery_net_power();
+        if battery_power > 0.0 {
+            summary.battery_charged_wh += battery_power * hours;
+        } else if battery_power < 0.0 {
+            summary.battery_discharged_wh -= battery_power * hours;
+        }
+
+        for (panel, info) in self.data.mppt_panel_info.iter() {
+            if let Some((power, _, _)) = info.get() {
+                *summary.panel_energy_wh.entry(panel).or_default() += power * hours;
+            }
+        }
+
+        for voltage in self
+            .data
+            .battery_cell_voltages
+            .iter()
+            .filter_map(|cell| cell.get())
+        {
+            summary.min_cell_voltage = extreme(summary.min_cell_voltage, Some(*voltage), f32::min);
+            summary.max_cell_voltage = extreme(summary.max_cell_voltage, Some(*voltage), f32::max);
+        }
+
+        if let Some(speed_kmh) = self.data.speed_kmh.get() {
+            let bin = (speed_kmh.max(0.0) / SPEED_BIN_KMH) as usize;
+            summary.speed_histogram_s[bin.min(SPEED_BINS - 1)] += elapsed.as_secs_f32();
+            summary.max_speed_kmh = extreme(summary.max_speed_kmh, Some(*speed_kmh), f32::max);
+        }
+    }
+
+    /// The data model as it stands after the last frame, for the screenshot of the report
+    pub fn data(&self) -> &DisplayData {
+        &self.data
+    }
+
+    pub fn days(&self) -> &BTreeMap<NaiveDate, Summary> {
+        &self.days
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use eoi_can_decoder::frame;
+
+    #[test]
+    fn sums_up_the_days() {
+        let mut session = Session::new(DecoderConfig::default());
+        // 2025-06-01 23:59:00 UTC, 10 km/h for two minutes over midnight
+        let start = Duration::from_secs(1_748_822_340);
+        let speed = 10.0_f32.to_le_bytes();
+        let speed_frame = frame!(0x201, [speed[0], speed[1], speed[2], speed[3], 0, 0, 0, 0]);
+        // 3.3 V, 3.4 V, 3.5 V and 3.6 V
+        let cells = frame!(0x103, [0xE4, 0x0C, 0x48, 0x0D, 0xAC, 0x0D, 0x10, 0x0E]);
+        // The deadman missing for two seconds, and again at the end of the log
+        let throttle = |errors| frame!(0x337, [0, 0, 0, 0, 0, 0, 0, errors]);
+        for tenth in 0..1200 {
+            let timestamp = start + Duration::from_millis(tenth * 100);
+            session.ingest(timestamp, &speed_frame);
+            session.ingest(timestamp, &cells);
+            if (300..=320).contains(&tenth) {
+                session.ingest(timestamp, &throttle(if tenth < 320 { 0x40 } else { 0 }));
+            }
+        }
+        // An hour later, the values of before the gap are stale
+        session.ingest(start + Duration::from_secs(3720), &speed_frame);
+        session.ingest(start + Duration::from_secs(3720), &throttle(0x40));
+
+        let days = session.days();
+        let first = &days[&NaiveDate::from_ymd_opt(2025, 6, 1).unwrap()];
+        let second = &days[&NaiveDate::from_ymd_opt(2025, 6, 2).unwrap()];
+        assert!(first.frames == 1221);
+        assert!(second.frames == 1202);
+        assert!(first.speed_histogram_s[5] == 59.0);
+        assert!(second.speed_histogram_s[5] == 60.0);
+        assert!(first.max_speed_kmh == Some(10.0));
+        assert!(first.min_cell_voltage == Some(3.3));
+        assert!(first.max_cell_voltage == Some(3.6));
+        assert!(
+            first.throttle_faults
+                == [ThrottleFaultEpisode {
+                    at: start + Duration::from_secs(30),
+                    fault: ThrottleFault::DeadmanMissing,
+                    under_power: false,
+                    duration: Some(Duration::from_secs(2)),
+                }]
+        );
+        assert!(second.throttle_faults[0].duration.is_none());
+
+        let mut total = Summary::default();
+        for summary in days.values() {
+            total.merge(summary);
+        }
+        assert!(total.first_frame == Some(start));
+        assert!(total.last_frame == Some(start + Duration::from_secs(3720)));
+        assert!((total.distance_km - 119.0 / 360.0).abs() < 0.001);
+        assert!(total.speed_histogram_s.iter().sum::<f32>() == 119.0);
+    }
+}
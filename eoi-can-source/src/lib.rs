@@ -0,0 +1,688 @@
+//! Frames of a SocketCAN interface, read in their own task. The socket is opened again when the
+//! interface goes down or doesn't exist yet, so a binary started before the CAN adapter is
+//! plugged in still gets its frames. With a bitrate the reader also brings the interface up
+//! again, like after the USB adapter re-enumerated.
+//!
+//! The other way round, [`CanTransmitter`] writes frames by priority and [`PeriodicSender`]
+//! queues frames on a schedule. [`LogFilter`] is the tracing filter of the binaries, changeable
+//! while they run. [`VirtualCan`] creates a virtual interface for working without the boat.
+//! [`install_panic_hook`] keeps the panics of a binary until they are published.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+
+use embedded_can::Frame;
+use eoi_can_decoder::can_collector::CanCollector;
+use eoi_can_decoder::can_frame::CanFrame;
+use serde::Serialize;
+use socketcan::SocketOptions;
+use socketcan::errors::{CanError, ViolationType};
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+mod log_filter;
+mod panic_report;
+mod periodic;
+mod receive_time;
+mod transmit;
+mod vcan;
+
+pub use log_filter::{LogFilter, LogFilterCommand};
+pub use panic_report::{
+    DEFAULT_PANIC_DIR, PanicReport, PendingPanic, install_panic_hook, panic_dir, pending_panics,
+};
+pub use periodic::PeriodicSender;
+pub use transmit::CanTransmitter;
+pub use vcan::VirtualCan;
+
+/// Frames waiting for the receiver, the kernel buffers more when this is full
+const FRAME_BUFFER_SIZE: usize = 256;
+/// How long to wait before opening the socket again the first time, doubled for every restart
+/// without a frame in between
+const DEFAULT_MIN_BACKOFF: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// Frames received by [`spawn_can_reader`], also usable as a [`futures_core::Stream`]
+#[derive(Debug)]
+pub struct CanFrames {
+    receiver: mpsc::Receiver<(CanFrame, SystemTime)>,
+    states: InterfaceStates,
+}
+
+impl CanFrames {
+    /// Wait for the next frame, `None` when the reader stopped
+    pub async fn recv(&mut self) -> Option<CanFrame> {
+        Some(self.recv_timestamped().await?.0)
+    }
+
+    /// Wait for the next frame and the time the kernel received it, or the time it was read when
+    /// the kernel didn't stamp it
+    pub async fn recv_timestamped(&mut self) -> Option<(CanFrame, SystemTime)> {
+        self.receiver.recv().await
+    }
+
+    /// Follows the state of the interfaces, also after the frames moved into another task
+    pub fn states(&self) -> InterfaceStates {
+        self.states.clone()
+    }
+}
+
+impl futures_core::Stream for CanFrames {
+    type Item = CanFrame;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<CanFrame>> {
+        self.receiver
+            .poll_recv(cx)
+            .map(|received| received.map(|(frame, _)| frame))
+    }
+}
+
+/// Read the data frames of `interface` in a new task, which stops when [`CanFrames`] is dropped
+pub fn spawn_can_reader(interface: &str) -> (JoinHandle<()>, CanFrames) {
+    CanReader::new(interface).spawn()
+}
+
+/// Read several interfaces into one [`CanFrames`], the frames are tagged with the index of their
+/// interface as bus
+pub fn spawn_can_readers(
+    readers: impl IntoIterator<Item = CanReader>,
+) -> (Vec<JoinHandle<()>>, CanFrames) {
+    let (sender, receiver) = mpsc::channel(FRAME_BUFFER_SIZE);
+    let mut states = Vec::new();
+    let handles = readers
+        .into_iter()
+        .zip(0..=u8::MAX)
+        .map(|(reader, bus)| {
+            let (state_sender, state) = watch::channel(InterfaceState::Connecting);
+            let (errors_sender, errors) = watch::channel(ErrorCounts::default());
+            states.push(Interface {
+                name: reader.interface.clone(),
+                state,
+                errors,
+                error_frames_seen: 0,
+            });
+            tokio::spawn(CanReader { bus, ..reader }.run(
+                sender.clone(),
+                state_sender,
+                errors_sender,
+            ))
+        })
+        .collect();
+    let frames = CanFrames {
+        receiver,
+        states: InterfaceStates(states),
+    };
+    (handles, frames)
+}
+
+/// Whether frames are received from the interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum InterfaceState {
+    /// Opened, but no frame received yet
+    Connecting,
+    Up,
+    /// Down or missing, like when the USB adapter is re-enumerated
+    Down,
+    /// The reader stopped after the retries of its [`RestartPolicy`]
+    Failed,
+}
+
+/// Errors of an interface since its reader started
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ErrorCounts {
+    /// Failed reads, each one restarts the socket
+    pub read_errors: u64,
+    /// Error frames of the CAN controller, like for bus errors
+    pub error_frames: u64,
+    /// Times the socket was opened again
+    pub restarts: u64,
+    /// Remote frames, nothing on the boat asks for data this way
+    pub remote_frames: u64,
+    /// Error frames by [`BusFault`]
+    pub no_ack: u64,
+    pub stuff_errors: u64,
+    pub protocol_errors: u64,
+    pub controller_problems: u64,
+    pub bus_off: u64,
+    pub controller_restarts: u64,
+    pub other_errors: u64,
+    /// Fault of the latest error frame
+    pub last_fault: Option<BusFault>,
+}
+
+impl ErrorCounts {
+    fn count_fault(&mut self, fault: BusFault) {
+        self.error_frames += 1;
+        *match fault {
+            BusFault::NoAck => &mut self.no_ack,
+            BusFault::Stuff => &mut self.stuff_errors,
+            BusFault::Protocol => &mut self.protocol_errors,
+            BusFault::Controller => &mut self.controller_problems,
+            BusFault::BusOff => &mut self.bus_off,
+            BusFault::Restarted => &mut self.controller_restarts,
+            BusFault::Other => &mut self.other_errors,
+        } += 1;
+        self.last_fault = Some(fault);
+    }
+
+    /// Count `frame` if it is an error or remote frame, `false` for data frames
+    fn count_frame(&mut self, frame: &socketcan::CanFrame) -> bool {
+        match frame {
+            socketcan::CanFrame::Error(error_frame) => {
+                self.count_fault(BusFault::from(&CanError::from(*error_frame)));
+                true
+            }
+            socketcan::CanFrame::Remote(_) => {
+                self.remote_frames += 1;
+                true
+            }
+            socketcan::CanFrame::Data(_) => false,
+        }
+    }
+}
+
+/// What an error frame of the CAN controller reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum BusFault {
+    /// Nobody acknowledged a sent frame, like when the only other node is off or the bus isn't
+    /// terminated
+    NoAck,
+    /// Bit stuffing violated, often a wrong bitrate or noise on the bus
+    Stuff,
+    /// Other protocol violations, like form or bit errors
+    Protocol,
+    /// Buffer overflows and the error counters of the controller reaching warning or passive
+    Controller,
+    /// The controller went off the bus after too many errors
+    BusOff,
+    /// The controller came back after being off the bus
+    Restarted,
+    Other,
+}
+
+impl BusFault {
+    /// Short description for the display
+    pub fn label(self) -> &'static str {
+        match self {
+            BusFault::NoAck => "no ACK",
+            BusFault::Stuff => "stuff error",
+            BusFault::Protocol => "protocol error",
+            BusFault::Controller => "controller problem",
+            BusFault::BusOff => "bus off",
+            BusFault::Restarted => "controller restarted",
+            BusFault::Other => "bus error",
+        }
+    }
+}
+
+impl From<&CanError> for BusFault {
+    fn from(error: &CanError) -> Self {
+        match error {
+            CanError::NoAck => BusFault::NoAck,
+            CanError::ProtocolViolation {
+                vtype: ViolationType::BitStuffingError,
+                ..
+            } => BusFault::Stuff,
+            CanError::ProtocolViolation { .. } => BusFault::Protocol,
+            CanError::ControllerProblem(_) => BusFault::Controller,
+            CanError::BusOff => BusFault::BusOff,
+            CanError::Restarted => BusFault::Restarted,
+            _ => BusFault::Other,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Interface {
+    name: String,
+    state: watch::Receiver<InterfaceState>,
+    errors: watch::Receiver<ErrorCounts>,
+    /// Error frames already reported by [`InterfaceStates::new_fault`]
+    error_frames_seen: u64,
+}
+
+/// States of the interfaces read into a [`CanFrames`]
+#[derive(Debug, Clone)]
+pub struct InterfaceStates(Vec<Interface>);
+
+impl InterfaceStates {
+    pub fn any_down(&self) -> bool {
+        self.iter()
+            .any(|(_, state)| matches!(state, InterfaceState::Down | InterfaceState::Failed))
+    }
+
+    /// Name and state of every interface
+    pub fn iter(&self) -> impl Iterator<Item = (&str, InterfaceState)> {
+        self.0
+            .iter()
+            .map(|interface| (interface.name.as_str(), *interface.state.borrow()))
+    }
+
+    /// Name and errors of every interface
+    pub fn error_counts(&self) -> impl Iterator<Item = (&str, ErrorCounts)> {
+        self.0
+            .iter()
+            .map(|interface| (interface.name.as_str(), *interface.errors.borrow()))
+    }
+
+    /// Fault of the latest error frame of any interface since the last call, `None` without new
+    /// error frames
+    pub fn new_fault(&mut self) -> Option<BusFault> {
+        let mut fault = None;
+        for interface in &mut self.0 {
+            let errors = *interface.errors.borrow();
+            if errors.error_frames > interface.error_frames_seen {
+                interface.error_frames_seen = errors.error_frames;
+                fault = errors.last_fault.or(fault);
+            }
+        }
+        fault
+    }
+}
+
+/// How often and how quickly a reader opens its socket again after an error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestartPolicy {
+    /// Restarts in a row without a frame in between before the reader stops, `None` never stops
+    pub max_retries: Option<u32>,
+    /// Wait before the first restart, doubled for every further one
+    pub min_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: None,
+            min_backoff: DEFAULT_MIN_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Wait before restart `retry`, counted from 0 since the last frame
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.min_backoff
+            .saturating_mul(2_u32.saturating_pow(retry))
+            .min(self.max_backoff)
+    }
+
+    /// Whether to stop instead of restart `retry`
+    pub fn gives_up(&self, retry: u32) -> bool {
+        self.max_retries
+            .is_some_and(|max_retries| retry >= max_retries)
+    }
+}
+
+/// Reader of a CAN interface, for when [`spawn_can_reader`] isn't enough
+#[derive(Debug, Clone)]
+pub struct CanReader {
+    interface: String,
+    bitrate: Option<u32>,
+    listen_only: bool,
+    restart_policy: RestartPolicy,
+    bus: u8,
+}
+
+impl CanReader {
+    pub fn new(interface: &str) -> Self {
+        Self {
+            interface: interface.to_string(),
+            bitrate: None,
+            listen_only: false,
+            restart_policy: RestartPolicy::default(),
+            bus: 0,
+        }
+    }
+
+    /// Bring the interface up with `bitrate` when it is down, with
+    /// `ip link set <interface> up type can bitrate <bitrate>`, which needs `CAP_NET_ADMIN`
+    pub fn with_bring_up(self, bitrate: u32) -> Self {
+        Self {
+            bitrate: Some(bitrate),
+            ..self
+        }
+    }
+
+    /// Bring the interface up in listen-only mode, so its controller doesn't even acknowledge
+    /// frames. Only with [`with_bring_up`](Self::with_bring_up), an interface that is already up
+    /// keeps its mode
+    pub fn with_listen_only(self, listen_only: bool) -> Self {
+        Self {
+            listen_only,
+            ..self
+        }
+    }
+
+    /// Open the socket again after errors like `restart_policy` says, by default forever
+    pub fn with_restart_policy(self, restart_policy: RestartPolicy) -> Self {
+        Self {
+            restart_policy,
+            ..self
+        }
+    }
+
+    /// Read the data frames in a new task, which stops when [`CanFrames`] is dropped or the
+    /// [`RestartPolicy`] gives up
+    pub fn spawn(self) -> (JoinHandle<()>, CanFrames) {
+        let (mut handles, frames) = spawn_can_readers([self]);
+        (handles.remove(0), frames)
+    }
+
+    async fn run(
+        self,
+        sender: mpsc::Sender<(CanFrame, SystemTime)>,
+        state: watch::Sender<InterfaceState>,
+        errors: watch::Sender<ErrorCounts>,
+    ) {
+        let interface = self.interface.as_str();
+        // Restarts since the last frame
+        let mut retry = 0;
+        loop {
+            match socketcan::tokio::CanSocket::open(interface) {
+                Ok(can_sock) => {
+                    // Stays down until a frame is received, an interface that is down opens fine
+                    debug!(interface, "Opened CAN interface");
+                    if let Err(error) = can_sock.set_error_filter_accept_all() {
+                        warn!(interface, %error, "Unable to receive CAN error frames");
+                    }
+                    if let Err(error) = receive_time::enable_receive_time(&can_sock) {
+                        warn!(interface, %error, "Unable to get the receive time of CAN frames");
+                    }
+                    loop {
+                        let read = if *state.borrow() == InterfaceState::Up {
+                            can_sock.read_frame().await
+                        } else {
+                            // A socket opened on an interface that is down waits forever instead
+                            // of failing, so the interface is checked again after every backoff
+                            let backoff = self.restart_policy.backoff(retry);
+                            match tokio::time::timeout(backoff, can_sock.read_frame()).await {
+                                Ok(read) => read,
+                                // Up but silent, keep waiting
+                                Err(_) if is_interface_up(interface) => continue,
+                                Err(_) => {
+                                    if state.send_replace(InterfaceState::Down)
+                                        != InterfaceState::Down
+                                    {
+                                        warn!(interface, "CAN interface is down");
+                                    }
+                                    break;
+                                }
+                            }
+                        };
+                        let frame = match read {
+                            Ok(frame) => frame,
+                            // The socket has to be opened again either way
+                            Err(error) => {
+                                errors.send_modify(|errors| errors.read_errors += 1);
+                                if !is_down(&error) {
+                                    warn!(interface, %error, "Failed to read from CAN interface");
+                                } else if state.send_replace(InterfaceState::Down)
+                                    != InterfaceState::Down
+                                {
+                                    warn!(interface, %error, "CAN interface is down");
+                                }
+                                break;
+                            }
+                        };
+                        retry = 0;
+                        if state.send_replace(InterfaceState::Up) != InterfaceState::Up {
+                            info!(interface, "Receiving frames from CAN interface");
+                        }
+                        if errors.send_if_modified(|errors| errors.count_frame(&frame)) {
+                            debug!(interface, ?frame, "Received error or remote frame");
+                            continue;
+                        }
+                        let Some(frame) = to_can_frame(&frame) else {
+                            continue;
+                        };
+                        let received = receive_time::last_receive_time(&can_sock)
+                            .unwrap_or_else(SystemTime::now);
+                        if sender
+                            .send((frame.with_bus(self.bus), received))
+                            .await
+                            .is_err()
+                        {
+                            debug!("CAN frames aren't received anymore, stopping the reader");
+                            return;
+                        }
+                    }
+                }
+                Err(error) => {
+                    if state.send_replace(InterfaceState::Down) != InterfaceState::Down {
+                        warn!(interface, %error, "Unable to open CAN interface");
+                    }
+                }
+            }
+
+            if self.restart_policy.gives_up(retry) {
+                error!(interface, retries = retry, "Giving up on CAN interface");
+                state.send_replace(InterfaceState::Failed);
+                return;
+            }
+            if *state.borrow() == InterfaceState::Down
+                && let Some(bitrate) = self.bitrate
+            {
+                bring_up(interface, bitrate, self.listen_only).await;
+            }
+            let backoff = self.restart_policy.backoff(retry);
+            debug!(interface, retry, ?backoff, "Restarting CAN interface");
+            tokio::time::sleep(backoff).await;
+            retry += 1;
+            errors.send_modify(|errors| errors.restarts += 1);
+        }
+    }
+}
+
+/// Errors of an interface that is down (`ENETDOWN`) or gone (`ENODEV`)
+fn is_down(error: &io::Error) -> bool {
+    matches!(error.raw_os_error(), Some(libc::ENETDOWN | libc::ENODEV))
+}
+
+/// Whether `interface` exists and is up, by the flags in sysfs
+fn is_interface_up(interface: &str) -> bool {
+    std::fs::read_to_string(format!("/sys/class/net/{}/flags", interface))
+        .ok()
+        .and_then(|flags| u32::from_str_radix(flags.trim().trim_start_matches("0x"), 16).ok())
+        .is_some_and(|flags| flags & libc::IFF_UP as u32 != 0)
+}
+
+/// Arguments of `ip` bringing `interface` up
+fn bring_up_args(interface: &str, bitrate: u32, listen_only: bool) -> Vec<String> {
+    let mut args = ["link", "set", interface, "up", "type", "can", "bitrate"]
+        .map(String::from)
+        .to_vec();
+    args.push(bitrate.to_string());
+    if listen_only {
+        args.extend(["listen-only", "on"].map(String::from));
+    }
+    args
+}
+
+async fn bring_up(interface: &str, bitrate: u32, listen_only: bool) {
+    let result = tokio::process::Command::new("ip")
+        .args(bring_up_args(interface, bitrate, listen_only))
+        .output()
+        .await;
+    match result {
+        Ok(output) if output.status.success() => {
+            info!(
+                "Brought CAN interface {} up at {} bit/s",
+                interface, bitrate
+            );
+        }
+        // The adapter may not be back yet, it is tried again
+        Ok(output) => debug!(
+            "Unable to bring CAN interface {} up: {}",
+            interface,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+        Err(error) => warn!(
+            "Unable to run ip to bring CAN interface {} up: {:?}",
+            interface, error
+        ),
+    }
+}
+
+/// Log every ID `collector` found as standard and as extended frame since the last call, once
+pub fn log_new_id_collisions(collector: &mut CanCollector) {
+    while let Some(id) = collector.take_new_id_collision() {
+        error!(
+            "CAN ID {:#x} seen as standard and as extended frame, its frames may be decoded as the wrong message",
+            id
+        );
+    }
+}
+
+/// Value parser for the intervals of the binaries given in seconds, like `0.2`, more than 0
+pub fn parse_seconds(seconds: &str) -> Result<Duration, String> {
+    let seconds: f64 = seconds.parse().map_err(|error| format!("{}", error))?;
+    if seconds > 0.0 && seconds.is_finite() {
+        Ok(Duration::from_secs_f64(seconds))
+    } else {
+        Err("has to be more than 0 seconds".to_string())
+    }
+}
+
+/// The data frame, or `None` for remote and error frames and data that doesn't fit
+fn to_can_frame(frame: &socketcan::CanFrame) -> Option<CanFrame> {
+    let socketcan::CanFrame::Data(frame) = frame else {
+        debug!("Received non-data CAN frame: {:?}", frame);
+        return None;
+    };
+    trace!(
+        "Received CAN frame: ID: {:?}, Data: {:?}",
+        frame.id(),
+        frame.data()
+    );
+    CanFrame::try_new(frame.id(), frame.data())
+        .inspect_err(|error| warn!("Dropping CAN frame {:?}: {}", frame.id(), error))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use embedded_can::StandardId;
+
+    #[test]
+    fn intervals_in_seconds() {
+        assert!(parse_seconds("0.2") == Ok(Duration::from_millis(200)));
+        assert!(parse_seconds("2") == Ok(Duration::from_secs(2)));
+        assert!(parse_seconds("0").is_err());
+        assert!(parse_seconds("-1").is_err());
+        assert!(parse_seconds("inf").is_err());
+        assert!(parse_seconds("NaN").is_err());
+        assert!(parse_seconds("a second").is_err());
+    }
+
+    #[test]
+    fn only_data_frames() {
+        let id = StandardId::new(0x123).unwrap();
+        let frame = socketcan::CanFrame::new(id, &[1, 2, 3]).unwrap();
+        assert!(to_can_frame(&frame) == Some(CanFrame::from_array(id.into(), [1, 2, 3])));
+
+        if let Some(remote) = socketcan::CanFrame::new_remote(id, 3) {
+            assert!(to_can_frame(&remote).is_none());
+        }
+    }
+
+    #[test]
+    fn bring_up_in_listen_only_mode() {
+        assert!(
+            bring_up_args("can0", 500_000, false).join(" ")
+                == "link set can0 up type can bitrate 500000"
+        );
+        assert!(
+            bring_up_args("can0", 500_000, true).join(" ")
+                == "link set can0 up type can bitrate 500000 listen-only on"
+        );
+    }
+
+    #[test]
+    fn restart_backoff() {
+        let policy = RestartPolicy {
+            max_retries: Some(3),
+            ..RestartPolicy::default()
+        };
+        assert!(policy.backoff(0) == Duration::from_secs(1));
+        assert!(policy.backoff(2) == Duration::from_secs(4));
+        assert!(policy.backoff(40) == Duration::from_secs(10));
+        assert!(!policy.gives_up(2));
+        assert!(policy.gives_up(3));
+        assert!(!RestartPolicy::default().gives_up(u32::MAX));
+    }
+
+    #[test]
+    fn bus_faults() {
+        let mut counts = ErrorCounts::default();
+        for error in [
+            CanError::NoAck,
+            CanError::ProtocolViolation {
+                vtype: ViolationType::BitStuffingError,
+                location: socketcan::errors::Location::Unspecified,
+            },
+            CanError::ProtocolViolation {
+                vtype: ViolationType::FrameFormatError,
+                location: socketcan::errors::Location::Unspecified,
+            },
+            CanError::Restarted,
+            CanError::Restarted,
+        ] {
+            counts.count_fault(BusFault::from(&error));
+        }
+        assert!(counts.error_frames == 5);
+        assert!(counts.no_ack == 1);
+        assert!(counts.stuff_errors == 1);
+        assert!(counts.protocol_errors == 1);
+        assert!(counts.controller_restarts == 2);
+        assert!(counts.last_fault == Some(BusFault::Restarted));
+
+        let (sender, errors) = watch::channel(ErrorCounts::default());
+        let (_, state) = watch::channel(InterfaceState::Up);
+        let mut states = InterfaceStates(vec![Interface {
+            name: "can0".to_string(),
+            state,
+            errors,
+            error_frames_seen: 0,
+        }]);
+        assert!(states.new_fault().is_none());
+        sender.send_modify(|errors| errors.count_fault(BusFault::BusOff));
+        assert!(states.new_fault() == Some(BusFault::BusOff));
+        // Reported once
+        assert!(states.new_fault().is_none());
+    }
+
+    #[test]
+    fn error_frames_are_counted() {
+        let id = StandardId::new(0x123).unwrap();
+        let mut counts = ErrorCounts::default();
+        assert!(!counts.count_frame(&socketcan::CanFrame::new(id, &[1, 2, 3]).unwrap()));
+        let error_frame = socketcan::CanErrorFrame::from(CanError::BusOff);
+        assert!(counts.count_frame(&socketcan::CanFrame::from(error_frame)));
+        assert!(counts.error_frames == 1);
+        assert!(counts.bus_off == 1);
+        assert!(counts.last_fault == Some(BusFault::BusOff));
+
+        if let Some(remote) = socketcan::CanFrame::new_remote(id, 3) {
+            assert!(counts.count_frame(&remote));
+            assert!(counts.remote_frames == 1);
+        }
+        assert!(counts.error_frames == 1);
+    }
+
+    #[test]
+    fn down_interfaces() {
+        assert!(is_down(&io::Error::from_raw_os_error(libc::ENETDOWN)));
+        assert!(is_down(&io::Error::from_raw_os_error(libc::ENODEV)));
+        assert!(!is_down(&io::Error::from_raw_os_error(libc::ENOBUFS)));
+        assert!(is_interface_up("lo"));
+        assert!(!is_interface_up("no-such-can"));
+    }
+}
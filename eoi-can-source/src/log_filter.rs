@@ -0,0 +1,181 @@
+//! The tracing filter of a binary, changeable while it runs. Restarting a process mid-race to
+//! change `RUST_LOG` loses data, so the datalogger and the displays take a new filter over MQTT
+//! or HTTP instead, optionally only for a while like `eoi_can_decoder=trace` for 5 minutes.
+
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use serde::Deserialize;
+use tracing::level_filters::LevelFilter;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Registry, reload};
+
+/// Payload of the `log-filter` command, like `{"filter": "eoi_can_decoder=trace", "duration_s":
+/// 300}`. Without a filter the one of the start is restored, without a duration the filter stays
+/// until the next change
+#[derive(Debug, Default, Deserialize)]
+pub struct LogFilterCommand {
+    #[serde(default)]
+    pub filter: Option<String>,
+    #[serde(default)]
+    pub duration_s: Option<f64>,
+}
+
+/// Handle to the filter of the tracing subscriber, cheap to clone
+#[derive(Debug, Clone)]
+pub struct LogFilter {
+    handle: reload::Handle<EnvFilter, Registry>,
+    /// Level of the targets a filter doesn't mention
+    default_level: LevelFilter,
+    /// Filter of the start from `RUST_LOG`, restored after a temporary one
+    initial: String,
+    /// Counts the changes, so the end of an old temporary filter doesn't undo a newer one
+    changes: Arc<AtomicU64>,
+}
+
+impl LogFilter {
+    /// Register the tracing subscriber of every binary, logging with the file and line to stdout,
+    /// filtered by `RUST_LOG` with `default_level` for what it doesn't mention
+    pub fn init(default_level: LevelFilter) -> Self {
+        Self::init_with_writer(default_level, io::stdout)
+    }
+
+    /// Like [`Self::init`], logging to `writer`, like stderr for binaries writing their output
+    /// to stdout
+    pub fn init_with_writer<W>(default_level: LevelFilter, writer: W) -> Self
+    where
+        W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+    {
+        let filter = EnvFilter::builder()
+            .with_default_directive(default_level.into())
+            .from_env_lossy();
+        let (log_filter, layer) = Self::new(default_level, filter);
+        tracing_subscriber::registry()
+            .with(layer)
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .with_writer(writer)
+                    .with_file(true)
+                    .with_line_number(true),
+            )
+            .init();
+        log_filter
+    }
+
+    fn new(
+        default_level: LevelFilter,
+        filter: EnvFilter,
+    ) -> (Self, reload::Layer<EnvFilter, Registry>) {
+        let initial = filter.to_string();
+        let (layer, handle) = reload::Layer::new(filter);
+        let log_filter = Self {
+            handle,
+            default_level,
+            initial,
+            changes: Arc::default(),
+        };
+        (log_filter, layer)
+    }
+
+    /// The filter as `RUST_LOG` directives
+    pub fn current(&self) -> String {
+        self.handle
+            .with_current(|filter| filter.to_string())
+            .unwrap_or_default()
+    }
+
+    /// Replace the filter by `directives` like `info,eoi_can_decoder=trace`, for `duration` or
+    /// until the next change. Needs a tokio runtime for the duration
+    pub fn set(&self, directives: &str, duration: Option<Duration>) -> Result<(), String> {
+        let filter = EnvFilter::builder()
+            .with_default_directive(self.default_level.into())
+            .parse(directives)
+            .map_err(|error| format!("Invalid log filter {:?}: {}", directives, error))?;
+        let change = self.changes.fetch_add(1, Ordering::Relaxed) + 1;
+        self.handle
+            .reload(filter)
+            .map_err(|error| format!("Failed to change the log filter: {}", error))?;
+        info!("Log filter set to {} for {:?}", directives, duration);
+
+        if let Some(duration) = duration {
+            let log_filter = self.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(duration).await;
+                if log_filter.changes.load(Ordering::Relaxed) == change {
+                    log_filter.reset();
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Restore the filter of the start
+    pub fn reset(&self) {
+        self.changes.fetch_add(1, Ordering::Relaxed);
+        // The initial filter was parsed before
+        if let Err(error) = self.handle.reload(EnvFilter::new(&self.initial)) {
+            warn!("Failed to restore the log filter: {}", error);
+        }
+        info!("Log filter restored to {}", self.initial);
+    }
+
+    /// Carry out a [`LogFilterCommand`]
+    pub fn apply(&self, command: &LogFilterCommand) -> Result<(), String> {
+        let Some(filter) = &command.filter else {
+            self.reset();
+            return Ok(());
+        };
+        let duration = command
+            .duration_s
+            .map(|seconds| {
+                Duration::try_from_secs_f64(seconds)
+                    .map_err(|_| format!("Invalid duration {} s", seconds))
+            })
+            .transpose()?;
+        self.set(filter, duration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+
+    #[tokio::test]
+    async fn temporary_filter() {
+        let (log_filter, _layer) = LogFilter::new(LevelFilter::INFO, EnvFilter::new("info"));
+        assert!(log_filter.current() == "info");
+
+        assert!(log_filter.set("eoi_can_decoder=loud", None).is_err());
+        assert!(log_filter.current() == "info");
+
+        let command = LogFilterCommand {
+            filter: Some("eoi_can_decoder=trace".to_string()),
+            duration_s: Some(0.01),
+        };
+        log_filter.apply(&command).unwrap();
+        assert!(log_filter.current().contains("eoi_can_decoder=trace"));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(log_filter.current() == "info");
+    }
+
+    #[tokio::test]
+    async fn newer_filter_stays() {
+        let (log_filter, _layer) = LogFilter::new(LevelFilter::INFO, EnvFilter::new("info"));
+        log_filter
+            .set("debug", Some(Duration::from_millis(10)))
+            .unwrap();
+        log_filter.set("warn", None).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(log_filter.current() == "warn");
+
+        log_filter.apply(&LogFilterCommand::default()).unwrap();
+        assert!(log_filter.current() == "info");
+    }
+}
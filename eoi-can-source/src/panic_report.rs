@@ -0,0 +1,158 @@
+//! Panics of the binaries, kept as a file in the panic directory until `eoi-can-to-mqtt`
+//! published them on the "error" topic. Otherwise a panic only ends up in the journal of the
+//! datalogger, which nobody reads during a race, and a bridge that panicked before it could
+//! publish would lose its own. The directory is `EOI_CAN_PANIC_DIR`, or
+//! [`DEFAULT_PANIC_DIR`] when it isn't set.
+
+use std::backtrace::Backtrace;
+use std::fs;
+use std::io;
+use std::panic::{self, PanicHookInfo};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+/// Where the reports are kept when `EOI_CAN_PANIC_DIR` isn't set, it survives a reboot
+pub const DEFAULT_PANIC_DIR: &str = "/var/lib/eoi-can/panics";
+
+/// A panic of a binary, published as it is
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PanicReport {
+    /// Name of the binary
+    pub source: String,
+    pub message: String,
+    pub location: Option<String>,
+    pub thread: Option<String>,
+    pub backtrace: String,
+    /// When it panicked, in ms since the unix epoch
+    pub timestamp_ms: u64,
+}
+
+impl PanicReport {
+    fn new(source: &str, info: &PanicHookInfo) -> Self {
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("Box<dyn Any>");
+        Self {
+            source: source.to_string(),
+            message: message.to_string(),
+            location: info.location().map(ToString::to_string),
+            thread: std::thread::current().name().map(ToString::to_string),
+            backtrace: Backtrace::force_capture().to_string(),
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_millis() as u64),
+        }
+    }
+}
+
+/// A report waiting in the panic directory
+#[derive(Debug)]
+pub struct PendingPanic {
+    pub path: PathBuf,
+    pub report: PanicReport,
+}
+
+impl PendingPanic {
+    /// Delete the report once it is published
+    pub fn remove(&self) -> io::Result<()> {
+        fs::remove_file(&self.path)
+    }
+}
+
+/// `EOI_CAN_PANIC_DIR`, or [`DEFAULT_PANIC_DIR`]
+pub fn panic_dir() -> PathBuf {
+    std::env::var_os("EOI_CAN_PANIC_DIR")
+        .map_or_else(|| PathBuf::from(DEFAULT_PANIC_DIR), PathBuf::from)
+}
+
+/// Keep every panic of the binary `source` in the panic directory, after the default hook
+/// printed it. A panic of a task leaves the process running, so the report may well be published
+/// before the process ends
+pub fn install_panic_hook(source: &'static str) {
+    let default_hook = panic::take_hook();
+    let dir = panic_dir();
+    panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        if let Err(error) = write_report(&dir, &PanicReport::new(source, info)) {
+            eprintln!(
+                "Unable to keep the panic in {}: {}",
+                dir.to_string_lossy(),
+                error
+            );
+        }
+    }));
+}
+
+fn write_report(dir: &Path, report: &PanicReport) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let name = format!(
+        "{}-{}-{}",
+        report.source,
+        report.timestamp_ms,
+        std::process::id()
+    );
+    let path = dir.join(format!("{}.json", name));
+    // Renamed into place, so a half written report is never published
+    let partial = dir.join(format!("{}.partial", name));
+    fs::write(
+        &partial,
+        serde_json::to_vec(report).map_err(io::Error::other)?,
+    )?;
+    fs::rename(&partial, &path)?;
+    Ok(path)
+}
+
+/// The reports in `dir` not published yet, oldest first. Unreadable files are left alone
+pub fn pending_panics(dir: &Path) -> Vec<PendingPanic> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut pending: Vec<_> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        })
+        .filter_map(|path| {
+            let report = serde_json::from_slice(&fs::read(&path).ok()?).ok()?;
+            Some(PendingPanic { path, report })
+        })
+        .collect();
+    pending.sort_by_key(|pending| pending.report.timestamp_ms);
+    pending
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+
+    #[test]
+    fn reports_are_kept_until_removed() {
+        let dir = std::env::temp_dir().join(format!("eoi-can-panics-test-{}", std::process::id()));
+        let report = |timestamp_ms| PanicReport {
+            source: "eoi-can-faker".to_string(),
+            message: "index out of bounds".to_string(),
+            location: Some("src/main.rs:12:5".to_string()),
+            thread: Some("main".to_string()),
+            backtrace: String::new(),
+            timestamp_ms,
+        };
+        write_report(&dir, &report(2_000)).unwrap();
+        write_report(&dir, &report(1_000)).unwrap();
+        fs::write(dir.join("garbage.json"), "{").unwrap();
+
+        let pending = pending_panics(&dir);
+        assert!(pending.len() == 2);
+        assert!(pending[0].report == report(1_000));
+        pending[0].remove().unwrap();
+        assert!(pending_panics(&dir).len() == 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
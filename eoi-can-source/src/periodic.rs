@@ -0,0 +1,152 @@
+//! Frames queued on a fixed schedule, like the GNSS position or the faked traffic. Every schedule
+//! keeps its own deadlines, so a busy runtime doesn't make the sends drift, and sends missed while
+//! late are skipped instead of sent in a burst.
+
+use std::time::Duration;
+
+use embedded_can::Frame;
+use eoi_can_decoder::EoiCanData;
+use eoi_can_decoder::can_encoder::encode_eoi_can_data;
+use socketcan::CanFrame;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::CanTransmitter;
+
+type Produce = Box<dyn FnMut() -> Vec<CanFrame> + Send>;
+
+struct Schedule {
+    interval: Duration,
+    /// When the frames are due next
+    next: Instant,
+    produce: Produce,
+}
+
+impl Schedule {
+    /// Move the deadline past `now`, by whole intervals so the phase stays the same
+    fn advance(&mut self, now: Instant) {
+        while self.next <= now {
+            self.next += self.interval;
+        }
+    }
+}
+
+/// Queues the frames of closures at their interval in one task
+#[derive(Default)]
+pub struct PeriodicSender {
+    schedules: Vec<Schedule>,
+}
+
+impl std::fmt::Debug for PeriodicSender {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.schedules.iter().map(|schedule| schedule.interval))
+            .finish()
+    }
+}
+
+impl PeriodicSender {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send the frames `produce` returns every `interval`, the first time right away
+    pub fn with_frames<I>(
+        mut self,
+        interval: Duration,
+        mut produce: impl FnMut() -> I + Send + 'static,
+    ) -> Self
+    where
+        I: IntoIterator<Item = CanFrame>,
+    {
+        assert!(!interval.is_zero(), "The interval must not be zero");
+        self.schedules.push(Schedule {
+            interval,
+            next: Instant::now(),
+            produce: Box::new(move || produce().into_iter().collect()),
+        });
+        self
+    }
+
+    /// Like [`Self::with_frames`], encoding the data with the encoder of the decoder crate
+    pub fn with_data<I>(
+        self,
+        interval: Duration,
+        mut produce: impl FnMut() -> I + Send + 'static,
+    ) -> Self
+    where
+        I: IntoIterator<Item = EoiCanData>,
+    {
+        self.with_frames(interval, move || {
+            produce()
+                .into_iter()
+                .filter_map(|data| {
+                    let frame = encode_eoi_can_data(&data);
+                    if frame.is_none() {
+                        warn!("Unable to encode {:?}", data);
+                    }
+                    frame
+                })
+                .filter_map(|frame| CanFrame::new(frame.id, &frame.data))
+                .collect::<Vec<_>>()
+        })
+    }
+
+    /// Queue the frames on `transmitter` in a new task, which runs until it is aborted
+    pub fn spawn(self, transmitter: CanTransmitter) -> JoinHandle<()> {
+        tokio::spawn(self.run(transmitter))
+    }
+
+    async fn run(mut self, transmitter: CanTransmitter) {
+        let Some(first) = self.schedules.iter().map(|schedule| schedule.next).min() else {
+            debug!("Nothing to send periodically");
+            return;
+        };
+        let mut next = first;
+        loop {
+            tokio::time::sleep_until(next).await;
+
+            for schedule in &mut self.schedules {
+                if schedule.next > Instant::now() {
+                    continue;
+                }
+                for frame in (schedule.produce)() {
+                    transmitter.send(frame);
+                }
+                schedule.advance(Instant::now());
+            }
+
+            next = self
+                .schedules
+                .iter()
+                .map(|schedule| schedule.next)
+                .min()
+                .unwrap_or(next);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+
+    #[test]
+    fn late_sends_are_skipped() {
+        let start = Instant::now();
+        let mut schedule = Schedule {
+            interval: Duration::from_millis(100),
+            next: start,
+            produce: Box::new(Vec::new),
+        };
+
+        schedule.advance(start + Duration::from_millis(5));
+        assert!(schedule.next == start + Duration::from_millis(100));
+
+        // Late by more than two intervals, the phase stays
+        schedule.advance(start + Duration::from_millis(330));
+        assert!(schedule.next == start + Duration::from_millis(400));
+    }
+}
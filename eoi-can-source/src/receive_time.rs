@@ -0,0 +1,79 @@
+//! Receive time of the frames, as stamped by the kernel when a frame came in. Under load the
+//! reader task gets to a frame well after that, so taking the time in the task would skew the
+//! CAN logs and the timing of their replay.
+
+use std::io;
+use std::os::fd::AsRawFd;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// `SIOCGSTAMP` of `linux/sockios.h`, which the libc crate doesn't have
+const SIOCGSTAMP: libc::c_ulong = 0x8906;
+
+/// Have the kernel stamp every frame received on `socket`, otherwise it only starts with the
+/// first [`last_receive_time`]
+pub fn enable_receive_time(socket: &impl AsRawFd) -> io::Result<()> {
+    let enable: libc::c_int = 1;
+    // The option is read from a live local of the size given
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_TIMESTAMP,
+            (&raw const enable).cast(),
+            size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// When the kernel received the frame read last from `socket`, `None` before the first frame or
+/// when `socket` isn't one
+pub fn last_receive_time(socket: &impl AsRawFd) -> Option<SystemTime> {
+    let mut time = libc::timeval {
+        tv_sec: 0,
+        tv_usec: 0,
+    };
+    // The kernel writes a timeval into the live local
+    let result = unsafe { libc::ioctl(socket.as_raw_fd(), SIOCGSTAMP as _, &raw mut time) };
+    if result < 0 {
+        return None;
+    }
+    from_timeval(time)
+}
+
+fn from_timeval(time: libc::timeval) -> Option<SystemTime> {
+    let micros = u32::try_from(time.tv_usec).ok()?;
+    let since_epoch = Duration::new(u64::try_from(time.tv_sec).ok()?, micros.checked_mul(1000)?);
+    UNIX_EPOCH.checked_add(since_epoch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+
+    #[test]
+    fn kernel_time_stamps() {
+        let time = libc::timeval {
+            tv_sec: 1_718_895_900,
+            tv_usec: 123_456,
+        };
+        assert!(
+            from_timeval(time) == Some(UNIX_EPOCH + Duration::from_micros(1_718_895_900_123_456))
+        );
+        let invalid = libc::timeval {
+            tv_sec: -1,
+            tv_usec: 0,
+        };
+        assert!(from_timeval(invalid).is_none());
+
+        // Not a socket
+        let file = std::fs::File::open("/dev/null").unwrap();
+        assert!(last_receive_time(&file).is_none());
+        assert!(enable_receive_time(&file).is_err());
+    }
+}
@@ -0,0 +1,150 @@
+//! Frames written to a SocketCAN interface by priority, so commands aren't delayed behind
+//! telemetry waiting for a busy bus. The senders never wait, a full class drops its oldest frame.
+//! In listen-only mode nothing is ever written, for attaching to a bus strictly passively.
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use embedded_can::{Frame, Id};
+use eoi_can_decoder::can_encoder::encode_eoi_can_data;
+use eoi_can_decoder::transmit_queue::{Priority, TransmitQueue};
+use eoi_can_decoder::{EoiCanData, parse_eoi_can_data};
+use socketcan::tokio::AsyncCanSocket;
+use socketcan::{CanFrame, CanSocket};
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+/// Frames waiting per priority class
+const QUEUE_SIZE: usize = 64;
+/// How long to wait before writing again when the transmit queue of the kernel is full
+const BUS_BUSY_RETRY_INTERVAL: Duration = Duration::from_millis(5);
+
+#[derive(Debug)]
+struct Shared {
+    queue: Mutex<TransmitQueue<CanFrame, QUEUE_SIZE>>,
+    queued: Notify,
+    /// Data and time of the last frame written per CAN ID
+    written: Mutex<HashMap<Id, (Vec<u8>, Instant)>>,
+}
+
+impl Shared {
+    fn new() -> Self {
+        Self {
+            queue: Mutex::new(TransmitQueue::new()),
+            queued: Notify::new(),
+            written: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+/// Queues frames for the task writing them to the interface, cheap to clone for every sender
+#[derive(Debug, Clone)]
+pub struct CanTransmitter {
+    shared: Arc<Shared>,
+    listen_only: bool,
+}
+
+impl CanTransmitter {
+    /// Write the queued frames to `socket` in a new task, which runs until it is aborted. The
+    /// socket can be shared with a reader behind an [`Arc`]
+    pub fn spawn<S>(socket: S) -> (JoinHandle<()>, Self)
+    where
+        S: Borrow<AsyncCanSocket<CanSocket>> + Send + Sync + 'static,
+    {
+        let transmitter = Self {
+            shared: Arc::new(Shared::new()),
+            listen_only: false,
+        };
+        let handle = tokio::spawn(transmitter.clone().run(socket));
+        (handle, transmitter)
+    }
+
+    /// A transmitter that drops every frame instead of writing it, without a socket or a task
+    pub fn listen_only() -> Self {
+        Self {
+            shared: Arc::new(Shared::new()),
+            listen_only: true,
+        }
+    }
+
+    /// Queue `frame` with the priority of the data it decodes to, telemetry when it doesn't
+    pub fn send(&self, frame: CanFrame) {
+        let priority = eoi_can_decoder::can_frame::CanFrame::try_new(frame.id(), frame.data())
+            .ok()
+            .and_then(|frame| parse_eoi_can_data(&frame))
+            .map_or(Priority::Telemetry, |data| Priority::of(&data));
+        self.send_with_priority(priority, frame);
+    }
+
+    pub fn send_with_priority(&self, priority: Priority, frame: CanFrame) {
+        if self.listen_only {
+            debug!("Listen-only, not sending {:?}", frame);
+            return;
+        }
+        let dropped = self.shared.queue.lock().unwrap().push(priority, frame);
+        if let Some(dropped) = dropped {
+            debug!("{:?} transmit queue full, dropped {:?}", priority, dropped);
+        }
+        self.shared.queued.notify_one();
+    }
+
+    /// Data and time of the last frame of `id` that was written to the interface, frames that
+    /// are still queued or failed don't count
+    pub fn last_written(&self, id: Id) -> Option<(Vec<u8>, Instant)> {
+        self.shared.written.lock().unwrap().get(&id).cloned()
+    }
+
+    /// Encode `data` with the encoder of the decoder crate and queue it
+    pub fn send_data(&self, data: &EoiCanData) {
+        let Some(frame) = encode_eoi_can_data(data) else {
+            warn!("Unable to encode {:?}", data);
+            return;
+        };
+        if let Some(frame) = CanFrame::new(frame.id, &frame.data) {
+            self.send_with_priority(Priority::of(data), frame);
+        }
+    }
+
+    async fn run<S: Borrow<AsyncCanSocket<CanSocket>>>(self, socket: S) {
+        let socket = socket.borrow();
+        loop {
+            self.shared.queued.notified().await;
+            loop {
+                // Not held while writing, so the senders don't wait for the bus
+                let next = self.shared.queue.lock().unwrap().pop();
+                let Some((_, frame)) = next else {
+                    break;
+                };
+                loop {
+                    match socket.write_frame(frame).await {
+                        Ok(()) => {
+                            trace!("Sent CAN frame: {:?}", frame);
+                            self.shared
+                                .written
+                                .lock()
+                                .unwrap()
+                                .insert(frame.id(), (frame.data().to_vec(), Instant::now()));
+                        }
+                        Err(error) if is_bus_busy(&error) => {
+                            tokio::time::sleep(BUS_BUSY_RETRY_INTERVAL).await;
+                            continue;
+                        }
+                        Err(error) => warn!("Failed to send CAN frame {:?}: {}", frame, error),
+                    }
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// SocketCAN reports a full transmit queue as `ENOBUFS` instead of blocking
+fn is_bus_busy(error: &io::Error) -> bool {
+    error.raw_os_error() == Some(libc::ENOBUFS)
+}
@@ -0,0 +1,80 @@
+//! Virtual CAN interfaces for working without the boat. With `--auto-vcan` the simulator, the
+//! faker and the replay create the interface they are given when it doesn't exist yet, like
+//! `ip link add dev vcan0 type vcan && ip link set vcan0 up` by hand, and delete it again when
+//! they exit. An interface that already exists is used as it is and kept. Creating one needs
+//! `CAP_NET_ADMIN`, like `sudo setcap cap_net_admin+ep target/debug/eoi-can-display-simulator`.
+
+use std::io;
+
+use socketcan::nl::CanInterface;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+/// A CAN interface that exists while this is kept, deleted when dropped if it was created by
+/// [`VirtualCan::ensure`]
+pub struct VirtualCan {
+    name: String,
+    /// Only when created here
+    created: Option<CanInterface>,
+}
+
+impl VirtualCan {
+    /// Create the virtual interface `name` and bring it up, unless an interface of that name
+    /// exists already
+    pub fn ensure(name: &str) -> io::Result<Self> {
+        if CanInterface::open(name).is_ok() {
+            debug!("CAN interface {} exists, not creating it", name);
+            return Ok(Self {
+                name: name.to_string(),
+                created: None,
+            });
+        }
+        let interface = CanInterface::create_vcan(name, None).map_err(netlink_error)?;
+        if let Err(error) = interface.bring_up() {
+            let _ = interface.delete();
+            return Err(netlink_error(error));
+        }
+        info!("Created virtual CAN interface {}", name);
+        Ok(Self {
+            name: name.to_string(),
+            created: Some(interface),
+        })
+    }
+
+    /// Whether the interface was created and is deleted again when this is dropped
+    pub fn is_created(&self) -> bool {
+        self.created.is_some()
+    }
+}
+
+impl Drop for VirtualCan {
+    fn drop(&mut self) {
+        let Some(interface) = self.created.take() else {
+            return;
+        };
+        match interface.delete() {
+            Ok(()) => info!("Deleted virtual CAN interface {}", self.name),
+            Err((_, error)) => warn!(
+                "Unable to delete virtual CAN interface {}: {}",
+                self.name, error
+            ),
+        }
+    }
+}
+
+fn netlink_error(error: impl std::fmt::Display) -> io::Error {
+    io::Error::other(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+
+    #[test]
+    fn existing_interface_is_kept() {
+        // The loopback interface always exists, no privileges are needed to leave it alone
+        let interface = VirtualCan::ensure("lo").unwrap();
+        assert!(!interface.is_created());
+    }
+}
@@ -0,0 +1,29 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    telemetry_service();
+}
+
+/// Server of the `Telemetry` service in `eoi-can-decoder/proto/eoi_can.proto`. The messages are
+/// written out in `src/grpc.rs`, so building doesn't need `protoc`
+#[cfg(feature = "grpc")]
+fn telemetry_service() {
+    use tonic_build::manual::{Builder, Method, Service};
+
+    let method = |name: &str, route_name: &str, input_type: &str| {
+        Method::builder()
+            .name(name)
+            .route_name(route_name)
+            .input_type(input_type)
+            .output_type("crate::grpc::Snapshot")
+            .codec_path("tonic::codec::ProstCodec")
+            .server_streaming()
+            .build()
+    };
+    let service = Service::builder()
+        .name("Telemetry")
+        .package("eoi_can")
+        .method(method("live", "Live", "crate::grpc::LiveRequest"))
+        .method(method("history", "History", "crate::grpc::HistoryRequest"))
+        .build();
+    Builder::new().build_client(false).compile(&[service]);
+}
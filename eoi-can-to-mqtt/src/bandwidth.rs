@@ -0,0 +1,300 @@
+//! Keeps the critical telemetry alive on a saturated uplink, like the cellular link of the boat
+//! far from the shore. The time the snapshots take to get to the broker and the ones of earlier
+//! intervals still waiting when a flush starts tell whether the link keeps up with the publish
+//! interval. When it doesn't, the low priority topics, like the per-cell voltages, are dropped
+//! first and then everything but the safety and alarm topics, which are always published with at
+//! least QoS 1. The level goes back one step at a time once the link kept up for a while, and is
+//! published on `bridge/bandwidth`.
+
+use std::time::{Duration, Instant};
+
+use paho_mqtt as mqtt;
+use serde::Serialize;
+
+/// Saturated flushes in a row before dropping more topics
+pub const DEGRADE_AFTER: u32 = 3;
+/// Time the link has to keep up before publishing more topics again
+pub const RECOVER_AFTER: Duration = Duration::from_secs(30);
+
+/// Subsystem topics of the alarms and of the safety of the crew
+const CRITICAL_TOPICS: &[&str] = &[
+    "battery/reserve",
+    "battery/cutoff",
+    "battery/cell-imbalance",
+    "battery/soc-disagreement",
+    "gnss/clock-divergence",
+    "motor/fault",
+    "throttle/status",
+    "error",
+    "datalogger/shutdown",
+    "bridge/bandwidth",
+];
+
+/// Subsystem topics which are only needed for a closer look, by prefix
+const LOW_PRIORITY_TOPICS: &[&str] = &[
+    "battery/cells/",
+    "battery/temperatures",
+    "display/heartbeat",
+    "display/log",
+    "nodes/",
+    "raw/",
+    "dbc/",
+    "flight-recorder/",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TopicPriority {
+    Low,
+    Normal,
+    Critical,
+}
+
+/// Priority of a subsystem topic without the prefix, like `battery/cells/1-4`
+pub fn topic_priority(topic: &str) -> TopicPriority {
+    if CRITICAL_TOPICS.contains(&topic) {
+        TopicPriority::Critical
+    } else if LOW_PRIORITY_TOPICS
+        .iter()
+        .any(|prefix| topic.starts_with(prefix))
+    {
+        TopicPriority::Low
+    } else {
+        TopicPriority::Normal
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Degradation {
+    /// Every topic is published
+    Full,
+    /// The low priority topics are dropped
+    DropLow,
+    /// Only the critical topics are published
+    CriticalOnly,
+}
+
+impl Degradation {
+    pub fn keeps(self, priority: TopicPriority) -> bool {
+        match self {
+            Degradation::Full => true,
+            Degradation::DropLow => priority >= TopicPriority::Normal,
+            Degradation::CriticalOnly => priority == TopicPriority::Critical,
+        }
+    }
+
+    fn worse(self) -> Self {
+        match self {
+            Degradation::Full => Degradation::DropLow,
+            Degradation::DropLow | Degradation::CriticalOnly => Degradation::CriticalOnly,
+        }
+    }
+
+    fn better(self) -> Self {
+        match self {
+            Degradation::Full | Degradation::DropLow => Degradation::Full,
+            Degradation::CriticalOnly => Degradation::DropLow,
+        }
+    }
+}
+
+/// Published on `bridge/bandwidth` when the level changes, like
+/// `{"level": "drop-low", "queue_depth": 2, "flush_ms": 1450, "dropped_messages": 120}`
+#[derive(Debug, PartialEq, Serialize)]
+pub struct BandwidthReport {
+    pub level: Degradation,
+    /// Snapshots of earlier intervals waiting when the last flush started
+    pub queue_depth: usize,
+    /// Time the last flush took
+    pub flush_ms: u64,
+    /// Messages dropped since the start
+    pub dropped_messages: u64,
+}
+
+#[derive(Debug)]
+pub struct BandwidthManager {
+    /// Of the subsystem topics, like `eoi/boat/`
+    prefix: String,
+    level: Degradation,
+    saturated_flushes: u32,
+    keeping_up_since: Option<Instant>,
+    queue_depth: usize,
+    flush_time: Duration,
+    dropped_messages: u64,
+    changed: bool,
+}
+
+impl BandwidthManager {
+    pub fn new(prefix: &str) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+            level: Degradation::Full,
+            saturated_flushes: 0,
+            keeping_up_since: None,
+            queue_depth: 0,
+            flush_time: Duration::ZERO,
+            dropped_messages: 0,
+            changed: false,
+        }
+    }
+
+    pub fn level(&self) -> Degradation {
+        self.level
+    }
+
+    /// Topics outside of the subsystem topics, like the merged document or the Home Assistant
+    /// discovery configs, are of normal priority
+    pub fn priority(&self, message: &mqtt::Message) -> TopicPriority {
+        message
+            .topic()
+            .strip_prefix(&self.prefix)
+            .map_or(TopicPriority::Normal, topic_priority)
+    }
+
+    /// Whether the message is published at the current level
+    pub fn keeps(&self, message: &mqtt::Message) -> bool {
+        self.level.keeps(self.priority(message))
+    }
+
+    /// The messages of the snapshot kept at the current level, the critical ones with QoS 1 at
+    /// least
+    pub fn apply(&mut self, snapshot: Vec<mqtt::Message>) -> Vec<mqtt::Message> {
+        let before = snapshot.len();
+        let kept: Vec<_> = snapshot
+            .into_iter()
+            .filter(|message| self.keeps(message))
+            .map(|message| {
+                if self.priority(&message) == TopicPriority::Critical && message.qos() < 1 {
+                    mqtt::MessageBuilder::new()
+                        .topic(message.topic())
+                        .payload(message.payload())
+                        .retained(message.retained())
+                        .qos(1)
+                        .finalize()
+                } else {
+                    message
+                }
+            })
+            .collect();
+        self.dropped_messages += (before - kept.len()) as u64;
+        kept
+    }
+
+    /// Called after every flush while connected, with the snapshots of earlier intervals that
+    /// were waiting when it started and how long it took. The link is saturated when it didn't
+    /// get everything out within the interval
+    pub fn observe(
+        &mut self,
+        queue_depth: usize,
+        flush_time: Duration,
+        interval: Duration,
+        now: Instant,
+    ) {
+        self.queue_depth = queue_depth;
+        self.flush_time = flush_time;
+        if queue_depth > 0 || flush_time > interval {
+            self.keeping_up_since = None;
+            self.saturated_flushes += 1;
+            if self.saturated_flushes >= DEGRADE_AFTER && self.level != Degradation::CriticalOnly {
+                self.level = self.level.worse();
+                self.saturated_flushes = 0;
+                self.changed = true;
+            }
+            return;
+        }
+        self.saturated_flushes = 0;
+        // Only well within the interval it keeps up, so publishing more won't saturate it again
+        if flush_time > interval / 2 {
+            self.keeping_up_since = None;
+            return;
+        }
+        let since = *self.keeping_up_since.get_or_insert(now);
+        if now.duration_since(since) >= RECOVER_AFTER && self.level != Degradation::Full {
+            self.level = self.level.better();
+            self.keeping_up_since = Some(now);
+            self.changed = true;
+        }
+    }
+
+    /// The report when the level changed since the last call
+    pub fn take_change(&mut self) -> Option<BandwidthReport> {
+        if !std::mem::take(&mut self.changed) {
+            return None;
+        }
+        Some(BandwidthReport {
+            level: self.level,
+            queue_depth: self.queue_depth,
+            flush_ms: self.flush_time.as_millis() as u64,
+            dropped_messages: self.dropped_messages,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+
+    fn snapshot() -> Vec<mqtt::Message> {
+        vec![
+            mqtt::Message::new_retained("eoi/boat/battery/cells/1-4", "[3.3]", 0),
+            mqtt::Message::new_retained("eoi/boat/battery/soc", "80", 0),
+            mqtt::Message::new("eoi/boat/motor/fault", "{}", 0),
+        ]
+    }
+
+    fn topics(messages: &[mqtt::Message]) -> Vec<(&str, i32)> {
+        messages
+            .iter()
+            .map(|message| (message.topic(), message.qos()))
+            .collect()
+    }
+
+    #[test]
+    fn priorities_of_the_topics() {
+        assert!(topic_priority("battery/cells/13-14") == TopicPriority::Low);
+        assert!(topic_priority("battery/reserve") == TopicPriority::Critical);
+        assert!(topic_priority("vesc/9/status-1") == TopicPriority::Normal);
+        // A summary of the capture, the file has the frames
+        assert!(topic_priority("flight-recorder/capture") == TopicPriority::Low);
+    }
+
+    #[test]
+    fn degrades_and_recovers() {
+        let mut manager = BandwidthManager::new("eoi/boat/");
+        let interval = Duration::from_secs(1);
+        let start = Instant::now();
+        let at = |s| start + Duration::from_secs(s);
+
+        let kept = manager.apply(snapshot());
+        assert!(kept.len() == 3);
+        assert!(topics(&kept)[2] == ("eoi/boat/motor/fault", 1));
+
+        // Three flushes that took longer than the interval
+        for s in 0..3 {
+            manager.observe(0, Duration::from_millis(1500), interval, at(s));
+        }
+        assert!(manager.level() == Degradation::DropLow);
+        let report = manager.take_change().unwrap();
+        assert!(report.flush_ms == 1500);
+        assert!(manager.take_change().is_none());
+        let kept = manager.apply(snapshot());
+        assert!(topics(&kept) == [("eoi/boat/battery/soc", 0), ("eoi/boat/motor/fault", 1)]);
+
+        for s in 3..6 {
+            manager.observe(2, Duration::from_millis(900), interval, at(s));
+        }
+        assert!(manager.level() == Degradation::CriticalOnly);
+        assert!(topics(&manager.apply(snapshot())) == [("eoi/boat/motor/fault", 1)]);
+        assert!(manager.take_change().unwrap().dropped_messages == 3);
+
+        // A step back once the link kept up for a while
+        manager.observe(0, Duration::from_millis(100), interval, at(6));
+        manager.observe(0, Duration::from_millis(100), interval, at(35));
+        assert!(manager.level() == Degradation::CriticalOnly);
+        manager.observe(0, Duration::from_millis(100), interval, at(36));
+        assert!(manager.level() == Degradation::DropLow);
+        manager.observe(0, Duration::from_millis(100), interval, at(66));
+        assert!(manager.level() == Degradation::Full);
+    }
+}
@@ -0,0 +1,248 @@
+//! The bridge against a real broker: rumqttd runs in the test process on a port of its own and
+//! the paho client of the bridge connects to it with the options of [`MqttSettings`]. A frame of
+//! every message stands in for the CAN bus, and the bridge reaches the broker through an uplink
+//! the tests can cut, like the cellular link dropping out on the water.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use assert2::assert;
+use clap::Parser;
+use draw_display::DisplayData;
+use eoi_can_decoder::DecoderConfig;
+use eoi_can_decoder::current::CurrentConvention;
+use eoi_can_decoder::signals::sample_frames;
+use paho_mqtt as mqtt;
+use serde_json::json;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Notify;
+
+use crate::mqtt_settings::{MqttSettings, OFFLINE, ONLINE, PayloadFormat};
+use crate::outbox::Outbox;
+use crate::tick::process_frames;
+use crate::topics::subsystem_topic;
+
+/// Long enough for the reconnect of the bridge, which waits a second at first
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Parser)]
+struct Cli {
+    #[command(flatten)]
+    mqtt: MqttSettings,
+}
+
+/// A broker on a free port of localhost, running until the test process ends
+async fn start_broker() -> u16 {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    // Like the `rumqttd.toml` of the broker
+    let config: rumqttd::Config = serde_json::from_value(json!({
+        "id": 0,
+        "router": {
+            "max_connections": 10,
+            "max_outgoing_packet_count": 200,
+            "max_segment_size": 104_857_600,
+            "max_segment_count": 10,
+        },
+        "v4": {
+            "1": {
+                "name": "v4-1",
+                "listen": format!("127.0.0.1:{}", port),
+                "next_connection_delay_ms": 1,
+                "connections": {
+                    "connection_timeout_ms": 60_000,
+                    "max_payload_size": 1_048_576,
+                    "max_inflight_count": 100,
+                    "dynamic_filters": true,
+                },
+            },
+        },
+    }))
+    .unwrap();
+    let mut broker = rumqttd::Broker::new(config);
+    std::thread::spawn(move || broker.start());
+    port
+}
+
+/// Forwards every connection to the broker until `cut` is notified, the connections are then
+/// closed without a word to either side. New connections are forwarded again
+async fn uplink(broker_port: u16) -> (u16, Arc<Notify>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let cut = Arc::new(Notify::new());
+    let cut_connections = cut.clone();
+    tokio::spawn(async move {
+        while let Ok((mut bridge, _)) = listener.accept().await {
+            let cut = cut_connections.clone();
+            tokio::spawn(async move {
+                let Ok(mut broker) = TcpStream::connect(("127.0.0.1", broker_port)).await else {
+                    return;
+                };
+                tokio::select! {
+                    _ = tokio::io::copy_bidirectional(&mut bridge, &mut broker) => {}
+                    _ = cut.notified() => {}
+                }
+            });
+        }
+    });
+    (port, cut)
+}
+
+fn settings(port: u16) -> MqttSettings {
+    Cli::parse_from([
+        "eoi-can-to-mqtt",
+        "--broker",
+        &format!("tcp://127.0.0.1:{}", port),
+        "--topic-mode",
+        "subsystem",
+    ])
+    .mqtt
+}
+
+/// Connected like in `main`, retried while the broker is starting
+async fn connect_bridge(settings: &MqttSettings) -> mqtt::AsyncClient {
+    let client = mqtt::AsyncClient::new(settings.create_options()).unwrap();
+    client.set_connected_callback(settings.on_connected());
+    let options = settings.connect_options(None);
+    for _ in 0..50 {
+        if client.connect(options.clone()).await.is_ok() {
+            return client;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("Unable to connect to the broker");
+}
+
+/// A client of the dashboards subscribed to `topic`
+async fn subscribe(
+    port: u16,
+    topic: &str,
+) -> (
+    mqtt::AsyncClient,
+    mqtt::AsyncReceiver<Option<mqtt::Message>>,
+) {
+    let mut client = mqtt::AsyncClient::new(
+        mqtt::CreateOptionsBuilder::new()
+            .server_uri(format!("tcp://127.0.0.1:{}", port))
+            .client_id("dashboard")
+            .finalize(),
+    )
+    .unwrap();
+    let messages = client.get_stream(1024);
+    for _ in 0..50 {
+        if client.connect(None).await.is_ok() {
+            client.subscribe(topic, 1).await.unwrap();
+            return (client, messages);
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("Unable to connect to the broker");
+}
+
+async fn receive(messages: &mqtt::AsyncReceiver<Option<mqtt::Message>>) -> mqtt::Message {
+    tokio::time::timeout(RECEIVE_TIMEOUT, messages.recv())
+        .await
+        .expect("Nothing received from the broker")
+        .unwrap()
+        .expect("Connection to the broker lost")
+}
+
+/// The subsystem topics of a frame of every message, like the main loop publishes them
+fn snapshot(settings: &MqttSettings) -> Vec<mqtt::Message> {
+    let frames: Vec<_> = sample_frames(0x01).map(|(_, _, frame)| frame).collect();
+    let mut topics = BTreeMap::new();
+    process_frames(
+        &frames,
+        &DecoderConfig::default(),
+        CurrentConvention::default(),
+        &mut DisplayData::default(),
+        None,
+        |data| {
+            if let Some((topic, value)) = subsystem_topic(data) {
+                topics.insert(topic, value);
+            }
+        },
+    );
+    topics
+        .into_iter()
+        .map(|(topic, value)| {
+            mqtt::Message::new_retained(
+                settings.subsystem_topic(&topic),
+                PayloadFormat::Json.encode(&value),
+                settings.qos,
+            )
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn frames_arrive_on_the_subsystem_topics() {
+    let port = start_broker().await;
+    let settings = settings(port);
+    let (_dashboard, messages) = subscribe(port, "eoi/boat/#").await;
+    let bridge = connect_bridge(&settings).await;
+
+    let snapshot = snapshot(&settings);
+    let mut expected: BTreeMap<_, _> = snapshot
+        .iter()
+        .map(|message| {
+            (
+                message.topic().to_string(),
+                message.payload_str().to_string(),
+            )
+        })
+        .collect();
+    expected.insert(settings.availability_topic(), ONLINE.to_string());
+    assert!(expected.contains_key("eoi/boat/battery/soc"));
+    let mut outbox = Outbox::new(10);
+    outbox.push(snapshot);
+    outbox.flush(&bridge).await;
+    assert!(outbox.is_empty());
+
+    let mut received = BTreeMap::new();
+    while received.len() < expected.len() {
+        let message = receive(&messages).await;
+        received.insert(
+            message.topic().to_string(),
+            message.payload_str().to_string(),
+        );
+    }
+    assert!(received == expected);
+}
+
+#[tokio::test]
+async fn will_and_reconnect_after_the_uplink_dropped() {
+    let broker_port = start_broker().await;
+    let (uplink_port, cut) = uplink(broker_port).await;
+    let settings = settings(uplink_port);
+    let (_dashboard, messages) = subscribe(broker_port, "eoi/boat/#").await;
+    let bridge = connect_bridge(&settings).await;
+    let availability = |message: mqtt::Message| {
+        assert!(message.topic() == settings.availability_topic());
+        message.payload_str().to_string()
+    };
+    assert!(availability(receive(&messages).await) == ONLINE);
+
+    // Dropped without a disconnect, so the broker publishes the will
+    cut.notify_waiters();
+    assert!(availability(receive(&messages).await) == OFFLINE);
+    let mut outbox = Outbox::new(10);
+    outbox.push(vec![mqtt::Message::new_retained(
+        "eoi/boat/battery/soc",
+        "81",
+        1,
+    )]);
+
+    // The automatic reconnect of the connect options, then the snapshot gets through
+    assert!(availability(receive(&messages).await) == ONLINE);
+    outbox.flush(&bridge).await;
+    assert!(outbox.is_empty());
+    let message = receive(&messages).await;
+    assert!(message.topic() == "eoi/boat/battery/soc");
+    assert!(message.payload_str() == "81");
+}
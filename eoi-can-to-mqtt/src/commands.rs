@@ -0,0 +1,264 @@
+//! Commands received over MQTT on `eoi/command/<command>`, the topic selects the command and the
+//! payload holds its parameters as JSON.
+//!
+//! Commands map to the same data the decoder produces, so the encoder of the decoder crate turns
+//! them into CAN frames. Most commands are a single frame, display messages are split over
+//! several. Charging is disabled with `charge-control`. The BMS has no reset request on the bus,
+//! so there is no command to reset the battery.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use eoi_can_decoder::{
+    ChargeControl, DISPLAY_MESSAGE_ID_MAX, DisplayControl, DisplayMessage, EoiBattery, EoiCanData,
+    RudderControllerData, ServoData, ServoRudderCommand, ThrottleConfig, ThrottleControlType,
+    ThrottleData,
+};
+use serde::Deserialize;
+
+/// Every display message gets its own ID, so the displays don't mix up the parts of two messages
+static NEXT_MESSAGE_ID: AtomicU8 = AtomicU8::new(0);
+
+#[derive(Debug)]
+pub enum CommandError {
+    UnknownCommand(String),
+    InvalidPayload(serde_json::Error),
+    /// A parameter is out of its range, like a node ID that doesn't exist
+    InvalidValue(String),
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandError::UnknownCommand(command) => write!(f, "Unknown command: {}", command),
+            CommandError::InvalidPayload(error) => write!(f, "Invalid command payload: {}", error),
+            CommandError::InvalidValue(error) => write!(f, "Invalid command value: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// Control type names as they show up in the published telemetry
+#[derive(Debug, Deserialize)]
+enum ControlType {
+    DutyCycle,
+    FilteredDutyCycle,
+    Current,
+    Rpm,
+    CurrentRelative,
+}
+
+#[derive(Debug, Deserialize)]
+struct ThrottleConfigCommand {
+    control_type: ControlType,
+    lever_forward: i16,
+    lever_backward: i16,
+}
+
+#[derive(Debug, Deserialize)]
+struct RudderSetpointCommand {
+    setpoint: u16,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChargeControlCommand {
+    charging_disabled: bool,
+}
+
+/// Page names like `draw_display::Page`, the index is what is sent
+#[derive(Debug, Deserialize)]
+enum Page {
+    Main = 0,
+    Diagnostics = 1,
+    Render = 2,
+    Strategy = 3,
+    Commissioning = 4,
+    Trim = 5,
+    Alarms = 6,
+    Pilot = 7,
+    Cells = 8,
+    Exhibition = 9,
+    Inventory = 10,
+    Parked = 11,
+    Throttle = 12,
+}
+
+#[derive(Debug, Deserialize)]
+struct DisplayPageCommand {
+    page: Page,
+}
+
+/// Profile names like `draw_display::Profile`, the index is what is sent
+#[derive(Debug, Deserialize)]
+enum Profile {
+    Race = 0,
+    TestBench = 1,
+    Charging = 2,
+    Diagnostics = 3,
+    Exhibition = 4,
+}
+
+#[derive(Debug, Deserialize)]
+struct DisplayProfileCommand {
+    profile: Profile,
+}
+
+/// Without `end` and `duration_s` the countdown is cleared
+#[derive(Debug, Deserialize)]
+struct RaceEndCommand {
+    /// UTC time of day the race ends, like "14:30" or "14:30:15"
+    #[serde(default)]
+    end: Option<String>,
+    /// Seconds from now until the race ends
+    #[serde(default)]
+    duration_s: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DisplayMessageCommand {
+    /// Empty to clear the banner
+    text: String,
+    /// Comes back with the acknowledgement of the pilot, counted up when not given
+    #[serde(default)]
+    message_id: Option<u8>,
+}
+
+/// Parse a command into the data of the frames to send, `command` is the part of the topic after
+/// `eoi/command/`
+pub fn parse_command(command: &str, payload: &[u8]) -> Result<Vec<EoiCanData>, CommandError> {
+    if command == "display-message" {
+        let message: DisplayMessageCommand =
+            serde_json::from_slice(payload).map_err(CommandError::InvalidPayload)?;
+        let message_id = match message.message_id {
+            Some(message_id) if message_id > DISPLAY_MESSAGE_ID_MAX => {
+                return Err(CommandError::InvalidValue(format!(
+                    "message ID {} is larger than {}",
+                    message_id, DISPLAY_MESSAGE_ID_MAX
+                )));
+            }
+            Some(message_id) => message_id,
+            None => NEXT_MESSAGE_ID.fetch_add(1, Ordering::Relaxed) & DISPLAY_MESSAGE_ID_MAX,
+        };
+        return Ok(DisplayMessage::split(message_id, &message.text)
+            .into_iter()
+            .map(|part| EoiCanData::DisplayControl(DisplayControl::Message(part)))
+            .collect());
+    }
+    parse_single_frame_command(command, payload).map(|data| vec![data])
+}
+
+fn parse_single_frame_command(command: &str, payload: &[u8]) -> Result<EoiCanData, CommandError> {
+    match command {
+        "throttle-config" => {
+            let config: ThrottleConfigCommand =
+                serde_json::from_slice(payload).map_err(CommandError::InvalidPayload)?;
+            let control_type = match config.control_type {
+                ControlType::DutyCycle => ThrottleControlType::DutyCycle,
+                ControlType::FilteredDutyCycle => ThrottleControlType::FilteredDutyCycle,
+                ControlType::Current => ThrottleControlType::Current,
+                ControlType::Rpm => ThrottleControlType::Rpm,
+                ControlType::CurrentRelative => ThrottleControlType::CurrentRelative,
+            };
+            Ok(EoiCanData::Throttle(ThrottleData::Config(ThrottleConfig {
+                control_type,
+                lever_forward: config.lever_forward,
+                lever_backward: config.lever_backward,
+            })))
+        }
+        "rudder-initialize" => Ok(EoiCanData::RudderController(RudderControllerData::Servo(
+            ServoData::Command(ServoRudderCommand::Initialize),
+        ))),
+        "rudder-setpoint" => {
+            let setpoint: RudderSetpointCommand =
+                serde_json::from_slice(payload).map_err(CommandError::InvalidPayload)?;
+            Ok(EoiCanData::RudderController(RudderControllerData::Servo(
+                ServoData::Setpoint(setpoint.setpoint),
+            )))
+        }
+        "charge-control" => {
+            let command: ChargeControlCommand =
+                serde_json::from_slice(payload).map_err(CommandError::InvalidPayload)?;
+            Ok(EoiCanData::EoiBattery(EoiBattery::ChargeControlCommand(
+                ChargeControl {
+                    charging_disabled: command.charging_disabled,
+                },
+            )))
+        }
+        "display-page" => {
+            let command: DisplayPageCommand =
+                serde_json::from_slice(payload).map_err(CommandError::InvalidPayload)?;
+            Ok(EoiCanData::DisplayControl(DisplayControl::Page(
+                command.page as u8,
+            )))
+        }
+        "display-profile" => {
+            let command: DisplayProfileCommand =
+                serde_json::from_slice(payload).map_err(CommandError::InvalidPayload)?;
+            Ok(EoiCanData::DisplayControl(DisplayControl::Profile(
+                command.profile as u8,
+            )))
+        }
+        "race-end" => {
+            let command: RaceEndCommand =
+                serde_json::from_slice(payload).map_err(CommandError::InvalidPayload)?;
+            let end_s = match (command.end, command.duration_s) {
+                (Some(_), Some(_)) => {
+                    return Err(CommandError::InvalidValue(
+                        "Give either the end or the duration of the race".to_string(),
+                    ));
+                }
+                (Some(end), None) => Some(parse_time_of_day(&end)?),
+                (None, Some(duration_s)) => {
+                    let now_s = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .map_or(0, |now| now.as_secs());
+                    Some(((now_s + u64::from(duration_s)) % SECONDS_PER_DAY) as u32)
+                }
+                (None, None) => None,
+            };
+            Ok(EoiCanData::DisplayControl(DisplayControl::RaceEnd(end_s)))
+        }
+        "trip-reset" => Ok(EoiCanData::DisplayControl(DisplayControl::TripReset)),
+        _ => Err(CommandError::UnknownCommand(command.to_string())),
+    }
+}
+
+const SECONDS_PER_DAY: u64 = 24 * 3600;
+
+/// Second of the day of a time like "14:30" or "14:30:15"
+fn parse_time_of_day(time: &str) -> Result<u32, CommandError> {
+    let invalid = || CommandError::InvalidValue(format!("{} is not a time like 14:30", time));
+    let mut parts = time.split(':').map(|part| part.parse::<u32>());
+    let (Some(Ok(hours)), Some(Ok(minutes))) = (parts.next(), parts.next()) else {
+        return Err(invalid());
+    };
+    let seconds = match parts.next() {
+        Some(Ok(seconds)) => seconds,
+        Some(Err(_)) => return Err(invalid()),
+        None => 0,
+    };
+    if hours >= 24 || minutes >= 60 || seconds >= 60 || parts.next().is_some() {
+        return Err(invalid());
+    }
+    Ok(hours * 3600 + minutes * 60 + seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+
+    #[test]
+    fn display_message_ids_out_of_range_are_refused() {
+        let parts = parse_command(
+            "display-message",
+            br#"{"text": "PIT NOW", "message_id": 127}"#,
+        );
+        assert!(parts.unwrap().len() == 1);
+        let parts = parse_command(
+            "display-message",
+            br#"{"text": "PIT NOW", "message_id": 128}"#,
+        );
+        assert!(matches!(parts, Err(CommandError::InvalidValue(_))));
+    }
+}
@@ -0,0 +1,229 @@
+//! Export of the decoded data to CSV, one row per collector flush, for post-race analysis
+//! without MQTT (`pandas.read_csv` just works).
+//!
+//! The columns are the flattened serde JSON of the decoded data, like
+//! `EoiBattery.SocErrorFlagsAndBalancing.state_of_charge`, so they follow the decoder by
+//! themselves. Columns showing up later (a board that powers on mid race) start a new part of
+//! the export with all columns so far, like `race.1.csv` after `race.csv`, so the rows written
+//! already are never rewritten. A restart of the bridge continues in the next part the same way.
+//! `pandas.concat` of the parts gives one table again.
+
+use serde_json::Value;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// First column, seconds since the unix epoch
+pub const TIMESTAMP_COLUMN: &str = "timestamp";
+
+pub struct CsvExport {
+    path: PathBuf,
+    /// Part written to, 0 for `path` itself
+    part: u32,
+    /// Rows in the part written to
+    rows: usize,
+    columns: Vec<String>,
+    file: BufWriter<File>,
+}
+
+impl CsvExport {
+    /// Create the CSV file at `path`, or continue an export there in its next part, like after a
+    /// restart mid race
+    pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let part = parts(&path).count() as u32;
+        let columns = vec![TIMESTAMP_COLUMN.to_string()];
+        let file = create_part(&part_path(&path, part), &columns)?;
+
+        Ok(Self {
+            path,
+            part,
+            rows: 0,
+            columns,
+            file,
+        })
+    }
+
+    /// Write a row with all values of `data`, `timestamp` is the time since the unix epoch
+    pub fn write_row(&mut self, timestamp: Duration, data: &Value) -> io::Result<()> {
+        let mut values = Vec::new();
+        flatten("", data, &mut values);
+
+        let new_columns: Vec<String> = values
+            .iter()
+            .filter(|(column, _)| !self.columns.contains(column))
+            .map(|(column, _)| column.clone())
+            .collect();
+        if !new_columns.is_empty() {
+            self.add_columns(new_columns)?;
+        }
+
+        let mut row = vec![String::new(); self.columns.len()];
+        row[0] = format!("{:.3}", timestamp.as_secs_f64());
+        for (column, value) in values {
+            if let Some(index) = self.columns.iter().position(|name| *name == column) {
+                row[index] = value;
+            }
+        }
+        writeln!(self.file, "{}", row.join(","))?;
+        self.rows += 1;
+        self.file.flush()
+    }
+
+    /// Continue in the next part with the new columns in its header, or replace the header of a
+    /// part without rows
+    fn add_columns(&mut self, new_columns: Vec<String>) -> io::Result<()> {
+        self.file.flush()?;
+        self.columns.extend(new_columns);
+        if self.rows > 0 {
+            self.part += 1;
+            self.rows = 0;
+        }
+        self.file = create_part(&part_path(&self.path, self.part), &self.columns)?;
+        Ok(())
+    }
+}
+
+/// File of the given part of the export at `path`, like `race.2.csv` for `race.csv`
+pub fn part_path(path: &Path, part: u32) -> PathBuf {
+    if part == 0 {
+        return path.to_path_buf();
+    }
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push(format!(".{}", part));
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    path.with_file_name(name)
+}
+
+/// The files of the export at `path` that exist, in order
+pub fn parts(path: &Path) -> impl Iterator<Item = PathBuf> + '_ {
+    (0..)
+        .map(|part| part_path(path, part))
+        .take_while(|part| part.exists())
+}
+
+fn create_part(path: &Path, columns: &[String]) -> io::Result<BufWriter<File>> {
+    let mut file = BufWriter::new(File::create(path)?);
+    writeln!(
+        file,
+        "{}",
+        columns
+            .iter()
+            .map(|column| escape(column))
+            .collect::<Vec<_>>()
+            .join(",")
+    )?;
+    file.flush()?;
+    Ok(file)
+}
+
+/// Flatten JSON into column names joined with dots and escaped CSV values
+fn flatten(prefix: &str, value: &Value, values: &mut Vec<(String, String)>) {
+    let join = |key: &str| {
+        if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", prefix, key)
+        }
+    };
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object {
+                flatten(&join(key), value, values);
+            }
+        }
+        Value::Array(array) => {
+            for (index, value) in array.iter().enumerate() {
+                flatten(&join(&index.to_string()), value, values);
+            }
+        }
+        Value::Null => values.push((prefix.to_string(), String::new())),
+        Value::String(string) => values.push((prefix.to_string(), escape(string))),
+        value => values.push((prefix.to_string(), value.to_string())),
+    }
+}
+
+fn escape(value: &str) -> String {
+    let value = value.replace(['\r', '\n'], " ");
+    if value.contains([',', '"']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use serde_json::json;
+
+    #[test]
+    fn flatten_json() {
+        let mut values = Vec::new();
+        flatten(
+            "",
+            &json!({ "Gnss": { "GnssSpeedAndHeading": [20.5, 90.0] }, "WifiIp": "N/A, \"none\"" }),
+            &mut values,
+        );
+        assert!(
+            values
+                == [
+                    ("Gnss.GnssSpeedAndHeading.0".to_string(), "20.5".to_string()),
+                    ("Gnss.GnssSpeedAndHeading.1".to_string(), "90.0".to_string()),
+                    ("WifiIp".to_string(), "\"N/A, \"\"none\"\"\"".to_string()),
+                ]
+        );
+    }
+
+    #[test]
+    fn columns_are_added() {
+        let path = std::env::temp_dir().join(format!("eoi-csv-export-{}.csv", std::process::id()));
+        let mut export = CsvExport::new(&path).unwrap();
+        export
+            .write_row(Duration::from_millis(1500), &json!({ "a": 1 }))
+            .unwrap();
+        export
+            .write_row(
+                Duration::from_secs(2),
+                &json!({ "b": { "c": true }, "a": 2 }),
+            )
+            .unwrap();
+        export
+            .write_row(Duration::from_secs(3), &json!({ "b": { "c": false } }))
+            .unwrap();
+        drop(export);
+
+        let contents: Vec<_> = parts(&path)
+            .map(|part| std::fs::read_to_string(part).unwrap())
+            .collect();
+        assert!(
+            contents
+                == [
+                    "timestamp,a\n1.500,1\n",
+                    "timestamp,a,b.c\n2.000,2,true\n3.000,,false\n"
+                ]
+        );
+        assert!(part_path(&path, 1).to_string_lossy().ends_with(".1.csv"));
+
+        // A restart continues in the next part, the rows written already are kept
+        let mut export = CsvExport::new(&path).unwrap();
+        export
+            .write_row(Duration::from_secs(4), &json!({ "a": 3 }))
+            .unwrap();
+        drop(export);
+        let contents: Vec<_> = parts(&path)
+            .map(|part| std::fs::read_to_string(part).unwrap())
+            .collect();
+        assert!(contents.len() == 3);
+        assert!(contents[2] == "timestamp,a\n4.000,3\n");
+
+        for part in parts(&path).collect::<Vec<_>>() {
+            std::fs::remove_file(part).unwrap();
+        }
+    }
+}
@@ -0,0 +1,254 @@
+//! Health of the telemetry pipeline itself, published on `bridge/diagnostics`: the frames read
+//! and the load of every bus, the frames replaced and lost before decoding, the frames that failed
+//! to decode by ID, the IDs seen as standard and as extended frame and how often the broker
+//! connection was lost. So a problem of the datalogger shows up remotely, not only in the logs on
+//! the boat. The calibration the signals were decoded with is part of it, so the published values
+//! can be traced back to what the sensors sent.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use eoi_can_decoder::calibration::Calibration;
+use eoi_can_decoder::can_frame::CanFrame;
+use serde::Serialize;
+use tokio::time::Instant;
+
+use crate::topics::raw_id;
+
+/// How often the diagnostics are published
+pub const DIAGNOSTICS_INTERVAL: Duration = Duration::from_secs(10);
+/// Bitrate for the bus load when it isn't given with `--can-bitrate`, the one of the boat
+pub const DEFAULT_BUS_BITRATE: u32 = 1_000_000;
+
+/// Bits of a frame on the bus without stuff bits: start of frame, arbitration, control, data,
+/// CRC, acknowledge, end of frame and the interframe space
+fn frame_bits(frame: &CanFrame) -> u64 {
+    let overhead = match frame.id {
+        embedded_can::Id::Standard(_) => 47,
+        embedded_can::Id::Extended(_) => 67,
+    };
+    overhead + 8 * frame.data.len() as u64
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct BusCounters {
+    frames: u64,
+    /// Since the last report
+    window_frames: u64,
+    window_bits: u64,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct BusReport {
+    pub frames: u64,
+    pub frames_per_second: f32,
+    /// Share of the bitrate, from 0 to 1, stuff bits left out
+    pub bus_load: f32,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct DiagnosticsReport {
+    pub uptime_s: u64,
+    /// Keyed by interface
+    pub buses: BTreeMap<String, BusReport>,
+    /// Frames a newer frame of the same ID replaced before they were published, since the
+    /// start. Normal for IDs sent more often than the publish interval
+    pub replaced_frames: u64,
+    /// Frames lost because the collector was full, since the start
+    pub dropped_frames: u64,
+    /// Frames of `--raw-id` not published because the broker couldn't keep up, since the start
+    pub dropped_raw_frames: u64,
+    /// Frames nothing could decode since the start, keyed by ID like `0x123`
+    pub decode_failures: BTreeMap<String, u64>,
+    /// IDs seen both as standard and as extended frame, like `0x101`, which the decoder can't
+    /// tell apart
+    pub id_collisions: Vec<String>,
+    pub reconnects: u64,
+    /// Of `--calibration`, the signals that were corrected before they were decoded
+    pub calibration: Calibration,
+}
+
+#[derive(Debug)]
+pub struct Diagnostics {
+    started: Instant,
+    window_start: Instant,
+    bitrate: u32,
+    buses: Vec<BusCounters>,
+    replaced_frames: u64,
+    dropped_frames: u64,
+    dropped_raw_frames: u64,
+    decode_failures: BTreeMap<u32, u64>,
+    id_collisions: Vec<u16>,
+    calibration: Calibration,
+}
+
+impl Diagnostics {
+    pub fn new(bitrate: u32, now: Instant) -> Self {
+        Self {
+            started: now,
+            window_start: now,
+            bitrate,
+            buses: Vec::new(),
+            replaced_frames: 0,
+            dropped_frames: 0,
+            dropped_raw_frames: 0,
+            decode_failures: BTreeMap::new(),
+            id_collisions: Vec::new(),
+            calibration: Calibration::default(),
+        }
+    }
+
+    pub fn with_calibration(self, calibration: Calibration) -> Self {
+        Self {
+            calibration,
+            ..self
+        }
+    }
+
+    /// Called by the reader for every frame
+    pub fn received(&mut self, frame: &CanFrame) {
+        let bus = usize::from(frame.bus);
+        if self.buses.len() <= bus {
+            self.buses.resize(bus + 1, BusCounters::default());
+        }
+        let counters = &mut self.buses[bus];
+        counters.frames += 1;
+        counters.window_frames += 1;
+        counters.window_bits += frame_bits(frame);
+    }
+
+    /// Frames the collector replaced and dropped since it was cleared last
+    pub fn collected(&mut self, replaced: usize, dropped: usize) {
+        self.replaced_frames += replaced as u64;
+        self.dropped_frames += dropped as u64;
+    }
+
+    /// Called by the reader for every raw frame the publisher had no room for
+    pub fn raw_frame_dropped(&mut self) {
+        self.dropped_raw_frames += 1;
+    }
+
+    pub fn decode_failed(&mut self, frame: &CanFrame) {
+        *self.decode_failures.entry(raw_id(frame)).or_default() += 1;
+    }
+
+    /// All colliding IDs the collector found since the start
+    pub fn id_collisions(&mut self, ids: &[u16]) {
+        self.id_collisions = ids.to_vec();
+    }
+
+    pub fn is_due(&self, now: Instant) -> bool {
+        now.duration_since(self.window_start) >= DIAGNOSTICS_INTERVAL
+    }
+
+    /// The report of the window since the last one, the next window starts now
+    pub fn report(
+        &mut self,
+        interfaces: &[String],
+        reconnects: u64,
+        now: Instant,
+    ) -> DiagnosticsReport {
+        let window_s = now.duration_since(self.window_start).as_secs_f32();
+        self.window_start = now;
+        let buses = interfaces
+            .iter()
+            .enumerate()
+            .map(|(bus, interface)| {
+                let counters = self.buses.get(bus).copied().unwrap_or_default();
+                let (frames_per_second, bus_load) = if window_s > 0.0 {
+                    (
+                        counters.window_frames as f32 / window_s,
+                        counters.window_bits as f32 / (self.bitrate as f32 * window_s),
+                    )
+                } else {
+                    (0.0, 0.0)
+                };
+                (
+                    interface.clone(),
+                    BusReport {
+                        frames: counters.frames,
+                        frames_per_second,
+                        bus_load,
+                    },
+                )
+            })
+            .collect();
+        for counters in &mut self.buses {
+            counters.window_frames = 0;
+            counters.window_bits = 0;
+        }
+        DiagnosticsReport {
+            uptime_s: now.duration_since(self.started).as_secs(),
+            buses,
+            replaced_frames: self.replaced_frames,
+            dropped_frames: self.dropped_frames,
+            dropped_raw_frames: self.dropped_raw_frames,
+            decode_failures: self
+                .decode_failures
+                .iter()
+                .map(|(id, count)| (format!("{:#x}", id), *count))
+                .collect(),
+            id_collisions: self
+                .id_collisions
+                .iter()
+                .map(|id| format!("{:#x}", id))
+                .collect(),
+            reconnects,
+            calibration: self.calibration.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use embedded_can::{ExtendedId, Id, StandardId};
+
+    fn frame(id: Id, bus: u8) -> CanFrame {
+        let mut frame = CanFrame::try_new(id, &[0; 8]).unwrap();
+        frame.bus = bus;
+        frame
+    }
+
+    #[test]
+    fn load_and_failures() {
+        let start = Instant::now();
+        let calibration = toml::from_str(include_str!("../../eoi-can-decoder/calibration.toml"));
+        let mut diagnostics =
+            Diagnostics::new(500_000, start).with_calibration(calibration.unwrap());
+        let standard = Id::Standard(StandardId::new(0x101).unwrap());
+        for _ in 0..1000 {
+            diagnostics.received(&frame(standard, 0));
+        }
+        diagnostics.received(&frame(Id::Extended(ExtendedId::new(0x1234).unwrap()), 1));
+        diagnostics.decode_failed(&frame(standard, 0));
+        diagnostics.decode_failed(&frame(standard, 0));
+        diagnostics.collected(40, 3);
+        diagnostics.raw_frame_dropped();
+        diagnostics.id_collisions(&[0x101]);
+
+        let now = start + Duration::from_secs(1);
+        assert!(diagnostics.is_due(start + DIAGNOSTICS_INTERVAL));
+        let interfaces = ["can0".to_string(), "can1".to_string()];
+        let report = diagnostics.report(&interfaces, 2, now);
+        // 111 bits per frame of 8 bytes
+        assert!(report.buses["can0"].frames == 1000);
+        assert!((report.buses["can0"].bus_load - 0.222).abs() < 0.001);
+        assert!(report.buses["can1"].frames_per_second == 1.0);
+        assert!(report.decode_failures["0x101"] == 2);
+        assert!(report.replaced_frames == 40);
+        assert!(report.dropped_frames == 3);
+        assert!(report.dropped_raw_frames == 1);
+        assert!(report.id_collisions == ["0x101"]);
+        assert!(report.reconnects == 2);
+        let calibration = serde_json::to_value(&report.calibration).unwrap();
+        assert!(calibration["signals"][0]["signal"] == "PerriCurrent");
+        assert!(calibration["signals"][0]["offset"] == -0.3_f32);
+
+        // The totals stay, the rates start over
+        let report = diagnostics.report(&interfaces, 2, now + Duration::from_secs(1));
+        assert!(report.buses["can0"].frames == 1000);
+        assert!(report.buses["can0"].bus_load == 0.0);
+    }
+}
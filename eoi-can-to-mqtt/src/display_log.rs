@@ -0,0 +1,166 @@
+//! Log records and panic messages of the display firmware, reassembled from their [`DisplayLog`]
+//! and [`DisplayPanic`] frames.
+//!
+//! The frames are taken before the CAN collector, which only keeps the latest frame of every ID.
+
+use eoi_can_decoder::{DisplayLog, DisplayPanic, LogLevel};
+use serde::Serialize;
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct LogRecord {
+    pub level: LogLevel,
+    pub text: String,
+    /// Frames of the record were lost, so the text misses a part
+    pub incomplete: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct LogAssembler {
+    pending: Option<LogRecord>,
+    next_sequence: Option<u8>,
+}
+
+impl LogAssembler {
+    /// Add the next frame, returns the record when this was its last frame
+    pub fn push(&mut self, frame: DisplayLog) -> Option<LogRecord> {
+        let lost = self
+            .next_sequence
+            .is_some_and(|sequence| sequence != frame.sequence);
+        self.next_sequence = Some(frame.sequence.wrapping_add(1));
+
+        let record = match self.pending.take() {
+            Some(mut record) if !lost => {
+                record.text.push_str(&frame.text);
+                record
+            }
+            _ => LogRecord {
+                level: frame.level,
+                text: frame.text.to_string(),
+                incomplete: lost,
+            },
+        };
+
+        if frame.continued {
+            self.pending = Some(record);
+            None
+        } else {
+            Some(record)
+        }
+    }
+}
+
+/// Panic message the display firmware sent after its restart
+#[derive(Debug, PartialEq, Serialize)]
+pub struct PanicReport {
+    pub source: &'static str,
+    pub message: String,
+    /// Parts of the message were lost
+    pub incomplete: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct PanicAssembler {
+    pending: Option<PanicReport>,
+    next_part: u8,
+}
+
+impl PanicAssembler {
+    /// Add the next part, returns the report when this was its last part
+    pub fn push(&mut self, frame: DisplayPanic) -> Option<PanicReport> {
+        let report = match self.pending.take() {
+            // The first part of a new message
+            _ if frame.part == 0 => PanicReport {
+                source: "display-firmware",
+                message: frame.text.to_string(),
+                incomplete: false,
+            },
+            Some(mut report) => {
+                report.incomplete |= frame.part != self.next_part;
+                report.message.push_str(&frame.text);
+                report
+            }
+            None => PanicReport {
+                source: "display-firmware",
+                message: frame.text.to_string(),
+                incomplete: true,
+            },
+        };
+        self.next_part = (frame.part + 1) & 0x7F;
+
+        if frame.last {
+            Some(report)
+        } else {
+            self.pending = Some(report);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+
+    fn frame(sequence: u8, continued: bool, text: &str) -> DisplayLog {
+        DisplayLog {
+            level: LogLevel::Warn,
+            sequence,
+            continued,
+            text: text.try_into().unwrap(),
+        }
+    }
+
+    #[test]
+    fn reassemble_records() {
+        let mut assembler = LogAssembler::default();
+        assert!(assembler.push(frame(254, true, "CAN bu")).is_none());
+        assert!(assembler.push(frame(255, true, "s-off,")).is_none());
+        assert!(
+            assembler.push(frame(0, false, " reset"))
+                == Some(LogRecord {
+                    level: LogLevel::Warn,
+                    text: "CAN bus-off, reset".to_string(),
+                    incomplete: false,
+                })
+        );
+
+        // The middle frame is lost
+        assert!(assembler.push(frame(1, true, "Going ")).is_none());
+        assert!(
+            assembler.push(frame(3, false, "sleep"))
+                == Some(LogRecord {
+                    level: LogLevel::Warn,
+                    text: "sleep".to_string(),
+                    incomplete: true,
+                })
+        );
+    }
+
+    fn part(part: u8, last: bool, text: &str) -> DisplayPanic {
+        DisplayPanic {
+            part,
+            last,
+            text: text.try_into().unwrap(),
+        }
+    }
+
+    #[test]
+    fn reassemble_panics() {
+        let mut assembler = PanicAssembler::default();
+        assert!(assembler.push(part(0, false, "oops at")).is_none());
+        assert!(
+            assembler.push(part(1, true, " main"))
+                == Some(PanicReport {
+                    source: "display-firmware",
+                    message: "oops at main".to_string(),
+                    incomplete: false,
+                })
+        );
+
+        // Started in the middle, then a part is lost
+        assert!(assembler.push(part(3, false, "tail")).is_none());
+        let report = assembler.push(part(5, true, "end")).unwrap();
+        assert!(report.message == "tailend");
+        assert!(report.incomplete);
+    }
+}
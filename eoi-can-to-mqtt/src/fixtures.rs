@@ -0,0 +1,33 @@
+//! Fixtures of the tests in `fixtures/`. The Grafana dashboards query the published fields by
+//! name, so a changed fixture has to be looked at and checked in on purpose, with
+//! `UPDATE_FIXTURES=1 cargo test -p eoi-can-to-mqtt`.
+
+use std::path::{Path, PathBuf};
+
+use assert2::assert;
+use serde::Serialize;
+
+pub fn fixture_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures")
+        .join(name)
+}
+
+/// Compare `text` with the fixture, or write it with `UPDATE_FIXTURES` set
+pub fn assert_fixture(name: &str, text: &str) {
+    let path = fixture_path(name);
+    if std::env::var_os("UPDATE_FIXTURES").is_some() {
+        std::fs::write(&path, text).unwrap();
+    }
+    let fixture = std::fs::read_to_string(&path).unwrap_or_default();
+    assert!(
+        text == fixture,
+        "fixtures/{} changed, check the difference and the Grafana dashboards and run `UPDATE_FIXTURES=1 cargo test -p eoi-can-to-mqtt` to update it",
+        name
+    );
+}
+
+/// [`assert_fixture`] with `value` as pretty JSON
+pub fn assert_json_fixture(name: &str, value: &impl Serialize) {
+    assert_fixture(name, &(serde_json::to_string_pretty(value).unwrap() + "\n"));
+}
@@ -0,0 +1,179 @@
+//! Flight recorder of the bus: every frame of the last minute is kept in memory, and when a
+//! critical alarm is raised, like a cutoff of the BMS or a fault of a motor controller, the frames
+//! leading up to it are decoded into a capture. The publish interval only sees the latest frame
+//! of every ID, the capture has all of them, so the conditions before the alarm are always
+//! recorded at the full rate of the bus.
+//!
+//! The capture is written to `--flight-recorder <dir>` as
+//! `flight-recorder-2024-06-20_150500-motor-fault.json`, away from the publish loop, and only its
+//! summary is published on `flight-recorder/capture`, a minute of frames is megabytes of JSON.
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, Local};
+use draw_display::AlarmEvent;
+use eoi_can_decoder::can_frame::CanFrame;
+use eoi_can_decoder::{DecoderConfig, parse_eoi_can_data_with_config};
+use serde::Serialize;
+use serde_json::{Value, json};
+
+use crate::topics::raw_frame_topic;
+
+/// Time before the alarm that is kept
+pub const FLIGHT_RECORDER_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Serialize)]
+pub struct Capture {
+    /// Name of the alarm like `motor-fault`, see [`draw_display::Alarm::name`]
+    pub alarm: &'static str,
+    /// Like `VESC 9: FET hot`
+    pub description: String,
+    /// Seconds since the UNIX epoch
+    pub triggered: f64,
+    /// Oldest first, like the raw topic with the decoded message, `null` for unknown frames
+    pub frames: Vec<Value>,
+}
+
+impl Capture {
+    /// Written to `dir` and named by the local time of the alarm
+    pub fn write(&self, dir: &Path) -> std::io::Result<PathBuf> {
+        let triggered = UNIX_EPOCH + Duration::from_secs_f64(self.triggered);
+        let path = dir.join(format!(
+            "{}-{}.json",
+            DateTime::<Local>::from(triggered).format("flight-recorder-%Y-%m-%d_%H%M%S"),
+            self.alarm
+        ));
+        std::fs::write(&path, serde_json::to_vec(self)?)?;
+        Ok(path)
+    }
+
+    /// What is published of the capture, `path` is where it was written to
+    pub fn summary(&self, path: Option<PathBuf>) -> CaptureSummary {
+        CaptureSummary {
+            alarm: self.alarm,
+            description: self.description.clone(),
+            triggered: self.triggered,
+            frames: self.frames.len(),
+            path,
+        }
+    }
+}
+
+/// A capture without its frames
+#[derive(Debug, Serialize)]
+pub struct CaptureSummary {
+    pub alarm: &'static str,
+    pub description: String,
+    pub triggered: f64,
+    /// Number of frames in the capture
+    pub frames: usize,
+    /// `None` when the capture couldn't be written
+    pub path: Option<PathBuf>,
+}
+
+/// Cloned to build a capture, so the reader isn't kept waiting while it is decoded
+#[derive(Debug, Clone)]
+pub struct FlightRecorder {
+    window: Duration,
+    /// Oldest first, with the time they were received
+    frames: VecDeque<(SystemTime, CanFrame)>,
+    interfaces: Vec<String>,
+}
+
+impl FlightRecorder {
+    pub fn new(interfaces: &[String]) -> Self {
+        Self {
+            window: FLIGHT_RECORDER_WINDOW,
+            frames: VecDeque::new(),
+            interfaces: interfaces.to_vec(),
+        }
+    }
+
+    /// Called by the reader for every frame, the ones older than the window are dropped
+    pub fn record(&mut self, frame: &CanFrame, received: SystemTime) {
+        self.frames.push_back((received, frame.clone()));
+        while let Some((oldest, _)) = self.frames.front()
+            && received.duration_since(*oldest).unwrap_or_default() > self.window
+        {
+            self.frames.pop_front();
+        }
+    }
+
+    /// The frames of the window before `now`, decoded like the published data
+    pub fn capture(&self, event: &AlarmEvent, config: &DecoderConfig, now: SystemTime) -> Capture {
+        let frames = self
+            .frames
+            .iter()
+            .filter(|(received, _)| {
+                now.duration_since(*received).unwrap_or_default() <= self.window
+            })
+            .map(|(received, frame)| {
+                let interface = self
+                    .interfaces
+                    .get(usize::from(frame.bus))
+                    .or(self.interfaces.first())
+                    .map_or("", String::as_str);
+                let (_, mut value) = raw_frame_topic(frame, interface, *received);
+                value["message"] = json!(parse_eoi_can_data_with_config(frame, config));
+                value
+            })
+            .collect();
+        Capture {
+            alarm: event.alarm.name(),
+            description: event.alarm.to_string(),
+            triggered: now
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64(),
+            frames,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use draw_display::Alarm;
+    use eoi_can_decoder::{VescFault, frame};
+
+    #[test]
+    fn last_minute_before_the_alarm() {
+        let mut recorder = FlightRecorder::new(&["can0".to_string()]);
+        let start = UNIX_EPOCH + Duration::from_secs(1_718_895_900);
+        let at = |s| start + Duration::from_secs(s);
+        recorder.record(&frame!(0x101, [0x32, 0x00, 0xAB]), at(0));
+        for s in 30..=90 {
+            recorder.record(&frame!(0x7FF, [0x01]), at(s));
+        }
+        recorder.record(
+            &frame!(0x250, [0x00, 0x00, 0x20, 0x41, 0x00, 0x00, 0x80, 0x3F]),
+            at(95),
+        );
+        assert!(recorder.frames.len() == 57);
+
+        let event = AlarmEvent {
+            alarm: Alarm::MotorFault {
+                controller_id: 9,
+                fault: VescFault::OverTempFet,
+            },
+            raised: true,
+            at: Some(draw_display::Instant::now()),
+            time: None,
+        };
+        let capture = recorder.capture(&event, &DecoderConfig::default(), at(100));
+        assert!(capture.alarm == "motor-fault");
+        assert!(capture.description == "VESC 9: FET hot");
+        assert!(capture.frames.len() == 52);
+        assert!(capture.frames[0]["id"] == "0x7ff");
+        assert!(capture.frames[0]["timestamp"] == 1_718_895_940.0);
+        assert!(capture.frames[0]["message"].is_null());
+        assert!(capture.frames[51]["message"]["WaterSpeed"]["speed_kmh"] == 10.0);
+
+        let summary = json!(capture.summary(None));
+        assert!(summary["frames"] == 52);
+        assert!(summary["path"].is_null());
+    }
+}
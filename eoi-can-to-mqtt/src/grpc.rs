@@ -0,0 +1,209 @@
+//! gRPC `Telemetry` service of `eoi-can-decoder/proto/eoi_can.proto`, for race-ops software that
+//! reads the telemetry without MQTT. Every publish interval the latest value of every signal is
+//! taken as snapshot, `Live` streams them as they are taken and `History` returns the ones kept in
+//! memory.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use eoi_can_decoder::can_frame::CanFrame;
+use eoi_can_decoder::current::CurrentConvention;
+use tokio::sync::watch;
+use tokio_stream::wrappers::WatchStream;
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status};
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+mod service {
+    include!(concat!(env!("OUT_DIR"), "/eoi_can.Telemetry.rs"));
+}
+
+use service::telemetry_server::{Telemetry, TelemetryServer};
+
+/// Snapshots kept for `History`, at the default interval 30 minutes
+const HISTORY_SIZE: usize = 1800;
+
+/// Values not received again for this long are left out of the snapshots, like the signals of a
+/// device that was switched off
+const STALE_AFTER: Duration = Duration::from_secs(10);
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SignalValue {
+    /// Number of the `Signal` enum, see `eoi_can_decoder::proto`
+    #[prost(int32, tag = "1")]
+    pub signal: i32,
+    #[prost(uint32, tag = "2")]
+    pub node: u32,
+    #[prost(double, tag = "3")]
+    pub value: f64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Snapshot {
+    #[prost(uint64, tag = "1")]
+    pub timestamp_ms: u64,
+    #[prost(message, repeated, tag = "2")]
+    pub values: Vec<SignalValue>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct LiveRequest {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HistoryRequest {
+    #[prost(uint64, tag = "1")]
+    pub from_ms: u64,
+    /// 0 for up to now
+    #[prost(uint64, tag = "2")]
+    pub to_ms: u64,
+}
+
+/// Takes the snapshots from the decoded frames, the server gets them through [`Shared`]
+pub struct GrpcServer {
+    /// Latest value by signal and node with the time it was received
+    latest: BTreeMap<(i32, u8), (f64, SystemTime)>,
+    interval: Duration,
+    last_snapshot: Option<SystemTime>,
+    current_convention: CurrentConvention,
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    live: watch::Sender<Arc<Snapshot>>,
+    history: Mutex<VecDeque<Arc<Snapshot>>>,
+}
+
+impl GrpcServer {
+    /// Start serving on `address` in the background, errors are logged. A snapshot is taken at
+    /// most every `interval`
+    pub fn spawn(address: SocketAddr, interval: Duration) -> Self {
+        let shared = Arc::new(Shared {
+            live: watch::Sender::new(Arc::default()),
+            history: Mutex::new(VecDeque::with_capacity(HISTORY_SIZE)),
+        });
+        let service = TelemetryService {
+            shared: shared.clone(),
+        };
+        tokio::spawn(async move {
+            info!("gRPC server listening on {}", address);
+            if let Err(error) = tonic::transport::Server::builder()
+                .add_service(TelemetryServer::new(service))
+                .serve(address)
+                .await
+            {
+                error!("gRPC server on {} failed: {:?}", address, error);
+            }
+        });
+        Self {
+            latest: BTreeMap::new(),
+            interval,
+            last_snapshot: None,
+            current_convention: CurrentConvention::DECODED,
+            shared,
+        }
+    }
+
+    /// Serve the currents of the battery in `current_convention`
+    pub fn with_current_convention(self, current_convention: CurrentConvention) -> Self {
+        Self {
+            current_convention,
+            ..self
+        }
+    }
+
+    /// Keep the values of the signals in `frame`, received at `now`
+    pub fn insert(&mut self, frame: &CanFrame, now: SystemTime) {
+        for (signal, node, value) in
+            eoi_can_decoder::proto::signal_values_in(frame, self.current_convention)
+        {
+            self.latest.insert((signal, node), (value, now));
+        }
+    }
+
+    /// Take a snapshot when the interval is over, call this after inserting the frames
+    pub fn update(&mut self, now: SystemTime) {
+        if self
+            .last_snapshot
+            .is_some_and(|last| now.duration_since(last).unwrap_or_default() < self.interval)
+        {
+            return;
+        }
+        self.last_snapshot = Some(now);
+        self.latest.retain(|_, (_, received)| {
+            now.duration_since(*received).unwrap_or_default() < STALE_AFTER
+        });
+
+        let snapshot = Arc::new(Snapshot {
+            timestamp_ms: unix_ms(now),
+            values: self
+                .latest
+                .iter()
+                .map(|(&(signal, node), &(value, _))| SignalValue {
+                    signal,
+                    node: node.into(),
+                    value,
+                })
+                .collect(),
+        });
+        if let Ok(mut history) = self.shared.history.lock() {
+            if history.len() == HISTORY_SIZE {
+                history.pop_front();
+            }
+            history.push_back(snapshot.clone());
+        }
+        self.shared.live.send_replace(snapshot);
+    }
+}
+
+fn unix_ms(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+struct TelemetryService {
+    shared: Arc<Shared>,
+}
+
+type SnapshotStream = Pin<Box<dyn Stream<Item = Result<Snapshot, Status>> + Send>>;
+
+// The error type is given by tonic
+#[allow(clippy::result_large_err)]
+#[tonic::async_trait]
+impl Telemetry for TelemetryService {
+    type LiveStream = SnapshotStream;
+    type HistoryStream = SnapshotStream;
+
+    async fn live(
+        &self,
+        _request: Request<LiveRequest>,
+    ) -> Result<Response<Self::LiveStream>, Status> {
+        // Starts with the latest snapshot, slow clients skip snapshots
+        let stream = WatchStream::new(self.shared.live.subscribe())
+            .map(|snapshot| Ok(Snapshot::clone(&snapshot)));
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn history(
+        &self,
+        request: Request<HistoryRequest>,
+    ) -> Result<Response<Self::HistoryStream>, Status> {
+        let HistoryRequest { from_ms, to_ms } = request.into_inner();
+        let snapshots: Vec<Result<Snapshot, Status>> = self
+            .shared
+            .history
+            .lock()
+            .map_err(|_| Status::internal("History unavailable"))?
+            .iter()
+            .filter(|snapshot| {
+                snapshot.timestamp_ms >= from_ms && (to_ms == 0 || snapshot.timestamp_ms <= to_ms)
+            })
+            .map(|snapshot| Ok(Snapshot::clone(snapshot)))
+            .collect();
+        Ok(Response::new(Box::pin(tokio_stream::iter(snapshots))))
+    }
+}
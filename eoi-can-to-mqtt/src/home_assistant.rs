@@ -0,0 +1,197 @@
+//! Home Assistant MQTT discovery for the key sensors, so dashboards fill themselves.
+//!
+//! The sensors read the subsystem topics (see [`crate::topics`]), the value templates use the
+//! field names serde gives the decoded data.
+
+use serde_json::{Value, json};
+
+pub const DISCOVERY_PREFIX: &str = "homeassistant";
+
+struct Sensor {
+    object_id: String,
+    name: String,
+    value_template: String,
+    unit: &'static str,
+    device_class: &'static str,
+}
+
+impl Sensor {
+    fn field(
+        object_id: String,
+        name: String,
+        field: &str,
+        unit: &'static str,
+        device_class: &'static str,
+    ) -> Self {
+        Self {
+            object_id,
+            name,
+            value_template: format!("{{{{ value_json.{} }}}}", field),
+            unit,
+            device_class,
+        }
+    }
+}
+
+/// Discovery config messages (topic and payload) for the sensors found on a subsystem topic,
+/// `topic` is relative to `<prefix>/<boat>/` and `state_topic` is the full topic
+pub fn discovery_configs(topic: &str, state_topic: &str, boat: &str) -> Vec<(String, Value)> {
+    let parts: Vec<&str> = topic.split('/').collect();
+    let sensors = match parts.as_slice() {
+        ["battery", "soc"] => vec![Sensor::field(
+            "battery_soc".to_string(),
+            "Battery SoC".to_string(),
+            "state_of_charge",
+            "%",
+            "battery",
+        )],
+        ["battery", "cells", "13-14"] => vec![Sensor::field(
+            "battery_pack_voltage".to_string(),
+            "Battery pack voltage".to_string(),
+            "pack_voltage",
+            "V",
+            "voltage",
+        )],
+        ["gnss", "speed"] => vec![Sensor::field(
+            "speed".to_string(),
+            "Speed".to_string(),
+            "speed",
+            "km/h",
+            "speed",
+        )],
+        ["mppt", id, "power"] => vec![Sensor {
+            object_id: format!("mppt_{}_power", id),
+            name: format!("MPPT {} solar power", id),
+            value_template: "{{ (value_json.voltage_out * value_json.current_out) | round(1) }}"
+                .to_string(),
+            unit: "W",
+            device_class: "power",
+        }],
+        ["vesc", id, "status-4"] => vec![
+            Sensor::field(
+                format!("vesc_{}_motor_temperature", id),
+                format!("Motor {} temperature", id),
+                "motor_temp",
+                "°C",
+                "temperature",
+            ),
+            Sensor::field(
+                format!("vesc_{}_fet_temperature", id),
+                format!("Motor {} FET temperature", id),
+                "fet_temp",
+                "°C",
+                "temperature",
+            ),
+        ],
+        _ => Vec::new(),
+    };
+
+    let node_id = format!("eoi_{}", boat);
+    sensors
+        .into_iter()
+        .map(|sensor| {
+            let unique_id = format!("{}_{}", node_id, sensor.object_id);
+            (
+                format!(
+                    "{}/sensor/{}/{}/config",
+                    DISCOVERY_PREFIX, node_id, sensor.object_id
+                ),
+                json!({
+                    "name": sensor.name,
+                    "unique_id": unique_id,
+                    "state_topic": state_topic,
+                    "value_template": sensor.value_template,
+                    "unit_of_measurement": sensor.unit,
+                    "device_class": sensor.device_class,
+                    "state_class": "measurement",
+                    "device": {
+                        "identifiers": [node_id],
+                        "name": format!("EoI {}", boat),
+                        "manufacturer": "Engineers of Innovation",
+                    },
+                }),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topics::subsystem_topic;
+    use assert2::assert;
+    use eoi_can_decoder::{
+        CellVoltages13_14PackAndStack, EoiBattery, EoiCanData, GnssData, MpptData, MpptInfo,
+        MpptPower, SocErrorFlagsAndBalancing, VescData, VescMessage,
+    };
+
+    /// Every field used in a value template has to exist in the published JSON
+    #[test]
+    fn templates_use_serialized_fields() {
+        let data = [
+            EoiCanData::EoiBattery(EoiBattery::SocErrorFlagsAndBalancing(
+                SocErrorFlagsAndBalancing {
+                    state_of_charge: 80.0,
+                    error_flags: 0,
+                    balancing_status: 0,
+                },
+            )),
+            EoiCanData::EoiBattery(EoiBattery::CellVoltages13_14PackAndStack(
+                CellVoltages13_14PackAndStack {
+                    cell_voltage: [4.0, 4.0],
+                    pack_voltage: 56.0,
+                    stack_voltage: 56.0,
+                },
+            )),
+            EoiCanData::Gnss(GnssData::GnssSpeedAndHeading(20.0, 90.0)),
+            EoiCanData::Mppt(MpptData::Id2(MpptInfo::Power(MpptPower {
+                voltage_out: 50.0,
+                current_out: 2.0,
+            }))),
+            EoiCanData::Vesc(
+                VescData::from_node_id(
+                    9,
+                    VescMessage::StatusMessage4 {
+                        fet_temp: 30.0,
+                        motor_temp: 40.0,
+                        total_input_current: 0.0,
+                        current_pid_position: 0.0,
+                    },
+                )
+                .unwrap(),
+            ),
+        ];
+
+        let mut sensors = 0;
+        for data in &data {
+            let (topic, value) = subsystem_topic(data).unwrap();
+            for (_, config) in discovery_configs(&topic, "state", "test") {
+                let template = config["value_template"].as_str().unwrap();
+                for field in template.split("value_json.").skip(1) {
+                    let field: String = field
+                        .chars()
+                        .take_while(|c| c.is_alphanumeric() || *c == '_')
+                        .collect();
+                    assert!(
+                        value.get(&field).is_some(),
+                        "{} missing in {}",
+                        field,
+                        topic
+                    );
+                }
+                sensors += 1;
+            }
+        }
+        assert!(sensors == 6);
+    }
+
+    #[test]
+    fn discovery_topic() {
+        let configs = discovery_configs("mppt/3/power", "eoi/boat/mppt/3/power", "boat");
+        assert!(configs.len() == 1);
+        assert!(configs[0].0 == "homeassistant/sensor/eoi_boat/mppt_3_power/config");
+        assert!(configs[0].1["unique_id"] == "eoi_boat_mppt_3_power");
+        assert!(configs[0].1["state_topic"] == "eoi/boat/mppt/3/power");
+        assert!(discovery_configs("mppt/3/status", "", "boat").is_empty());
+    }
+}
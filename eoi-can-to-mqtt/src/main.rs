@@ -1,82 +1,487 @@
-use clap::Parser;
+use bandwidth::BandwidthManager;
+use can_logger::CanLogger;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
+use csv_export::CsvExport;
+use diagnostics::{DEFAULT_BUS_BITRATE, Diagnostics};
+use draw_display::{
+    Alarm, CellImbalance, ClockCheck, DEFAULT_CLOCK_DIVERGENCE, DEFAULT_IMBALANCE_THRESHOLD,
+    DEFAULT_RESERVE_STATE_OF_CHARGE, DEFAULT_SOC_DISAGREEMENT, FinishLine, LapCounter, PanelId,
+    ReserveMode, SocSelection, SocSource, SolarPanels,
+};
 use embedded_can::Frame;
-use eoi_can_decoder::{can_collector, parse_eoi_can_data};
-use get_wifi_ip::get_wifi_ip;
+use eoi_can_decoder::calibration::Calibration;
+use eoi_can_decoder::can_encoder::encode_eoi_can_data;
+use eoi_can_decoder::current::CurrentConvention;
+use eoi_can_decoder::dbc_database::DbcDatabase;
+use eoi_can_decoder::transmit_queue::Priority;
+use eoi_can_decoder::{
+    DecoderConfig, EoiCanData, MAX_MPPT_DEVICES, MAX_VESC_CONTROLLERS,
+    NODE_IDENTIFICATION_INTERVAL_S, VescData, can_collector, parse_eoi_can_data,
+};
+use eoi_can_source::{CanReader, CanTransmitter, LogFilter, LogFilterCommand, install_panic_hook};
+use flight_recorder::FlightRecorder;
+use get_wifi_ip::wifi_status;
 use json_patch::merge;
+use mqtt_settings::{
+    OFFLINE, PayloadFormat, RECONNECT_MAX_INTERVAL, RECONNECT_MIN_INTERVAL, TopicMode,
+};
+use outbox::Outbox;
 use paho_mqtt as mqtt;
-use rand::Rng;
-use rand::distr::Alphanumeric;
+use publish_filter::PublishFilter;
 use serde_json::json;
+use session_export::{ExportSessionCommand, GpxTrack, SessionExport, Upload};
+use std::collections::HashSet;
 use std::env;
+#[cfg(any(feature = "http", feature = "grpc"))]
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use systemstat::{Platform, System};
+use tokio::signal::unix::{SignalKind, signal};
 use tokio::time::Instant;
 #[allow(unused_imports)]
 use tracing::{Level, debug, error, info, trace, warn};
-use tracing_subscriber::filter::{EnvFilter, LevelFilter};
-use tracing_subscriber::prelude::*;
+use tracing_subscriber::filter::LevelFilter;
 
+mod bandwidth;
+#[cfg(test)]
+mod broker_tests;
+mod commands;
+mod csv_export;
+mod diagnostics;
+mod display_log;
+#[cfg(test)]
+mod fixtures;
+mod flight_recorder;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod home_assistant;
 mod mqtt_settings;
+mod outbox;
+mod panic_report;
+#[cfg(test)]
+mod pipeline_tests;
+mod publish_filter;
+mod session_export;
+mod tick;
+mod topics;
+
+const COMMAND_BUFFER_SIZE: usize = 16;
+/// Raw frames waiting to be published, more are dropped so the CAN reader never waits for the
+/// broker
+const RAW_FRAME_BUFFER_SIZE: usize = 256;
+/// Snapshots kept while the broker can't be reached, at the default interval 10 minutes
+const OFFLINE_BUFFER_SIZE: usize = 600;
+/// How often values are checked when publishing on change
+const CHANGE_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+/// The node inventory is first published once every node had the time to identify itself
+const NODE_INVENTORY_AFTER: Duration =
+    Duration::from_secs(NODE_IDENTIFICATION_INTERVAL_S as u64 + 5);
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    /// CAN interface
-    #[arg(short, long, default_value_t = String::from("can0"))]
-    can_interface: String,
+    /// CAN interface, give it multiple times to read more than one bus
+    #[arg(short, long = "can-interface", default_values_t = [String::from("can0")])]
+    can_interfaces: Vec<String>,
+
+    /// Bring the CAN interface up with this bitrate when it is down, like 500000, which needs
+    /// `CAP_NET_ADMIN`
+    #[arg(long)]
+    can_bitrate: Option<u32>,
+
+    /// Never send anything on the bus, for attaching to a bus inspected by others. An interface
+    /// brought up with `--can-bitrate` doesn't even acknowledge frames, commands from MQTT are
+    /// dropped
+    #[arg(long)]
+    listen_only: bool,
+
+    /// Sign of the published battery and peripheral currents: charge-positive like the decoder,
+    /// or discharge-positive. Everything published is converted: MQTT, the CSV export, HTTP,
+    /// Signal K and gRPC
+    #[arg(long, default_value = "charge-positive")]
+    current_convention: CurrentConvention,
+
+    /// Count frames with the same ID and payload arriving within this many milliseconds once,
+    /// like when a bus is read directly and over the UDP bridge. 0 keeps every frame
+    #[arg(long, default_value_t = 0)]
+    dedup_window: u64,
+
+    /// Name of the WiFi interface, `*` matches any characters
+    #[arg(long, default_value_t = String::from(get_wifi_ip::DEFAULT_INTERFACE_PATTERN))]
+    wifi_interface: String,
+
+    /// VESC controller ID, give it multiple times for boats with more than one motor
+    #[arg(
+        long = "vesc-id",
+        default_values_t = [DecoderConfig::DEFAULT_VESC_CONTROLLER_ID],
+        value_parser = clap::value_parser!(u8).range(..i64::from(VescData::BROADCAST_CONTROLLER_ID))
+    )]
+    vesc_ids: Vec<u8>,
+
+    /// Also decode frames longer than their message, like from nodes with a newer protocol
+    #[arg(long)]
+    permissive_dlc: bool,
+
+    /// MPPTs on the bus, with the IDs from 0 up. Other nodes may use the IDs of the others
+    #[arg(long, default_value_t = MAX_MPPT_DEVICES as u8)]
+    mppt_devices: u8,
+
+    /// Solar panels in the order they are numbered, by the MPPT ID and the channel they are wired
+    /// to, like 2.1,2.2,5.0. Without it the panels of the boat
+    #[arg(long, value_delimiter = ',')]
+    mppt_panels: Vec<PanelId>,
+
+    /// Every solar panel seen on the bus ordered by MPPT ID and channel, instead of `--mppt-panels`
+    #[arg(long, conflicts_with = "mppt_panels")]
+    observed_panels: bool,
+
+    /// Correct signals with a known error of their sensor before decoding them, by the scale and
+    /// offset per signal of a TOML file like `eoi-can-decoder/calibration.toml`
+    #[arg(long)]
+    calibration: Option<PathBuf>,
+
+    /// DBC file used to decode frames the decoder doesn't know, published under "Dbc"
+    #[arg(long)]
+    dbc: Option<PathBuf>,
+
+    /// CAN ID (hex) commands from MQTT may be sent to, give it multiple times to allow more.
+    /// Commands for other IDs are dropped, by default nothing is sent on the bus. Commands are
+    /// sent on the first CAN interface
+    #[arg(long = "allow-can-id", value_parser = parse_hex_id)]
+    allowed_can_ids: Vec<u32>,
+
+    /// Publish every received frame of the `--raw-id`s on `raw/<id>`, as hex with the time it was
+    /// received, for debugging a node remotely. Not retained
+    #[arg(long, requires = "raw_ids")]
+    raw_topic: bool,
+
+    /// CAN ID (hex) published with `--raw-topic`, give it multiple times for more
+    #[arg(long = "raw-id", value_parser = parse_hex_id)]
+    raw_ids: Vec<u32>,
+
+    /// Directory to write candump compatible logs of all received frames to
+    #[arg(long)]
+    log_dir: Option<PathBuf>,
+
+    /// Directory to write every frame of the minute before a critical alarm to, decoded as JSON.
+    /// A summary of the capture is published on "flight-recorder/capture"
+    #[arg(long)]
+    flight_recorder: Option<PathBuf>,
+
+    /// CSV file to write all decoded data to, one row per publish interval
+    #[arg(long)]
+    csv: Option<PathBuf>,
+
+    /// Directory the `export-session` command writes the archive of the session to, the log
+    /// directory by default
+    #[arg(long)]
+    export_dir: Option<PathBuf>,
+
+    /// Endpoint the `export-session` command sends the archive to with `{"upload": "http"}`, like
+    /// http://192.168.1.10:8000/upload
+    #[arg(long, value_parser = parse_export_url)]
+    export_url: Option<String>,
+
+    /// Start/finish line for counting laps, as latitude,longitude of both ends,
+    /// like 43.7340,7.4210,43.7345,7.4220. The laps are published under "laps"
+    #[arg(long)]
+    finish_line: Option<FinishLine>,
+
+    /// State of charge in percent below which the displays switch to the reserve mode, its start
+    /// and end are published on "battery/reserve"
+    #[arg(long, default_value_t = DEFAULT_RESERVE_STATE_OF_CHARGE)]
+    reserve_soc: f32,
+
+    /// Difference between the highest and lowest cell voltage in V which, sustained for 10
+    /// seconds, raises the cell imbalance alarm, its start and end are published on
+    /// "battery/cell-imbalance"
+    #[arg(long, default_value_t = DEFAULT_IMBALANCE_THRESHOLD)]
+    imbalance_threshold: f32,
+
+    /// Divergence in s of the system clock from the GNSS time which raises the clock alarm, as
+    /// it stamps the published data. Its start and end are published on "gnss/clock-divergence"
+    #[arg(long, default_value_t = DEFAULT_CLOCK_DIVERGENCE)]
+    clock_divergence: f32,
+
+    /// Sources of the state of charge in the order they are preferred, a later one is shown
+    /// while the ones before it are stale: bms, ah-count and voltage. The others are ignored,
+    /// the voltage follows a generic curve and has to be added for packs it fits
+    #[arg(long, value_delimiter = ',', default_value = "bms,ah-count")]
+    soc_sources: Vec<SocSource>,
+
+    /// Capacity of the pack in Ah, for counting its charge from the pack current
+    #[arg(long)]
+    pack_capacity: Option<f32>,
+
+    /// Difference in percentage points between two sources of the state of charge which,
+    /// sustained for 30 seconds, raises the SoC disagreement alarm, its start
+    /// and end are published on "battery/soc-disagreement"
+    #[arg(long, default_value_t = DEFAULT_SOC_DISAGREEMENT)]
+    soc_disagreement: f32,
+
+    /// Correct the time to empty, the range and the energy budget of the race for a cold pack,
+    /// with the usable state of charge by temperature of a TOML file like
+    /// `draw-display/soc-correction.toml`
+    #[arg(long)]
+    soc_correction: Option<PathBuf>,
+
+    /// Address to serve the display data on over HTTP (`/api/state`, `/api/alarms` and `/metrics`),
+    /// like 0.0.0.0:8080
+    #[cfg(feature = "http")]
+    #[arg(long)]
+    http: Option<SocketAddr>,
+
+    /// Send position, speed, course and battery as Signal K deltas over UDP to this address, like
+    /// 192.168.1.255:8375 for the chart plotters on the support boat
+    #[cfg(feature = "http")]
+    #[arg(long)]
+    signal_k_udp: Option<SocketAddr>,
+
+    /// Address to serve the `Telemetry` gRPC service of `eoi-can-decoder/proto/eoi_can.proto`
+    /// on, like 0.0.0.0:50051
+    #[cfg(feature = "grpc")]
+    #[arg(long)]
+    grpc: Option<SocketAddr>,
+
+    #[command(flatten)]
+    mqtt: mqtt_settings::MqttSettings,
 }
 
-fn register_tracing_subscriber(level_filter: LevelFilter) {
-    tracing_subscriber::registry()
-        .with(
-            EnvFilter::builder()
-                .with_default_directive(level_filter.into())
-                .from_env_lossy(),
-        )
-        .with(
-            tracing_subscriber::fmt::layer()
-                .with_file(true)
-                .with_line_number(true),
-        )
-        .init();
+fn parse_hex_id(id: &str) -> Result<u32, std::num::ParseIntError> {
+    u32::from_str_radix(id.trim_start_matches("0x"), 16)
+}
+
+fn parse_export_url(url: &str) -> Result<String, String> {
+    match session_export::parse_http_url(url) {
+        Some(_) => Ok(url.to_string()),
+        None => Err(format!("{} is not an http:// URL", url)),
+    }
+}
+
+/// Connected to `target`, which may be a broadcast address
+#[cfg(feature = "http")]
+fn open_udp_socket(target: SocketAddr) -> std::io::Result<std::net::UdpSocket> {
+    let local: SocketAddr = if target.is_ipv4() {
+        (std::net::Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (std::net::Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+    let socket = std::net::UdpSocket::bind(local)?;
+    socket.set_broadcast(target.is_ipv4())?;
+    socket.set_nonblocking(true)?;
+    socket.connect(target)?;
+    Ok(socket)
+}
+
+/// Write the archive of the session and upload it, returns what is published on
+/// `session/export`
+async fn export_session(
+    session_export: SessionExport,
+    track: GpxTrack,
+    upload: Option<Upload>,
+    export_url: Option<&str>,
+    client: &mqtt::AsyncClient,
+    archive_topic: &str,
+    qos: i32,
+) -> std::io::Result<serde_json::Value> {
+    let archive = tokio::task::spawn_blocking(move || session_export.export(&track))
+        .await
+        .map_err(std::io::Error::other)??;
+    info!(
+        "Exported the session to {:?}, {} bytes",
+        archive.path, archive.size
+    );
+    let mut payload = json!(archive);
+    match upload {
+        Some(Upload::Mqtt) => {
+            let chunks = session_export::upload_mqtt(client, archive_topic, qos, &archive).await?;
+            payload["chunks"] = json!(chunks);
+        }
+        Some(Upload::Http) => {
+            let url = export_url.ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "No --export-url to upload the session to",
+                )
+            })?;
+            session_export::upload_http(url, &archive).await?;
+            info!("Uploaded the session to {}", url);
+        }
+        None => {}
+    }
+    payload["upload"] = json!(upload);
+    Ok(payload)
 }
 
 #[tokio::main]
 async fn main() -> Result<(), core::convert::Infallible> {
-    register_tracing_subscriber(LevelFilter::DEBUG);
+    let log_filter = LogFilter::init(LevelFilter::DEBUG);
+    install_panic_hook("eoi-can-to-mqtt");
     let args = Args::parse();
-    info!("CAN interface: {}", args.can_interface);
+    info!("CAN interfaces: {:?}", args.can_interfaces);
+    info!("VESC controller IDs: {:?}", args.vesc_ids);
+    let calibration = args
+        .calibration
+        .as_ref()
+        .map_or_else(Calibration::default, |path| {
+            let text = std::fs::read_to_string(path).expect("Unable to read the calibration");
+            toml::from_str(&text).expect("Invalid calibration")
+        });
+    for signal in &calibration.signals {
+        info!(
+            "Calibrated {} of {}: * {} + {}",
+            signal.signal.name, signal.message.name, signal.scale, signal.offset
+        );
+    }
+    let Some(decoder_config) = DecoderConfig::with_vesc_controller_ids(&args.vesc_ids) else {
+        Args::command()
+            .error(
+                ErrorKind::TooManyValues,
+                format!(
+                    "--vesc-id can be given at most {} times",
+                    MAX_VESC_CONTROLLERS
+                ),
+            )
+            .exit();
+    };
+    let decoder_config = decoder_config
+        .with_permissive_dlc(args.permissive_dlc)
+        .with_mppt_devices(args.mppt_devices)
+        .with_calibration(calibration);
+    let dbc_database = args.dbc.as_ref().map(|path| {
+        let database = DbcDatabase::load(path).unwrap_or_else(|error| panic!("{}", error));
+        info!(
+            "Loaded {} messages from DBC file {:?}",
+            database.len(),
+            path
+        );
+        database
+    });
 
-    let shared_can_collector = Arc::new(Mutex::new(can_collector::CanCollector::new()));
+    let shared_can_collector = Arc::new(Mutex::new(
+        can_collector::CanCollector::new().with_dedup_window(args.dedup_window),
+    ));
 
     let can_collector_receiver = shared_can_collector.clone();
+    let shared_log_records = Arc::new(Mutex::new(Vec::new()));
+    let log_records_receiver = shared_log_records.clone();
+    let shared_panic_reports = Arc::new(Mutex::new(Vec::new()));
+    let panic_reports_receiver = shared_panic_reports.clone();
+    let shared_diagnostics = Arc::new(Mutex::new(
+        Diagnostics::new(
+            args.can_bitrate.unwrap_or(DEFAULT_BUS_BITRATE),
+            Instant::now(),
+        )
+        .with_calibration(decoder_config.calibration().clone()),
+    ));
+    let diagnostics_receiver = shared_diagnostics.clone();
+    let shared_flight_recorder = args.flight_recorder.as_ref().map(|dir| {
+        info!(
+            "Recording the last minute before critical alarms to {:?}",
+            dir
+        );
+        Arc::new(Mutex::new(FlightRecorder::new(&args.can_interfaces)))
+    });
+    let flight_recorder_receiver = shared_flight_recorder.clone();
+    let (capture_sender, mut captures) = tokio::sync::mpsc::unbounded_channel();
 
-    let can_sock: socketcan::tokio::AsyncCanSocket<socketcan::CanSocket> =
-        socketcan::tokio::AsyncCanSocket::open(args.can_interface.as_str())
-            .expect("Unable to open CAN socket");
-    info!("Connected to CAN interface: {}", args.can_interface);
+    let (_, mut can_frames) =
+        eoi_can_source::spawn_can_readers(args.can_interfaces.iter().map(|interface| {
+            let reader = CanReader::new(interface).with_listen_only(args.listen_only);
+            match args.can_bitrate {
+                Some(bitrate) => reader.with_bring_up(bitrate),
+                None => reader,
+            }
+        }));
+    let mut can_states = can_frames.states();
+
+    let mut can_logger = args.log_dir.as_ref().map(|dir| {
+        info!("Logging CAN frames to {:?}", dir);
+        CanLogger::new(dir, &args.can_interfaces[0])
+            .expect("Unable to create CAN log file")
+            .with_interfaces(&args.can_interfaces)
+    });
 
+    let mut csv_export = args.csv.as_ref().map(|path| {
+        info!("Exporting decoded data to {:?}", path);
+        CsvExport::new(path).expect("Unable to create CSV file")
+    });
+
+    let mut session_export = SessionExport::new(
+        args.export_dir
+            .as_ref()
+            .or(args.log_dir.as_ref())
+            .map_or(Path::new("."), PathBuf::as_path),
+        SystemTime::now(),
+    );
+    if let Some(log_dir) = &args.log_dir {
+        session_export = session_export.with_log_dir(log_dir);
+    }
+    if let Some(csv) = &args.csv {
+        session_export = session_export.with_csv(csv);
+    }
+    let export_url = args.export_url.clone();
+    let mut gpx_track = GpxTrack::default();
+
+    #[cfg(feature = "http")]
+    let status = args.http.map(|address| {
+        status_server::Status::default()
+            .with_log_filter(log_filter.clone())
+            .start(address)
+    });
+    #[cfg(feature = "http")]
+    let mut signal_k_udp = args.signal_k_udp.map(|target| {
+        info!("Sending Signal K deltas to {}", target);
+        let socket = open_udp_socket(target).expect("Unable to open UDP socket for Signal K");
+        (socket, status_server::signal_k::SignalK::new())
+    });
+    #[cfg(feature = "grpc")]
+    let mut grpc = args.grpc.map(|address| {
+        grpc::GrpcServer::spawn(address, args.mqtt.publish_interval)
+            .with_current_convention(args.current_convention)
+    });
+    // Also keeps the lap count, so it is always updated
+    let mut display_data = draw_display::DisplayData::default();
+    if let Some(finish_line) = args.finish_line {
+        info!("Counting laps over the finish line {:?}", finish_line);
+        display_data.lap_counter = LapCounter::new(finish_line);
+    }
+    display_data.reserve = ReserveMode::new(args.reserve_soc);
+    display_data.cell_imbalance = CellImbalance::new(args.imbalance_threshold);
+    display_data.clock_check = ClockCheck::new(args.clock_divergence);
+    let soc_selection = SocSelection::new(&args.soc_sources, args.soc_disagreement);
+    display_data.soc_selection = match args.pack_capacity {
+        Some(capacity_ah) => soc_selection.with_capacity_ah(capacity_ah),
+        None => soc_selection,
+    };
+    info!("SoC sources: {:?}", display_data.soc_selection.order());
+    if let Some(path) = &args.soc_correction {
+        let text = std::fs::read_to_string(path).expect("Unable to read the SoC correction");
+        display_data.soc_correction = toml::from_str(&text).expect("Invalid SoC correction");
+        info!("SoC correction: {:?}", display_data.soc_correction);
+    }
+    display_data.mppt_panel_info = if args.observed_panels {
+        SolarPanels::observed()
+    } else if args.mppt_panels.is_empty() {
+        SolarPanels::default()
+    } else {
+        SolarPanels::configured(&args.mppt_panels).expect("Too many solar panels given")
+    };
+
+    let mqtt_settings = args.mqtt;
+    info!("MQTT broker: {}", mqtt_settings.broker);
     let mut trust_store = env::current_dir().unwrap();
-    trust_store.push(mqtt_settings::TRUST_STORE);
+    trust_store.push(&mqtt_settings.trust_store);
 
     if !trust_store.exists() {
         panic!("The trust store file does not exist: {:?}", trust_store);
     }
 
-    let create_opts = mqtt::CreateOptionsBuilder::new()
-        .server_uri(mqtt_settings::BROKER.to_string())
-        .client_id(format!("eoi-can-to-mqtt-{}", {
-            let rand_string: String = rand::rng()
-                .sample_iter(&Alphanumeric)
-                .take(8)
-                .map(char::from)
-                .collect();
-            rand_string
-        }))
-        .finalize();
-
-    let client = mqtt::Client::new(create_opts).unwrap_or_else(|err| {
+    let mut client = mqtt::AsyncClient::new(mqtt_settings.create_options()).unwrap_or_else(|err| {
         panic!("Error creating the client: {:?}", err);
     });
 
@@ -85,51 +490,272 @@ async fn main() -> Result<(), core::convert::Infallible> {
         .unwrap()
         .finalize();
 
-    let conn_opts = mqtt::ConnectOptionsBuilder::new()
-        .ssl_options(ssl_opts)
-        .keep_alive_interval(Duration::from_secs(20))
-        .clean_session(true)
-        .user_name(mqtt_settings::USER.to_string())
-        .password(mqtt_settings::PASSWORD.to_string())
-        .finalize();
+    let conn_opts = mqtt_settings.connect_options(Some(ssl_opts));
+    client.set_connected_callback(mqtt_settings.on_connected());
+    let reconnects = Arc::new(AtomicU64::new(0));
+    let lost_connections = reconnects.clone();
+    client.set_connection_lost_callback(move |_| {
+        warn!("Connection to MQTT broker lost, reconnecting");
+        lost_connections.fetch_add(1, Ordering::Relaxed);
+    });
 
-    if let Err(error) = client.connect(conn_opts.clone()) {
-        panic!("Unable to connect to MQTT broker: {:?}", error);
-    }
+    // Get the stream before connecting, so no commands get lost
+    let command_stream = client.get_stream(COMMAND_BUFFER_SIZE);
 
-    // Spawn a task to read CAN frames
+    // Automatic reconnect only kicks in after the first connection, so retry that ourselves
+    let connect_client = client.clone();
     tokio::spawn(async move {
-        loop {
-            let frame = can_sock.read_frame().await.unwrap();
-
-            let embedded_frame = if let socketcan::CanFrame::Data(frame) = frame {
-                trace!(
-                    "Received CAN frame: ID: {:?}, Data: {:?}",
-                    frame.id(),
-                    frame.data()
+        let mut retry_interval = RECONNECT_MIN_INTERVAL;
+        while let Err(error) = connect_client.connect(conn_opts.clone()).await {
+            warn!(
+                "Unable to connect to MQTT broker, retrying in {:?}: {:?}",
+                retry_interval, error
+            );
+            tokio::time::sleep(retry_interval).await;
+            retry_interval = (retry_interval * 2).min(RECONNECT_MAX_INTERVAL);
+        }
+    });
+
+    tokio::spawn(panic_report::publish_pending(
+        client.clone(),
+        mqtt_settings.subsystem_topic("error"),
+        mqtt_settings.qos,
+        mqtt_settings.payload_format,
+    ));
+
+    info!("CAN IDs allowed for commands: {:x?}", args.allowed_can_ids);
+    let command_transmitter = if args.listen_only {
+        info!("Listen-only, commands are not sent on the bus");
+        CanTransmitter::listen_only()
+    } else {
+        // The data is still published without commands
+        match socketcan::tokio::AsyncCanSocket::open(args.can_interfaces[0].as_str()) {
+            Ok(command_sock) => CanTransmitter::spawn(command_sock).1,
+            Err(error) => {
+                error!(
+                    "Unable to open CAN socket for commands, they are not sent on the bus: {}",
+                    error
                 );
+                CanTransmitter::listen_only()
+            }
+        }
+    };
+    let (export_sender, mut export_requests) = tokio::sync::mpsc::channel(1);
+    let allowed_can_ids = args.allowed_can_ids;
+    let command_topic_prefix = mqtt_settings.command_topic_prefix();
 
-                eoi_can_decoder::can_frame::CanFrame::from_encoded(frame.id(), frame.data())
-            } else {
-                debug!("Received non-data CAN frame: {:?}", frame);
+    tokio::spawn(async move {
+        while let Ok(message) = command_stream.recv().await {
+            // `None` signals a lost connection, which is reconnected automatically
+            let Some(message) = message else {
+                continue;
+            };
+            let Some(command) = message.topic().strip_prefix(command_topic_prefix.as_str()) else {
                 continue;
             };
 
+            // Handled by the datalogger itself, nothing is sent on the bus
+            if command == "export-session" {
+                match ExportSessionCommand::parse(message.payload()) {
+                    Ok(export) => {
+                        if export_sender.try_send(export).is_err() {
+                            warn!("An export of the session is already pending");
+                        }
+                    }
+                    Err(error) => warn!("Invalid command payload: {}", error),
+                }
+                continue;
+            }
+
+            if command == "log-filter" {
+                let result = serde_json::from_slice::<LogFilterCommand>(message.payload())
+                    .map_err(|error| error.to_string())
+                    .and_then(|command| log_filter.apply(&command));
+                if let Err(error) = result {
+                    warn!("Invalid log filter command: {}", error);
+                }
+                continue;
+            }
+
+            let data = match commands::parse_command(command, message.payload()) {
+                Ok(data) => data,
+                Err(error) => {
+                    warn!("{}", error);
+                    continue;
+                }
+            };
+            let Some(frames) = data
+                .iter()
+                .map(encode_eoi_can_data)
+                .collect::<Option<Vec<_>>>()
+            else {
+                warn!("Unable to encode command {:?}", data);
+                continue;
+            };
+
+            // A command is only sent when all of its frames are allowed
+            let not_allowed = frames.iter().find_map(|frame| {
+                let id = match frame.id {
+                    embedded_can::Id::Standard(id) => id.as_raw() as u32,
+                    embedded_can::Id::Extended(id) => id.as_raw(),
+                };
+                (!allowed_can_ids.contains(&id)).then_some(id)
+            });
+            if let Some(id) = not_allowed {
+                warn!("Command {} for CAN ID {:#x} is not allowed", command, id);
+                continue;
+            }
+
+            for (frame, data) in frames.iter().zip(&data) {
+                let Some(socket_frame) = socketcan::CanFrame::new(frame.id, &frame.data) else {
+                    continue;
+                };
+                command_transmitter.send_with_priority(Priority::of(data), socket_frame);
+                info!("Sending command {}: {:?}", command, data);
+            }
+        }
+    });
+
+    let raw_ids = if args.raw_topic {
+        info!("Publishing the raw frames of: {:x?}", args.raw_ids);
+        args.raw_ids.clone()
+    } else {
+        Vec::new()
+    };
+    let raw_client = client.clone();
+    let (raw_sender, mut raw_receiver) = tokio::sync::mpsc::channel(RAW_FRAME_BUFFER_SIZE);
+    tokio::spawn(async move {
+        while let Some(message) = raw_receiver.recv().await {
+            if let Err(error) = raw_client.publish(message).await {
+                debug!("Failed to publish a raw frame: {:?}", error);
+            }
+        }
+    });
+    let raw_connected = client.clone();
+    let raw_interfaces = args.can_interfaces.clone();
+    let raw_topic_prefix = mqtt_settings.subsystem_topic("");
+    let raw_payload_format = mqtt_settings.payload_format;
+
+    // Spawn a task to read CAN frames
+    tokio::spawn(async move {
+        // Time base of the de-duplication window
+        let reader_start = std::time::Instant::now();
+        let mut log_assembler = display_log::LogAssembler::default();
+        let mut panic_assembler = display_log::PanicAssembler::default();
+        while let Some((frame, received)) = can_frames.recv_timestamped().await {
+            if let Ok(mut diagnostics) = diagnostics_receiver.lock() {
+                diagnostics.received(&frame);
+            }
+            if let Some(recorder) = &flight_recorder_receiver
+                && let Ok(mut recorder) = recorder.lock()
+            {
+                recorder.record(&frame, received);
+            }
+            if let Some(logger) = can_logger.as_mut()
+                && let Err(error) = logger.log_at(
+                    received.duration_since(UNIX_EPOCH).unwrap_or_default(),
+                    &frame,
+                )
+            {
+                warn!("Failed to log CAN frame: {:?}", error);
+            }
+
+            if raw_ids.contains(&topics::raw_id(&frame)) && raw_connected.is_connected() {
+                let interface = raw_interfaces.get(usize::from(frame.bus));
+                let (topic, value) = topics::raw_frame_topic(
+                    &frame,
+                    interface.unwrap_or(&raw_interfaces[0]),
+                    received,
+                );
+                let message = mqtt::Message::new(
+                    format!("{}{}", raw_topic_prefix, topic),
+                    raw_payload_format.encode(&value),
+                    mqtt::QOS_0,
+                );
+                if raw_sender.try_send(message).is_err() {
+                    debug!("Dropping a raw frame, the broker can't keep up");
+                    if let Ok(mut diagnostics) = diagnostics_receiver.lock() {
+                        diagnostics.raw_frame_dropped();
+                    }
+                }
+            }
+
+            // Every frame of a log record is needed, the collector only keeps the latest one
+            match parse_eoi_can_data(&frame) {
+                Some(EoiCanData::DisplayLog(log)) => {
+                    if let Some(record) = log_assembler.push(log)
+                        && let Ok(mut records) = log_records_receiver.lock()
+                    {
+                        records.push(record);
+                    }
+                    continue;
+                }
+                Some(EoiCanData::DisplayPanic(part)) => {
+                    if let Some(report) = panic_assembler.push(part)
+                        && let Ok(mut reports) = panic_reports_receiver.lock()
+                    {
+                        reports.push(report);
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+
             if let Ok(mut collector) = can_collector_receiver.lock() {
-                collector.insert(embedded_frame);
+                collector.insert_at(frame, reader_start.elapsed().as_millis() as u64);
             }
         }
     });
 
     let process_start = Instant::now();
+    // Sent by systemd when the datalogger powers off, like on a low display battery
+    let mut terminate = signal(SignalKind::terminate()).expect("Unable to listen for SIGTERM");
+    let mut outbox = Outbox::new(OFFLINE_BUFFER_SIZE);
+    let mut bandwidth = mqtt_settings
+        .adaptive_bandwidth
+        .then(|| BandwidthManager::new(&mqtt_settings.subsystem_topic("")));
+    let subsystem_mode = mqtt_settings.topic_mode == TopicMode::Subsystem;
+    if mqtt_settings.home_assistant && !subsystem_mode {
+        warn!("Home Assistant discovery needs the subsystem topics, it is disabled");
+    }
+    let payload_format = mqtt_settings.payload_format;
+    if mqtt_settings.home_assistant && payload_format != PayloadFormat::Json {
+        warn!(
+            "Home Assistant reads the states as JSON, they are published as {:?}",
+            payload_format
+        );
+    }
+    if mqtt_settings.publish_on_change.is_some() && !subsystem_mode {
+        warn!("Publishing on change needs the subsystem topics, publishing every interval");
+    }
+    let tick_interval = if mqtt_settings.publish_on_change.is_some() && subsystem_mode {
+        CHANGE_CHECK_INTERVAL.min(mqtt_settings.publish_interval)
+    } else {
+        mqtt_settings.publish_interval
+    };
+    let mut publish_filter = PublishFilter::new(
+        mqtt_settings.publish_interval,
+        mqtt_settings.publish_on_change,
+    );
+    // Discovery configs are sent the first time a subsystem topic shows up
+    let mut discovered_topics = HashSet::new();
+    let mut underperforming_panels = Vec::new();
+    let mut charge_diverging = false;
+    let mut inventory_published = false;
+    let mut alarms_recorded = display_data.alarm_history.recorded();
     let sys = System::new();
 
-    tokio::time::sleep(Duration::from_secs(1)).await;
+    tokio::time::sleep(tick_interval).await;
 
     loop {
+        // Left over by the flush of the last tick, so the link didn't keep up
+        let queue_depth = outbox.len();
         if let Ok(mut can_collector) = shared_can_collector.lock() {
             if can_collector.get_dropped_frames() > 0 {
-                trace!("Dropped frames: {}", can_collector.get_dropped_frames());
+                warn!(
+                    "Dropped {} frames, the collector is full",
+                    can_collector.get_dropped_frames()
+                );
             }
             let mut parsed_frames = 0_u32;
             let system_uptime = sys.uptime().unwrap_or_default().as_secs();
@@ -146,49 +772,524 @@ async fn main() -> Result<(), core::convert::Infallible> {
                 0
             };
             let cpu_temperature = sys.cpu_temp().unwrap_or_default();
-            let wifi_ip = if let Some(ip) = get_wifi_ip() {
+            display_data.can_interface_up.update(!can_states.any_down());
+            if let Some(fault) = can_states.new_fault() {
+                display_data.can_bus_fault.update(fault.label());
+            }
+            let can_interfaces: serde_json::Map<String, serde_json::Value> = can_states
+                .iter()
+                .map(|(interface, state)| (interface.to_string(), json!(state)))
+                .collect();
+            let can_errors: serde_json::Map<String, serde_json::Value> = can_states
+                .error_counts()
+                .map(|(interface, errors)| (interface.to_string(), json!(errors)))
+                .collect();
+            let wifi = wifi_status(&args.wifi_interface, true);
+            let wifi_ip = if let Some(ip) = wifi.as_ref().and_then(|wifi| wifi.ipv4()) {
                 ip.to_string()
             } else {
                 "N/A".to_string()
             };
-            let mut merged_json = json!({ "DataLogger": { "Uptime": { "System": system_uptime, "Process": process_uptime }, "CpuLoad1M": cpu_usage_m1, "CpuTemp": cpu_temperature, "MemoryUsage": memory_percent_used, "WifiIp": wifi_ip } });
+            let data_logger = json!({ "Uptime": { "System": system_uptime, "Process": process_uptime }, "CpuLoad1M": cpu_usage_m1, "CpuTemp": cpu_temperature, "MemoryUsage": memory_percent_used, "WifiIp": wifi_ip, "WifiAddresses": wifi.as_ref().map(|wifi| &wifi.addresses), "WifiSignal": wifi.as_ref().and_then(|wifi| wifi.signal).map(|signal| signal.level_dbm), "CanInterfaces": can_interfaces, "CanErrors": can_errors, "CanDuplicates": can_collector.get_suppressed_duplicates() });
+            let mut merged_json = json!({ "DataLogger": data_logger });
+            let mut snapshot = Vec::new();
+            let now = Instant::now().into_std();
+            let mut publish_subsystem = |topic: &str, value: &serde_json::Value| {
+                let state_topic = mqtt_settings.subsystem_topic(topic);
+                if mqtt_settings.home_assistant && discovered_topics.insert(topic.to_string()) {
+                    for (config_topic, config) in
+                        home_assistant::discovery_configs(topic, &state_topic, &mqtt_settings.boat)
+                    {
+                        snapshot.push(mqtt::Message::new_retained(
+                            config_topic,
+                            config.to_string(),
+                            mqtt_settings.qos,
+                        ));
+                    }
+                }
+                if !publish_filter.should_publish(topic, value, now) {
+                    return;
+                }
+                snapshot.push(mqtt::Message::new_retained(
+                    state_topic,
+                    payload_format.encode(value),
+                    mqtt_settings.qos,
+                ));
+            };
+            if subsystem_mode {
+                publish_subsystem("datalogger", &merged_json["DataLogger"]);
+            }
 
             can_collector.iter().for_each(|frame| {
-                trace!("Paring CAN frame: {:?}", frame);
-                if let Some(data) = parse_eoi_can_data(frame) {
-                    trace!("{:?}", data);
-                    if let Ok(json) = serde_json::to_value(&data) {
-                        trace!("{:?}", json);
-                        merge(&mut merged_json, &json);
-                    } else {
-                        warn!("Failed to serialize json of {:?}", data)
+                #[cfg(feature = "http")]
+                if let Some(status) = &status {
+                    let interface = args.can_interfaces.get(usize::from(frame.bus));
+                    status.publish_frame(interface.unwrap_or(&args.can_interfaces[0]), frame);
+                }
+                #[cfg(feature = "grpc")]
+                if let Some(grpc) = grpc.as_mut() {
+                    grpc.insert(frame, SystemTime::now());
+                }
+                parsed_frames = parsed_frames.saturating_add(1);
+            });
+            // The merged document is also the row of the CSV export
+            let merge_frames = !subsystem_mode || csv_export.is_some();
+            let undecoded = tick::process_frames(
+                can_collector.iter(),
+                &decoder_config,
+                args.current_convention,
+                &mut display_data,
+                merge_frames.then_some(&mut merged_json),
+                |data| {
+                    if subsystem_mode && let Some((topic, value)) = topics::subsystem_topic(data) {
+                        publish_subsystem(&topic, &value);
+                    }
+                    #[cfg(feature = "http")]
+                    if let Some(status) = &status {
+                        status.publish(data);
+                    }
+                    #[cfg(feature = "http")]
+                    if let Some((socket, signal_k)) = signal_k_udp.as_mut()
+                        && let Some(delta) = signal_k.delta(data, SystemTime::now())
+                        && let Err(error) = socket.send(delta.to_string().as_bytes())
+                    {
+                        debug!("Failed to send Signal K delta: {:?}", error);
+                    }
+                    // Only the position, the convention of the currents doesn't matter
+                    gpx_track.update(data, SystemTime::now());
+                },
+            );
+            for frame in undecoded {
+                if let Some(message) = dbc_database
+                    .as_ref()
+                    .and_then(|database| database.decode(frame))
+                {
+                    trace!("{:?}", message);
+                    if subsystem_mode {
+                        publish_subsystem(
+                            &format!("dbc/{}", message.name),
+                            &json!(message.signals),
+                        );
+                    }
+                    if merge_frames {
+                        merge(
+                            &mut merged_json,
+                            &json!({ "Dbc": { message.name: message.signals } }),
+                        );
                     }
                 } else {
                     warn!("Failed to parse data from CAN frame: {:?}", frame);
+                    if let Ok(mut diagnostics) = shared_diagnostics.lock() {
+                        diagnostics.decode_failed(frame);
+                    }
                 }
-                parsed_frames = parsed_frames.saturating_add(1);
-            });
+            }
             trace!("Parsed frames: {}", parsed_frames);
+            eoi_can_source::log_new_id_collisions(&mut can_collector);
+            if let Ok(mut diagnostics) = shared_diagnostics.lock() {
+                diagnostics.collected(
+                    can_collector.get_replaced_frames(),
+                    can_collector.get_dropped_frames(),
+                );
+                diagnostics.id_collisions(can_collector.id_collisions());
+            }
             can_collector.clear();
 
-            // Send merged JSON to MQTT
-            let mqtt_message = mqtt::Message::new(
-                mqtt_settings::TOPIC.to_string(),
-                merged_json.to_string(),
-                mqtt::QOS_1,
+            if args.finish_line.is_some() {
+                let laps = json!(display_data.lap_counter);
+                if subsystem_mode {
+                    publish_subsystem("laps", &laps);
+                }
+                if !subsystem_mode || csv_export.is_some() {
+                    merge(&mut merged_json, &json!({ "Laps": laps }));
+                }
+            }
+
+            // Numbered from 1 like on the display
+            let underperforming: Vec<usize> =
+                draw_display::underperforming_panels(&display_data.mppt_panel_info)
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &underperforming)| underperforming)
+                    .map(|(panel, _)| panel + 1)
+                    .collect();
+            if underperforming != underperforming_panels {
+                if !underperforming.is_empty() {
+                    warn!(
+                        "Solar panels producing less than their peers: {:?}",
+                        underperforming
+                    );
+                }
+                underperforming_panels = underperforming;
+            }
+            let underperforming = json!(underperforming_panels);
+            if subsystem_mode {
+                publish_subsystem("solar/underperforming", &underperforming);
+            }
+            if !subsystem_mode || csv_export.is_some() {
+                merge(
+                    &mut merged_json,
+                    &json!({ "Solar": { "Underperforming": underperforming } }),
+                );
+            }
+
+            let charge_check = &display_data.charge_check;
+            if charge_check.diverging != charge_diverging {
+                charge_diverging = charge_check.diverging;
+                if charge_diverging {
+                    warn!(
+                        "MPPT output current {:?} A and BMS charge current {:?} A diverge, check the wiring and the MPPT outputs",
+                        charge_check.mppt_current, charge_check.battery_current
+                    );
+                } else {
+                    info!("MPPT output and BMS charge current agree again");
+                }
+            }
+            let charge_check = json!(charge_check);
+            if subsystem_mode {
+                publish_subsystem("solar/charge_check", &charge_check);
+            }
+            if !subsystem_mode || csv_export.is_some() {
+                merge(
+                    &mut merged_json,
+                    &json!({ "Solar": { "ChargeCheck": charge_check } }),
+                );
+            }
+
+            // The published data is stamped by the system clock, which NTP may not have set
+            display_data.check_clock(
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_micros() as u64,
             );
-            if let Err(e) = client.publish(mqtt_message) {
-                error!("Failed to publish message: {:?}", e);
-                if matches!(e, mqtt::Error::Disconnected) {
-                    client
-                        .connect(conn_opts.clone())
-                        .expect("Unable to reconnect");
+            // Like the log records an event, so it isn't retained and is published in both modes
+            if let Some(active) = display_data.reserve.take_change() {
+                info!("Reserve mode: {}", active);
+                snapshot.push(mqtt::Message::new(
+                    mqtt_settings.subsystem_topic("battery/reserve"),
+                    payload_format.encode(&json!({
+                        "active": active,
+                        "state_of_charge": display_data.usable_state_of_charge(),
+                        "threshold": display_data.reserve.threshold(),
+                    })),
+                    mqtt_settings.qos,
+                ));
+            }
+            if let Some(active) = display_data.cell_imbalance.take_change() {
+                warn!("Cell imbalance: {}", active);
+                snapshot.push(mqtt::Message::new(
+                    mqtt_settings.subsystem_topic("battery/cell-imbalance"),
+                    payload_format.encode(&json!({
+                        "active": active,
+                        "difference": display_data.cell_voltage_difference(),
+                        "threshold": display_data.cell_imbalance.threshold(),
+                    })),
+                    mqtt_settings.qos,
+                ));
+            }
+            if let Some(active) = display_data.clock_check.take_change() {
+                warn!("Clock off GNSS time: {}", active);
+                snapshot.push(mqtt::Message::new(
+                    mqtt_settings.subsystem_topic("gnss/clock-divergence"),
+                    payload_format.encode(&json!({
+                        "active": active,
+                        "divergence": display_data.clock_check.divergence(),
+                        "threshold": display_data.clock_check.threshold(),
+                    })),
+                    mqtt_settings.qos,
+                ));
+            }
+            if let Some(active) = display_data.soc_selection.take_change() {
+                warn!("SoC sources disagree: {}", active);
+                let selection = &display_data.soc_selection;
+                snapshot.push(mqtt::Message::new(
+                    mqtt_settings.subsystem_topic("battery/soc-disagreement"),
+                    payload_format.encode(&json!({
+                        "active": active,
+                        "bms": selection.bms.get(),
+                        "ah_count": selection.ah_count.get(),
+                        "voltage": selection.voltage.get(),
+                        "selected": selection.selected(),
+                        "threshold": selection.disagreement(),
+                    })),
+                    mqtt_settings.qos,
+                ));
+            }
+            while let Some((controller_id, fault)) = display_data.take_motor_fault_change() {
+                warn!("Motor controller {} fault: {:?}", controller_id, fault);
+                snapshot.push(mqtt::Message::new(
+                    mqtt_settings.subsystem_topic("motor/fault"),
+                    payload_format.encode(&json!({
+                        "controller_id": controller_id,
+                        "active": fault.is_fault(),
+                        "fault": fault,
+                        "code": u8::from(fault),
+                        "label": fault.label(),
+                    })),
+                    mqtt_settings.qos,
+                ));
+            }
+
+            if let Some(summary) = display_data.lap_history.take_completed() {
+                info!(
+                    "Lap {} in {:.1} s, {:.1} Wh",
+                    summary.lap,
+                    summary.time.as_millis() as f32 / 1000.0,
+                    summary.energy_wh
+                );
+                let mut payload = json!(summary);
+                payload["best"] = json!(display_data.lap_history.best() == Some(&summary));
+                snapshot.push(mqtt::Message::new(
+                    mqtt_settings.subsystem_topic("laps/summary"),
+                    payload_format.encode(&payload),
+                    mqtt_settings.qos,
+                ));
+            }
+
+            // Log records are events, so they aren't retained and are published in both modes
+            if let Ok(mut records) = shared_log_records.lock() {
+                for record in records.drain(..) {
+                    debug!("Display log: {:?}", record);
+                    snapshot.push(mqtt::Message::new(
+                        mqtt_settings.subsystem_topic("display/log"),
+                        payload_format.encode(&json!(record)),
+                        mqtt_settings.qos,
+                    ));
                 }
-            } else {
-                debug!("Published message: {:?}", merged_json);
             }
+            if let Ok(mut reports) = shared_panic_reports.lock() {
+                for report in reports.drain(..) {
+                    error!("Display firmware panicked: {:?}", report);
+                    snapshot.push(mqtt::Message::new(
+                        mqtt_settings.subsystem_topic("error"),
+                        payload_format.encode(&json!(report)),
+                        mqtt_settings.qos,
+                    ));
+                }
+            }
+
+            let new_alarms: Vec<_> = display_data
+                .alarm_history
+                .since(alarms_recorded)
+                .copied()
+                .collect();
+            alarms_recorded = display_data.alarm_history.recorded();
+            for event in new_alarms
+                .iter()
+                .filter(|event| event.alarm == Alarm::BatteryCutoff)
+            {
+                warn!("BMS cut off: {}", event.raised);
+                snapshot.push(mqtt::Message::new(
+                    mqtt_settings.subsystem_topic("battery/cutoff"),
+                    payload_format.encode(&json!({
+                        "active": event.raised,
+                        "discharge_state": display_data.battery_discharge_state.get(),
+                    })),
+                    mqtt_settings.qos,
+                ));
+            }
+
+            // The frames leading up to the alarm, not retained and published in both modes
+            let critical_alarms: Vec<_> = new_alarms
+                .into_iter()
+                .filter(|event| event.raised && event.alarm.is_critical())
+                .collect();
+            if let (Some(recorder), Some(dir)) = (&shared_flight_recorder, &args.flight_recorder)
+                && !critical_alarms.is_empty()
+                && let Ok(recorder) = recorder.lock().map(|recorder| recorder.clone())
+            {
+                // Decoding a minute of frames takes a while, the reader keeps recording meanwhile
+                let dir = dir.clone();
+                let decoder_config = decoder_config.clone();
+                let capture_sender = capture_sender.clone();
+                let now = SystemTime::now();
+                tokio::task::spawn_blocking(move || {
+                    for event in &critical_alarms {
+                        let capture = recorder.capture(event, &decoder_config, now);
+                        warn!(
+                            "Critical alarm {}, captured {} frames",
+                            capture.description,
+                            capture.frames.len()
+                        );
+                        let path = match capture.write(&dir) {
+                            Ok(path) => {
+                                info!("Wrote the flight recorder to {:?}", path);
+                                Some(path)
+                            }
+                            Err(error) => {
+                                warn!("Failed to write the flight recorder: {:?}", error);
+                                None
+                            }
+                        };
+                        let _ = capture_sender.send(capture.summary(path));
+                    }
+                });
+            }
+            while let Ok(summary) = captures.try_recv() {
+                snapshot.push(mqtt::Message::new(
+                    mqtt_settings.subsystem_topic("flight-recorder/capture"),
+                    payload_format.encode(&json!(summary)),
+                    mqtt_settings.qos,
+                ));
+            }
+
+            // At the session start and again when a node shows up or is flashed, in both modes
+            if process_start.elapsed() >= NODE_INVENTORY_AFTER
+                && (display_data.node_inventory.take_change() || !inventory_published)
+            {
+                info!(
+                    "Node inventory: {} nodes",
+                    display_data.node_inventory.iter().count()
+                );
+                snapshot.push(mqtt::Message::new_retained(
+                    mqtt_settings.subsystem_topic("session/inventory"),
+                    payload_format.encode(&json!(display_data.node_inventory)),
+                    mqtt_settings.qos,
+                ));
+                inventory_published = true;
+            }
+
+            // Retained like the subsystem topics, but published in both modes
+            if let Ok(mut diagnostics) = shared_diagnostics.lock()
+                && diagnostics.is_due(Instant::now())
+            {
+                let report = diagnostics.report(
+                    &args.can_interfaces,
+                    reconnects.load(Ordering::Relaxed),
+                    Instant::now(),
+                );
+                debug!("Diagnostics: {:?}", report);
+                snapshot.push(mqtt::Message::new_retained(
+                    mqtt_settings.subsystem_topic("bridge/diagnostics"),
+                    payload_format.encode(&json!(report)),
+                    mqtt_settings.qos,
+                ));
+            }
+
+            #[cfg(feature = "http")]
+            if let Some(status) = &status {
+                status.update(&display_data);
+            }
+            #[cfg(feature = "grpc")]
+            if let Some(grpc) = grpc.as_mut() {
+                grpc.update(SystemTime::now());
+            }
+
+            if let Some(export) = csv_export.as_mut() {
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default();
+                if let Err(error) = export.write_row(timestamp, &merged_json) {
+                    warn!("Failed to write CSV row: {:?}", error);
+                }
+            }
+
+            if !subsystem_mode {
+                snapshot.push(mqtt::Message::new(
+                    mqtt_settings.topic.clone(),
+                    payload_format.encode(&merged_json),
+                    mqtt_settings.qos,
+                ));
+            }
+            if let Some(bandwidth) = bandwidth.as_mut() {
+                if let Some(report) = bandwidth.take_change() {
+                    warn!("Uplink bandwidth changed: {:?}", report);
+                    snapshot.push(mqtt::Message::new_retained(
+                        mqtt_settings.subsystem_topic("bridge/bandwidth"),
+                        payload_format.encode(&json!(report)),
+                        mqtt_settings.qos,
+                    ));
+                }
+                snapshot = bandwidth.apply(snapshot);
+            }
+            outbox.push(snapshot);
         }
 
-        tokio::time::sleep(Duration::from_secs(1)).await;
+        if let Ok(command) = export_requests.try_recv() {
+            let session_export = session_export.clone();
+            let track = gpx_track.clone();
+            let client = client.clone();
+            let export_url = export_url.clone();
+            let topic = mqtt_settings.subsystem_topic("session/export");
+            let archive_topic = mqtt_settings.subsystem_topic("session/archive");
+            let qos = mqtt_settings.qos;
+            tokio::spawn(async move {
+                let result = export_session(
+                    session_export,
+                    track,
+                    command.upload,
+                    export_url.as_deref(),
+                    &client,
+                    &archive_topic,
+                    qos,
+                )
+                .await;
+                let payload = match result {
+                    Ok(payload) => payload,
+                    Err(error) => {
+                        error!("Failed to export the session: {:?}", error);
+                        json!({ "error": error.to_string() })
+                    }
+                };
+                let message = mqtt::Message::new(topic, payload_format.encode(&payload), qos);
+                if let Err(error) = client.publish(message).await {
+                    warn!("Failed to publish the session export: {:?}", error);
+                }
+            });
+        }
+
+        // Snapshots are kept until the broker has them, oldest first
+        let flush_start = Instant::now();
+        outbox.flush(&client).await;
+        if let Some(bandwidth) = bandwidth.as_mut()
+            && client.is_connected()
+        {
+            let level = bandwidth.level();
+            bandwidth.observe(
+                queue_depth,
+                flush_start.elapsed(),
+                mqtt_settings.publish_interval,
+                Instant::now().into_std(),
+            );
+            // The waiting snapshots are the first to cross the saturated link
+            if bandwidth.level() != level {
+                outbox.retain(|message| bandwidth.keeps(message));
+            }
+        }
+        if !outbox.is_empty() {
+            debug!("Snapshots waiting for the broker: {}", outbox.len());
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(tick_interval) => {}
+            _ = terminate.recv() => break,
+        }
+    }
+
+    // Not retained, so it only tells who is listening right now
+    info!("Terminated, publishing that the datalogger is going down");
+    if client.is_connected() {
+        let message = mqtt::Message::new(
+            mqtt_settings.subsystem_topic("datalogger/shutdown"),
+            payload_format.encode(&json!({ "state": "going down" })),
+            mqtt_settings.qos,
+        );
+        if let Err(error) = client.publish(message).await {
+            error!("Failed to publish the shutdown: {:?}", error);
+        }
+        // The will is only published when the connection drops
+        let offline = mqtt::Message::new_retained(
+            mqtt_settings.availability_topic(),
+            OFFLINE,
+            mqtt_settings.qos,
+        );
+        if let Err(error) = client.publish(offline).await {
+            warn!("Failed to publish that the bridge is offline: {:?}", error);
+        }
+        if let Err(error) = client.disconnect(None).await {
+            warn!("Failed to disconnect from the MQTT broker: {:?}", error);
+        }
     }
+    Ok(())
 }
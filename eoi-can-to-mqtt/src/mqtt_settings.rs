@@ -1,5 +1,219 @@
+use clap::{Args, ValueEnum};
+use eoi_can_source::parse_seconds;
+use paho_mqtt as mqtt;
+use rand::Rng;
+use rand::distr::Alphanumeric;
+use std::path::PathBuf;
+use std::time::Duration;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
 pub const BROKER: &str = "ssl://git.engineersofinnovation.nl:8883";
 pub const USER: &str = "engineer";
 pub const PASSWORD: &str = "EoI-42";
 pub const TRUST_STORE: &str = "certs/isrgrootx1.pem";
 pub const TOPIC: &str = "eoi-can-to-mqtt";
+pub const TOPIC_PREFIX: &str = "eoi";
+pub const BOAT: &str = "boat";
+
+pub const RECONNECT_MIN_INTERVAL: Duration = Duration::from_secs(1);
+pub const RECONNECT_MAX_INTERVAL: Duration = Duration::from_secs(60);
+/// Payloads of the availability topic
+pub const ONLINE: &str = "online";
+pub const OFFLINE: &str = "offline";
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum TopicMode {
+    /// Every subsystem on its own retained topic, `<prefix>/<boat>/battery/soc` etc.
+    Subsystem,
+    /// Everything merged into one JSON document on `--topic`
+    Legacy,
+}
+
+/// Encoding of the published payloads, all from the same serde model
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum PayloadFormat {
+    Json,
+    /// About half the size of JSON, for metered cellular uplinks
+    Cbor,
+    #[value(name = "msgpack")]
+    MessagePack,
+}
+
+impl PayloadFormat {
+    pub fn encode(self, value: &serde_json::Value) -> Vec<u8> {
+        match self {
+            PayloadFormat::Json => value.to_string().into_bytes(),
+            PayloadFormat::Cbor => {
+                let mut payload = Vec::new();
+                // Writing to a `Vec` doesn't fail and a JSON value is always representable
+                ciborium::into_writer(value, &mut payload).expect("Failed to encode CBOR");
+                payload
+            }
+            PayloadFormat::MessagePack => {
+                rmp_serde::to_vec_named(value).expect("Failed to encode MessagePack")
+            }
+        }
+    }
+}
+
+// Broker settings, the constants above are the defaults for our own broker
+#[derive(Args, Debug)]
+pub struct MqttSettings {
+    /// MQTT broker URI
+    #[arg(long, env = "MQTT_BROKER", default_value_t = String::from(BROKER))]
+    pub broker: String,
+
+    /// MQTT client ID, a random one is used when not given
+    #[arg(long, env = "MQTT_CLIENT_ID")]
+    pub client_id: Option<String>,
+
+    /// MQTT user name
+    #[arg(long, env = "MQTT_USER", default_value_t = String::from(USER))]
+    pub user: String,
+
+    /// MQTT password
+    #[arg(long, env = "MQTT_PASSWORD", default_value_t = String::from(PASSWORD), hide_env_values = true, hide_default_value = true)]
+    pub password: String,
+
+    /// Certificate(s) used to verify the broker
+    #[arg(long, env = "MQTT_TRUST_STORE", default_value = TRUST_STORE)]
+    pub trust_store: PathBuf,
+
+    /// How the decoded CAN data is published
+    #[arg(long, env = "MQTT_TOPIC_MODE", value_enum, default_value_t = TopicMode::Legacy)]
+    pub topic_mode: TopicMode,
+
+    /// Topic the merged JSON document is published on in legacy mode
+    #[arg(long, env = "MQTT_TOPIC", default_value_t = String::from(TOPIC))]
+    pub topic: String,
+
+    /// Prefix of the subsystem and command topics, commands are received on
+    /// `<prefix>/command/<command>`
+    #[arg(long, env = "MQTT_TOPIC_PREFIX", default_value_t = String::from(TOPIC_PREFIX))]
+    pub topic_prefix: String,
+
+    /// Name of the boat in the subsystem topics
+    #[arg(long, env = "MQTT_BOAT", default_value_t = String::from(BOAT))]
+    pub boat: String,
+
+    /// Publish Home Assistant discovery configs for the key sensors, needs the subsystem topics
+    #[arg(long, env = "MQTT_HOME_ASSISTANT")]
+    pub home_assistant: bool,
+
+    /// Seconds between publishing the same topic again
+    #[arg(long, env = "MQTT_PUBLISH_INTERVAL", default_value = "1", value_parser = parse_seconds)]
+    pub publish_interval: Duration,
+
+    /// Publish a subsystem topic as soon as one of its values changed by more than this delta,
+    /// instead of only once every publish interval
+    #[arg(long, env = "MQTT_PUBLISH_ON_CHANGE")]
+    pub publish_on_change: Option<f64>,
+
+    /// Drop the low priority topics, like the per-cell voltages, and then all but the alarms when
+    /// the link can't keep up with the publish interval. The alarms are published with QoS 1 at
+    /// least and the level on "bridge/bandwidth"
+    #[arg(long, env = "MQTT_ADAPTIVE_BANDWIDTH")]
+    pub adaptive_bandwidth: bool,
+
+    /// Encoding of the published data, commands and Home Assistant discovery configs stay JSON
+    #[arg(long, env = "MQTT_PAYLOAD_FORMAT", value_enum, default_value_t = PayloadFormat::Json)]
+    pub payload_format: PayloadFormat,
+
+    /// Quality of service used for publishing and subscribing
+    #[arg(long, env = "MQTT_QOS", default_value_t = 1, value_parser = clap::value_parser!(i32).range(0..=2))]
+    pub qos: i32,
+}
+
+impl MqttSettings {
+    pub fn subsystem_topic(&self, topic: &str) -> String {
+        format!("{}/{}/{}", self.topic_prefix, self.boat, topic)
+    }
+
+    pub fn command_topic(&self) -> String {
+        format!("{}/command/#", self.topic_prefix)
+    }
+
+    pub fn command_topic_prefix(&self) -> String {
+        format!("{}/command/", self.topic_prefix)
+    }
+
+    /// Retained `online` while the bridge is connected and `offline` once it isn't, the broker
+    /// publishes that as the will of the bridge when the connection drops without a disconnect
+    pub fn availability_topic(&self) -> String {
+        self.subsystem_topic("bridge/availability")
+    }
+
+    pub fn create_options(&self) -> mqtt::CreateOptions {
+        mqtt::CreateOptionsBuilder::new()
+            .server_uri(self.broker.clone())
+            .client_id(self.client_id.clone().unwrap_or_else(|| {
+                let rand_string: String = rand::rng()
+                    .sample_iter(&Alphanumeric)
+                    .take(8)
+                    .map(char::from)
+                    .collect();
+                format!("eoi-can-to-mqtt-{}", rand_string)
+            }))
+            .finalize()
+    }
+
+    /// A clean session with the will on the availability topic, `ssl_options` for an `ssl://`
+    /// broker
+    pub fn connect_options(&self, ssl_options: Option<mqtt::SslOptions>) -> mqtt::ConnectOptions {
+        let mut builder = mqtt::ConnectOptionsBuilder::new();
+        if let Some(ssl_options) = ssl_options {
+            builder.ssl_options(ssl_options);
+        }
+        builder
+            .keep_alive_interval(Duration::from_secs(20))
+            .clean_session(true)
+            .user_name(self.user.clone())
+            .password(self.password.clone())
+            .automatic_reconnect(RECONNECT_MIN_INTERVAL, RECONNECT_MAX_INTERVAL)
+            .will_message(mqtt::Message::new_retained(
+                self.availability_topic(),
+                OFFLINE,
+                self.qos,
+            ))
+            .finalize()
+    }
+
+    /// Called after every (re)connect: the session is clean, so the commands are subscribed to
+    /// again, and the bridge is online
+    pub fn on_connected(&self) -> impl FnMut(&mqtt::AsyncClient) + Send + 'static {
+        let command_topic = self.command_topic();
+        let online = mqtt::Message::new_retained(self.availability_topic(), ONLINE, self.qos);
+        let qos = self.qos;
+        move |client| {
+            info!("Connected to MQTT broker");
+            // The requests are sent right away, the tokens are only needed to wait for the result
+            client.subscribe(command_topic.clone(), qos);
+            client.publish(online.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use serde_json::json;
+
+    #[test]
+    fn compact_payloads_decode_to_the_same_value() {
+        // The decoder has f32 values, JSON prints them with all digits of the f64
+        let value = json!({ "Battery": { "PackVoltage": 52.4_f32, "StateOfCharge": 81, "Cells": [3.28_f32, 3.27_f32] } });
+        let json = PayloadFormat::Json.encode(&value);
+
+        let cbor = PayloadFormat::Cbor.encode(&value);
+        let decoded: serde_json::Value = ciborium::from_reader(cbor.as_slice()).unwrap();
+        assert!(decoded == value);
+        assert!(cbor.len() < json.len());
+
+        let msgpack = PayloadFormat::MessagePack.encode(&value);
+        let decoded: serde_json::Value = rmp_serde::from_slice(&msgpack).unwrap();
+        assert!(decoded == value);
+        assert!(msgpack.len() < json.len());
+    }
+}
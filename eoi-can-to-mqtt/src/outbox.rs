@@ -0,0 +1,299 @@
+//! Snapshots waiting for the broker. Every publish interval adds a snapshot with the messages of
+//! all topics, which is published once the broker has the ones before it. While the connection is
+//! down they pile up, the oldest are dropped when there are too many, and a snapshot interrupted
+//! by a lost connection goes on with the messages that didn't get through.
+
+use std::collections::VecDeque;
+use std::future::Future;
+
+use futures::future::join_all;
+use paho_mqtt as mqtt;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+/// Where the snapshots go, the MQTT client or a broker of the tests
+pub trait Publisher {
+    fn is_connected(&self) -> bool;
+    /// Sends `message` right away, the returned future completes once the broker has it
+    fn publish(&self, message: mqtt::Message) -> impl Future<Output = mqtt::Result<()>>;
+}
+
+impl Publisher for mqtt::AsyncClient {
+    fn is_connected(&self) -> bool {
+        mqtt::AsyncClient::is_connected(self)
+    }
+
+    fn publish(&self, message: mqtt::Message) -> impl Future<Output = mqtt::Result<()>> {
+        mqtt::AsyncClient::publish(self, message)
+    }
+}
+
+#[derive(Debug)]
+pub struct Outbox {
+    snapshots: VecDeque<Vec<mqtt::Message>>,
+    capacity: usize,
+}
+
+impl Outbox {
+    /// Keeps up to `capacity` snapshots while the broker can't be reached
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            snapshots: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Snapshots waiting for the broker
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    /// Queue the messages of a publish interval, dropping the oldest snapshot when full. Empty
+    /// snapshots, when nothing changed, are left out
+    pub fn push(&mut self, snapshot: Vec<mqtt::Message>) {
+        if snapshot.is_empty() {
+            return;
+        }
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    /// Drop the waiting messages `keep` returns false for, like the topics a saturated link can't
+    /// take any more
+    pub fn retain(&mut self, mut keep: impl FnMut(&mqtt::Message) -> bool) {
+        for snapshot in &mut self.snapshots {
+            snapshot.retain(&mut keep);
+        }
+        self.snapshots.retain(|snapshot| !snapshot.is_empty());
+    }
+
+    /// Publish the snapshots oldest first while connected, until one fails. The messages of a
+    /// snapshot are all sent before waiting for the broker, the ones that failed stay in the
+    /// outbox in their order
+    pub async fn flush(&mut self, publisher: &impl Publisher) {
+        while publisher.is_connected() {
+            let Some(snapshot) = self.snapshots.pop_front() else {
+                break;
+            };
+            let deliveries = snapshot
+                .iter()
+                .map(|message| publisher.publish(message.clone()))
+                .collect::<Vec<_>>();
+            let results = join_all(deliveries).await;
+
+            let mut failed = Vec::new();
+            for (message, result) in snapshot.into_iter().zip(results) {
+                match result {
+                    Ok(()) => debug!(
+                        "Published message on {}: {}",
+                        message.topic(),
+                        message.payload_str()
+                    ),
+                    Err(e) => {
+                        error!("Failed to publish message on {}: {:?}", message.topic(), e);
+                        failed.push(message);
+                    }
+                }
+            }
+            if !failed.is_empty() {
+                self.snapshots.push_front(failed);
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use std::cell::{Cell, RefCell};
+    use std::collections::BTreeMap;
+
+    /// Keeps what a subscriber to everything would get and the retained message of every topic,
+    /// and drops the connection after a number of messages
+    #[derive(Default)]
+    struct TestBroker {
+        connected: Cell<bool>,
+        /// Messages until the connection is lost, `None` to never lose it
+        messages_until_lost: Cell<Option<usize>>,
+        /// Topics it refuses messages on, while staying connected
+        refused: RefCell<Vec<&'static str>>,
+        received: RefCell<Vec<String>>,
+        retained: RefCell<BTreeMap<String, String>>,
+    }
+
+    impl TestBroker {
+        fn online() -> Self {
+            let broker = Self::default();
+            broker.connected.set(true);
+            broker
+        }
+
+        fn received(&self) -> Vec<String> {
+            self.received.take()
+        }
+    }
+
+    impl Publisher for TestBroker {
+        fn is_connected(&self) -> bool {
+            self.connected.get()
+        }
+
+        /// Takes the message at once like the client, the result is ready right away
+        fn publish(&self, message: mqtt::Message) -> impl Future<Output = mqtt::Result<()>> {
+            std::future::ready(self.receive(message))
+        }
+    }
+
+    impl TestBroker {
+        fn receive(&self, message: mqtt::Message) -> mqtt::Result<()> {
+            if let Some(left) = self.messages_until_lost.get() {
+                if left == 0 {
+                    self.connected.set(false);
+                }
+                self.messages_until_lost.set(left.checked_sub(1));
+            }
+            if !self.connected.get() {
+                return Err(mqtt::Error::Disconnected);
+            }
+            if self.refused.borrow().contains(&message.topic()) {
+                return Err(mqtt::Error::Failure);
+            }
+            let payload = message.payload_str().to_string();
+            self.received
+                .borrow_mut()
+                .push(format!("{} {}", message.topic(), payload));
+            if message.retained() {
+                self.retained
+                    .borrow_mut()
+                    .insert(message.topic().to_string(), payload);
+            }
+            Ok(())
+        }
+    }
+
+    fn snapshot(soc: u32) -> Vec<mqtt::Message> {
+        vec![
+            mqtt::Message::new_retained("eoi/boat/battery/soc", soc.to_string(), 1),
+            mqtt::Message::new_retained("eoi/boat/datalogger", "{}", 1),
+            mqtt::Message::new("eoi/boat/battery/reserve", "{\"active\":false}", 1),
+        ]
+    }
+
+    #[tokio::test]
+    async fn snapshots_wait_for_the_broker() {
+        let broker = TestBroker::default();
+        let mut outbox = Outbox::new(10);
+        outbox.push(snapshot(80));
+        outbox.push(Vec::new());
+        outbox.push(snapshot(79));
+        outbox.flush(&broker).await;
+        assert!(outbox.len() == 2);
+        assert!(broker.received().is_empty());
+
+        broker.connected.set(true);
+        outbox.flush(&broker).await;
+        assert!(outbox.is_empty());
+        let received = broker.received();
+        assert!(received.len() == 6);
+        assert!(received[0] == "eoi/boat/battery/soc 80");
+        assert!(received[3] == "eoi/boat/battery/soc 79");
+        // The events aren't retained, a new subscriber only gets the latest state
+        let retained = broker.retained.borrow();
+        assert!(
+            retained.keys().collect::<Vec<_>>() == ["eoi/boat/battery/soc", "eoi/boat/datalogger"]
+        );
+        assert!(retained["eoi/boat/battery/soc"] == "79");
+    }
+
+    #[tokio::test]
+    async fn interrupted_snapshot_goes_on_after_reconnecting() {
+        let broker = TestBroker::online();
+        broker.messages_until_lost.set(Some(4));
+        let mut outbox = Outbox::new(10);
+        outbox.push(snapshot(80));
+        outbox.push(snapshot(79));
+        outbox.flush(&broker).await;
+        assert!(broker.received().len() == 4);
+        assert!(outbox.len() == 1);
+
+        broker.connected.set(true);
+        outbox.flush(&broker).await;
+        assert!(
+            broker.received()
+                == [
+                    "eoi/boat/datalogger {}",
+                    "eoi/boat/battery/reserve {\"active\":false}"
+                ]
+        );
+        assert!(outbox.is_empty());
+    }
+
+    #[tokio::test]
+    async fn only_failed_messages_are_sent_again() {
+        let broker = TestBroker::online();
+        broker
+            .refused
+            .replace(vec!["eoi/boat/battery/soc", "eoi/boat/battery/reserve"]);
+        let mut outbox = Outbox::new(10);
+        outbox.push(snapshot(80));
+        outbox.push(snapshot(79));
+        outbox.flush(&broker).await;
+        assert!(broker.received() == ["eoi/boat/datalogger {}"]);
+        assert!(outbox.len() == 2);
+
+        broker.refused.replace(Vec::new());
+        outbox.flush(&broker).await;
+        assert!(
+            broker.received()
+                == [
+                    "eoi/boat/battery/soc 80",
+                    "eoi/boat/battery/reserve {\"active\":false}",
+                    "eoi/boat/battery/soc 79",
+                    "eoi/boat/datalogger {}",
+                    "eoi/boat/battery/reserve {\"active\":false}",
+                ]
+        );
+        assert!(outbox.is_empty());
+    }
+
+    #[tokio::test]
+    async fn oldest_snapshots_are_dropped() {
+        let broker = TestBroker::default();
+        let mut outbox = Outbox::new(2);
+        for soc in [80, 79, 78] {
+            outbox.push(snapshot(soc));
+        }
+        assert!(outbox.len() == 2);
+
+        broker.connected.set(true);
+        outbox.flush(&broker).await;
+        assert!(broker.received()[0] == "eoi/boat/battery/soc 79");
+        assert!(broker.retained.borrow()["eoi/boat/battery/soc"] == "78");
+    }
+
+    #[tokio::test]
+    async fn waiting_messages_are_dropped_by_topic() {
+        let broker = TestBroker::default();
+        let mut outbox = Outbox::new(10);
+        outbox.push(snapshot(80));
+        outbox.push(vec![mqtt::Message::new_retained(
+            "eoi/boat/battery/soc",
+            "79",
+            1,
+        )]);
+        outbox.retain(|message| message.topic() != "eoi/boat/battery/soc");
+        assert!(outbox.len() == 1);
+
+        broker.connected.set(true);
+        outbox.flush(&broker).await;
+        assert!(broker.received().len() == 2);
+    }
+}
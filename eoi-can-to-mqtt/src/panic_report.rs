@@ -0,0 +1,49 @@
+//! Panics of the binaries on the datalogger, published with their backtrace on the "error" topic
+//! like the panics the display firmware reports after its restart. Every binary keeps its panics
+//! in the panic directory (see [`eoi_can_source::install_panic_hook`]), the bridge publishes
+//! them from there once connected, also those of an earlier run of its own.
+
+use std::time::Duration;
+
+use eoi_can_source::{panic_dir, pending_panics};
+use paho_mqtt as mqtt;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::mqtt_settings::PayloadFormat;
+
+/// How often the panic directory is looked at
+const PUBLISH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Publish the reports of the panic directory on `topic` while connected, each is deleted once
+/// the broker took it
+pub async fn publish_pending(
+    client: mqtt::AsyncClient,
+    topic: String,
+    qos: i32,
+    payload_format: PayloadFormat,
+) {
+    let dir = panic_dir();
+    let mut interval = tokio::time::interval(PUBLISH_INTERVAL);
+    loop {
+        interval.tick().await;
+        if !client.is_connected() {
+            continue;
+        }
+        for pending in pending_panics(&dir) {
+            error!(
+                "{} panicked at {:?}: {}",
+                pending.report.source, pending.report.location, pending.report.message
+            );
+            let payload = serde_json::to_value(&pending.report).unwrap_or_default();
+            let message = mqtt::Message::new(topic.clone(), payload_format.encode(&payload), qos);
+            if let Err(error) = client.publish(message).await {
+                warn!("Failed to publish the panic, retrying: {:?}", error);
+                break;
+            }
+            if let Err(error) = pending.remove() {
+                warn!("Unable to remove {}: {}", pending.path.display(), error);
+            }
+        }
+    }
+}
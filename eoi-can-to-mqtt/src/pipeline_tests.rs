@@ -0,0 +1,115 @@
+//! End-to-end test of the bridge on a recorded log. The frames of `fixtures/sample.log`, four
+//! seconds of the faker cruising, go through the collector and [`process_frames`] of the main
+//! loop into the subsystem topics, the CSV export and the data model, flushed once per second of
+//! the log.
+//! The display data, the last payload of every topic and the CSV file at the end are compared
+//! with fixtures. The frames are read from the log in memory, so no CAN interface is needed, and
+//! the data model takes its time from a virtual clock following the log.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use assert2::assert;
+use can_logger::parse_candump_line;
+use draw_display::{DisplayData, Instant, VirtualClock};
+use eoi_can_decoder::DecoderConfig;
+use eoi_can_decoder::can_collector::CanCollector;
+use eoi_can_decoder::current::CurrentConvention;
+use serde_json::{Value, json};
+
+use crate::csv_export::{self, CsvExport};
+use crate::fixtures::{assert_fixture, assert_json_fixture, fixture_path};
+use crate::tick::process_frames;
+use crate::topics::subsystem_topic;
+
+/// Like the default `--publish-interval`
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+struct Pipeline {
+    collector: CanCollector,
+    decoder_config: DecoderConfig,
+    display_data: DisplayData,
+    /// The last payload of every subsystem topic
+    topics: BTreeMap<String, Value>,
+    csv_export: CsvExport,
+}
+
+impl Pipeline {
+    /// What the main loop does with the frames collected since the last flush, without the
+    /// health of the data logger, which depends on the machine
+    fn flush(&mut self, timestamp: Duration) {
+        let mut merged_json = json!({});
+        let undecoded = process_frames(
+            self.collector.iter(),
+            &self.decoder_config,
+            CurrentConvention::default(),
+            &mut self.display_data,
+            Some(&mut merged_json),
+            |data| {
+                if let Some((topic, value)) = subsystem_topic(data) {
+                    self.topics.insert(topic, value);
+                }
+            },
+        );
+        assert!(undecoded.is_empty());
+        self.collector.clear();
+        self.csv_export.write_row(timestamp, &merged_json).unwrap();
+    }
+}
+
+#[test]
+fn replayed_log_matches_the_fixtures() {
+    let log = std::fs::read_to_string(fixture_path("sample.log")).unwrap();
+    let frames: Vec<_> = log
+        .lines()
+        .filter_map(parse_candump_line)
+        .map(|(timestamp, _, frame)| (timestamp, frame))
+        .collect();
+    assert!(frames.len() == 328);
+
+    let first = frames[0].0;
+    let seed = Instant::now();
+    let clock = VirtualClock::starting_at(seed);
+    clock.install();
+    let at = |timestamp: Duration| seed + (timestamp - first).try_into().unwrap();
+
+    let csv_path =
+        std::env::temp_dir().join(format!("eoi-can-to-mqtt-replay-{}.csv", std::process::id()));
+    let mut pipeline = Pipeline {
+        collector: CanCollector::new(),
+        decoder_config: DecoderConfig::default(),
+        display_data: DisplayData::default(),
+        topics: BTreeMap::new(),
+        csv_export: CsvExport::new(&csv_path).unwrap(),
+    };
+    let mut next_flush = first + FLUSH_INTERVAL;
+    for (timestamp, frame) in frames {
+        while timestamp >= next_flush {
+            clock.set(at(next_flush));
+            pipeline.flush(next_flush);
+            next_flush += FLUSH_INTERVAL;
+        }
+        clock.set(at(timestamp));
+        pipeline
+            .collector
+            .insert_at(frame, (timestamp - first).as_millis() as u64);
+    }
+    clock.set(at(next_flush));
+    pipeline.flush(next_flush);
+    VirtualClock::uninstall();
+
+    // Parts of the export one after the other, every one with its header
+    let mut csv = String::new();
+    let mut rows = 0;
+    for part in csv_export::parts(&csv_path).collect::<Vec<_>>() {
+        let text = std::fs::read_to_string(&part).unwrap();
+        let _ = std::fs::remove_file(&part);
+        rows += text.lines().count() - 1;
+        csv += &text;
+    }
+    assert!(rows == 4);
+
+    assert_json_fixture("replay_display_data.json", &pipeline.display_data);
+    assert_json_fixture("replay_topics.json", &pipeline.topics);
+    assert_fixture("replay.csv", &csv);
+}
@@ -0,0 +1,116 @@
+//! Decides which subsystem topics get published, so high rate signals don't saturate the uplink.
+//!
+//! A topic is published when its interval elapsed, or with change based publishing as soon as one
+//! of its values changed by more than the configured delta.
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+pub struct PublishFilter {
+    interval: Duration,
+    epsilon: Option<f64>,
+    last_published: HashMap<String, (Value, Instant)>,
+}
+
+impl PublishFilter {
+    pub fn new(interval: Duration, epsilon: Option<f64>) -> Self {
+        Self {
+            interval,
+            epsilon,
+            last_published: HashMap::new(),
+        }
+    }
+
+    /// Returns true when the value has to be published, it is then remembered as last value
+    pub fn should_publish(&mut self, topic: &str, value: &Value, now: Instant) -> bool {
+        let publish = match self.last_published.get(topic) {
+            None => true,
+            Some((last_value, last_time)) => {
+                now.duration_since(*last_time) >= self.interval
+                    || self
+                        .epsilon
+                        .is_some_and(|epsilon| changed(last_value, value, epsilon))
+            }
+        };
+
+        if publish {
+            self.last_published
+                .insert(topic.to_string(), (value.clone(), now));
+        }
+        publish
+    }
+}
+
+/// Numbers count as changed when they differ more than `epsilon`, everything else on any change
+fn changed(old: &Value, new: &Value, epsilon: f64) -> bool {
+    match (old, new) {
+        (Value::Number(old), Value::Number(new)) => match (old.as_f64(), new.as_f64()) {
+            (Some(old), Some(new)) => (old - new).abs() > epsilon,
+            _ => old != new,
+        },
+        (Value::Array(old), Value::Array(new)) => {
+            old.len() != new.len()
+                || old
+                    .iter()
+                    .zip(new)
+                    .any(|(old, new)| changed(old, new, epsilon))
+        }
+        (Value::Object(old), Value::Object(new)) => {
+            old.len() != new.len()
+                || new
+                    .iter()
+                    .any(|(key, new)| old.get(key).is_none_or(|old| changed(old, new, epsilon)))
+        }
+        (old, new) => old != new,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use serde_json::json;
+
+    #[test]
+    fn publish_after_interval() {
+        let mut filter = PublishFilter::new(Duration::from_secs(1), None);
+        let start = Instant::now();
+        let value = json!({ "rpm": 1000 });
+
+        assert!(filter.should_publish("vesc/9/status-1", &value, start));
+        assert!(!filter.should_publish(
+            "vesc/9/status-1",
+            &json!({ "rpm": 2000 }),
+            start + Duration::from_millis(500)
+        ));
+        assert!(filter.should_publish(
+            "vesc/9/status-5",
+            &value,
+            start + Duration::from_millis(500)
+        ));
+        assert!(filter.should_publish("vesc/9/status-1", &value, start + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn publish_on_change() {
+        let mut filter = PublishFilter::new(Duration::from_secs(10), Some(0.5));
+        let start = Instant::now();
+
+        assert!(filter.should_publish("battery/soc", &json!({ "state_of_charge": 80.0 }), start));
+        assert!(!filter.should_publish("battery/soc", &json!({ "state_of_charge": 80.4 }), start));
+        assert!(filter.should_publish("battery/soc", &json!({ "state_of_charge": 80.6 }), start));
+        assert!(filter.should_publish("rudder/command", &json!("Initialize"), start));
+        assert!(filter.should_publish("rudder/command", &json!("Unknown"), start));
+        assert!(filter.should_publish(
+            "battery/cells/1-4",
+            &json!({ "cell_voltage": [4.0, 4.0] }),
+            start
+        ));
+        assert!(filter.should_publish(
+            "battery/cells/1-4",
+            &json!({ "cell_voltage": [4.0, 3.0] }),
+            start
+        ));
+    }
+}
@@ -0,0 +1,498 @@
+//! Bundles the logs of the running session into one archive on the `export-session` command, so
+//! collecting the data at the end of a race is a single button press on the dashboard of the
+//! chase car instead of copying files off the datalogger.
+//!
+//! The archive is an uncompressed tar named after the local time of the export like the candump
+//! logs, `session-2024-06-20_150500.tar`, with the candump logs written since the start, the CSV
+//! export and the GNSS track as GPX. It is kept in the export directory and can also be published
+//! in chunks over MQTT or sent by an HTTP POST.
+
+use crate::csv_export;
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use eoi_can_decoder::{EoiCanData, GnssData};
+use paho_mqtt as mqtt;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
+use tokio::time::timeout;
+
+/// Positions kept for the track, a day at one position per second
+const MAX_TRACK_POINTS: usize = 24 * 3600;
+/// Size of the MQTT messages the archive is split into
+pub const MQTT_CHUNK_SIZE: u64 = 256 * 1024;
+const TAR_BLOCK_SIZE: usize = 512;
+/// Largest size the octal size field of a tar header holds, larger entries are base-256 encoded
+const TAR_MAX_OCTAL_SIZE: u64 = 0o777_7777_7777;
+/// For connecting to the upload server and for its answer once the archive is sent
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+/// Slowest upload accepted while sending the archive, a stalled connection gives up instead of
+/// hanging the export
+const HTTP_MIN_UPLOAD_RATE: u64 = 64 * 1024;
+
+/// Payload of `eoi/command/export-session`, like `{"upload": "mqtt"}`. Without an upload the
+/// archive is only written to the export directory
+#[derive(Debug, Default, Deserialize)]
+pub struct ExportSessionCommand {
+    #[serde(default)]
+    pub upload: Option<Upload>,
+}
+
+impl ExportSessionCommand {
+    /// An empty payload is the same as `{}`
+    pub fn parse(payload: &[u8]) -> Result<Self, serde_json::Error> {
+        if payload.is_empty() {
+            return Ok(Self::default());
+        }
+        serde_json::from_slice(payload)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Upload {
+    /// Published on `session/archive/<index>`, see [`upload_mqtt`]
+    Mqtt,
+    /// Sent to `--export-url`, see [`upload_http`]
+    Http,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TrackPoint {
+    time: SystemTime,
+    latitude: f64,
+    longitude: f64,
+}
+
+/// Positions of the GNSS since the start, the oldest are dropped after a day
+#[derive(Debug, Default, Clone)]
+pub struct GpxTrack {
+    points: VecDeque<TrackPoint>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+}
+
+impl GpxTrack {
+    /// Called with every decoded message, a point is added once both halves of a position came in
+    pub fn update(&mut self, data: &EoiCanData, time: SystemTime) {
+        match data {
+            EoiCanData::Gnss(GnssData::GnssLatitude(latitude)) => self.latitude = Some(*latitude),
+            EoiCanData::Gnss(GnssData::GnssLongitude(longitude)) => {
+                self.longitude = Some(*longitude)
+            }
+            _ => return,
+        }
+        if let (Some(latitude), Some(longitude)) = (self.latitude, self.longitude) {
+            self.latitude = None;
+            self.longitude = None;
+            if !latitude.is_finite() || !longitude.is_finite() {
+                return;
+            }
+            if self.points.len() == MAX_TRACK_POINTS {
+                self.points.pop_front();
+            }
+            self.points.push_back(TrackPoint {
+                time,
+                latitude,
+                longitude,
+            });
+        }
+    }
+
+    pub fn to_gpx(&self) -> String {
+        let mut gpx = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <gpx version=\"1.1\" creator=\"eoi-can-to-mqtt\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n\
+             <trk><name>Session</name><trkseg>\n",
+        );
+        for point in &self.points {
+            let time = DateTime::<Utc>::from(point.time).to_rfc3339_opts(SecondsFormat::Secs, true);
+            // Writing to a String can not fail
+            let _ = writeln!(
+                gpx,
+                "<trkpt lat=\"{:.7}\" lon=\"{:.7}\"><time>{}</time></trkpt>",
+                point.latitude, point.longitude, time
+            );
+        }
+        gpx.push_str("</trkseg></trk>\n</gpx>\n");
+        gpx
+    }
+}
+
+/// The written archive, published on `session/export` when it is done
+#[derive(Debug, Serialize)]
+pub struct Archive {
+    #[serde(skip)]
+    pub path: PathBuf,
+    /// File name of the archive
+    pub archive: String,
+    /// Entries in the archive
+    pub files: Vec<String>,
+    /// Size of the archive in bytes
+    pub size: u64,
+}
+
+/// What goes into the archive of a session
+#[derive(Debug, Clone)]
+pub struct SessionExport {
+    /// Directory the archives are written to
+    dir: PathBuf,
+    started: SystemTime,
+    log_dir: Option<PathBuf>,
+    csv: Option<PathBuf>,
+}
+
+impl SessionExport {
+    /// Write the archives to `dir`, the candump logs written to since `started` are the session
+    pub fn new(dir: impl AsRef<Path>, started: SystemTime) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            started,
+            log_dir: None,
+            csv: None,
+        }
+    }
+
+    /// Directory of the candump logs, see `--log-dir`
+    pub fn with_log_dir(mut self, log_dir: impl AsRef<Path>) -> Self {
+        self.log_dir = Some(log_dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// File of the CSV export, see `--csv`
+    pub fn with_csv(mut self, csv: impl AsRef<Path>) -> Self {
+        self.csv = Some(csv.as_ref().to_path_buf());
+        self
+    }
+
+    /// The candump logs written to since the start of the session, oldest first, and the CSV
+    fn session_files(&self) -> io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        if let Some(log_dir) = &self.log_dir {
+            for entry in std::fs::read_dir(log_dir)? {
+                let path = entry?.path();
+                if path.extension().is_some_and(|extension| extension == "log")
+                    && path.metadata()?.modified()? >= self.started
+                {
+                    files.push(path);
+                }
+            }
+            // The names start with the time they were created
+            files.sort();
+        }
+        if let Some(csv) = &self.csv {
+            files.extend(csv_export::parts(csv));
+        }
+        Ok(files)
+    }
+
+    /// Write the archive of the session, this reads every log, so call it off the async runtime
+    pub fn export(&self, track: &GpxTrack) -> io::Result<Archive> {
+        let name = Local::now().format("session-%Y-%m-%d_%H%M%S").to_string();
+        self.export_as(&name, track)
+    }
+
+    fn export_as(&self, name: &str, track: &GpxTrack) -> io::Result<Archive> {
+        std::fs::create_dir_all(&self.dir)?;
+        let archive = format!("{}.tar", name);
+        let path = self.dir.join(&archive);
+        let mut tar = BufWriter::new(File::create(&path)?);
+        let mtime = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let mut files = Vec::new();
+        for file in self.session_files()? {
+            let entry = format!("{}/{}", name, file.file_name().unwrap().to_string_lossy());
+            let size = file.metadata()?.len();
+            write_tar_header(&mut tar, &entry, size, mtime)?;
+            // The logs are still being written, only what was there when the size was taken is
+            // in the archive
+            let copied = io::copy(&mut File::open(&file)?.take(size), &mut tar)?;
+            io::copy(&mut io::repeat(0).take(size - copied), &mut tar)?;
+            write_tar_padding(&mut tar, size)?;
+            files.push(entry);
+        }
+
+        let entry = format!("{}/track.gpx", name);
+        let gpx = track.to_gpx();
+        write_tar_header(&mut tar, &entry, gpx.len() as u64, mtime)?;
+        tar.write_all(gpx.as_bytes())?;
+        write_tar_padding(&mut tar, gpx.len() as u64)?;
+        files.push(entry);
+
+        // Two empty blocks end the archive
+        tar.write_all(&[0; 2 * TAR_BLOCK_SIZE])?;
+        tar.into_inner()
+            .map_err(|error| error.into_error())?
+            .sync_all()?;
+        let size = path.metadata()?.len();
+        Ok(Archive {
+            path,
+            archive,
+            files,
+            size,
+        })
+    }
+}
+
+/// ustar header of a regular file
+fn write_tar_header<W: Write>(tar: &mut W, name: &str, size: u64, mtime: u64) -> io::Result<()> {
+    let mut header = [0; TAR_BLOCK_SIZE];
+    if name.len() > 100 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is too long for a tar entry", name),
+        ));
+    }
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    header[100..108].copy_from_slice(b"0000644\0");
+    header[108..116].copy_from_slice(b"0000000\0");
+    header[116..124].copy_from_slice(b"0000000\0");
+    header[124..136].copy_from_slice(&tar_size_field(size));
+    header[136..148].copy_from_slice(format!("{:011o}\0", mtime).as_bytes());
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    // The checksum is taken with its own field filled with spaces
+    header[148..156].copy_from_slice(b"        ");
+    let checksum: u32 = header.iter().map(|&byte| u32::from(byte)).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+    tar.write_all(&header)
+}
+
+/// Octal like ustar up to 8 GiB, larger sizes in the base-256 encoding of GNU tar, which GNU tar,
+/// bsdtar and Python read: the first byte has its top bit set and the rest is big endian
+fn tar_size_field(size: u64) -> [u8; 12] {
+    let mut field = [0; 12];
+    if size <= TAR_MAX_OCTAL_SIZE {
+        field.copy_from_slice(format!("{:011o}\0", size).as_bytes());
+    } else {
+        field[4..].copy_from_slice(&size.to_be_bytes());
+        field[0] = 0x80;
+    }
+    field
+}
+
+/// Fill the last block of an entry of `size` bytes
+fn write_tar_padding<W: Write>(tar: &mut W, size: u64) -> io::Result<()> {
+    let rest = size as usize % TAR_BLOCK_SIZE;
+    if rest > 0 {
+        tar.write_all(&[0; TAR_BLOCK_SIZE][rest..])?;
+    }
+    Ok(())
+}
+
+/// Publish the archive in chunks of [`MQTT_CHUNK_SIZE`] on `<topic>/<index>`, counted from 0.
+/// Returns the number of chunks, which is published on `session/export` once they are all sent
+pub async fn upload_mqtt(
+    client: &mqtt::AsyncClient,
+    topic: &str,
+    qos: i32,
+    archive: &Archive,
+) -> io::Result<usize> {
+    let mut file = tokio::fs::File::open(&archive.path).await?;
+    let mut chunks = 0;
+    loop {
+        let mut chunk = Vec::new();
+        (&mut file)
+            .take(MQTT_CHUNK_SIZE)
+            .read_to_end(&mut chunk)
+            .await?;
+        if chunk.is_empty() {
+            return Ok(chunks);
+        }
+        client
+            .publish(mqtt::Message::new(
+                format!("{}/{}", topic, chunks),
+                chunk,
+                qos,
+            ))
+            .await
+            .map_err(io::Error::other)?;
+        chunks += 1;
+    }
+}
+
+/// POST the archive to `url`, a plain HTTP endpoint on the network of the team like
+/// `http://192.168.1.10:8000/upload`. The file name is in the `Content-Disposition` header. Fails
+/// with `TimedOut` when the server doesn't answer within [`HTTP_TIMEOUT`] or the archive is sent
+/// slower than [`HTTP_MIN_UPLOAD_RATE`]
+pub async fn upload_http(url: &str, archive: &Archive) -> io::Result<()> {
+    let (host, port, path) = parse_http_url(url).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not an http:// URL", url),
+        )
+    })?;
+    let mut stream = with_timeout(
+        HTTP_TIMEOUT,
+        "connecting",
+        tokio::net::TcpStream::connect((host, port)),
+    )
+    .await?;
+    let request = format!(
+        "POST {} HTTP/1.1\r\n\
+         Host: {}:{}\r\n\
+         Content-Type: application/x-tar\r\n\
+         Content-Length: {}\r\n\
+         Content-Disposition: attachment; filename=\"{}\"\r\n\
+         Connection: close\r\n\r\n",
+        path, host, port, archive.size, archive.archive
+    );
+    let mut file = tokio::fs::File::open(&archive.path).await?;
+    let send_timeout = HTTP_TIMEOUT + Duration::from_secs(archive.size / HTTP_MIN_UPLOAD_RATE);
+    with_timeout(send_timeout, "sending the archive", async {
+        stream.write_all(request.as_bytes()).await?;
+        tokio::io::copy(&mut file, &mut stream).await?;
+        stream.flush().await
+    })
+    .await?;
+
+    let mut status = String::new();
+    let mut response = tokio::io::BufReader::new(stream);
+    with_timeout(
+        HTTP_TIMEOUT,
+        "waiting for the answer",
+        response.read_line(&mut status),
+    )
+    .await?;
+    match status.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => Err(io::Error::other(format!(
+            "Upload rejected: {}",
+            status.trim()
+        ))),
+    }
+}
+
+async fn with_timeout<T>(
+    duration: Duration,
+    step: &str,
+    future: impl Future<Output = io::Result<T>>,
+) -> io::Result<T> {
+    timeout(duration, future).await.unwrap_or_else(|_| {
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("Upload timed out {} after {:?}", step, duration),
+        ))
+    })
+}
+
+/// Host, port and path of an `http://` URL, the port is 80 and the path `/` when not given
+pub fn parse_http_url(url: &str) -> Option<(&str, u16, &str)> {
+    let rest = url.strip_prefix("http://")?;
+    let (authority, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse().ok()?),
+        None => (authority, 80),
+    };
+    (!host.is_empty()).then_some((host, port, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use std::time::Duration;
+
+    #[test]
+    fn track_from_gnss_positions() {
+        let mut track = GpxTrack::default();
+        let time = UNIX_EPOCH + Duration::from_secs(1_718_895_930);
+        track.update(&EoiCanData::Gnss(GnssData::GnssLatitude(43.734)), time);
+        assert!(track.points.is_empty());
+        track.update(&EoiCanData::Gnss(GnssData::GnssLongitude(7.421)), time);
+        // Only one half of the next position
+        track.update(&EoiCanData::Gnss(GnssData::GnssLongitude(7.422)), time);
+        assert!(track.points.len() == 1);
+        assert!(track.to_gpx().contains(
+            "<trkpt lat=\"43.7340000\" lon=\"7.4210000\"><time>2024-06-20T15:05:30Z</time></trkpt>"
+        ));
+    }
+
+    #[test]
+    fn archive_of_the_session() {
+        let dir = std::env::temp_dir().join(format!("eoi-session-export-{}", std::process::id()));
+        let log_dir = dir.join("logs");
+        std::fs::create_dir_all(&log_dir).unwrap();
+        let started = SystemTime::now() - Duration::from_secs(60);
+        std::fs::write(
+            log_dir.join("candump-2024-06-20_150000.log"),
+            "(1.0) can0 100#00\n",
+        )
+        .unwrap();
+        std::fs::write(log_dir.join("notes.txt"), "not a log").unwrap();
+        let csv = dir.join("race.csv");
+        std::fs::write(&csv, "timestamp\n1.000\n").unwrap();
+        std::fs::write(dir.join("race.1.csv"), "timestamp,a\n2.000,1\n").unwrap();
+
+        let archive = SessionExport::new(dir.join("exports"), started)
+            .with_log_dir(&log_dir)
+            .with_csv(&csv)
+            .export_as("session", &GpxTrack::default())
+            .unwrap();
+        assert!(
+            archive.files
+                == [
+                    "session/candump-2024-06-20_150000.log",
+                    "session/race.csv",
+                    "session/race.1.csv",
+                    "session/track.gpx"
+                ]
+        );
+
+        // Every entry is a header block and its data padded to whole blocks
+        let tar = std::fs::read(&archive.path).unwrap();
+        assert!(tar.len() as u64 == archive.size);
+        assert!(tar.len().is_multiple_of(TAR_BLOCK_SIZE));
+        let mut offset = 0;
+        let mut entries = Vec::new();
+        while tar[offset] != 0 {
+            let header = &tar[offset..offset + TAR_BLOCK_SIZE];
+            let name = String::from_utf8_lossy(&header[..100])
+                .trim_end_matches('\0')
+                .to_string();
+            let size = u64::from_str_radix(std::str::from_utf8(&header[124..135]).unwrap(), 8)
+                .unwrap() as usize;
+            assert!(&header[257..262] == b"ustar");
+            let data = &tar[offset + TAR_BLOCK_SIZE..offset + TAR_BLOCK_SIZE + size];
+            entries.push((name, String::from_utf8_lossy(data).to_string()));
+            offset += TAR_BLOCK_SIZE + size.div_ceil(TAR_BLOCK_SIZE) * TAR_BLOCK_SIZE;
+        }
+        assert!(entries[0].1 == "(1.0) can0 100#00\n");
+        assert!(entries[1].1 == "timestamp\n1.000\n");
+        assert!(entries[2].1 == "timestamp,a\n2.000,1\n");
+        assert!(entries[3].1.contains("<trkseg>"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sizes_beyond_the_octal_field() {
+        assert!(&tar_size_field(1024) == b"00000002000\0");
+        assert!(&tar_size_field(TAR_MAX_OCTAL_SIZE) == b"77777777777\0");
+        let size = 8 << 30;
+        let field = tar_size_field(size);
+        assert!(field[0] == 0x80);
+        assert!(u64::from_be_bytes(field[4..].try_into().unwrap()) == size);
+    }
+
+    #[test]
+    fn http_urls() {
+        assert!(
+            parse_http_url("http://192.168.1.10:8000/upload")
+                == Some(("192.168.1.10", 8000, "/upload"))
+        );
+        assert!(parse_http_url("http://datalake") == Some(("datalake", 80, "/")));
+        assert!(parse_http_url("https://datalake/upload").is_none());
+        assert!(parse_http_url("http://:80/").is_none());
+    }
+}
@@ -0,0 +1,47 @@
+//! What the main loop does with the frames collected during a tick. The pipeline test replays a
+//! log through the same function, so it checks the bridge rather than a copy of it.
+
+use draw_display::DisplayData;
+use eoi_can_decoder::can_frame::CanFrame;
+use eoi_can_decoder::current::CurrentConvention;
+use eoi_can_decoder::{DecoderConfig, EoiCanData, parse_eoi_can_data_with_config};
+use json_patch::merge;
+use serde_json::Value;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+/// Decode `frames` and ingest them into the data model, which works with the convention of the
+/// decoder. Every decoded frame is handed to `decoded` in `convention`, like everything
+/// published, and merged into `merged_json` when it is given, the merged document is also the
+/// row of the CSV export. The frames the decoder doesn't know are returned
+pub fn process_frames<'a>(
+    frames: impl IntoIterator<Item = &'a CanFrame>,
+    config: &DecoderConfig,
+    convention: CurrentConvention,
+    display_data: &mut DisplayData,
+    mut merged_json: Option<&mut Value>,
+    mut decoded: impl FnMut(&EoiCanData),
+) -> Vec<&'a CanFrame> {
+    let mut undecoded = Vec::new();
+    for frame in frames {
+        trace!("Paring CAN frame: {:?}", frame);
+        let Some(data) = parse_eoi_can_data_with_config(frame, config) else {
+            undecoded.push(frame);
+            continue;
+        };
+        trace!("{:?}", data);
+        let data = data.with_current_convention(convention);
+        decoded(&data);
+        if let Some(merged_json) = merged_json.as_deref_mut() {
+            match serde_json::to_value(&data) {
+                Ok(json) => {
+                    trace!("{:?}", json);
+                    merge(merged_json, &json);
+                }
+                Err(_) => warn!("Failed to serialize json of {:?}", data),
+            }
+        }
+        display_data.ingest_eoi_can_data(data.into_decoded_convention(convention));
+    }
+    undecoded
+}
@@ -0,0 +1,385 @@
+//! Topic scheme for publishing every subsystem on its own topic, like `eoi/<boat>/battery/soc`
+//! or `eoi/<boat>/mppt/<id>/power`, instead of one merged JSON document.
+
+use std::fmt::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use embedded_can::Id;
+use eoi_can_decoder::can_frame::CanFrame;
+use eoi_can_decoder::{
+    DisplayControl, EoiBattery, EoiCanData, GanMpptPacket, GnssData, HeightSensorData, MpptChannel,
+    MpptInfo, RudderControllerData, ServoData, TemperatureData, ThrottleData, VescMessage,
+};
+use serde::Serialize;
+use serde_json::{Value, json};
+
+/// Topic (relative to `<prefix>/<boat>/`) and JSON payload of decoded data
+pub fn subsystem_topic(data: &EoiCanData) -> Option<(String, Value)> {
+    let (topic, value) = match data {
+        EoiCanData::EoiBattery(battery) => match battery {
+            EoiBattery::PackAndPerriCurrent(data) => {
+                ("battery/current".to_string(), to_value(data))
+            }
+            EoiBattery::ChargeAndDischargeCurrent(data) => (
+                "battery/charge-discharge-current".to_string(),
+                to_value(data),
+            ),
+            EoiBattery::SocErrorFlagsAndBalancing(data) => {
+                ("battery/soc".to_string(), to_value(data))
+            }
+            EoiBattery::CellVoltages1_4(data) => ("battery/cells/1-4".to_string(), to_value(data)),
+            EoiBattery::CellVoltages5_8(data) => ("battery/cells/5-8".to_string(), to_value(data)),
+            EoiBattery::CellVoltages9_12(data) => {
+                ("battery/cells/9-12".to_string(), to_value(data))
+            }
+            EoiBattery::CellVoltages13_14PackAndStack(data) => {
+                ("battery/cells/13-14".to_string(), to_value(data))
+            }
+            EoiBattery::CellVoltages15_16(data) => {
+                ("battery/cells/15-16".to_string(), to_value(data))
+            }
+            EoiBattery::TemperaturesAndStates(data) => {
+                ("battery/temperatures".to_string(), to_value(data))
+            }
+            EoiBattery::Temperatures5_8(data) => {
+                ("battery/temperatures/5-8".to_string(), to_value(data))
+            }
+            EoiBattery::BatteryUptime(data) => ("battery/uptime".to_string(), to_value(data)),
+            EoiBattery::ChargeControlStatus(data) => {
+                ("battery/charge-control".to_string(), to_value(data))
+            }
+            EoiBattery::ChargeControlCommand(data) => {
+                ("battery/charge-control/command".to_string(), to_value(data))
+            }
+            EoiBattery::TimeEstimate(data) => ("battery/time-estimate".to_string(), to_value(data)),
+        },
+        EoiCanData::Vesc(vesc) => {
+            let (controller_id, message) = vesc.as_parts();
+            let name = match message {
+                VescMessage::StatusMessage1 { .. } => "status-1",
+                VescMessage::StatusMessage2 { .. } => "status-2",
+                VescMessage::StatusMessage3 { .. } => "status-3",
+                VescMessage::StatusMessage4 { .. } => "status-4",
+                VescMessage::StatusMessage5 { .. } => "status-5",
+                VescMessage::StatusMessage6 { .. } => "status-6",
+                VescMessage::FirmwareVersion { .. } => "firmware",
+                VescMessage::Ping { .. } => "ping",
+                VescMessage::Pong { .. } => "pong",
+                VescMessage::Fault { .. } => "fault",
+            };
+            (
+                format!("vesc/{}/{}", controller_id, name),
+                variant_value(to_value(message)),
+            )
+        }
+        EoiCanData::Throttle(throttle) => match throttle {
+            ThrottleData::ToVescDutyCycle(value) => {
+                ("throttle/to-vesc/duty-cycle".to_string(), json!(value))
+            }
+            ThrottleData::ToVescCurrent(value) => {
+                ("throttle/to-vesc/current".to_string(), json!(value))
+            }
+            ThrottleData::ToVescRpm(value) => ("throttle/to-vesc/rpm".to_string(), json!(value)),
+            ThrottleData::Status(status) => ("throttle/status".to_string(), to_value(status)),
+            ThrottleData::Config(config) => ("throttle/config".to_string(), to_value(config)),
+        },
+        EoiCanData::Mppt(mppt) => {
+            let (mppt_id, info) = mppt.as_parts();
+            let (name, value) = match info {
+                MpptInfo::Channel0(channel) => mppt_channel(0, channel),
+                MpptInfo::Channel1(channel) => mppt_channel(1, channel),
+                MpptInfo::Channel2(channel) => mppt_channel(2, channel),
+                MpptInfo::Channel3(channel) => mppt_channel(3, channel),
+                MpptInfo::ChannelUnknown(_) => return None,
+                MpptInfo::Power(power) => ("power".to_string(), to_value(power)),
+                MpptInfo::Status(status) => ("status".to_string(), to_value(status)),
+            };
+            (format!("mppt/{}/{}", mppt_id, name), value)
+        }
+        EoiCanData::Gnss(gnss) => match gnss {
+            GnssData::GnssStatus(status) => ("gnss/status".to_string(), to_value(status)),
+            GnssData::GnssSpeedAndHeading(speed, heading) => (
+                "gnss/speed".to_string(),
+                json!({ "speed": speed, "heading": heading }),
+            ),
+            // Latitude and longitude are sent in their own frames
+            GnssData::GnssLatitude(latitude) => {
+                ("gnss/position/latitude".to_string(), json!(latitude))
+            }
+            GnssData::GnssLongitude(longitude) => {
+                ("gnss/position/longitude".to_string(), json!(longitude))
+            }
+            GnssData::GnssDateTime(date_time) => {
+                ("gnss/date-time".to_string(), to_value(date_time))
+            }
+            GnssData::GnssAltitude(altitude) => ("gnss/altitude".to_string(), json!(altitude)),
+            GnssData::GnssUtcOffset(offset) => ("gnss/utc-offset".to_string(), json!(offset)),
+            GnssData::GnssTimeSync(utc_us) => ("gnss/time-sync".to_string(), json!(utc_us)),
+            GnssData::GnssFixQuality(quality) => {
+                ("gnss/fix-quality".to_string(), to_value(quality))
+            }
+        },
+        EoiCanData::RudderController(RudderControllerData::Servo(servo)) => match servo {
+            ServoData::Setpoint(setpoint) => ("rudder/setpoint".to_string(), json!(setpoint)),
+            ServoData::Status(status) => ("rudder/status".to_string(), to_value(status)),
+            ServoData::Command(command) => ("rudder/command".to_string(), to_value(command)),
+        },
+        EoiCanData::HeightSensors(height) => {
+            let (name, status) = match height {
+                HeightSensorData::FrontLeft(status) => ("front-left", status),
+                HeightSensorData::FrontRight(status) => ("front-right", status),
+                HeightSensorData::Reserved1(status) => ("reserved-1", status),
+                HeightSensorData::Reserved2(status) => ("reserved-2", status),
+            };
+            (format!("height/{}", name), to_value(status))
+        }
+        EoiCanData::GanMppt(gan_mppt) => {
+            let (node_id, packet) = gan_mppt.as_parts();
+            let (name, value) = match packet {
+                GanMpptPacket::Power(power) => ("power", to_value(power)),
+                GanMpptPacket::Status(status) => ("status", to_value(status)),
+                GanMpptPacket::SweepData(sweep) => ("sweep", to_value(sweep)),
+            };
+            (format!("gan-mppt/{}/{}", node_id, name), value)
+        }
+        EoiCanData::Temperature(temperature) => match temperature {
+            TemperatureData::HeightSensorsController(value) => (
+                "temperature/height-sensors-controller".to_string(),
+                json!(value),
+            ),
+            TemperatureData::RudderController(value) => {
+                ("temperature/rudder-controller".to_string(), json!(value))
+            }
+        },
+        // Like "peripherals/pump/current"
+        EoiCanData::PeripheralPower(power) => (
+            format!("peripherals/{:?}/current", power.consumer).to_lowercase(),
+            json!(power.current),
+        ),
+        EoiCanData::WaterSpeed(speed) => ("water-speed".to_string(), to_value(speed)),
+        EoiCanData::Imu(attitude) => ("imu/attitude".to_string(), to_value(attitude)),
+        EoiCanData::NodeIdentification(identification) => (
+            format!(
+                "nodes/{}/identification",
+                identification.node.label().to_lowercase().replace(' ', "-")
+            ),
+            to_value(identification),
+        ),
+        EoiCanData::DisplayHeartbeat(heartbeat) => {
+            ("display/heartbeat".to_string(), to_value(heartbeat))
+        }
+        // Reassembled from all its frames and published on "display/log" by the bridge
+        EoiCanData::DisplayLog(_) => return None,
+        // Reassembled and published on the "error" topic by the bridge
+        EoiCanData::DisplayPanic(_) => return None,
+        EoiCanData::DisplayBattery(battery) => ("display/battery".to_string(), to_value(battery)),
+        EoiCanData::DisplayControl(DisplayControl::Page(page)) => {
+            ("display/page".to_string(), json!(page))
+        }
+        EoiCanData::DisplayControl(DisplayControl::Profile(profile)) => {
+            ("display/profile".to_string(), json!(profile))
+        }
+        EoiCanData::DisplayControl(DisplayControl::RaceEnd(end_s)) => {
+            ("display/race-end".to_string(), json!(end_s))
+        }
+        EoiCanData::DisplayControl(DisplayControl::TripReset) => {
+            ("display/trip-reset".to_string(), json!({}))
+        }
+        // Sent from MQTT in the first place, a single part is of no use
+        EoiCanData::DisplayControl(DisplayControl::Message(_)) => return None,
+        EoiCanData::DisplayAcknowledge(acknowledge) => (
+            "display/message/acknowledge".to_string(),
+            to_value(acknowledge),
+        ),
+    };
+
+    Some((topic, value))
+}
+
+fn mppt_channel(channel: u8, data: &MpptChannel) -> (String, Value) {
+    match data {
+        MpptChannel::Power(power) => (format!("channel/{}/power", channel), to_value(power)),
+        MpptChannel::State(state) => (format!("channel/{}/state", channel), to_value(state)),
+    }
+}
+
+fn to_value<T: Serialize>(data: &T) -> Value {
+    serde_json::to_value(data).unwrap_or_default()
+}
+
+/// Struct like enum variants serialize as `{"Variant": {...}}`, the topic already names the variant
+fn variant_value(value: Value) -> Value {
+    match value {
+        Value::Object(object) if object.len() == 1 => object
+            .into_iter()
+            .next()
+            .map(|(_, value)| value)
+            .unwrap_or_default(),
+        value => value,
+    }
+}
+
+/// Raw ID of a frame, standard or extended
+pub fn raw_id(frame: &CanFrame) -> u32 {
+    match frame.id {
+        Id::Standard(id) => id.as_raw().into(),
+        Id::Extended(id) => id.as_raw(),
+    }
+}
+
+/// Topic (relative to `<prefix>/<boat>/`) and payload of a raw frame, for debugging a node
+/// remotely, like `raw/0x101` with
+/// `{"bus": "can0", "data": "3200000000000000", "extended": false, "id": "0x101", "timestamp": 1718895900.123}`
+pub fn raw_frame_topic(frame: &CanFrame, interface: &str, received: SystemTime) -> (String, Value) {
+    let id = format!("{:#x}", raw_id(frame));
+    let data = frame.data.iter().fold(String::new(), |mut data, byte| {
+        // Writing to a `String` doesn't fail
+        let _ = write!(data, "{:02X}", byte);
+        data
+    });
+    let timestamp = received
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+    let value = json!({
+        "bus": interface,
+        "data": data,
+        "extended": matches!(frame.id, Id::Extended(_)),
+        "id": id,
+        "timestamp": timestamp,
+    });
+    (format!("raw/{}", id), value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::assert_json_fixture;
+    use assert2::assert;
+    use eoi_can_decoder::signals::sample_frames;
+    use eoi_can_decoder::{NodeIdentification, NodeType, parse_eoi_can_data};
+
+    /// Messages the bridge publishes some other way, see [`subsystem_topic`]
+    const NOT_PUBLISHED: &[&str] = &["DisplayLog", "DisplayMessage", "DisplayPanic"];
+
+    /// Numbers, booleans and strings in `value`
+    fn leaf_count(value: &Value) -> usize {
+        match value {
+            Value::Null => 0,
+            Value::Array(values) => values.iter().map(leaf_count).sum(),
+            Value::Object(values) => values.values().map(leaf_count).sum(),
+            _ => 1,
+        }
+    }
+
+    #[test]
+    fn raw_frames() {
+        let frame = eoi_can_decoder::frame!(0x101, [0x32, 0x00, 0xAB]);
+        let received = UNIX_EPOCH + std::time::Duration::from_millis(1_718_895_900_125);
+        let (topic, value) = raw_frame_topic(&frame, "can0", received);
+        assert!(topic == "raw/0x101");
+        assert!(
+            value
+                == json!({
+                    "bus": "can0",
+                    "data": "3200AB",
+                    "extended": false,
+                    "id": "0x101",
+                    "timestamp": 1718895900.125,
+                })
+        );
+    }
+
+    #[test]
+    fn node_topics_in_kebab_case() {
+        let identification = |node| {
+            EoiCanData::NodeIdentification(NodeIdentification {
+                node,
+                version_major: 1,
+                version_minor: 0,
+                version_patch: 0,
+                dirty: false,
+                git_hash: 0,
+            })
+        };
+        let topic = |node| subsystem_topic(&identification(node)).unwrap().0;
+        assert!(topic(NodeType::RudderController) == "nodes/rudder/identification");
+        assert!(topic(NodeType::HeightSensors) == "nodes/height-sensors/identification");
+    }
+
+    #[test]
+    fn every_registered_signal_is_published() {
+        for (message, node, frame) in sample_frames(0x01) {
+            let data = parse_eoi_can_data(&frame).unwrap();
+            let Some((topic, value)) = subsystem_topic(&data) else {
+                assert!(
+                    NOT_PUBLISHED.contains(&message.name),
+                    "{} of node {} isn't published",
+                    message.name,
+                    node
+                );
+                continue;
+            };
+            assert!(
+                leaf_count(&value) >= message.signals.len(),
+                "{} on {} has fewer values than signals: {}",
+                message.name,
+                topic,
+                value
+            );
+        }
+    }
+
+    /// `value` with every number, boolean and string replaced by its type and the keys sorted, so
+    /// the fixtures only change with the structure
+    fn structure(value: &Value) -> Value {
+        match value {
+            Value::Null => Value::Null,
+            Value::Bool(_) => json!("boolean"),
+            Value::Number(_) => json!("number"),
+            Value::String(_) => json!("string"),
+            Value::Array(values) => values.iter().map(structure).collect(),
+            Value::Object(values) => {
+                let mut keys: Vec<_> = values.keys().collect();
+                keys.sort_unstable();
+                keys.into_iter()
+                    .map(|key| (key.clone(), structure(&values[key])))
+                    .collect::<serde_json::Map<_, _>>()
+                    .into()
+            }
+        }
+    }
+
+    #[test]
+    fn message_payloads_match_the_fixture() {
+        let mut messages = std::collections::BTreeMap::new();
+        for (message, _, frame) in sample_frames(0x01) {
+            if messages.contains_key(message.name) {
+                continue;
+            }
+            let data = parse_eoi_can_data(&frame).unwrap();
+            let subsystem = subsystem_topic(&data)
+                .map(|(topic, value)| json!({ "payload": structure(&value), "topic": topic }));
+            messages.insert(
+                message.name,
+                json!({
+                    "merged": structure(&serde_json::to_value(&data).unwrap()),
+                    "subsystem": subsystem,
+                }),
+            );
+        }
+        assert_json_fixture("messages.json", &json!(messages));
+    }
+
+    #[test]
+    fn display_data_matches_the_fixture() {
+        let mut display_data = draw_display::DisplayData::default();
+        for (_, _, frame) in sample_frames(0x01) {
+            display_data.ingest_eoi_can_data(parse_eoi_can_data(&frame).unwrap());
+        }
+        assert_json_fixture(
+            "display_data.json",
+            &structure(&serde_json::to_value(&display_data).unwrap()),
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use clap::Parser;
+use eoi_can_decoder::parse_eoi_can_data;
+use eoi_can_source::{LogFilter, parse_seconds};
+use nmea::Navigation;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+#[allow(unused_imports)]
+use tracing::{Level, debug, error, info, trace, warn};
+use tracing_subscriber::filter::LevelFilter;
+
+mod nmea;
+
+/// Sentences buffered per client, slower clients skip sentences
+const CLIENT_BUFFER_SIZE: usize = 64;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    /// CAN interface to read
+    #[arg(short, long, default_value_t = String::from("can0"))]
+    can_interface: String,
+
+    /// Address to serve the sentences on over TCP, 10110 is the usual NMEA port
+    #[arg(long, default_value = "0.0.0.0:10110")]
+    tcp: SocketAddr,
+
+    /// Serial port to also write the sentences to, like /dev/ttyUSB0. Set its baud rate with
+    /// `stty -F /dev/ttyUSB0 4800` beforehand
+    #[arg(long)]
+    serial: Option<PathBuf>,
+
+    /// Seconds between two sets of sentences
+    #[arg(long, default_value = "1", value_parser = parse_seconds)]
+    interval: Duration,
+}
+
+#[tokio::main]
+async fn main() {
+    LogFilter::init(LevelFilter::INFO);
+    eoi_can_source::install_panic_hook("eoi-can-to-nmea");
+    let args = Args::parse();
+
+    let navigation = Arc::new(Mutex::new(Navigation::default()));
+    let (_, mut can_frames) = eoi_can_source::spawn_can_reader(&args.can_interface);
+    let receiving_navigation = navigation.clone();
+    tokio::spawn(async move {
+        while let Some(frame) = can_frames.recv().await {
+            if let Some(data) = parse_eoi_can_data(&frame)
+                && let Ok(mut navigation) = receiving_navigation.lock()
+            {
+                navigation.ingest(&data, Instant::now());
+            }
+        }
+    });
+
+    let (sentences, _) = broadcast::channel::<String>(CLIENT_BUFFER_SIZE);
+    let listener = TcpListener::bind(args.tcp)
+        .await
+        .expect("Unable to bind TCP socket");
+    info!("Serving NMEA sentences on {}", args.tcp);
+    let clients = sentences.clone();
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, address) = match listener.accept().await {
+                Ok(client) => client,
+                Err(error) => {
+                    warn!("Failed to accept TCP client: {:?}", error);
+                    continue;
+                }
+            };
+            info!("NMEA client {} connected", address);
+            let mut receiver = clients.subscribe();
+            tokio::spawn(async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(sentence) => {
+                            if stream.write_all(sentence.as_bytes()).await.is_err() {
+                                info!("NMEA client {} disconnected", address);
+                                break;
+                            }
+                        }
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            debug!("NMEA client {} skipped {} sentences", address, skipped);
+                        }
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+            });
+        }
+    });
+
+    let mut serial = match &args.serial {
+        Some(path) => {
+            info!("Writing NMEA sentences to {:?}", path);
+            Some(
+                tokio::fs::OpenOptions::new()
+                    .write(true)
+                    .open(path)
+                    .await
+                    .expect("Unable to open serial port"),
+            )
+        }
+        None => None,
+    };
+
+    let mut interval = tokio::time::interval(args.interval);
+    loop {
+        interval.tick().await;
+        let Ok(batch) = navigation
+            .lock()
+            .map(|navigation| navigation.sentences(Instant::now()))
+        else {
+            continue;
+        };
+        for sentence in batch {
+            trace!("{}", sentence.trim_end());
+            if let Some(port) = serial.as_mut()
+                && let Err(error) = port.write_all(sentence.as_bytes()).await
+            {
+                warn!("Failed to write to the serial port: {:?}", error);
+            }
+            // Only fails without clients
+            _ = sentences.send(sentence);
+        }
+    }
+}
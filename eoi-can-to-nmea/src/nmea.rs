@@ -0,0 +1,284 @@
+//! NMEA 0183 sentences from the decoded GNSS and battery data. Position, speed and course go out
+//! as RMC, VTG and GGA like from a GPS receiver, the battery as XDR transducer readings.
+
+use std::time::{Duration, Instant};
+
+use eoi_can_decoder::{EoiBattery, EoiCanData, GnssData};
+
+/// Without a new position for this long the fix is reported as invalid
+const STALE_AFTER: Duration = Duration::from_secs(5);
+const KNOTS_PER_KMH: f32 = 1.0 / 1.852;
+
+/// Time of day and date in UTC, `GnssDateTime` isn't `Clone`
+#[derive(Debug, Clone, Copy)]
+struct DateTime {
+    year: u16,
+    month: u8,
+    day: u8,
+    hours: u8,
+    minutes: u8,
+    seconds: u8,
+}
+
+/// The latest values needed for the sentences
+#[derive(Debug, Default)]
+pub struct Navigation {
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    position_received: Option<Instant>,
+    speed_kmh: Option<f32>,
+    course: Option<f32>,
+    date_time: Option<DateTime>,
+    fix: bool,
+    sats_used: Option<u8>,
+    altitude: Option<f32>,
+    battery_voltage: Option<f32>,
+    battery_current: Option<f32>,
+    battery_state_of_charge: Option<f32>,
+    battery_temperature: Option<i8>,
+}
+
+impl Navigation {
+    pub fn ingest(&mut self, data: &EoiCanData, now: Instant) {
+        match data {
+            EoiCanData::Gnss(GnssData::GnssLatitude(latitude)) => {
+                self.latitude = Some(*latitude);
+                self.position_received = Some(now);
+            }
+            EoiCanData::Gnss(GnssData::GnssLongitude(longitude)) => {
+                self.longitude = Some(*longitude);
+                self.position_received = Some(now);
+            }
+            EoiCanData::Gnss(GnssData::GnssSpeedAndHeading(speed_kmh, course)) => {
+                self.speed_kmh = Some(*speed_kmh);
+                self.course = Some(*course);
+            }
+            EoiCanData::Gnss(GnssData::GnssDateTime(date_time)) => {
+                self.date_time = Some(DateTime {
+                    year: date_time.year,
+                    month: date_time.month,
+                    day: date_time.day,
+                    hours: date_time.hours,
+                    minutes: date_time.minutes,
+                    seconds: date_time.seconds,
+                });
+            }
+            EoiCanData::Gnss(GnssData::GnssStatus(status)) => {
+                self.fix = status.fix != 0;
+                self.sats_used = Some(status.sats_used);
+            }
+            EoiCanData::Gnss(GnssData::GnssAltitude(altitude)) => self.altitude = Some(*altitude),
+            EoiCanData::EoiBattery(EoiBattery::CellVoltages13_14PackAndStack(data)) => {
+                self.battery_voltage = Some(data.pack_voltage);
+            }
+            EoiCanData::EoiBattery(EoiBattery::PackAndPerriCurrent(data)) => {
+                self.battery_current = Some(data.pack_current);
+            }
+            EoiCanData::EoiBattery(EoiBattery::SocErrorFlagsAndBalancing(data)) => {
+                self.battery_state_of_charge = Some(data.state_of_charge);
+            }
+            EoiCanData::EoiBattery(EoiBattery::TemperaturesAndStates(data)) => {
+                self.battery_temperature = data.temperatures.iter().max().copied();
+            }
+            _ => {}
+        }
+    }
+
+    /// RMC, VTG and GGA once a position was received, and XDR once a battery value was
+    pub fn sentences(&self, now: Instant) -> Vec<String> {
+        let mut sentences = Vec::new();
+        if let (Some(latitude), Some(longitude)) = (self.latitude, self.longitude) {
+            let valid = self.fix
+                && self
+                    .position_received
+                    .is_some_and(|received| now.duration_since(received) < STALE_AFTER);
+            let position = format_position(latitude, longitude);
+            let time = self.date_time.map(format_time).unwrap_or_default();
+            let knots = self.speed_kmh.map(|speed| speed * KNOTS_PER_KMH);
+
+            sentences.push(sentence(
+                "GPRMC",
+                &format!(
+                    "{},{},{},{},{},{},,,{}",
+                    time,
+                    if valid { "A" } else { "V" },
+                    position,
+                    optional(knots, 1),
+                    optional(self.course, 1),
+                    self.date_time.map(format_date).unwrap_or_default(),
+                    if valid { "A" } else { "N" },
+                ),
+            ));
+            sentences.push(sentence(
+                "GPVTG",
+                &format!(
+                    "{},T,,M,{},N,{},K,{}",
+                    optional(self.course, 1),
+                    optional(knots, 1),
+                    optional(self.speed_kmh, 1),
+                    if valid { "A" } else { "N" },
+                ),
+            ));
+            // Without the HDOP, the bus has the horizontal error in m instead of the dilution of
+            // precision
+            sentences.push(sentence(
+                "GPGGA",
+                &format!(
+                    "{},{},{},{},,{},M,,M,,",
+                    time,
+                    position,
+                    u8::from(valid),
+                    self.sats_used
+                        .map(|sats| format!("{:02}", sats))
+                        .unwrap_or_default(),
+                    optional(self.altitude, 1),
+                ),
+            ));
+        }
+
+        let mut transducers = Vec::new();
+        if let Some(voltage) = self.battery_voltage {
+            transducers.push(format!("U,{voltage:.2},V,BATTERY"));
+        }
+        if let Some(current) = self.battery_current {
+            transducers.push(format!("I,{current:.1},A,BATTERY"));
+        }
+        if let Some(state_of_charge) = self.battery_state_of_charge {
+            transducers.push(format!("G,{state_of_charge:.1},P,BATTERY_SOC"));
+        }
+        if let Some(temperature) = self.battery_temperature {
+            transducers.push(format!("C,{temperature},C,BATTERY"));
+        }
+        if !transducers.is_empty() {
+            sentences.push(sentence("IIXDR", &transducers.join(",")));
+        }
+        sentences
+    }
+}
+
+/// `$<address>,<fields>*<checksum>` with the line ending
+fn sentence(address: &str, fields: &str) -> String {
+    let body = format!("{},{}", address, fields);
+    let checksum = body.bytes().fold(0, |checksum, byte| checksum ^ byte);
+    format!("${}*{:02X}\r\n", body, checksum)
+}
+
+fn optional(value: Option<f32>, decimals: usize) -> String {
+    value
+        .map(|value| format!("{:.*}", decimals, value))
+        .unwrap_or_default()
+}
+
+/// `ddmm.mmmm,N,dddmm.mmmm,E`
+fn format_position(latitude: f64, longitude: f64) -> String {
+    format!(
+        "{},{},{},{}",
+        degrees_and_minutes(latitude, 2),
+        if latitude < 0.0 { "S" } else { "N" },
+        degrees_and_minutes(longitude, 3),
+        if longitude < 0.0 { "W" } else { "E" },
+    )
+}
+
+/// Like `4344.0700`, rounded to the ten thousandth of a minute before splitting, so 59.99999'
+/// carries into the degrees instead of showing as 60.0000
+fn degrees_and_minutes(degrees: f64, degree_digits: usize) -> String {
+    const PER_MINUTE: u64 = 10_000;
+    const PER_DEGREE: u64 = 60 * PER_MINUTE;
+    let total = (degrees.abs() * PER_DEGREE as f64).round() as u64;
+    format!(
+        "{:0width$}{:02}.{:04}",
+        total / PER_DEGREE,
+        total % PER_DEGREE / PER_MINUTE,
+        total % PER_MINUTE,
+        width = degree_digits
+    )
+}
+
+fn format_time(date_time: DateTime) -> String {
+    format!(
+        "{:02}{:02}{:02}.00",
+        date_time.hours, date_time.minutes, date_time.seconds
+    )
+}
+
+fn format_date(date_time: DateTime) -> String {
+    format!(
+        "{:02}{:02}{:02}",
+        date_time.day,
+        date_time.month,
+        date_time.year % 100
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use eoi_can_decoder::{GnssDateTime, GnssStatus, PackAndPerriCurrent};
+
+    #[test]
+    fn checksum() {
+        // Example from the NMEA 0183 documentation of gpsd
+        assert!(
+            sentence(
+                "GPGGA",
+                "172814.0,3723.46587704,N,12202.26957864,W,2,6,1.2,18.893,M,-25.669,M,2.0,0031"
+            ) == "$GPGGA,172814.0,3723.46587704,N,12202.26957864,W,2,6,1.2,18.893,M,-25.669,M,2.0,0031*4F\r\n"
+        );
+    }
+
+    #[test]
+    fn position_and_battery_sentences() {
+        let now = Instant::now();
+        let mut navigation = Navigation::default();
+        assert!(navigation.sentences(now).is_empty());
+
+        for data in [
+            EoiCanData::Gnss(GnssData::GnssStatus(GnssStatus {
+                fix: 3,
+                sats: 12,
+                sats_used: 9,
+            })),
+            EoiCanData::Gnss(GnssData::GnssLatitude(43.7345)),
+            EoiCanData::Gnss(GnssData::GnssLongitude(-7.4215)),
+            EoiCanData::Gnss(GnssData::GnssSpeedAndHeading(18.52, 90.0)),
+            EoiCanData::Gnss(GnssData::GnssDateTime(GnssDateTime {
+                year: 2025,
+                month: 6,
+                day: 14,
+                hours: 12,
+                minutes: 30,
+                seconds: 5,
+            })),
+            EoiCanData::EoiBattery(EoiBattery::PackAndPerriCurrent(PackAndPerriCurrent {
+                pack_current: -20.5,
+                perri_current: 0.4,
+            })),
+        ] {
+            navigation.ingest(&data, now);
+        }
+
+        let sentences = navigation.sentences(now);
+        assert!(sentences.len() == 4);
+        assert!(
+            sentences[0]
+                .starts_with("$GPRMC,123005.00,A,4344.0700,N,00725.2900,W,10.0,90.0,140625,,,A*")
+        );
+        assert!(sentences[1].starts_with("$GPVTG,90.0,T,,M,10.0,N,18.5,K,A*"));
+        assert!(
+            sentences[2].starts_with("$GPGGA,123005.00,4344.0700,N,00725.2900,W,1,09,,,M,,M,,*")
+        );
+        assert!(sentences[3].starts_with("$IIXDR,I,-20.5,A,BATTERY*"));
+
+        // Without new positions the fix is no longer valid
+        let sentences = navigation.sentences(now + STALE_AFTER);
+        assert!(sentences[0].contains(",V,"));
+    }
+
+    #[test]
+    fn minutes_carry_into_the_degrees() {
+        assert!(format_position(43.999_999_9, -7.999_999_9) == "4400.0000,N,00800.0000,W");
+        assert!(format_position(-0.5, 179.25) == "0030.0000,S,17915.0000,E");
+    }
+}
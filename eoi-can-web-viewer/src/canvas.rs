@@ -0,0 +1,86 @@
+//! Off-screen picture of the display as RGBA bytes, the layout of `ImageData` in the browser. The
+//! colors are converted like on the framebuffer of the boat.
+
+use core::convert::Infallible;
+
+use embedded_graphics::pixelcolor::Rgb888;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+/// Size of the display of the boat
+pub const DISPLAY_SIZE: Size = Size::new(800, 480);
+
+pub struct Canvas {
+    size: Size,
+    rgba: Vec<u8>,
+}
+
+impl Default for Canvas {
+    fn default() -> Self {
+        Self::new(DISPLAY_SIZE)
+    }
+}
+
+impl Canvas {
+    pub fn new(size: Size) -> Self {
+        let mut rgba = vec![0; size.width as usize * size.height as usize * 4];
+        // Opaque, the drawing only sets the colors
+        for pixel in rgba.as_chunks_mut::<4>().0 {
+            pixel[3] = u8::MAX;
+        }
+        Self { size, rgba }
+    }
+
+    /// The pixels line by line, 4 bytes each
+    pub fn rgba(&self) -> &[u8] {
+        &self.rgba
+    }
+
+    fn offset(&self, point: Point) -> Option<usize> {
+        let x = usize::try_from(point.x).ok()?;
+        let y = usize::try_from(point.y).ok()?;
+        if x >= self.size.width as usize || y >= self.size.height as usize {
+            return None;
+        }
+        Some((y * self.size.width as usize + x) * 4)
+    }
+}
+
+impl OriginDimensions for Canvas {
+    fn size(&self) -> Size {
+        self.size
+    }
+}
+
+impl DrawTarget for Canvas {
+    type Color = Rgb888;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if let Some(offset) = self.offset(point) {
+                self.rgba[offset..offset + 3].copy_from_slice(&[color.r(), color.g(), color.b()]);
+            }
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let area = area.intersection(&self.bounding_box());
+        let Some(bottom_right) = area.bottom_right() else {
+            return Ok(());
+        };
+        for y in area.top_left.y..=bottom_right.y {
+            // Both corners are within the bounds after the intersection
+            let start = self.offset(Point::new(area.top_left.x, y)).unwrap();
+            let end = self.offset(Point::new(bottom_right.x, y)).unwrap() + 4;
+            for pixel in self.rgba[start..end].as_chunks_mut::<4>().0 {
+                pixel[..3].copy_from_slice(&[color.r(), color.g(), color.b()]);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,170 @@
+//! Mirror of the display in the browser of the chase car. The CAN frames pushed by `/ws/frames`
+//! of the status server are decoded and drawn with the same decoder and drawing code as on the
+//! boat, into a canvas of the display's size, so the mirror is pixel-identical to the display.
+//! Built with `wasm-pack build --target web eoi-can-web-viewer`, `index.html` shows it.
+//!
+//! VESC frames are decoded for the default controller ID, see [`DecoderConfig`].
+//!
+//! [`DecoderConfig`]: eoi_can_decoder::DecoderConfig
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use can_logger::parse_candump_line;
+use draw_display::{DisplayData, PageSelection, Profile};
+use eoi_can_decoder::parse_eoi_can_data;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::{Clamped, JsCast};
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageData, MessageEvent, WebSocket};
+
+mod canvas;
+
+pub use canvas::{Canvas, DISPLAY_SIZE};
+
+/// The decoded data and the shown page, what the display of the boat keeps between frames
+pub struct Mirror {
+    display_data: DisplayData,
+    pages: PageSelection,
+}
+
+impl Default for Mirror {
+    fn default() -> Self {
+        Self {
+            display_data: DisplayData::default(),
+            pages: PageSelection::new(Profile::default()),
+        }
+    }
+}
+
+impl Mirror {
+    /// Decode a candump line of `/ws/frames`, returns whether the decoder knew the frame
+    pub fn ingest_line(&mut self, line: &str) -> bool {
+        let Some((_, _, frame)) = parse_candump_line(line) else {
+            return false;
+        };
+        let Some(data) = parse_eoi_can_data(&frame) else {
+            return false;
+        };
+        self.display_data.ingest_eoi_can_data(data);
+        true
+    }
+
+    /// Go to the next page of the profile, like by the button
+    pub fn next_page(&mut self) {
+        self.pages.next();
+    }
+
+    /// Draw the shown page, following the pages and profiles the chase car asks for
+    pub fn draw(&mut self, canvas: &mut Canvas) {
+        if let Some(page) = self.display_data.requested_page.take() {
+            self.pages.show(page);
+        }
+        if let Some(profile) = self.display_data.requested_profile.take() {
+            self.pages.set_profile(profile);
+        }
+        self.pages.rotate();
+        let Ok(()) = draw_display::draw_page(canvas, &self.display_data, self.pages.page());
+    }
+}
+
+/// The viewer in the page, see `index.html`
+#[wasm_bindgen]
+pub struct Viewer {
+    mirror: Rc<RefCell<Mirror>>,
+    canvas: Canvas,
+    context: CanvasRenderingContext2d,
+    socket: WebSocket,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+}
+
+#[wasm_bindgen]
+impl Viewer {
+    /// Mirror the display into the `<canvas>` with the ID `canvas_id`, from the frames of the
+    /// status server at `url` like `ws://10.12.0.208:8080/ws/frames`
+    #[wasm_bindgen(constructor)]
+    pub fn new(canvas_id: &str, url: &str) -> Result<Viewer, JsValue> {
+        let element = web_sys::window()
+            .and_then(|window| window.document())
+            .and_then(|document| document.get_element_by_id(canvas_id))
+            .ok_or_else(|| format!("No element {}", canvas_id))?;
+        let element: HtmlCanvasElement = element
+            .dyn_into()
+            .map_err(|_| format!("{} is not a canvas", canvas_id))?;
+        element.set_width(DISPLAY_SIZE.width);
+        element.set_height(DISPLAY_SIZE.height);
+        let context: CanvasRenderingContext2d = element
+            .get_context("2d")?
+            .ok_or("No 2D context")?
+            .dyn_into()?;
+
+        let mirror = Rc::new(RefCell::new(Mirror::default()));
+        let socket = WebSocket::new(url)?;
+        let receiver = mirror.clone();
+        let on_message = Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+            if let Some(line) = event.data().as_string() {
+                receiver.borrow_mut().ingest_line(&line);
+            }
+        });
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+
+        Ok(Self {
+            mirror,
+            canvas: Canvas::default(),
+            context,
+            socket,
+            _on_message: on_message,
+        })
+    }
+
+    /// Draw the shown page into the canvas, call it every 100 ms like the display does
+    pub fn draw(&mut self) -> Result<(), JsValue> {
+        self.mirror.borrow_mut().draw(&mut self.canvas);
+        let image = ImageData::new_with_u8_clamped_array_and_sh(
+            Clamped(self.canvas.rgba()),
+            DISPLAY_SIZE.width,
+            DISPLAY_SIZE.height,
+        )?;
+        self.context.put_image_data(&image, 0.0, 0.0)
+    }
+
+    /// Go to the next page, like by the button of the display
+    pub fn next_page(&mut self) {
+        self.mirror.borrow_mut().next_page();
+    }
+}
+
+impl Drop for Viewer {
+    fn drop(&mut self) {
+        self.socket.set_onmessage(None);
+        _ = self.socket.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use draw_display::Page;
+
+    #[test]
+    fn mirror_the_display() {
+        let mut mirror = Mirror::default();
+        assert!(mirror.ingest_line("(1718900000.250000) can0 242#0000C03F"));
+        // Another team's frame and a remote frame
+        assert!(!mirror.ingest_line("(1718900000.260000) can0 7FF#00"));
+        assert!(!mirror.ingest_line("(1718900000.270000) can0 242#R"));
+
+        let mut canvas = Canvas::default();
+        mirror.draw(&mut canvas);
+        assert!(mirror.pages.page() == Page::Main);
+        // Drawn in the colors of the framebuffer
+        let (pixels, _) = canvas.rgba().as_chunks::<4>();
+        assert!(pixels.contains(&[255, 255, 255, 255]));
+        assert!(pixels.contains(&[0, 0, 0, 255]));
+
+        // The chase car asks for the diagnostics page
+        assert!(mirror.ingest_line("(1718900001.000000) can0 233#01"));
+        mirror.draw(&mut canvas);
+        assert!(mirror.pages.page() == Page::Diagnostics);
+    }
+}
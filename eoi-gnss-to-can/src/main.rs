@@ -1,134 +1,335 @@
-use chrono::{DateTime, Datelike, Local, Timelike, Utc};
+use chrono::{DateTime, Datelike, Local, Offset, Timelike, Utc};
+use chrono_tz::Tz;
 use clap::Parser;
 use embedded_can::{Frame, StandardId};
+use eoi_can_decoder::GNSS_HORIZONTAL_ERROR_UNKNOWN;
+use eoi_can_source::{CanTransmitter, LogFilter, PeriodicSender, parse_seconds};
 use gpsd_client::*;
 use socketcan::{CanFrame, tokio::CanSocket};
-use std::process;
 use std::thread;
 use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Instant;
 #[allow(unused_imports)]
 use tracing::{Level, debug, error, info, trace, warn};
-use tracing_subscriber::filter::{EnvFilter, LevelFilter};
-use tracing_subscriber::prelude::*;
+use tracing_subscriber::filter::LevelFilter;
+
+const RECONNECT_MIN_INTERVAL: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_INTERVAL: Duration = Duration::from_secs(30);
+/// Unchanged frames are still sent this often, the display treats values older than 5 seconds
+/// as stale
+const RESEND_INTERVAL: Duration = Duration::from_secs(2);
+
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
     /// CAN interface
     #[arg(short, long, default_value_t = String::from("can0"))]
     can_interface: String,
+
+    /// Seconds between sending the GNSS frames
+    #[arg(short, long, default_value = "1", value_parser = parse_seconds)]
+    interval: Duration,
+
+    /// Only send frames of which the data changed, unchanged frames are still sent every 2 seconds
+    #[arg(long)]
+    on_change: bool,
+
+    /// Seconds between sending the speed and heading while moving, the receiver has to report
+    /// this often too (like `gpsctl -c 0.2`) or the same speed is repeated
+    #[arg(long, default_value = "0.2", value_parser = parse_seconds)]
+    moving_interval: Duration,
+
+    /// Seconds between sending the speed and heading while standing still
+    #[arg(long, default_value = "2", value_parser = parse_seconds)]
+    stationary_interval: Duration,
+
+    /// Speed in km/h above which the boat is moving
+    #[arg(long, default_value_t = 1.0)]
+    moving_speed: f32,
+
+    /// Time zone the display shows the time in, like Europe/Monaco. Defaults to the time zone
+    /// of this computer
+    #[arg(long, value_parser = parse_time_zone)]
+    timezone: Option<Tz>,
+
+    /// Show the time on the display in UTC
+    #[arg(long, conflicts_with = "timezone")]
+    utc: bool,
+}
+
+/// When to send the speed and heading, quicker while moving so the readout follows manoeuvres,
+/// slower while standing still to leave the bus to the others
+#[derive(Debug)]
+struct SpeedRate {
+    moving_interval: Duration,
+    stationary_interval: Duration,
+    moving_speed: f32,
+}
+
+impl SpeedRate {
+    /// Called every moving interval, whether the speed is due at `speed` in km/h, when it was
+    /// last written to the bus at `last_sent`
+    fn is_due(&self, speed: f32, now: Instant, last_sent: Option<Instant>) -> bool {
+        let interval = if speed > self.moving_speed {
+            self.moving_interval
+        } else {
+            self.stationary_interval
+        };
+        // Ticks come a little early or late, half a tick of tolerance keeps the rate steady
+        last_sent.is_none_or(|sent| now.duration_since(sent) + self.moving_interval / 2 >= interval)
+    }
 }
 
-fn register_tracing_subscriber(level_filter: LevelFilter) {
-    tracing_subscriber::registry()
-        .with(
-            EnvFilter::builder()
-                .with_default_directive(level_filter.into())
-                .from_env_lossy(),
+/// Time zone of the race, the time is sent in UTC with the offset to this time zone
+#[derive(Clone, Copy, Debug)]
+enum RaceTimeZone {
+    Utc,
+    Local,
+    Named(Tz),
+}
+
+impl RaceTimeZone {
+    /// Offset to UTC in minutes at the given moment, so daylight saving time switches are followed
+    fn utc_offset(self, datetime: DateTime<Utc>) -> i16 {
+        let seconds = match self {
+            RaceTimeZone::Utc => 0,
+            RaceTimeZone::Local => datetime.with_timezone(&Local).offset().local_minus_utc(),
+            RaceTimeZone::Named(time_zone) => datetime
+                .with_timezone(&time_zone)
+                .offset()
+                .fix()
+                .local_minus_utc(),
+        };
+        (seconds / 60) as i16
+    }
+}
+
+fn parse_time_zone(name: &str) -> Result<Tz, String> {
+    name.parse::<Tz>().map_err(|error| error.to_string())
+}
+
+/// Read gpsd in a blocking thread, reconnecting with a backoff when gpsd (re)starts.
+/// `None` is sent while there is no connection, so no outdated position is sent on the bus
+fn read_gpsd(sender: watch::Sender<Option<GPSData>>) {
+    let mut retry_interval = RECONNECT_MIN_INTERVAL;
+    loop {
+        let mut gps: GPS = match GPS::connect() {
+            Ok(gps) => gps,
+            Err(e) => {
+                warn!(
+                    "Unable to connect to gpsd, retrying in {:?}: {e}",
+                    retry_interval
+                );
+                thread::sleep(retry_interval);
+                retry_interval = (retry_interval * 2).min(RECONNECT_MAX_INTERVAL);
+                continue;
+            }
+        };
+        info!("Connected to gpsd server");
+        retry_interval = RECONNECT_MIN_INTERVAL;
+
+        loop {
+            match gps.current_data() {
+                Ok(data) => {
+                    debug!("{data:#?}");
+                    if sender.send(Some(data)).is_err() {
+                        return;
+                    }
+                }
+                Err(e) => {
+                    warn!("Lost connection to gpsd, reconnecting: {e:?}");
+                    if sender.send(None).is_err() {
+                        return;
+                    }
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Speed over ground and heading, sent on a schedule of its own, see [`SpeedRate`]
+fn speed_frame(data: &GPSData) -> CanFrame {
+    CanFrame::new(
+        StandardId::new(0x201).unwrap(),
+        &data
+            .convert_speed(false) //kph
+            .to_le_bytes()
+            .iter()
+            .chain(data.track.to_le_bytes().iter())
+            .copied()
+            .collect::<Vec<u8>>(),
+    )
+    .unwrap()
+}
+
+/// The larger of the longitude and the latitude error estimates of gpsd in cm, unknown when
+/// neither is a number
+fn horizontal_error(data: &GPSData) -> u16 {
+    let error_m = data.epx.max(data.epy);
+    if error_m.is_nan() {
+        return GNSS_HORIZONTAL_ERROR_UNKNOWN;
+    }
+    (error_m * 100.0)
+        .round()
+        .clamp(0.0, (GNSS_HORIZONTAL_ERROR_UNKNOWN - 1) as f32) as u16
+}
+
+fn gnss_frames(data: &GPSData, time_zone: RaceTimeZone) -> Vec<CanFrame> {
+    let fix: u8 = matches!(data.mode, gpsd_client::Fix::Fix3D) as u8;
+    // Same numbers as the gpsd mode
+    let fix_mode: u8 = match data.mode {
+        gpsd_client::Fix::Fix3D => 3,
+        gpsd_client::Fix::Fix2D => 2,
+        _ => 1,
+    };
+
+    let horizontal_error = horizontal_error(data).to_le_bytes();
+
+    let mut frames = vec![
+        CanFrame::new(
+            StandardId::new(0x200).unwrap(),
+            &[fix, data.sats, data.sats_valid],
         )
-        .with(
-            tracing_subscriber::fmt::layer()
-                .with_file(true)
-                .with_line_number(true),
+        .unwrap(),
+        CanFrame::new(StandardId::new(0x202).unwrap(), &data.lat.to_le_bytes()).unwrap(),
+        CanFrame::new(StandardId::new(0x203).unwrap(), &data.lon.to_le_bytes()).unwrap(),
+        CanFrame::new(StandardId::new(0x205).unwrap(), &data.alt.to_le_bytes()).unwrap(),
+        CanFrame::new(
+            StandardId::new(0x206).unwrap(),
+            &[fix_mode, horizontal_error[0], horizontal_error[1]],
         )
-        .init();
+        .unwrap(),
+    ];
+
+    // gpsd has no time before the receiver got it from the satellites
+    match data.time.parse::<DateTime<Utc>>() {
+        Ok(datetime) => {
+            let utc_offset = time_zone.utc_offset(datetime);
+            let hour: u8 = datetime.hour().try_into().unwrap();
+            let minute: u8 = datetime.minute().try_into().unwrap();
+            let second: u8 = datetime.second().try_into().unwrap();
+            let year: u16 = datetime.year().try_into().unwrap();
+            let month: u8 = datetime.month().try_into().unwrap();
+            let day: u8 = datetime.day().try_into().unwrap();
+
+            frames.push(
+                CanFrame::new(
+                    StandardId::new(0x204).unwrap(),
+                    &year
+                        .to_le_bytes()
+                        .iter()
+                        .chain(month.to_le_bytes().iter())
+                        .chain(day.to_le_bytes().iter())
+                        .chain(hour.to_le_bytes().iter())
+                        .chain(minute.to_le_bytes().iter())
+                        .chain(second.to_le_bytes().iter())
+                        .copied()
+                        .collect::<Vec<u8>>(),
+                )
+                .unwrap(),
+            );
+            frames.push(
+                CanFrame::new(StandardId::new(0x207).unwrap(), &utc_offset.to_le_bytes()).unwrap(),
+            );
+            // The clock of this computer, which timestamps the CAN logs, rather than the time of
+            // the fix, which is already old when gpsd reports it. The boards sync their clocks to it
+            let utc_us = Utc::now().timestamp_micros() as u64;
+            frames.push(
+                CanFrame::new(StandardId::new(0x208).unwrap(), &utc_us.to_le_bytes()).unwrap(),
+            );
+            debug!(
+                "Time: {:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC, offset {} minutes",
+                year, month, day, hour, minute, second, utc_offset
+            );
+        }
+        Err(e) => debug!("No valid time {:?}: {e}", data.time),
+    }
 
     info!(
-        "Environment override for debug level to: {:?}",
-        level_filter
+        "Fix: {fix}, Sats: {}, Sats Valid: {}, Speed: {} kph, Track: {} degrees, Latitude, Longitude: {},{}, Altitude: {} m",
+        data.sats,
+        data.sats_valid,
+        data.convert_speed(false),
+        data.track,
+        data.lat,
+        data.lon,
+        data.alt
     );
+    frames
 }
+
 #[tokio::main]
 async fn main() {
-    register_tracing_subscriber(LevelFilter::INFO);
+    LogFilter::init(LevelFilter::INFO);
+    eoi_can_source::install_panic_hook("eoi-gnss-to-can");
     let args = Args::parse();
     info!("CAN interface: {}", args.can_interface);
 
-    // Connecting to the gpsd socket server.
-    let mut gps: GPS = match GPS::connect() {
-        Ok(t) => t,
-        Err(e) => {
-            error!("{e}");
-            process::exit(1);
-        }
-    };
-
-    info!("Connected to gpsd server");
-
     let can_sock: socketcan::tokio::AsyncCanSocket<socketcan::CanSocket> =
         CanSocket::open(args.can_interface.as_str()).expect("Unable to open CAN socket");
     info!("Connected to CAN interface: {}", args.can_interface);
 
-    loop {
-        let data: GPSData = gps.current_data().unwrap();
-        debug!("{data:#?}");
-
-        let fix: u8 = matches!(data.mode, gpsd_client::Fix::Fix3D) as u8;
-        let datetime: DateTime<Utc> = data.time.parse().expect("Invalid ISO8601 format");
-        let datetime: DateTime<Local> = datetime.with_timezone(&Local);
-        let hour: u8 = datetime.hour().try_into().unwrap();
-        let minute: u8 = datetime.minute().try_into().unwrap();
-        let second: u8 = datetime.second().try_into().unwrap();
-        let year: u16 = datetime.year().try_into().unwrap();
-        let month: u8 = datetime.month().try_into().unwrap();
-        let day: u8 = datetime.day().try_into().unwrap();
-
-        let can_block = [
-            CanFrame::new(
-                StandardId::new(0x200).unwrap(),
-                &[fix, data.sats, data.sats_valid],
-            )
-            .unwrap(),
-            CanFrame::new(
-                StandardId::new(0x201).unwrap(),
-                &data
-                    .convert_speed(false) //kph
-                    .to_le_bytes()
-                    .iter()
-                    .chain(data.track.to_le_bytes().iter())
-                    .copied()
-                    .collect::<Vec<u8>>(),
-            )
-            .unwrap(),
-            CanFrame::new(StandardId::new(0x202).unwrap(), &data.lat.to_le_bytes()).unwrap(),
-            CanFrame::new(StandardId::new(0x203).unwrap(), &data.lon.to_le_bytes()).unwrap(),
-            CanFrame::new(
-                StandardId::new(0x204).unwrap(),
-                &year
-                    .to_le_bytes()
-                    .iter()
-                    .chain(month.to_le_bytes().iter())
-                    .chain(day.to_le_bytes().iter())
-                    .chain(hour.to_le_bytes().iter())
-                    .chain(minute.to_le_bytes().iter())
-                    .chain(second.to_le_bytes().iter())
-                    .copied()
-                    .collect::<Vec<u8>>(),
-            )
-            .unwrap(),
-        ];
-
-        for frame in can_block.iter() {
-            trace!("CAN frame: {:?}", frame);
-
-            match can_sock.write_frame(*frame).await {
-                Ok(_) => trace!("Sent CAN frame: {:?}", frame),
-                Err(e) => error!("Failed to send CAN frame: {e}"),
-            }
+    let time_zone = match (args.utc, args.timezone) {
+        (true, _) => RaceTimeZone::Utc,
+        (false, Some(time_zone)) => RaceTimeZone::Named(time_zone),
+        (false, None) => RaceTimeZone::Local,
+    };
+    info!("Time zone shown on the display: {:?}", time_zone);
+
+    // The gpsd client blocks while reading, so keep it out of the async runtime
+    let (sender, mut receiver) = watch::channel(None);
+    let speed_receiver = receiver.clone();
+    thread::spawn(move || read_gpsd(sender));
+
+    // Only frames that were written count as sent, a frame lost on a busy bus is sent again
+    let (_, transmitter) = CanTransmitter::spawn(can_sock);
+    let on_change = args.on_change;
+    let written = transmitter.clone();
+
+    let gnss_sender = PeriodicSender::new().with_frames(args.interval, move || {
+        let mut frames = match receiver.borrow_and_update().as_ref() {
+            Some(data) => gnss_frames(data, time_zone),
+            None => return Vec::new(),
+        };
+        let now = Instant::now();
+        if on_change {
+            frames.retain(|frame| {
+                let unchanged = written
+                    .last_written(frame.id())
+                    .is_some_and(|(data, sent)| {
+                        data == frame.data() && now.duration_since(sent) < RESEND_INTERVAL
+                    });
+                if unchanged {
+                    trace!("Unchanged CAN frame: {:?}", frame);
+                }
+                !unchanged
+            });
         }
+        frames
+    });
 
-        info!(
-            "Fix: {fix}, Sats: {}, Sats Valid: {}",
-            data.sats, data.sats_valid
-        );
-        info!(
-            "Speed: {} kph, Track: {} degrees",
-            data.convert_speed(false),
-            data.track
-        );
-        info!("Latitude, Longitude: {},{}", data.lat, data.lon);
-        info!("Time: {:02}:{:02}:{:02}", hour, minute, second);
-        info!("Date: {:04}-{:02}-{:02}", year, month, day);
-
-        thread::sleep(Duration::from_millis(1000));
+    info!(
+        "Speed every {:?} above {} km/h, every {:?} below",
+        args.moving_interval, args.moving_speed, args.stationary_interval
+    );
+    let speed_rate = SpeedRate {
+        moving_interval: args.moving_interval,
+        stationary_interval: args.stationary_interval,
+        moving_speed: args.moving_speed,
+    };
+    let written = transmitter.clone();
+    let gnss_sender = gnss_sender.with_frames(args.moving_interval, move || {
+        let data = speed_receiver.borrow();
+        let data = data.as_ref()?;
+        let frame = speed_frame(data);
+        let last_sent = written.last_written(frame.id()).map(|(_, sent)| sent);
+        speed_rate
+            .is_due(data.convert_speed(false), Instant::now(), last_sent)
+            .then_some(frame)
+    });
+    if let Err(error) = gnss_sender.spawn(transmitter).await {
+        error!("Sending the GNSS frames stopped: {}", error);
     }
 }
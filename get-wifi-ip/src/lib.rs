@@ -1,14 +1,140 @@
+//! Addresses and signal strength of the WiFi interface, shown on the displays so we know where to
+//! reach the datalogger.
+
+mod watch;
+
+pub use watch::watch_ip;
+
+use std::net::{IpAddr, Ipv4Addr};
+
 use network_interface::NetworkInterface;
 use network_interface::NetworkInterfaceConfig;
 
-pub fn get_wifi_ip() -> Option<core::net::Ipv4Addr> {
-    let network_interfaces = NetworkInterface::show().unwrap_or(vec![]);
-    for itf in network_interfaces.iter() {
-        if itf.name.starts_with("w")
-            && let Some(&network_interface::Addr::V4(ip)) = itf.addr.first()
-        {
-            return Some(ip.ip);
+/// Matches the usual names of WiFi interfaces, like `wlan0` and `wlp2s0`
+pub const DEFAULT_INTERFACE_PATTERN: &str = "w*";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WifiStatus {
+    pub interface: String,
+    /// IPv4 and IPv6 addresses, in the order the system reports them
+    pub addresses: Vec<IpAddr>,
+    /// `None` when not asked for or the interface isn't in `/proc/net/wireless`
+    pub signal: Option<WifiSignal>,
+}
+
+impl WifiStatus {
+    pub fn ipv4(&self) -> Option<Ipv4Addr> {
+        self.addresses.iter().find_map(|address| match address {
+            IpAddr::V4(address) => Some(*address),
+            IpAddr::V6(_) => None,
+        })
+    }
+}
+
+/// Signal of the access point, as the driver reports it in `/proc/net/wireless`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WifiSignal {
+    /// Link quality, the scale depends on the driver (usually up to 70)
+    pub link_quality: f32,
+    /// Received signal strength (RSSI) in dBm
+    pub level_dbm: f32,
+}
+
+/// First IPv4 address of the first interface that looks like WiFi
+pub fn get_wifi_ip() -> Option<Ipv4Addr> {
+    wifi_status(DEFAULT_INTERFACE_PATTERN, false)?.ipv4()
+}
+
+/// Addresses of the first interface whose name matches `pattern` and has an address, `*` in the
+/// pattern matches any number of characters. The signal is only read with `with_signal`.
+pub fn wifi_status(pattern: &str, with_signal: bool) -> Option<WifiStatus> {
+    let network_interfaces = NetworkInterface::show().unwrap_or_default();
+    // Every address of an interface shows up as its own entry
+    let interface = network_interfaces
+        .iter()
+        .find(|itf| matches_pattern(pattern, &itf.name) && !itf.addr.is_empty())?;
+    let addresses = network_interfaces
+        .iter()
+        .filter(|itf| itf.name == interface.name)
+        .flat_map(|itf| itf.addr.iter().map(|addr| addr.ip()))
+        .collect();
+
+    let signal = if with_signal {
+        std::fs::read_to_string("/proc/net/wireless")
+            .ok()
+            .and_then(|wireless| parse_wireless(&wireless, &interface.name))
+    } else {
+        None
+    };
+
+    Some(WifiStatus {
+        interface: interface.name.clone(),
+        addresses,
+        signal,
+    })
+}
+
+/// Glob match supporting only `*`
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == name,
+        Some((prefix, rest)) => {
+            let Some(name) = name.strip_prefix(prefix) else {
+                return false;
+            };
+            // Try every split of the name for the star
+            name.char_indices()
+                .map(|(index, _)| index)
+                .chain(std::iter::once(name.len()))
+                .any(|index| matches_pattern(rest, &name[index..]))
         }
     }
-    None
+}
+
+/// Signal of `interface` from the contents of `/proc/net/wireless`, which looks like
+/// ` wlan0: 0000   70.  -40.  -256        0      0      0      0      0        0`
+fn parse_wireless(wireless: &str, interface: &str) -> Option<WifiSignal> {
+    let line = wireless
+        .lines()
+        .find_map(|line| line.trim_start().strip_prefix(interface)?.strip_prefix(':'))?;
+    let mut fields = line.split_whitespace().skip(1);
+    let mut next_value = || fields.next()?.trim_end_matches('.').parse().ok();
+    Some(WifiSignal {
+        link_quality: next_value()?,
+        level_dbm: next_value()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+
+    #[test]
+    fn interface_patterns() {
+        assert!(matches_pattern("w*", "wlan0"));
+        assert!(matches_pattern("wlan0", "wlan0"));
+        assert!(matches_pattern("*0", "wlan0"));
+        assert!(matches_pattern("wl*n*", "wlan0"));
+        assert!(!matches_pattern("w*", "eth0"));
+        assert!(!matches_pattern("wlan", "wlan0"));
+        assert!(!matches_pattern("*1", "wlan0"));
+    }
+
+    #[test]
+    fn parse_signal() {
+        let wireless = "\
+Inter-| sta-|   Quality        |   Discarded packets               | Missed | WE
+ face | tus | link level noise |  nwid  crypt   frag  retry   misc | beacon | 22
+  wlan0: 0000   58.  -52.  -256        0      0      0      0     12        0
+";
+        assert!(
+            parse_wireless(wireless, "wlan0")
+                == Some(WifiSignal {
+                    link_quality: 58.0,
+                    level_dbm: -52.0,
+                })
+        );
+        assert!(parse_wireless(wireless, "wlan1").is_none());
+    }
 }
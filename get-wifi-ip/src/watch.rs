@@ -0,0 +1,105 @@
+//! Watching the WiFi interface for address changes, so the displays don't have to ask for all
+//! interfaces on every frame they draw.
+//!
+//! The kernel announces new and removed addresses over a netlink socket. Its messages only wake
+//! the watcher up, the status itself is read the same way [`wifi_status`] always does.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::time::Duration;
+
+use tokio::io::unix::AsyncFd;
+use tokio::sync::watch;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::{WifiStatus, wifi_status};
+
+/// The kernel doesn't announce changes of the signal, so it is read again this often
+const SIGNAL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Watch the first interface matching `pattern`, see [`wifi_status`]. The receiver is updated
+/// when an address is added or removed and when the signal changes, the watcher stops when all
+/// receivers are dropped. Needs to be called within a tokio runtime.
+pub fn watch_ip(pattern: &str) -> io::Result<watch::Receiver<Option<WifiStatus>>> {
+    let socket = AsyncFd::new(address_changes_socket()?)?;
+    let pattern = pattern.to_string();
+    let (sender, receiver) = watch::channel(wifi_status(&pattern, true));
+
+    tokio::spawn(async move {
+        let mut signal_interval = tokio::time::interval(SIGNAL_INTERVAL);
+        let mut buffer = [0; 4096];
+        loop {
+            tokio::select! {
+                guard = socket.readable() => {
+                    let mut guard = match guard {
+                        Ok(guard) => guard,
+                        Err(error) => {
+                            error!("Stopped watching the WiFi addresses: {:?}", error);
+                            return;
+                        }
+                    };
+                    // Only the wake up matters, so just empty the socket
+                    while let Ok(result) = guard.try_io(|socket| socket.get_ref().read(&mut buffer)) {
+                        if let Err(error) = result {
+                            // Like a full receive buffer, the next read works again
+                            debug!("Failed to read netlink message: {:?}", error);
+                            break;
+                        }
+                    }
+                }
+                _ = signal_interval.tick() => {}
+                _ = sender.closed() => return,
+            }
+
+            let status = wifi_status(&pattern, true);
+            sender.send_if_modified(|current| {
+                if *current == status {
+                    return false;
+                }
+                trace!("WiFi status changed: {:?}", status);
+                *current = status;
+                true
+            });
+        }
+    });
+
+    Ok(receiver)
+}
+
+/// Non blocking netlink socket receiving the address and link changes of all interfaces
+fn address_changes_socket() -> io::Result<File> {
+    // SAFETY: Creates a new socket, which is owned right after checking for an error
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_RAW | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+            libc::NETLINK_ROUTE,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: The descriptor was just created and nothing else owns it
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    // SAFETY: All zeros is a valid address, it has padding so it can't be built field by field
+    let mut address: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+    address.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+    address.nl_groups =
+        (libc::RTMGRP_LINK | libc::RTMGRP_IPV4_IFADDR | libc::RTMGRP_IPV6_IFADDR) as u32;
+    // SAFETY: The address is a valid `sockaddr_nl` and its size is given along
+    let result = unsafe {
+        libc::bind(
+            fd.as_raw_fd(),
+            (&raw const address).cast::<libc::sockaddr>(),
+            size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        )
+    };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(File::from(fd))
+}
@@ -1,39 +1,196 @@
+//! Client for the PiSugar power manager server, which answers `get <property>` requests on a TCP
+//! port with a single `<property>: <value>` line.
+
+use std::time::Duration;
+
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 
-pub async fn battery_info() -> Result<(f32, bool), Box<dyn std::error::Error + Send + Sync>> {
-    let addr = "127.0.0.1:8423";
+#[cfg(any(test, feature = "simulated"))]
+pub mod simulated;
 
-    let command_soc = b"get battery\n";
-    let command_charging = b"get battery_power_plugged\n";
+#[derive(Debug)]
+pub enum PiSugarError {
+    Io(std::io::Error),
+    /// The server didn't answer within the timeout of the client
+    Timeout,
+    /// The answer isn't `<property>: <value>` or the value couldn't be parsed
+    InvalidResponse {
+        property: &'static str,
+        response: String,
+    },
+}
 
-    let soc = {
-        let mut stream = TcpStream::connect(addr).await?;
-        stream.write_all(command_soc).await?;
-        stream.shutdown().await?;
-        let mut buffer = Vec::new();
-        stream.read_to_end(&mut buffer).await?;
-        let buffer_str = String::from_utf8_lossy(&buffer);
-        let soc_str = buffer_str
-            .split(':')
-            .nth(1)
-            .ok_or("Failed to parse SOC from response")?;
-        soc_str.trim().parse()?
-    };
-
-    let charging = {
-        let mut stream = TcpStream::connect(addr).await?;
-        stream.write_all(command_charging).await?;
+impl std::fmt::Display for PiSugarError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PiSugarError::Io(error) => write!(f, "Unable to reach the PiSugar server: {}", error),
+            PiSugarError::Timeout => write!(f, "The PiSugar server didn't answer in time"),
+            PiSugarError::InvalidResponse { property, response } => {
+                write!(
+                    f,
+                    "Invalid PiSugar response for {}: {:?}",
+                    property, response
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for PiSugarError {}
+
+impl From<std::io::Error> for PiSugarError {
+    fn from(error: std::io::Error) -> Self {
+        PiSugarError::Io(error)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PiSugarClient {
+    address: String,
+    timeout: Duration,
+}
+
+impl Default for PiSugarClient {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_ADDRESS)
+    }
+}
+
+impl PiSugarClient {
+    /// Where the PiSugar server listens by default
+    pub const DEFAULT_ADDRESS: &str = "127.0.0.1:8423";
+    /// Time a single request may take, including connecting
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+    pub fn new(address: impl Into<String>) -> Self {
+        Self {
+            address: address.into(),
+            timeout: Self::DEFAULT_TIMEOUT,
+        }
+    }
+
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+
+    /// State of charge and whether the charger is plugged in
+    pub async fn battery_info(&self) -> Result<(f32, bool), PiSugarError> {
+        Ok((self.state_of_charge().await?, self.charging().await?))
+    }
+
+    /// State of charge in percent
+    pub async fn state_of_charge(&self) -> Result<f32, PiSugarError> {
+        self.get_parsed("battery").await
+    }
+
+    /// Whether the charger is plugged in
+    pub async fn charging(&self) -> Result<bool, PiSugarError> {
+        self.get_parsed("battery_power_plugged").await
+    }
+
+    /// Battery voltage in volts
+    pub async fn voltage(&self) -> Result<f32, PiSugarError> {
+        self.get_parsed("battery_v").await
+    }
+
+    /// Temperature of the PiSugar board in degrees Celsius
+    pub async fn temperature(&self) -> Result<f32, PiSugarError> {
+        self.get_parsed("temperature").await
+    }
+
+    /// Model of the PiSugar, like `PiSugar 3`
+    pub async fn model(&self) -> Result<String, PiSugarError> {
+        self.get("model").await
+    }
+
+    /// Time of the real time clock of the PiSugar, as RFC 3339 like `2024-05-04T13:37:00+02:00`
+    pub async fn rtc_time(&self) -> Result<String, PiSugarError> {
+        self.get("rtc_time").await
+    }
+
+    async fn get_parsed<T: std::str::FromStr>(
+        &self,
+        property: &'static str,
+    ) -> Result<T, PiSugarError> {
+        let value = self.get(property).await?;
+        value.parse().map_err(|_| PiSugarError::InvalidResponse {
+            property,
+            response: value,
+        })
+    }
+
+    async fn get(&self, property: &'static str) -> Result<String, PiSugarError> {
+        let response = tokio::time::timeout(self.timeout, self.request(property))
+            .await
+            .map_err(|_| PiSugarError::Timeout)??;
+        parse_response(property, &response)
+            .ok_or(PiSugarError::InvalidResponse { property, response })
+    }
+
+    async fn request(&self, property: &str) -> Result<String, std::io::Error> {
+        let mut stream = TcpStream::connect(&self.address).await?;
+        stream
+            .write_all(format!("get {}\n", property).as_bytes())
+            .await?;
         stream.shutdown().await?;
         let mut buffer = Vec::new();
         stream.read_to_end(&mut buffer).await?;
-        let buffer_str = String::from_utf8_lossy(&buffer);
-        let charging_str = buffer_str
-            .split(':')
-            .nth(1)
-            .ok_or("Failed to parse charging status from response")?;
-        charging_str.trim() == "true"
-    };
-
-    Ok((soc, charging))
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+}
+
+/// The value of a `<property>: <value>` response, the value itself may contain colons
+fn parse_response(property: &str, response: &str) -> Option<String> {
+    let (name, value) = response.split_once(':')?;
+    (name.trim() == property).then(|| value.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn parse_responses() {
+        assert!(parse_response("battery", "battery: 87.5\n") == Some("87.5".to_string()));
+        assert!(
+            parse_response("rtc_time", "rtc_time: 2024-05-04T13:37:00+02:00\n")
+                == Some("2024-05-04T13:37:00+02:00".to_string())
+        );
+        assert!(parse_response("battery", "battery_v: 4.1\n").is_none());
+        assert!(parse_response("battery", "Invalid request.\n").is_none());
+    }
+
+    #[tokio::test]
+    async fn query_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = PiSugarClient::new(listener.local_addr().unwrap().to_string());
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = String::new();
+                stream.read_to_string(&mut request).await.unwrap();
+                let response = match request.as_str() {
+                    "get battery\n" => "battery: 87.5\n",
+                    "get battery_power_plugged\n" => "battery_power_plugged: true\n",
+                    _ => "Invalid request.\n",
+                };
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        let (state_of_charge, charging) = client.battery_info().await.unwrap();
+        assert!(state_of_charge == 87.5);
+        assert!(charging);
+        let error = client.model().await.unwrap_err();
+        assert!(matches!(
+            error,
+            PiSugarError::InvalidResponse {
+                property: "model",
+                ..
+            }
+        ));
+    }
 }
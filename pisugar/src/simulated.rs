@@ -0,0 +1,198 @@
+//! A PiSugar server on a local port for the tests of the display battery, without a PiSugar or
+//! its server. It answers `get battery`, `get battery_power_plugged`, `get battery_v` and
+//! `get model` like the real one, and can misbehave like a server that is starting up, hanging
+//! or talking to a PiSugar with a loose I2C connection.
+
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+use crate::PiSugarClient;
+
+/// How the simulated server misbehaves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Sends the first half of the answer and closes the connection
+    PartialResponse,
+    /// Reads the request and never answers
+    NoResponse,
+    /// Closes the connection without an answer
+    Closed,
+    /// Answers `Invalid request.` like for an unknown property
+    InvalidRequest,
+}
+
+#[derive(Debug)]
+struct State {
+    state_of_charge: f32,
+    plugged: bool,
+    voltage: f32,
+    fault: Option<Fault>,
+    requests: usize,
+}
+
+/// Stops serving when dropped
+#[derive(Debug)]
+pub struct SimulatedPiSugar {
+    address: String,
+    state: Arc<Mutex<State>>,
+    task: JoinHandle<()>,
+}
+
+impl SimulatedPiSugar {
+    /// Serve on a free port of localhost, with a full battery and no charger
+    pub async fn start() -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?.to_string();
+        let state = Arc::new(Mutex::new(State {
+            state_of_charge: 100.0,
+            plugged: false,
+            voltage: 4.2,
+            fault: None,
+            requests: 0,
+        }));
+        let task = tokio::spawn(serve(listener, state.clone()));
+        Ok(Self {
+            address,
+            state,
+            task,
+        })
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// A client of the server with a short timeout, so the tests of a hanging server are quick
+    pub fn client(&self) -> PiSugarClient {
+        PiSugarClient::new(self.address.as_str())
+            .with_timeout(std::time::Duration::from_millis(200))
+    }
+
+    pub fn set_state_of_charge(&self, state_of_charge: f32) {
+        self.state.lock().unwrap().state_of_charge = state_of_charge;
+    }
+
+    pub fn set_plugged(&self, plugged: bool) {
+        self.state.lock().unwrap().plugged = plugged;
+    }
+
+    pub fn set_voltage(&self, voltage: f32) {
+        self.state.lock().unwrap().voltage = voltage;
+    }
+
+    /// Misbehave from the next request on, `None` to answer normally again
+    pub fn set_fault(&self, fault: Option<Fault>) {
+        self.state.lock().unwrap().fault = fault;
+    }
+
+    /// Requests received so far
+    pub fn requests(&self) -> usize {
+        self.state.lock().unwrap().requests
+    }
+}
+
+impl Drop for SimulatedPiSugar {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn serve(listener: TcpListener, state: Arc<Mutex<State>>) {
+    while let Ok((stream, _)) = listener.accept().await {
+        tokio::spawn(answer(stream, state.clone()));
+    }
+}
+
+async fn answer(mut stream: TcpStream, state: Arc<Mutex<State>>) {
+    // The client shuts down its side after the request
+    let mut request = String::new();
+    if stream.read_to_string(&mut request).await.is_err() {
+        return;
+    }
+    let (response, fault) = {
+        let mut state = state.lock().unwrap();
+        state.requests += 1;
+        let response = match request.trim().strip_prefix("get ") {
+            Some("battery") => format!("battery: {}\n", state.state_of_charge),
+            Some("battery_power_plugged") => {
+                format!("battery_power_plugged: {}\n", state.plugged)
+            }
+            Some("battery_v") => format!("battery_v: {}\n", state.voltage),
+            Some("model") => "model: PiSugar 3\n".to_string(),
+            _ => "Invalid request.\n".to_string(),
+        };
+        (response, state.fault)
+    };
+    let response = match fault {
+        None => response,
+        Some(Fault::PartialResponse) => response[..response.len() / 2].to_string(),
+        Some(Fault::NoResponse) => {
+            // Held open until the client gives up
+            std::future::pending::<()>().await;
+            return;
+        }
+        Some(Fault::Closed) => return,
+        Some(Fault::InvalidRequest) => "Invalid request.\n".to_string(),
+    };
+    // The client may have given up already
+    _ = stream.write_all(response.as_bytes()).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PiSugarError;
+    use assert2::assert;
+
+    #[tokio::test]
+    async fn battery_of_the_simulated_server() {
+        let server = SimulatedPiSugar::start().await.unwrap();
+        let client = server.client();
+        server.set_state_of_charge(42.5);
+        server.set_plugged(true);
+        server.set_voltage(3.9);
+        assert!(client.battery_info().await.unwrap() == (42.5, true));
+        assert!(client.voltage().await.unwrap() == 3.9);
+        assert!(client.model().await.unwrap() == "PiSugar 3");
+        assert!(server.requests() == 4);
+    }
+
+    #[tokio::test]
+    async fn fault_modes() {
+        let server = SimulatedPiSugar::start().await.unwrap();
+        let client = server.client();
+        server.set_state_of_charge(87.5);
+
+        server.set_fault(Some(Fault::PartialResponse));
+        let error = client.state_of_charge().await.unwrap_err();
+        assert!(matches!(
+            error,
+            PiSugarError::InvalidResponse {
+                property: "battery",
+                ..
+            }
+        ));
+
+        server.set_fault(Some(Fault::NoResponse));
+        let error = client.state_of_charge().await.unwrap_err();
+        assert!(matches!(error, PiSugarError::Timeout));
+
+        server.set_fault(Some(Fault::Closed));
+        // Nothing to parse
+        let error = client.charging().await.unwrap_err();
+        assert!(
+            matches!(error, PiSugarError::InvalidResponse { response, .. } if response.is_empty())
+        );
+
+        server.set_fault(Some(Fault::InvalidRequest));
+        let error = client.battery_info().await.unwrap_err();
+        assert!(matches!(error, PiSugarError::InvalidResponse { .. }));
+
+        // Back to normal, like after the server restarted
+        server.set_fault(None);
+        assert!(client.state_of_charge().await.unwrap() == 87.5);
+    }
+}
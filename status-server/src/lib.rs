@@ -0,0 +1,590 @@
+//! Small HTTP server for the data on the display, so the chase car can poll the boat over WiFi:
+//!
+//! - `GET /api/state`: the current [`DisplayData`] as JSON, stale values are `null`
+//! - `GET /api/alarms`: the active alarms and the kept history of raised and cleared ones, newest
+//!   first, like `{"active": ["reserve"], "events": [{"age": 12.5, "alarm": "reserve", ...}]}`. The
+//!   age of the events of an earlier run read back from the alarm log is `null`
+//! - `GET /metrics`: the key values in the Prometheus text format
+//! - `GET /ws`: WebSocket pushing every decoded [`EoiCanData`] as JSON text message, like
+//!   `{"Gnss":{"GnssSpeedAndHeading":[20.5,90.0]}}`, so a browser can mirror the display
+//! - `GET /ws/frames`: WebSocket pushing every received CAN frame as candump log line, like
+//!   `(1718900000.250000) can0 242#0000C03F`, for the web viewer to decode and draw the display
+//!   exactly like the boat does
+//! - `GET /signalk/v1/stream`: WebSocket pushing position, speed, course and battery as
+//!   [`signal_k`] deltas for chart plotters, `GET /signalk` points Signal K clients to it
+//! - `GET /api/log-filter`: the tracing filter of the binary like `{"filter": "info"}`, `POST` a
+//!   [`LogFilterCommand`] to change it while it runs. Only with [`Status::with_log_filter`]
+
+use axum::Router;
+use axum::extract::State;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::IntoResponse;
+use axum::routing::get;
+use can_logger::format_candump_line;
+use draw_display::{DisplayData, DisplayValue};
+use eoi_can_decoder::EoiCanData;
+use eoi_can_decoder::can_frame::CanFrame;
+use eoi_can_source::{LogFilter, LogFilterCommand};
+use serde_json::{Value, json};
+use signal_k::SignalK;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+#[allow(unused_imports)]
+use tracing::{debug, error, info, trace, warn};
+
+pub mod signal_k;
+
+/// Decoded messages buffered per WebSocket client, slower clients skip messages
+const LIVE_BUFFER_SIZE: usize = 256;
+
+/// Latest snapshot of the display data and the live decoded data, cheap to clone and share with
+/// the server
+#[derive(Clone)]
+pub struct Status {
+    snapshot: Arc<Mutex<Snapshot>>,
+    live: broadcast::Sender<String>,
+    frames: broadcast::Sender<String>,
+    signal_k: broadcast::Sender<String>,
+    /// Also updated without clients, so the first delta they get has the position
+    signal_k_state: Arc<Mutex<SignalK>>,
+    log_filter: Option<LogFilter>,
+}
+
+#[derive(Default)]
+struct Snapshot {
+    state: Value,
+    alarms: Value,
+    metrics: String,
+}
+
+impl Default for Status {
+    fn default() -> Self {
+        Self {
+            snapshot: Arc::default(),
+            live: broadcast::Sender::new(LIVE_BUFFER_SIZE),
+            frames: broadcast::Sender::new(LIVE_BUFFER_SIZE),
+            signal_k: broadcast::Sender::new(LIVE_BUFFER_SIZE),
+            signal_k_state: Arc::default(),
+            log_filter: None,
+        }
+    }
+}
+
+impl Status {
+    /// Take a new snapshot, call this whenever the display data was updated
+    pub fn update(&self, display_data: &DisplayData) {
+        let state = serde_json::to_value(display_data).unwrap_or_else(|error| {
+            warn!("Failed to serialize display data: {:?}", error);
+            Value::Null
+        });
+        let alarms = serde_json::to_value(&display_data.alarm_history).unwrap_or_else(|error| {
+            warn!("Failed to serialize the alarm history: {:?}", error);
+            Value::Null
+        });
+        let metrics = prometheus_metrics(display_data);
+
+        if let Ok(mut snapshot) = self.snapshot.lock() {
+            *snapshot = Snapshot {
+                state,
+                alarms,
+                metrics,
+            };
+        }
+    }
+
+    /// Push decoded data to all connected WebSocket clients
+    pub fn publish(&self, data: &EoiCanData) {
+        let delta = self
+            .signal_k_state
+            .lock()
+            .ok()
+            .and_then(|mut signal_k| signal_k.delta(data, SystemTime::now()));
+        if let Some(delta) = delta
+            && self.signal_k.receiver_count() > 0
+        {
+            _ = self.signal_k.send(delta.to_string());
+        }
+
+        // Don't serialize for nobody
+        if self.live.receiver_count() == 0 {
+            return;
+        }
+        match serde_json::to_string(data) {
+            // Only fails when there are no clients
+            Ok(json) => _ = self.live.send(json),
+            Err(error) => warn!("Failed to serialize {:?}: {:?}", data, error),
+        }
+    }
+
+    /// Push a received frame to all connected WebSocket clients of the web viewer, `interface` is
+    /// written into the candump line
+    pub fn publish_frame(&self, interface: &str, frame: &CanFrame) {
+        if self.frames.receiver_count() == 0 {
+            return;
+        }
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let line = format_candump_line(timestamp, interface, frame);
+        _ = self.frames.send(line.trim_end().to_string());
+    }
+
+    /// Let clients change the tracing filter of the binary over `/api/log-filter`
+    pub fn with_log_filter(self, log_filter: LogFilter) -> Self {
+        Self {
+            log_filter: Some(log_filter),
+            ..self
+        }
+    }
+
+    /// Start serving on `address` in the background, errors are logged
+    pub fn spawn(address: SocketAddr) -> Self {
+        Self::default().start(address)
+    }
+
+    /// Like [`Status::spawn`], for a status built with the `with_*` methods
+    pub fn start(self, address: SocketAddr) -> Self {
+        let server = self.clone();
+        tokio::spawn(async move {
+            if let Err(error) = server.serve(address).await {
+                error!("Status server on {} failed: {:?}", address, error);
+            }
+        });
+        self
+    }
+
+    /// Serve the snapshots on `address` until an error occurs
+    pub async fn serve(self, address: SocketAddr) -> std::io::Result<()> {
+        let listener = tokio::net::TcpListener::bind(address).await?;
+        info!("Status server listening on http://{}", address);
+        axum::serve(listener, self.router()).await
+    }
+
+    fn router(self) -> Router {
+        Router::new()
+            .route("/api/state", get(state))
+            .route("/api/alarms", get(alarms))
+            .route("/metrics", get(metrics))
+            .route("/ws", get(websocket))
+            .route("/ws/frames", get(frames_websocket))
+            .route("/signalk", get(signal_k_discovery))
+            .route("/signalk/v1/stream", get(signal_k_stream))
+            .route("/api/log-filter", get(log_filter).post(set_log_filter))
+            .with_state(self)
+    }
+}
+
+async fn state(State(status): State<Status>) -> impl IntoResponse {
+    let state = status
+        .snapshot
+        .lock()
+        .map(|snapshot| snapshot.state.clone())
+        .unwrap_or_default();
+    axum::Json(state)
+}
+
+async fn alarms(State(status): State<Status>) -> impl IntoResponse {
+    let alarms = status
+        .snapshot
+        .lock()
+        .map(|snapshot| snapshot.alarms.clone())
+        .unwrap_or_default();
+    axum::Json(alarms)
+}
+
+async fn metrics(State(status): State<Status>) -> impl IntoResponse {
+    let metrics = status
+        .snapshot
+        .lock()
+        .map(|snapshot| snapshot.metrics.clone())
+        .unwrap_or_default();
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics,
+    )
+}
+
+async fn log_filter(State(status): State<Status>) -> impl IntoResponse {
+    match status.log_filter {
+        Some(log_filter) => Ok(axum::Json(json!({ "filter": log_filter.current() }))),
+        None => Err(StatusCode::NOT_FOUND),
+    }
+}
+
+async fn set_log_filter(
+    State(status): State<Status>,
+    axum::Json(command): axum::Json<LogFilterCommand>,
+) -> impl IntoResponse {
+    let Some(log_filter) = status.log_filter else {
+        return Err((StatusCode::NOT_FOUND, String::new()));
+    };
+    log_filter
+        .apply(&command)
+        .map_err(|error| (StatusCode::BAD_REQUEST, error))?;
+    Ok(axum::Json(json!({ "filter": log_filter.current() })))
+}
+
+async fn websocket(State(status): State<Status>, upgrade: WebSocketUpgrade) -> impl IntoResponse {
+    let receiver = status.live.subscribe();
+    upgrade.on_upgrade(|socket| stream_live_data(socket, receiver))
+}
+
+async fn frames_websocket(
+    State(status): State<Status>,
+    upgrade: WebSocketUpgrade,
+) -> impl IntoResponse {
+    let receiver = status.frames.subscribe();
+    upgrade.on_upgrade(|socket| stream_live_data(socket, receiver))
+}
+
+/// Where Signal K clients find the stream, the URL has to be absolute
+async fn signal_k_discovery(headers: HeaderMap) -> impl IntoResponse {
+    let host = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .unwrap_or("localhost");
+    axum::Json(json!({
+        "endpoints": {
+            "v1": {
+                "version": signal_k::VERSION,
+                "signalk-ws": format!("ws://{}/signalk/v1/stream", host),
+            }
+        },
+        "server": { "id": "eoi-can", "version": env!("CARGO_PKG_VERSION") },
+    }))
+}
+
+async fn signal_k_stream(
+    State(status): State<Status>,
+    upgrade: WebSocketUpgrade,
+) -> impl IntoResponse {
+    let receiver = status.signal_k.subscribe();
+    upgrade.on_upgrade(|mut socket| async move {
+        let hello = signal_k::hello(SystemTime::now()).to_string();
+        if socket.send(Message::Text(hello.into())).await.is_ok() {
+            stream_live_data(socket, receiver).await;
+        }
+    })
+}
+
+/// Push the broadcast to the client until it goes away. The socket is read meanwhile, so pings
+/// are answered and a closed socket ends the stream even while nothing is broadcast
+async fn stream_live_data(mut socket: WebSocket, mut receiver: broadcast::Receiver<String>) {
+    loop {
+        tokio::select! {
+            received = receiver.recv() => match received {
+                Ok(json) => {
+                    if socket.send(Message::Text(json.into())).await.is_err() {
+                        debug!("WebSocket client disconnected");
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    debug!("WebSocket client too slow, skipped {} messages", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            message = socket.recv() => match message {
+                None | Some(Err(_)) | Some(Ok(Message::Close(_))) => {
+                    debug!("WebSocket client disconnected");
+                    break;
+                }
+                // Nothing is expected from the clients
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+/// Gauges of all valid values, values that are stale or never received are left out, except for
+/// the age of the key values
+pub fn prometheus_metrics(data: &DisplayData) -> String {
+    let mut metrics = Metrics::default();
+
+    metrics.gauge("eoi_speed_kmh", "Speed over ground in km/h");
+    metrics.value("", data.speed_kmh.get().copied());
+    metrics.gauge("eoi_gnss_altitude", "Altitude above mean sea level in m");
+    metrics.value("", data.gnss_altitude.get().copied());
+    metrics.gauge(
+        "eoi_gnss_horizontal_error",
+        "Horizontal position error in m",
+    );
+    metrics.value("", data.gnss_horizontal_error.get().copied());
+    metrics.gauge("eoi_laps", "Laps completed since the race started");
+    metrics.value(
+        "",
+        (data.lap_counter.race_time().is_some()).then(|| data.lap_counter.laps() as f32),
+    );
+
+    metrics.gauge(
+        "eoi_battery_state_of_charge",
+        "Battery state of charge in %",
+    );
+    metrics.value("", data.battery_state_of_charge.get().copied());
+    metrics.gauge("eoi_battery_voltage", "Battery pack voltage in V");
+    metrics.value("", data.battery_voltage.get().copied());
+    metrics.gauge("eoi_battery_current", "Battery currents in A");
+    metrics.value("{kind=\"pack\"}", data.battery_current_pack.get().copied());
+    metrics.value("{kind=\"in\"}", data.battery_current_in.get().copied());
+    metrics.value(
+        "{kind=\"motor\"}",
+        data.battery_current_out_motor.get().copied(),
+    );
+    metrics.value(
+        "{kind=\"peripherals\"}",
+        data.battery_current_out_peripherals.get().copied(),
+    );
+    // Cells and sensors are numbered from 1, like in the names of the battery messages
+    metrics.gauge("eoi_battery_cell_voltage", "Battery cell voltages in V");
+    for (cell, voltage) in data.battery_cell_voltages.iter().enumerate() {
+        metrics.value(
+            &format!("{{cell=\"{}\"}}", cell + 1),
+            voltage.get().copied(),
+        );
+    }
+    metrics.gauge("eoi_battery_temperature", "Battery temperatures in °C");
+    for (sensor, temperature) in data.battery_temperatures.iter().enumerate() {
+        metrics.value(
+            &format!("{{sensor=\"{}\"}}", sensor + 1),
+            temperature.get().map(|temperature| *temperature as f32),
+        );
+    }
+
+    metrics.gauge("eoi_mppt_power", "Solar power per MPPT channel in W");
+    for (panel, info) in data.mppt_panel_info.iter() {
+        metrics.value(
+            &format!("{{mppt=\"{}\",channel=\"{}\"}}", panel.mppt, panel.channel),
+            info.get().map(|(power, _, _)| *power),
+        );
+    }
+
+    metrics.gauge("eoi_motor_rpm", "Motor RPM per VESC controller");
+    for (id, motor) in &data.motors {
+        metrics.value(
+            &format!("{{controller=\"{}\"}}", id),
+            motor.rpm.get().map(|rpm| *rpm as f32),
+        );
+    }
+    metrics.gauge(
+        "eoi_motor_current",
+        "Motor current per VESC controller in A",
+    );
+    for (id, motor) in &data.motors {
+        metrics.value(
+            &format!("{{controller=\"{}\"}}", id),
+            motor.current.get().copied(),
+        );
+    }
+    metrics.gauge(
+        "eoi_motor_temperature",
+        "Motor temperature per VESC controller in °C",
+    );
+    for (id, motor) in &data.motors {
+        metrics.value(
+            &format!("{{controller=\"{}\"}}", id),
+            motor.temperature.get().copied(),
+        );
+    }
+    metrics.gauge(
+        "eoi_motor_fet_temperature",
+        "FET temperature per VESC controller in °C",
+    );
+    for (id, motor) in &data.motors {
+        metrics.value(
+            &format!("{{controller=\"{}\"}}", id),
+            motor.fet_temperature.get().copied(),
+        );
+    }
+
+    metrics.gauge("eoi_throttle", "Throttle position in %");
+    metrics.value("", data.throttle_value.get().copied());
+
+    // Tells a silent board apart from one that was never seen
+    metrics.gauge(
+        "eoi_value_age_seconds",
+        "Seconds since the value was last received, also when it is stale",
+    );
+    metrics.value("{value=\"speed_kmh\"}", age(&data.speed_kmh));
+    metrics.value(
+        "{value=\"battery_state_of_charge\"}",
+        age(&data.battery_state_of_charge),
+    );
+    metrics.value("{value=\"battery_voltage\"}", age(&data.battery_voltage));
+    metrics.value("{value=\"throttle_value\"}", age(&data.throttle_value));
+    for (panel, info) in data.mppt_panel_info.iter() {
+        metrics.value(
+            &format!(
+                "{{value=\"mppt_power\",mppt=\"{}\",channel=\"{}\"}}",
+                panel.mppt, panel.channel
+            ),
+            age(info),
+        );
+    }
+
+    metrics.text
+}
+
+fn age<T>(value: &DisplayValue<T>) -> Option<f32> {
+    value
+        .get_stale()
+        .map(|(_, age)| age.as_millis() as f32 / 1000.0)
+}
+
+/// Prometheus text format writer
+#[derive(Default)]
+struct Metrics {
+    text: String,
+    name: &'static str,
+}
+
+impl Metrics {
+    fn gauge(&mut self, name: &'static str, help: &str) {
+        self.name = name;
+        // Writing to a String can not fail
+        let _ = writeln!(self.text, "# HELP {} {}", name, help);
+        let _ = writeln!(self.text, "# TYPE {} gauge", name);
+    }
+
+    fn value(&mut self, labels: &str, value: Option<f32>) {
+        if let Some(value) = value {
+            let _ = writeln!(self.text, "{}{} {}", self.name, labels, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use draw_display::PanelId;
+
+    #[test]
+    fn metrics_of_valid_values() {
+        let mut data = DisplayData::default();
+        data.battery_state_of_charge.update(80.5);
+        data.battery_cell_voltages[1].update(4.0);
+        data.update_battery_temperatures(0, &[24, 25]);
+        data.mppt_panel_info
+            .update(PanelId::new(2, 3), (100.0, 40.0, 2.5));
+
+        let metrics = prometheus_metrics(&data);
+        assert!(metrics.contains("# TYPE eoi_battery_state_of_charge gauge\n"));
+        assert!(metrics.contains("\neoi_battery_state_of_charge 80.5\n"));
+        assert!(metrics.contains("\neoi_battery_cell_voltage{cell=\"2\"} 4\n"));
+        assert!(metrics.contains("\neoi_battery_temperature{sensor=\"1\"} 24\n"));
+        assert!(metrics.contains("\neoi_battery_temperature{sensor=\"2\"} 25\n"));
+        assert!(!metrics.contains("{sensor=\"0\"}"));
+        assert!(metrics.contains("\neoi_mppt_power{mppt=\"2\",channel=\"3\"} 100\n"));
+        // Values never received are left out
+        assert!(!metrics.contains("\neoi_speed_kmh "));
+        assert!(!metrics.contains("{cell=\"1\"}"));
+        let age: f32 = metrics
+            .lines()
+            .find_map(|line| {
+                line.strip_prefix("eoi_value_age_seconds{value=\"battery_state_of_charge\"} ")
+            })
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((0.0..1.0).contains(&age));
+        assert!(!metrics.contains("{value=\"speed_kmh\"}"));
+    }
+
+    #[tokio::test]
+    async fn state_as_json() {
+        let mut data = DisplayData::default();
+        data.speed_kmh.update(21.0);
+        let status = Status::default();
+        status.update(&data);
+
+        let state = status.snapshot.lock().unwrap().state.clone();
+        assert!(state["speed_kmh"] == 21.0);
+        assert!(state["gnss_fix"].is_null());
+        assert!(state["battery_cell_voltages"].as_array().unwrap().len() == 14);
+    }
+
+    #[tokio::test]
+    async fn alarms_as_json() {
+        let mut data = DisplayData::default();
+        data.ingest_eoi_can_data(EoiCanData::Vesc(
+            eoi_can_decoder::VescData::from_node_id(
+                9,
+                eoi_can_decoder::VescMessage::Fault {
+                    fault: eoi_can_decoder::VescFault::OverTempFet,
+                },
+            )
+            .unwrap(),
+        ));
+        let status = Status::default();
+        status.update(&data);
+
+        let alarms = status.snapshot.lock().unwrap().alarms.clone();
+        assert!(alarms["active"] == json!(["motor-fault"]));
+        let event = &alarms["events"][0];
+        assert!(event["alarm"] == "motor-fault");
+        assert!(event["controller_id"] == 9);
+        assert!(event["fault"] == "FET hot");
+        assert!(event["raised"] == true);
+        assert!(event["time"].is_null());
+    }
+
+    #[tokio::test]
+    async fn publish_live_data() {
+        let status = Status::default();
+        let data = || EoiCanData::Gnss(eoi_can_decoder::GnssData::GnssSpeedAndHeading(20.5, 90.0));
+        // Without clients nothing is sent
+        status.publish(&data());
+
+        let mut receiver = status.live.subscribe();
+        status.publish(&data());
+        assert!(
+            receiver.recv().await.unwrap() == r#"{"Gnss":{"GnssSpeedAndHeading":[20.5,90.0]}}"#
+        );
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn closed_websocket_ends_the_stream() {
+        let status = Status::default();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(axum::serve(listener, status.clone().router()).into_future());
+
+        let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", address))
+            .await
+            .unwrap();
+        let status = &status;
+        let receivers = |count| async move {
+            while status.live.receiver_count() != count {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        };
+        receivers(1).await;
+        // Nothing is published, the server notices the close by reading the socket
+        client.close(None).await.unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(5), receivers(0))
+            .await
+            .expect("WebSocket still streaming after the client closed it");
+    }
+
+    #[tokio::test]
+    async fn publish_frames() {
+        let status = Status::default();
+        let id = embedded_can::StandardId::new(0x242).unwrap();
+        let frame = CanFrame::from_array(id.into(), [0x00, 0x00, 0xC0, 0x3F]);
+        status.publish_frame("can0", &frame);
+
+        let mut receiver = status.frames.subscribe();
+        status.publish_frame("can0", &frame);
+        let line = receiver.recv().await.unwrap();
+        assert!(line.ends_with(") can0 242#0000C03F"));
+        let (_, interface, parsed) = can_logger::parse_candump_line(&line).unwrap();
+        assert!(interface == "can0");
+        assert!(parsed == frame);
+        assert!(receiver.try_recv().is_err());
+    }
+}
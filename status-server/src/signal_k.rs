@@ -0,0 +1,175 @@
+//! Position, speed, course and battery as [Signal K](https://signalk.org/specification/) deltas,
+//! so chart plotters and apps on the support boat show the boat without knowing our messages.
+//! Signal K uses SI units: m/s, radians, Kelvin and ratios instead of percentages.
+
+use eoi_can_decoder::{EoiBattery, EoiCanData, GnssData};
+use serde_json::{Value, json};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Version of the specification the deltas follow
+pub const VERSION: &str = "1.7.0";
+const SOURCE_LABEL: &str = "eoi-can";
+/// The boat has a single battery
+const BATTERY: &str = "electrical.batteries.0";
+
+/// Turns decoded data into deltas, the latitude is kept until the longitude arrives since Signal K
+/// sends the position as one value
+#[derive(Debug, Default)]
+pub struct SignalK {
+    latitude: Option<f64>,
+}
+
+impl SignalK {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Delta of the values in `data`, `None` when nothing in it has a Signal K path
+    pub fn delta(&mut self, data: &EoiCanData, timestamp: SystemTime) -> Option<Value> {
+        let values = match data {
+            EoiCanData::Gnss(GnssData::GnssSpeedAndHeading(speed_kmh, heading)) => vec![
+                value("navigation.speedOverGround", json!(speed_kmh / 3.6)),
+                value(
+                    "navigation.courseOverGroundTrue",
+                    json!(heading.to_radians()),
+                ),
+            ],
+            EoiCanData::Gnss(GnssData::GnssLatitude(latitude)) => {
+                self.latitude = Some(*latitude);
+                return None;
+            }
+            EoiCanData::Gnss(GnssData::GnssLongitude(longitude)) => vec![value(
+                "navigation.position",
+                json!({ "latitude": self.latitude?, "longitude": longitude }),
+            )],
+            EoiCanData::EoiBattery(EoiBattery::CellVoltages13_14PackAndStack(data)) => {
+                vec![value(
+                    &format!("{BATTERY}.voltage"),
+                    json!(data.pack_voltage),
+                )]
+            }
+            EoiCanData::EoiBattery(EoiBattery::PackAndPerriCurrent(data)) => {
+                vec![value(
+                    &format!("{BATTERY}.current"),
+                    json!(data.pack_current),
+                )]
+            }
+            EoiCanData::EoiBattery(EoiBattery::SocErrorFlagsAndBalancing(data)) => vec![value(
+                &format!("{BATTERY}.capacity.stateOfCharge"),
+                json!(data.state_of_charge / 100.0),
+            )],
+            EoiCanData::EoiBattery(EoiBattery::TemperaturesAndStates(data)) => {
+                let hottest = data.temperatures.iter().max()?;
+                vec![value(
+                    &format!("{BATTERY}.temperature"),
+                    json!(f32::from(*hottest) + 273.15),
+                )]
+            }
+            _ => return None,
+        };
+        Some(json!({
+            "context": "vessels.self",
+            "updates": [{
+                "source": { "label": SOURCE_LABEL },
+                "timestamp": iso_8601(timestamp),
+                "values": values,
+            }],
+        }))
+    }
+}
+
+/// First message on a stream, before the deltas
+pub fn hello(timestamp: SystemTime) -> Value {
+    json!({
+        "name": SOURCE_LABEL,
+        "version": VERSION,
+        "self": "vessels.self",
+        "roles": ["master"],
+        "timestamp": iso_8601(timestamp),
+    })
+}
+
+fn value(path: &str, value: Value) -> Value {
+    json!({ "path": path, "value": value })
+}
+
+/// UTC time like `2025-06-14T12:30:05.250Z`
+fn iso_8601(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        seconds % 86400 / 3600,
+        seconds % 3600 / 60,
+        seconds % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Date of a day since the Unix epoch, from <https://howardhinnant.github.io/date_algorithms.html>
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert2::assert;
+    use std::time::Duration;
+
+    #[test]
+    fn navigation_deltas() {
+        let mut signal_k = SignalK::new();
+        // 2025-06-14 12:30:05.250 UTC
+        let timestamp = UNIX_EPOCH + Duration::from_millis(1_749_904_205_250);
+
+        let delta = signal_k
+            .delta(
+                &EoiCanData::Gnss(GnssData::GnssSpeedAndHeading(36.0, 180.0)),
+                timestamp,
+            )
+            .unwrap();
+        let update = &delta["updates"][0];
+        assert!(delta["context"] == "vessels.self");
+        assert!(update["timestamp"] == "2025-06-14T12:30:05.250Z");
+        assert!(update["values"][0]["path"] == "navigation.speedOverGround");
+        assert!(update["values"][0]["value"] == 10.0);
+        let course = update["values"][1]["value"].as_f64().unwrap();
+        assert!((course - std::f64::consts::PI).abs() < 1e-6);
+
+        // The position needs both halves
+        let longitude = EoiCanData::Gnss(GnssData::GnssLongitude(7.42));
+        assert!(signal_k.delta(&longitude, timestamp).is_none());
+        let latitude = EoiCanData::Gnss(GnssData::GnssLatitude(43.73));
+        assert!(signal_k.delta(&latitude, timestamp).is_none());
+        let delta = signal_k.delta(&longitude, timestamp).unwrap();
+        let position = &delta["updates"][0]["values"][0];
+        assert!(position["path"] == "navigation.position");
+        assert!(position["value"] == json!({ "latitude": 43.73, "longitude": 7.42 }));
+    }
+
+    #[test]
+    fn dates() {
+        assert!(civil_from_days(0) == (1970, 1, 1));
+        assert!(civil_from_days(11016) == (2000, 2, 29));
+        assert!(civil_from_days(20253) == (2025, 6, 14));
+    }
+}